@@ -0,0 +1,89 @@
+//! Records the ruffle commit the fuzzer is built against as `RUFFLE_COMMIT`, see `reverify`. Ruffle is either a git
+//! dependency, whose commit is in Cargo.lock, or a checkout in `ruffle/`, whose commit is asked of git. The version of
+//! the ruffle_core crate that is linked is recorded as `RUFFLE_CORE_VERSION`, from Cargo.lock
+use std::path::Path;
+use std::process::Command;
+
+/// Written when the commit or version can't be found
+const UNKNOWN_COMMIT: &str = "unknown";
+
+/// The entry of ruffle_core in Cargo.lock
+fn locked_package(lock: &str) -> Option<&str> {
+    lock.split("[[package]]")
+        .find(|package| package.contains("name = \"ruffle_core\""))
+}
+
+/// The commit of ruffle_core in Cargo.lock, if it is a git dependency, e.g.
+/// `source = "git+https://github.com/ruffle-rs/ruffle?branch=master#<commit>"`
+fn locked_commit(lock: &str) -> Option<String> {
+    let source = locked_package(lock)?
+        .lines()
+        .find_map(|line| line.strip_prefix("source = \"git+"))?;
+    let (_, commit) = source.trim_end_matches('"').rsplit_once('#')?;
+    Some(commit.to_string())
+}
+
+/// The version of ruffle_core in Cargo.lock, which is the crate that is linked whether it is a git or path dependency
+fn locked_version(lock: &str) -> Option<String> {
+    let version = locked_package(lock)?
+        .lines()
+        .find_map(|line| line.strip_prefix("version = \""))?;
+    Some(version.trim_end_matches('"').to_string())
+}
+
+/// The commit checked out in `dir`, with `-dirty` after it if there are uncommitted changes
+fn checkout_commit(dir: &Path) -> Option<String> {
+    // Otherwise git would answer for the fuzzer's own repo
+    if !dir.join(".git").exists() {
+        return None;
+    }
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    let commit = git(&["rev-parse", "HEAD"])?;
+    match git(&["status", "--porcelain", "--untracked-files=no"]) {
+        Some(status) if !status.is_empty() => Some(format!("{}-dirty", commit)),
+        _ => Some(commit),
+    }
+}
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let manifest_dir = Path::new(&manifest_dir);
+    let lock_path = manifest_dir.join("Cargo.lock");
+    let ruffle_dir = manifest_dir.join("ruffle");
+
+    let lock = std::fs::read_to_string(&lock_path).ok();
+
+    let commit = lock
+        .as_deref()
+        .and_then(locked_commit)
+        .or_else(|| checkout_commit(&ruffle_dir))
+        .unwrap_or_else(|| UNKNOWN_COMMIT.to_string());
+    println!("cargo:rustc-env=RUFFLE_COMMIT={}", commit);
+
+    let version = lock
+        .as_deref()
+        .and_then(locked_version)
+        .unwrap_or_else(|| UNKNOWN_COMMIT.to_string());
+    println!("cargo:rustc-env=RUFFLE_CORE_VERSION={}", version);
+
+    // A path that doesn't exist would rerun the script on every build
+    println!("cargo:rerun-if-changed=build.rs");
+    for path in [
+        lock_path,
+        ruffle_dir.join(".git/HEAD"),
+        ruffle_dir.join(".git/index"),
+    ] {
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
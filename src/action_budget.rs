@@ -0,0 +1,138 @@
+//! The action budget of a case. Ruffle can't be told to stop after a number of actions, only `RUN_TIMEOUT` and the
+//! frame budget bound how long it runs, and a single frame can run millions of actions. So the AVM1 actions in a
+//! generated case are counted before it runs, with the bodies of functions, `with` and `try` blocks, clip and button
+//! actions and sprites, and a case with more than `MAX_CASE_ACTIONS` isn't run. It is counted as a script limit case,
+//! apart from the cases that time out. Loops can still run more actions than the case has, only the timeouts bound
+//! those
+use crate::MAX_CASE_ACTIONS;
+use swf::avm1::read::Reader;
+use swf::avm1::types::Action;
+use swf::Tag;
+
+/// Add the action blocks of `tags` to `blocks`, including those of sprites
+fn action_blocks<'a>(tags: &[Tag<'a>], blocks: &mut Vec<&'a [u8]>) {
+    for tag in tags {
+        match tag {
+            Tag::DoAction(actions) => blocks.push(actions),
+            Tag::DoInitAction { action_data, .. } => blocks.push(action_data),
+            Tag::PlaceObject(place_object) => blocks.extend(
+                place_object
+                    .clip_actions
+                    .iter()
+                    .flatten()
+                    .map(|clip_action| clip_action.action_data),
+            ),
+            Tag::DefineButton(button) | Tag::DefineButton2(button) => blocks.extend(
+                button
+                    .actions
+                    .iter()
+                    .map(|button_action| button_action.action_data),
+            ),
+            Tag::DefineSprite(sprite) => action_blocks(&sprite.tags, blocks),
+            _ => {}
+        }
+    }
+}
+
+/// The actions in `blocks` and the blocks nested in them. Counting a block stops at its first action that can't be
+/// read, and counting stops altogether once past `limit`
+fn count_actions(mut blocks: Vec<&[u8]>, swf_version: u8, limit: usize) -> usize {
+    let mut count = 0;
+    while let Some(block) = blocks.pop() {
+        let mut reader = Reader::new(block, swf_version);
+        while count <= limit {
+            let action = match reader.read_action() {
+                Ok(Action::End) | Err(_) => break,
+                Ok(action) => action,
+            };
+            count += 1;
+            match action {
+                Action::DefineFunction(function) => blocks.push(function.actions),
+                Action::DefineFunction2(function) => blocks.push(function.actions),
+                Action::With(with) => blocks.push(with.actions),
+                Action::Try(try_block) => {
+                    blocks.push(try_block.try_body);
+                    blocks.extend(try_block.catch_body.map(|(_, body)| body));
+                    blocks.extend(try_block.finally_body);
+                }
+                _ => {}
+            }
+        }
+    }
+    count
+}
+
+/// The AVM1 actions in the swf, counting stops once past `limit`. Tags that can't be read aren't counted
+pub fn case_actions(swf: &[u8], limit: usize) -> usize {
+    let swf_buf = match swf::decompress_swf(swf) {
+        Ok(swf_buf) => swf_buf,
+        Err(_) => return 0,
+    };
+    let swf_version = swf_buf.header.version();
+    let mut reader = swf::read::Reader::new(&swf_buf.data, swf_version);
+    let mut blocks = Vec::new();
+    while !reader.get_ref().is_empty() {
+        let remaining = reader.get_ref().len();
+        match reader.read_tag() {
+            Ok(Tag::End) => break,
+            Ok(tag) => action_blocks(&[tag], &mut blocks),
+            // The rest can't be read if not even the tag header could be
+            Err(_) if reader.get_ref().len() == remaining => break,
+            Err(_) => {}
+        }
+    }
+    count_actions(blocks, swf_version, limit)
+}
+
+/// Does the case have more actions than `MAX_CASE_ACTIONS`
+pub fn over_action_budget(swf: &[u8]) -> bool {
+    case_actions(swf, MAX_CASE_ACTIONS) > MAX_CASE_ACTIONS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use swf::avm1::types::{DefineFunction, Try, With};
+    use swf::avm1::write::Writer;
+    use swf::{Header, SwfStr};
+
+    fn action_bytes(actions: &[Action]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut writer = Writer::new(&mut bytes, 8);
+        for action in actions {
+            writer.write_action(action).unwrap();
+        }
+        writer.write_action(&Action::End).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn count_nested_actions() {
+        let body = action_bytes(&[Action::Add, Action::Pop]);
+        let actions = action_bytes(&[
+            Action::DefineFunction(DefineFunction {
+                name: SwfStr::from_utf8_str("f"),
+                params: vec![],
+                actions: &body,
+            }),
+            Action::With(With { actions: &body }),
+            Action::Try(Try {
+                try_body: &body,
+                catch_body: None,
+                finally_body: Some(&body),
+            }),
+            Action::Stop,
+        ]);
+        let mut swf = Vec::new();
+        swf::write_swf(
+            &Header::default_with_swf_version(8),
+            &[Tag::DoAction(&actions), Tag::ShowFrame],
+            &mut swf,
+        )
+        .unwrap();
+
+        // 4 actions in the tag and 2 in each of the 4 bodies, each body's End isn't counted
+        assert_eq!(case_actions(&swf, usize::MAX), 12);
+        assert!(case_actions(&swf, 5) <= 6);
+    }
+}
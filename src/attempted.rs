@@ -0,0 +1,395 @@
+//! The hashes of every case generated so far, so that workers only run cases that are new. The set is split into
+//! shards, each behind its own mutex and picked by the hash, and checking and adding a hash is a single operation
+//! under one shard's lock, so workers only wait on each other when their hashes land in the same shard. A shard keeps
+//! its hashes in a `HashSet` until it would take more than its part of `ATTEMPTED_MEMORY_CAP`, then moves them into a
+//! bloom filter of that size, which never grows but may take a new case for one already run
+use crate::state_file::{read_u64, write_u64};
+use crate::ATTEMPTED_MEMORY_CAP;
+use md5::Digest;
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+
+/// How many shards the set is split into, well above the number of workers so that they rarely share one
+const ATTEMPTED_SHARDS: usize = 64;
+
+/// The estimated memory of each slot of a shard's `HashSet`, the 16 byte hash, its control byte and a share of the
+/// slots that are always kept empty
+const EXACT_BYTES_PER_SLOT: usize = 20;
+
+/// How many bits of a shard's bloom filter are set for each hash
+const BLOOM_HASHES: u32 = 7;
+
+/// What each shard is saved as by `AttemptedSet::write_to`
+const EXACT_TAG: u8 = 0;
+const BLOOM_TAG: u8 = 1;
+
+/// A fixed size bloom filter, indexed by double hashing the two halves of the md5 (which is already evenly distributed)
+struct BloomFilter {
+    words: Vec<u64>,
+    set_bits: usize,
+}
+
+impl BloomFilter {
+    fn new(bytes: usize) -> Self {
+        Self {
+            words: vec![0; (bytes / 8).max(1)],
+            set_bits: 0,
+        }
+    }
+
+    /// Set the hash's bits, returns true if any of them weren't set yet
+    fn insert(&mut self, digest: &[u8; 16]) -> bool {
+        let bit_count = self.words.len() as u64 * 64;
+        // The first byte picks the shard, so it's at the top of the second hash rather than in the first
+        let first = u64::from_le_bytes(digest[8..].try_into().unwrap());
+        let second = u64::from_be_bytes(digest[..8].try_into().unwrap()) | 1;
+        let mut new = false;
+        for i in 0..BLOOM_HASHES as u64 {
+            let bit = first.wrapping_add(i.wrapping_mul(second)) % bit_count;
+            let word = &mut self.words[(bit / 64) as usize];
+            let mask = 1 << (bit % 64);
+            if *word & mask == 0 {
+                *word |= mask;
+                self.set_bits += 1;
+                new = true;
+            }
+        }
+        new
+    }
+
+    /// The chance that a hash that was never inserted has all its bits set already
+    fn false_positive_rate(&self) -> f64 {
+        (self.set_bits as f64 / (self.words.len() * 64) as f64).powi(BLOOM_HASHES as i32)
+    }
+}
+
+enum ShardHashes {
+    Exact(HashSet<[u8; 16]>),
+    Bloom(BloomFilter),
+}
+
+struct Shard {
+    hashes: ShardHashes,
+    /// How many hashes were new when inserted
+    len: usize,
+}
+
+impl Shard {
+    fn insert(&mut self, digest: Digest, memory_cap: usize) -> bool {
+        if let ShardHashes::Exact(set) = &self.hashes {
+            // Switch before the set doubles its table past the cap, rather than after
+            if set.len() == set.capacity() && set.capacity() * 2 * EXACT_BYTES_PER_SLOT > memory_cap
+            {
+                let mut bloom = BloomFilter::new(memory_cap);
+                for hash in set {
+                    bloom.insert(hash);
+                }
+                self.hashes = ShardHashes::Bloom(bloom);
+            }
+        }
+        let new = match &mut self.hashes {
+            ShardHashes::Exact(set) => set.insert(digest.0),
+            ShardHashes::Bloom(bloom) => bloom.insert(&digest.0),
+        };
+        if new {
+            self.len += 1;
+        }
+        new
+    }
+
+    fn memory_bytes(&self) -> usize {
+        match &self.hashes {
+            ShardHashes::Exact(set) => set.capacity() * EXACT_BYTES_PER_SLOT,
+            ShardHashes::Bloom(bloom) => bloom.words.len() * 8,
+        }
+    }
+}
+
+pub struct AttemptedSet {
+    shards: Vec<Mutex<Shard>>,
+    /// The memory each shard may take
+    shard_memory_cap: usize,
+}
+
+impl Default for AttemptedSet {
+    fn default() -> Self {
+        Self::with_memory_cap(ATTEMPTED_MEMORY_CAP)
+    }
+}
+
+impl AttemptedSet {
+    /// A set that switches to bloom filters once it would take more than roughly `memory_cap` bytes
+    pub fn with_memory_cap(memory_cap: usize) -> Self {
+        Self {
+            shards: (0..ATTEMPTED_SHARDS)
+                .map(|_| {
+                    Mutex::new(Shard {
+                        hashes: ShardHashes::Exact(HashSet::new()),
+                        len: 0,
+                    })
+                })
+                .collect(),
+            shard_memory_cap: memory_cap / ATTEMPTED_SHARDS,
+        }
+    }
+
+    /// Add a case's hash, returns true if it wasn't already in the set. Once its shard is a bloom filter this may
+    /// return false for a hash that wasn't, see `false_positive_rate`
+    pub fn insert(&self, digest: Digest) -> bool {
+        // md5 is evenly distributed, so the first byte is enough to pick a shard
+        self.shards[digest.0[0] as usize % ATTEMPTED_SHARDS]
+            .lock()
+            .unwrap()
+            .insert(digest, self.shard_memory_cap)
+    }
+
+    /// Every hash in shards that still keep them, in no particular order. The hashes in bloom filters can't be listed
+    pub fn digests(&self) -> Vec<Digest> {
+        self.shards
+            .iter()
+            .flat_map(|shard| match &shard.lock().unwrap().hashes {
+                ShardHashes::Exact(set) => set.iter().copied().map(Digest).collect(),
+                ShardHashes::Bloom(_) => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// How many hashes were new when inserted
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The estimated memory taken by the hashes
+    pub fn memory_bytes(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().memory_bytes())
+            .sum()
+    }
+
+    /// The chance that a new case is taken for one already run, None while every shard still keeps its hashes
+    pub fn false_positive_rate(&self) -> Option<f64> {
+        let rates = self
+            .shards
+            .iter()
+            .map(|shard| match &shard.lock().unwrap().hashes {
+                ShardHashes::Exact(_) => None,
+                ShardHashes::Bloom(bloom) => Some(bloom.false_positive_rate()),
+            })
+            .collect::<Vec<_>>();
+        if rates.iter().all(Option::is_none) {
+            return None;
+        }
+        // Hashes are spread evenly over the shards, so this is the average
+        Some(rates.iter().flatten().sum::<f64>() / ATTEMPTED_SHARDS as f64)
+    }
+
+    /// Write the hashes of every shard, or its bloom filter, see `state_file`
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        write_u64(out, ATTEMPTED_SHARDS as u64)?;
+        for shard in &self.shards {
+            let shard = shard.lock().unwrap();
+            write_u64(out, shard.len as u64)?;
+            match &shard.hashes {
+                ShardHashes::Exact(set) => {
+                    out.write_all(&[EXACT_TAG])?;
+                    write_u64(out, set.len() as u64)?;
+                    for hash in set {
+                        out.write_all(hash)?;
+                    }
+                }
+                ShardHashes::Bloom(bloom) => {
+                    out.write_all(&[BLOOM_TAG])?;
+                    write_u64(out, bloom.set_bits as u64)?;
+                    write_u64(out, bloom.words.len() as u64)?;
+                    for word in &bloom.words {
+                        write_u64(out, *word)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a set written by `write_to`. Saved hashes are added to shards capped at `memory_cap` as usual, while saved
+    /// bloom filters keep the size they were saved with
+    pub fn read_from(input: &mut impl Read, memory_cap: usize) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let set = Self::with_memory_cap(memory_cap);
+        let shard_count = read_u64(input)?;
+        if shard_count != ATTEMPTED_SHARDS as u64 {
+            return Err(invalid(format!(
+                "{} shards saved, not {}",
+                shard_count, ATTEMPTED_SHARDS
+            )));
+        }
+        for shard in &set.shards {
+            let mut shard = shard.lock().unwrap();
+            let len = read_u64(input)? as usize;
+            let mut tag = [0];
+            input.read_exact(&mut tag)?;
+            match tag[0] {
+                EXACT_TAG => {
+                    for _ in 0..read_u64(input)? {
+                        let mut hash = [0; 16];
+                        input.read_exact(&mut hash)?;
+                        shard.insert(Digest(hash), set.shard_memory_cap);
+                    }
+                }
+                BLOOM_TAG => {
+                    let set_bits = read_u64(input)? as usize;
+                    let words = (0..read_u64(input)?)
+                        .map(|_| read_u64(input))
+                        .collect::<io::Result<_>>()?;
+                    shard.hashes = ShardHashes::Bloom(BloomFilter { words, set_bits });
+                }
+                tag => return Err(invalid(format!("Unknown shard kind {}", tag))),
+            }
+            shard.len = len;
+        }
+        Ok(set)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "cases": self.len(),
+            "memory_bytes": self.memory_bytes(),
+            "false_positive_rate": self.false_positive_rate(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::RwLock;
+    use std::time::{Duration, Instant};
+
+    const INSERTS_PER_THREAD: usize = 500;
+
+    /// Every thread inserts the same hashes, each starting at a different one, so they contend on every hash.
+    /// Returns how long it took and how many inserts reported a new hash
+    fn contend(threads: usize, insert: impl Fn(Digest) -> bool + Sync) -> (Duration, usize) {
+        let digests = (0..INSERTS_PER_THREAD)
+            .map(|i| md5::compute(i.to_le_bytes()))
+            .collect::<Vec<_>>();
+        let inserted = AtomicUsize::new(0);
+        let start = Instant::now();
+        std::thread::scope(|scope| {
+            for thread in 0..threads {
+                let (insert, digests, inserted) = (&insert, &digests, &inserted);
+                scope.spawn(move || {
+                    let offset = thread * INSERTS_PER_THREAD / threads;
+                    for i in 0..INSERTS_PER_THREAD {
+                        if insert(digests[(i + offset) % INSERTS_PER_THREAD]) {
+                            inserted.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                });
+            }
+        });
+        (start.elapsed(), inserted.into_inner())
+    }
+
+    #[test]
+    fn insert_under_contention() {
+        for threads in [8, 32] {
+            let set = AttemptedSet::default();
+            let (_, inserted) = contend(threads, |digest| set.insert(digest));
+            assert_eq!(inserted, set.len());
+            assert_eq!(inserted, INSERTS_PER_THREAD);
+        }
+    }
+
+    /// Compares the time of inserting under contention with the protocol this replaced, run it with
+    /// `cargo test --release -- --ignored --nocapture bench_insert_under_contention`
+    #[test]
+    #[ignore = "benchmark, only reports timings"]
+    fn bench_insert_under_contention() {
+        for threads in [8, 32] {
+            let set = AttemptedSet::default();
+            let (sharded, _) = contend(threads, |digest| set.insert(digest));
+
+            // A read lock to check then a write lock to add. It can add a hash twice when two threads check it before
+            // either adds it, so only the time is compared
+            let attempted = RwLock::new(Vec::new());
+            let (rw_lock, _) = contend(threads, |digest| {
+                if !attempted.read().unwrap().contains(&digest) {
+                    attempted.write().unwrap().push(digest);
+                    true
+                } else {
+                    false
+                }
+            });
+            println!(
+                "{} threads: sharded {:?}, read then write lock {:?}",
+                threads, sharded, rw_lock
+            );
+        }
+    }
+
+    /// Insert `cases` hashes into a set capped at `memory_cap`, checking that it stays under the cap and how many new
+    /// hashes are taken for ones already inserted
+    fn fill_past_memory_cap(cases: usize, memory_cap: usize) {
+        // Evenly distributed like md5, but much quicker to make
+        let digest = |i: usize| {
+            let mix = |mut x: u64| {
+                x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+                x ^ (x >> 31)
+            };
+            let mut bytes = [0; 16];
+            bytes[..8].copy_from_slice(&mix(i as u64 * 2).to_le_bytes());
+            bytes[8..].copy_from_slice(&mix(i as u64 * 2 + 1).to_le_bytes());
+            Digest(bytes)
+        };
+
+        let set = AttemptedSet::with_memory_cap(memory_cap);
+        for i in 0..1000 {
+            assert!(set.insert(digest(i)));
+        }
+        assert_eq!(set.false_positive_rate(), None);
+        assert_eq!(set.digests().len(), 1000);
+
+        let mut inserted = 1000;
+        for i in 1000..cases {
+            if set.insert(digest(i)) {
+                inserted += 1;
+            }
+        }
+        assert!(set.memory_bytes() <= memory_cap, "{}", set.memory_bytes());
+        assert_eq!(set.len(), inserted);
+        let false_positive_rate = set.false_positive_rate().unwrap();
+        assert!(false_positive_rate < 0.01, "{}", false_positive_rate);
+        assert!(
+            (cases - inserted) as f64 <= cases as f64 * false_positive_rate * 2.0,
+            "{} taken as run",
+            cases - inserted
+        );
+        // A bloom filter never forgets
+        assert!((0..cases).step_by(997).all(|i| !set.insert(digest(i))));
+    }
+
+    #[test]
+    fn stays_under_memory_cap() {
+        // Well past the point the shards switch to bloom filters, with a few more bits per hash than the full run
+        fill_past_memory_cap(200_000, 512 * 1024);
+    }
+
+    /// The full size run, too slow for every `cargo test`, run it with
+    /// `cargo test --release -- --ignored stays_under_memory_cap_full`
+    #[test]
+    #[ignore = "slow, 10M inserts"]
+    fn stays_under_memory_cap_full() {
+        fill_past_memory_cap(10_000_000, 16 * 1024 * 1024);
+    }
+}
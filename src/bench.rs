@@ -0,0 +1,150 @@
+//! Throughput benchmark, the cases from a fixed list of seeds are run for a fixed time and the median durations are
+//! compared against a saved baseline so that a slowdown in either player (or the harness) fails the run
+use crate::config::Config;
+use crate::flash_projector_runner::open_flash_cmd;
+use crate::ruffle_runner::{open_ruffle, RuffleSettings};
+use crate::sentinel::RunUntil;
+use crate::stats::{CaseTimings, DurationHistogram};
+use crate::swf_generator::SwfGenerator;
+use crate::{setup_run_dir, MyError};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+/// Run the benchmark with `flash-fuzz bench`
+pub const BENCH_COMMAND: &str = "bench";
+
+/// Pass this to `bench` to save the results as the new baseline instead of comparing against the old one
+pub const SAVE_BASELINE_ARG: &str = "--save-baseline";
+
+/// The seeds that cases are generated from, each run cycles through these in order until `BENCH_DURATION` is up
+const BENCH_SEEDS: &[u64] = &[
+    0xbe7c_0001,
+    0xbe7c_0002,
+    0xbe7c_0003,
+    0xbe7c_0004,
+    0xbe7c_0005,
+    0xbe7c_0006,
+    0xbe7c_0007,
+    0xbe7c_0008,
+];
+
+/// How many cases are generated from each seed before moving on to the next
+const CASES_PER_SEED: usize = 4;
+
+/// How long to keep running cases for
+const BENCH_DURATION: Duration = Duration::from_secs(60);
+
+/// How much slower (0.1 = 10%) a median can be than the baseline before the run fails
+const REGRESSION_THRESHOLD: f64 = 0.1;
+
+/// Compare a median against the baseline one, logging it, returns true if it regressed
+fn regressed(name: &str, current: &DurationHistogram, baseline: &DurationHistogram) -> bool {
+    let (current, baseline) = (current.median(), baseline.median());
+    let change = if baseline.is_zero() {
+        0.0
+    } else {
+        current.as_secs_f64() / baseline.as_secs_f64() - 1.0
+    };
+    let regressed = change > REGRESSION_THRESHOLD;
+    tracing::info!(
+        "{}: median = {:?}, baseline = {:?} ({:+.1}%){}",
+        name,
+        current,
+        baseline,
+        change * 100.0,
+        if regressed { " REGRESSED" } else { "" }
+    );
+    regressed
+}
+
+pub fn run(config: &Config, save_baseline: bool) -> Result<(), Box<dyn Error>> {
+    let paths = &config.paths;
+    setup_run_dir(paths, 1)?;
+    let temp_dir = paths.worker_temp(0);
+
+    let timings = CaseTimings::default();
+    let mut swf_content = Vec::with_capacity(1024);
+    let bench_start = Instant::now();
+
+    'bench: loop {
+        for seed in BENCH_SEEDS {
+            let mut generator = SwfGenerator::with_seed(*seed);
+            for _ in 0..CASES_PER_SEED {
+                if bench_start.elapsed() > BENCH_DURATION {
+                    break 'bench;
+                }
+                let start = Instant::now();
+                swf_content.clear();
+                generator.reset();
+                generator.next_swf(&mut swf_content)?;
+
+                let input = generator.input_script();
+                let settings = RuffleSettings::default().with_max_frames(generator.frame_budget());
+                let (_, ruffle_dur) = futures::executor::block_on(open_ruffle(
+                    &swf_content,
+                    &settings,
+                    input,
+                    RunUntil::Sentinel,
+                    None,
+                ))?;
+                let flash_dur = match futures::executor::block_on(open_flash_cmd(
+                    &swf_content,
+                    &config.flash,
+                    &temp_dir,
+                    input,
+                    None,
+                    RunUntil::Sentinel,
+                    None,
+                )) {
+                    Ok((_, flash_dur, _)) => flash_dur,
+                    // A crash finishes early, so would make flash look faster than it is
+                    Err(MyError::FlashCrash) => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                timings.total.record(start.elapsed());
+                timings.ruffle.record(ruffle_dur);
+                timings.flash.record(flash_dur);
+            }
+        }
+    }
+
+    tracing::info!(
+        "Ran {} cases in {:?}",
+        timings.total.count(),
+        BENCH_DURATION
+    );
+    let results = serde_json::to_string_pretty(&timings.to_json())?;
+    std::fs::write(&paths.bench_file, &results)?;
+    paths.remove_temp();
+
+    if save_baseline {
+        std::fs::write(&paths.bench_baseline_file, &results)?;
+        tracing::info!("Saved baseline to {}", paths.bench_baseline_file.display());
+        return Ok(());
+    }
+
+    let baseline = match std::fs::read_to_string(&paths.bench_baseline_file) {
+        Ok(baseline) => baseline,
+        Err(_) => {
+            tracing::error!(
+                "No baseline at {}, run with {} to create one",
+                paths.bench_baseline_file.display(),
+                SAVE_BASELINE_ARG
+            );
+            return Err(MyError::InvalidBenchBaseline.into());
+        }
+    };
+    let baseline = CaseTimings::from_json(&serde_json::from_str(&baseline)?)
+        .ok_or(MyError::InvalidBenchBaseline)?;
+
+    // Check every part, rather than stopping at the first, so the log shows which one got slower
+    let regressions = [
+        regressed("total", &timings.total, &baseline.total),
+        regressed("ruffle", &timings.ruffle, &baseline.ruffle),
+        regressed("flash", &timings.flash, &baseline.flash),
+    ];
+    if regressions.contains(&true) {
+        return Err(MyError::BenchRegression.into());
+    }
+    Ok(())
+}
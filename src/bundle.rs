@@ -0,0 +1,231 @@
+//! Failure bundles, `flash-fuzz bundle <archive> <failure dir>...` packs failure dirs into a single `.tar.gz` along
+//! with a disassembly of each swf, a diff of the outputs and a README on reproducing them, so a finding can be
+//! handed to a Ruffle developer as one file
+use crate::normalize::normalize;
+use crate::selftest::{line_diff, listing};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Bundle failures with `flash-fuzz bundle <archive> <failure dir>...`
+pub const BUNDLE_COMMAND: &str = "bundle";
+
+/// The extension bundles are written with, added to the archive path if it is missing
+const BUNDLE_EXTENSION: &str = ".tar.gz";
+
+/// Files written by `failure_writer` that a failure dir must have
+const SWF_FILE: &str = "out.swf";
+const RUFFLE_FILE: &str = "ruffle.txt";
+const FLASH_FILE: &str = "flash.txt";
+const ENV_FILE: &str = "env.txt";
+const INPUT_FILE: &str = "input.txt";
+const META_FILE: &str = "meta.json";
+
+/// Written by `failure_writer` with the constructed values annotated, or next to a failure's files in the bundle for
+/// failures saved before it was
+const LISTING_FILE: &str = "actions.txt";
+
+/// Written next to a failure's files in the bundle
+const DIFF_FILE: &str = "diff.txt";
+
+/// A failure added to the bundle, for the README
+struct BundledFailure {
+    name: String,
+    env: String,
+    has_input: bool,
+    /// Files in the failure dir other than the ones `failure_writer` writes, such as a minimized swf
+    extra_files: Vec<String>,
+}
+
+/// Pack `failure_dirs` into the archive at `archive_path`
+pub fn run(archive_path: &Path, failure_dirs: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    if failure_dirs.is_empty() {
+        return Err(format!(
+            "Usage: flash-fuzz {} <archive> <failure dir>...",
+            BUNDLE_COMMAND
+        )
+        .into());
+    }
+    let archive_path = if archive_path.to_string_lossy().ends_with(BUNDLE_EXTENSION) {
+        archive_path.to_path_buf()
+    } else {
+        PathBuf::from(format!("{}{}", archive_path.display(), BUNDLE_EXTENSION))
+    };
+    // Everything is under a dir named after the archive, so extracting it doesn't spill files into the cwd
+    let file_name = archive_path
+        .file_name()
+        .ok_or("The archive path has no file name")?
+        .to_string_lossy();
+    let root = Path::new(file_name.trim_end_matches(BUNDLE_EXTENSION));
+
+    let encoder = GzEncoder::new(File::create(&archive_path)?, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+    let mut bundled = Vec::new();
+    for dir in failure_dirs {
+        bundled.push(add_failure(&mut archive, root, dir)?);
+        tracing::info!("Added {}", dir.display());
+    }
+    append(
+        &mut archive,
+        &root.join("README.md"),
+        readme(&bundled).as_bytes(),
+    )?;
+    archive.into_inner()?.finish()?;
+
+    tracing::info!(
+        "Wrote {} failures to {}",
+        bundled.len(),
+        archive_path.display()
+    );
+    Ok(())
+}
+
+/// Add a failure dir under `root/<name>`, with the listing of its swf and the diff of its outputs
+fn add_failure(
+    archive: &mut tar::Builder<GzEncoder<File>>,
+    root: &Path,
+    dir: &Path,
+) -> Result<BundledFailure, Box<dyn Error>> {
+    let name = dir
+        .file_name()
+        .ok_or_else(|| format!("{} has no name", dir.display()))?
+        .to_string_lossy()
+        .into_owned();
+    let read = |file: &str| {
+        std::fs::read(dir.join(file))
+            .map_err(|e| format!("{} isn't a failure dir, {}: {}", dir.display(), file, e))
+    };
+    let swf = read(SWF_FILE)?;
+    let ruffle = String::from_utf8_lossy(&read(RUFFLE_FILE)?).into_owned();
+    let flash = String::from_utf8_lossy(&read(FLASH_FILE)?).into_owned();
+    let env = String::from_utf8_lossy(&read(ENV_FILE)?).into_owned();
+
+    let bundle_dir = root.join(&name);
+    let mut extra_files = Vec::new();
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        let file_name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        if path.is_dir() {
+            archive.append_dir_all(bundle_dir.join(&file_name), &path)?;
+        } else if file_name == LISTING_FILE {
+            // Added below, so the archive doesn't have it twice
+            continue;
+        } else {
+            append(
+                archive,
+                &bundle_dir.join(&file_name),
+                &std::fs::read(&path)?,
+            )?;
+        }
+        if ![
+            SWF_FILE,
+            RUFFLE_FILE,
+            FLASH_FILE,
+            ENV_FILE,
+            INPUT_FILE,
+            META_FILE,
+        ]
+        .contains(&file_name.as_str())
+        {
+            extra_files.push(file_name);
+        }
+    }
+
+    // A swf that can't be disassembled is still worth sending, the error is listed instead
+    let listing = match std::fs::read_to_string(dir.join(LISTING_FILE)) {
+        Ok(listing) => listing,
+        Err(_) => {
+            listing(&swf).unwrap_or_else(|e| format!("Couldn't disassemble {}: {}\n", SWF_FILE, e))
+        }
+    };
+    append(archive, &bundle_dir.join(LISTING_FILE), listing.as_bytes())?;
+    let diff = format!(
+        "Normalized output, - is ruffle and + is flash\n{}",
+        line_diff(&normalize(&ruffle), &normalize(&flash), None)
+    );
+    append(archive, &bundle_dir.join(DIFF_FILE), diff.as_bytes())?;
+
+    Ok(BundledFailure {
+        name,
+        has_input: dir.join(INPUT_FILE).exists(),
+        env,
+        extra_files,
+    })
+}
+
+/// Add a file to the archive. The mode and time are fixed so bundling the same failures gives the same archive
+fn append(
+    archive: &mut tar::Builder<GzEncoder<File>>,
+    path: &Path,
+    contents: &[u8],
+) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    archive.append_data(&mut header, path, contents)
+}
+
+/// The README of the bundle, how to reproduce the failures and what each one's files are
+fn readme(failures: &[BundledFailure]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# Ruffle mismatches\n\n\
+         Each dir is a case where Ruffle's trace output differed from Flash Player's, found by ruffle-fuzz.\n\n\
+         ## Files\n\
+         - `{swf}`: the case, as run by both players\n\
+         - `{ruffle}`, `{flash}`: what each player traced\n\
+         - `{diff}`: the lines that differ once both outputs are normalized the way the fuzzer compares them\n\
+         - `{listing}`: the tags of `{swf}`, with its actions disassembled\n\
+         - `{env}`: the SWF version, the Flash Player and Ruffle settings it ran with and the ops that differed\n\
+         - `{input}`: key and mouse events sent between frames, only for input cases\n\n\
+         ## Reproducing\n\
+         Run `{swf}` in Ruffle (`ruffle {swf}`) and in the Flash Player debug projector with tracing enabled, and \
+         compare the traces with `{ruffle}` and `{flash}`. Each case traces a marker before every op it tests, so \
+         the ops listed in `{env}` can be found in both outputs. Cases with `{input}` need those events sent at \
+         the listed frames.\n\n\
+         With ruffle-fuzz checked out, `cargo run --release -- corpus <dir>` runs every swf in a dir through both \
+         players and reports which still disagree.\n",
+        swf = SWF_FILE,
+        ruffle = RUFFLE_FILE,
+        flash = FLASH_FILE,
+        diff = DIFF_FILE,
+        listing = LISTING_FILE,
+        env = ENV_FILE,
+        input = INPUT_FILE,
+    );
+    let _ = writeln!(out, "## Failures");
+    for failure in failures {
+        let _ = writeln!(out, "\n### {}", failure.name);
+        for line in failure.env.lines() {
+            let _ = writeln!(out, "- {}", line);
+        }
+        if failure.has_input {
+            let _ = writeln!(out, "- Has input events, see `{}`", INPUT_FILE);
+        }
+        if !failure.extra_files.is_empty() {
+            let _ = writeln!(
+                out,
+                "- Also included: {}",
+                failure
+                    .extra_files
+                    .iter()
+                    .map(|file| format!("`{}`", file))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+    out
+}
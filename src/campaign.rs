@@ -0,0 +1,534 @@
+//! Bounded fuzz campaigns for CI, `flash-fuzz campaign <cases> [<minutes>]` fuzzes until the budget is spent then
+//! writes `RunPaths::campaign_summary` and exits with one of the `EXIT_*` codes. A mismatch is new unless its
+//! signature is in `SUPPRESSIONS_FILE` or was found by a previous campaign, see `RunPaths::signatures`
+use crate::checkpoint;
+use crate::config::Config;
+use crate::diff_class::DiffClass;
+use crate::flash_player_info::{identify_flash_player, smoke_test, FlashPlayerInfo};
+use crate::fuzz_session::SharedFuzzState;
+use crate::generator_filter::GeneratorFilter;
+use crate::op_marker::PREAMBLE;
+use crate::reverify::{reverify_if_moved, RUFFLE_COMMIT, RUFFLE_CORE_VERSION};
+use crate::state_file;
+use crate::{
+    run_workers, setup_run_dir, MyError, CAMPAIGN_MAX_DURATION, GENERATOR_FILTER_FILE,
+    SUPPRESSIONS_FILE,
+};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Run a campaign with `flash-fuzz campaign <cases> [<minutes>] [--resume <checkpoint>]`
+pub const CAMPAIGN_COMMAND: &str = "campaign";
+
+/// The budget was spent without finding any new mismatches
+pub const EXIT_CLEAN: i32 = 0;
+
+/// At least one mismatch wasn't suppressed or found by a previous campaign
+pub const EXIT_NEW_MISMATCHES: i32 = 2;
+
+/// The campaign couldn't run, e.g. flash is missing or failed the smoke test
+pub const EXIT_INFRASTRUCTURE: i32 = 3;
+
+/// How long a campaign runs for, and how many cases it runs
+#[derive(Debug, Clone, Copy)]
+pub struct CampaignBudget {
+    pub max_cases: usize,
+    pub max_duration: Duration,
+}
+
+impl CampaignBudget {
+    /// Parse the arguments after `campaign`, the duration is in minutes and defaults to `CAMPAIGN_MAX_DURATION`
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let usage = || format!("Usage: flash-fuzz {} <cases> [<minutes>]", CAMPAIGN_COMMAND);
+        let max_cases = args
+            .first()
+            .and_then(|cases| cases.parse().ok())
+            .ok_or_else(usage)?;
+        let max_duration = match args.get(1) {
+            Some(minutes) => Duration::from_secs(minutes.parse::<u64>().map_err(|_| usage())? * 60),
+            None => CAMPAIGN_MAX_DURATION,
+        };
+        Ok(Self {
+            max_cases,
+            max_duration,
+        })
+    }
+}
+
+/// A budget being spent, workers stop starting cases once it is
+#[derive(Debug)]
+pub struct BudgetTracker {
+    budget: CampaignBudget,
+    start: Instant,
+}
+
+impl BudgetTracker {
+    pub fn start(budget: CampaignBudget) -> Self {
+        Self {
+            budget,
+            start: Instant::now(),
+        }
+    }
+
+    /// Carry on spending a budget that a checkpointed campaign had already spent `elapsed` of
+    pub fn resume(budget: CampaignBudget, elapsed: Duration) -> Self {
+        Self {
+            budget,
+            start: Instant::now()
+                .checked_sub(elapsed)
+                .unwrap_or_else(Instant::now),
+        }
+    }
+
+    pub fn budget(&self) -> CampaignBudget {
+        self.budget
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Has the budget been spent, workers finish the case they are running so a few more cases than
+    /// `max_cases` can be run
+    pub fn spent(&self, cases: usize) -> bool {
+        cases >= self.budget.max_cases || self.start.elapsed() >= self.budget.max_duration
+    }
+}
+
+/// The signature of a mismatch, the description of the first operation whose output differs without its index,
+/// arguments or numbers. The same bug found with different values should have the same signature
+pub fn mismatch_signature(differing_ops: &[String]) -> String {
+    let op = match differing_ops.first() {
+        Some(op) => op,
+        None => return PREAMBLE.to_string(),
+    };
+    // Markers are `<index>:<description>`, the preamble has no index
+    let description = match op.split_once(':') {
+        Some((index, description)) if index.parse::<usize>().is_ok() => description,
+        _ => op,
+    };
+
+    let mut signature = String::with_capacity(description.len());
+    let mut depth = 0usize;
+    for c in description.chars() {
+        match c {
+            '(' => {
+                if depth == 0 {
+                    signature.push(c);
+                }
+                depth += 1;
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    signature.push(c);
+                }
+            }
+            _ if depth > 0 => {}
+            '0'..='9' => {
+                if !signature.ends_with('N') {
+                    signature.push('N');
+                }
+            }
+            _ => signature.push(c),
+        }
+    }
+    signature
+}
+
+/// The signature of a mismatch followed by how its output differs, e.g. `Math.sqrt() [numeric-noise]`, so that
+/// mismatches in how numbers are formatted are grouped apart from the same op's other mismatches. Textual mismatches
+/// are left as the plain signature
+pub fn classified_signature(differing_ops: &[String], diff_class: DiffClass) -> String {
+    let signature = mismatch_signature(differing_ops);
+    match diff_class {
+        DiffClass::Textual => signature,
+        _ => format!("{} [{}]", signature, diff_class),
+    }
+}
+
+/// Read a list of signatures, one per line, blank lines and lines starting with `#` are skipped.
+/// A missing file is an empty list
+pub fn read_signature_list(path: &Path) -> std::io::Result<BTreeSet<String>> {
+    let list = match std::fs::read_to_string(path) {
+        Ok(list) => list,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeSet::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(list
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Is the signature suppressed, an entry ending in `*` suppresses every signature starting with the rest of it
+fn is_suppressed(signature: &str, suppressions: &BTreeSet<String>) -> bool {
+    suppressions
+        .iter()
+        .any(|suppression| match suppression.strip_suffix('*') {
+            Some(prefix) => signature.starts_with(prefix),
+            None => signature == suppression,
+        })
+}
+
+/// Whether a signature counts against the campaign
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+    New,
+    /// Found by a previous campaign
+    Known,
+    Suppressed,
+}
+
+impl SignatureStatus {
+    pub fn classify(
+        signature: &str,
+        suppressions: &BTreeSet<String>,
+        known: &BTreeSet<String>,
+    ) -> Self {
+        if is_suppressed(signature, suppressions) {
+            SignatureStatus::Suppressed
+        } else if known.contains(signature) {
+            SignatureStatus::Known
+        } else {
+            SignatureStatus::New
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SignatureStatus::New => "new",
+            SignatureStatus::Known => "known",
+            SignatureStatus::Suppressed => "suppressed",
+        }
+    }
+}
+
+/// The mismatches found with one signature
+#[derive(Debug, Clone, Default)]
+pub struct SignatureCount {
+    pub mismatches: usize,
+    /// The first case found with this signature, in `RunPaths::failures`
+    pub first_case: String,
+}
+
+/// What a campaign found, written to `RunPaths::campaign_summary`
+#[derive(Debug, Default)]
+pub struct CampaignSummary {
+    pub cases: usize,
+    pub mismatches: usize,
+    pub signatures: BTreeMap<String, (SignatureCount, SignatureStatus)>,
+    pub flash_crashes: usize,
+    /// Cases that timed out or stopped early in ruffle
+    pub ruffle_incomplete: usize,
+    /// Cases that weren't run for having more than `MAX_CASE_ACTIONS`
+    pub ruffle_script_limit: usize,
+    /// Cases that timed out or stopped early in flash
+    pub flash_incomplete: usize,
+    pub capture_errors: usize,
+    pub duration: Duration,
+    /// Why the campaign couldn't run, if it couldn't
+    pub infrastructure_error: Option<String>,
+}
+
+impl CampaignSummary {
+    pub fn from_state(
+        state: &SharedFuzzState,
+        suppressions: &BTreeSet<String>,
+        known: &BTreeSet<String>,
+    ) -> Self {
+        let signatures = state
+            .signatures
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(signature, count)| {
+                let status = SignatureStatus::classify(signature, suppressions, known);
+                (signature.clone(), (count.clone(), status))
+            })
+            .collect();
+        Self {
+            cases: state.cases_run.load(Ordering::SeqCst),
+            mismatches: state.mismatches.load(Ordering::SeqCst),
+            signatures,
+            flash_crashes: state.flash_crashes.load(Ordering::SeqCst),
+            ruffle_incomplete: state.ruffle_incomplete.load(Ordering::SeqCst),
+            ruffle_script_limit: state.ruffle_script_limit.load(Ordering::SeqCst),
+            flash_incomplete: state.flash_incomplete.load(Ordering::SeqCst),
+            capture_errors: state.capture_errors.load(Ordering::SeqCst),
+            ..Self::default()
+        }
+    }
+
+    /// How many signatures have the given status
+    pub fn count(&self, status: SignatureStatus) -> usize {
+        self.signatures
+            .values()
+            .filter(|(_, s)| *s == status)
+            .count()
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        if self.infrastructure_error.is_some() {
+            EXIT_INFRASTRUCTURE
+        } else if self.count(SignatureStatus::New) > 0 {
+            EXIT_NEW_MISMATCHES
+        } else {
+            EXIT_CLEAN
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let signatures = self
+            .signatures
+            .iter()
+            .map(|(signature, (count, status))| {
+                json!({
+                    "signature": signature,
+                    "status": status.name(),
+                    "mismatches": count.mismatches,
+                    "first_case": count.first_case,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "exit_code": self.exit_code(),
+            "infrastructure_error": self.infrastructure_error,
+            "duration_secs": self.duration.as_secs_f64(),
+            "cases": self.cases,
+            "mismatches": self.mismatches,
+            "unique_signatures": self.signatures.len(),
+            "new": self.count(SignatureStatus::New),
+            "known": self.count(SignatureStatus::Known),
+            "suppressed": self.count(SignatureStatus::Suppressed),
+            "flash_crashes": self.flash_crashes,
+            "ruffle_incomplete": self.ruffle_incomplete,
+            "ruffle_script_limit": self.ruffle_script_limit,
+            "flash_incomplete": self.flash_incomplete,
+            "capture_errors": self.capture_errors,
+            "ruffle_commit": RUFFLE_COMMIT,
+            "ruffle_core_version": RUFFLE_CORE_VERSION,
+            "signatures": signatures,
+        })
+    }
+}
+
+/// Set up the run dir and check that flash is there and logs traces
+fn check_infrastructure(config: &Config) -> Result<FlashPlayerInfo, String> {
+    setup_run_dir(&config.paths, config.threads as u32)
+        .map_err(|e| format!("Failed to set up the run dir: {}", e))?;
+    let flash_player = identify_flash_player(&config.flash)
+        .map_err(|e| format!("Failed to read {}: {}", config.flash.binary.display(), e))?;
+    match futures::executor::block_on(smoke_test(&config.flash, &config.paths.worker_temp(0))) {
+        Ok(true) => Ok(flash_player),
+        Ok(false) => Err(MyError::NonDebugFlashPlayer.to_string()),
+        Err(e) => Err(format!("Smoke test failed: {}", e)),
+    }
+}
+
+/// Add the signatures found by this campaign to the store, so the next one knows them
+fn update_signature_store(path: &Path, summary: &CampaignSummary) -> std::io::Result<()> {
+    let mut store = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    for (signature, (_, status)) in &summary.signatures {
+        if *status == SignatureStatus::New {
+            writeln!(store, "{}", signature)?;
+        }
+    }
+    Ok(())
+}
+
+/// Run the campaign and write its summary, returns the exit code. With a checkpoint, the campaign carries on from it
+/// and its budget includes the cases and time spent before it was taken
+pub fn run(config: &Config, budget: CampaignBudget, resume: Option<&Path>) -> i32 {
+    let paths = &config.paths;
+    let start = Instant::now();
+    let generator_filter = GeneratorFilter::from_file(Path::new(GENERATOR_FILTER_FILE));
+    let lists = read_signature_list(Path::new(SUPPRESSIONS_FILE))
+        .map_err(|e| format!("Failed to read {}: {}", SUPPRESSIONS_FILE, e))
+        .and_then(|suppressions| {
+            read_signature_list(&paths.signatures)
+                .map(|known| (suppressions, known))
+                .map_err(|e| format!("Failed to read {}: {}", paths.signatures.display(), e))
+        });
+
+    let mut summary = match lists
+        .and_then(|lists| Ok((lists, generator_filter?, check_infrastructure(config)?)))
+    {
+        Ok(((suppressions, known), generator_filter, flash_player)) => {
+            tracing::info!(
+                "Starting campaign of {} cases or {:?}, flash player = {}, generator filter = {}",
+                budget.max_cases,
+                budget.max_duration,
+                flash_player,
+                generator_filter
+            );
+            reverify_if_moved(paths);
+            let mut state = SharedFuzzState::with_flash_player(flash_player, config.clone());
+            state.budget = Some(BudgetTracker::start(budget));
+            state.generator_filter = generator_filter;
+            let resumed = match resume {
+                Some(checkpoint) => checkpoint::resume(&mut state, checkpoint),
+                None => {
+                    state_file::load(&mut state);
+                    Ok(())
+                }
+            };
+            match resumed {
+                Ok(()) => {
+                    let state = Arc::new(state);
+                    run_workers(&state, paths);
+                    let summary = CampaignSummary::from_state(&state, &suppressions, &known);
+                    if let Err(e) = update_signature_store(&paths.signatures, &summary) {
+                        tracing::warn!("Failed to update {}: {}", paths.signatures.display(), e);
+                    }
+                    summary
+                }
+                Err(e) => {
+                    tracing::error!("Campaign couldn't resume: {}", e);
+                    CampaignSummary {
+                        infrastructure_error: Some(e),
+                        ..CampaignSummary::default()
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("Campaign couldn't run: {}", e);
+            CampaignSummary {
+                infrastructure_error: Some(e),
+                ..CampaignSummary::default()
+            }
+        }
+    };
+    summary.duration = start.elapsed();
+
+    for (signature, (count, status)) in &summary.signatures {
+        tracing::info!(
+            "{}: {} ({} mismatches, first {})",
+            status.name(),
+            signature,
+            count.mismatches,
+            count.first_case
+        );
+    }
+    let json = summary.to_json();
+    tracing::info!(
+        "Campaign finished with exit code {}, {} cases, {} new, {} known, {} suppressed",
+        summary.exit_code(),
+        summary.cases,
+        summary.count(SignatureStatus::New),
+        summary.count(SignatureStatus::Known),
+        summary.count(SignatureStatus::Suppressed)
+    );
+    let written = serde_json::to_string_pretty(&json)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(&paths.campaign_summary, json).map_err(|e| e.to_string()));
+    match written {
+        Ok(()) => summary.exit_code(),
+        Err(e) => {
+            tracing::error!(
+                "Failed to write {}: {}",
+                paths.campaign_summary.display(),
+                e
+            );
+            EXIT_INFRASTRUCTURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(signatures: &[&str]) -> BTreeSet<String> {
+        signatures.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn signature_drops_index_and_arguments() {
+        let signature = |op: &str| mismatch_signature(&[op.to_string(), "9:Trace".to_string()]);
+        assert_eq!(
+            signature("3:Math.max(3 args, count Double(1000000000.0))"),
+            "Math.max()"
+        );
+        assert_eq!(
+            signature("12:new String(1 args).charAt(0 args)"),
+            "new String().charAt()"
+        );
+        assert_eq!(signature("0:frame 12"), "frame N");
+        assert_eq!(signature("7:StringLength"), "StringLength");
+        assert_eq!(signature(PREAMBLE), PREAMBLE);
+        assert_eq!(mismatch_signature(&[]), PREAMBLE);
+    }
+
+    #[test]
+    fn signature_includes_diff_class() {
+        let ops = ["2:Math.sqrt(1 args)".to_string()];
+        assert_eq!(
+            classified_signature(&ops, DiffClass::NumericNoise),
+            "Math.sqrt() [numeric-noise]"
+        );
+        assert_eq!(
+            classified_signature(&ops, DiffClass::Textual),
+            "Math.sqrt()"
+        );
+    }
+
+    #[test]
+    fn classify_signatures() {
+        let suppressions = set(&["Math.max()", "System.capabilities*"]);
+        let known = set(&["Math.max()", "escape()"]);
+        let classify = |signature| SignatureStatus::classify(signature, &suppressions, &known);
+        assert_eq!(classify("Math.max()"), SignatureStatus::Suppressed);
+        assert_eq!(
+            classify("System.capabilities.version"),
+            SignatureStatus::Suppressed
+        );
+        assert_eq!(classify("escape()"), SignatureStatus::Known);
+        assert_eq!(classify("Math.min()"), SignatureStatus::New);
+    }
+
+    #[test]
+    fn exit_codes() {
+        let mut summary = CampaignSummary::default();
+        assert_eq!(summary.exit_code(), EXIT_CLEAN);
+
+        for (signature, status) in [
+            ("Math.max()", SignatureStatus::Suppressed),
+            ("escape()", SignatureStatus::Known),
+        ] {
+            summary
+                .signatures
+                .insert(signature.to_string(), (SignatureCount::default(), status));
+        }
+        assert_eq!(summary.exit_code(), EXIT_CLEAN);
+
+        summary.signatures.insert(
+            "Math.min()".to_string(),
+            (SignatureCount::default(), SignatureStatus::New),
+        );
+        assert_eq!(summary.exit_code(), EXIT_NEW_MISMATCHES);
+        let json = summary.to_json();
+        assert_eq!(json["exit_code"], EXIT_NEW_MISMATCHES);
+        assert_eq!(json["unique_signatures"], 3);
+        assert_eq!(
+            (&json["new"], &json["known"], &json["suppressed"]),
+            (&json!(1), &json!(1), &json!(1))
+        );
+
+        // An infrastructure failure trumps anything found
+        summary.infrastructure_error = Some("Flash player missing".to_string());
+        assert_eq!(summary.exit_code(), EXIT_INFRASTRUCTURE);
+        assert_eq!(summary.to_json()["exit_code"], EXIT_INFRASTRUCTURE);
+    }
+}
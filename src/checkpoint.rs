@@ -0,0 +1,421 @@
+//! Checkpoints of a run, so that a campaign that was stopped can carry on where it left off. Every
+//! `CHECKPOINT_INTERVAL` the aggregates of `SharedFuzzState` are written to `RunPaths::checkpoint` along with the
+//! master seed and how many seeds each worker has drawn from it, and `--resume <checkpoint>` restores them. Case seeds
+//! are derived from those, so the resumed run generates the cases the stopped one would have generated next. Cases
+//! that were running when the checkpoint was taken aren't run again
+use crate::campaign::{BudgetTracker, SignatureCount};
+use crate::diff_class::DIFF_CLASSES;
+use crate::fuzz_session::SharedFuzzState;
+use crate::generator_filter::GeneratorFilter;
+use crate::stats::CaseTimings;
+use crate::swf_generator::{GeneratorMode, GENERATOR_VERSION};
+use crate::{ALL_SWF_VERSIONS, CHECKPOINT_INTERVAL};
+use md5::Digest;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Restore a run from a checkpoint with `--resume <checkpoint>`
+pub const RESUME_ARG: &str = "--resume";
+
+/// Bumped whenever the checkpoint format or what it restores changes, checkpoints of other versions can't be resumed
+pub const CHECKPOINT_VERSION: u32 = 5;
+
+/// Take `--resume <checkpoint>` out of the arguments, returns the other arguments and the checkpoint
+pub fn split_resume_arg(args: &[String]) -> Result<(Vec<String>, Option<PathBuf>), String> {
+    match args.iter().position(|arg| arg == RESUME_ARG) {
+        Some(index) => {
+            let checkpoint = args
+                .get(index + 1)
+                .ok_or_else(|| format!("Usage: {} <checkpoint>", RESUME_ARG))?;
+            let mut rest = args[..index].to_vec();
+            rest.extend_from_slice(&args[index + 2..]);
+            Ok((rest, Some(PathBuf::from(checkpoint))))
+        }
+        None => Ok((args.to_vec(), None)),
+    }
+}
+
+/// The plain counters of the state, by the name they are saved under
+fn counters(state: &SharedFuzzState) -> [(&'static str, &AtomicUsize); 11] {
+    [
+        ("cases_run", &state.cases_run),
+        ("mismatches", &state.mismatches),
+        ("flash_crashes", &state.flash_crashes),
+        ("ruffle_nondeterministic", &state.ruffle_nondeterministic),
+        ("ruffle_incomplete", &state.ruffle_incomplete),
+        ("ruffle_script_limit", &state.ruffle_script_limit),
+        ("flash_incomplete", &state.flash_incomplete),
+        ("output_overflows", &state.output_overflows),
+        ("ruffle_only", &state.ruffle_only),
+        ("capture_errors", &state.capture_errors),
+        ("total_iterations", &state.total_iterations),
+    ]
+}
+
+pub fn to_json(state: &SharedFuzzState) -> Value {
+    let mut counters = counters(state)
+        .iter()
+        .map(|(name, count)| (name.to_string(), json!(count.load(Ordering::SeqCst))))
+        .collect::<serde_json::Map<_, _>>();
+    // The stats logger hasn't added the iterations since it last ran to the total yet
+    counters.insert(
+        "total_iterations".to_string(),
+        json!(
+            state.total_iterations.load(Ordering::SeqCst) + state.iterations.load(Ordering::SeqCst)
+        ),
+    );
+    let diff_classes = DIFF_CLASSES
+        .iter()
+        .zip(&state.diff_classes)
+        .map(|(class, count)| {
+            (
+                class.name().to_string(),
+                json!(count.load(Ordering::SeqCst)),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>();
+    let versions = ALL_SWF_VERSIONS
+        .iter()
+        .zip(&state.version_stats)
+        .map(|(swf_version, stats)| {
+            json!({
+                "swf_version": swf_version,
+                "cases": stats.cases.load(Ordering::SeqCst),
+                "mismatches": stats.mismatches.load(Ordering::SeqCst),
+            })
+        })
+        .collect::<Vec<_>>();
+    let signatures = state
+        .signatures
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(signature, count)| {
+            json!({
+                "signature": signature,
+                "mismatches": count.mismatches,
+                "first_case": count.first_case,
+            })
+        })
+        .collect::<Vec<_>>();
+    let workers = state
+        .workers
+        .iter()
+        .map(|worker| {
+            json!({
+                "iterations": worker.iterations.load(Ordering::SeqCst),
+                "seeds_drawn": worker.seeds_drawn.load(Ordering::SeqCst),
+            })
+        })
+        .collect::<Vec<_>>();
+    let static_calls = state
+        .static_coverage
+        .executed_calls()
+        .into_iter()
+        .map(|(class, method, arg_count)| json!([class, method, arg_count]))
+        .collect::<Vec<_>>();
+    let attempted = state
+        .attempted
+        .digests()
+        .into_iter()
+        .map(|digest| format!("{:x}", digest))
+        .collect::<Vec<_>>();
+    json!({
+        "version": CHECKPOINT_VERSION,
+        "generator_version": GENERATOR_VERSION,
+        "master_seed": state.master_seed,
+        "generator_filter": state.generator_filter.entries(),
+        "elapsed_secs": state.budget.as_ref().map(|budget| budget.elapsed().as_secs_f64()),
+        "counters": counters,
+        "diff_classes": diff_classes,
+        "versions": versions,
+        "modes": state.mode_stats.to_json(&state.mode_scheduler),
+        "timings": state.timings.to_json(),
+        "signatures": signatures,
+        "diff_signatures": state.diff_signatures.to_json(),
+        "workers": workers,
+        "static_calls": static_calls,
+        "attempted": attempted,
+    })
+}
+
+/// Restore the state from a checkpoint written by `to_json`. Fails if it was written by an incompatible build, or if
+/// the generator filter or number of workers changed since, as the resumed run wouldn't generate the same cases
+pub fn restore(state: &mut SharedFuzzState, checkpoint: &Value) -> Result<(), String> {
+    let field = |value: &Value, name: &str| -> Result<Value, String> {
+        value
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("The checkpoint has no {}", name))
+    };
+    let number = |value: &Value, name: &str| {
+        field(value, name)?
+            .as_u64()
+            .ok_or_else(|| format!("{} isn't a number", name))
+    };
+    let list = |value: &Value, name: &str| -> Result<Vec<Value>, String> {
+        match field(value, name)? {
+            Value::Array(list) => Ok(list),
+            _ => Err(format!("{} isn't a list", name)),
+        }
+    };
+
+    let version = number(checkpoint, "version")?;
+    if version != CHECKPOINT_VERSION as u64 {
+        return Err(format!(
+            "The checkpoint is version {}, this build only resumes version {}",
+            version, CHECKPOINT_VERSION
+        ));
+    }
+    let generator_version = number(checkpoint, "generator_version")?;
+    if generator_version != GENERATOR_VERSION as u64 {
+        return Err(format!(
+            "The checkpoint was taken with generator version {}, this build has {}",
+            generator_version, GENERATOR_VERSION
+        ));
+    }
+    let filter_entries = list(checkpoint, "generator_filter")?
+        .iter()
+        .map(|entry| entry.as_str().map(str::to_string))
+        .collect::<Option<Vec<_>>>()
+        .ok_or("generator_filter isn't a list of strings")?;
+    let generator_filter = GeneratorFilter::parse(&filter_entries.join("\n"))?;
+    if generator_filter != state.generator_filter {
+        return Err(format!(
+            "The checkpoint was taken with generator filter {}, not {}",
+            generator_filter, state.generator_filter
+        ));
+    }
+    let workers = list(checkpoint, "workers")?;
+    if workers.len() != state.workers.len() {
+        return Err(format!(
+            "The checkpoint was taken with {} workers, not {}",
+            workers.len(),
+            state.workers.len()
+        ));
+    }
+
+    state.master_seed = number(checkpoint, "master_seed")?;
+    for (worker, saved) in state.workers.iter().zip(&workers) {
+        worker
+            .iterations
+            .store(number(saved, "iterations")? as usize, Ordering::SeqCst);
+        worker
+            .seeds_drawn
+            .store(number(saved, "seeds_drawn")?, Ordering::SeqCst);
+    }
+    let counters_json = field(checkpoint, "counters")?;
+    for (name, count) in counters(state) {
+        count.store(number(&counters_json, name)? as usize, Ordering::SeqCst);
+    }
+    state.iterations.store(0, Ordering::SeqCst);
+    let diff_classes = field(checkpoint, "diff_classes")?;
+    for (class, count) in DIFF_CLASSES.iter().zip(&state.diff_classes) {
+        count.store(
+            number(&diff_classes, class.name())? as usize,
+            Ordering::SeqCst,
+        );
+    }
+    for saved in list(checkpoint, "versions")? {
+        let swf_version = number(&saved, "swf_version")?;
+        let index = ALL_SWF_VERSIONS
+            .iter()
+            .position(|v| *v as u64 == swf_version)
+            .ok_or_else(|| format!("SWF version {} isn't configured", swf_version))?;
+        let stats = &state.version_stats[index];
+        stats
+            .cases
+            .store(number(&saved, "cases")? as usize, Ordering::SeqCst);
+        stats
+            .mismatches
+            .store(number(&saved, "mismatches")? as usize, Ordering::SeqCst);
+    }
+    for saved in list(checkpoint, "modes")? {
+        let name = field(&saved, "mode")?;
+        let mode = name
+            .as_str()
+            .and_then(GeneratorMode::from_name)
+            .ok_or_else(|| format!("{} isn't a generator mode", name))?;
+        state
+            .mode_stats
+            .sub_cases(mode)
+            .store(number(&saved, "sub_cases")? as usize, Ordering::SeqCst);
+        state
+            .mode_stats
+            .mismatches(mode)
+            .store(number(&saved, "mismatches")? as usize, Ordering::SeqCst);
+        state
+            .mode_scheduler
+            .weight(mode)
+            .store(number(&saved, "weight")? as u32, Ordering::SeqCst);
+    }
+    state.timings =
+        CaseTimings::from_json(&field(checkpoint, "timings")?).ok_or("timings aren't valid")?;
+
+    let mut signatures = state.signatures.lock().unwrap();
+    for saved in list(checkpoint, "signatures")? {
+        let text = |name: &str| {
+            field(&saved, name)?
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| format!("{} isn't a string", name))
+        };
+        signatures.insert(
+            text("signature")?,
+            SignatureCount {
+                mismatches: number(&saved, "mismatches")? as usize,
+                first_case: text("first_case")?,
+            },
+        );
+    }
+    drop(signatures);
+    state
+        .diff_signatures
+        .restore(&field(checkpoint, "diff_signatures")?)?;
+    for call in list(checkpoint, "static_calls")? {
+        let (class, method, arg_count) = match (call.get(0), call.get(1), call.get(2)) {
+            (Some(class), Some(method), Some(arg_count)) => (
+                class.as_str().unwrap_or_default(),
+                method.as_str().unwrap_or_default(),
+                arg_count.as_i64().unwrap_or_default() as i32,
+            ),
+            _ => return Err(format!("Invalid static call {}", call)),
+        };
+        // Methods disabled since the checkpoint was taken are no longer counted
+        if !state.static_coverage.restore(class, method, arg_count) {
+            tracing::warn!(
+                "{}.{} with {} args is no longer called",
+                class,
+                method,
+                arg_count
+            );
+        }
+    }
+    for digest in list(checkpoint, "attempted")? {
+        let digest = digest
+            .as_str()
+            .and_then(|hex| u128::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| format!("Invalid case hash {}", digest))?;
+        state.attempted.insert(Digest(digest.to_be_bytes()));
+    }
+
+    if let Some(budget) = &state.budget {
+        let elapsed = field(checkpoint, "elapsed_secs")?
+            .as_f64()
+            .unwrap_or_default();
+        state.budget = Some(BudgetTracker::resume(
+            budget.budget(),
+            Duration::from_secs_f64(elapsed),
+        ));
+    }
+    Ok(())
+}
+
+/// Write a checkpoint of the state to `path`, through a temporary file so that a run stopped while it is being
+/// written leaves the previous checkpoint intact
+pub fn write(state: &SharedFuzzState, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, serde_json::to_string(&to_json(state))?)?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Read the checkpoint at `path` and restore the state from it
+pub fn resume(state: &mut SharedFuzzState, path: &Path) -> Result<(), String> {
+    let checkpoint = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    restore(state, &checkpoint)?;
+    tracing::info!(
+        "Resumed from {} after {} cases, master seed = {}",
+        path.display(),
+        state.cases_run.load(Ordering::SeqCst),
+        state.master_seed
+    );
+    Ok(())
+}
+
+/// Write a checkpoint to `RunPaths::checkpoint` every `CHECKPOINT_INTERVAL`
+pub fn spawn_checkpoint_writer(state: Arc<SharedFuzzState>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CHECKPOINT_INTERVAL);
+        if let Err(e) = write(&state, &state.config.paths.checkpoint) {
+            tracing::warn!(
+                "Failed to write {}: {}",
+                state.config.paths.checkpoint.display(),
+                e
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_continues_case_seeds() {
+        let state = SharedFuzzState {
+            master_seed: 1234,
+            workers: (0..2).map(|_| Default::default()).collect(),
+            ..SharedFuzzState::default()
+        };
+        state.next_case_seed(1);
+        state.cases_run.store(7, Ordering::SeqCst);
+        state.iterations.store(3, Ordering::SeqCst);
+        state.attempted.insert(md5::compute(b"case"));
+        state
+            .mode_stats
+            .sub_cases(GeneratorMode::Opcode)
+            .store(15, Ordering::SeqCst);
+        state
+            .mode_stats
+            .mismatches(GeneratorMode::Opcode)
+            .store(1, Ordering::SeqCst);
+        state
+            .mode_scheduler
+            .weight(GeneratorMode::Opcode)
+            .store(250, Ordering::SeqCst);
+        state.signatures.lock().unwrap().insert(
+            "Multiply".to_string(),
+            SignatureCount {
+                mismatches: 2,
+                first_case: "abc".to_string(),
+            },
+        );
+        state.diff_signatures.record("Multiply: N | N", "abc");
+        let checkpoint = to_json(&state);
+
+        let mut resumed = SharedFuzzState {
+            workers: (0..2).map(|_| Default::default()).collect(),
+            ..SharedFuzzState::default()
+        };
+        restore(&mut resumed, &checkpoint).unwrap();
+        assert_eq!(resumed.next_case_seed(0), state.next_case_seed(0));
+        assert_eq!(resumed.next_case_seed(1), state.next_case_seed(1));
+        assert_eq!(resumed.cases_run.load(Ordering::SeqCst), 7);
+        assert_eq!(resumed.total_iterations.load(Ordering::SeqCst), 3);
+        assert!(!resumed.attempted.insert(md5::compute(b"case")));
+        assert_eq!(resumed.signatures.lock().unwrap()["Multiply"].mismatches, 2);
+        assert_eq!(
+            resumed.diff_signatures.first_exemplar("Multiply: N | N"),
+            Some("abc".to_string())
+        );
+        assert_eq!(
+            resumed
+                .mode_stats
+                .mismatches(GeneratorMode::Opcode)
+                .load(Ordering::SeqCst),
+            1
+        );
+        assert_eq!(to_json(&resumed), to_json(&state));
+
+        let mut incompatible = checkpoint;
+        incompatible["version"] = json!(CHECKPOINT_VERSION + 1);
+        assert!(restore(&mut resumed, &incompatible).is_err());
+    }
+}
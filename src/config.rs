@@ -0,0 +1,142 @@
+//! The settings of a run that can be changed without recompiling, `flash-fuzz [fuzz] [<option>...]`. Every option
+//! defaults to the constant or environment variable it overrides, so a run without any fuzzes as before
+use crate::flash_projector_runner::FlashSettings;
+use crate::run_paths::RunPaths;
+use crate::{FUZZ_RANDOM_STRING, PIN_THREADS, SINGLE_ITER, THREAD_COUNT};
+use std::path::PathBuf;
+
+/// Fuzz with `flash-fuzz fuzz [<option>...]`, the same as giving no command
+pub const FUZZ_COMMAND: &str = "fuzz";
+
+/// Rerun the saved failures through ruffle with `flash-fuzz check-failures [<filter>] [<option>...]`, see
+/// `failure_checker`
+pub const CHECK_FAILURES_COMMAND: &str = "check-failures";
+
+/// `--threads <count>`, instead of `THREAD_COUNT`
+const THREADS_ARG: &str = "--threads";
+/// Don't pin threads to cores, see `PIN_THREADS`
+const NO_PIN_THREADS_ARG: &str = "--no-pin-threads";
+/// Stop after a single iteration, see `SINGLE_ITER`
+const SINGLE_ITER_ARG: &str = "--single-iter";
+/// Keep the input given to flash, like `KEEP_SWF_ENV`
+const KEEP_SWF_ARG: &str = "--keep-swf";
+/// Generate random byte strings, see `FUZZ_RANDOM_STRING`
+const RANDOM_STRINGS_ARG: &str = "--random-strings";
+/// `--flash-player <binary>`, instead of `FLASH_PLAYER_BINARY`
+const FLASH_PLAYER_ARG: &str = "--flash-player";
+/// `--run-dir <dir>`, instead of `RUN_DIR_ENV` or `RUN_DIR`
+const RUN_DIR_ARG: &str = "--run-dir";
+/// `--failures-dir <dir>`, instead of `FAILURES_DIR_ENV`
+const FAILURES_DIR_ARG: &str = "--failures-dir";
+/// `--inputs-dir <dir>`, instead of `INPUTS_DIR_ENV`
+const INPUTS_DIR_ARG: &str = "--inputs-dir";
+
+/// The options, with what each takes
+pub const OPTIONS_USAGE: &str = "[--threads <count>] [--no-pin-threads] [--single-iter] [--keep-swf] \
+    [--random-strings] [--flash-player <binary>] [--run-dir <dir>] [--failures-dir <dir>] [--inputs-dir <dir>]";
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Where the run's files are written
+    pub paths: RunPaths,
+    /// How many workers fuzz at once
+    pub threads: usize,
+    /// Should threads be pinned to cores
+    pub pin_threads: bool,
+    /// Should only a single iteration be performed
+    pub single_iter: bool,
+    /// Generate random byte strings, otherwise use fixed value strings
+    pub random_strings: bool,
+    pub flash: FlashSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            paths: RunPaths::default(),
+            threads: THREAD_COUNT as usize,
+            pin_threads: PIN_THREADS,
+            single_iter: SINGLE_ITER,
+            random_strings: FUZZ_RANDOM_STRING,
+            flash: FlashSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Take the options out of the arguments, returns the config and the other arguments
+    pub fn from_args(args: &[String]) -> Result<(Self, Vec<String>), String> {
+        let mut config = Self::default();
+        let mut rest = Vec::new();
+        let (mut run_dir, mut failures_dir, mut inputs_dir) = (None, None, None);
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} needs a value, usage: {}", arg, OPTIONS_USAGE))
+            };
+            match arg.as_str() {
+                THREADS_ARG => {
+                    config.threads = value()?
+                        .parse()
+                        .ok()
+                        .filter(|threads| *threads > 0)
+                        .ok_or_else(|| format!("{} needs a count above 0", THREADS_ARG))?
+                }
+                NO_PIN_THREADS_ARG => config.pin_threads = false,
+                SINGLE_ITER_ARG => config.single_iter = true,
+                KEEP_SWF_ARG => config.flash.delete_swf = false,
+                RANDOM_STRINGS_ARG => config.random_strings = true,
+                FLASH_PLAYER_ARG => config.flash.binary = PathBuf::from(value()?),
+                RUN_DIR_ARG => run_dir = Some(PathBuf::from(value()?)),
+                FAILURES_DIR_ARG => failures_dir = Some(PathBuf::from(value()?)),
+                INPUTS_DIR_ARG => inputs_dir = Some(PathBuf::from(value()?)),
+                _ => rest.push(arg.clone()),
+            }
+        }
+        config.paths = RunPaths::from_env(run_dir);
+        if let Some(failures_dir) = failures_dir {
+            config.paths.failures = failures_dir;
+        }
+        if let Some(inputs_dir) = inputs_dir {
+            config.paths.inputs = inputs_dir;
+        }
+        Ok((config, rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_override_defaults() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let (config, rest) = Config::from_args(&args(&[
+            "flash-fuzz",
+            "replay",
+            "case.swf",
+            "--threads",
+            "4",
+            "--keep-swf",
+            "--run-dir",
+            "elsewhere",
+            "--flash-player",
+            "player",
+        ]))
+        .unwrap();
+        assert_eq!(rest, args(&["flash-fuzz", "replay", "case.swf"]));
+        assert_eq!(config.threads, 4);
+        assert!(!config.flash.delete_swf);
+        assert_eq!(config.flash.binary, PathBuf::from("player"));
+        assert_eq!(
+            config.paths.stats_file,
+            PathBuf::from("elsewhere/stats.json")
+        );
+        assert_eq!(config.single_iter, SINGLE_ITER);
+
+        assert!(Config::from_args(&args(&["flash-fuzz", "--threads", "0"])).is_err());
+        assert!(Config::from_args(&args(&["flash-fuzz", "--run-dir"])).is_err());
+    }
+}
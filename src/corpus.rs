@@ -0,0 +1,282 @@
+//! Corpus replay, every swf in a directory is run through both players for a fixed number of frames and the
+//! normalized output compared, so that real movies can be checked as well as generated cases
+use crate::config::Config;
+use crate::flash_player_info::identify_flash_player;
+use crate::flash_projector_runner::open_flash_cmd;
+use crate::fuzz_session::save_case;
+use crate::input_script::InputScript;
+use crate::normalize::normalize;
+use crate::ruffle_runner::{open_ruffle, RuffleSettings};
+use crate::sentinel::RunUntil;
+use crate::{setup_run_dir, MyError};
+use std::error::Error;
+use std::fmt::Write;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Run the corpus replay with `flash-fuzz corpus <dir>`
+pub const CORPUS_COMMAND: &str = "corpus";
+
+/// How many frames each movie is run for, as they don't trace the completion sentinel
+const CORPUS_FRAMES: u32 = 120;
+
+/// Where the summary table is saved, in `RunPaths::corpus`
+const SUMMARY_FILE: &str = "summary.txt";
+
+/// How a movie from the corpus fared
+enum CorpusResult {
+    /// Both players traced the same thing
+    Agree,
+    /// Both players ran the movie without tracing anything, which says little about whether they agree
+    Silent,
+    /// The players traced different things, saved to `RunPaths::corpus/<file name>`
+    Disagree {
+        first_difference: usize,
+    },
+    FlashCrash,
+    /// Ruffle couldn't load the movie or panicked while running it
+    RuffleFailed(String),
+    /// The file isn't a swf that can be read
+    Unreadable(String),
+}
+
+impl CorpusResult {
+    fn name(&self) -> &'static str {
+        match self {
+            CorpusResult::Agree => "agree",
+            CorpusResult::Silent => "silent",
+            CorpusResult::Disagree { .. } => "disagree",
+            CorpusResult::FlashCrash => "flash crash",
+            CorpusResult::RuffleFailed(_) => "ruffle failed",
+            CorpusResult::Unreadable(_) => "unreadable",
+        }
+    }
+}
+
+/// A row of the summary table
+struct CorpusRow {
+    file_name: String,
+    result: CorpusResult,
+    ruffle_duration: Option<Duration>,
+    flash_duration: Option<Duration>,
+}
+
+/// The swf files in `dir`, sorted so the summary is in a stable order
+fn corpus_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = std::fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && matches!(path.extension(), Some(extension) if extension.eq_ignore_ascii_case("swf"))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+/// The index of the first line that differs between two outputs
+fn first_difference(ruffle: &str, flash: &str) -> usize {
+    ruffle
+        .lines()
+        .zip(flash.lines())
+        .position(|(ruffle, flash)| ruffle != flash)
+        .unwrap_or_else(|| ruffle.lines().count().min(flash.lines().count()))
+}
+
+/// Run a movie through both players and compare them, saving the movie and outputs if they disagree
+fn replay(
+    config: &Config,
+    swf: &[u8],
+    file_name: &str,
+    flash_player: &str,
+) -> Result<CorpusRow, Box<dyn Error>> {
+    let mut row = CorpusRow {
+        file_name: file_name.to_string(),
+        result: CorpusResult::Agree,
+        ruffle_duration: None,
+        flash_duration: None,
+    };
+    if let Err(e) = swf::decompress_swf(swf) {
+        row.result = CorpusResult::Unreadable(e.to_string());
+        return Ok(row);
+    }
+
+    let input = InputScript::default();
+    let run_until = RunUntil::Frames(CORPUS_FRAMES);
+    let settings = RuffleSettings::default();
+    // Real movies reach code that generated cases don't, so a panic is a result rather than the end of the run
+    let ruffle = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        futures::executor::block_on(open_ruffle(swf, &settings, &input, run_until, None))
+    }));
+    let (ruffle_output, ruffle_duration) = match ruffle {
+        Ok(Ok(ruffle)) => ruffle,
+        Ok(Err(e)) => {
+            row.result = CorpusResult::RuffleFailed(e.to_string());
+            return Ok(row);
+        }
+        Err(_) => {
+            row.result = CorpusResult::RuffleFailed("panicked".to_string());
+            return Ok(row);
+        }
+    };
+    row.ruffle_duration = Some(ruffle_duration);
+
+    let (flash_output, flash_duration, _) = match futures::executor::block_on(open_flash_cmd(
+        swf,
+        &config.flash,
+        &config.paths.worker_temp(0),
+        &input,
+        None,
+        run_until,
+        None,
+    )) {
+        Ok(flash) => flash,
+        Err(MyError::FlashCrash) => {
+            row.result = CorpusResult::FlashCrash;
+            return Ok(row);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    row.flash_duration = Some(flash_duration);
+
+    let (ruffle_normalized, flash_normalized) =
+        (normalize(&ruffle_output), normalize(&flash_output));
+    row.result = if ruffle_normalized != flash_normalized {
+        let first_difference = first_difference(&ruffle_normalized, &flash_normalized);
+        let mut env = format!("frames: {}\n", CORPUS_FRAMES);
+        writeln!(env, "flash_player: {}", flash_player)?;
+        writeln!(env, "ruffle_settings: {}", settings)?;
+        writeln!(env, "first_difference: line {}", first_difference + 1)?;
+        save_case(
+            &config.paths.corpus,
+            file_name,
+            swf,
+            &input,
+            &[
+                ("ruffle.txt", &ruffle_output),
+                ("flash.txt", &flash_output),
+                ("env.txt", &env),
+            ],
+        )?;
+        CorpusResult::Disagree { first_difference }
+    } else if ruffle_normalized.trim().is_empty() {
+        CorpusResult::Silent
+    } else {
+        CorpusResult::Agree
+    };
+    Ok(row)
+}
+
+/// The summary table, one row per movie followed by the totals for each result
+fn summary(rows: &[CorpusRow]) -> Result<String, std::fmt::Error> {
+    let name_width = rows
+        .iter()
+        .map(|row| row.file_name.chars().count())
+        .max()
+        .unwrap_or_default()
+        .max("file".len());
+    let duration = |duration: Option<Duration>| {
+        duration.map_or("-".to_string(), |duration| format!("{:.2?}", duration))
+    };
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:name_width$}  {:13}  {:>10}  {:>10}  detail",
+        "file",
+        "result",
+        "ruffle",
+        "flash",
+        name_width = name_width
+    )?;
+    for row in rows {
+        let detail = match &row.result {
+            CorpusResult::Disagree { first_difference } => {
+                format!("first difference at line {}", first_difference + 1)
+            }
+            CorpusResult::RuffleFailed(reason) | CorpusResult::Unreadable(reason) => reason.clone(),
+            _ => String::new(),
+        };
+        writeln!(
+            out,
+            "{:name_width$}  {:13}  {:>10}  {:>10}  {}",
+            row.file_name,
+            row.result.name(),
+            duration(row.ruffle_duration),
+            duration(row.flash_duration),
+            detail,
+            name_width = name_width
+        )?;
+    }
+
+    writeln!(out)?;
+    for name in [
+        "agree",
+        "silent",
+        "disagree",
+        "flash crash",
+        "ruffle failed",
+        "unreadable",
+    ] {
+        let count = rows.iter().filter(|row| row.result.name() == name).count();
+        writeln!(out, "{}: {}", name, count)?;
+    }
+    // Agreement out of the movies that both players ran
+    let compared = rows
+        .iter()
+        .filter(|row| {
+            matches!(
+                row.result,
+                CorpusResult::Agree | CorpusResult::Silent | CorpusResult::Disagree { .. }
+            )
+        })
+        .count();
+    let agreed = rows
+        .iter()
+        .filter(|row| matches!(row.result, CorpusResult::Agree | CorpusResult::Silent))
+        .count();
+    writeln!(
+        out,
+        "Agreement = {}/{} ({:.1}%)",
+        agreed,
+        compared,
+        if compared > 0 {
+            agreed as f64 * 100.0 / compared as f64
+        } else {
+            0.0
+        }
+    )?;
+    Ok(out)
+}
+
+pub fn run(config: &Config, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let paths = &config.paths;
+    setup_run_dir(paths, 1)?;
+    std::fs::create_dir_all(&paths.corpus)?;
+    let flash_player = identify_flash_player(&config.flash)?.to_string();
+
+    let files = corpus_files(dir)?;
+    tracing::info!("Replaying {} movies from {}", files.len(), dir.display());
+    let mut rows = Vec::with_capacity(files.len());
+    for path in files {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let swf = std::fs::read(&path)?;
+        let row = replay(config, &swf, &file_name, &flash_player)?;
+        tracing::info!("{}: {}", file_name, row.result.name());
+        rows.push(row);
+    }
+
+    paths.remove_temp();
+
+    let summary = summary(&rows)?;
+    print!("{}", summary);
+    let summary_path = paths.corpus.join(SUMMARY_FILE);
+    std::fs::write(&summary_path, &summary)?;
+    tracing::info!("Saved summary to {}", summary_path.display());
+    Ok(())
+}
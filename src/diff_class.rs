@@ -0,0 +1,196 @@
+//! Classifying how the output of a mismatch differs, so that numbers formatted differently by the two players can
+//! be told apart from differences in what a script did. Differing lines are paired up after each marker and split
+//! into fields on whitespace and the separators of `__dump`'s output, then compared field by field
+use crate::op_marker::sections;
+use std::fmt;
+
+/// How a pair of differing lines differ, in order of how much they matter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiffClass {
+    /// Only numbers differ, and each by a relative error below `NUMERIC_NOISE_EPSILON`, e.g. `0.30000000000000004`
+    /// and `0.3`
+    NumericNoise,
+    /// Only numbers differ, and at least one by more than `NUMERIC_NOISE_EPSILON`
+    NumericError,
+    /// One player gave a number where the other didn't, e.g. `5` and `hello` or `number` and `string` as the type
+    /// traced by `__dump`
+    TypeMismatch,
+    /// Anything else, including lines that only one player logged
+    Textual,
+}
+
+pub const DIFF_CLASSES: &[DiffClass] = &[
+    DiffClass::NumericNoise,
+    DiffClass::NumericError,
+    DiffClass::TypeMismatch,
+    DiffClass::Textual,
+];
+
+impl DiffClass {
+    pub fn name(self) -> &'static str {
+        match self {
+            DiffClass::NumericNoise => "numeric-noise",
+            DiffClass::NumericError => "numeric-error",
+            DiffClass::TypeMismatch => "type-mismatch",
+            DiffClass::Textual => "textual",
+        }
+    }
+
+    /// The index of the class in `DIFF_CLASSES`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for DiffClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The types `typeof` gives, as traced by `__dump`
+const TYPEOF_NAMES: &[&str] = &[
+    "number",
+    "string",
+    "boolean",
+    "undefined",
+    "null",
+    "object",
+    "function",
+    "movieclip",
+];
+
+/// The field as a number, if it is one the way AVM1 formats them
+fn parse_number(field: &str) -> Option<f64> {
+    let starts_numeric = matches!(field.chars().next(), Some('0'..='9' | '-' | '.'));
+    if !starts_numeric && !matches!(field, "NaN" | "Infinity") {
+        return None;
+    }
+    field.parse().ok()
+}
+
+fn relative_error(a: f64, b: f64) -> f64 {
+    if a == b {
+        0.0
+    } else {
+        (a - b).abs() / a.abs().max(b.abs())
+    }
+}
+
+/// Classify a pair of lines that differ
+pub fn classify_lines(ruffle: &str, flash: &str, epsilon: f64) -> DiffClass {
+    fn fields(line: &str) -> Vec<&str> {
+        line.split(|c: char| c.is_whitespace() || matches!(c, ':' | ',' | '='))
+            .collect()
+    }
+    let (ruffle_fields, flash_fields) = (fields(ruffle), fields(flash));
+    if ruffle_fields.len() != flash_fields.len() {
+        return DiffClass::Textual;
+    }
+
+    let mut class = DiffClass::NumericNoise;
+    for (ruffle_field, flash_field) in ruffle_fields.into_iter().zip(flash_fields) {
+        if ruffle_field == flash_field {
+            continue;
+        }
+        let field_class = match (parse_number(ruffle_field), parse_number(flash_field)) {
+            (Some(a), Some(b)) if relative_error(a, b) < epsilon => DiffClass::NumericNoise,
+            // Including NaN against a number
+            (Some(_), Some(_)) => DiffClass::NumericError,
+            (Some(_), None) | (None, Some(_)) => DiffClass::TypeMismatch,
+            (None, None)
+                if (ruffle_field == "number" || flash_field == "number")
+                    && TYPEOF_NAMES.contains(&ruffle_field)
+                    && TYPEOF_NAMES.contains(&flash_field) =>
+            {
+                DiffClass::TypeMismatch
+            }
+            (None, None) => DiffClass::Textual,
+        };
+        class = class.max(field_class);
+    }
+    class
+}
+
+/// Classify how two normalized outputs differ, by the most significant class of the lines that differ after each
+/// marker. Lines are paired in order, so a line that only one player logged makes the rest after that marker textual
+pub fn classify(ruffle: &str, flash: &str, epsilon: f64) -> DiffClass {
+    let ruffle_sections = sections(ruffle);
+    let flash_sections = sections(flash);
+
+    let mut class = None;
+    for (marker, ruffle_lines) in &ruffle_sections {
+        let flash_lines = match flash_sections.iter().find(|(m, _)| m == marker) {
+            Some((_, lines)) => lines,
+            None => return DiffClass::Textual,
+        };
+        if ruffle_lines.len() != flash_lines.len() {
+            return DiffClass::Textual;
+        }
+        for (ruffle_line, flash_line) in ruffle_lines.iter().zip(flash_lines) {
+            if ruffle_line != flash_line {
+                class = class.max(Some(classify_lines(ruffle_line, flash_line, epsilon)));
+            }
+        }
+    }
+    if flash_sections.len() != ruffle_sections.len() {
+        return DiffClass::Textual;
+    }
+    // The outputs can differ without any line differing, e.g. in line endings
+    class.unwrap_or(DiffClass::Textual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-12;
+
+    #[test]
+    fn classify_line_pairs() {
+        let classify = |ruffle, flash| classify_lines(ruffle, flash, EPSILON);
+        assert_eq!(
+            classify("0:number:0.30000000000000004", "0:number:0.3"),
+            DiffClass::NumericNoise
+        );
+        assert_eq!(
+            classify("0:number:5", "0:number:6"),
+            DiffClass::NumericError
+        );
+        assert_eq!(
+            classify("0:number:NaN", "0:number:0"),
+            DiffClass::NumericError
+        );
+        assert_eq!(classify("5", "hello"), DiffClass::TypeMismatch);
+        assert_eq!(
+            classify("0:number:1", "0:string:a"),
+            DiffClass::TypeMismatch
+        );
+        assert_eq!(classify("0:string:a", "0:string:b"), DiffClass::Textual);
+        assert_eq!(classify("0:array:2", "0:object"), DiffClass::Textual);
+    }
+
+    #[test]
+    fn classify_outputs() {
+        let classify = |ruffle, flash| super::classify(ruffle, flash, EPSILON);
+        assert_eq!(
+            classify(
+                "#OP:0:a#\n0:number:0.1\n#OP:1:b#\n0:number:1e-7",
+                "#OP:0:a#\n0:number:0.1000000000000001\n#OP:1:b#\n0:number:1e-7"
+            ),
+            DiffClass::NumericNoise
+        );
+        // The most significant class wins
+        assert_eq!(
+            classify(
+                "#OP:0:a#\n0:number:0.1\n#OP:1:b#\n0:number:1",
+                "#OP:0:a#\n0:number:0.1000000000000001\n#OP:1:b#\n0:number:2"
+            ),
+            DiffClass::NumericError
+        );
+        assert_eq!(
+            classify("#OP:0:a#\n0:number:1\n", "#OP:0:a#\n0:number:1\nextra"),
+            DiffClass::Textual
+        );
+    }
+}
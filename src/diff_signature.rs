@@ -0,0 +1,319 @@
+//! Deduplicating mismatches by how their output differs. A mismatch's diff signature is its campaign signature
+//! followed by the first pair of lines that differ, with numbers (and their sign) masked as `N` and generated strings
+//! (non-ASCII text and escaped bytes) as `S`, so the same bug found with different values has the same signature. Only
+//! the first `SIGNATURE_EXEMPLARS` failures of each signature are saved, the rest are counted, and the counts are logged
+//! with the stats and written to `RunPaths::signature_summary` so a long run can be triaged without reading every
+//! failure dir. The counts and saved failures are kept in checkpoints and the state file, so a restarted run doesn't
+//! save more failures of the signatures it already has.
+//!
+//! The campaign signature (see `campaign::mismatch_signature`) can't be derived from these or replace them: it is only
+//! the differing ops and how they differ, so that it stays the same across ruffle commits and can be listed in
+//! `RunPaths::signatures` and `SUPPRESSIONS_FILE`. A diff signature has the differing text too, which is what tells
+//! two bugs in the same op apart, but changes with every change to that text
+use crate::state_file::{read_string, read_u64, write_string, write_u64};
+use crate::{SIGNATURE_EXEMPLARS, SIGNATURE_LINE_LEN};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Stands in for a line that one player didn't log
+const MISSING_LINE: &str = "<missing>";
+
+/// A traced line with numbers and generated strings masked, cut at `SIGNATURE_LINE_LEN` chars
+fn mask_line(line: &str) -> String {
+    let mut masked = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '0'..='9' => {
+                if !masked.ends_with('N') {
+                    masked.push('N');
+                }
+            }
+            // So is a sign, unless it follows a number and so is a minus
+            '-' if !masked.ends_with('N') && matches!(chars.peek(), Some('0'..='9')) => {}
+            // A decimal point is part of the number
+            '.' if masked.ends_with('N') && matches!(chars.peek(), Some('0'..='9')) => {}
+            // Bytes that aren't UTF-8 are escaped as `\xNN`, see `trace_bytes`
+            '\\' if chars.peek() == Some(&'x') => {
+                chars.next();
+                chars.next();
+                chars.next();
+                if !masked.ends_with('S') {
+                    masked.push('S');
+                }
+            }
+            _ if !c.is_ascii() || c.is_ascii_control() => {
+                if !masked.ends_with('S') {
+                    masked.push('S');
+                }
+            }
+            _ => masked.push(c),
+        }
+    }
+    match masked.char_indices().nth(SIGNATURE_LINE_LEN) {
+        Some((end, _)) => masked[..end].to_string(),
+        None => masked,
+    }
+}
+
+/// The diff signature of a mismatch with the campaign signature `signature`, from the normalized outputs
+pub fn diff_signature(signature: &str, ruffle: &str, flash: &str) -> String {
+    let (mut ruffle_lines, mut flash_lines) = (ruffle.lines(), flash.lines());
+    loop {
+        match (ruffle_lines.next(), flash_lines.next()) {
+            (Some(ruffle_line), Some(flash_line)) if ruffle_line == flash_line => continue,
+            (None, None) => return signature.to_string(),
+            (ruffle_line, flash_line) => {
+                return format!(
+                    "{}: {} | {}",
+                    signature,
+                    ruffle_line.map_or(MISSING_LINE.to_string(), mask_line),
+                    flash_line.map_or(MISSING_LINE.to_string(), mask_line)
+                )
+            }
+        }
+    }
+}
+
+/// The mismatches found with one diff signature
+#[derive(Debug, Default)]
+struct DiffSignatureCount {
+    mismatches: usize,
+    /// The failures saved with this signature, in `RunPaths::failures`
+    exemplars: Vec<String>,
+}
+
+/// The signatures in `counts`, most mismatches first
+fn sorted(
+    counts: &HashMap<String, DiffSignatureCount>,
+) -> impl Iterator<Item = (&String, &DiffSignatureCount)> {
+    let mut sorted = counts.iter().collect::<Vec<_>>();
+    // By name after count, so ties are in a stable order
+    sorted.sort_by(|a, b| {
+        b.1.mismatches
+            .cmp(&a.1.mismatches)
+            .then_with(|| a.0.cmp(b.0))
+    });
+    sorted.into_iter()
+}
+
+/// The mismatches found with each diff signature
+#[derive(Debug, Default)]
+pub struct DiffSignatures {
+    counts: Mutex<HashMap<String, DiffSignatureCount>>,
+}
+
+impl DiffSignatures {
+    /// Count a mismatch against its signature, returns whether the failure should be saved as one of its first
+    /// `SIGNATURE_EXEMPLARS`
+    pub fn record(&self, signature: &str, case_name: &str) -> bool {
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(signature.to_string()).or_default();
+        count.mismatches += 1;
+        if count.exemplars.len() >= SIGNATURE_EXEMPLARS {
+            return false;
+        }
+        count.exemplars.push(case_name.to_string());
+        true
+    }
+
+    /// The first failure saved with a signature, if it has been seen
+    pub fn first_exemplar(&self, signature: &str) -> Option<String> {
+        self.counts
+            .lock()
+            .unwrap()
+            .get(signature)
+            .and_then(|count| count.exemplars.first().cloned())
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Up to `n` signatures with the most mismatches, with their counts
+    pub fn top(&self, n: usize) -> Vec<(String, usize)> {
+        let counts = self.counts.lock().unwrap();
+        let mut top = sorted(&counts)
+            .map(|(signature, count)| (signature.clone(), count.mismatches))
+            .collect::<Vec<_>>();
+        top.truncate(n);
+        top
+    }
+
+    /// Every signature, most mismatches first, with its count and saved failures
+    pub fn to_json(&self) -> Value {
+        let counts = self.counts.lock().unwrap();
+        let signatures = sorted(&counts)
+            .map(|(signature, count)| {
+                json!({
+                    "signature": signature,
+                    "mismatches": count.mismatches,
+                    "exemplars": count.exemplars,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "unique_signatures": signatures.len(),
+            "signatures": signatures,
+        })
+    }
+
+    /// Restore the signatures from the `to_json` of a checkpoint, see `checkpoint`
+    pub fn restore(&self, json: &Value) -> Result<(), String> {
+        let signatures = json
+            .get("signatures")
+            .and_then(Value::as_array)
+            .ok_or("diff signatures aren't a list")?;
+        let mut counts = self.counts.lock().unwrap();
+        for saved in signatures {
+            let signature = saved
+                .get("signature")
+                .and_then(Value::as_str)
+                .ok_or_else(|| format!("Invalid diff signature {}", saved))?;
+            let mismatches = saved
+                .get("mismatches")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| format!("Invalid diff signature {}", saved))?;
+            let exemplars = saved
+                .get("exemplars")
+                .and_then(Value::as_array)
+                .and_then(|exemplars| {
+                    exemplars
+                        .iter()
+                        .map(|exemplar| exemplar.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| format!("Invalid diff signature {}", saved))?;
+            counts.insert(
+                signature.to_string(),
+                DiffSignatureCount {
+                    mismatches: mismatches as usize,
+                    exemplars,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Write every signature with its count and saved failures, see `state_file`
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        let counts = self.counts.lock().unwrap();
+        write_u64(out, counts.len() as u64)?;
+        for (signature, count) in sorted(&counts) {
+            write_string(out, signature)?;
+            write_u64(out, count.mismatches as u64)?;
+            write_u64(out, count.exemplars.len() as u64)?;
+            for exemplar in &count.exemplars {
+                write_string(out, exemplar)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read signatures written by `write_to`
+    pub fn read_from(input: &mut impl Read) -> io::Result<Self> {
+        let mut counts = HashMap::new();
+        for _ in 0..read_u64(input)? {
+            let signature = read_string(input)?;
+            let mismatches = read_u64(input)? as usize;
+            let exemplars = (0..read_u64(input)?)
+                .map(|_| read_string(input))
+                .collect::<io::Result<_>>()?;
+            counts.insert(
+                signature,
+                DiffSignatureCount {
+                    mismatches,
+                    exemplars,
+                },
+            );
+        }
+        Ok(Self {
+            counts: Mutex::new(counts),
+        })
+    }
+
+    /// Write `to_json` to `path`, replacing what was there
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, serde_json::to_string_pretty(&self.to_json())?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_masks_values_of_first_differing_line() {
+        let signature = |ruffle: &str, flash: &str| diff_signature("Array.sort()", ruffle, flash);
+        assert_eq!(
+            signature("a\n1,10,2\nb\n", "a\n1,2,10\nc\n"),
+            "Array.sort(): N,N,N | N,N,N"
+        );
+        // Different values, same bug
+        assert_eq!(
+            signature("3.25e-7 caf\u{e9}\\xff\\xfe!\n", "0.5 \\x80!\n"),
+            "Array.sort(): NeN cafS! | N S!"
+        );
+        // A sign is masked with its number, a minus after a number isn't
+        assert_eq!(signature("-1\n", "1\n"), signature("1\n", "-1\n"));
+        assert_eq!(signature("x=-1\n", "3-1\n"), "Array.sort(): x=N | N-N");
+        assert_eq!(signature("a\n", "a\nb\n"), "Array.sort(): <missing> | b");
+        assert_eq!(
+            signature(&"x".repeat(1000), "y").len(),
+            "Array.sort(): ".len() + SIGNATURE_LINE_LEN + " | y".len()
+        );
+    }
+
+    #[test]
+    fn only_first_exemplars_are_saved() {
+        let signatures = DiffSignatures::default();
+        for i in 0..SIGNATURE_EXEMPLARS {
+            assert!(signatures.record("a", &i.to_string()));
+        }
+        assert!(!signatures.record("a", "late"));
+        assert!(signatures.record("b", "first b"));
+        assert_eq!(
+            signatures.top(1),
+            [("a".to_string(), SIGNATURE_EXEMPLARS + 1)]
+        );
+        assert_eq!(signatures.first_exemplar("a"), Some("0".to_string()));
+        assert_eq!(signatures.first_exemplar("c"), None);
+        let json = signatures.to_json();
+        assert_eq!(json["unique_signatures"], 2);
+        assert_eq!(
+            json["signatures"][0]["exemplars"].as_array().unwrap().len(),
+            SIGNATURE_EXEMPLARS
+        );
+    }
+
+    #[test]
+    fn signatures_round_trip() {
+        let signatures = DiffSignatures::default();
+        for i in 0..=SIGNATURE_EXEMPLARS {
+            signatures.record("a", &i.to_string());
+        }
+        signatures.record("b", "first b");
+
+        let restored = DiffSignatures::default();
+        restored.restore(&signatures.to_json()).unwrap();
+        assert_eq!(restored.to_json(), signatures.to_json());
+        assert!(restored.restore(&json!({ "signatures": [{}] })).is_err());
+
+        let mut bytes = Vec::new();
+        signatures.write_to(&mut bytes).unwrap();
+        let read = DiffSignatures::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read.to_json(), signatures.to_json());
+        // A restarted run doesn't save more failures of a signature it already has
+        assert!(!read.record("a", "after restart"));
+        assert!(DiffSignatures::read_from(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+}
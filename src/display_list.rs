@@ -0,0 +1,120 @@
+//! Summaries of the clips on the root timeline, compared as a channel of their own alongside the traces. With
+//! `DISPLAY_LIST_SUMMARY` every case traces a line for each clip on the root timeline just before the completion
+//! sentinel, and ruffle also builds the same lines from its display list once the case has run. Flash's traced summary
+//! is compared with ruffle's as part of the output, and when ruffle's traced summary doesn't match its own display list
+//! the display list's summary is added to its output after `RUFFLE_DISPLAY_LIST_MARKER`, so the case mismatches and
+//! the failure shows both
+use crate::normalize::{SORTED_BLOCK_BEGIN, SORTED_BLOCK_END};
+use crate::sentinel::CASE_COMPLETE_SENTINEL;
+use ruffle_core::display_object::{TDisplayObject, TDisplayObjectContainer};
+use ruffle_core::Player;
+
+/// Start of a summary line, followed by `<name>:<depth>:<x>:<y>:<visible>`
+pub const DISPLAY_LIST_PREFIX: &str = "#DL#";
+
+/// Added by ruffle's runner before the summary of its display list, when it differs from the summary ruffle traced
+pub const RUFFLE_DISPLAY_LIST_MARKER: &str = "#DL_RUFFLE#";
+
+/// `getDepth` is the timeline depth offset by this
+const AVM1_DEPTH_OFFSET: i32 = -16384;
+
+/// A coordinate the way AVM1 converts it to a string. Coordinates are whole twips, so the shortest representation
+/// matches AVM1's, except for -0 which AVM1 traces as 0
+fn avm1_coordinate(value: f64) -> String {
+    (value + 0.0).to_string()
+}
+
+/// The summary line of a clip, as traced by `SwfGenerator::write_display_list_summary`
+pub fn summary_line(name: &str, depth: i32, x: f64, y: f64, visible: bool) -> String {
+    format!(
+        "{}{}:{}:{}:{}:{}",
+        DISPLAY_LIST_PREFIX,
+        name,
+        depth,
+        avm1_coordinate(x),
+        avm1_coordinate(y),
+        visible
+    )
+}
+
+/// The summary lines of the clips on ruffle's root timeline, read from its display list rather than by the movie
+pub fn ruffle_summary(player: &mut Player) -> Vec<String> {
+    player.mutate_with_update_context(|context| {
+        let root = match context.stage.root_clip().as_container() {
+            Some(root) => root,
+            None => return Vec::new(),
+        };
+        root.iter_render_list()
+            .filter(|child| child.as_movie_clip().is_some())
+            .map(|child| {
+                summary_line(
+                    &child.name().to_string(),
+                    child.depth() + AVM1_DEPTH_OFFSET,
+                    child.x(),
+                    child.y(),
+                    child.visible(),
+                )
+            })
+            .collect()
+    })
+}
+
+/// Compare the summary ruffle traced with the one from its display list, and add the display list's before the
+/// completion sentinel if they differ
+pub fn check_ruffle_summary(output: &mut String, mut display_list: Vec<String>) {
+    let mut traced = output
+        .lines()
+        .filter(|line| line.starts_with(DISPLAY_LIST_PREFIX))
+        .collect::<Vec<_>>();
+    traced.sort_unstable();
+    display_list.sort_unstable();
+    if traced == display_list {
+        return;
+    }
+
+    let sentinel_line = match output.rfind(CASE_COMPLETE_SENTINEL) {
+        Some(sentinel) => output[..sentinel].rfind('\n').map_or(0, |i| i + 1),
+        None => output.len(),
+    };
+    let mut summary = format!("{}\n{}\n", RUFFLE_DISPLAY_LIST_MARKER, SORTED_BLOCK_BEGIN);
+    for line in display_list {
+        summary.push_str(&line);
+        summary.push('\n');
+    }
+    summary.push_str(SORTED_BLOCK_END);
+    summary.push('\n');
+    output.insert_str(sentinel_line, &summary);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ruffle_summary_checked_against_traced() {
+        assert_eq!(
+            summary_line("__clip", -16383, 12.35, -0.0, false),
+            "#DL#__clip:-16383:12.35:0:false"
+        );
+
+        let traced = "#SORTED_BEGIN#\n#DL#b:-16382:0:0:true\n#DL#a:-16383:1.5:2:true\n#SORTED_END#\n#CASE_COMPLETE#\n";
+        let mut output = traced.to_string();
+        check_ruffle_summary(
+            &mut output,
+            vec![
+                summary_line("a", -16383, 1.5, 2.0, true),
+                summary_line("b", -16382, 0.0, 0.0, true),
+            ],
+        );
+        assert_eq!(output, traced);
+
+        check_ruffle_summary(&mut output, vec![summary_line("a", -16383, 1.5, 2.0, true)]);
+        assert_eq!(
+            output,
+            format!(
+                "{}#DL_RUFFLE#\n#SORTED_BEGIN#\n#DL#a:-16383:1.5:2:true\n#SORTED_END#\n#CASE_COMPLETE#\n",
+                traced.trim_end_matches("#CASE_COMPLETE#\n")
+            )
+        );
+    }
+}
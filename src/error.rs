@@ -5,6 +5,21 @@ pub enum MyError {
     #[error("Flash Crash")]
     FlashCrash,
 
+    #[error("Flash player didn't log any traces, is it a debug player?")]
+    NonDebugFlashPlayer,
+
+    #[error("Bench median regressed past the threshold")]
+    BenchRegression,
+
+    #[error("Bench baseline is missing or invalid")]
+    InvalidBenchBaseline,
+
+    #[error("Captured output doesn't start with the case's nonce")]
+    CaptureError,
+
+    #[error("Ruffle couldn't load the movie: {0}")]
+    RuffleLoad(String),
+
     #[error("Io Error")]
     IoError(#[from] std::io::Error),
 
@@ -1,42 +1,468 @@
-use crate::ruffle_runner::open_ruffle;
-use crate::FAILURES_DIR;
+//! Checking saved failures against the current Ruffle, `flash-fuzz check-failures [<filter>] [--rerun-flash]
+//! [--prune] [--runs <count>]` runs the `out.swf` of every failure dir whose name contains the filter through ruffle
+//! `CHECK_FAILURE_RUNS` times and compares the output with the saved `flash.txt`. A failure that matches every time is
+//! fixed, one that differs every time is still failing and one that only differs some of the time is flaky. A summary
+//! table is printed and the exit code is `EXIT_STILL_FAILING` if any aren't fixed, so it can gate CI on a ruffle
+//! checkout. With `--rerun-flash` the swfs are run through flash again too and `flash.txt` is replaced, for when the
+//! saved output is suspected to be stale. With `--prune` the fixed failures are moved to `RunPaths::fixed_failures`
+//! along with a `fixed_on.txt` recording when and against which ruffle commit, so they aren't checked again
+use crate::campaign::{EXIT_CLEAN, EXIT_INFRASTRUCTURE};
+use crate::config::Config;
+use crate::flash_player_info::identify_flash_player;
+use crate::flash_projector_runner::open_flash_cmd;
+use crate::input_script::InputScript;
+use crate::normalize::normalize;
+use crate::op_marker::differing_ops;
+use crate::reverify::{RUFFLE_COMMIT, RUFFLE_CORE_VERSION};
+use crate::ruffle_runner::{open_ruffle, RuffleSettings};
+use crate::run_paths::FIXED_DIR;
+use crate::sentinel::RunUntil;
+use crate::{setup_run_dir, MyError, CHECK_FAILURE_RUNS};
 use std::error::Error;
+use std::fmt::Write;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub async fn check_failures() -> Result<(), Box<dyn Error>> {
-    let dir = std::fs::read_dir(FAILURES_DIR)?;
+/// Also run the swfs through flash again and replace their `flash.txt`
+pub const RERUN_FLASH_ARG: &str = "--rerun-flash";
 
-    let mut total = 0;
-    let mut failed = 0;
+/// Move the fixed failures to `RunPaths::fixed_failures`
+const PRUNE_ARG: &str = "--prune";
 
-    for entry in dir
-        .flatten()
-        .filter(|e| e.file_type().is_ok())
-        .filter(|e| e.file_type().unwrap().is_dir())
+/// `--runs <count>`, instead of `CHECK_FAILURE_RUNS`
+const RUNS_ARG: &str = "--runs";
+
+/// Written to a failure dir moved by `--prune`
+const FIXED_ON_FILE: &str = "fixed_on.txt";
+
+/// The exit code when any failure still differs or couldn't be rerun
+pub const EXIT_STILL_FAILING: i32 = 1;
+
+/// The swf and input script of a saved failure
+pub(crate) fn read_case(failure_dir: &Path) -> Result<(Vec<u8>, InputScript), Box<dyn Error>> {
+    let swf_content = std::fs::read(failure_dir.join("out.swf"))?;
+    // Only input cases have an input script
+    let input = match std::fs::read_to_string(failure_dir.join("input.txt")) {
+        Ok(input) => input.parse::<InputScript>()?,
+        Err(_) => InputScript::default(),
+    };
+    Ok((swf_content, input))
+}
+
+/// The settings in a failure's `env.txt`, the defaults for failures saved before they were recorded
+fn env_ruffle_settings(env: &str) -> Result<RuffleSettings, String> {
+    match env
+        .lines()
+        .find_map(|line| line.strip_prefix("ruffle_settings: "))
     {
-        let swf_path = entry.path().join("out.swf");
-        let flash_output_path = entry.path().join("flash.txt");
-        let swf_content = std::fs::read(swf_path)?;
-
-        //TODO:
-        let (ruffle_res, _) = open_ruffle(&swf_content).await?;
-        let expected = std::fs::read_to_string(flash_output_path.to_str().unwrap())?;
-
-        if ruffle_res != expected {
-            tracing::info!("---------- Found mismatch ----------");
-            tracing::info!("Test case = {}", entry.file_name().to_string_lossy());
-            tracing::info!("Ruffle output:");
-            tracing::info!("{}", ruffle_res);
-            tracing::info!("Flash output:");
-            tracing::info!("{}", expected);
-            tracing::info!("------------------------------------");
-            failed += 1;
-        } else {
-            tracing::info!("Test case {} - Passed", entry.file_name().to_string_lossy());
-        }
-        total += 1;
+        Some(settings) => settings.trim().parse(),
+        None => Ok(RuffleSettings::default()),
     }
+}
 
-    tracing::info!("Overall results: {}/{} failed", failed, total);
+/// Run a saved failure's swf through ruffle again with the settings it was saved with, returns ruffle's output and
+/// the flash output saved with it
+pub async fn rerun_failure(failure_dir: &Path) -> Result<(String, String), Box<dyn Error>> {
+    let (swf_content, input) = read_case(failure_dir)?;
+    let settings = match std::fs::read_to_string(failure_dir.join("env.txt")) {
+        Ok(env) => env_ruffle_settings(&env)?,
+        Err(e) if e.kind() == ErrorKind::NotFound => RuffleSettings::default(),
+        Err(e) => return Err(e.into()),
+    };
 
+    let (ruffle_res, _) =
+        open_ruffle(&swf_content, &settings, &input, RunUntil::Sentinel, None).await?;
+    let expected = std::fs::read_to_string(failure_dir.join("flash.txt"))?;
+    Ok((ruffle_res, expected))
+}
+
+/// Run a saved failure's swf through flash again and replace its `flash.txt`. A crash keeps the saved output
+async fn rerun_flash(config: &Config, failure_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let (swf_content, input) = read_case(failure_dir)?;
+    match open_flash_cmd(
+        &swf_content,
+        &config.flash,
+        &config.paths.worker_temp(0),
+        &input,
+        None,
+        RunUntil::Sentinel,
+        None,
+    )
+    .await
+    {
+        Ok((flash_output, _, _)) => std::fs::write(failure_dir.join("flash.txt"), flash_output)?,
+        Err(MyError::FlashCrash) => tracing::warn!(
+            "{}: flash crashed, keeping the saved output",
+            failure_dir.display()
+        ),
+        Err(e) => return Err(e.into()),
+    }
     Ok(())
 }
+
+/// What `check-failures` was asked to do
+#[derive(Debug, PartialEq, Eq)]
+pub struct CheckOptions {
+    /// Only check the failures whose dir names contain this
+    pub filter: Option<String>,
+    pub rerun_flash: bool,
+    pub prune: bool,
+    /// How many times each failure is run through ruffle
+    pub runs: usize,
+}
+
+impl CheckOptions {
+    /// Parse the arguments after the command
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let usage = || {
+            format!(
+                "Usage: flash-fuzz check-failures [<filter>] [{}] [{}] [{} <count>]",
+                RERUN_FLASH_ARG, PRUNE_ARG, RUNS_ARG
+            )
+        };
+        let mut options = Self {
+            filter: None,
+            rerun_flash: false,
+            prune: false,
+            runs: CHECK_FAILURE_RUNS,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                RERUN_FLASH_ARG => options.rerun_flash = true,
+                PRUNE_ARG => options.prune = true,
+                RUNS_ARG => {
+                    options.runs = args
+                        .next()
+                        .and_then(|runs| runs.parse().ok())
+                        .filter(|runs| *runs > 0)
+                        .ok_or_else(usage)?
+                }
+                filter if options.filter.is_none() && !filter.starts_with("--") => {
+                    options.filter = Some(filter.to_string())
+                }
+                _ => return Err(usage()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// How a saved failure fared
+enum CheckResult {
+    /// Ruffle's output now matches the saved flash output every run
+    Fixed,
+    /// The outputs still differ every run, with the first op whose output differs if there is one
+    Mismatch { first_differing_op: Option<String> },
+    /// The outputs differed in only `mismatches` of the `runs`
+    Flaky { mismatches: usize, runs: usize },
+    /// The failure couldn't be rerun, e.g. its swf is missing or ruffle failed to load it
+    Error(String),
+}
+
+/// A row of the summary table
+struct CheckRow {
+    name: String,
+    result: CheckResult,
+    /// Was the failure moved to `RunPaths::fixed_failures`
+    pruned: bool,
+}
+
+impl CheckRow {
+    fn still_failing(&self) -> bool {
+        !matches!(self.result, CheckResult::Fixed)
+    }
+}
+
+/// The failure dirs in `failures_dir` whose names contain `filter`, sorted so the summary is in a stable order. The
+/// failures already moved to `FIXED_DIR` aren't included
+fn failure_dirs(failures_dir: &Path, filter: Option<&str>) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut dirs = std::fs::read_dir(failures_dir)
+        .map_err(|e| format!("Failed to read {}: {}", failures_dir.display(), e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && !path.ends_with(FIXED_DIR))
+        .filter(|path| match filter {
+            Some(filter) => path.file_name().unwrap().to_string_lossy().contains(filter),
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Rerun one failure `runs` times, an error is a result rather than the end of the check
+async fn check_failure(
+    config: &Config,
+    failure_dir: &Path,
+    flash: bool,
+    runs: usize,
+) -> CheckResult {
+    if flash {
+        if let Err(e) = rerun_flash(config, failure_dir).await {
+            return CheckResult::Error(format!("flash: {}", e));
+        }
+    }
+    let mut mismatches = 0;
+    let mut first_differing_op = None;
+    for _ in 0..runs {
+        let (ruffle_res, expected) = match rerun_failure(failure_dir).await {
+            Ok(outputs) => outputs,
+            Err(e) => return CheckResult::Error(e.to_string()),
+        };
+        let (ruffle_normalized, flash_normalized) = (normalize(&ruffle_res), normalize(&expected));
+        if ruffle_normalized != flash_normalized {
+            mismatches += 1;
+            first_differing_op = first_differing_op.or_else(|| {
+                differing_ops(&ruffle_normalized, &flash_normalized)
+                    .into_iter()
+                    .next()
+            });
+        }
+    }
+    match mismatches {
+        0 => CheckResult::Fixed,
+        _ if mismatches == runs => CheckResult::Mismatch { first_differing_op },
+        _ => CheckResult::Flaky { mismatches, runs },
+    }
+}
+
+/// Move a fixed failure to `fixed_dir`, recording when and against which ruffle commit it was found fixed
+fn prune(failure_dir: &Path, fixed_dir: &Path) -> std::io::Result<()> {
+    let fixed_on = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    std::fs::write(
+        failure_dir.join(FIXED_ON_FILE),
+        format!(
+            "fixed_on: {}\nruffle_commit: {}\nruffle_core_version: {}\n",
+            fixed_on, RUFFLE_COMMIT, RUFFLE_CORE_VERSION
+        ),
+    )?;
+    std::fs::create_dir_all(fixed_dir)?;
+    std::fs::rename(
+        failure_dir,
+        fixed_dir.join(failure_dir.file_name().unwrap()),
+    )
+}
+
+/// The summary table, one row per failure and the totals
+fn summary(rows: &[CheckRow]) -> Result<String, std::fmt::Error> {
+    let name_width = rows
+        .iter()
+        .map(|row| row.name.len())
+        .chain(std::iter::once("failure".len()))
+        .max()
+        .unwrap_or_default();
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:name_width$}  {:8}  detail",
+        "failure",
+        "result",
+        name_width = name_width
+    )?;
+    for row in rows {
+        let (result, detail) = match &row.result {
+            CheckResult::Fixed if row.pruned => ("fixed", format!("moved to {}", FIXED_DIR)),
+            CheckResult::Fixed => ("fixed", String::new()),
+            CheckResult::Mismatch { first_differing_op } => (
+                "mismatch",
+                first_differing_op
+                    .as_ref()
+                    .map_or(String::new(), |op| format!("differs after {}", op)),
+            ),
+            CheckResult::Flaky { mismatches, runs } => (
+                "flaky",
+                format!("differs in {} of {} runs", mismatches, runs),
+            ),
+            CheckResult::Error(reason) => ("error", reason.clone()),
+        };
+        writeln!(
+            out,
+            "{:name_width$}  {:8}  {}",
+            row.name,
+            result,
+            detail,
+            name_width = name_width
+        )?;
+    }
+    let count =
+        |matches: fn(&CheckResult) -> bool| rows.iter().filter(|row| matches(&row.result)).count();
+    writeln!(
+        out,
+        "{} failures: {} newly fixed, {} still failing, {} flaky, {} errors",
+        rows.len(),
+        count(|result| matches!(result, CheckResult::Fixed)),
+        count(|result| matches!(result, CheckResult::Mismatch { .. })),
+        count(|result| matches!(result, CheckResult::Flaky { .. })),
+        count(|result| matches!(result, CheckResult::Error(_))),
+    )?;
+    Ok(out)
+}
+
+/// The exit code for the checked failures
+fn exit_code(rows: &[CheckRow]) -> i32 {
+    if rows.iter().any(CheckRow::still_failing) {
+        EXIT_STILL_FAILING
+    } else {
+        EXIT_CLEAN
+    }
+}
+
+/// Check the failures in `RunPaths::failures` as `options` asks, returns the exit code
+pub async fn check_failures(
+    config: &Config,
+    options: &CheckOptions,
+) -> Result<i32, Box<dyn Error>> {
+    let dirs = failure_dirs(&config.paths.failures, options.filter.as_deref())?;
+    let flash = options.rerun_flash;
+    if flash {
+        setup_run_dir(&config.paths, 1)?;
+        tracing::info!("Flash player = {}", identify_flash_player(&config.flash)?);
+    }
+    tracing::info!(
+        "Checking {} failures in {}",
+        dirs.len(),
+        config.paths.failures.display()
+    );
+
+    let mut rows = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        let mut row = CheckRow {
+            name: dir.file_name().unwrap().to_string_lossy().into_owned(),
+            result: check_failure(config, &dir, flash, options.runs).await,
+            pruned: false,
+        };
+        tracing::info!(
+            "{}: {}",
+            row.name,
+            if row.still_failing() {
+                "still failing"
+            } else {
+                "fixed"
+            }
+        );
+        if options.prune && !row.still_failing() {
+            match prune(&dir, &config.paths.fixed_failures()) {
+                Ok(()) => row.pruned = true,
+                Err(e) => tracing::warn!("Failed to move {}: {}", dir.display(), e),
+            }
+        }
+        rows.push(row);
+    }
+    if flash {
+        config.paths.remove_temp();
+    }
+
+    print!("{}", summary(&rows)?);
+    Ok(exit_code(&rows))
+}
+
+/// Run `check_failures` for `flash-fuzz check-failures`, returns the exit code
+pub fn run(config: &Config, options: &CheckOptions) -> i32 {
+    match futures::executor::block_on(check_failures(config, options)) {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            tracing::error!("Couldn't check the failures: {}", e);
+            EXIT_INFRASTRUCTURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn still_failing_sets_exit_code() {
+        let row = |name: &str, result| CheckRow {
+            name: name.to_string(),
+            result,
+            pruned: false,
+        };
+        let mut rows = vec![
+            CheckRow {
+                pruned: true,
+                ..row("abc", CheckResult::Fixed)
+            },
+            row("abd", CheckResult::Fixed),
+        ];
+        assert_eq!(exit_code(&rows), EXIT_CLEAN);
+
+        rows.push(row(
+            "def",
+            CheckResult::Mismatch {
+                first_differing_op: Some("#OP:3:[opcode] Add#".to_string()),
+            },
+        ));
+        rows.push(row(
+            "ghi",
+            CheckResult::Error("missing out.swf".to_string()),
+        ));
+        rows.push(row(
+            "jkl",
+            CheckResult::Flaky {
+                mismatches: 1,
+                runs: 3,
+            },
+        ));
+        assert_eq!(exit_code(&rows), EXIT_STILL_FAILING);
+        assert_eq!(
+            summary(&rows).unwrap(),
+            "failure  result    detail\n\
+             abc      fixed     moved to fixed\n\
+             abd      fixed     \n\
+             def      mismatch  differs after #OP:3:[opcode] Add#\n\
+             ghi      error     missing out.swf\n\
+             jkl      flaky     differs in 1 of 3 runs\n\
+             5 failures: 2 newly fixed, 1 still failing, 1 flaky, 1 errors\n"
+        );
+    }
+
+    #[test]
+    fn rerun_with_saved_settings() {
+        let settings = RuffleSettings {
+            player_version: 9,
+            tick_ms: 12.5,
+            ..RuffleSettings::default().with_max_frames(120)
+        };
+        let env = format!(
+            "swf_version: 8\nruffle_settings: {}\nsignature: abc\n",
+            settings
+        );
+        assert_eq!(
+            env_ruffle_settings(&env).unwrap().to_string(),
+            settings.to_string()
+        );
+
+        // Failures saved before the settings were recorded are run with the defaults
+        assert_eq!(
+            env_ruffle_settings("swf_version: 8\n").unwrap().to_string(),
+            RuffleSettings::default().to_string()
+        );
+        assert!(env_ruffle_settings("ruffle_settings: quality=Unknown\n").is_err());
+    }
+
+    #[test]
+    fn options_parse() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            CheckOptions::from_args(&args(&["--prune", "abc", "--runs", "5"])).unwrap(),
+            CheckOptions {
+                filter: Some("abc".to_string()),
+                rerun_flash: false,
+                prune: true,
+                runs: 5,
+            }
+        );
+        assert_eq!(
+            CheckOptions::from_args(&[]).unwrap().runs,
+            CHECK_FAILURE_RUNS
+        );
+        assert!(CheckOptions::from_args(&args(&["--runs", "0"])).is_err());
+        assert!(CheckOptions::from_args(&args(&["abc", "def"])).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Persisting mismatches to `RunPaths::failures` on a dedicated thread, so finding a burst of mismatches doesn't stall fuzzing
+use crate::diff_class::DiffClass;
+use crate::input_script::InputScript;
+use crate::replay::CaseRecord;
+use crate::reverify::{RUFFLE_COMMIT, RUFFLE_CORE_VERSION};
+use crate::FAILURE_QUEUE_LIMIT;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::JoinHandle;
+
+/// The most failures written between syncs to disk
+const FAILURE_BATCH_SIZE: usize = 16;
+
+/// A mismatch between ruffle and flash, to be saved to `<failures dir>/<name>`
+pub struct Failure {
+    pub name: String,
+    pub swf: Vec<u8>,
+    pub ruffle_output: String,
+    pub flash_output: String,
+    pub swf_version: u8,
+    /// The flash player that produced `flash_output`
+    pub flash_player: Option<String>,
+    /// The settings that produced `ruffle_output`
+    pub ruffle_settings: String,
+    /// The markers of the operations whose output differs, see `op_marker::differing_ops`
+    pub differing_ops: Vec<String>,
+    /// See `campaign::classified_signature`
+    pub signature: String,
+    /// How the normalized outputs differ
+    pub diff_class: DiffClass,
+    /// The input delivered while the case ran, saved as `input.txt` unless empty
+    pub input_script: InputScript,
+    /// The seed `Math.random` was replaced with, if it was, see `SwfGenerator::random_seed`
+    pub random_seed: Option<i32>,
+    /// The generator filter the case was generated with, None if it allowed everything
+    pub generator_filter: Option<String>,
+    /// How the case was generated, appended to `RunPaths::case_log`
+    pub case: CaseRecord,
+    /// The tags of `swf` with its actions disassembled, saved as `actions.txt`, see `selftest::annotated_listing`
+    pub action_listing: String,
+}
+
+enum Message {
+    Failure(Box<Failure>),
+    /// Reply once everything sent before this has been written
+    Flush(mpsc::Sender<()>),
+}
+
+/// Queues failures for the writer thread, sending blocks once `FAILURE_QUEUE_LIMIT` failures are waiting so findings are never dropped
+#[derive(Clone)]
+pub struct FailureSender {
+    tx: SyncSender<Message>,
+}
+
+impl FailureSender {
+    pub fn send(&self, failure: Failure) {
+        self.tx
+            .send(Message::Failure(Box::new(failure)))
+            .expect("Failure writer stopped");
+    }
+
+    /// Wait for all failures queued so far to be written
+    pub fn flush(&self) {
+        let (tx, rx) = mpsc::channel();
+        self.tx
+            .send(Message::Flush(tx))
+            .expect("Failure writer stopped");
+        let _ = rx.recv();
+    }
+}
+
+/// Start the writer thread saving to `failures_dir` and recording each case in `case_log`, it exits once every
+/// `FailureSender` has been dropped and the queue is drained
+pub fn spawn_failure_writer(
+    failures_dir: PathBuf,
+    case_log: PathBuf,
+) -> (FailureSender, JoinHandle<()>) {
+    let (tx, rx) = mpsc::sync_channel(FAILURE_QUEUE_LIMIT);
+    let handle = std::thread::spawn(move || write_failures(&failures_dir, &case_log, rx));
+    (FailureSender { tx }, handle)
+}
+
+/// Append the records of a batch of failures to the case log
+fn append_case_records(case_log: &Path, records: &str) -> std::io::Result<File> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(case_log)?;
+    file.write_all(records.as_bytes())?;
+    Ok(file)
+}
+
+fn write_failures(failures_dir: &Path, case_log: &Path, rx: Receiver<Message>) {
+    let mut batch = Vec::with_capacity(FAILURE_BATCH_SIZE);
+    // Block for the first message of a batch, then take whatever else is already queued
+    while let Ok(message) = rx.recv() {
+        batch.push(message);
+        while batch.len() < FAILURE_BATCH_SIZE {
+            match rx.try_recv() {
+                Ok(message) => batch.push(message),
+                Err(_) => break,
+            }
+        }
+
+        let mut written = Vec::new();
+        let mut flushes = Vec::new();
+        let mut records = String::new();
+        for message in batch.drain(..) {
+            match message {
+                Message::Failure(failure) => match write_failure(failures_dir, &failure) {
+                    Ok(files) => {
+                        written.extend(files);
+                        records.push_str(&failure.case.to_json().to_string());
+                        records.push('\n');
+                    }
+                    Err(e) => tracing::error!("Failed to save failure {}: {}", failure.name, e),
+                },
+                Message::Flush(reply) => flushes.push(reply),
+            }
+        }
+        if !records.is_empty() {
+            match append_case_records(case_log, &records) {
+                Ok(file) => written.push(file),
+                Err(e) => {
+                    tracing::error!("Failed to record cases in {}: {}", case_log.display(), e)
+                }
+            }
+        }
+
+        // Sync once per batch rather than after every file
+        for file in written {
+            if let Err(e) = file.sync_all() {
+                tracing::error!("Failed to sync failure: {}", e);
+            }
+        }
+        for reply in flushes {
+            let _ = reply.send(());
+        }
+    }
+}
+
+fn write_failure(failures_dir: &Path, failure: &Failure) -> std::io::Result<Vec<File>> {
+    let specific_failure_dir = failures_dir.join(&failure.name);
+
+    let _ = std::fs::create_dir(&specific_failure_dir);
+
+    let mut env = format!("swf_version: {}\n", failure.swf_version);
+    if let Some(flash_player) = &failure.flash_player {
+        env.push_str(&format!("flash_player: {}\n", flash_player));
+    }
+    env.push_str(&format!("ruffle_settings: {}\n", failure.ruffle_settings));
+    env.push_str(&format!("ruffle_commit: {}\n", RUFFLE_COMMIT));
+    env.push_str(&format!("ruffle_core_version: {}\n", RUFFLE_CORE_VERSION));
+    if let Some(random_seed) = failure.random_seed {
+        env.push_str(&format!(
+            "random_seed: {} (Math.random replaced)\n",
+            random_seed
+        ));
+    }
+    if let Some(generator_filter) = &failure.generator_filter {
+        env.push_str(&format!("generator_filter: {}\n", generator_filter));
+    }
+    env.push_str(&format!(
+        "case_seed: {} (generator version {})\n",
+        failure.case.seed, failure.case.generator_version
+    ));
+    env.push_str(&format!("signature: {}\n", failure.signature));
+    env.push_str(&format!("diff_class: {}\n", failure.diff_class));
+    for op in &failure.differing_ops {
+        env.push_str(&format!("differing_op: {}\n", op));
+    }
+
+    let mut files = Vec::new();
+    if !failure.input_script.is_empty() {
+        files.push(write_file(
+            &specific_failure_dir.join("input.txt"),
+            failure.input_script.to_string().as_bytes(),
+        )?);
+    }
+    files.extend([
+        write_file(&specific_failure_dir.join("out.swf"), &failure.swf)?,
+        write_file(
+            &specific_failure_dir.join("ruffle.txt"),
+            failure.ruffle_output.as_bytes(),
+        )?,
+        write_file(
+            &specific_failure_dir.join("flash.txt"),
+            failure.flash_output.as_bytes(),
+        )?,
+        write_file(&specific_failure_dir.join("env.txt"), env.as_bytes())?,
+        write_file(
+            &specific_failure_dir.join("actions.txt"),
+            failure.action_listing.as_bytes(),
+        )?,
+        // The seed and modes, enough for `flash-fuzz --replay-seed` to generate out.swf again
+        write_file(
+            &specific_failure_dir.join("meta.json"),
+            failure.case.to_json().to_string().as_bytes(),
+        )?,
+    ]);
+    Ok(files)
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> std::io::Result<File> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    Ok(file)
+}
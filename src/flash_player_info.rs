@@ -0,0 +1,83 @@
+//! Identifying the flash player binary being fuzzed against, and checking that it is a debug player
+use crate::flash_projector_runner::{open_flash_cmd, FlashSettings};
+use crate::input_script::InputScript;
+use crate::sentinel::{is_complete, RunUntil};
+use crate::swf_generator::SwfGenerator;
+use crate::MyError;
+use md5::Digest;
+use std::fmt;
+use std::path::Path;
+
+/// The platform prefixes of the `$version` string embedded in player binaries, e.g. "LNX 32,0,0,465"
+const VERSION_PREFIXES: &[&[u8]] = &[b"LNX ", b"WIN ", b"MAC "];
+
+/// Pass this argument to start even if the player doesn't look like a debug player
+pub const FORCE_ARG: &str = "--force";
+
+/// The identity of the flash player binary
+#[derive(Debug, Clone)]
+pub struct FlashPlayerInfo {
+    /// md5 of the binary
+    pub md5: Digest,
+    /// The embedded version string, if one could be found
+    pub version: Option<String>,
+}
+
+impl fmt::Display for FlashPlayerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:x})",
+            self.version.as_deref().unwrap_or("unknown version"),
+            self.md5
+        )
+    }
+}
+
+/// Find the `$version` string embedded in a player binary
+fn find_version(binary: &[u8]) -> Option<String> {
+    binary.windows(4).enumerate().find_map(|(i, window)| {
+        if !VERSION_PREFIXES.contains(&window) {
+            return None;
+        }
+        let digits = binary[i + 4..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b',')
+            .count();
+        let version = &binary[i..i + 4 + digits];
+        // Major, minor, build and revision
+        if digits > 0 && version.iter().filter(|b| **b == b',').count() == 3 {
+            Some(String::from_utf8_lossy(version).into_owned())
+        } else {
+            None
+        }
+    })
+}
+
+/// Hash the projector that `settings` run and extract its version
+pub fn identify_flash_player(settings: &FlashSettings) -> Result<FlashPlayerInfo, MyError> {
+    let binary = std::fs::read(&settings.binary)?;
+    Ok(FlashPlayerInfo {
+        md5: md5::compute(&binary),
+        version: find_version(&binary),
+    })
+}
+
+/// Run a case that only traces the completion sentinel, a release player never logs traces so this will fail
+pub async fn smoke_test(settings: &FlashSettings, temp_dir: &Path) -> Result<bool, MyError> {
+    let mut swf = Vec::new();
+    SwfGenerator::new()
+        .smoke_test_swf(&mut swf)
+        .expect("Failed to generate smoke test swf");
+    let (output, _, _) = open_flash_cmd(
+        &swf,
+        settings,
+        temp_dir,
+        &InputScript::default(),
+        None,
+        RunUntil::Sentinel,
+        None,
+    )
+    .await?;
+    Ok(is_complete(&output))
+}
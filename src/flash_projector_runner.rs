@@ -1,10 +1,277 @@
-use crate::{MyError, DELETE_SWF, FLASH_PLAYER_BINARY};
+use crate::heartbeat::WorkerHeartbeat;
+use crate::input_script::{key_sym, InputEvent, InputScript};
+use crate::sentinel::{check_nonce, truncate_to_sentinel, RunUntil, CASE_COMPLETE_SENTINEL};
+use crate::swf_generator::INPUT_FRAME_RATE;
+use crate::trace_bytes::{contains_text, escape_trace};
+use crate::{delete_swf, MyError, FLASH_PLAYER_BINARY, MAX_OUTPUT_LEN, RUN_TIMEOUT};
 ///! Support for running a fuzz case under flash projector and gathering output
-use std::io::Read;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
 use std::time::{Duration, Instant};
-use subprocess::{Exec, Redirection};
+use subprocess::{Exec, ExitStatus, NullFile, Popen, Redirection};
 
-pub async fn open_flash_cmd(bytes: &[u8], worker_id: u32) -> Result<(String, Duration), MyError> {
+/// How long to wait for the projector window to appear before giving up on sending it input
+const INPUT_WINDOW_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the projector is given to start up before the frames of a `RunUntil::Frames` run are counted
+const FLASH_STARTUP_TIME: Duration = Duration::from_secs(2);
+
+/// How often the projector is checked for having exited or run out of time. Output is looked at as soon as it
+/// arrives, so this doesn't hold up a case that traces the sentinel
+const FLASH_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How flash is run, see `config`
+#[derive(Debug, Clone)]
+pub struct FlashSettings {
+    /// The debug projector, `FLASH_PLAYER_BINARY` by default
+    pub binary: PathBuf,
+    /// Should the input be removed after running a test, see `delete_swf()`
+    pub delete_swf: bool,
+}
+
+impl Default for FlashSettings {
+    fn default() -> Self {
+        Self {
+            binary: PathBuf::from(FLASH_PLAYER_BINARY),
+            delete_swf: delete_swf(),
+        }
+    }
+}
+
+/// The input written for flash to run, removed when dropped unless `FlashSettings::delete_swf` is false.
+/// Dropping on every path (early returns, panics) means inputs aren't leaked into the temp dir
+pub struct TempSwf {
+    path: PathBuf,
+    delete: bool,
+}
+
+impl TempSwf {
+    /// Write `bytes` to a worker's temp dir, see `RunPaths::worker_temp`
+    fn create(bytes: &[u8], temp_dir: &Path, delete: bool) -> Result<Self, MyError> {
+        let path = temp_dir.join("test.swf");
+        std::fs::write(&path, bytes)?;
+        Ok(Self { path, delete })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempSwf {
+    fn drop(&mut self) {
+        if self.delete {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Find the window of the projector with the given pid
+fn find_window(pid: u32) -> Option<String> {
+    let start = Instant::now();
+    while start.elapsed() < INPUT_WINDOW_TIMEOUT {
+        let window = Exec::cmd("xdotool")
+            .args(&["search", "--onlyvisible", "--pid", &pid.to_string()])
+            .stderr(NullFile)
+            .capture()
+            .ok()
+            .and_then(|capture| capture.stdout_str().lines().next().map(str::to_owned));
+        if window.is_some() {
+            return window;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    None
+}
+
+/// Send the input to the projector window with xdotool, each event is sent half a frame before the frame it is
+/// for. The window shows up some time after the first frame runs, so this is best effort
+fn send_input(pid: u32, input: &InputScript) {
+    let window = match find_window(pid) {
+        Some(window) => window,
+        None => {
+            tracing::warn!("No projector window found, running without input");
+            return;
+        }
+    };
+    let start = Instant::now();
+    for (frame, event) in input.events() {
+        let send_at = Duration::from_secs_f32((*frame as f32 - 0.5) / INPUT_FRAME_RATE);
+        if let Some(wait) = send_at.checked_sub(start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+
+        let mut args = vec![];
+        match *event {
+            InputEvent::KeyDown(code) | InputEvent::KeyUp(code) => {
+                let command = if matches!(event, InputEvent::KeyDown(_)) {
+                    "keydown"
+                } else {
+                    "keyup"
+                };
+                let sym = key_sym(code).expect("Input key has no keysym");
+                args.extend([command, "--window", &window, sym].map(str::to_owned));
+            }
+            InputEvent::MouseMove { x, y }
+            | InputEvent::MouseDown { x, y }
+            | InputEvent::MouseUp { x, y } => {
+                args.extend(["mousemove", "--window", &window].map(str::to_owned));
+                args.extend([x.to_string(), y.to_string()]);
+                match event {
+                    InputEvent::MouseDown { .. } => {
+                        args.extend(["mousedown", "1"].map(str::to_owned))
+                    }
+                    InputEvent::MouseUp { .. } => args.extend(["mouseup", "1"].map(str::to_owned)),
+                    _ => {}
+                }
+            }
+        }
+        if let Err(e) = Exec::cmd("xdotool").args(&args).stderr(NullFile).join() {
+            tracing::warn!("Failed to send input to the projector: {}", e);
+        }
+    }
+}
+
+/// How long the projector takes to run `frames` frames of the movie, going by the frame rate in its header.
+/// The projector runs in real time, so unlike ruffle it can only be stopped after roughly that many frames
+fn frames_duration(bytes: &[u8], frames: u32) -> Duration {
+    // A frame rate of 0 (or a header that can't be read) is taken as 1fps, which only makes the run longer
+    let frame_rate = swf::decompress_swf(bytes)
+        .map(|swf_buf| swf_buf.header.frame_rate().to_f32())
+        .unwrap_or_default()
+        .max(1.0);
+    FLASH_STARTUP_TIME + Duration::from_secs_f32(frames as f32 / frame_rate)
+}
+
+/// The projector's output as it is read, the reader thread appends to it and wakes whoever waits on `changed`
+#[derive(Default)]
+struct CapturedOutput {
+    bytes: Mutex<Vec<u8>>,
+    changed: Condvar,
+}
+
+/// Read the projector's output into `output` until it closes stdout, or at most one byte past the cap so an
+/// overflow is seen the same way as ruffle's. The bytes are kept as they are: a read can end partway through a
+/// UTF-8 sequence, or the sentinel, so they are only converted once the output is complete
+fn read_output(mut stdout: impl Read, output: &CapturedOutput) {
+    let mut buf = [0; 4096];
+    loop {
+        let len = match stdout.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        let mut bytes = output.bytes.lock().unwrap();
+        let remaining = (MAX_OUTPUT_LEN + 1).saturating_sub(bytes.len());
+        bytes.extend_from_slice(&buf[..len.min(remaining)]);
+        output.changed.notify_all();
+        if bytes.len() > MAX_OUTPUT_LEN {
+            break;
+        }
+    }
+}
+
+/// Looks through the output as it grows for the end of the case, scanning only what arrived since the last look
+struct OutputScan {
+    run_until: RunUntil,
+    /// How much of the output has been scanned for the sentinel
+    scanned: usize,
+    complete: bool,
+}
+
+impl OutputScan {
+    fn new(run_until: RunUntil) -> Self {
+        Self {
+            run_until,
+            scanned: 0,
+            complete: false,
+        }
+    }
+
+    /// Is the output over the cap, or has the case run to completion
+    fn finished(&mut self, bytes: &[u8]) -> bool {
+        if self.run_until == RunUntil::Sentinel && !self.complete {
+            // Back up far enough to see a sentinel split across two reads
+            let start = self
+                .scanned
+                .saturating_sub(CASE_COMPLETE_SENTINEL.len() - 1);
+            self.complete = contains_text(&bytes[start..], CASE_COMPLETE_SENTINEL);
+            self.scanned = bytes.len();
+        }
+        bytes.len() > MAX_OUTPUT_LEN || self.complete
+    }
+}
+
+/// Capture the output of `popen` until the case is finished (see `OutputScan`), it exits, or it runs past
+/// `frames_limit` or `RUN_TIMEOUT`, then kill it. Returns its exit status if it exited by itself, and its output
+fn capture_output(
+    mut popen: Popen,
+    run_until: RunUntil,
+    frames_limit: Option<Duration>,
+) -> Result<(Option<ExitStatus>, Vec<u8>), MyError> {
+    let start_time = Instant::now();
+    // Read on another thread, so that a projector which never exits can still be stopped
+    let output = Arc::new(CapturedOutput::default());
+    let stdout = popen.stdout.take().expect("Flash stdout isn't piped");
+    let reader = {
+        let output = Arc::clone(&output);
+        std::thread::spawn(move || read_output(stdout, &output))
+    };
+
+    let mut scan = OutputScan::new(run_until);
+    let mut exit_status = None;
+    let mut bytes = output.bytes.lock().unwrap();
+    loop {
+        if let Some(status) = popen.poll() {
+            exit_status = Some(status);
+            break;
+        }
+
+        if scan.finished(&bytes) {
+            break;
+        }
+
+        let elapsed = Instant::now().duration_since(start_time);
+        if matches!(frames_limit, Some(limit) if elapsed > limit) {
+            break;
+        }
+        if elapsed > RUN_TIMEOUT {
+            println!("Flash timed out, run > {:?}", RUN_TIMEOUT);
+            break;
+        }
+
+        bytes = output
+            .changed
+            .wait_timeout(bytes, FLASH_POLL_INTERVAL)
+            .unwrap()
+            .0;
+    }
+    drop(bytes);
+
+    popen.kill()?;
+    popen.terminate()?;
+    drop(popen);
+    // The projector is gone, so the reader sees the end of its output
+    reader.join().expect("Flash output reader panicked");
+    let bytes = std::mem::take(&mut *output.bytes.lock().unwrap());
+    Ok((exit_status, bytes))
+}
+
+/// Run the case under flash until `run_until`, the input is written to `temp_dir` and the returned `TempSwf` keeps it around until it is dropped.
+/// The events in `input` are sent to the projector window while it runs, see `send_input`.
+/// The projector's pid is kept in `heartbeat` while it runs, so that it can be killed if it wedges.
+/// If a `nonce` is given the output must start with it, see `check_nonce`
+pub async fn open_flash_cmd(
+    bytes: &[u8],
+    settings: &FlashSettings,
+    temp_dir: &Path,
+    input: &InputScript,
+    heartbeat: Option<&WorkerHeartbeat>,
+    run_until: RunUntil,
+    nonce: Option<u64>,
+) -> Result<(String, Duration, TempSwf), MyError> {
     let flash_start = Instant::now();
 
     // let mut log_path = dirs_next::config_dir().expect("No config dir");
@@ -15,58 +282,126 @@ pub async fn open_flash_cmd(bytes: &[u8], worker_id: u32) -> Result<(String, Dur
     //     .truncate(true)
     //     .open(&log_path)?;
 
-    let path = format!("./run/test-{}.swf", worker_id);
-    std::fs::write(&path, bytes)?;
+    let swf = TempSwf::create(bytes, temp_dir, settings.delete_swf)?;
 
-    let cmd = Exec::cmd(FLASH_PLAYER_BINARY)
+    let cmd = Exec::cmd(&settings.binary)
         .env("LD_PRELOAD", "./utils/path-mapping.so")
         // .env("DISPLAY", ":2")
-        .args(&[&path])
+        .arg(swf.path())
         .stderr(Redirection::File(std::fs::File::open("/dev/null").unwrap()))
         .stdout(Redirection::Pipe)
         .detached();
 
-    let start_time = Instant::now();
-    let mut popen = cmd.popen()?;
-
-    let mut log_content = "".to_string();
+    let popen = cmd.popen()?;
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.set_flash_pid(popen.pid());
+    }
+    let input_sender = match popen.pid() {
+        Some(pid) if !input.is_empty() => {
+            let input = input.clone();
+            Some(std::thread::spawn(move || send_input(pid, &input)))
+        }
+        _ => None,
+    };
 
-    loop {
-        popen
-            .stdout
-            .as_mut()
-            .unwrap()
-            .read_to_string(&mut log_content)?;
+    let frames_limit = match run_until {
+        RunUntil::Sentinel => None,
+        RunUntil::Frames(frames) => Some(frames_duration(bytes, frames)),
+    };
+    let captured = capture_output(popen, run_until, frames_limit);
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.set_flash_pid(None);
+    }
+    // Don't let input meant for this case reach the next one's window
+    if let Some(input_sender) = input_sender {
+        let _ = input_sender.join();
+    }
+    let (exit_status, log_bytes) = captured?;
 
-        if log_content.contains("#CASE_COMPLETE#") {
-            break;
+    if log_bytes.len() > MAX_OUTPUT_LEN {
+        tracing::debug!("Flash output overflowed, > {} bytes", MAX_OUTPUT_LEN);
+    }
+    // Exiting with an error after the case finished isn't a crash, it was about to be killed anyway
+    if let Some(status) = exit_status {
+        if !status.success() && !OutputScan::new(run_until).finished(&log_bytes) {
+            tracing::info!("Flash crashed with {:?}", status);
+            return Err(MyError::FlashCrash);
         }
+    }
 
-        if Instant::now().duration_since(start_time) > Duration::from_secs(30) {
-            println!("Flash timed out, run > 30s");
-            break;
-        }
+    let log_content = flash_log_content(&log_bytes, run_until, nonce)?;
+    Ok((log_content, Instant::now() - flash_start, swf))
+}
 
-        if let Ok(Some(ex)) = popen.wait_timeout(Duration::from_millis(100)) {
-            if !ex.success() {
-                tracing::info!("Flash crashed with {:?}", ex);
-                if DELETE_SWF {
-                    std::fs::remove_file(&path)?;
-                }
-                return Err(MyError::FlashCrash);
-            } else {
-                break;
-            }
-        }
+/// The output of a case as text, escaped with `escape_trace` as it needn't be UTF-8, and cut at the sentinel when
+/// running until it
+pub(crate) fn flash_log_content(
+    log_bytes: &[u8],
+    run_until: RunUntil,
+    nonce: Option<u64>,
+) -> Result<String, MyError> {
+    let mut log_content = escape_trace(log_bytes);
+    if run_until == RunUntil::Sentinel {
+        truncate_to_sentinel(&mut log_content);
     }
+    if let Some(nonce) = nonce {
+        check_nonce(&log_content, nonce)?;
+    }
+    Ok(log_content)
+}
 
-    popen.kill()?;
-    popen.terminate()?;
-    drop(popen);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if DELETE_SWF {
-        std::fs::remove_file(&path)?;
+    /// A stand-in for the projector, a shell running `script` with its stdout piped
+    fn fake_projector(script: &str) -> Popen {
+        Exec::cmd("sh")
+            .args(&["-c", script])
+            .stdout(Redirection::Pipe)
+            .popen()
+            .unwrap()
+    }
+
+    #[test]
+    fn sentinel_split_across_writes() {
+        let start = Instant::now();
+        // `exec` so that killing the shell closes its stdout
+        let popen =
+            fake_projector("printf 'a\\n#CASE_'; sleep 0.2; printf 'COMPLETE#\\n'; exec sleep 20");
+        let (exit_status, bytes) = capture_output(popen, RunUntil::Sentinel, None).unwrap();
+        // Stopped at the sentinel, rather than waiting for the script to exit
+        assert!(exit_status.is_none());
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(bytes, b"a\n#CASE_COMPLETE#\n");
     }
 
-    Ok((log_content, Instant::now() - flash_start))
+    #[test]
+    fn invalid_utf8_mid_stream() {
+        // The two bytes of "é" are written separately, with an invalid byte between two lines
+        let popen = fake_projector(
+            "printf 'caf\\303'; sleep 0.1; printf '\\251\\n\\377\\n'; sleep 0.1; printf '#CASE_COMPLETE#\\nafter\\n'",
+        );
+        let (_, bytes) = capture_output(popen, RunUntil::Sentinel, None).unwrap();
+        let bytes = &bytes[..bytes.len().min(24)];
+        assert_eq!(bytes, b"caf\xc3\xa9\n\xff\n#CASE_COMPLETE#\n");
+        assert_eq!(
+            flash_log_content(bytes, RunUntil::Sentinel, None).unwrap(),
+            format!(
+                "café\n{}\n{}\n",
+                escape_trace(b"\xff"),
+                CASE_COMPLETE_SENTINEL
+            )
+        );
+    }
+
+    #[test]
+    fn scan_sees_sentinel_across_reads() {
+        let mut scan = OutputScan::new(RunUntil::Sentinel);
+        let mut bytes = b"trace\n#CASE_COMP".to_vec();
+        assert!(!scan.finished(&bytes));
+        bytes.extend_from_slice(b"LETE#");
+        assert!(scan.finished(&bytes));
+        assert!(!OutputScan::new(RunUntil::Frames(1)).finished(&bytes));
+    }
 }
@@ -1,26 +1,288 @@
-use crate::ruffle_runner::open_ruffle;
-use crate::{open_flash_cmd, MyError, SwfGenerator, FAILURES_DIR, SINGLE_ITER, TIMING_DEBUG};
-use md5::Digest;
+use crate::action_budget::over_action_budget;
+use crate::attempted::AttemptedSet;
+use crate::campaign::{classified_signature, BudgetTracker, SignatureCount};
+use crate::config::Config;
+use crate::diff_class::{classify, DIFF_CLASSES};
+use crate::diff_signature::{diff_signature, DiffSignatures};
+use crate::failure_writer::{Failure, FailureSender};
+use crate::flash_player_info::FlashPlayerInfo;
+use crate::flash_projector_runner::TempSwf;
+use crate::generator_filter::GeneratorFilter;
+use crate::heartbeat::{WorkerHeartbeat, WorkerPhase};
+use crate::input_script::InputScript;
+use crate::mode_scheduler::ModeScheduler;
+use crate::normalize::normalize;
+use crate::op_marker::{differing_ops, marker_mode};
+use crate::replay::CaseRecord;
+use crate::ruffle_runner::{open_ruffle, RuffleSettings};
+use crate::selftest::annotated_listing;
+use crate::sentinel::{is_complete, RunUntil};
+use crate::static_methods::StaticCoverage;
+use crate::stats::CaseTimings;
+use crate::swf_generator::{GeneratorMode, GENERATOR_VERSION};
+use crate::{
+    open_flash_cmd, MyError, SwfGenerator, ADAPTIVE_MODE_WEIGHTS, ADAPTIVE_WEIGHT_INTERVAL,
+    ALL_SWF_VERSIONS, INPUT_AGAINST_FLASH, MAX_OUTPUT_LEN, MISMATCH_DIFF_CLASSES,
+    NUMERIC_NOISE_EPSILON, RUFFLE_DETERMINISM_CHECK_RATE, SIGNATURE_EXEMPLARS, TIMING_DEBUG,
+};
+use rand::Rng;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::path::PathBuf;
-use std::str::FromStr;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 /// The fuzz state shared between threads
 #[derive(Default)]
 pub struct SharedFuzzState {
     /// All of the files that we have tested so far
-    attempted: RwLock<Vec<Digest>>,
+    pub attempted: AttemptedSet,
+    /// Every case seed is derived from this, see `next_case_seed`
+    pub master_seed: u64,
 
     pub iterations: AtomicUsize,
     pub total_iterations: AtomicUsize,
+    /// Every case finished since the start, unlike `iterations` this isn't reset by the stats logger
+    pub cases_run: AtomicUsize,
     pub mismatches: AtomicUsize,
+    /// Cases whose output differed, by how it differed, see `diff_class`. Only the classes in
+    /// `MISMATCH_DIFF_CLASSES` are counted in `mismatches`
+    pub diff_classes: [AtomicUsize; DIFF_CLASSES.len()],
     pub flash_crashes: AtomicUsize,
+    pub ruffle_nondeterministic: AtomicUsize,
+    /// Cases where ruffle timed out or stopped before tracing the completion sentinel
+    pub ruffle_incomplete: AtomicUsize,
+    /// Cases that weren't run as they have more than `MAX_CASE_ACTIONS`, see `action_budget`
+    pub ruffle_script_limit: AtomicUsize,
+    /// Cases where flash timed out or exited before tracing the completion sentinel
+    pub flash_incomplete: AtomicUsize,
+    /// Cases where either player logged more than `MAX_OUTPUT_LEN`
+    pub output_overflows: AtomicUsize,
+    /// Input cases that were only checked for determinism in ruffle, see `INPUT_AGAINST_FLASH`
+    pub ruffle_only: AtomicUsize,
+    /// Cases whose output didn't start with their nonce even when rerun, see `sentinel::check_nonce`
+    pub capture_errors: AtomicUsize,
+    /// The flash player binary that cases are compared against
+    pub flash_player: Option<FlashPlayerInfo>,
+    /// Compared cases and mismatches for each of `ALL_SWF_VERSIONS`
+    pub version_stats: [VersionStats; ALL_SWF_VERSIONS.len()],
+    /// Compared sub-cases and mismatches for each generator mode, so `MODE_WEIGHTS` can be tuned by yield
+    pub mode_stats: ModeStats,
+    /// The mode weights cases are generated with, see `mode_scheduler`
+    pub mode_scheduler: ModeScheduler,
+    /// How long cases that ran in both players took
+    pub timings: CaseTimings,
+    /// The heartbeat of each worker, indexed by worker id
+    pub workers: Vec<WorkerHeartbeat>,
+    /// Where cases, stats and each worker's inputs are written, how flash is run and so on
+    pub config: Config,
+    /// Workers stop once this is spent, there is no limit outside of campaigns
+    pub budget: Option<BudgetTracker>,
+    /// Set on ctrl-c, workers stop once the case they are running is done, see `stop_on_ctrl_c`
+    pub interrupted: AtomicBool,
+    /// The mismatches found with each signature, see `campaign::mismatch_signature`. Only the ops and how they
+    /// differ, so they can be compared with earlier campaigns and suppressed
+    pub signatures: Mutex<BTreeMap<String, SignatureCount>>,
+    /// The mismatches found with each diff signature, which adds the differing text to tell bugs in the same op apart
+    /// and decides which failures are saved, see `diff_signature`
+    pub diff_signatures: DiffSignatures,
+    /// The static method calls that have been run, see `static_methods`
+    pub static_coverage: StaticCoverage,
+    /// What the generator may pick, see `generator_filter`
+    pub generator_filter: GeneratorFilter,
 }
 
-pub fn fuzz(shared_state: Arc<SharedFuzzState>, worker_id: u32) -> Result<(), Box<dyn Error>> {
+#[derive(Default)]
+pub struct VersionStats {
+    pub cases: AtomicUsize,
+    pub mismatches: AtomicUsize,
+}
+
+/// The sub-cases and mismatches of each of `GeneratorMode::ALL`. A mismatch counts against the mode of the first
+/// operation whose output differs, see `op_marker::marker_mode`
+pub struct ModeStats {
+    pub sub_cases: Vec<AtomicUsize>,
+    pub mismatches: Vec<AtomicUsize>,
+}
+
+impl Default for ModeStats {
+    fn default() -> Self {
+        let counters = || {
+            GeneratorMode::ALL
+                .iter()
+                .map(|_| AtomicUsize::new(0))
+                .collect()
+        };
+        Self {
+            sub_cases: counters(),
+            mismatches: counters(),
+        }
+    }
+}
+
+impl ModeStats {
+    pub fn sub_cases(&self, mode: GeneratorMode) -> &AtomicUsize {
+        &self.sub_cases[mode.index()]
+    }
+
+    pub fn mismatches(&self, mode: GeneratorMode) -> &AtomicUsize {
+        &self.mismatches[mode.index()]
+    }
+
+    /// The counts and current weight of the modes that have had any sub-cases or whose weight has been adapted
+    pub fn to_json(&self, scheduler: &ModeScheduler) -> Value {
+        GeneratorMode::ALL
+            .iter()
+            .filter(|mode| {
+                self.sub_cases(**mode).load(Ordering::SeqCst) > 0
+                    || scheduler.weight(**mode).load(Ordering::SeqCst) != mode.weight()
+            })
+            .map(|mode| {
+                json!({
+                    "mode": mode.name(),
+                    "sub_cases": self.sub_cases(*mode).load(Ordering::SeqCst),
+                    "mismatches": self.mismatches(*mode).load(Ordering::SeqCst),
+                    "weight": scheduler.weight(*mode).load(Ordering::SeqCst),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The exit code after stopping on ctrl-c, as if the process had been killed by SIGINT
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Stop the workers on ctrl-c, so that `run_workers` still writes the checkpoint and state and saves the queued
+/// failures before exiting with `INTERRUPTED_EXIT_CODE`. A second ctrl-c exits straight away
+pub fn stop_on_ctrl_c(state: Arc<SharedFuzzState>) {
+    if let Err(e) = ctrlc::set_handler(move || {
+        if state.interrupted.swap(true, Ordering::SeqCst) {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        tracing::info!(
+            "Interrupted, stopping once the running cases are done, ctrl-c again to exit now"
+        );
+    }) {
+        tracing::warn!("Failed to set the ctrl-c handler: {}", e);
+    }
+}
+
+impl SharedFuzzState {
+    /// The state of a run with a worker for each of `Config::threads`
+    pub fn with_flash_player(flash_player: FlashPlayerInfo, config: Config) -> Self {
+        Self {
+            flash_player: Some(flash_player),
+            master_seed: rand::thread_rng().gen(),
+            workers: (0..config.threads)
+                .map(|_| WorkerHeartbeat::default())
+                .collect(),
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn worker(&self, worker_id: u32) -> &WorkerHeartbeat {
+        &self.workers[worker_id as usize]
+    }
+
+    /// Count a finished case, for the stats and for the worker that ran it
+    fn case_done(&self, worker_id: u32) {
+        self.iterations.fetch_add(1, Ordering::SeqCst);
+        let cases_run = self.cases_run.fetch_add(1, Ordering::SeqCst) + 1;
+        self.worker(worker_id)
+            .iterations
+            .fetch_add(1, Ordering::SeqCst);
+        if ADAPTIVE_MODE_WEIGHTS && cases_run % ADAPTIVE_WEIGHT_INTERVAL == 0 {
+            self.mode_scheduler.adapt(&self.mode_stats);
+        }
+    }
+
+    /// The seed of the next case the worker generates, derived from the master seed and how many seeds the worker has
+    /// drawn, so that a campaign restored from a checkpoint generates the cases it would have generated next
+    pub fn next_case_seed(&self, worker_id: u32) -> u64 {
+        let index = self
+            .worker(worker_id)
+            .seeds_drawn
+            .fetch_add(1, Ordering::SeqCst);
+        case_seed(self.master_seed, worker_id, index)
+    }
+
+    /// Should the workers stop, as the run was interrupted or the campaign's budget has been spent
+    fn should_stop(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+            || matches!(&self.budget, Some(budget) if budget.spent(self.cases_run.load(Ordering::SeqCst)))
+    }
+
+    /// Count a mismatch against its signature
+    fn record_signature(&self, signature: &str, case_name: &str) {
+        let mut signatures = self.signatures.lock().unwrap();
+        let count = signatures
+            .entry(signature.to_string())
+            .or_insert_with(|| SignatureCount {
+                mismatches: 0,
+                first_case: case_name.to_string(),
+            });
+        count.mismatches += 1;
+    }
+
+    /// The stats for a version from `ALL_SWF_VERSIONS`, None for any other version
+    pub fn version_stats(&self, swf_version: u8) -> Option<&VersionStats> {
+        let index = ALL_SWF_VERSIONS.iter().position(|v| *v == swf_version)?;
+        Some(&self.version_stats[index])
+    }
+}
+
+/// The seed of a worker's `index`th case. Mixed with SplitMix64's finalizer, so that neighbouring workers and cases
+/// get unrelated seeds
+fn case_seed(master_seed: u64, worker_id: u32, index: u64) -> u64 {
+    let mut z = master_seed ^ ((worker_id as u64) << 48) ^ index;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// The result of running a single case through both players
+enum CaseOutcome {
+    /// Both players ran the case, with the given outputs
+    Compared {
+        ruffle: (String, Duration),
+        flash: Result<(String, Duration, TempSwf), MyError>,
+    },
+    /// Running the case through ruffle twice produced different output, so it wasn't compared with flash
+    RuffleNondeterministic { first: String, second: String },
+    /// The case was run through ruffle twice with the same output, and not compared with flash
+    RuffleOnly,
+}
+
+/// Save a case that wasn't compared with flash, along with its input and the given outputs
+pub(crate) fn save_case(
+    dir: &Path,
+    name: &str,
+    swf: &[u8],
+    input: &InputScript,
+    outputs: &[(&str, &str)],
+) -> Result<(), Box<dyn Error>> {
+    let case_dir = dir.join(name);
+
+    let _ = std::fs::create_dir(&case_dir);
+
+    std::fs::write(case_dir.join("out.swf"), swf)?;
+    if !input.is_empty() {
+        std::fs::write(case_dir.join("input.txt"), input.to_string())?;
+    }
+    for (file_name, output) in outputs {
+        std::fs::write(case_dir.join(file_name), output)?;
+    }
+    Ok(())
+}
+
+pub fn fuzz(
+    shared_state: Arc<SharedFuzzState>,
+    failures: FailureSender,
+    worker_id: u32,
+) -> Result<(), Box<dyn Error>> {
     let mut overall_duration = Duration::ZERO;
     let mut ruffle_duration = Duration::ZERO;
     let mut flash_duration = Duration::ZERO;
@@ -28,24 +290,34 @@ pub fn fuzz(shared_state: Arc<SharedFuzzState>, worker_id: u32) -> Result<(), Bo
     let mut swf_content = Vec::with_capacity(1024);
     let mut ruffle_content = Vec::with_capacity(1024);
     let mut flash_content = Vec::with_capacity(1024);
-    let mut swf_generator = SwfGenerator::new();
+    let mut swf_generator = SwfGenerator::new()
+        .with_filter(shared_state.generator_filter.clone())
+        .with_random_strings(shared_state.config.random_strings);
+    let heartbeat = shared_state.worker(worker_id);
+    let temp_dir = shared_state.config.paths.worker_temp(worker_id);
 
     loop {
+        if shared_state.should_stop() {
+            return Ok(());
+        }
+        heartbeat.beat(WorkerPhase::Generating);
         let start = Instant::now();
         // Keep generating until we produce a unique swf
         let mut warning_shown = false;
 
-        let swf_md5 = loop {
+        let (swf_md5, swf_version, case_seed) = loop {
             swf_content.clear();
 
             swf_generator.reset();
-            swf_generator.next_swf(&mut swf_content)?;
+            // Each case has a seed of its own, so that it can be generated again from its record
+            let case_seed = shared_state.next_case_seed(worker_id);
+            swf_generator.reseed(case_seed);
+            swf_generator.set_mode_weights(shared_state.mode_scheduler.weights());
+            let swf_version = swf_generator.next_swf(&mut swf_content)?;
             let swf_md5 = md5::compute(&swf_content);
             // If its unique
-            if !shared_state.attempted.read().unwrap().contains(&swf_md5) {
-                // Store it
-                shared_state.attempted.write().unwrap().push(swf_md5);
-                break swf_md5;
+            if shared_state.attempted.insert(swf_md5) {
+                break (swf_md5, swf_version, case_seed);
             }
             if Instant::now().duration_since(start) > Duration::from_secs(10) && !warning_shown {
                 tracing::info!("No unique swfs generated in 10 seconds, are we done?");
@@ -57,23 +329,141 @@ pub fn fuzz(shared_state: Arc<SharedFuzzState>, worker_id: u32) -> Result<(), Bo
             }
         };
 
+        // Ruffle can't stop a case that runs too many actions in a frame, so such cases aren't run at all
+        if over_action_budget(&swf_content) {
+            tracing::info!("Case over the action budget @ {:x}", swf_md5);
+            shared_state
+                .ruffle_script_limit
+                .fetch_add(1, Ordering::SeqCst);
+            shared_state.case_done(worker_id);
+            continue;
+        }
+
         ruffle_content.clear();
         ruffle_content.extend_from_slice(&swf_content);
         flash_content.clear();
         flash_content.extend_from_slice(&swf_content);
 
-        let (ruffle_result, flash_result) = futures::executor::block_on(async {
-            let ruffle_res = open_ruffle(&ruffle_content).await;
-            let flash_res = open_flash_cmd(&flash_content, worker_id).await;
+        let input = swf_generator.input_script();
+        // Input can't be delivered to flash at exactly the same point, so by default it is only checked against ruffle
+        let ruffle_only = !input.is_empty() && !INPUT_AGAINST_FLASH;
+        let check_determinism =
+            ruffle_only || rand::thread_rng().gen_bool(RUFFLE_DETERMINISM_CHECK_RATE);
+        let ruffle_settings = RuffleSettings::random(&mut rand::thread_rng())
+            .with_max_frames(swf_generator.frame_budget());
+        let nonce = Some(swf_generator.nonce());
+        let run_case = || async {
+            heartbeat.beat(WorkerPhase::Ruffle);
+            let ruffle_res = open_ruffle(
+                &ruffle_content,
+                &ruffle_settings,
+                input,
+                RunUntil::Sentinel,
+                nonce,
+            )
+            .await?;
+            if check_determinism {
+                let (second_res, _) = open_ruffle(
+                    &ruffle_content,
+                    &ruffle_settings,
+                    input,
+                    RunUntil::Sentinel,
+                    nonce,
+                )
+                .await?;
+                if second_res != ruffle_res.0 {
+                    return Ok::<_, MyError>(CaseOutcome::RuffleNondeterministic {
+                        first: ruffle_res.0,
+                        second: second_res,
+                    });
+                }
+            }
+            if ruffle_only {
+                return Ok(CaseOutcome::RuffleOnly);
+            }
+            heartbeat.beat(WorkerPhase::Flash);
+            let flash_res = open_flash_cmd(
+                &flash_content,
+                &shared_state.config.flash,
+                &temp_dir,
+                input,
+                Some(heartbeat),
+                RunUntil::Sentinel,
+                nonce,
+            )
+            .await;
+            if let Err(MyError::CaptureError) = flash_res {
+                return Err(MyError::CaptureError);
+            }
+
+            Ok(CaseOutcome::Compared {
+                ruffle: ruffle_res,
+                flash: flash_res,
+            })
+        };
+        // Output from another case getting into the capture is a problem with the harness rather than the case,
+        // so it is run again once before giving up on it
+        let outcome = match futures::executor::block_on(run_case()) {
+            Err(MyError::CaptureError) => {
+                tracing::info!("Capture error @ {:x}, rerunning", swf_md5);
+                futures::executor::block_on(run_case())
+            }
+            outcome => outcome,
+        };
+        let outcome = match outcome {
+            Err(MyError::CaptureError) => {
+                tracing::info!("Capture error on rerun @ {:x}", swf_md5);
+                shared_state.capture_errors.fetch_add(1, Ordering::SeqCst);
+                shared_state.case_done(worker_id);
+                continue;
+            }
+            outcome => outcome?,
+        };
+        shared_state
+            .static_coverage
+            .record(swf_generator.static_calls());
+        heartbeat.beat(WorkerPhase::Comparing);
 
-            (ruffle_res, flash_res)
-        });
+        let (ruffle_result, flash_result) = match outcome {
+            CaseOutcome::Compared { ruffle, flash } => (ruffle, flash),
+            CaseOutcome::RuffleNondeterministic { first, second } => {
+                let new_name = format!("{:x}", swf_md5);
+                tracing::info!("Ruffle nondeterminism detected @ {}", new_name);
+                shared_state
+                    .ruffle_nondeterministic
+                    .fetch_add(1, Ordering::SeqCst);
+                save_case(
+                    &shared_state.config.paths.nondeterministic,
+                    &new_name,
+                    &swf_content,
+                    input,
+                    &[("ruffle.txt", &first), ("ruffle2.txt", &second)],
+                )?;
+                shared_state.case_done(worker_id);
+                continue;
+            }
+            CaseOutcome::RuffleOnly => {
+                shared_state.ruffle_only.fetch_add(1, Ordering::SeqCst);
+                shared_state.case_done(worker_id);
+                continue;
+            }
+        };
 
-        let (flash_res, flash_dur) = match flash_result {
+        let (flash_res, flash_dur, flash_swf) = match flash_result {
             Ok(x) => Ok(x),
             Err(MyError::FlashCrash) => {
-                tracing::info!("Flash crash detected, ignoring input");
+                // Not compared, but kept as a crash in flash is still worth looking at
+                let new_name = format!("{:x}", swf_md5);
+                tracing::info!("Flash crash detected @ {}", new_name);
                 shared_state.flash_crashes.fetch_add(1, Ordering::SeqCst);
+                save_case(
+                    &shared_state.config.paths.crashes,
+                    &new_name,
+                    &swf_content,
+                    input,
+                    &[("ruffle.txt", &ruffle_result.0)],
+                )?;
+                shared_state.case_done(worker_id);
                 continue;
             }
             Err(e) => Err(e),
@@ -82,28 +472,139 @@ pub fn fuzz(shared_state: Arc<SharedFuzzState>, worker_id: u32) -> Result<(), Bo
             flash_duration += flash_dur;
         }
 
-        let (ruffle_res, ruffle_dur) = ruffle_result?;
+        let (ruffle_res, ruffle_dur) = ruffle_result;
         if TIMING_DEBUG {
             ruffle_duration += ruffle_dur;
         }
+        shared_state.timings.total.record(start.elapsed());
+        shared_state.timings.ruffle.record(ruffle_dur);
+        shared_state.timings.flash.record(flash_dur);
 
-        // Did we find a mismatch
-        if ruffle_res != flash_res {
-            let new_name = format!("{:x}", swf_md5);
-            tracing::info!("Found mismatch @ {}", new_name);
-            shared_state.mismatches.fetch_add(1, Ordering::SeqCst);
-            let specific_failure_dir = PathBuf::from_str(FAILURES_DIR)
-                .expect("No failures-other dir")
-                .join(new_name);
+        // Truncated output can't be compared, even if both players logged the same thing
+        if ruffle_res.len() > MAX_OUTPUT_LEN || flash_res.len() > MAX_OUTPUT_LEN {
+            tracing::info!("Output overflow @ {:x}", swf_md5);
+            shared_state.output_overflows.fetch_add(1, Ordering::SeqCst);
+            shared_state.case_done(worker_id);
+            continue;
+        }
 
-            let _ = std::fs::create_dir(&specific_failure_dir);
+        // A case that didn't run to completion on either side can't be compared
+        if !is_complete(&ruffle_res) {
+            tracing::info!("Ruffle didn't complete case @ {:x}", swf_md5);
+            shared_state
+                .ruffle_incomplete
+                .fetch_add(1, Ordering::SeqCst);
+            shared_state.case_done(worker_id);
+            continue;
+        }
+        if !is_complete(&flash_res) {
+            tracing::info!("Flash didn't complete case @ {:x}", swf_md5);
+            shared_state.flash_incomplete.fetch_add(1, Ordering::SeqCst);
+            shared_state.case_done(worker_id);
+            continue;
+        }
 
-            std::fs::write(&specific_failure_dir.join("out.swf"), &swf_content)?;
-            std::fs::write(&specific_failure_dir.join("ruffle.txt"), ruffle_res)?;
-            std::fs::write(&specific_failure_dir.join("flash.txt"), flash_res)?;
+        // A version without stats is only left out of them, it mustn't stop the worker
+        let version_stats = shared_state.version_stats(swf_version);
+        if let Some(version_stats) = version_stats {
+            version_stats.cases.fetch_add(1, Ordering::SeqCst);
+        }
+        for mode in swf_generator.case_modes() {
+            shared_state
+                .mode_stats
+                .sub_cases(*mode)
+                .fetch_add(1, Ordering::SeqCst);
+        }
+
+        // Did we find a mismatch, output that only differs in ways that don't count is just tallied by class
+        let (ruffle_normalized, flash_normalized) = (normalize(&ruffle_res), normalize(&flash_res));
+        let diff_class = (ruffle_normalized != flash_normalized)
+            .then(|| classify(&ruffle_normalized, &flash_normalized, NUMERIC_NOISE_EPSILON));
+        if let Some(diff_class) = diff_class {
+            shared_state.diff_classes[diff_class.index()].fetch_add(1, Ordering::SeqCst);
+            if !MISMATCH_DIFF_CLASSES.contains(&diff_class) {
+                tracing::info!("Ignored {} difference @ {:x}", diff_class, swf_md5);
+            }
+        }
+        if let Some(diff_class) = diff_class.filter(|class| MISMATCH_DIFF_CLASSES.contains(class)) {
+            let new_name = format!("{:x}", swf_md5);
+            tracing::info!("Found {} mismatch @ {}", diff_class, new_name);
+            shared_state.mismatches.fetch_add(1, Ordering::SeqCst);
+            if let Some(version_stats) = version_stats {
+                version_stats.mismatches.fetch_add(1, Ordering::SeqCst);
+            }
+            let differing_ops = differing_ops(&ruffle_normalized, &flash_normalized);
+            if let Some(mode) = differing_ops.first().and_then(|op| marker_mode(op)) {
+                shared_state
+                    .mode_stats
+                    .mismatches(mode)
+                    .fetch_add(1, Ordering::SeqCst);
+            }
+            let signature = classified_signature(&differing_ops, diff_class);
+            let diff_signature = diff_signature(&signature, &ruffle_normalized, &flash_normalized);
+            let exemplar = shared_state
+                .diff_signatures
+                .record(&diff_signature, &new_name);
+            // `SignatureCount::first_case` must be a saved failure. The diff signatures may have been loaded from an
+            // earlier run, so a signature new to this run can still have its failures saved already
+            let first_case = if exemplar {
+                new_name.clone()
+            } else {
+                shared_state
+                    .diff_signatures
+                    .first_exemplar(&diff_signature)
+                    .unwrap_or_else(|| new_name.clone())
+            };
+            shared_state.record_signature(&signature, &first_case);
+            if exemplar {
+                let case = CaseRecord {
+                    name: new_name.clone(),
+                    seed: case_seed,
+                    swf_version,
+                    modes: GeneratorMode::enabled(),
+                    mode_weights: swf_generator.mode_weights().to_vec(),
+                    random_strings: swf_generator.random_strings(),
+                    generator_filter: shared_state.generator_filter.clone(),
+                    ruffle_player_version: ruffle_settings.player_version,
+                    generator_version: GENERATOR_VERSION,
+                };
+                // Read back the exact input flash ran before it is removed
+                let swf = std::fs::read(flash_swf.path())?;
+                let action_listing = annotated_listing(&swf, swf_generator.constructions())
+                    .unwrap_or_else(|e| format!("Couldn't disassemble out.swf: {}\n", e));
+                failures.send(Failure {
+                    name: new_name,
+                    swf,
+                    action_listing,
+                    differing_ops,
+                    signature,
+                    diff_class,
+                    ruffle_output: ruffle_res,
+                    flash_output: flash_res,
+                    swf_version,
+                    flash_player: shared_state
+                        .flash_player
+                        .as_ref()
+                        .map(|player| player.to_string()),
+                    ruffle_settings: ruffle_settings.to_string(),
+                    input_script: input.clone(),
+                    random_seed: swf_generator.random_seed(),
+                    generator_filter: (!shared_state.generator_filter.is_empty())
+                        .then(|| shared_state.generator_filter.to_string()),
+                    case,
+                });
+            } else {
+                tracing::info!(
+                    "Not saving {}, its signature already has {} failures: {}",
+                    new_name,
+                    SIGNATURE_EXEMPLARS,
+                    diff_signature
+                );
+            }
         }
 
-        if SINGLE_ITER {
+        if shared_state.config.single_iter {
+            failures.flush();
             std::process::exit(0);
         }
 
@@ -111,7 +612,7 @@ pub fn fuzz(shared_state: Arc<SharedFuzzState>, worker_id: u32) -> Result<(), Bo
             overall_duration += Instant::now() - start;
             iters += 1;
         }
-        shared_state.iterations.fetch_add(1, Ordering::SeqCst);
+        shared_state.case_done(worker_id);
 
         if TIMING_DEBUG && overall_duration > Duration::from_secs(1) {
             tracing::info!(
@@ -128,3 +629,27 @@ pub fn fuzz(shared_state: Arc<SharedFuzzState>, worker_id: u32) -> Result<(), Bo
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swf_generator::mode_swf_versions;
+    use crate::SWF_VERSIONS;
+
+    #[test]
+    fn every_generated_version_has_stats() {
+        let state = SharedFuzzState::default();
+        let mode_versions = GeneratorMode::ALL
+            .iter()
+            .filter_map(|mode| mode_swf_versions(&[*mode]));
+        for versions in std::iter::once(SWF_VERSIONS).chain(mode_versions) {
+            for swf_version in versions {
+                let stats = state
+                    .version_stats(*swf_version)
+                    .unwrap_or_else(|| panic!("SWF{} is generated but has no stats", swf_version));
+                stats.cases.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        assert!(state.version_stats(3).is_none());
+    }
+}
@@ -0,0 +1,292 @@
+//! Filtering what the generator picks, so a campaign can leave out areas with known Ruffle issues.
+//! `GENERATOR_FILTER_FILE` has one `allow <kind>:<name>` or `deny <kind>:<name>` per line, where the kind is `op`
+//! (an opcode in opcode cases), `static` (a `Class.method` in static function cases) or `value` (one of
+//! `VALUE_CATEGORIES`). A name ending in `*` matches every name starting with the rest of it
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// The kinds of values the generator pushes that can be filtered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueCategory {
+    Undefined,
+    Null,
+    Int,
+    Bool,
+    Double,
+    /// A NaN double, see `FUZZ_DOUBLE_NAN`
+    NanDouble,
+    Float,
+    String,
+    /// A string of an integer, see `FUZZ_INT_STRING`
+    IntString,
+    Object,
+    Array,
+}
+
+pub const VALUE_CATEGORIES: &[ValueCategory] = &[
+    ValueCategory::Undefined,
+    ValueCategory::Null,
+    ValueCategory::Int,
+    ValueCategory::Bool,
+    ValueCategory::Double,
+    ValueCategory::NanDouble,
+    ValueCategory::Float,
+    ValueCategory::String,
+    ValueCategory::IntString,
+    ValueCategory::Object,
+    ValueCategory::Array,
+];
+
+impl ValueCategory {
+    pub fn name(self) -> &'static str {
+        match self {
+            ValueCategory::Undefined => "undefined",
+            ValueCategory::Null => "null",
+            ValueCategory::Int => "int",
+            ValueCategory::Bool => "bool",
+            ValueCategory::Double => "double",
+            ValueCategory::NanDouble => "nan-double",
+            ValueCategory::Float => "float",
+            ValueCategory::String => "string",
+            ValueCategory::IntString => "int-string",
+            ValueCategory::Object => "object",
+            ValueCategory::Array => "array",
+        }
+    }
+}
+
+const OP: &str = "op";
+const STATIC: &str = "static";
+const VALUE: &str = "value";
+
+/// An `allow` or `deny` line
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct FilterEntry {
+    kind: &'static str,
+    name: String,
+}
+
+impl FilterEntry {
+    fn matches(&self, kind: &str, name: &str) -> bool {
+        self.kind == kind
+            && match self.name.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == self.name,
+            }
+    }
+}
+
+impl fmt::Display for FilterEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind, self.name)
+    }
+}
+
+/// What the generator may pick. Anything denied is left out, and if a kind has any allowed names only those are
+/// picked. The default filter allows everything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeneratorFilter {
+    allow: BTreeSet<FilterEntry>,
+    deny: BTreeSet<FilterEntry>,
+}
+
+impl GeneratorFilter {
+    /// Read the filter from `path`, a missing file allows everything
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(filter) => Self::parse(&filter).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn parse(filter: &str) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        for (line_number, line) in filter.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| format!("line {}: {}: {}", line_number + 1, reason, line);
+            let (action, entry) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| invalid("expected allow or deny and an entry"))?;
+            let (kind, name) = entry
+                .trim()
+                .split_once(':')
+                .ok_or_else(|| invalid("expected <kind>:<name>"))?;
+            let kind = [OP, STATIC, VALUE]
+                .into_iter()
+                .find(|known| *known == kind)
+                .ok_or_else(|| invalid("the kind isn't op, static or value"))?;
+            let entry = FilterEntry {
+                kind,
+                name: name.to_string(),
+            };
+            if kind == VALUE
+                && !VALUE_CATEGORIES
+                    .iter()
+                    .any(|category| entry.matches(VALUE, category.name()))
+            {
+                return Err(invalid(&format!(
+                    "no value category matches, they are {}",
+                    VALUE_CATEGORIES
+                        .iter()
+                        .map(|category| category.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+            match action {
+                "allow" => parsed.allow.insert(entry),
+                "deny" => parsed.deny.insert(entry),
+                _ => return Err(invalid("expected allow or deny")),
+            };
+        }
+        Ok(parsed)
+    }
+
+    /// Does the filter allow everything
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    fn allows(&self, kind: &str, name: &str) -> bool {
+        if self.deny.iter().any(|entry| entry.matches(kind, name)) {
+            return false;
+        }
+        let mut allow = self
+            .allow
+            .iter()
+            .filter(|entry| entry.kind == kind)
+            .peekable();
+        allow.peek().is_none() || allow.any(|entry| entry.matches(kind, name))
+    }
+
+    /// The filter's lines, which `parse` reads back as the same filter
+    pub fn entries(&self) -> Vec<String> {
+        self.allow
+            .iter()
+            .map(|entry| format!("allow {}", entry))
+            .chain(self.deny.iter().map(|entry| format!("deny {}", entry)))
+            .collect()
+    }
+
+    /// Can opcode cases use the action, by the name of its variant
+    pub fn allows_op(&self, name: &str) -> bool {
+        self.allows(OP, name)
+    }
+
+    /// Can static function cases call `class.method`
+    pub fn allows_static(&self, class: &str, method: &str) -> bool {
+        self.allows(STATIC, &format!("{}.{}", class, method))
+    }
+
+    pub fn allows_value(&self, category: ValueCategory) -> bool {
+        self.allows(VALUE, category.name())
+    }
+
+    /// The options that `allowed` accepts. If it accepts none of them the filter would leave nothing to generate,
+    /// so it is ignored and all of them are returned
+    fn pool<'o, T>(&self, options: &'o [T], allowed: impl Fn(&Self, &T) -> bool) -> Vec<&'o T> {
+        let pool = options
+            .iter()
+            .filter(|option| allowed(self, option))
+            .collect::<Vec<_>>();
+        if pool.is_empty() {
+            options.iter().collect()
+        } else {
+            pool
+        }
+    }
+
+    /// Pick one of `options` that `allowed` accepts, using the rng the same way as `DoActionGenerator::select`
+    /// when the filter allows everything
+    pub fn select<T: Clone>(
+        &self,
+        rng: &mut StdRng,
+        options: &[T],
+        allowed: impl Fn(&Self, &T) -> bool,
+    ) -> T {
+        let pool = self.pool(options, allowed);
+        pool[rng.gen_range(0..pool.len())].clone()
+    }
+
+    /// Pick one of the value categories that `allowed` accepts. The value generators pick with an inclusive i32
+    /// range, so this does too to generate the same values when the filter allows everything
+    pub fn select_value(
+        &self,
+        rng: &mut StdRng,
+        options: &[ValueCategory],
+        allowed: impl Fn(&Self, &ValueCategory) -> bool,
+    ) -> ValueCategory {
+        let pool = self.pool(options, allowed);
+        *pool[rng.gen_range(0..=pool.len() as i32 - 1) as usize]
+    }
+}
+
+impl fmt::Display for GeneratorFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+        write!(f, "{}", self.entries().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn allow_and_deny() {
+        let filter = GeneratorFilter::parse(
+            "# comment\n\
+             deny op:Multiply\n\
+             allow static:Math.*\n\
+             deny static:Math.random\n\
+             deny value:nan-double\n",
+        )
+        .unwrap();
+        assert!(!filter.allows_op("Multiply"));
+        assert!(filter.allows_op("Add"));
+        assert!(filter.allows_static("Math", "max"));
+        assert!(!filter.allows_static("Math", "random"));
+        assert!(!filter.allows_static("Key", "isDown"));
+        assert!(!filter.allows_value(ValueCategory::NanDouble));
+        assert!(filter.allows_value(ValueCategory::Double));
+        assert_eq!(
+            filter.to_string(),
+            "allow static:Math.*, deny op:Multiply, deny static:Math.random, deny value:nan-double"
+        );
+        assert_eq!(GeneratorFilter::default().to_string(), "none");
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert!(GeneratorFilter::parse("deny Multiply").is_err());
+        assert!(GeneratorFilter::parse("block op:Multiply").is_err());
+        assert!(GeneratorFilter::parse("deny opcode:Multiply").is_err());
+        assert!(GeneratorFilter::parse("deny value:nan-string").is_err());
+        assert!(GeneratorFilter::parse("deny value:nan*").is_ok());
+    }
+
+    #[test]
+    fn select_skips_denied() {
+        let filter = GeneratorFilter::parse("deny op:b").unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let option = filter.select(&mut rng, &["a", "b"], |filter, op| filter.allows_op(op));
+            assert_eq!(option, "a");
+        }
+        // Denying everything leaves the choice unfiltered rather than having nothing to pick
+        let filter = GeneratorFilter::parse("deny op:*").unwrap();
+        let option = filter.select(&mut rng, &["a", "b"], |filter, op| filter.allows_op(op));
+        assert!(["a", "b"].contains(&option));
+    }
+}
@@ -0,0 +1,177 @@
+//! Per-worker heartbeats, so that a worker wedged in one of the players shows up in the log rather than the
+//! campaign silently running with fewer threads
+use crate::{KILL_STUCK_FLASH, STUCK_WORKER_TIMEOUT};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What a worker is doing, as of its last heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerPhase {
+    Starting,
+    Generating,
+    Ruffle,
+    Flash,
+    Comparing,
+}
+
+impl WorkerPhase {
+    /// Every phase, indexed by the value stored in `WorkerHeartbeat::phase`
+    const ALL: &'static [WorkerPhase] = &[
+        WorkerPhase::Starting,
+        WorkerPhase::Generating,
+        WorkerPhase::Ruffle,
+        WorkerPhase::Flash,
+        WorkerPhase::Comparing,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WorkerPhase::Starting => "starting",
+            WorkerPhase::Generating => "generating",
+            WorkerPhase::Ruffle => "ruffle",
+            WorkerPhase::Flash => "flash",
+            WorkerPhase::Comparing => "comparing",
+        }
+    }
+}
+
+/// Milliseconds since the unix epoch, atomics can't hold an `Instant`
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// The liveness of a single worker thread, updated by the worker and checked by the stats thread
+#[derive(Default)]
+pub struct WorkerHeartbeat {
+    /// When the worker last beat, in milliseconds since the unix epoch, 0 before the first beat
+    last_beat: AtomicU64,
+    /// The index in `WorkerPhase::ALL` of the phase the worker was in at its last beat
+    phase: AtomicU8,
+    /// Cases this worker has finished
+    pub iterations: AtomicUsize,
+    /// Case seeds this worker has drawn, including those of duplicate cases, see `SharedFuzzState::next_case_seed`
+    pub seeds_drawn: AtomicU64,
+    /// The pid of the flash projector the worker is waiting on, 0 when it isn't running one
+    flash_pid: AtomicU32,
+    /// Has the current stall been logged, cleared by the next beat so each stall is only logged once
+    reported_stuck: AtomicBool,
+}
+
+impl WorkerHeartbeat {
+    /// Record that the worker is alive and has moved on to `phase`
+    pub fn beat(&self, phase: WorkerPhase) {
+        let index = WorkerPhase::ALL
+            .iter()
+            .position(|p| *p == phase)
+            .unwrap_or_default();
+        self.phase.store(index as u8, Ordering::SeqCst);
+        self.last_beat.store(now_millis(), Ordering::SeqCst);
+        self.reported_stuck.store(false, Ordering::SeqCst);
+    }
+
+    pub fn phase(&self) -> WorkerPhase {
+        WorkerPhase::ALL
+            .get(self.phase.load(Ordering::SeqCst) as usize)
+            .copied()
+            .unwrap_or(WorkerPhase::Starting)
+    }
+
+    /// How long it has been since the last beat, None if the worker hasn't started yet
+    pub fn since_last_beat(&self) -> Option<Duration> {
+        match self.last_beat.load(Ordering::SeqCst) {
+            0 => None,
+            last_beat => Some(Duration::from_millis(
+                now_millis().saturating_sub(last_beat),
+            )),
+        }
+    }
+
+    /// Set the flash projector that the worker is waiting on, None once it has exited
+    pub fn set_flash_pid(&self, pid: Option<u32>) {
+        self.flash_pid.store(pid.unwrap_or(0), Ordering::SeqCst);
+    }
+
+    /// Kill the flash projector the worker is waiting on, so its read of the output ends and it can move on.
+    /// The killed case is then saved as a flash crash. Returns false if there was no projector to kill
+    fn kill_flash(&self) -> bool {
+        let pid = self.flash_pid.swap(0, Ordering::SeqCst);
+        if pid == 0 {
+            return false;
+        }
+        #[cfg(unix)]
+        {
+            unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) == 0 }
+        }
+        #[cfg(not(unix))]
+        {
+            false
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "iterations": self.iterations.load(Ordering::SeqCst),
+            "phase": self.phase().name(),
+            "since_last_beat_ms": self.since_last_beat().map(|since| since.as_millis() as u64),
+        })
+    }
+}
+
+/// Log every worker that hasn't beat in `STUCK_WORKER_TIMEOUT`, and kill the projector of those stuck in flash if
+/// `KILL_STUCK_FLASH` is set. Returns the number of stuck workers
+pub fn check_workers(workers: &[WorkerHeartbeat]) -> usize {
+    let mut stuck = 0;
+    for (worker_id, worker) in workers.iter().enumerate() {
+        let stalled_for = match worker.since_last_beat() {
+            Some(since) if since > STUCK_WORKER_TIMEOUT => since,
+            _ => continue,
+        };
+        stuck += 1;
+        if worker.reported_stuck.swap(true, Ordering::SeqCst) {
+            continue;
+        }
+
+        let phase = worker.phase();
+        tracing::warn!(
+            "Worker {} has been stuck in {} for {:?}, after {} cases",
+            worker_id,
+            phase.name(),
+            stalled_for,
+            worker.iterations.load(Ordering::SeqCst)
+        );
+        if KILL_STUCK_FLASH && phase == WorkerPhase::Flash {
+            if worker.kill_flash() {
+                tracing::warn!("Killed the flash projector of worker {}", worker_id);
+            } else {
+                tracing::warn!("Couldn't kill the flash projector of worker {}", worker_id);
+            }
+        }
+    }
+    stuck
+}
+
+/// Log how many cases each worker finished, so uneven progress between threads is visible
+pub fn log_worker_report(workers: &[WorkerHeartbeat]) {
+    let total: usize = workers
+        .iter()
+        .map(|worker| worker.iterations.load(Ordering::SeqCst))
+        .sum();
+    for (worker_id, worker) in workers.iter().enumerate() {
+        let iterations = worker.iterations.load(Ordering::SeqCst);
+        tracing::info!(
+            "Worker {}: {} cases ({:.1}%), last in {}",
+            worker_id,
+            iterations,
+            if total > 0 {
+                iterations as f64 * 100.0 / total as f64
+            } else {
+                0.0
+            },
+            worker.phase().name()
+        );
+    }
+}
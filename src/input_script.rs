@@ -0,0 +1,139 @@
+//! Synthetic keyboard and mouse input delivered to a case between frames, so that Key and Mouse listeners run
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The keys used by input fuzz cases, as (flash key code, the character it types, X11 keysym)
+pub const INPUT_KEYS: &[(u8, Option<char>, &str)] = &[
+    (8, None, "BackSpace"),
+    (13, Some('\r'), "Return"),
+    (16, None, "Shift_L"),
+    (17, None, "Control_L"),
+    (32, Some(' '), "space"),
+    (37, None, "Left"),
+    (38, None, "Up"),
+    (39, None, "Right"),
+    (40, None, "Down"),
+    (48, Some('0'), "0"),
+    (65, Some('a'), "a"),
+    (90, Some('z'), "z"),
+];
+
+/// The character typed by a key from `INPUT_KEYS`
+pub fn key_char(key_code: u8) -> Option<char> {
+    INPUT_KEYS
+        .iter()
+        .find(|(code, _, _)| *code == key_code)
+        .and_then(|(_, key_char, _)| *key_char)
+}
+
+/// The X11 keysym of a key from `INPUT_KEYS`
+pub fn key_sym(key_code: u8) -> Option<&'static str> {
+    INPUT_KEYS
+        .iter()
+        .find(|(code, _, _)| *code == key_code)
+        .map(|(_, _, sym)| *sym)
+}
+
+/// A single input, mouse positions are in pixels relative to the top left of the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyDown(u8),
+    KeyUp(u8),
+    MouseMove { x: i32, y: i32 },
+    MouseDown { x: i32, y: i32 },
+    MouseUp { x: i32, y: i32 },
+}
+
+/// The inputs of a case, each delivered before the frame it is for runs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputScript {
+    /// (frame, event) pairs in delivery order
+    events: Vec<(usize, InputEvent)>,
+}
+
+impl InputScript {
+    pub fn push(&mut self, frame: usize, event: InputEvent) {
+        self.events.push((frame, event));
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Every event with the frame it is delivered before
+    pub fn events(&self) -> &[(usize, InputEvent)] {
+        &self.events
+    }
+
+    /// The events to deliver before `frame` runs
+    pub fn events_before(&self, frame: usize) -> impl Iterator<Item = &InputEvent> {
+        self.events
+            .iter()
+            .filter(move |(event_frame, _)| *event_frame == frame)
+            .map(|(_, event)| event)
+    }
+}
+
+impl Display for InputEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputEvent::KeyDown(key_code) => write!(f, "key_down {}", key_code),
+            InputEvent::KeyUp(key_code) => write!(f, "key_up {}", key_code),
+            InputEvent::MouseMove { x, y } => write!(f, "mouse_move {} {}", x, y),
+            InputEvent::MouseDown { x, y } => write!(f, "mouse_down {} {}", x, y),
+            InputEvent::MouseUp { x, y } => write!(f, "mouse_up {} {}", x, y),
+        }
+    }
+}
+
+/// One `<frame> <event>` line per event, as read back by `FromStr`
+impl Display for InputScript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (frame, event) in &self.events {
+            writeln!(f, "{} {}", frame, event)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for InputScript {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut script = InputScript::default();
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let invalid = || format!("Invalid input event: {}", line);
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let int = |index: usize| -> Result<i32, String> {
+                fields
+                    .get(index)
+                    .and_then(|field| field.parse().ok())
+                    .ok_or_else(invalid)
+            };
+            let frame = int(0)? as usize;
+            let event = match fields.get(1).copied() {
+                Some("key_down") => InputEvent::KeyDown(int(2)? as u8),
+                Some("key_up") => InputEvent::KeyUp(int(2)? as u8),
+                Some("mouse_move") => InputEvent::MouseMove {
+                    x: int(2)?,
+                    y: int(3)?,
+                },
+                Some("mouse_down") => InputEvent::MouseDown {
+                    x: int(2)?,
+                    y: int(3)?,
+                },
+                Some("mouse_up") => InputEvent::MouseUp {
+                    x: int(2)?,
+                    y: int(3)?,
+                },
+                _ => return Err(invalid()),
+            };
+            script.push(frame, event);
+        }
+        Ok(script)
+    }
+}
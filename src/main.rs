@@ -1,46 +1,213 @@
+use crate::bench::{BENCH_COMMAND, SAVE_BASELINE_ARG};
+use crate::bundle::BUNDLE_COMMAND;
+use crate::campaign::{CampaignBudget, CAMPAIGN_COMMAND, EXIT_INFRASTRUCTURE};
+use crate::checkpoint::{spawn_checkpoint_writer, split_resume_arg};
+use crate::config::{Config, CHECK_FAILURES_COMMAND, FUZZ_COMMAND};
+use crate::corpus::CORPUS_COMMAND;
+use crate::diff_class::DiffClass;
 use crate::error::MyError;
+use crate::failure_checker::CheckOptions;
+use crate::failure_writer::spawn_failure_writer;
+use crate::flash_player_info::{identify_flash_player, smoke_test, FORCE_ARG};
 use crate::flash_projector_runner::open_flash_cmd;
-use crate::fuzz_session::{fuzz, SharedFuzzState};
-use crate::swf_generator::SwfGenerator;
+use crate::fuzz_session::{fuzz, stop_on_ctrl_c, SharedFuzzState, INTERRUPTED_EXIT_CODE};
+use crate::generator_filter::GeneratorFilter;
+use crate::heartbeat::log_worker_report;
+use crate::minimizer::MINIMIZE_ARG;
+use crate::replay::{REPLAY_COMMAND, REPLAY_SEED_ARG, RUN_ARG};
+use crate::reverify::reverify_if_moved;
+use crate::ruffle_test_export::EXPORT_RUFFLE_TEST_COMMAND;
+use crate::run_paths::RunPaths;
+use crate::selftest::{BLESS_ARG, SELFTEST_COMMAND};
+use crate::state_file::spawn_state_writer;
+use crate::static_methods::log_static_coverage;
+use crate::stats::spawn_stats_logger;
+use crate::swf_generator::{GeneratorMode, SwfGenerator};
 use env_logger::Env;
 
 use std::error::Error;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
+pub mod action_budget;
+pub mod attempted;
+pub mod bench;
+pub mod bundle;
+pub mod campaign;
+pub mod checkpoint;
+pub mod config;
+pub mod corpus;
+pub mod diff_class;
+pub mod diff_signature;
+pub mod display_list;
 pub mod error;
 pub mod failure_checker;
+pub mod failure_writer;
+pub mod flash_player_info;
 pub mod flash_projector_runner;
 pub mod fuzz_session;
+pub mod generator_filter;
+pub mod heartbeat;
+pub mod input_script;
+pub mod minimizer;
+pub mod mode_scheduler;
+pub mod normalize;
+pub mod op_marker;
+pub mod replay;
+pub mod reverify;
 pub mod rng;
 pub mod ruffle_runner;
+pub mod ruffle_test_export;
+pub mod run_paths;
+pub mod selftest;
+pub mod sentinel;
+pub mod state_file;
+pub mod static_methods;
+pub mod stats;
 pub mod swf_generator;
+pub mod trace_bytes;
 
-///*Note*: Only 1 of these should be enabled at a time
 /// Should single opcode fuzz cases be generated
 const OPCODE_FUZZ: bool = false;
 /// Should static function fuzz cases be generated
 const STATIC_FUNCTION_FUZZ: bool = false;
-/// Should dynamic function fuzz cases be generated, (function calls on an objet/other value)
+/// Should dynamic function fuzz cases be generated, (function calls on an objet/other value, or a method from the
+/// class's prototype called on a value of another type)
 const DYNAMIC_FUNCTION_FUZZ: bool = true;
+/// Should function body fuzz cases be generated (an opcode case inside a DefineFunction or a DefineFunction2 with
+/// random preload and suppress flags, called with fewer or more args than it declares)
+const FUNCTION_FUZZ: bool = false;
+/// Should navigation fuzz cases be generated (GetURL2 loadMovie/loadVariables with bogus urls)
+const NAVIGATION_FUZZ: bool = false;
+/// Should display list fuzz cases be generated (PlaceObject2/3 and RemoveObject across several frames)
+const PLACEMENT_FUZZ: bool = false;
+/// Should argument count fuzz cases be generated (CallFunction/CallMethod/NewObject with a count that doesn't match the args)
+const ARG_COUNT_FUZZ: bool = false;
+/// Should player version fuzz cases be generated (getVersion(), System.capabilities.version and $version)
+const VERSION_FUZZ: bool = false;
+/// Should System.capabilities fuzz cases be generated (every capability with its type, environment dependent values are masked)
+const CAPABILITIES_FUZZ: bool = false;
+/// Should input fuzz cases be generated (Key and Mouse listeners, with key and mouse events delivered between frames)
+const INPUT_FUZZ: bool = false;
+/// Should tag size fuzz cases be generated (DoAction tags and function/try bodies either side of the 63 byte and 65535 byte limits, and tags of several MiB)
+const TAG_SIZE_FUZZ: bool = false;
+/// Should malformed container cases be generated (a tag whose length doesn't match its data, a truncated tag, an invalid
+/// sound format or deeply nested sprites after the main actions, to compare how the players' loaders cope)
+const MALFORMED_CONTAINER_FUZZ: bool = false;
+/// Should binary data cases be generated (a DefineBinaryData of 0 bytes to several MiB, maybe exported, that the main
+/// actions try to attach, tracing getBytesLoaded/getBytesTotal of the root and attached clips)
+const BINARY_DATA_FUZZ: bool = false;
+/// Should member chain fuzz cases be generated (`a.b.c`, `undefined.x.y`, `null[0]` and so on, on bases that are
+/// undeclared, declared without a value, primitives or objects with only part of the chain set)
+const MEMBER_CHAIN_FUZZ: bool = false;
+/// Should frame label cases be generated (a sprite with randomly labeled frames, including duplicate, numeric,
+/// unicode and anchor labels, navigated with ActionGoToLabel, gotoAndStop/gotoAndPlay and call by label)
+const FRAME_LABEL_FUZZ: bool = false;
+/// Should target path cases be generated (nested clips referenced in dot and slash syntax turned into a path with
+/// ActionTargetPath, which is then resolved again with GetVariable, GetProperty and SetTarget2)
+const TARGET_PATH_FUZZ: bool = false;
+/// Should string comparison cases be generated (curated pairs of strings and numbers compared with StringEquals,
+/// StringLess, StringGreater and the generic Equals, Equals2, Less, Less2 and Greater, both ways round)
+const STRING_COMPARE_FUZZ: bool = false;
+/// Should member access cases be generated (a random object, or null or undefined, put through a sequence of
+/// GetMember, SetMember, Delete and Delete2 with names it has and doesn't have, tracing each get and delete)
+const MEMBER_ACCESS_FUZZ: bool = false;
+/// Should prototype chain cases be generated (an object literal given another as its `__proto__`, with properties
+/// read that are on the instance, the prototype, both or neither, writes through the chain and cyclic chains)
+const PROTO_CHAIN_FUZZ: bool = false;
+/// Should class hierarchy cases be generated (two DefineFunction2 constructors wired with Extends, maybe implementing an
+/// interface with ImplementsOp, an instance of the derived one checked with InstanceOf against each class)
+const CLASS_HIERARCHY_FUZZ: bool = false;
+/// Should global function cases be generated (parseInt, parseFloat, isNaN, isFinite, escape, unescape and the String,
+/// Number, Boolean, Object and Array conversions called as plain functions, with awkward numeric strings)
+const GLOBAL_FUNCTION_FUZZ: bool = false;
+/// Should number formatting cases be generated (doubles near the boundaries where number to string conversion changes,
+/// from `NUMBER_FORMAT_VALUES`, converted with ToString, StringAdd and Trace, and parsed back with ToNumber)
+const NUMBER_FORMAT_FUZZ: bool = false;
+/// Should multi-frame cases be generated (root frames with random labels that each trace their number and navigate
+/// with GotoFrame, GotoLabel, GotoFrame2, Play, Stop or WaitForFrame, completed by `onEnterFrame` after a fixed count)
+const MULTI_FRAME_FUZZ: bool = false;
+/// Should movie clip cases be generated (a sprite placed on the stage, read and written through `_x` and `_y` and
+/// called with getDepth, getBounds, gotoAndStop, duplicateMovieClip and removeMovieClip, by name and by target path)
+const MOVIE_CLIP_FUZZ: bool = false;
+/// Should clone sprite cases be generated (a placed sprite cloned with CloneSprite and removed with RemoveSprite, with
+/// valid and invalid depths and sources that may not be clips, and SetTarget and SetTarget2 changing where traces run)
+const CLONE_SPRITE_FUZZ: bool = false;
+/// Should try/catch cases be generated (Try blocks, nested up to two deep, that throw a random value, the result of a
+/// CastOp or nothing at all from an empty stack, tracing what each catch caught and the order the finally blocks ran in)
+const TRY_CATCH_FUZZ: bool = false;
+
+/// How often each enabled mode is picked for a sub-case of the main actions, so one case can mix the modes. Modes that
+/// aren't listed have a weight of `DEFAULT_MODE_WEIGHT`, and those with a weight of 0 are only generated on their own.
+/// Modes that run in frames of their own or after the main actions, such as placement and input, are in every case
+/// while they are enabled
+const MODE_WEIGHTS: &[(GeneratorMode, u32)] = &[
+    (GeneratorMode::Opcode, 4),
+    (GeneratorMode::StaticFunction, 2),
+    (GeneratorMode::DynamicFunction, 2),
+];
+
+/// The weight of the modes that `MODE_WEIGHTS` doesn't list
+const DEFAULT_MODE_WEIGHT: u32 = 1;
+
+/// Should the mode weights be adapted to the mismatches each mode finds as the run goes, rather than staying at
+/// `MODE_WEIGHTS`, see `mode_scheduler`
+const ADAPTIVE_MODE_WEIGHTS: bool = false;
+
+/// How many cases are run between each adaptation of the mode weights
+const ADAPTIVE_WEIGHT_INTERVAL: usize = 1000;
+
+/// The smallest adapted weight of a mode, as a fraction of the largest, so that no mode starves
+const ADAPTIVE_WEIGHT_FLOOR: f64 = 0.05;
+
+/// Should random strings sometimes be pushed by reference to a ConstantPool at the start of each DoAction tag of the
+/// main actions, rather than inline, including references past the end of the pool. Not used with tag size cases, as
+/// the pool would change the size of their tags
+const CONSTANT_POOL_PUSH: bool = false;
+
+/// Should `Math.random` be replaced at the start of each case with a deterministic generator seeded by the case, so
+/// that its output can be compared. Static function cases then sometimes call it, and failures record the seed
+const SEEDED_RANDOM: bool = false;
+
+/// Should input fuzz cases be compared with flash, the input is sent to the projector window with xdotool.
+/// Otherwise they are run through ruffle twice and only checked for determinism, as the projector's frame timing
+/// means input can land a frame early or late
+const INPUT_AGAINST_FLASH: bool = false;
+
+/// The flash player versions that ruffle emulates, each case picks one at random.
+/// Should include the projector's version, otherwise every version fuzz case will mismatch
+const RUFFLE_PLAYER_VERSIONS: &[u8] = &[32];
+
+/// Modes whose sub-cases are wrapped in a try block that traces any error thrown, so that a throw in one player
+/// shows up in the diff rather than just as missing output. The try block changes semantics slightly, so this is per-mode
+const CATCH_ERROR_MODES: &[GeneratorMode] = &[
+    GeneratorMode::DynamicFunction,
+    GeneratorMode::StaticFunction,
+    GeneratorMode::MemberChain,
+];
+
+/// The dir that a run's files are written to, see `RunPaths`
+const RUN_DIR: &str = "run";
+
+/// Set this environment variable to use another run dir than `RUN_DIR`
+pub const RUN_DIR_ENV: &str = "FUZZ_RUN_DIR";
+
+/// Set this environment variable to save failures somewhere other than the run dir
+pub const FAILURES_DIR_ENV: &str = "FUZZ_FAILURES_DIR";
+
+/// Set this environment variable to keep inputs somewhere other than the run dir
+pub const INPUTS_DIR_ENV: &str = "FUZZ_INPUTS_DIR";
 
-#[cfg(windows)]
-const INPUTS_DIR: &str = ".\\run\\inputs";
-#[cfg(windows)]
-const FAILURES_DIR: &str = ".\\run\\failures";
 #[cfg(windows)]
 const FLASH_PLAYER_BINARY: &str = ".\\utils\\flashplayer_32_sa_debug.exe";
 #[cfg(windows)]
 const FLASH_LOG_PATH: &str = "Macromedia\\Flash Player\\Logs\\flashlog.txt";
 
-#[cfg(unix)]
-const INPUTS_DIR: &str = "./run/inputs/";
-#[cfg(unix)]
-const FAILURES_DIR: &str = "./run/failures/";
 #[cfg(unix)]
 const FLASH_PLAYER_BINARY: &str = "./utils/flashplayer_32_sa_debug";
 // const FLASH_PLAYER_BINARY: &str = "./utils/flashplayer_10_3r183_90_linux_sa";
@@ -53,14 +220,92 @@ const FUZZ_RANDOM_STRING: bool = false;
 /// Generate random numbers, otherwise use fixed value numbers (10)
 const FUZZ_RANDOM_INT: bool = false;
 
+/// The chance of each argument of a dynamic function call being a random value rather than one of the type the method
+/// takes, so coercion of the wrong types is still tested
+const DYNAMIC_ARG_MISTYPE_RATE: f64 = 0.2;
+
+/// The most methods a dynamic function case calls in turn on the object it constructs, so that sequences like push,
+/// pop then join on an Array are tested
+const DYNAMIC_MAX_CALLS: usize = 4;
+
 /// Generate strings with ints, otherwise use fixed strings
 const FUZZ_INT_STRING: bool = false;
 
 /// Generate NaN doubles
 const FUZZ_DOUBLE_NAN: bool = false;
 
-/// Use random swf versions, otherwise only use 32 (latest)
-const RANDOM_SWF_VERSION: bool = false;
+/// The swf versions to generate cases for, cases are split evenly between them.
+/// AVM1 behaviour is mostly gated on 6, 7 and 8, later versions all behave like 32 (latest)
+const SWF_VERSIONS: &[u8] = &[6, 7, 8, 32];
+
+/// Every swf version a case can be generated for, `SWF_VERSIONS` and those of each mode on its own, e.g.
+/// `OPCODE_SWF_VERSIONS`. The per-version stats are kept for each of these
+const ALL_SWF_VERSIONS: &[u8] = &[4, 5, 6, 7, 8, 32];
+
+/// The swf versions that opcode cases pick from when they are the only mode. They only use opcodes of the version
+/// (see `action_min_version`), so they go back to SWF4, where strings double as numbers, and before SWF5 they trace
+/// their results directly. Other modes rely on later opcodes
+const OPCODE_SWF_VERSIONS: &[u8] = &[4, 5, 6, 7, 8, 32];
+
+/// The swf versions that target path cases pick from when they are the only mode, the default path syntax changed
+/// across these
+const TARGET_PATH_SWF_VERSIONS: &[u8] = &[5, 6, 7];
+
+/// The swf versions that string comparison cases pick from when they are the only mode, string encoding and
+/// comparison rules changed across these
+const STRING_COMPARE_SWF_VERSIONS: &[u8] = &[5, 6, 7];
+
+/// The swf versions that try/catch cases pick from when they are the only mode, ActionTry and ActionThrow are SWF7
+const TRY_CATCH_SWF_VERSIONS: &[u8] = &[7, 8, 32];
+
+/// The swf versions that class hierarchy cases pick from when they are the only mode, DefineFunction2, Extends and
+/// ImplementsOp are SWF7
+const CLASS_HIERARCHY_SWF_VERSIONS: &[u8] = &[7, 8, 32];
+
+/// Should every case trace a summary of the clips on the root timeline at the end, which ruffle also checks against
+/// its display list, see `display_list`. Only useful with modes that put clips on stage, e.g. `PLACEMENT_FUZZ` or
+/// `FRAME_LABEL_FUZZ`
+const DISPLAY_LIST_SUMMARY: bool = false;
+
+/// The chance of each of Metadata, DefineSceneAndFrameLabelData and DefineScalingGrid being added to a case.
+/// None of these affect scripts, but the players still have to handle (or ignore) them the same way
+const EXTRA_TAG_RATE: f64 = 0.1;
+
+/// How long a campaign runs for when no duration is given, see `campaign`
+const CAMPAIGN_MAX_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// How often a checkpoint that `--resume` can carry on from is written, see `checkpoint`
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Roughly the most memory the hashes of the cases run so far may take, after which they are kept in a bloom filter
+/// of this size instead, see `attempted`
+const ATTEMPTED_MEMORY_CAP: usize = 256 * 1024 * 1024;
+
+/// How often the state that a restarted run carries on with is written, see `state_file`
+const STATE_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many failures are saved for each diff signature, later mismatches with it are only counted, see `diff_signature`
+const SIGNATURE_EXEMPLARS: usize = 5;
+
+/// The most chars of each differing line kept in a diff signature
+const SIGNATURE_LINE_LEN: usize = 120;
+
+/// How many of the diff signatures with the most mismatches are logged with the stats
+const SIGNATURE_LOG_TOP: usize = 5;
+
+/// How many times `check-failures` runs each failure through ruffle unless given `--runs`, a failure that only differs
+/// in some of them is reported as flaky
+const CHECK_FAILURE_RUNS: usize = 3;
+
+/// Mismatch signatures that don't fail a campaign, one per line, see `campaign::read_signature_list`
+const SUPPRESSIONS_FILE: &str = "suppressions.txt";
+
+/// The most saved failures rerun through ruffle at startup when it has moved to another commit since the signature
+/// store was last used, see `reverify`
+const REVERIFY_MAX_FAILURES: usize = 100;
+
+/// What the generator may pick, e.g. to leave out areas with known Ruffle issues, see `generator_filter`
+const GENERATOR_FILTER_FILE: &str = "generator_filter.txt";
 
 /// Number of threads to use
 const THREAD_COUNT: i32 = 32;
@@ -68,17 +313,70 @@ const THREAD_COUNT: i32 = 32;
 /// Should threads be pinned to cores
 const PIN_THREADS: bool = true;
 
+/// A worker that hasn't moved on from the phase it is in for this long is logged as stuck.
+/// Flash times out after 30s when it is still logging, this is well past that
+const STUCK_WORKER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Should the flash projector of a worker stuck waiting on flash be killed, so the worker can carry on
+const KILL_STUCK_FLASH: bool = true;
+
 /// Should low level timeing info be collected, like the time for running the file in each player
 pub const TIMING_DEBUG: bool = false;
 
 /// Should only a single iteration be performed
 pub const SINGLE_ITER: bool = false;
 
+/// Set this environment variable to keep the input given to flash after running a test, by default it is removed
+pub const KEEP_SWF_ENV: &str = "FUZZ_KEEP_SWF";
+
 /// Should the input be removed after running a test
-pub const DELETE_SWF: bool = false;
+pub fn delete_swf() -> bool {
+    std::env::var_os(KEEP_SWF_ENV).is_none()
+}
 
 pub const TESTS_PER_FUZZ_CASE: usize = 15;
 
+/// How long either player can run a case for before it is stopped, a case that times out is incomplete
+pub const RUN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The frames ruffle runs a generated case for before it is stopped, on top of any frames the case adds after the
+/// first (see `SwfGenerator::frame_budget`). A case that doesn't complete within its budget is incomplete
+pub const RUFFLE_FRAME_BUDGET: u32 = 3;
+
+/// The most AVM1 actions a generated case can have, cases with more aren't run and are counted as script limit cases
+/// (see `action_budget`). Ruffle has no limit on the actions it runs in a frame, so this bounds a case's time in ruffle
+/// where `RUN_TIMEOUT` alone would let a single frame run for the whole timeout
+pub const MAX_CASE_ACTIONS: usize = 1_000_000;
+
+/// Should ruffle render each frame, the null renderer draws nothing so this only costs time
+pub const RUFFLE_RENDER: bool = false;
+
+/// How far ruffle's clock is advanced after each frame, in milliseconds
+pub const RUFFLE_TICK_MS: f64 = 1000. / 60.;
+
+/// The fraction of cases (0.0 - 1.0) that are run through ruffle twice to check that ruffle is deterministic,
+/// cases that produce different output on each run are saved to `RunPaths::nondeterministic` and not compared with flash
+pub const RUFFLE_DETERMINISM_CHECK_RATE: f64 = 0.05;
+
+/// The most trace output kept from either player for a single case, cases that log more than this are not compared.
+/// Both players are cut off the same way, output longer than this means it was truncated
+pub const MAX_OUTPUT_LEN: usize = 4 * 1024 * 1024;
+
+/// The kinds of difference that make a case a mismatch, see `DiffClass`. Cases whose output only differs in other
+/// ways are counted by class in the stats but not saved. By default numbers that differ by less than
+/// `NUMERIC_NOISE_EPSILON` are left out, as the players format some floats differently
+pub const MISMATCH_DIFF_CLASSES: &[DiffClass] = &[
+    DiffClass::NumericError,
+    DiffClass::TypeMismatch,
+    DiffClass::Textual,
+];
+
+/// The largest relative error between two numbers in the output that is put down to float formatting
+pub const NUMERIC_NOISE_EPSILON: f64 = 1e-12;
+
+/// How many mismatches can be waiting to be saved before fuzz threads block
+pub const FAILURE_QUEUE_LIMIT: usize = 64;
+
 /// Empty the flash log file, this avoids a crash were the file is missing
 fn clear_flash_log() -> Result<(), Box<dyn Error>> {
     let log_path = dirs_next::config_dir()
@@ -93,54 +391,165 @@ fn clear_flash_log() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("flash_fuzz=info")).init();
-
-    // create the run dir
-    std::fs::create_dir_all(FAILURES_DIR)?;
-    std::fs::create_dir_all(INPUTS_DIR)?;
+/// Create the run dirs, with a temp dir for each of `worker_count` workers, and an empty flash log
+fn setup_run_dir(paths: &RunPaths, worker_count: u32) -> Result<(), Box<dyn Error>> {
+    paths.create(worker_count)?;
     // Create the flash dir
     let flash_log = dirs_next::config_dir()
         .expect("No config dir")
         .join(FLASH_LOG_PATH);
     std::fs::create_dir_all(flash_log.parent().unwrap())?;
     // Ensure that the flash log exists or we will crash
-    clear_flash_log()?;
+    clear_flash_log()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::Builder::from_env(Env::default().default_filter_or("flash_fuzz=info")).init();
+
+    let args: Vec<String> = std::env::args().collect();
+    let (args, resume) = split_resume_arg(&args)?;
+    let (config, mut args) = Config::from_args(&args)?;
+    let paths = &config.paths;
+    if args.get(1).map(String::as_str) == Some(FUZZ_COMMAND) {
+        args.remove(1);
+    }
+    if args.get(1).map(String::as_str) == Some(SELFTEST_COMMAND) {
+        return selftest::run(args.iter().any(|arg| arg == BLESS_ARG));
+    }
+    if args.get(1).map(String::as_str) == Some(BENCH_COMMAND) {
+        return bench::run(&config, args.iter().any(|arg| arg == SAVE_BASELINE_ARG));
+    }
+    if args.get(1).map(String::as_str) == Some(CORPUS_COMMAND) {
+        let dir = args.get(2).ok_or("Usage: flash-fuzz corpus <dir>")?;
+        return corpus::run(&config, Path::new(dir));
+    }
+    if args.get(1).map(String::as_str) == Some(BUNDLE_COMMAND) {
+        let archive = args
+            .get(2)
+            .ok_or("Usage: flash-fuzz bundle <archive> <failure dir>...")?;
+        let failure_dirs = args[3..].iter().map(PathBuf::from).collect::<Vec<_>>();
+        return bundle::run(Path::new(archive), &failure_dirs);
+    }
+    if args.get(1).map(String::as_str) == Some(EXPORT_RUFFLE_TEST_COMMAND) {
+        let usage = "Usage: flash-fuzz export-ruffle-test <failure dir> <dest>";
+        let failure_dir = args.get(2).ok_or(usage)?;
+        let dest = args.get(3).ok_or(usage)?;
+        return ruffle_test_export::run(Path::new(failure_dir), Path::new(dest));
+    }
+    if args.get(1).map(String::as_str) == Some(REPLAY_COMMAND) {
+        let log = args
+            .get(2)
+            .ok_or("Usage: flash-fuzz replay <case log> [--run] | <swf>")?;
+        if Path::new(log)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            == Some("swf")
+        {
+            return replay::run_swf(&config, Path::new(log));
+        }
+        return replay::run(
+            &config,
+            Path::new(log),
+            args.iter().any(|arg| arg == RUN_ARG),
+        );
+    }
+    if args.get(1).map(String::as_str) == Some(REPLAY_SEED_ARG) {
+        let usage = "Usage: flash-fuzz --replay-seed <seed> <swf version>";
+        let seed = args
+            .get(2)
+            .and_then(|seed| seed.parse().ok())
+            .ok_or(usage)?;
+        let swf_version = args
+            .get(3)
+            .and_then(|version| version.parse().ok())
+            .ok_or(usage)?;
+        return replay::run_seed(&config, seed, swf_version, Path::new(GENERATOR_FILTER_FILE));
+    }
+    if args.get(1).map(String::as_str) == Some(MINIMIZE_ARG) {
+        let failure_dir = args
+            .get(2)
+            .ok_or("Usage: flash-fuzz --minimize <failure dir>")?;
+        return minimizer::run(&config, Path::new(failure_dir));
+    }
+    if args.get(1).map(String::as_str) == Some(CAMPAIGN_COMMAND) {
+        let exit_code = match CampaignBudget::from_args(&args[2..]) {
+            Ok(budget) => campaign::run(&config, budget, resume.as_deref()),
+            Err(usage) => {
+                tracing::error!("{}", usage);
+                EXIT_INFRASTRUCTURE
+            }
+        };
+        std::process::exit(exit_code);
+    }
+    if args.get(1).map(String::as_str) == Some(CHECK_FAILURES_COMMAND) {
+        let exit_code = match CheckOptions::from_args(&args[2..]) {
+            Ok(options) => failure_checker::run(&config, &options),
+            Err(usage) => {
+                tracing::error!("{}", usage);
+                EXIT_INFRASTRUCTURE
+            }
+        };
+        std::process::exit(exit_code);
+    }
+
+    setup_run_dir(paths, config.threads as u32)?;
 
     //TODO: setup mm.cfg
 
+    // A release player never logs traces, so every case would look like agreement
+    let flash_player = identify_flash_player(&config.flash)?;
+    tracing::info!("Flash player = {}", flash_player);
+    if !futures::executor::block_on(smoke_test(&config.flash, &paths.worker_temp(0)))? {
+        if std::env::args().any(|arg| arg == FORCE_ARG) {
+            tracing::warn!(
+                "Flash player didn't log any traces in the smoke test, continuing due to {}",
+                FORCE_ARG
+            );
+        } else {
+            tracing::error!(
+                "Flash player didn't log any traces in the smoke test, pass {} to start anyway",
+                FORCE_ARG
+            );
+            return Err(MyError::NonDebugFlashPlayer.into());
+        }
+    }
+
+    reverify_if_moved(paths);
+
     tracing::info!("Starting fuzz loop");
 
-    let state = Arc::new(SharedFuzzState::default());
-
-    let stats_state = Arc::clone(&state);
-    std::thread::spawn(move || loop {
-        let iters = stats_state.iterations.load(Ordering::SeqCst);
-        stats_state
-            .total_iterations
-            .fetch_add(iters, Ordering::SeqCst);
-        stats_state.iterations.store(0, Ordering::SeqCst);
-        let total_iters = stats_state.total_iterations.load(Ordering::SeqCst);
-        let desc = stats_state.mismatches.load(Ordering::SeqCst);
-        let crashes = stats_state.flash_crashes.load(Ordering::SeqCst);
-
-        tracing::info!(
-            "Iterations = {} (Mult = {}), iters/s = {}, Discrepancies = {}, Flash Crashes = {}",
-            total_iters,
-            total_iters * TESTS_PER_FUZZ_CASE,
-            iters / 5,
-            desc,
-            crashes
-        );
-        std::thread::sleep(Duration::from_secs(5));
-    });
+    let mut state = SharedFuzzState::with_flash_player(flash_player, config.clone());
+    state.generator_filter = GeneratorFilter::from_file(Path::new(GENERATOR_FILTER_FILE))?;
+    tracing::info!("Generator filter = {}", state.generator_filter);
+    match &resume {
+        Some(checkpoint) => checkpoint::resume(&mut state, checkpoint)?,
+        None => state_file::load(&mut state),
+    }
+    let state = Arc::new(state);
+    run_workers(&state, paths);
+
+    Ok(())
+}
+
+/// Fuzz on `Config::threads` threads until they all stop, then save any failures still queued. Exits with
+/// `INTERRUPTED_EXIT_CODE` once everything is saved if the workers were stopped by ctrl-c
+fn run_workers(state: &Arc<SharedFuzzState>, paths: &RunPaths) {
+    tracing::info!("Master seed = {}", state.master_seed);
+    stop_on_ctrl_c(Arc::clone(state));
+    spawn_stats_logger(Arc::clone(state));
+    spawn_checkpoint_writer(Arc::clone(state));
+    spawn_state_writer(Arc::clone(state));
+
+    let (failures, failure_writer) =
+        spawn_failure_writer(paths.failures.clone(), paths.case_log.clone());
 
     // Create thread for each fuzzing job
-    let threads = (0..THREAD_COUNT)
+    let threads = (0..state.config.threads)
         .map(|thread_index| {
-            let state_copy = Arc::clone(&state);
+            let state_copy = Arc::clone(state);
+            let failures = failures.clone();
             std::thread::spawn(move || {
-                if PIN_THREADS {
+                if state_copy.config.pin_threads {
                     // Attempt to pin threads to cores on linux
                     #[cfg(target_os = "linux")]
                     {
@@ -149,7 +558,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                         let mut cpu_set: libc::cpu_set_t =
                             unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
                         unsafe { libc::CPU_ZERO(&mut cpu_set) };
-                        unsafe { libc::CPU_SET(thread_index as usize, &mut cpu_set) };
+                        unsafe { libc::CPU_SET(thread_index, &mut cpu_set) };
 
                         unsafe {
                             libc::sched_setaffinity(
@@ -162,20 +571,35 @@ fn main() -> Result<(), Box<dyn Error>> {
                 }
 
                 // Start fuzzing
-                fuzz(state_copy, thread_index as _).expect("Thread failed");
+                fuzz(state_copy, failures, thread_index as _).expect("Thread failed");
             })
         })
         .collect::<Vec<_>>();
     for x in threads {
         x.join().expect("Thread failed to join or panic");
     }
+    log_worker_report(&state.workers);
+    log_static_coverage(&state.static_coverage);
+    if let Err(e) = checkpoint::write(state, &paths.checkpoint) {
+        tracing::warn!("Failed to write {}: {}", paths.checkpoint.display(), e);
+    }
+    if let Err(e) = state_file::write(state, &paths.state) {
+        tracing::warn!("Failed to write {}: {}", paths.state.display(), e);
+    }
 
-    Ok(())
+    // Let the writer drain its queue before exiting
+    drop(failures);
+    failure_writer
+        .join()
+        .expect("Failure writer failed to join or panic");
+    paths.remove_temp();
+    if state.interrupted.load(Ordering::SeqCst) {
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
 }
 
 // Write the opcodes to a file as well
 //TODO:
 // Dynamic function more classes
-// Try using Class.prototype.func() with the wrong `this` arg
 // avm2 support
 // registers and slots and movieclips as value types
@@ -0,0 +1,168 @@
+//! Minimizing mismatches, `flash-fuzz --minimize <failure dir>` generates the case recorded in the dir's `meta.json`
+//! again with subsets of the sub-cases of its main actions, see `SwfGenerator::keep_sub_cases`. The smallest that
+//! still fails with the same signature is written to `min.swf` in the dir, with its listing in `min_actions.txt`
+use crate::campaign::classified_signature;
+use crate::config::Config;
+use crate::diff_class::classify;
+use crate::flash_player_info::identify_flash_player;
+use crate::normalize::normalize;
+use crate::op_marker::differing_ops;
+use crate::replay::{run_players, CaseRecord};
+use crate::selftest::annotated_listing;
+use crate::swf_generator::{SwfGenerator, GENERATOR_VERSION};
+use crate::{setup_run_dir, MISMATCH_DIFF_CLASSES, NUMERIC_NOISE_EPSILON};
+use std::error::Error;
+use std::path::Path;
+
+/// Minimize a failure with `flash-fuzz --minimize <failure dir>`
+pub const MINIMIZE_ARG: &str = "--minimize";
+
+/// Written by `failure_writer`, how the case was generated
+const META_FILE: &str = "meta.json";
+
+/// Written to the failure dir
+const MIN_SWF_FILE: &str = "min.swf";
+const MIN_LISTING_FILE: &str = "min_actions.txt";
+
+/// The smallest subset of the sub-cases `0..count` found that `still_fails`. Chunks of the sub-cases are left out
+/// while that keeps it failing, and the chunks are halved once none of them can be
+fn minimize(
+    count: usize,
+    mut still_fails: impl FnMut(&[usize]) -> Result<bool, Box<dyn Error>>,
+) -> Result<Vec<usize>, Box<dyn Error>> {
+    let mut kept: Vec<usize> = (0..count).collect();
+    let mut chunk_len = (count / 2).max(1);
+    while chunk_len > 0 {
+        let mut removed = false;
+        let mut chunk_start = 0;
+        while chunk_start < kept.len() {
+            let chunk_end = (chunk_start + chunk_len).min(kept.len());
+            let candidate = [&kept[..chunk_start], &kept[chunk_end..]].concat();
+            if still_fails(&candidate)? {
+                kept = candidate;
+                removed = true;
+            } else {
+                chunk_start = chunk_end;
+            }
+        }
+        if !removed {
+            chunk_len /= 2;
+        }
+    }
+    Ok(kept)
+}
+
+/// Run a regenerated case through both players, returns the signature of its mismatch. None if the outputs only differ
+/// in ways that don't count, don't differ at all or flash crashed
+fn mismatch_signature(
+    config: &Config,
+    record: &CaseRecord,
+    swf: &[u8],
+    generator: &SwfGenerator,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let (ruffle_output, flash_output) = match run_players(config, record, swf, generator)? {
+        Some(outputs) => outputs,
+        None => return Ok(None),
+    };
+    let (ruffle_normalized, flash_normalized) =
+        (normalize(&ruffle_output), normalize(&flash_output));
+    if ruffle_normalized == flash_normalized {
+        return Ok(None);
+    }
+    let diff_class = classify(&ruffle_normalized, &flash_normalized, NUMERIC_NOISE_EPSILON);
+    Ok(MISMATCH_DIFF_CLASSES.contains(&diff_class).then(|| {
+        classified_signature(
+            &differing_ops(&ruffle_normalized, &flash_normalized),
+            diff_class,
+        )
+    }))
+}
+
+fn minimize_case(
+    config: &Config,
+    failure_dir: &Path,
+    record: &CaseRecord,
+) -> Result<(), Box<dyn Error>> {
+    let mut swf = Vec::new();
+    let generator = record.regenerate(&mut swf)?;
+    let md5 = format!("{:x}", md5::compute(&swf));
+    if md5 != record.name {
+        return Err(format!(
+            "{}: regenerated as {}, recorded by generator version {} (now {})",
+            record.name, md5, record.generator_version, GENERATOR_VERSION
+        )
+        .into());
+    }
+    let signature = mismatch_signature(config, record, &swf, &generator)?
+        .ok_or_else(|| format!("{}: no longer fails", record.name))?;
+    let count = generator.sub_case_count();
+    tracing::info!(
+        "{}: minimizing {} sub-cases failing with {}",
+        record.name,
+        count,
+        signature
+    );
+
+    let kept = minimize(count, |sub_cases| {
+        let mut swf = Vec::new();
+        let generator = record.regenerate_sub_cases(Some(sub_cases.to_vec()), &mut swf)?;
+        let still_fails = mismatch_signature(config, record, &swf, &generator)?.as_deref()
+            == Some(signature.as_str());
+        tracing::info!(
+            "{} sub-cases: {}",
+            sub_cases.len(),
+            if still_fails { "still fails" } else { "passes" }
+        );
+        Ok(still_fails)
+    })?;
+
+    let mut min_swf = Vec::new();
+    let generator = record.regenerate_sub_cases(Some(kept.clone()), &mut min_swf)?;
+    let listing = annotated_listing(&min_swf, generator.constructions())?;
+    std::fs::write(failure_dir.join(MIN_SWF_FILE), &min_swf)?;
+    std::fs::write(failure_dir.join(MIN_LISTING_FILE), listing)?;
+    tracing::info!(
+        "{}: kept sub-cases {:?} of {}, {} bytes down from {}",
+        record.name,
+        kept,
+        count,
+        min_swf.len(),
+        swf.len()
+    );
+    Ok(())
+}
+
+/// Minimize the failure in `failure_dir`
+pub fn run(config: &Config, failure_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let meta_path = failure_dir.join(META_FILE);
+    let meta = std::fs::read_to_string(&meta_path)
+        .map_err(|e| format!("Failed to read {}: {}", meta_path.display(), e))?;
+    let record = CaseRecord::from_json(&serde_json::from_str(&meta)?)?;
+
+    setup_run_dir(&config.paths, 1)?;
+    tracing::info!("Flash player = {}", identify_flash_player(&config.flash)?);
+    let result = minimize_case(config, failure_dir, &record);
+    config.paths.remove_temp();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimize_to_failing_sub_cases() {
+        let mut checks = 0;
+        let kept = minimize(15, |sub_cases| {
+            checks += 1;
+            Ok(sub_cases.contains(&3) && sub_cases.contains(&11))
+        })
+        .unwrap();
+        assert_eq!(kept, [3, 11]);
+        assert!(checks < 40, "{} checks", checks);
+
+        // Fails without any of them, e.g. in the setup
+        assert!(minimize(15, |_| Ok(true)).unwrap().is_empty());
+        assert_eq!(minimize(4, |_| Ok(false)).unwrap(), [0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,106 @@
+//! Adapting the mode weights to the mismatches each mode finds, with `ADAPTIVE_MODE_WEIGHTS`. Every
+//! `ADAPTIVE_WEIGHT_INTERVAL` cases each mode's weight is set in proportion to (mismatches + 1) / sub-cases, so a run
+//! left for days spends more of its time on the modes that find mismatches. No weight falls below
+//! `ADAPTIVE_WEIGHT_FLOOR` of the largest, so a mode that hasn't found anything yet still gets tried, and modes with a
+//! weight of 0 in `MODE_WEIGHTS` are left out of mixed cases as before
+use crate::fuzz_session::ModeStats;
+use crate::swf_generator::GeneratorMode;
+use crate::ADAPTIVE_WEIGHT_FLOOR;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// The weight of the mode with the best yield, the others are scaled down from it
+const ADAPTED_MAX_WEIGHT: u32 = 1000;
+
+/// The weights that cases are currently generated with, starting at `MODE_WEIGHTS`
+pub struct ModeScheduler {
+    weights: Vec<AtomicU32>,
+}
+
+impl Default for ModeScheduler {
+    fn default() -> Self {
+        Self {
+            weights: GeneratorMode::configured_weights()
+                .into_iter()
+                .map(AtomicU32::new)
+                .collect(),
+        }
+    }
+}
+
+impl ModeScheduler {
+    /// The current weight of each of `GeneratorMode::ALL`, see `SwfGenerator::set_mode_weights`
+    pub fn weights(&self) -> Vec<u32> {
+        self.weights
+            .iter()
+            .map(|weight| weight.load(Ordering::SeqCst))
+            .collect()
+    }
+
+    pub fn weight(&self, mode: GeneratorMode) -> &AtomicU32 {
+        &self.weights[mode.index()]
+    }
+
+    /// Set the weights from the yield of each mode so far
+    pub fn adapt(&self, stats: &ModeStats) {
+        let counts = GeneratorMode::ALL
+            .iter()
+            .map(|mode| {
+                (
+                    mode.weight(),
+                    stats.sub_cases(*mode).load(Ordering::SeqCst),
+                    stats.mismatches(*mode).load(Ordering::SeqCst),
+                )
+            })
+            .collect::<Vec<_>>();
+        for (weight, adapted) in self.weights.iter().zip(adapted_weights(&counts)) {
+            weight.store(adapted, Ordering::SeqCst);
+        }
+    }
+}
+
+/// The weight of each mode given its configured weight, sub-cases and mismatches. The mode with the best
+/// (mismatches + 1) / sub-cases gets `ADAPTED_MAX_WEIGHT` and the others proportionally less, but no less than
+/// `ADAPTIVE_WEIGHT_FLOOR` of it. Modes configured with a weight of 0 keep it
+fn adapted_weights(counts: &[(u32, usize, usize)]) -> Vec<u32> {
+    let mode_yield =
+        |sub_cases: usize, mismatches: usize| (mismatches + 1) as f64 / sub_cases.max(1) as f64;
+    let best_yield = counts
+        .iter()
+        .filter(|(configured, _, _)| *configured > 0)
+        .map(|(_, sub_cases, mismatches)| mode_yield(*sub_cases, *mismatches))
+        .fold(0.0, f64::max);
+    let floor = (ADAPTED_MAX_WEIGHT as f64 * ADAPTIVE_WEIGHT_FLOOR).ceil() as u32;
+    counts
+        .iter()
+        .map(|(configured, sub_cases, mismatches)| {
+            if *configured == 0 {
+                return 0;
+            }
+            let share = mode_yield(*sub_cases, *mismatches) / best_yield;
+            ((ADAPTED_MAX_WEIGHT as f64 * share).round() as u32).max(floor)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_follow_yield_above_floor() {
+        let weights = adapted_weights(&[
+            // 10 mismatches in 100 sub-cases, the best yield
+            (1, 100, 10),
+            // Half the yield, whatever the configured weight
+            (4, 200, 10),
+            // Nothing found in many sub-cases, held up by the floor
+            (1, 100_000, 0),
+            // Configured out of mixed cases
+            (0, 100, 50),
+        ]);
+        assert_eq!(weights, [1000, 500, 50, 0]);
+
+        // A mode that hasn't run yet counts as one sub-case, so it is tried
+        assert_eq!(adapted_weights(&[(1, 100, 10), (1, 0, 0)]), [110, 1000]);
+    }
+}
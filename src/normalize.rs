@@ -0,0 +1,204 @@
+//! Normalisation applied to both players' output before it is compared, for differences that aren't bugs
+
+/// Traced before output whose line order isn't meaningful, e.g. enumerating an object
+pub const SORTED_BLOCK_BEGIN: &str = "#SORTED_BEGIN#";
+
+/// Traced after the output started by `SORTED_BLOCK_BEGIN`
+pub const SORTED_BLOCK_END: &str = "#SORTED_END#";
+
+/// Start of a traced capability, followed by `<key>=<typeof value>:<value>`
+pub const CAPABILITY_PREFIX: &str = "#CAP#";
+
+/// Start of a traced property of the object a dynamic function case called a method on, followed by
+/// `<key>=<typeof value>:<value>`
+pub const PROPERTY_PREFIX: &str = "#PROPS#";
+
+/// Start of a traced value that depends on the player's state rather than the case, such as the mouse position,
+/// followed by `<name>=<typeof value>:<value>`. Only its type is compared
+pub const UNSTABLE_PREFIX: &str = "#UNSTABLE#";
+
+/// Replaces the value of an environment dependent capability or an unstable value
+const MASKED_VALUE: &str = "<masked>";
+
+/// System.capabilities keys whose value depends on the machine or on ruffle's null backends,
+/// only their presence and type are compared
+const ENVIRONMENT_DEPENDENT_CAPABILITIES: &[&str] = &[
+    "avHardwareDisable",
+    "cpuArchitecture",
+    "hasAudio",
+    "hasAudioEncoder",
+    "hasIME",
+    "hasMP3",
+    "hasPrinting",
+    "hasScreenBroadcast",
+    "hasScreenPlayback",
+    "hasStreamingAudio",
+    "hasStreamingVideo",
+    "hasVideoEncoder",
+    "language",
+    "manufacturer",
+    "maxLevelIDC",
+    "os",
+    "pixelAspectRatio",
+    "screenColor",
+    "screenDPI",
+    "screenResolutionX",
+    "screenResolutionY",
+    "serverString",
+    "version",
+];
+
+/// Mask the value of a capability line if its key is environment dependent
+fn mask_capability(line: &str) -> Option<String> {
+    let capability = line.strip_prefix(CAPABILITY_PREFIX)?;
+    let (key, type_and_value) = capability.split_once('=')?;
+    if !ENVIRONMENT_DEPENDENT_CAPABILITIES.contains(&key) {
+        return None;
+    }
+    let (value_type, _) = type_and_value.split_once(':')?;
+    Some(format!(
+        "{}{}={}:{}",
+        CAPABILITY_PREFIX, key, value_type, MASKED_VALUE
+    ))
+}
+
+/// Mask the value of an unstable value line
+fn mask_unstable(line: &str) -> Option<String> {
+    let (name, type_and_value) = line.strip_prefix(UNSTABLE_PREFIX)?.split_once('=')?;
+    let (value_type, _) = type_and_value.split_once(':')?;
+    Some(format!(
+        "{}{}={}:{}",
+        UNSTABLE_PREFIX, name, value_type, MASKED_VALUE
+    ))
+}
+
+/// A line with its value masked if it is an environment dependent capability or an unstable value
+pub(crate) fn mask_line(line: &str) -> String {
+    mask_capability(line)
+        .or_else(|| mask_unstable(line))
+        .unwrap_or_else(|| line.to_string())
+}
+
+/// Normalise a player's output, masking environment dependent capabilities and unstable values and sorting the lines
+/// of sorted blocks
+pub fn normalize(output: &str) -> String {
+    let mut normalized = String::with_capacity(output.len());
+    let mut sorted_block: Option<Vec<String>> = None;
+
+    for line in output.lines() {
+        let line = mask_line(line);
+        match &mut sorted_block {
+            None if line == SORTED_BLOCK_BEGIN => sorted_block = Some(Vec::new()),
+            Some(block) if line == SORTED_BLOCK_END => {
+                block.sort();
+                for block_line in block.drain(..) {
+                    normalized.push_str(&block_line);
+                    normalized.push('\n');
+                }
+                sorted_block = None;
+            }
+            Some(block) => block.push(line),
+            None => {
+                normalized.push_str(&line);
+                normalized.push('\n');
+            }
+        }
+    }
+
+    // A block that never ended is still sorted, so an early exit in both players doesn't look like a difference
+    if let Some(mut block) = sorted_block {
+        block.sort();
+        for block_line in block {
+            normalized.push_str(&block_line);
+            normalized.push('\n');
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorted_block() {
+        assert_eq!(
+            normalize("a\n#SORTED_BEGIN#\nc\nb\n#SORTED_END#\nd\n"),
+            "a\nb\nc\nd\n"
+        );
+        // Only the lines in the block are sorted
+        assert_ne!(normalize("b\na\n"), normalize("a\nb\n"));
+    }
+
+    #[test]
+    fn unterminated_sorted_block() {
+        assert_eq!(normalize("a\n#SORTED_BEGIN#\nc\nb\n"), "a\nb\nc\n");
+        assert_eq!(
+            normalize("#SORTED_BEGIN#\nc\nb"),
+            normalize("#SORTED_BEGIN#\nb\nc")
+        );
+        // One player stopping before the end of the block doesn't hide that it traced less
+        assert_ne!(
+            normalize("#SORTED_BEGIN#\nc\nb\n"),
+            normalize("#SORTED_BEGIN#\nb\nc\na\n")
+        );
+    }
+
+    #[test]
+    fn nested_markers() {
+        // Blocks don't nest, an inner begin is sorted as an ordinary line and the first end closes the block
+        assert_eq!(
+            normalize("#SORTED_BEGIN#\nz\n#SORTED_BEGIN#\ny\n#SORTED_END#\nx\n#SORTED_END#\n"),
+            "#SORTED_BEGIN#\ny\nz\nx\n#SORTED_END#\n"
+        );
+        // Lines in a block are masked before they are sorted
+        assert_eq!(
+            normalize("#SORTED_BEGIN#\n#UNSTABLE#b=number:1\n#CAP#os=string:Linux\n#SORTED_END#\n"),
+            "#CAP#os=string:<masked>\n#UNSTABLE#b=number:<masked>\n"
+        );
+        assert_eq!(
+            normalize("#SORTED_BEGIN#\n#CAP#os=string:Linux\n#UNSTABLE#b=number:1\n#SORTED_END#\n"),
+            normalize(
+                "#SORTED_BEGIN#\n#UNSTABLE#b=number:2\n#CAP#os=string:Windows\n#SORTED_END#\n"
+            )
+        );
+    }
+
+    #[test]
+    fn masking_keeps_real_differences() {
+        let same = |ruffle: &str, flash: &str| normalize(ruffle) == normalize(flash);
+        assert!(same("#CAP#os=string:Linux", "#CAP#os=string:Windows"));
+        assert!(same(
+            "#UNSTABLE#_xmouse=number:1",
+            "#UNSTABLE#_xmouse=number:20"
+        ));
+
+        // Only the value of environment dependent capabilities is masked, not their type
+        assert!(!same("#CAP#os=string:Linux", "#CAP#os=undefined:undefined"));
+        assert!(!same(
+            "#CAP#hasAccessibility=boolean:true",
+            "#CAP#hasAccessibility=boolean:false"
+        ));
+        assert!(!same("#CAP#os=string:Linux", "#CAP#language=string:Linux"));
+        assert!(!same(
+            "#UNSTABLE#_xmouse=number:1",
+            "#UNSTABLE#_xmouse=string:1"
+        ));
+        assert!(!same(
+            "#UNSTABLE#_xmouse=number:1",
+            "#UNSTABLE#_ymouse=number:1"
+        ));
+        // Properties are compared in full
+        assert!(!same("#PROPS#a=number:1", "#PROPS#a=number:2"));
+        // Lines that don't have the form of a masked line are kept as they are
+        assert!(!same("#UNSTABLE#x", "#UNSTABLE#y"));
+        assert!(!same("#UNSTABLE#x=1", "#UNSTABLE#x=2"));
+        // A prefix that doesn't start the line isn't a marker
+        assert!(!same("a #CAP#os=string:Linux", "a #CAP#os=string:Windows"));
+        assert!(!same(
+            "#SORTED_BEGIN#\nc\nb\n#SORTED_END#\n",
+            "#SORTED_BEGIN#\nb\nd\n#SORTED_END#\n"
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+//! Markers traced before each generated operation, so that differing output can be attributed to the operation that produced it
+use crate::swf_generator::GeneratorMode;
+
+/// Start of every marker line, followed by `<index>:<description>#`
+pub const OP_MARKER_PREFIX: &str = "#OP:";
+
+/// End of every marker line
+const OP_MARKER_SUFFIX: &str = "#";
+
+/// Attribution for output logged before the first marker
+pub const PREAMBLE: &str = "<preamble>";
+
+/// The marker line for the `index`th operation of a case
+pub fn op_marker(index: usize, description: &str) -> String {
+    // The description must not end the marker early or split it over lines
+    format!(
+        "{}{}:{}{}",
+        OP_MARKER_PREFIX,
+        index,
+        description.replace(['#', '\n', '\r'], "_"),
+        OP_MARKER_SUFFIX
+    )
+}
+
+/// The description of a marker for an operation generated by `mode`, so differing output can be attributed to it
+pub fn mode_description(mode: GeneratorMode, description: &str) -> String {
+    format!("[{}] {}", mode.name(), description)
+}
+
+/// The mode named by a marker's `<index>:<description>`, if it has one, see `mode_description`
+pub fn marker_mode(marker: &str) -> Option<GeneratorMode> {
+    let (_, description) = marker.split_once(':')?;
+    let (name, _) = description.strip_prefix('[')?.split_once(']')?;
+    GeneratorMode::from_name(name)
+}
+
+/// The `<index>:<description>` of a marker line, or `None` if it isn't one
+pub fn parse_op_marker(line: &str) -> Option<&str> {
+    line.strip_prefix(OP_MARKER_PREFIX)?
+        .strip_suffix(OP_MARKER_SUFFIX)
+}
+
+/// Split output into the lines logged after each marker, starting with the lines before any marker
+pub(crate) fn sections(output: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut sections = vec![(PREAMBLE, Vec::new())];
+    for line in output.lines() {
+        match parse_op_marker(line) {
+            Some(marker) => sections.push((marker, Vec::new())),
+            None => sections.last_mut().unwrap().1.push(line),
+        }
+    }
+    sections
+}
+
+/// The lines logged after `marker`, if it was logged at all
+fn section_lines<'a>(sections: &'a [(&str, Vec<&'a str>)], marker: &str) -> Option<&'a [&'a str]> {
+    sections
+        .iter()
+        .find(|(m, _)| *m == marker)
+        .map(|(_, lines)| lines.as_slice())
+}
+
+/// The markers whose following output differs between the two players, in the order they were logged.
+/// Markers are traced identically by both players, so they never differ themselves
+pub fn differing_ops(ruffle: &str, flash: &str) -> Vec<String> {
+    let ruffle_sections = sections(ruffle);
+    let flash_sections = sections(flash);
+
+    let mut markers: Vec<&str> = flash_sections.iter().map(|(marker, _)| *marker).collect();
+    for (marker, _) in &ruffle_sections {
+        if !markers.contains(marker) {
+            markers.push(marker);
+        }
+    }
+
+    markers
+        .into_iter()
+        .filter(|marker| {
+            section_lines(&ruffle_sections, marker) != section_lines(&flash_sections, marker)
+        })
+        .map(str::to_string)
+        .collect()
+}
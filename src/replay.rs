@@ -0,0 +1,426 @@
+//! Regenerating recorded cases, `flash-fuzz replay <log> [--run]` generates every case in a `RunPaths::case_log`
+//! again from its seed and checks that it comes out with the same md5, catching changes to the generator that break
+//! reproducing old findings. With `--run` the cases are also run through both players again, so a set of findings
+//! can be checked against a new Ruffle build without keeping their swfs. `flash-fuzz --replay-seed <seed> <swf version>`
+//! generates and runs the single case of a seed, as found in the `meta.json` of a failure, and `flash-fuzz replay
+//! <swf>` runs any swf through both players
+use crate::config::Config;
+use crate::diff_class::classify;
+use crate::flash_player_info::identify_flash_player;
+use crate::flash_projector_runner::open_flash_cmd;
+use crate::fuzz_session::save_case;
+use crate::generator_filter::GeneratorFilter;
+use crate::input_script::InputScript;
+use crate::normalize::normalize;
+use crate::op_marker::differing_ops;
+use crate::ruffle_runner::{open_ruffle, RuffleSettings};
+use crate::sentinel::RunUntil;
+use crate::swf_generator::{GeneratorMode, GENERATOR_VERSION};
+use crate::{setup_run_dir, MyError, SwfGenerator, FUZZ_RANDOM_STRING, NUMERIC_NOISE_EPSILON};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::path::Path;
+
+/// Replay recorded cases with `flash-fuzz replay <log> [--run]`, or run a single swf with `flash-fuzz replay <swf>`
+pub const REPLAY_COMMAND: &str = "replay";
+
+/// Also run the regenerated cases through both players
+pub const RUN_ARG: &str = "--run";
+
+/// Generate and run one case with `flash-fuzz --replay-seed <seed> <swf version>`, the version is needed as it is
+/// picked in turn rather than from the seed
+pub const REPLAY_SEED_ARG: &str = "--replay-seed";
+
+/// How a case was generated, enough to generate it again
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseRecord {
+    /// The md5 of the swf, also the name of its failure dir
+    pub name: String,
+    pub seed: u64,
+    pub swf_version: u8,
+    pub modes: Vec<GeneratorMode>,
+    /// The weight of each of `GeneratorMode::ALL`, see `SwfGenerator::set_mode_weights`
+    pub mode_weights: Vec<u32>,
+    /// See `SwfGenerator::with_random_strings`
+    pub random_strings: bool,
+    pub generator_filter: GeneratorFilter,
+    /// The flash player version that ruffle emulated
+    pub ruffle_player_version: u8,
+    /// See `GENERATOR_VERSION`
+    pub generator_version: u32,
+}
+
+impl CaseRecord {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "seed": self.seed,
+            "swf_version": self.swf_version,
+            "modes": self.modes.iter().map(|mode| mode.name()).collect::<Vec<_>>(),
+            "mode_weights": GeneratorMode::ALL
+                .iter()
+                .zip(&self.mode_weights)
+                .map(|(mode, weight)| (mode.name().to_string(), json!(weight)))
+                .collect::<serde_json::Map<_, _>>(),
+            "random_strings": self.random_strings,
+            "generator_filter": self.generator_filter.entries(),
+            "ruffle_player_version": self.ruffle_player_version,
+            "generator_version": self.generator_version,
+        })
+    }
+
+    pub fn from_json(record: &Value) -> Result<Self, String> {
+        let field = |name: &str| {
+            record
+                .get(name)
+                .ok_or_else(|| format!("The record has no {}", name))
+        };
+        let number = |name: &str| {
+            field(name)?
+                .as_u64()
+                .ok_or_else(|| format!("{} isn't a number", name))
+        };
+        let strings = |name: &str| -> Result<Vec<&str>, String> {
+            field(name)?
+                .as_array()
+                .ok_or_else(|| format!("{} isn't a list", name))?
+                .iter()
+                .map(|value| {
+                    value
+                        .as_str()
+                        .ok_or_else(|| format!("{} isn't a list of strings", name))
+                })
+                .collect()
+        };
+        Ok(Self {
+            name: field("name")?
+                .as_str()
+                .ok_or("name isn't a string")?
+                .to_string(),
+            seed: number("seed")?,
+            swf_version: number("swf_version")? as u8,
+            modes: strings("modes")?
+                .into_iter()
+                .map(|name| {
+                    GeneratorMode::from_name(name).ok_or_else(|| format!("Unknown mode {}", name))
+                })
+                .collect::<Result<_, _>>()?,
+            // Records from before the weights were recorded were generated with the configured ones
+            mode_weights: match record.get("mode_weights") {
+                Some(weights) => GeneratorMode::ALL
+                    .iter()
+                    .map(|mode| match weights.get(mode.name()) {
+                        Some(weight) => weight
+                            .as_u64()
+                            .map(|weight| weight as u32)
+                            .ok_or_else(|| format!("The weight of {} isn't a number", mode.name())),
+                        None => Ok(mode.weight()),
+                    })
+                    .collect::<Result<_, _>>()?,
+                None => GeneratorMode::configured_weights(),
+            },
+            // As were records from before random strings could be turned on without recompiling
+            random_strings: match record.get("random_strings") {
+                Some(random_strings) => random_strings
+                    .as_bool()
+                    .ok_or("random_strings isn't a bool")?,
+                None => FUZZ_RANDOM_STRING,
+            },
+            generator_filter: GeneratorFilter::parse(&strings("generator_filter")?.join("\n"))?,
+            ruffle_player_version: number("ruffle_player_version")? as u8,
+            generator_version: number("generator_version")? as u32,
+        })
+    }
+
+    /// Generate the case again, returns the generator it was generated with for its input, nonce and frame budget
+    pub(crate) fn regenerate(&self, swf: &mut Vec<u8>) -> Result<SwfGenerator, Box<dyn Error>> {
+        self.regenerate_sub_cases(None, swf)
+    }
+
+    /// Generate the case again with only the given sub-cases, see `SwfGenerator::keep_sub_cases`
+    pub(crate) fn regenerate_sub_cases(
+        &self,
+        sub_cases: Option<Vec<usize>>,
+        swf: &mut Vec<u8>,
+    ) -> Result<SwfGenerator, Box<dyn Error>> {
+        let mut generator = SwfGenerator::new()
+            .with_filter(self.generator_filter.clone())
+            .with_random_strings(self.random_strings);
+        generator.keep_sub_cases(sub_cases);
+        generator.set_mode_weights(self.mode_weights.clone());
+        generator.reseed(self.seed);
+        generator.generate_version(self.swf_version, &self.modes, swf)?;
+        Ok(generator)
+    }
+}
+
+/// Read the records of a case log, one JSON object per line
+fn read_case_log(path: &Path) -> Result<Vec<CaseRecord>, Box<dyn Error>> {
+    let log = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    log.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_number, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| e.to_string())
+                .and_then(|record| CaseRecord::from_json(&record))
+                .map_err(|e| format!("{} line {}: {}", path.display(), line_number + 1, e).into())
+        })
+        .collect()
+}
+
+/// Run a regenerated case through both players, returns the output of ruffle and flash, or None if flash crashed
+pub(crate) fn run_players(
+    config: &Config,
+    record: &CaseRecord,
+    swf: &[u8],
+    generator: &SwfGenerator,
+) -> Result<Option<(String, String)>, Box<dyn Error>> {
+    let input = generator.input_script();
+    let nonce = Some(generator.nonce());
+    let settings = RuffleSettings {
+        player_version: record.ruffle_player_version,
+        ..RuffleSettings::default()
+    }
+    .with_max_frames(generator.frame_budget());
+    let (ruffle_output, _) = futures::executor::block_on(open_ruffle(
+        swf,
+        &settings,
+        input,
+        RunUntil::Sentinel,
+        nonce,
+    ))?;
+    let flash_output = match futures::executor::block_on(open_flash_cmd(
+        swf,
+        &config.flash,
+        &config.paths.worker_temp(0),
+        input,
+        None,
+        RunUntil::Sentinel,
+        nonce,
+    )) {
+        Ok((flash_output, _, _)) => flash_output,
+        Err(MyError::FlashCrash) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some((ruffle_output, flash_output)))
+}
+
+/// Run a regenerated case through both players, returns how it compares now
+fn rerun(
+    config: &Config,
+    record: &CaseRecord,
+    swf: &[u8],
+    generator: &SwfGenerator,
+) -> Result<String, Box<dyn Error>> {
+    let (ruffle_output, flash_output) = match run_players(config, record, swf, generator)? {
+        Some(outputs) => outputs,
+        None => return Ok("flash crashed".to_string()),
+    };
+    let input = generator.input_script();
+    let (ruffle_normalized, flash_normalized) =
+        (normalize(&ruffle_output), normalize(&flash_output));
+    if ruffle_normalized == flash_normalized {
+        return Ok("now agrees".to_string());
+    }
+    save_case(
+        &config.paths.replay,
+        &record.name,
+        swf,
+        input,
+        &[("ruffle.txt", &ruffle_output), ("flash.txt", &flash_output)],
+    )?;
+    Ok(format!(
+        "still differs ({})",
+        classify(&ruffle_normalized, &flash_normalized, NUMERIC_NOISE_EPSILON)
+    ))
+}
+
+/// Regenerate every case in the log at `log_path`, and rerun them if `run_cases` is set. Fails if any case no
+/// longer generates the same swf
+pub fn run(config: &Config, log_path: &Path, run_cases: bool) -> Result<(), Box<dyn Error>> {
+    let paths = &config.paths;
+    let records = read_case_log(log_path)?;
+    if run_cases {
+        setup_run_dir(paths, 1)?;
+        std::fs::create_dir_all(&paths.replay)?;
+        tracing::info!("Flash player = {}", identify_flash_player(&config.flash)?);
+    }
+    tracing::info!(
+        "Replaying {} cases from {}",
+        records.len(),
+        log_path.display()
+    );
+
+    let mut drifted = 0;
+    let mut swf = Vec::new();
+    for record in &records {
+        swf.clear();
+        let generator = record.regenerate(&mut swf)?;
+        let md5 = format!("{:x}", md5::compute(&swf));
+        if md5 != record.name {
+            drifted += 1;
+            tracing::warn!(
+                "{}: regenerated as {}, recorded by generator version {} (now {})",
+                record.name,
+                md5,
+                record.generator_version,
+                GENERATOR_VERSION
+            );
+            continue;
+        }
+        if run_cases {
+            tracing::info!(
+                "{}: {}",
+                record.name,
+                rerun(config, record, &swf, &generator)?
+            );
+        } else {
+            tracing::info!("{}: regenerated", record.name);
+        }
+    }
+
+    if run_cases {
+        paths.remove_temp();
+    }
+    if drifted > 0 {
+        return Err(format!(
+            "{} of {} cases no longer generate the same swf",
+            drifted,
+            records.len()
+        )
+        .into());
+    }
+    tracing::info!("All {} cases regenerated the same swf", records.len());
+    Ok(())
+}
+
+/// Generate the case of `seed` with the enabled modes, their configured weights and the generator filter in
+/// `filter_path`, then run it through both players
+pub fn run_seed(
+    config: &Config,
+    seed: u64,
+    swf_version: u8,
+    filter_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut record = CaseRecord {
+        name: String::new(),
+        seed,
+        swf_version,
+        modes: GeneratorMode::enabled(),
+        mode_weights: GeneratorMode::configured_weights(),
+        random_strings: config.random_strings,
+        generator_filter: GeneratorFilter::from_file(filter_path)?,
+        ruffle_player_version: RuffleSettings::default().player_version,
+        generator_version: GENERATOR_VERSION,
+    };
+    let mut swf = Vec::new();
+    let generator = record.regenerate(&mut swf)?;
+    record.name = format!("{:x}", md5::compute(&swf));
+
+    let paths = &config.paths;
+    setup_run_dir(paths, 1)?;
+    std::fs::create_dir_all(&paths.replay)?;
+    tracing::info!("Flash player = {}", identify_flash_player(&config.flash)?);
+    tracing::info!(
+        "Seed {} generated {} (SWF{})",
+        seed,
+        record.name,
+        swf_version
+    );
+    let result = rerun(config, &record, &swf, &generator);
+    paths.remove_temp();
+    tracing::info!("{}: {}", record.name, result?);
+    Ok(())
+}
+
+/// Run the swf at `swf_path` through both players with the default settings and log how their outputs compare, for
+/// swfs that weren't generated by this build or have no record
+pub fn run_swf(config: &Config, swf_path: &Path) -> Result<(), Box<dyn Error>> {
+    let swf = std::fs::read(swf_path)
+        .map_err(|e| format!("Failed to read {}: {}", swf_path.display(), e))?;
+    let paths = &config.paths;
+    setup_run_dir(paths, 1)?;
+    tracing::info!("Flash player = {}", identify_flash_player(&config.flash)?);
+    let input = InputScript::default();
+    let outputs = futures::executor::block_on(async {
+        let (ruffle_output, _) = open_ruffle(
+            &swf,
+            &RuffleSettings::default(),
+            &input,
+            RunUntil::Sentinel,
+            None,
+        )
+        .await?;
+        let (flash_output, _, _) = open_flash_cmd(
+            &swf,
+            &config.flash,
+            &paths.worker_temp(0),
+            &input,
+            None,
+            RunUntil::Sentinel,
+            None,
+        )
+        .await?;
+        Ok::<_, MyError>((ruffle_output, flash_output))
+    });
+    paths.remove_temp();
+    let (ruffle_output, flash_output) = outputs?;
+    let (ruffle_normalized, flash_normalized) =
+        (normalize(&ruffle_output), normalize(&flash_output));
+    if ruffle_normalized == flash_normalized {
+        tracing::info!("{}: the players agree", swf_path.display());
+        return Ok(());
+    }
+    tracing::info!(
+        "{}: the players differ ({})",
+        swf_path.display(),
+        classify(&ruffle_normalized, &flash_normalized, NUMERIC_NOISE_EPSILON)
+    );
+    for op in differing_ops(&ruffle_normalized, &flash_normalized) {
+        tracing::info!("Differs after: {}", op);
+    }
+    tracing::info!("Ruffle output:\n{}", ruffle_output);
+    tracing::info!("Flash output:\n{}", flash_output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_regenerates_case() {
+        let mut generator = SwfGenerator::new();
+        generator.reseed(1234);
+        let mut swf = Vec::new();
+        let swf_version = generator.next_swf(&mut swf).unwrap();
+        let record = CaseRecord {
+            name: format!("{:x}", md5::compute(&swf)),
+            seed: 1234,
+            swf_version,
+            modes: GeneratorMode::enabled(),
+            mode_weights: GeneratorMode::configured_weights(),
+            random_strings: FUZZ_RANDOM_STRING,
+            generator_filter: GeneratorFilter::default(),
+            ruffle_player_version: 32,
+            generator_version: GENERATOR_VERSION,
+        };
+        let mut regenerated = Vec::new();
+        record.regenerate(&mut regenerated).unwrap();
+        assert_eq!(regenerated, swf);
+        let case_seed_line = crate::swf_generator::case_seed_line(1234);
+        assert!(swf
+            .windows(case_seed_line.len())
+            .any(|window| window == case_seed_line.as_bytes()));
+
+        let record = CaseRecord {
+            generator_filter: GeneratorFilter::parse("deny op:Multiply").unwrap(),
+            mode_weights: vec![2; GeneratorMode::ALL.len()],
+            random_strings: !FUZZ_RANDOM_STRING,
+            ..record
+        };
+        assert_eq!(CaseRecord::from_json(&record.to_json()).unwrap(), record);
+    }
+}
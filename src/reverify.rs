@@ -0,0 +1,302 @@
+//! Re-verification of saved failures when ruffle moves. Ruffle's behaviour changes with every update of the
+//! dependency, so the build script records the commit the fuzzer is built against as `RUFFLE_COMMIT`, and the
+//! signature store (`RunPaths::signatures`) records the commit it was last used with. When they differ at startup, up
+//! to `REVERIFY_MAX_FAILURES` saved failures are run through ruffle again before fuzzing starts, and the signatures
+//! none of whose failures differ any more are reported as gone, in the log and `RunPaths::reverify`
+use crate::failure_checker::rerun_failure;
+use crate::normalize::normalize;
+use crate::run_paths::RunPaths;
+use crate::REVERIFY_MAX_FAILURES;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The ruffle commit the fuzzer was built against, `unknown` if the build script couldn't find it. This is the commit
+/// of the ruffle source the build script found, from Cargo.lock for a git dependency or from the checkout in `ruffle/`
+/// for a path dependency, not something ruffle_core reports about itself, so a path dependency pointed elsewhere isn't
+/// described by it
+pub const RUFFLE_COMMIT: &str = env!("RUFFLE_COMMIT");
+
+/// The version of the ruffle_core crate that is linked, from Cargo.lock. It always describes the linked crate, but
+/// ruffle rarely bumps it, so it's recorded next to `RUFFLE_COMMIT` rather than used to tell ruffle builds apart
+pub const RUFFLE_CORE_VERSION: &str = env!("RUFFLE_CORE_VERSION");
+
+/// What the build script records when it can't find the commit
+const UNKNOWN_COMMIT: &str = "unknown";
+
+/// The line of the signature store that records the ruffle commit, it is a comment so it isn't read as a signature
+const COMMIT_PREFIX: &str = "# ruffle_commit: ";
+
+/// The ruffle commit recorded in a signature store
+fn recorded_commit(store: &str) -> Option<&str> {
+    store
+        .lines()
+        .find_map(|line| line.strip_prefix(COMMIT_PREFIX))
+        .map(str::trim)
+}
+
+/// The signature store with `commit` recorded in its first line, in place of any commit recorded before
+fn with_recorded_commit(store: &str, commit: &str) -> String {
+    let mut updated = format!("{}{}\n", COMMIT_PREFIX, commit);
+    for line in store
+        .lines()
+        .filter(|line| !line.starts_with(COMMIT_PREFIX))
+    {
+        updated.push_str(line);
+        updated.push('\n');
+    }
+    updated
+}
+
+/// The signature in a failure's `env.txt`
+fn env_signature(env: &str) -> Option<&str> {
+    env.lines()
+        .find_map(|line| line.strip_prefix("signature: "))
+        .map(str::trim)
+}
+
+/// The saved failures in `failures_dir` by signature, failures without a signature are left out
+fn failures_by_signature(failures_dir: &Path) -> std::io::Result<BTreeMap<String, Vec<PathBuf>>> {
+    let mut failures = BTreeMap::<String, Vec<PathBuf>>::new();
+    let entries = match std::fs::read_dir(failures_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(failures),
+        Err(e) => return Err(e),
+    };
+    for entry in entries.flatten().filter(|entry| entry.path().is_dir()) {
+        let env = match std::fs::read_to_string(entry.path().join("env.txt")) {
+            Ok(env) => env,
+            Err(_) => continue,
+        };
+        if let Some(signature) = env_signature(&env) {
+            failures
+                .entry(signature.to_string())
+                .or_default()
+                .push(entry.path());
+        }
+    }
+    for dirs in failures.values_mut() {
+        dirs.sort();
+    }
+    Ok(failures)
+}
+
+/// What rerunning the saved failures of a signature found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureRecheck {
+    /// One of its failures still differs
+    StillFailing,
+    /// None of its failures differ any more
+    Gone,
+    /// The budget ran out, or its failures couldn't be rerun, before any of them was found to still differ
+    Unchecked,
+}
+
+/// The result of a re-verification pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reverification {
+    pub previous_commit: String,
+    pub ruffle_commit: String,
+    /// How many failures were rerun
+    pub rerun: usize,
+    pub signatures: BTreeMap<String, SignatureRecheck>,
+}
+
+impl Reverification {
+    /// The signatures with the given result
+    pub fn with(&self, recheck: SignatureRecheck) -> Vec<&str> {
+        self.signatures
+            .iter()
+            .filter(|(_, r)| **r == recheck)
+            .map(|(signature, _)| signature.as_str())
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "previous_commit": self.previous_commit,
+            "ruffle_commit": self.ruffle_commit,
+            "rerun": self.rerun,
+            "still_failing": self.with(SignatureRecheck::StillFailing),
+            "gone": self.with(SignatureRecheck::Gone),
+            "unchecked": self.with(SignatureRecheck::Unchecked),
+        })
+    }
+}
+
+/// Rerun the failures of each signature until one still differs, at most `max_failures` in all. `still_differs`
+/// reruns a failure, a failure that can't be rerun decides nothing
+fn recheck_signatures(
+    failures: &BTreeMap<String, Vec<PathBuf>>,
+    max_failures: usize,
+    mut still_differs: impl FnMut(&Path) -> Result<bool, Box<dyn Error>>,
+) -> (usize, BTreeMap<String, SignatureRecheck>) {
+    let mut rerun = 0;
+    let mut signatures = BTreeMap::new();
+    for (signature, dirs) in failures {
+        let mut recheck = SignatureRecheck::Unchecked;
+        for dir in dirs {
+            if rerun == max_failures {
+                // A signature is only gone once all of its failures agree
+                if recheck == SignatureRecheck::Gone {
+                    recheck = SignatureRecheck::Unchecked;
+                }
+                break;
+            }
+            rerun += 1;
+            match still_differs(dir) {
+                Ok(true) => {
+                    recheck = SignatureRecheck::StillFailing;
+                    break;
+                }
+                Ok(false) => recheck = SignatureRecheck::Gone,
+                Err(e) => tracing::warn!("Failed to rerun {}: {}", dir.display(), e),
+            }
+        }
+        signatures.insert(signature.clone(), recheck);
+    }
+    (rerun, signatures)
+}
+
+/// Rerun the saved failures through ruffle if it has moved since the signature store was last used, then record the
+/// current commit in the store. Returns None when there was nothing to do
+fn reverify(paths: &RunPaths) -> Result<Option<Reverification>, Box<dyn Error>> {
+    if RUFFLE_COMMIT == UNKNOWN_COMMIT {
+        tracing::warn!(
+            "The ruffle commit isn't known, saved failures won't be rerun when it changes"
+        );
+        return Ok(None);
+    }
+    let store = match std::fs::read_to_string(&paths.signatures) {
+        Ok(store) => store,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let record_commit = || {
+        std::fs::write(
+            &paths.signatures,
+            with_recorded_commit(&store, RUFFLE_COMMIT),
+        )
+    };
+    let previous_commit = match recorded_commit(&store) {
+        Some(commit) if commit == RUFFLE_COMMIT => return Ok(None),
+        Some(commit) => commit.to_string(),
+        // A store from before commits were recorded has nothing to compare with
+        None => {
+            record_commit()?;
+            return Ok(None);
+        }
+    };
+
+    let failures = failures_by_signature(&paths.failures)?;
+    tracing::info!(
+        "Ruffle moved from {} to {}, rerunning up to {} of the failures of {} signatures",
+        previous_commit,
+        RUFFLE_COMMIT,
+        REVERIFY_MAX_FAILURES,
+        failures.len()
+    );
+    let (rerun, signatures) = recheck_signatures(&failures, REVERIFY_MAX_FAILURES, |dir| {
+        let (ruffle_output, flash_output) = futures::executor::block_on(rerun_failure(dir))?;
+        Ok(normalize(&ruffle_output) != normalize(&flash_output))
+    });
+    // Only once the failures have been rerun, so an interrupted pass runs again next time
+    record_commit()?;
+    Ok(Some(Reverification {
+        previous_commit,
+        ruffle_commit: RUFFLE_COMMIT.to_string(),
+        rerun,
+        signatures,
+    }))
+}
+
+/// Re-verify the saved failures if ruffle has moved, see `reverify`, and report which signatures are gone. Failing
+/// to doesn't stop the run
+pub fn reverify_if_moved(paths: &RunPaths) {
+    let reverification = match reverify(paths) {
+        Ok(Some(reverification)) => reverification,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("Failed to re-verify the saved failures: {}", e);
+            return;
+        }
+    };
+    for signature in reverification.with(SignatureRecheck::Gone) {
+        tracing::info!(
+            "Gone since {}: {}",
+            reverification.previous_commit,
+            signature
+        );
+    }
+    tracing::info!(
+        "Reran {} failures, {} signatures still failing, {} gone, {} unchecked",
+        reverification.rerun,
+        reverification.with(SignatureRecheck::StillFailing).len(),
+        reverification.with(SignatureRecheck::Gone).len(),
+        reverification.with(SignatureRecheck::Unchecked).len()
+    );
+    let written = serde_json::to_string_pretty(&reverification.to_json())
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(&paths.reverify, json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        tracing::warn!("Failed to write {}: {}", paths.reverify.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_commit_in_store() {
+        let store = "first\nsecond\n";
+        assert_eq!(recorded_commit(store), None);
+        let store = with_recorded_commit(store, "abc");
+        assert_eq!(store, "# ruffle_commit: abc\nfirst\nsecond\n");
+        assert_eq!(recorded_commit(&store), Some("abc"));
+        let store = with_recorded_commit(&store, "def");
+        assert_eq!(store, "# ruffle_commit: def\nfirst\nsecond\n");
+        assert_eq!(
+            env_signature("swf_version: 8\nsignature: Add(#)\ndiff_class: textual\n"),
+            Some("Add(#)")
+        );
+    }
+
+    #[test]
+    fn recheck_until_still_failing_or_budget_spent() {
+        let failures = [
+            ("a", vec!["a1", "a2"]),
+            ("b", vec!["b1", "b2"]),
+            ("c", vec!["c1"]),
+            ("d", vec!["d1"]),
+        ]
+        .into_iter()
+        .map(|(signature, dirs)| {
+            (
+                signature.to_string(),
+                dirs.into_iter().map(PathBuf::from).collect(),
+            )
+        })
+        .collect();
+        let mut rerun_dirs = Vec::new();
+        let (rerun, signatures) = recheck_signatures(&failures, 4, |dir| {
+            rerun_dirs.push(dir.to_path_buf());
+            match dir.to_str().unwrap() {
+                "a1" | "b1" => Ok(false),
+                "a2" => Ok(true),
+                _ => Err("missing out.swf".into()),
+            }
+        });
+
+        assert_eq!(rerun, 4);
+        assert_eq!(rerun_dirs, ["a1", "a2", "b1", "b2"].map(PathBuf::from));
+        assert_eq!(signatures["a"], SignatureRecheck::StillFailing);
+        // b2 couldn't be rerun, b1 agrees
+        assert_eq!(signatures["b"], SignatureRecheck::Gone);
+        // The budget was spent before these
+        assert_eq!(signatures["c"], SignatureRecheck::Unchecked);
+        assert_eq!(signatures["d"], SignatureRecheck::Unchecked);
+    }
+}
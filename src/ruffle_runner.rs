@@ -1,38 +1,208 @@
 //! Runner for running a fuzz case through Ruffle and extracting the output
 
-use crate::MyError;
+use crate::display_list::{check_ruffle_summary, ruffle_summary};
+use crate::input_script::{key_char, InputEvent, InputScript};
+use crate::sentinel::{
+    check_nonce, is_complete, truncate_to_sentinel, RunUntil, CASE_COMPLETE_SENTINEL,
+};
+use crate::trace_bytes::push_text;
+use crate::{
+    MyError, DISPLAY_LIST_SUMMARY, MAX_OUTPUT_LEN, RUFFLE_PLAYER_VERSIONS, RUFFLE_RENDER,
+    RUFFLE_TICK_MS, RUN_TIMEOUT,
+};
+use rand::Rng;
 use ruffle_core::backend::audio::NullAudioBackend;
 use ruffle_core::backend::log::LogBackend;
 use ruffle_core::backend::navigator::NullNavigatorBackend;
 use ruffle_core::backend::storage::MemoryStorageBackend;
 use ruffle_core::backend::ui::NullUiBackend;
 use ruffle_core::backend::video::NullVideoBackend;
+use ruffle_core::config::Letterbox;
+use ruffle_core::events::{KeyCode, MouseButton, PlayerEvent};
 use ruffle_core::tag_utils::SwfMovie;
 use ruffle_render::backend::null::NullRenderer;
 use ruffle_render::backend::ViewportDimensions;
+use ruffle_render::quality::StageQuality;
 use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 
+/// Collects the traces, escaped as they are logged the same way as flash's output, see `trace_bytes`. Ruffle logs
+/// decoded text, so unlike flash's output there are never invalid bytes to escape
 #[derive(Default)]
-struct StringLogger {
+pub(crate) struct StringLogger {
+    /// Only appended to, so each trace is escaped once however often the log is read
     msgs: RefCell<String>,
 }
 
 impl LogBackend for StringLogger {
     fn avm_trace(&self, message: &str) {
         let mut st = self.msgs.borrow_mut();
-        st.push_str(message);
+        // Once past the cap the output is only used to tell that it overflowed
+        if st.len() > MAX_OUTPUT_LEN {
+            return;
+        }
+        push_text(&mut st, message);
         st.push('\n');
     }
     fn __fuzz__get_log_string(&self) -> String {
-        self.msgs.borrow().to_string()
+        self.msgs.borrow().clone()
+    }
+}
+
+/// The player settings that a case is run through ruffle with
+#[derive(Debug, Clone, Copy)]
+pub struct RuffleSettings {
+    /// The flash player version that ruffle emulates, as seen by `getVersion()` and `$version`
+    pub player_version: u8,
+    pub quality: StageQuality,
+    pub letterbox: Letterbox,
+    /// The most frames to run, a case that hasn't completed by then is stopped. Only `RUN_TIMEOUT` applies if None
+    pub max_frames: Option<u32>,
+    /// Should each frame be rendered
+    pub render: bool,
+    /// How far the clock is advanced after each frame, in milliseconds
+    pub tick_ms: f64,
+    /// Should the summary the case traced of the root timeline be checked against ruffle's display list, see
+    /// `display_list`
+    pub display_list: bool,
+}
+
+impl Default for RuffleSettings {
+    fn default() -> Self {
+        Self {
+            player_version: RUFFLE_PLAYER_VERSIONS[0],
+            quality: StageQuality::High,
+            letterbox: Letterbox::Off,
+            max_frames: None,
+            render: RUFFLE_RENDER,
+            tick_ms: RUFFLE_TICK_MS,
+            display_list: DISPLAY_LIST_SUMMARY,
+        }
+    }
+}
+
+impl RuffleSettings {
+    /// Settings with a player version picked from `RUFFLE_PLAYER_VERSIONS`
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self {
+            player_version: RUFFLE_PLAYER_VERSIONS[rng.gen_range(0..RUFFLE_PLAYER_VERSIONS.len())],
+            ..Self::default()
+        }
+    }
+
+    /// These settings, stopping after `max_frames`
+    pub fn with_max_frames(self, max_frames: u32) -> Self {
+        Self {
+            max_frames: Some(max_frames),
+            ..self
+        }
+    }
+}
+
+impl Display for RuffleSettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "player_version={} quality={:?} letterbox={:?}",
+            self.player_version, self.quality, self.letterbox
+        )?;
+        if let Some(max_frames) = self.max_frames {
+            write!(f, " max_frames={}", max_frames)?;
+        }
+        write!(
+            f,
+            " render={} tick_ms={} display_list={}",
+            self.render, self.tick_ms, self.display_list
+        )
     }
 }
 
-pub async fn open_ruffle(bytes: &[u8]) -> Result<(String, Duration), MyError> {
+impl FromStr for RuffleSettings {
+    type Err = String;
+
+    /// Parse settings as `Display` writes them, fields that are left out keep their defaults
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = Self::default();
+        for field in s.split_whitespace() {
+            let invalid = || format!("Invalid ruffle setting: {}", field);
+            let (name, value) = field.split_once('=').ok_or_else(invalid)?;
+            match name {
+                "player_version" => {
+                    settings.player_version = value.parse().map_err(|_| invalid())?
+                }
+                "quality" => {
+                    settings.quality = [
+                        StageQuality::Low,
+                        StageQuality::Medium,
+                        StageQuality::High,
+                        StageQuality::Best,
+                    ]
+                    .into_iter()
+                    .find(|quality| format!("{:?}", quality) == value)
+                    .ok_or_else(invalid)?
+                }
+                "letterbox" => {
+                    settings.letterbox = [Letterbox::Off, Letterbox::Fullscreen, Letterbox::On]
+                        .into_iter()
+                        .find(|letterbox| format!("{:?}", letterbox) == value)
+                        .ok_or_else(invalid)?
+                }
+                "max_frames" => settings.max_frames = Some(value.parse().map_err(|_| invalid())?),
+                "render" => settings.render = value.parse().map_err(|_| invalid())?,
+                "tick_ms" => settings.tick_ms = value.parse().map_err(|_| invalid())?,
+                "display_list" => settings.display_list = value.parse().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// The player event for an input event, mouse buttons are always the left button
+fn player_event(event: &InputEvent) -> PlayerEvent {
+    let key_code = |code: u8| KeyCode::from_u8(code).expect("Input key unknown to ruffle");
+    match *event {
+        InputEvent::KeyDown(code) => PlayerEvent::KeyDown {
+            key_code: key_code(code),
+            key_char: key_char(code),
+        },
+        InputEvent::KeyUp(code) => PlayerEvent::KeyUp {
+            key_code: key_code(code),
+            key_char: key_char(code),
+        },
+        InputEvent::MouseMove { x, y } => PlayerEvent::MouseMove {
+            x: x.into(),
+            y: y.into(),
+        },
+        InputEvent::MouseDown { x, y } => PlayerEvent::MouseDown {
+            x: x.into(),
+            y: y.into(),
+            button: MouseButton::Left,
+        },
+        InputEvent::MouseUp { x, y } => PlayerEvent::MouseUp {
+            x: x.into(),
+            y: y.into(),
+            button: MouseButton::Left,
+        },
+    }
+}
+
+/// Run the case under ruffle until `run_until`, delivering the events in `input` before the frame they are for.
+/// The frame loop is set by `settings`, see `RuffleSettings::max_frames`.
+/// If a `nonce` is given the output must start with it, see `check_nonce`
+pub async fn open_ruffle(
+    bytes: &[u8],
+    settings: &RuffleSettings,
+    input: &InputScript,
+    run_until: RunUntil,
+    nonce: Option<u64>,
+) -> Result<(String, Duration), MyError> {
     let ruffle_start = Instant::now();
 
-    let movie = SwfMovie::from_data(&bytes, None, None).expect("Load movie fail");
+    let movie =
+        SwfMovie::from_data(bytes, None, None).map_err(|e| MyError::RuffleLoad(e.to_string()))?;
     let log = StringLogger::default();
 
     let player = ruffle_core::PlayerBuilder::new()
@@ -47,6 +217,9 @@ pub async fn open_ruffle(bytes: &[u8]) -> Result<(String, Duration), MyError> {
         .with_video(NullVideoBackend::default())
         .with_log(log)
         .with_ui(NullUiBackend::new())
+        .with_player_version(Some(settings.player_version))
+        .with_quality(settings.quality)
+        .with_letterbox(settings.letterbox)
         .build();
 
     let mut lock = player.lock().unwrap();
@@ -54,28 +227,56 @@ pub async fn open_ruffle(bytes: &[u8]) -> Result<(String, Duration), MyError> {
     lock.set_is_playing(true);
     drop(lock);
 
+    let mut frame = 0;
     loop {
         let mut lock = player.lock().unwrap();
 
+        for event in input.events_before(frame) {
+            lock.handle_event(player_event(event));
+        }
+        frame += 1;
         lock.run_frame();
-        lock.tick(1000. / 60.);
-        lock.render();
+        lock.tick(settings.tick_ms);
+        if settings.render {
+            lock.render();
+        }
         if !lock.is_playing() {
             break;
         }
 
         let out = lock.log_backend().__fuzz__get_log_string();
-        if out.contains("#CASE_") {
+        let done = match run_until {
+            RunUntil::Sentinel => out.contains(CASE_COMPLETE_SENTINEL),
+            RunUntil::Frames(frames) => frame >= frames as usize,
+        };
+        if done {
+            lock.set_is_playing(false);
+        } else if let Some(max_frames) = settings.max_frames.filter(|&max| frame >= max as usize) {
+            tracing::debug!("Ruffle ran out of frames, > {}", max_frames);
+            lock.set_is_playing(false);
+        }
+
+        if out.len() > MAX_OUTPUT_LEN {
+            tracing::debug!("Ruffle output overflowed, > {} bytes", MAX_OUTPUT_LEN);
             lock.set_is_playing(false);
         }
 
-        if Instant::now().duration_since(ruffle_start) > Duration::from_secs(30) {
-            println!("Ruffle timed out, run > 30s");
+        if Instant::now().duration_since(ruffle_start) > RUN_TIMEOUT {
+            println!("Ruffle timed out, run > {:?}", RUN_TIMEOUT);
             lock.set_is_playing(false);
         }
     }
 
-    let lock = player.lock().unwrap();
-    let out = lock.log_backend().__fuzz__get_log_string();
+    let mut lock = player.lock().unwrap();
+    let mut out = lock.log_backend().__fuzz__get_log_string();
+    if run_until == RunUntil::Sentinel {
+        truncate_to_sentinel(&mut out);
+        if settings.display_list && is_complete(&out) {
+            check_ruffle_summary(&mut out, ruffle_summary(&mut lock));
+        }
+    }
+    if let Some(nonce) = nonce {
+        check_nonce(&out, nonce)?;
+    }
     Ok((out, Instant::now() - ruffle_start))
 }
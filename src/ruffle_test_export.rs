@@ -0,0 +1,254 @@
+//! Exporting a failure as a Ruffle regression test, `flash-fuzz export-ruffle-test <failure dir> <dest>` writes the
+//! `test.swf`, `output.txt` and `test.toml` that ruffle's test runner expects to `dest`, ready to be copied into
+//! `tests/swfs` of a ruffle checkout. `test.swf` is the case unchanged, so it still traces its nonce, the completion
+//! sentinel and whatever follows it, and ruffle's runner compares everything traced with `output.txt` as plain text
+//! without our `normalize`. So `output.txt` is the saved flash output up to the sentinel, with each line or sorted
+//! block that `normalize` makes equal to ruffle's taken from ruffle, followed by what ruffle traced after the sentinel.
+//! `num_frames` is the fewest frames, doubling from 1, that ruffle takes to trace the sentinel. The export is then
+//! checked by comparing ruffle's whole output after those frames with `output.txt` as plain text, which has to pass
+//! exactly when ruffle matches `flash.txt` the way cases are compared, and otherwise differ after the same op
+use crate::failure_checker::read_case;
+use crate::input_script::InputScript;
+use crate::normalize::{mask_line, normalize, SORTED_BLOCK_BEGIN, SORTED_BLOCK_END};
+use crate::op_marker::differing_ops;
+use crate::ruffle_runner::{open_ruffle, RuffleSettings};
+use crate::sentinel::{trim_to_sentinel, RunUntil, CASE_COMPLETE_SENTINEL};
+use std::error::Error;
+use std::path::Path;
+
+/// Export a failure with `flash-fuzz export-ruffle-test <failure dir> <dest>`
+pub const EXPORT_RUFFLE_TEST_COMMAND: &str = "export-ruffle-test";
+
+/// The files of a ruffle regression test
+const TEST_SWF_FILE: &str = "test.swf";
+const OUTPUT_FILE: &str = "output.txt";
+const TEST_TOML_FILE: &str = "test.toml";
+
+/// The most frames a case is run for to find how many it needs, a case that hasn't traced the sentinel by then isn't
+/// exported
+const EXPORT_MAX_FRAMES: u32 = 1024;
+
+/// How ruffle's output differs from `expected` the way cases are compared, `None` if it doesn't, otherwise the first
+/// op whose output differs if there is one
+fn difference(ruffle: &str, expected: &str) -> Option<Option<String>> {
+    let (ruffle, expected) = (normalize(ruffle), normalize(expected));
+    if ruffle == expected {
+        return None;
+    }
+    Some(differing_ops(&ruffle, &expected).into_iter().next())
+}
+
+/// A line of output, or a sorted block from its begin line up to its end line if it has one
+#[derive(Debug, PartialEq, Eq)]
+enum Unit<'a> {
+    Line(&'a str),
+    Sorted(Vec<&'a str>),
+}
+
+impl Unit<'_> {
+    fn lines(&self) -> &[&str] {
+        match self {
+            Unit::Line(line) => std::slice::from_ref(line),
+            Unit::Sorted(lines) => lines.as_slice(),
+        }
+    }
+
+    /// What `normalize` compares of this unit, the masked line or the masked lines of the block in order
+    fn normalized(&self) -> Vec<String> {
+        match self {
+            Unit::Line(line) => vec![mask_line(line)],
+            Unit::Sorted(lines) => {
+                let mut block = lines
+                    .iter()
+                    .map(|line| mask_line(line))
+                    .filter(|line| line != SORTED_BLOCK_BEGIN && line != SORTED_BLOCK_END)
+                    .collect::<Vec<_>>();
+                block.sort();
+                block.insert(0, SORTED_BLOCK_BEGIN.to_string());
+                block
+            }
+        }
+    }
+}
+
+/// Split output into the units `normalize` compares, see `Unit`
+fn units(output: &str) -> Vec<Unit> {
+    let mut units = Vec::new();
+    let mut lines = output.lines();
+    while let Some(line) = lines.next() {
+        if line != SORTED_BLOCK_BEGIN {
+            units.push(Unit::Line(line));
+            continue;
+        }
+        let mut block = vec![line];
+        for line in lines.by_ref() {
+            block.push(line);
+            if line == SORTED_BLOCK_END {
+                break;
+            }
+        }
+        units.push(Unit::Sorted(block));
+    }
+    units
+}
+
+/// The `output.txt` of a case that flash traced `flash` for and ruffle `ruffle`: flash's output up to the sentinel,
+/// with the units that only differ in what `normalize` masks or sorts taken from ruffle, so ruffle's runner passes the
+/// test exactly when the case doesn't differ. What was traced after the sentinel is the fuzzer's own and isn't
+/// compared, so it is ruffle's
+fn expected_output(ruffle: &str, flash: &str) -> String {
+    let ruffle_units = units(ruffle);
+    let flash = trim_to_sentinel(flash).unwrap_or(flash);
+    let mut expected = String::with_capacity(flash.len());
+    for (i, flash_unit) in units(flash).iter().enumerate() {
+        let unit = match ruffle_units.get(i) {
+            Some(ruffle_unit) if ruffle_unit.normalized() == flash_unit.normalized() => ruffle_unit,
+            _ => flash_unit,
+        };
+        for line in unit.lines() {
+            expected.push_str(line);
+            expected.push('\n');
+        }
+    }
+    let ruffle_tail = trim_to_sentinel(ruffle).map_or("", |trimmed| &ruffle[trimmed.len()..]);
+    for line in ruffle_tail.lines() {
+        expected.push_str(line);
+        expected.push('\n');
+    }
+    expected
+}
+
+/// The fewest frames, doubling from 1, that ruffle takes to trace the completion sentinel, and its output after them
+async fn frames_to_complete(
+    swf_content: &[u8],
+    input: &InputScript,
+) -> Result<(u32, String), Box<dyn Error>> {
+    let mut frames = 1;
+    loop {
+        let (output, _) = open_ruffle(
+            swf_content,
+            &RuffleSettings::default(),
+            input,
+            RunUntil::Frames(frames),
+            None,
+        )
+        .await?;
+        if trim_to_sentinel(&output).is_some() {
+            return Ok((frames, output));
+        }
+        if frames >= EXPORT_MAX_FRAMES {
+            return Err(format!(
+                "ruffle didn't trace {} within {} frames",
+                CASE_COMPLETE_SENTINEL, EXPORT_MAX_FRAMES
+            )
+            .into());
+        }
+        frames *= 2;
+    }
+}
+
+/// The `test.toml` of a test that runs for `num_frames`
+fn test_toml(num_frames: u32) -> String {
+    format!("num_frames = {}\n", num_frames)
+}
+
+/// Export the failure in `failure_dir` as a ruffle regression test in `dest`
+pub async fn export(failure_dir: &Path, dest: &Path) -> Result<(), Box<dyn Error>> {
+    let (swf_content, input) = read_case(failure_dir)?;
+    let flash_output = std::fs::read_to_string(failure_dir.join("flash.txt"))?;
+    if !input.is_empty() {
+        tracing::warn!(
+            "{} has an input script, which isn't exported, the test needs an input.json written by hand",
+            failure_dir.display()
+        );
+    }
+
+    let (num_frames, ruffle_output) = frames_to_complete(&swf_content, &input).await?;
+    let expected = expected_output(&ruffle_output, &flash_output);
+
+    // Ruffle's runner compares the whole output as plain text, which has to agree with how cases are compared
+    let full = difference(trim_to_sentinel(&ruffle_output).unwrap(), &flash_output);
+    if (ruffle_output == expected) != full.is_none()
+        || difference(&ruffle_output, &expected) != full
+    {
+        return Err(format!(
+            "{} doesn't differ from ruffle the way flash.txt does, not exporting it",
+            OUTPUT_FILE
+        )
+        .into());
+    }
+    match full {
+        None => tracing::warn!("Ruffle already matches flash, the exported test passes"),
+        Some(Some(op)) => tracing::info!("The exported test fails in ruffle after {}", op),
+        Some(None) => tracing::info!("The exported test fails in ruffle"),
+    }
+
+    std::fs::create_dir_all(dest)?;
+    std::fs::write(dest.join(TEST_SWF_FILE), &swf_content)?;
+    std::fs::write(dest.join(OUTPUT_FILE), &expected)?;
+    std::fs::write(dest.join(TEST_TOML_FILE), test_toml(num_frames))?;
+    tracing::info!(
+        "Exported {} to {}, running {} frames",
+        failure_dir.display(),
+        dest.display(),
+        num_frames
+    );
+    Ok(())
+}
+
+/// Run `export` for `flash-fuzz export-ruffle-test`
+pub fn run(failure_dir: &Path, dest: &Path) -> Result<(), Box<dyn Error>> {
+    futures::executor::block_on(export(failure_dir, dest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op_marker::PREAMBLE;
+
+    #[test]
+    fn toml_has_frames() {
+        assert_eq!(test_toml(3), "num_frames = 3\n");
+    }
+
+    #[test]
+    fn difference_normalizes() {
+        assert_eq!(difference("a\n", "a\n"), None);
+        assert_eq!(
+            difference("#CAP#os=string:Linux\n", "#CAP#os=string:Windows\n"),
+            None
+        );
+        assert_eq!(difference("a\n", "b\n"), Some(Some(PREAMBLE.to_string())));
+        assert_eq!(
+            difference("#OP:0:x#\na\n", "#OP:0:x#\nb\n"),
+            Some(Some("0:x".to_string()))
+        );
+    }
+
+    #[test]
+    fn expected_output_keeps_fuzzer_lines() {
+        let ruffle = "#NONCE:1#\na\n#CASE_COMPLETE#\nquit\n";
+        let flash = "#NONCE:1#\r\na\r\n#CASE_COMPLETE#\r\n";
+        assert_eq!(expected_output(ruffle, flash), ruffle);
+    }
+
+    #[test]
+    fn expected_output_takes_masked_lines_from_ruffle() {
+        let ruffle =
+            "#CAP#os=string:Linux\n#SORTED_BEGIN#\nb\na\n#SORTED_END#\nx\n#CASE_COMPLETE#\n";
+        let flash =
+            "#CAP#os=string:Windows\n#SORTED_BEGIN#\na\nb\n#SORTED_END#\nx\n#CASE_COMPLETE#\n";
+        assert_eq!(expected_output(ruffle, flash), ruffle);
+        assert_eq!(difference(ruffle, &expected_output(ruffle, flash)), None);
+    }
+
+    #[test]
+    fn expected_output_keeps_real_differences() {
+        let ruffle =
+            "#CAP#os=string:Linux\n#SORTED_BEGIN#\nb\na\n#SORTED_END#\n1\n#CASE_COMPLETE#\n";
+        let flash = "#CAP#os=number:1\n#SORTED_BEGIN#\nc\na\n#SORTED_END#\n2\n#CASE_COMPLETE#\n";
+        let expected = expected_output(ruffle, flash);
+        assert_eq!(expected, flash);
+        assert_eq!(difference(ruffle, &expected), difference(ruffle, flash));
+    }
+}
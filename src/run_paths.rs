@@ -0,0 +1,276 @@
+//! Where a run writes its files. Everything is kept under one run dir, with a temp dir per process holding a dir for
+//! each worker, so that several runs can share the same run dir without writing over each other's inputs
+use crate::{FAILURES_DIR_ENV, INPUTS_DIR_ENV, RUN_DIR, RUN_DIR_ENV};
+use std::path::{Path, PathBuf};
+
+/// The file in a process's temp dir holding the pid of the process that owns it
+const PID_FILE: &str = "owner.pid";
+
+/// Appended to the pid a process's temp dir is created under, it is renamed into place once it has its pid file so
+/// another process never sees it without one
+const STAGING_SUFFIX: &str = ".creating";
+
+/// The dir in the failures dir that `check-failures --prune` moves the failures ruffle has since fixed to
+pub const FIXED_DIR: &str = "fixed";
+
+/// The paths of a run, see `RunPaths::from_env` for how they are configured
+#[derive(Debug, Clone)]
+pub struct RunPaths {
+    pub inputs: PathBuf,
+    pub failures: PathBuf,
+    pub nondeterministic: PathBuf,
+    pub crashes: PathBuf,
+    pub corpus: PathBuf,
+    /// Replayed cases whose output still differs, see `replay`
+    pub replay: PathBuf,
+    /// How each mismatch was generated, one JSON object per line, see `replay::CaseRecord`
+    pub case_log: PathBuf,
+    pub stats_file: PathBuf,
+    pub bench_file: PathBuf,
+    pub bench_baseline_file: PathBuf,
+    /// The mismatch signatures found by previous campaigns, see `campaign`
+    pub signatures: PathBuf,
+    /// The mismatches found with each diff signature, see `diff_signature`
+    pub signature_summary: PathBuf,
+    /// The summary of the last campaign
+    pub campaign_summary: PathBuf,
+    /// The latest checkpoint of the run, see `checkpoint`
+    pub checkpoint: PathBuf,
+    /// What carries over to the next run, see `state_file`
+    pub state: PathBuf,
+    /// What rerunning the saved failures found the last time ruffle moved, see `reverify`
+    pub reverify: PathBuf,
+    /// The temp dirs of every process using the run dir
+    temp_root: PathBuf,
+    /// The temp dir of this process, named after its pid
+    temp: PathBuf,
+}
+
+impl Default for RunPaths {
+    fn default() -> Self {
+        Self::under(Path::new(RUN_DIR))
+    }
+}
+
+impl RunPaths {
+    /// The default layout under `run_dir`
+    pub fn under(run_dir: &Path) -> Self {
+        let temp_root = run_dir.join("tmp");
+        Self {
+            inputs: run_dir.join("inputs"),
+            failures: run_dir.join("failures"),
+            nondeterministic: run_dir.join("nondeterministic"),
+            crashes: run_dir.join("crashes"),
+            corpus: run_dir.join("corpus"),
+            replay: run_dir.join("replay"),
+            case_log: run_dir.join("cases.jsonl"),
+            stats_file: run_dir.join("stats.json"),
+            bench_file: run_dir.join("bench.json"),
+            bench_baseline_file: run_dir.join("bench_baseline.json"),
+            signatures: run_dir.join("signatures.txt"),
+            signature_summary: run_dir.join("signatures.json"),
+            campaign_summary: run_dir.join("summary.json"),
+            checkpoint: run_dir.join("checkpoint.json"),
+            state: run_dir.join("state").join("fuzz_state.bin"),
+            reverify: run_dir.join("reverify.json"),
+            temp: temp_root.join(std::process::id().to_string()),
+            temp_root,
+        }
+    }
+
+    /// The paths under `run_dir` if given, otherwise under `RUN_DIR_ENV` if set or `RUN_DIR`. The failures and
+    /// inputs dirs can be moved out of the run dir with `FAILURES_DIR_ENV` and `INPUTS_DIR_ENV`
+    pub fn from_env(run_dir: Option<PathBuf>) -> Self {
+        let run_dir = run_dir
+            .or_else(|| std::env::var_os(RUN_DIR_ENV).map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(RUN_DIR));
+        let mut paths = Self::under(&run_dir);
+        if let Some(failures) = std::env::var_os(FAILURES_DIR_ENV) {
+            paths.failures = PathBuf::from(failures);
+        }
+        if let Some(inputs) = std::env::var_os(INPUTS_DIR_ENV) {
+            paths.inputs = PathBuf::from(inputs);
+        }
+        paths
+    }
+
+    /// Where failures ruffle has since fixed are kept, see `failure_checker`
+    pub fn fixed_failures(&self) -> PathBuf {
+        self.failures.join(FIXED_DIR)
+    }
+
+    /// The temp dir of a worker, where the input flash runs is written
+    pub fn worker_temp(&self, worker_id: u32) -> PathBuf {
+        self.temp.join(format!("worker-{}", worker_id))
+    }
+
+    /// Create the run dirs, remove the temp dirs left by processes that are no longer running, and create this
+    /// process's temp dir with a dir for each of `worker_count` workers
+    pub fn create(&self, worker_count: u32) -> std::io::Result<()> {
+        for dir in [
+            &self.failures,
+            &self.inputs,
+            &self.nondeterministic,
+            &self.crashes,
+            &self.temp_root,
+        ] {
+            std::fs::create_dir_all(dir)?;
+        }
+        self.remove_stale_temps()?;
+
+        let staging = self.staging_temp();
+        // Either would be left by an earlier process with the same pid
+        remove_path(&staging)?;
+        remove_path(&self.temp)?;
+        std::fs::create_dir_all(&staging)?;
+        std::fs::write(staging.join(PID_FILE), std::process::id().to_string())?;
+        std::fs::rename(&staging, &self.temp)?;
+        for worker_id in 0..worker_count {
+            std::fs::create_dir_all(self.worker_temp(worker_id))?;
+        }
+        Ok(())
+    }
+
+    /// Where this process's temp dir is created before it is renamed into place, see `STAGING_SUFFIX`
+    fn staging_temp(&self) -> PathBuf {
+        self.temp_root
+            .join(format!("{}{}", std::process::id(), STAGING_SUFFIX))
+    }
+
+    /// Remove this process's temp dir, on a clean shutdown
+    pub fn remove_temp(&self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.temp) {
+            tracing::warn!("Failed to remove {}: {}", self.temp.display(), e);
+        }
+    }
+
+    /// Remove the temp dirs whose owner isn't running, they were left by a run that crashed or was killed.
+    /// A dir still being created is owned by the pid in its name, a dir without a pid file is from before temp dirs
+    /// were per process
+    fn remove_stale_temps(&self) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(&self.temp_root)?.flatten() {
+            let path = entry.path();
+            let owner = match entry
+                .file_name()
+                .to_string_lossy()
+                .strip_suffix(STAGING_SUFFIX)
+            {
+                Some(pid) => pid.parse::<u32>().ok(),
+                None => std::fs::read_to_string(path.join(PID_FILE))
+                    .ok()
+                    .and_then(|pid| pid.trim().parse::<u32>().ok()),
+            };
+            if matches!(owner, Some(pid) if pid == std::process::id() || is_running(pid)) {
+                continue;
+            }
+            tracing::info!("Removing stale temp dir {}", path.display());
+            remove_path(&path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Remove a file or dir, which another process sharing the run dir may already have removed
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    let removed = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    match removed {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Is there a process with the given pid
+fn is_running(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // Signal 0 only checks that the process exists, EPERM means it does but belongs to someone else
+        let exists = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+        exists || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    {
+        // Without a way to check, assume it is so another run's inputs are never removed
+        let _ = pid;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pid above any system's `pid_max`, so never running
+    const DEAD_PID: u32 = 0x7fff_fff0;
+
+    /// An empty run dir for a test
+    fn run_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "flash-fuzz-run-paths-{}-{}",
+            std::process::id(),
+            name
+        ));
+        remove_path(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn create_temp_with_pid_and_workers() {
+        let dir = run_dir("create");
+        let paths = RunPaths::under(&dir);
+        paths.create(2).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(paths.temp.join(PID_FILE)).unwrap(),
+            std::process::id().to_string()
+        );
+        assert!(paths.worker_temp(0).is_dir() && paths.worker_temp(1).is_dir());
+        assert!(!paths.staging_temp().exists());
+
+        // Creating again, as a later process with the same pid would, starts over
+        std::fs::write(paths.worker_temp(0).join("out.swf"), b"").unwrap();
+        paths.create(1).unwrap();
+        assert!(!paths.worker_temp(0).join("out.swf").exists());
+        assert!(!paths.worker_temp(1).exists());
+
+        paths.remove_temp();
+        assert!(!paths.temp.exists());
+        remove_path(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_stale_temps_removed() {
+        let dir = run_dir("stale");
+        let paths = RunPaths::under(&dir);
+        let temp = |name: &str, pid: Option<u32>| {
+            let temp = paths.temp_root.join(name);
+            std::fs::create_dir_all(&temp).unwrap();
+            if let Some(pid) = pid {
+                std::fs::write(temp.join(PID_FILE), pid.to_string()).unwrap();
+            }
+            temp
+        };
+        let parent = std::os::unix::process::parent_id();
+        let live = temp(&parent.to_string(), Some(parent));
+        // Another process between creating its temp dir and renaming it into place, with no pid file yet
+        let creating = temp(&format!("{}{}", parent, STAGING_SUFFIX), None);
+        let dead = temp(&DEAD_PID.to_string(), Some(DEAD_PID));
+        let dead_creating = temp(&format!("{}{}", DEAD_PID, STAGING_SUFFIX), None);
+        let legacy = temp("worker-0", None);
+
+        paths.create(1).unwrap();
+        assert!(live.is_dir() && creating.is_dir());
+        assert!(!dead.exists() && !dead_creating.exists() && !legacy.exists());
+        remove_path(&dir).unwrap();
+    }
+
+    #[test]
+    fn removing_missing_path_succeeds() {
+        let dir = run_dir("missing");
+        remove_path(&dir).unwrap();
+        remove_path(&dir.join("owner.pid")).unwrap();
+    }
+}
@@ -0,0 +1,267 @@
+//! Generator self-test, each mode generates a case from a fixed seed which is disassembled and compared
+//! against a checked-in listing, so changes to what the generator emits show up as reviewable diffs
+use crate::swf_generator::{GeneratorMode, SwfGenerator};
+use std::error::Error;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::slice::Iter;
+use swf::avm1::read::Reader;
+use swf::avm1::types::Action;
+use swf::Tag;
+
+/// Run the self-test with `flash-fuzz selftest`
+pub const SELFTEST_COMMAND: &str = "selftest";
+
+/// Pass this to `selftest` to overwrite the goldens with the current output
+pub const BLESS_ARG: &str = "--bless";
+
+/// The seed every mode is generated from
+const SELFTEST_SEED: u64 = 0x5eed;
+
+/// Where the golden listings are kept, one `<mode>.txt` per mode
+const GOLDEN_DIR: &str = "selftest";
+
+/// The most differing lines printed for each mode
+const MAX_DIFF_LINES: usize = 20;
+
+/// Actions that print longer than this, such as the padding in tag size cases, are cut short in the listing
+const MAX_ACTION_LEN: usize = 200;
+
+/// Disassemble an action stream into `out`, nested function and try bodies are indented under their action.
+/// InitObject and InitArray actions are followed by the next of `constructions`, if there are any left
+fn disassemble(
+    out: &mut String,
+    actions: &[u8],
+    swf_version: u8,
+    indent: usize,
+    constructions: &mut Iter<String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut reader = Reader::new(actions, swf_version);
+    while !reader.get_ref().is_empty() {
+        let start = reader.get_ref();
+        let action = match reader.read_action() {
+            Ok(action) => action,
+            // Such as a push of a string with a null in it, which ends the string early. Listed as an error and
+            // skipped up to the length in its header
+            Err(e) => {
+                writeln!(out, "{:indent$}Error: {}", "", e, indent = indent)?;
+                let mut header = Reader::new(start, swf_version);
+                let (_, length) = header.read_opcode_and_length()?;
+                let rest = header.get_ref();
+                reader = Reader::new(&rest[length.min(rest.len())..], swf_version);
+                continue;
+            }
+        };
+        match &action {
+            Action::DefineFunction(function) => {
+                writeln!(
+                    out,
+                    "{:indent$}DefineFunction {}({})",
+                    "",
+                    function.name,
+                    function
+                        .params
+                        .iter()
+                        .map(|param| param.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    indent = indent
+                )?;
+                disassemble(
+                    out,
+                    function.actions,
+                    swf_version,
+                    indent + 4,
+                    constructions,
+                )?;
+            }
+            Action::DefineFunction2(function) => {
+                writeln!(
+                    out,
+                    "{:indent$}DefineFunction2 {}",
+                    "",
+                    function.name,
+                    indent = indent
+                )?;
+                disassemble(
+                    out,
+                    function.actions,
+                    swf_version,
+                    indent + 4,
+                    constructions,
+                )?;
+            }
+            Action::Try(try_block) => {
+                writeln!(out, "{:indent$}Try", "", indent = indent)?;
+                disassemble(
+                    out,
+                    try_block.try_body,
+                    swf_version,
+                    indent + 4,
+                    constructions,
+                )?;
+                if let Some((catch_var, catch_body)) = &try_block.catch_body {
+                    writeln!(out, "{:indent$}Catch {:?}", "", catch_var, indent = indent)?;
+                    disassemble(out, catch_body, swf_version, indent + 4, constructions)?;
+                }
+                if let Some(finally_body) = try_block.finally_body {
+                    writeln!(out, "{:indent$}Finally", "", indent = indent)?;
+                    disassemble(out, finally_body, swf_version, indent + 4, constructions)?;
+                }
+            }
+            Action::End => break,
+            _ => {
+                let line = format!("{:?}", action);
+                match line.char_indices().nth(MAX_ACTION_LEN) {
+                    Some((cut, _)) => writeln!(
+                        out,
+                        "{:indent$}{}... ({} chars)",
+                        "",
+                        &line[..cut],
+                        line.chars().count(),
+                        indent = indent
+                    )?,
+                    None => writeln!(out, "{:indent$}{}", "", line, indent = indent)?,
+                }
+                if matches!(action, Action::InitObject | Action::InitArray) {
+                    if let Some(construction) = constructions.next() {
+                        writeln!(
+                            out,
+                            "{:indent$}// {}",
+                            "",
+                            construction,
+                            indent = indent + 4
+                        )?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Produce the listing of a generated swf, DoAction tags are disassembled, DefineBinaryData tags are listed by length
+/// and other tags are debug printed.
+/// Tags that can't be read, such as those in malformed container cases, are listed as errors
+pub fn listing(swf_data: &[u8]) -> Result<String, Box<dyn Error>> {
+    annotated_listing(swf_data, &[])
+}
+
+/// The listing of a generated swf with each object and array construction annotated with the value it constructs,
+/// see `SwfGenerator::constructions`
+pub fn annotated_listing(
+    swf_data: &[u8],
+    constructions: &[String],
+) -> Result<String, Box<dyn Error>> {
+    let mut constructions = constructions.iter();
+    let swf_buf = swf::decompress_swf(swf_data)?;
+    let swf_version = swf_buf.header.version();
+    let mut reader = swf::read::Reader::new(&swf_buf.data, swf_version);
+
+    let mut out = String::new();
+    writeln!(out, "SWF{}", swf_version)?;
+    while !reader.get_ref().is_empty() {
+        let remaining = reader.get_ref().len();
+        match reader.read_tag() {
+            Ok(Tag::End) => break,
+            Ok(Tag::DoAction(actions)) => {
+                writeln!(out, "DoAction")?;
+                disassemble(&mut out, actions, swf_version, 4, &mut constructions)?;
+            }
+            Ok(Tag::DefineBinaryData(data)) => writeln!(
+                out,
+                "DefineBinaryData {{ id: {}, len: {} }}",
+                data.id,
+                data.data.len()
+            )?,
+            Ok(tag) => writeln!(out, "{:?}", tag)?,
+            Err(e) => {
+                writeln!(out, "Error: {}", e)?;
+                // The rest can't be read if not even the tag header could be
+                if reader.get_ref().len() == remaining {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn golden_path(mode: GeneratorMode) -> PathBuf {
+    Path::new(GOLDEN_DIR).join(format!("{}.txt", mode.name()))
+}
+
+/// The lines that differ between `expected` and `actual`, numbered and marked - and +. At most `max_lines` are
+/// included, None for all of them
+pub fn line_diff(expected: &str, actual: &str, max_lines: Option<usize>) -> String {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let mut out = String::new();
+    let mut printed = 0;
+    for i in 0..expected_lines.len().max(actual_lines.len()) {
+        let (expected, actual) = (expected_lines.get(i), actual_lines.get(i));
+        if expected == actual {
+            continue;
+        }
+        if Some(printed) == max_lines {
+            out.push_str("...\n");
+            break;
+        }
+        if let Some(expected) = expected {
+            let _ = writeln!(out, "{:5} - {}", i + 1, expected);
+        }
+        if let Some(actual) = actual {
+            let _ = writeln!(out, "{:5} + {}", i + 1, actual);
+        }
+        printed += 1;
+    }
+    out
+}
+
+/// Generate every mode from the fixed seed and compare with the goldens, or overwrite them when `bless` is set
+pub fn run(bless: bool) -> Result<(), Box<dyn Error>> {
+    if bless {
+        std::fs::create_dir_all(GOLDEN_DIR)?;
+    }
+
+    let mut failed = Vec::new();
+    for &mode in GeneratorMode::ALL {
+        let mut swf_data = Vec::new();
+        let mut generator = SwfGenerator::with_seed(SELFTEST_SEED);
+        // Seeded the way workers seed each case, so that the case embeds its seed as theirs do
+        generator.reseed(SELFTEST_SEED);
+        generator.generate(&[mode], &mut swf_data)?;
+        let mut generated = listing(&swf_data)?;
+        // The input is part of the case too
+        for line in generator.input_script().to_string().lines() {
+            writeln!(generated, "input {}", line)?;
+        }
+        let path = golden_path(mode);
+
+        if bless {
+            std::fs::write(&path, &generated)?;
+            println!("{}: blessed", mode.name());
+            continue;
+        }
+
+        let golden = std::fs::read_to_string(&path).unwrap_or_default();
+        if golden == generated {
+            println!("{}: ok", mode.name());
+        } else {
+            println!("{}: output changed ({})", mode.name(), path.display());
+            print!("{}", line_diff(&golden, &generated, Some(MAX_DIFF_LINES)));
+            failed.push(mode.name());
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Generator output changed for: {}, rerun with {} if this is intended",
+            failed.join(", "),
+            BLESS_ARG
+        )
+        .into())
+    }
+}
@@ -0,0 +1,113 @@
+//! Handling of the sentinel traced at the end of every fuzz case, and the nonce traced at the start
+use crate::MyError;
+
+/// Traced by every fuzz case once it has run to completion
+pub const CASE_COMPLETE_SENTINEL: &str = "#CASE_COMPLETE#";
+
+/// Traced by every fuzz case before anything else, followed by the case's nonce
+pub const NONCE_PREFIX: &str = "#NONCE:";
+
+/// The first line traced by the case with the given nonce
+pub fn nonce_line(nonce: u64) -> String {
+    format!("{}{:016x}#", NONCE_PREFIX, nonce)
+}
+
+/// Check that `output` starts with the line for `nonce`, so output left over from another case isn't taken for
+/// this one's. Empty output can't have come from another case, the case just didn't get as far as tracing
+pub fn check_nonce(output: &str, nonce: u64) -> Result<(), MyError> {
+    match output.lines().next() {
+        Some(line) if line.trim_end() != nonce_line(nonce) => Err(MyError::CaptureError),
+        _ => Ok(()),
+    }
+}
+
+/// What a player runs a movie until, aside from it timing out or its output overflowing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunUntil {
+    /// The case traces `CASE_COMPLETE_SENTINEL`, output after it is dropped
+    Sentinel,
+    /// This many frames have run, for movies that weren't generated by the fuzzer and so never trace the sentinel
+    Frames(u32),
+}
+
+/// Cut `output` at the end of the line containing the completion sentinel, anything logged after it is dropped.
+/// Returns `None` if the sentinel was never logged
+pub fn trim_to_sentinel(output: &str) -> Option<&str> {
+    let sentinel_end = output.find(CASE_COMPLETE_SENTINEL)? + CASE_COMPLETE_SENTINEL.len();
+    let line_end = output[sentinel_end..]
+        .find('\n')
+        .map_or(output.len(), |i| sentinel_end + i + 1);
+    Some(&output[..line_end])
+}
+
+/// Truncate `output` in place at the end of the completion sentinel line, if it has one
+pub fn truncate_to_sentinel(output: &mut String) {
+    if let Some(len) = trim_to_sentinel(output).map(str::len) {
+        output.truncate(len);
+    }
+}
+
+/// Does `output` end with the completion sentinel line, i.e. did the case run to completion
+pub fn is_complete(output: &str) -> bool {
+    matches!(trim_to_sentinel(output), Some(trimmed) if trimmed.len() == output.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentinel_mid_buffer() {
+        let output = "a\n#CASE_COMPLETE#\nb\n";
+        assert_eq!(trim_to_sentinel(output), Some("a\n#CASE_COMPLETE#\n"));
+        assert!(!is_complete(output));
+
+        let mut output = output.to_string();
+        truncate_to_sentinel(&mut output);
+        assert_eq!(output, "a\n#CASE_COMPLETE#\n");
+        assert!(is_complete(&output));
+    }
+
+    #[test]
+    fn sentinel_at_end() {
+        assert_eq!(
+            trim_to_sentinel("a\n#CASE_COMPLETE#\n"),
+            Some("a\n#CASE_COMPLETE#\n")
+        );
+        assert_eq!(
+            trim_to_sentinel("a\r\n#CASE_COMPLETE#\r\nb"),
+            Some("a\r\n#CASE_COMPLETE#\r\n")
+        );
+        // The final line may not have been terminated yet
+        assert_eq!(
+            trim_to_sentinel("a\n#CASE_COMPLETE#"),
+            Some("a\n#CASE_COMPLETE#")
+        );
+        assert!(is_complete("a\n#CASE_COMPLETE#"));
+    }
+
+    #[test]
+    fn nonce_first_line() {
+        let output = format!("{}\r\na\n#CASE_COMPLETE#\n", nonce_line(0x1234));
+        assert!(check_nonce(&output, 0x1234).is_ok());
+        assert!(check_nonce("", 0x1234).is_ok());
+        // Output from a previous case ahead of this one's
+        let stale = format!("b\n#CASE_COMPLETE#\n{}", output);
+        assert!(matches!(
+            check_nonce(&stale, 0x1234),
+            Err(MyError::CaptureError)
+        ));
+        assert!(check_nonce(&output, 0x1235).is_err());
+    }
+
+    #[test]
+    fn sentinel_missing() {
+        assert_eq!(trim_to_sentinel(""), None);
+        assert_eq!(trim_to_sentinel("a\n#CASE_\n"), None);
+        assert!(!is_complete("a\n#CASE_\n"));
+
+        let mut output = "a\n#CASE_".to_string();
+        truncate_to_sentinel(&mut output);
+        assert_eq!(output, "a\n#CASE_");
+    }
+}
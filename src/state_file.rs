@@ -0,0 +1,292 @@
+//! The state that carries over from one run to the next in the same run dir, so that a run restarted after a reboot
+//! or a wedged flash player doesn't run the cases it already ran again. Every `STATE_FLUSH_INTERVAL` and when the
+//! workers stop, including on ctrl-c, the hashes of the cases run so far, the total iterations, the per-mode stats and the diff
+//! signatures are written to `RunPaths::state`, and a run that isn't resuming a checkpoint loads them when it starts. The file starts with
+//! `STATE_MAGIC`, `STATE_VERSION` and `GENERATOR_VERSION` and ends with the md5 of what's between, and a file that was
+//! written by another build or doesn't check out is ignored rather than loaded
+use crate::attempted::AttemptedSet;
+use crate::diff_signature::DiffSignatures;
+use crate::fuzz_session::SharedFuzzState;
+use crate::swf_generator::{GeneratorMode, GENERATOR_VERSION};
+use crate::{ATTEMPTED_MEMORY_CAP, STATE_FLUSH_INTERVAL};
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+/// The start of every state file
+const STATE_MAGIC: &[u8; 8] = b"FFZSTATE";
+
+/// Bumped whenever the layout of the state file changes, files of other versions are ignored
+const STATE_VERSION: u32 = 2;
+
+/// Held while writing, so the timer and the workers stopping don't write the temp file at the same time
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+pub(crate) fn write_u64(out: &mut impl Write, value: u64) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub(crate) fn write_string(out: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u64(out, value.len() as u64)?;
+    out.write_all(value.as_bytes())
+}
+
+pub(crate) fn read_string(input: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_u64(input)? as usize];
+    input.read_exact(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The counts of a mode, by the name it was saved under
+struct SavedMode {
+    name: String,
+    sub_cases: usize,
+    mismatches: usize,
+    weight: u32,
+}
+
+/// What a state file holds
+struct SavedState {
+    total_iterations: usize,
+    modes: Vec<SavedMode>,
+    attempted: AttemptedSet,
+    diff_signatures: DiffSignatures,
+}
+
+/// The contents of a state file for `state`
+fn to_bytes(state: &SharedFuzzState) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    // The stats logger hasn't added the iterations since it last ran to the total yet
+    write_u64(
+        &mut body,
+        (state.total_iterations.load(Ordering::SeqCst) + state.iterations.load(Ordering::SeqCst))
+            as u64,
+    )?;
+    write_u64(&mut body, GeneratorMode::ALL.len() as u64)?;
+    for mode in GeneratorMode::ALL {
+        write_string(&mut body, mode.name())?;
+        write_u64(
+            &mut body,
+            state.mode_stats.sub_cases(*mode).load(Ordering::SeqCst) as u64,
+        )?;
+        write_u64(
+            &mut body,
+            state.mode_stats.mismatches(*mode).load(Ordering::SeqCst) as u64,
+        )?;
+        write_u64(
+            &mut body,
+            state.mode_scheduler.weight(*mode).load(Ordering::SeqCst) as u64,
+        )?;
+    }
+    state.attempted.write_to(&mut body)?;
+    state.diff_signatures.write_to(&mut body)?;
+
+    let mut bytes = STATE_MAGIC.to_vec();
+    bytes.extend_from_slice(&STATE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&GENERATOR_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&body);
+    bytes.extend_from_slice(&md5::compute(&body).0);
+    Ok(bytes)
+}
+
+/// Parse a state file written by `to_bytes`, fails if it was written by another build or is corrupt
+fn from_bytes(bytes: &[u8]) -> Result<SavedState, String> {
+    let header_len = STATE_MAGIC.len() + 8;
+    if bytes.len() < header_len + 16 || !bytes.starts_with(STATE_MAGIC) {
+        return Err("not a state file".to_string());
+    }
+    let header_u32 = |offset: usize| {
+        u32::from_le_bytes(
+            bytes[STATE_MAGIC.len() + offset..STATE_MAGIC.len() + offset + 4]
+                .try_into()
+                .unwrap(),
+        )
+    };
+    let (version, generator_version) = (header_u32(0), header_u32(4));
+    if version != STATE_VERSION {
+        return Err(format!(
+            "state version {}, this build reads version {}",
+            version, STATE_VERSION
+        ));
+    }
+    if generator_version != GENERATOR_VERSION {
+        return Err(format!(
+            "written with generator version {}, this build has {}",
+            generator_version, GENERATOR_VERSION
+        ));
+    }
+    let (body, checksum) = bytes[header_len..].split_at(bytes.len() - header_len - 16);
+    if md5::compute(body).0 != checksum {
+        return Err("checksum doesn't match".to_string());
+    }
+
+    let parse = |mut body: &[u8]| -> io::Result<SavedState> {
+        let total_iterations = read_u64(&mut body)? as usize;
+        let modes = (0..read_u64(&mut body)?)
+            .map(|_| {
+                Ok(SavedMode {
+                    name: read_string(&mut body)?,
+                    sub_cases: read_u64(&mut body)? as usize,
+                    mismatches: read_u64(&mut body)? as usize,
+                    weight: read_u64(&mut body)? as u32,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let attempted = AttemptedSet::read_from(&mut body, ATTEMPTED_MEMORY_CAP)?;
+        let diff_signatures = DiffSignatures::read_from(&mut body)?;
+        Ok(SavedState {
+            total_iterations,
+            modes,
+            attempted,
+            diff_signatures,
+        })
+    };
+    parse(body).map_err(|e| e.to_string())
+}
+
+/// Write the state to `path`, through a temporary file so that a run stopped while it is being written leaves the
+/// previous state intact
+pub fn write(state: &SharedFuzzState, path: &Path) -> Result<(), Box<dyn Error>> {
+    let _lock = WRITE_LOCK.lock().unwrap();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, to_bytes(state)?)?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Load the state left by previous runs from `RunPaths::state` if there is one, ignoring it if it can't be loaded
+pub fn load(state: &mut SharedFuzzState) {
+    let path = state.config.paths.state.clone();
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => {
+            tracing::warn!("Ignoring {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let saved = match from_bytes(&bytes) {
+        Ok(saved) => saved,
+        Err(e) => {
+            tracing::warn!("Ignoring {}: {}", path.display(), e);
+            return;
+        }
+    };
+    state
+        .total_iterations
+        .store(saved.total_iterations, Ordering::SeqCst);
+    for saved_mode in &saved.modes {
+        // Modes removed since the state was written are dropped
+        if let Some(mode) = GeneratorMode::from_name(&saved_mode.name) {
+            state
+                .mode_stats
+                .sub_cases(mode)
+                .store(saved_mode.sub_cases, Ordering::SeqCst);
+            state
+                .mode_stats
+                .mismatches(mode)
+                .store(saved_mode.mismatches, Ordering::SeqCst);
+            state
+                .mode_scheduler
+                .weight(mode)
+                .store(saved_mode.weight, Ordering::SeqCst);
+        }
+    }
+    state.attempted = saved.attempted;
+    state.diff_signatures = saved.diff_signatures;
+    tracing::info!(
+        "Loaded {} with {} attempted cases and {} diff signatures after {} iterations",
+        path.display(),
+        state.attempted.len(),
+        state.diff_signatures.len(),
+        saved.total_iterations
+    );
+}
+
+/// Write the state to `RunPaths::state` every `STATE_FLUSH_INTERVAL`
+pub fn spawn_state_writer(state: Arc<SharedFuzzState>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(STATE_FLUSH_INTERVAL);
+        if let Err(e) = write(&state, &state.config.paths.state) {
+            tracing::warn!(
+                "Failed to write {}: {}",
+                state.config.paths.state.display(),
+                e
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_round_trips_and_rejects_corruption() {
+        let state = SharedFuzzState::default();
+        state.total_iterations.store(40, Ordering::SeqCst);
+        state.iterations.store(2, Ordering::SeqCst);
+        state.attempted.insert(md5::compute(b"case"));
+        state
+            .mode_stats
+            .sub_cases(GeneratorMode::Opcode)
+            .store(15, Ordering::SeqCst);
+        state
+            .mode_scheduler
+            .weight(GeneratorMode::Opcode)
+            .store(250, Ordering::SeqCst);
+        state.diff_signatures.record("Multiply: N | N", "abc");
+        let bytes = to_bytes(&state).unwrap();
+
+        let saved = from_bytes(&bytes).unwrap();
+        assert_eq!(saved.total_iterations, 42);
+        assert_eq!(
+            saved.diff_signatures.first_exemplar("Multiply: N | N"),
+            Some("abc".to_string())
+        );
+        assert!(!saved.attempted.insert(md5::compute(b"case")));
+        assert!(saved.attempted.insert(md5::compute(b"other case")));
+        let opcode = saved
+            .modes
+            .iter()
+            .find(|mode| mode.name == GeneratorMode::Opcode.name())
+            .unwrap();
+        assert_eq!((opcode.sub_cases, opcode.weight), (15, 250));
+
+        let mut corrupt = bytes.clone();
+        corrupt[bytes.len() / 2] ^= 1;
+        assert!(from_bytes(&corrupt).is_err());
+        let mut other_version = bytes.clone();
+        other_version[STATE_MAGIC.len()] += 1;
+        assert!(from_bytes(&other_version).is_err());
+        assert!(from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(from_bytes(b"{}").is_err());
+
+        // Past the memory cap the bloom filters are saved as they are
+        let state = SharedFuzzState {
+            attempted: AttemptedSet::with_memory_cap(64 * 1024),
+            ..SharedFuzzState::default()
+        };
+        for i in 0..10_000u32 {
+            state.attempted.insert(md5::compute(i.to_le_bytes()));
+        }
+        let saved = from_bytes(&to_bytes(&state).unwrap()).unwrap();
+        assert_eq!(saved.attempted.len(), state.attempted.len());
+        assert_eq!(
+            saved.attempted.false_positive_rate(),
+            state.attempted.false_positive_rate()
+        );
+        assert!((0..10_000u32).all(|i| !saved.attempted.insert(md5::compute(i.to_le_bytes()))));
+    }
+}
@@ -0,0 +1,393 @@
+//! The static methods that static function fuzz cases call, one table per class, and tracking of which
+//! (class, method, argument count) combinations have been run
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
+
+/// A static method of a class
+pub struct StaticMethod {
+    pub name: &'static str,
+    /// The argument counts to call it with, every count up to the end of the range is used so that missing
+    /// arguments are covered too
+    pub args: RangeInclusive<i32>,
+    /// The type each argument should have, static function cases mostly pass a value of that type (see
+    /// `DoActionGenerator::push_typed_arg`). One of `Number`, `Boolean`, `String`, `LinkageName` (the name of an
+    /// exported symbol), `MathNumber` (a number from the corner cases of the Math methods), `Object`, `Array`, `Point`,
+    /// `Function` or `Any`
+    pub arg_types: &'static [&'static str],
+    /// Why this method isn't called, None if it is
+    pub disabled: Option<&'static str>,
+}
+
+impl StaticMethod {
+    const fn enabled(
+        name: &'static str,
+        args: RangeInclusive<i32>,
+        arg_types: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            args,
+            arg_types,
+            disabled: None,
+        }
+    }
+
+    const fn disabled(
+        name: &'static str,
+        args: RangeInclusive<i32>,
+        arg_types: &'static [&'static str],
+        reason: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            args,
+            arg_types,
+            disabled: Some(reason),
+        }
+    }
+}
+
+pub struct StaticClass {
+    pub name: &'static str,
+    pub methods: &'static [StaticMethod],
+}
+
+const ACCESSIBILITY: &[StaticMethod] = &[StaticMethod::enabled("isActive", 0..=0, &[])];
+
+const BITMAP_DATA: &[StaticMethod] = &[StaticMethod::enabled("loadBitmap", 1..=1, &["String"])];
+
+const CAMERA: &[StaticMethod] = &[StaticMethod::disabled(
+    "get",
+    0..=1,
+    &["Number"],
+    "Depends on the cameras of the machine",
+)];
+
+const CUSTOM_ACTIONS: &[StaticMethod] = &[
+    StaticMethod::enabled("get", 1..=1, &["String"]),
+    StaticMethod::enabled("install", 2..=2, &["String", "String"]),
+    StaticMethod::enabled("list", 0..=0, &[]),
+    StaticMethod::enabled("uninstall", 1..=1, &["String"]),
+];
+
+const DATE: &[StaticMethod] = &[StaticMethod::enabled(
+    "UTC",
+    2..=7,
+    &[
+        "Number", "Number", "Number", "Number", "Number", "Number", "Number",
+    ],
+)];
+
+const EXTERNAL_INTERFACE: &[StaticMethod] = &[
+    StaticMethod::enabled("addCallback", 3..=3, &["String", "Object", "Function"]),
+    StaticMethod::enabled("call", 1..=2, &["String", "Any"]),
+];
+
+const KEY: &[StaticMethod] = &[
+    StaticMethod::enabled("getAscii", 0..=0, &[]),
+    StaticMethod::enabled("getCode", 0..=0, &[]),
+    StaticMethod::disabled(
+        "isAccessible",
+        0..=0,
+        &[],
+        "Depends on the accessibility settings of the machine",
+    ),
+    StaticMethod::enabled("isDown", 1..=1, &["Number"]),
+    StaticMethod::disabled(
+        "isToggled",
+        1..=1,
+        &["Number"],
+        "Depends on the caps and num lock state of the machine",
+    ),
+    StaticMethod::enabled("removeListener", 1..=1, &["Object"]),
+];
+
+const LOCALE: &[StaticMethod] = &[
+    StaticMethod::enabled("checkXMLStatus", 0..=0, &[]),
+    StaticMethod::enabled("getDefaultLang", 0..=0, &[]),
+    StaticMethod::enabled("loadString", 1..=1, &["String"]),
+    StaticMethod::enabled("loadStringEx", 2..=2, &["String", "String"]),
+];
+
+const STRING: &[StaticMethod] = &[StaticMethod::enabled("fromCharCode", 1..=1, &["Number"])];
+
+const MATH: &[StaticMethod] = &[
+    StaticMethod::enabled("abs", 1..=1, &["MathNumber"]),
+    StaticMethod::enabled("atan2", 2..=2, &["MathNumber", "MathNumber"]),
+    StaticMethod::enabled("ceil", 1..=1, &["MathNumber"]),
+    StaticMethod::enabled("exp", 1..=1, &["MathNumber"]),
+    StaticMethod::enabled("floor", 1..=1, &["MathNumber"]),
+    StaticMethod::enabled("log", 1..=1, &["MathNumber"]),
+    StaticMethod::enabled("max", 2..=2, &["MathNumber", "MathNumber"]),
+    StaticMethod::enabled("min", 2..=2, &["MathNumber", "MathNumber"]),
+    StaticMethod::enabled("pow", 2..=2, &["MathNumber", "MathNumber"]),
+    StaticMethod::disabled(
+        "random",
+        0..=0,
+        &[],
+        "Nondeterministic, called separately when SEEDED_RANDOM is set",
+    ),
+    StaticMethod::enabled("round", 1..=1, &["MathNumber"]),
+    StaticMethod::enabled("sqrt", 1..=1, &["MathNumber"]),
+];
+
+const MICROPHONE: &[StaticMethod] = &[StaticMethod::disabled(
+    "get",
+    1..=1,
+    &["Number"],
+    "Depends on the microphones of the machine",
+)];
+
+const MOUSE: &[StaticMethod] = &[
+    StaticMethod::disabled(
+        "hide",
+        0..=0,
+        &[],
+        "Changes the cursor of the projector window",
+    ),
+    StaticMethod::enabled("removeListener", 1..=1, &["Object"]),
+    StaticMethod::disabled(
+        "show",
+        0..=0,
+        &[],
+        "Changes the cursor of the projector window",
+    ),
+];
+
+const OBJECT: &[StaticMethod] = &[StaticMethod::enabled(
+    "registerClass",
+    2..=2,
+    &["LinkageName", "Function"],
+)];
+
+const POINT: &[StaticMethod] = &[
+    StaticMethod::enabled("distance", 2..=2, &["Point", "Point"]),
+    StaticMethod::enabled("interpolate", 3..=3, &["Point", "Point", "Number"]),
+    StaticMethod::enabled("polar", 2..=2, &["Number", "Number"]),
+];
+
+const SELECTION: &[StaticMethod] = &[
+    StaticMethod::enabled("getBeginIndex", 0..=0, &[]),
+    StaticMethod::enabled("getCaretIndex", 0..=0, &[]),
+    StaticMethod::enabled("getEndIndex", 0..=0, &[]),
+    StaticMethod::enabled("getFocus", 0..=0, &[]),
+    StaticMethod::enabled("removeListener", 1..=1, &["Object"]),
+    StaticMethod::enabled("setFocus", 1..=1, &["Object"]),
+];
+
+const SHARED_OBJECT: &[StaticMethod] = &[StaticMethod::enabled(
+    "getLocal",
+    1..=3,
+    &["String", "String", "Boolean"],
+)];
+
+const STAGE: &[StaticMethod] = &[StaticMethod::enabled("removeListener", 1..=1, &["Object"])];
+
+const TEXT_FIELD: &[StaticMethod] = &[StaticMethod::enabled("getFontList", 0..=0, &[])];
+
+const XMLUI: &[StaticMethod] = &[StaticMethod::enabled("get", 1..=1, &["String"])];
+
+//TODO: IME
+/// Every class with static methods, static function cases pick from the enabled methods in this order
+pub const STATIC_CLASSES: &[StaticClass] = &[
+    StaticClass {
+        name: "Accessibility",
+        methods: ACCESSIBILITY,
+    },
+    StaticClass {
+        name: "BitmapData",
+        methods: BITMAP_DATA,
+    },
+    StaticClass {
+        name: "Camera",
+        methods: CAMERA,
+    },
+    StaticClass {
+        name: "CustomActions",
+        methods: CUSTOM_ACTIONS,
+    },
+    StaticClass {
+        name: "Date",
+        methods: DATE,
+    },
+    StaticClass {
+        name: "ExternalInterface",
+        methods: EXTERNAL_INTERFACE,
+    },
+    StaticClass {
+        name: "Key",
+        methods: KEY,
+    },
+    StaticClass {
+        name: "Locale",
+        methods: LOCALE,
+    },
+    StaticClass {
+        name: "String",
+        methods: STRING,
+    },
+    StaticClass {
+        name: "Math",
+        methods: MATH,
+    },
+    StaticClass {
+        name: "Microphone",
+        methods: MICROPHONE,
+    },
+    StaticClass {
+        name: "Mouse",
+        methods: MOUSE,
+    },
+    StaticClass {
+        name: "Object",
+        methods: OBJECT,
+    },
+    StaticClass {
+        name: "Point",
+        methods: POINT,
+    },
+    StaticClass {
+        name: "Selection",
+        methods: SELECTION,
+    },
+    StaticClass {
+        name: "SharedObject",
+        methods: SHARED_OBJECT,
+    },
+    StaticClass {
+        name: "Stage",
+        methods: STAGE,
+    },
+    StaticClass {
+        name: "TextField",
+        methods: TEXT_FIELD,
+    },
+    StaticClass {
+        name: "XMLUI",
+        methods: XMLUI,
+    },
+];
+
+/// A static method call that a case made: the class, method and argument count
+pub type StaticCall = (&'static str, &'static str, i32);
+
+/// The methods that static function cases call, as (class name, method) pairs
+pub fn enabled_methods() -> Vec<(&'static str, &'static StaticMethod)> {
+    STATIC_CLASSES
+        .iter()
+        .flat_map(|class| class.methods.iter().map(move |method| (class.name, method)))
+        .filter(|(_, method)| method.disabled.is_none())
+        .collect()
+}
+
+/// Every call that static function cases can make
+fn all_calls() -> BTreeSet<StaticCall> {
+    enabled_methods()
+        .into_iter()
+        .flat_map(|(class, method)| {
+            (0..=*method.args.end()).map(move |arg_count| (class, method.name, arg_count))
+        })
+        .collect()
+}
+
+/// Which of the calls that static function cases can make have been run
+pub struct StaticCoverage {
+    all: BTreeSet<StaticCall>,
+    executed: Mutex<BTreeSet<StaticCall>>,
+}
+
+impl Default for StaticCoverage {
+    fn default() -> Self {
+        Self {
+            all: all_calls(),
+            executed: Mutex::default(),
+        }
+    }
+}
+
+impl StaticCoverage {
+    /// Record the calls of a case that ran, calls to disabled methods aren't counted
+    pub fn record(&self, calls: &[StaticCall]) {
+        let mut executed = self.executed.lock().unwrap();
+        for call in calls {
+            if self.all.contains(call) {
+                executed.insert(*call);
+            }
+        }
+    }
+
+    pub fn executed(&self) -> usize {
+        self.executed.lock().unwrap().len()
+    }
+
+    pub fn total(&self) -> usize {
+        self.all.len()
+    }
+
+    /// The calls that have been run
+    pub fn executed_calls(&self) -> Vec<StaticCall> {
+        self.executed.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Mark a call as run, e.g. by a checkpointed campaign. Returns false if static function cases can't make it
+    pub fn restore(&self, class: &str, method: &str, arg_count: i32) -> bool {
+        let call = self
+            .all
+            .iter()
+            .find(|(c, m, a)| *c == class && *m == method && *a == arg_count);
+        match call {
+            Some(call) => {
+                self.executed.lock().unwrap().insert(*call);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The calls that haven't been run yet
+    pub fn missing(&self) -> Vec<StaticCall> {
+        let executed = self.executed.lock().unwrap();
+        self.all.difference(&executed).copied().collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "executed": self.executed(),
+            "total": self.total(),
+        })
+    }
+}
+
+/// Log how many of the static method calls have been run, and which haven't, when a run ends
+pub fn log_static_coverage(coverage: &StaticCoverage) {
+    tracing::info!(
+        "Static methods: {}/{} calls run",
+        coverage.executed(),
+        coverage.total()
+    );
+    for (class, method, arg_count) in coverage.missing() {
+        tracing::info!("Never called {}.{}({} args)", class, method, arg_count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coverage_counts_enabled_calls() {
+        let coverage = StaticCoverage::default();
+        // Date.UTC can be called with 0 to 7 arguments
+        assert!(coverage.total() >= 8);
+        coverage.record(&[
+            ("Date", "UTC", 3),
+            ("Date", "UTC", 3),
+            ("Math", "random", 0),
+        ]);
+        assert_eq!(coverage.executed(), 1);
+        assert!(!coverage.missing().contains(&("Date", "UTC", 3)));
+        assert!(coverage.missing().contains(&("Date", "UTC", 0)));
+    }
+}
@@ -0,0 +1,361 @@
+//! Fuzz statistics, periodically logged and written to `RunPaths::stats_file` along with histograms of how long cases take.
+//! The mismatches by diff signature are written to `RunPaths::signature_summary` at the same time
+use crate::diff_class::DIFF_CLASSES;
+use crate::fuzz_session::SharedFuzzState;
+use crate::heartbeat::check_workers;
+use crate::swf_generator::GeneratorMode;
+use crate::{
+    ALL_SWF_VERSIONS, MISMATCH_DIFF_CLASSES, SIGNATURE_LOG_TOP, SWF_VERSIONS, TESTS_PER_FUZZ_CASE,
+};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often stats are logged and `RunPaths::stats_file` is rewritten
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Durations under `SUB_BUCKETS` microseconds are recorded exactly, above that each power of two is split into
+/// `SUB_BUCKETS / 2` buckets, so a recorded value is never more than ~6% below the real one
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const HALF_SUB_BUCKETS: u64 = SUB_BUCKETS / 2;
+
+/// Durations of 2^MAX_MAGNITUDE microseconds (~12 days) and over are recorded in the last bucket
+const MAX_MAGNITUDE: u32 = 40;
+const BUCKET_COUNT: usize =
+    (SUB_BUCKETS + (MAX_MAGNITUDE - SUB_BUCKET_BITS) as u64 * HALF_SUB_BUCKETS) as usize;
+
+/// The bucket a duration in microseconds is counted in
+fn bucket_index(micros: u64) -> usize {
+    let micros = micros.min((1 << MAX_MAGNITUDE) - 1);
+    if micros < SUB_BUCKETS {
+        return micros as usize;
+    }
+    let magnitude = 63 - micros.leading_zeros();
+    let shift = magnitude - (SUB_BUCKET_BITS - 1);
+    let top = micros >> shift;
+    (SUB_BUCKETS + (shift as u64 - 1) * HALF_SUB_BUCKETS + (top - HALF_SUB_BUCKETS)) as usize
+}
+
+/// The smallest duration in microseconds that is counted in a bucket
+fn bucket_lower_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let offset = index - SUB_BUCKETS;
+    let shift = offset / HALF_SUB_BUCKETS + 1;
+    let top = offset % HALF_SUB_BUCKETS + HALF_SUB_BUCKETS;
+    top << shift
+}
+
+/// A log-bucketed (HDR style) histogram of durations, can be recorded into from several threads at once
+pub struct DurationHistogram {
+    buckets: Vec<AtomicU64>,
+    max_micros: AtomicU64,
+}
+
+impl Default for DurationHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..BUCKET_COUNT).map(|_| AtomicU64::new(0)).collect(),
+            max_micros: AtomicU64::new(0),
+        }
+    }
+}
+
+impl DurationHistogram {
+    pub fn record(&self, duration: Duration) {
+        let micros = duration.as_micros().min(u64::MAX as u128) as u64;
+        self.record_micros(micros, 1);
+    }
+
+    fn record_micros(&self, micros: u64, count: u64) {
+        self.buckets[bucket_index(micros)].fetch_add(count, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    /// The number of durations recorded
+    pub fn count(&self) -> u64 {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// The duration that `percentile`% of recorded durations are at or above the bucket of, zero if nothing was recorded
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((percentile / 100.0) * count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= rank {
+                return Duration::from_micros(bucket_lower_bound(index));
+            }
+        }
+        self.max()
+    }
+
+    pub fn median(&self) -> Duration {
+        self.percentile(50.0)
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max_micros.load(Ordering::Relaxed))
+    }
+
+    /// Summary percentiles and the non-empty buckets as `[lower_bound_us, count]` pairs
+    pub fn to_json(&self) -> Value {
+        let buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(index, bucket)| (bucket_lower_bound(index), bucket.load(Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .map(|(lower_bound, count)| json!([lower_bound, count]))
+            .collect::<Vec<_>>();
+        json!({
+            "count": self.count(),
+            "p50_us": self.percentile(50.0).as_micros() as u64,
+            "p90_us": self.percentile(90.0).as_micros() as u64,
+            "p99_us": self.percentile(99.0).as_micros() as u64,
+            "max_us": self.max_micros.load(Ordering::Relaxed),
+            "buckets": buckets,
+        })
+    }
+
+    /// Rebuild a histogram from the output of `to_json`, None if it isn't in that format
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let histogram = Self::default();
+        for bucket in value.get("buckets")?.as_array()? {
+            let lower_bound = bucket.get(0)?.as_u64()?;
+            let count = bucket.get(1)?.as_u64()?;
+            histogram.record_micros(lower_bound, count);
+        }
+        // The bucket bounds are all that's saved, so restore the real max rather than the top bucket's bound
+        histogram
+            .max_micros
+            .store(value.get("max_us")?.as_u64()?, Ordering::Relaxed);
+        Some(histogram)
+    }
+}
+
+/// How long each part of a compared case took
+#[derive(Default)]
+pub struct CaseTimings {
+    /// From starting to generate the case to having the output of both players
+    pub total: DurationHistogram,
+    pub ruffle: DurationHistogram,
+    pub flash: DurationHistogram,
+}
+
+impl CaseTimings {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "total": self.total.to_json(),
+            "ruffle": self.ruffle.to_json(),
+            "flash": self.flash.to_json(),
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        Some(Self {
+            total: DurationHistogram::from_json(value.get("total")?)?,
+            ruffle: DurationHistogram::from_json(value.get("ruffle")?)?,
+            flash: DurationHistogram::from_json(value.get("flash")?)?,
+        })
+    }
+}
+
+/// Write the current stats to `RunPaths::stats_file`, replacing the previous ones
+fn write_stats_file(
+    state: &SharedFuzzState,
+    total_iters: usize,
+    stuck_workers: usize,
+) -> Result<(), Box<dyn Error>> {
+    let versions = ALL_SWF_VERSIONS
+        .iter()
+        .zip(&state.version_stats)
+        .map(|(swf_version, stats)| {
+            json!({
+                "swf_version": swf_version,
+                "cases": stats.cases.load(Ordering::SeqCst),
+                "mismatches": stats.mismatches.load(Ordering::SeqCst),
+            })
+        })
+        .collect::<Vec<_>>();
+    let diff_classes = DIFF_CLASSES
+        .iter()
+        .zip(&state.diff_classes)
+        .map(|(class, count)| {
+            (
+                class.name().to_string(),
+                json!(count.load(Ordering::SeqCst)),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>();
+    let workers = state
+        .workers
+        .iter()
+        .map(|worker| worker.to_json())
+        .collect::<Vec<_>>();
+    let stats = json!({
+        "iterations": total_iters,
+        "mismatches": state.mismatches.load(Ordering::SeqCst),
+        "diff_classes": diff_classes,
+        "flash_crashes": state.flash_crashes.load(Ordering::SeqCst),
+        "ruffle_nondeterministic": state.ruffle_nondeterministic.load(Ordering::SeqCst),
+        "ruffle_incomplete": state.ruffle_incomplete.load(Ordering::SeqCst),
+        "ruffle_script_limit": state.ruffle_script_limit.load(Ordering::SeqCst),
+        "flash_incomplete": state.flash_incomplete.load(Ordering::SeqCst),
+        "output_overflows": state.output_overflows.load(Ordering::SeqCst),
+        "ruffle_only": state.ruffle_only.load(Ordering::SeqCst),
+        "capture_errors": state.capture_errors.load(Ordering::SeqCst),
+        "stuck_workers": stuck_workers,
+        "versions": versions,
+        "modes": state.mode_stats.to_json(&state.mode_scheduler),
+        "workers": workers,
+        "timings": state.timings.to_json(),
+        "static_method_coverage": state.static_coverage.to_json(),
+        "attempted": state.attempted.to_json(),
+    });
+    std::fs::write(
+        &state.config.paths.stats_file,
+        serde_json::to_string_pretty(&stats)?,
+    )?;
+    Ok(())
+}
+
+/// Log the stats every `STATS_INTERVAL` and keep `RunPaths::stats_file` up to date
+pub fn spawn_stats_logger(state: Arc<SharedFuzzState>) {
+    std::thread::spawn(move || loop {
+        let iters = state.iterations.load(Ordering::SeqCst);
+        state.total_iterations.fetch_add(iters, Ordering::SeqCst);
+        state.iterations.store(0, Ordering::SeqCst);
+        let total_iters = state.total_iterations.load(Ordering::SeqCst);
+        let desc = state.mismatches.load(Ordering::SeqCst);
+        let ignored = DIFF_CLASSES
+            .iter()
+            .zip(&state.diff_classes)
+            .filter(|(class, _)| !MISMATCH_DIFF_CLASSES.contains(class))
+            .map(|(_, count)| count.load(Ordering::SeqCst))
+            .sum::<usize>();
+        let crashes = state.flash_crashes.load(Ordering::SeqCst);
+        let nondeterministic = state.ruffle_nondeterministic.load(Ordering::SeqCst);
+        let ruffle_incomplete = state.ruffle_incomplete.load(Ordering::SeqCst);
+        let ruffle_script_limit = state.ruffle_script_limit.load(Ordering::SeqCst);
+        let flash_incomplete = state.flash_incomplete.load(Ordering::SeqCst);
+        let output_overflows = state.output_overflows.load(Ordering::SeqCst);
+        let ruffle_only = state.ruffle_only.load(Ordering::SeqCst);
+        let capture_errors = state.capture_errors.load(Ordering::SeqCst);
+        let stuck_workers = check_workers(&state.workers);
+        let flash_version = state
+            .flash_player
+            .as_ref()
+            .and_then(|player| player.version.as_deref())
+            .unwrap_or("unknown");
+
+        tracing::info!(
+            "Iterations = {} (Mult = {}), iters/s = {}, Discrepancies = {}, Ignored Differences = {}, Flash Crashes = {}, Ruffle Nondeterministic = {}, Ruffle Incomplete = {}, Ruffle Script Limit = {}, Flash Incomplete = {}, Output Overflows = {}, Ruffle Only = {}, Capture Errors = {}, Stuck Workers = {}, Static Methods = {}/{}, Flash = {}",
+            total_iters,
+            total_iters * TESTS_PER_FUZZ_CASE,
+            iters / STATS_INTERVAL.as_secs() as usize,
+            desc,
+            ignored,
+            crashes,
+            nondeterministic,
+            ruffle_incomplete,
+            ruffle_script_limit,
+            flash_incomplete,
+            output_overflows,
+            ruffle_only,
+            capture_errors,
+            stuck_workers,
+            state.static_coverage.executed(),
+            state.static_coverage.total(),
+            flash_version
+        );
+        for (swf_version, stats) in ALL_SWF_VERSIONS.iter().zip(&state.version_stats) {
+            let cases = stats.cases.load(Ordering::SeqCst);
+            let mismatches = stats.mismatches.load(Ordering::SeqCst);
+            // The versions only some modes generate are left out until they have cases
+            if cases == 0 && !SWF_VERSIONS.contains(swf_version) {
+                continue;
+            }
+            tracing::info!(
+                "SWF{}: Cases = {}, Discrepancies = {} ({:.2}%)",
+                swf_version,
+                cases,
+                mismatches,
+                if cases > 0 {
+                    mismatches as f64 * 100.0 / cases as f64
+                } else {
+                    0.0
+                }
+            );
+        }
+        for mode in GeneratorMode::ALL {
+            let sub_cases = state.mode_stats.sub_cases(*mode).load(Ordering::SeqCst);
+            if sub_cases == 0 {
+                continue;
+            }
+            tracing::info!(
+                "{}: Sub-cases = {}, Discrepancies = {}, Weight = {}",
+                mode.name(),
+                sub_cases,
+                state.mode_stats.mismatches(*mode).load(Ordering::SeqCst),
+                state.mode_scheduler.weight(*mode).load(Ordering::SeqCst)
+            );
+        }
+        if let Some(false_positive_rate) = state.attempted.false_positive_rate() {
+            tracing::info!(
+                "Attempted = {} in {} MiB, bloom filter false positives = {:.4}%",
+                state.attempted.len(),
+                state.attempted.memory_bytes() / (1024 * 1024),
+                false_positive_rate * 100.0
+            );
+        } else {
+            tracing::info!(
+                "Attempted = {} in {} MiB",
+                state.attempted.len(),
+                state.attempted.memory_bytes() / (1024 * 1024)
+            );
+        }
+        if !state.diff_signatures.is_empty() {
+            tracing::info!("Signatures = {}", state.diff_signatures.len());
+            for (signature, mismatches) in state.diff_signatures.top(SIGNATURE_LOG_TOP) {
+                tracing::info!("{:>8} x {}", mismatches, signature);
+            }
+        }
+        tracing::info!(
+            "Median case = {:?}, ruffle = {:?}, flash = {:?}",
+            state.timings.total.median(),
+            state.timings.ruffle.median(),
+            state.timings.flash.median()
+        );
+        if let Err(e) = write_stats_file(&state, total_iters, stuck_workers) {
+            tracing::warn!(
+                "Failed to write {}: {}",
+                state.config.paths.stats_file.display(),
+                e
+            );
+        }
+        if let Err(e) = state
+            .diff_signatures
+            .write(&state.config.paths.signature_summary)
+        {
+            tracing::warn!(
+                "Failed to write {}: {}",
+                state.config.paths.signature_summary.display(),
+                e
+            );
+        }
+        std::thread::sleep(STATS_INTERVAL);
+    });
+}
@@ -1,15 +1,46 @@
+use crate::display_list::DISPLAY_LIST_PREFIX;
+use crate::generator_filter::{GeneratorFilter, ValueCategory};
+use crate::input_script::{InputEvent, InputScript, INPUT_KEYS};
+use crate::normalize::{
+    CAPABILITY_PREFIX, PROPERTY_PREFIX, SORTED_BLOCK_BEGIN, SORTED_BLOCK_END, UNSTABLE_PREFIX,
+};
+use crate::op_marker::{mode_description, op_marker};
+use crate::sentinel::{nonce_line, CASE_COMPLETE_SENTINEL};
+use crate::static_methods::{self, StaticCall};
 use crate::{
-    DYNAMIC_FUNCTION_FUZZ, FUZZ_DOUBLE_NAN, FUZZ_INT_STRING, FUZZ_RANDOM_INT, FUZZ_RANDOM_STRING,
-    OPCODE_FUZZ, RANDOM_SWF_VERSION, STATIC_FUNCTION_FUZZ, TESTS_PER_FUZZ_CASE,
+    ARG_COUNT_FUZZ, BINARY_DATA_FUZZ, CAPABILITIES_FUZZ, CATCH_ERROR_MODES, CLASS_HIERARCHY_FUZZ,
+    CLASS_HIERARCHY_SWF_VERSIONS, CLONE_SPRITE_FUZZ, CONSTANT_POOL_PUSH, DEFAULT_MODE_WEIGHT,
+    DISPLAY_LIST_SUMMARY, DYNAMIC_ARG_MISTYPE_RATE, DYNAMIC_FUNCTION_FUZZ, DYNAMIC_MAX_CALLS,
+    EXTRA_TAG_RATE, FRAME_LABEL_FUZZ, FUNCTION_FUZZ, FUZZ_DOUBLE_NAN, FUZZ_INT_STRING,
+    FUZZ_RANDOM_INT, FUZZ_RANDOM_STRING, GLOBAL_FUNCTION_FUZZ, INPUT_FUZZ,
+    MALFORMED_CONTAINER_FUZZ, MEMBER_ACCESS_FUZZ, MEMBER_CHAIN_FUZZ, MODE_WEIGHTS, MOVIE_CLIP_FUZZ,
+    MULTI_FRAME_FUZZ, NAVIGATION_FUZZ, NUMBER_FORMAT_FUZZ, OPCODE_FUZZ, OPCODE_SWF_VERSIONS,
+    PLACEMENT_FUZZ, PROTO_CHAIN_FUZZ, RUFFLE_FRAME_BUDGET, SEEDED_RANDOM, STATIC_FUNCTION_FUZZ,
+    STRING_COMPARE_FUZZ, STRING_COMPARE_SWF_VERSIONS, SWF_VERSIONS, TAG_SIZE_FUZZ,
+    TARGET_PATH_FUZZ, TARGET_PATH_SWF_VERSIONS, TESTS_PER_FUZZ_CASE, TRY_CATCH_FUZZ,
+    TRY_CATCH_SWF_VERSIONS, VERSION_FUZZ,
 };
 use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
 use std::borrow::Cow;
 use std::error::Error;
+use std::fmt;
+use std::num::NonZeroU8;
 use std::ops::RangeInclusive;
-use swf::avm1::types::{Action, GetUrl, If, Push, Value};
+use swf::avm1::types::{
+    Action, CatchVar, ConstantPool, DefineFunction, DefineFunction2, FunctionFlags, FunctionParam,
+    GetUrl, GetUrl2, GotoFrame, GotoFrame2, GotoLabel, If, Jump, Push, SendVarsMethod, SetTarget,
+    StoreRegister, Try, Value, WaitForFrame,
+};
 use swf::avm1::write::Writer;
-use swf::{Compression, Header, Rectangle, SwfStr, Tag, Twips};
+use swf::write::{raw, WriteOptions};
+use swf::{
+    BlendMode, BlurFilter, CharacterId, Color, ColorTransform, Compression, DefineBinaryData,
+    DefineSceneAndFrameLabelData, Depth, DropShadowFilter, ExportedAsset, Filter, Fixed16, Fixed8,
+    FrameLabel, FrameLabelData, GlowFilter, Header, Matrix, PlaceObject, PlaceObjectAction,
+    Rectangle, RemoveObject, Sprite, SwfStr, Tag, TagCode, Twips,
+};
 
 #[derive(Debug, Clone)]
 pub struct SimpleObject<'v> {
@@ -23,7 +54,6 @@ pub struct SimpleArray<'v> {
     members: Vec<SimpleValue<'v>>,
 }
 
-//TODO: registers and constant pools
 #[derive(Debug, Clone)]
 pub enum SimpleValue<'v> {
     Undefined,
@@ -35,12 +65,723 @@ pub enum SimpleValue<'v> {
     String(Cow<'v, str>),
     Object(SimpleObject<'v>),
     Array(SimpleArray<'v>),
+    /// The value in a register, see `DoActionGenerator::push_through_register`
+    Register(u8),
+}
+
+/// The version of the generator recorded with each mismatch, bump it whenever the cases generated from a seed
+/// change (e.g. when the selftest goldens are blessed), so `replay` can say which generator recorded a case that no
+/// longer regenerates the same
+pub const GENERATOR_VERSION: u32 = 16;
+
+/// Where the state of the seeded `Math.random` is kept, see `define_seeded_random`
+const RANDOM_SEED_MEMBER: &str = "__random_seed";
+
+/// The multiplier and modulus of the seeded `Math.random`, the MINSTD generator. Every intermediate value is an
+/// integer below 2^53, so the doubles that AVM1 computes with are exact in both players
+const RANDOM_MULTIPLIER: i32 = 16807;
+const RANDOM_MODULUS: i32 = i32::MAX;
+
+/// The fraction of static function cases that call `Math.random` when it is seeded
+const SEEDED_RANDOM_CALL_RATE: f64 = 0.1;
+
+/// The chance of each argument of a static function case being a random value rather than one of the type the
+/// method takes, see `StaticMethod::arg_types`
+const STATIC_ARG_MISTYPE_RATE: f64 = 0.2;
+
+/// The fraction of static function cases that pass more arguments than the method takes, flash ignores the extras
+const STATIC_EXTRA_ARGS_RATE: f64 = 0.1;
+
+/// The most arguments past the end of a method's argument count that a static function case passes
+const STATIC_MAX_EXTRA_ARGS: i32 = 2;
+
+/// Strings passed to static and dynamic methods that take a `String`
+const TYPED_ARG_STRINGS: &[&str] = &["test", "", "0", "_root", "日本語"];
+
+/// Strings passed to static methods that take a `LinkageName`, the symbols of generated cases aren't exported with
+/// these names, so they are names that don't exist, an empty name and a package path
+const STATIC_LINKAGE_NAMES: &[&str] = &["Symbol1", "clip", "", "__Packages.a.B"];
+
+/// Numbers passed to static and dynamic methods that take a `Number`, indices and counts in range of the arrays and
+/// strings that cases construct, and some out of range
+const TYPED_ARG_NUMBERS: &[i32] = &[0, 1, -1, 2, 10, 255, 1000];
+
+/// The fraction of dynamic function cases that call the method from the class's prototype with a receiver of another
+/// type, see `MISMATCHED_THIS_RECEIVERS`
+const MISMATCHED_THIS_RATE: f64 = 0.3;
+
+/// The receivers that dynamic function cases call a prototype method with, ones of the method's own class are skipped
+const MISMATCHED_THIS_RECEIVERS: &[&str] = &["String", "Number", "Array", "Object", "undefined"];
+
+/// The times, in milliseconds since the epoch, that dynamic function cases construct a Date with. `new Date()` is the
+/// current time, which would never match between the players. These are away from the start and end of a month so
+/// the date is the same in any time zone
+const DATE_EPOCH_MILLIS: &[f64] = &[
+    1_000_000_000_000.0,
+    1_234_567_890_000.0,
+    -1_000_000_000_000.0,
+    951_825_600_000.0,
+];
+
+/// The fraction of dynamic function cases that construct the object with NewMethod rather than NewObject, see
+/// `NEW_METHOD_KINDS`
+const NEW_METHOD_RATE: f64 = 0.3;
+
+/// How dynamic function cases use NewMethod: with the class as a member of `_global`, on the class itself with an
+/// empty method name, which constructs the object itself, or on a value that isn't a function with an empty name
+const NEW_METHOD_KINDS: &[&str] = &["member", "empty name", "non-function"];
+
+/// The fraction of dynamic function cases that read a property of the object rather than call a method, if its class
+/// has properties listed. Classes without methods always read a property
+const DYNAMIC_PROPERTY_READ_RATE: f64 = 0.2;
+
+/// Numbers passed to static methods that take a `MathNumber`, the corner cases of the Math methods: ties that
+/// round depends on the direction of, signed zero, infinities, NaN and integers past 2^31 and 2^53
+const MATH_ARG_NUMBERS: &[f64] = &[
+    0.5,
+    -0.5,
+    1.5,
+    -1.5,
+    2.5,
+    -2.5,
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+    f64::NAN,
+    2_147_483_647.0,
+    2_147_483_648.0,
+    9_007_199_254_740_991.0,
+    9_007_199_254_740_994.0,
+    1e-300,
+];
+
+/// The chance of a random string being pushed from the constant pool, when `CONSTANT_POOL_PUSH` is set
+const CONSTANT_POOL_REF_RATE: f64 = 0.5;
+
+/// The chance of a constant pool reference being past the end of the pool, see `INVALID_POOL_INDICES`
+const CONSTANT_POOL_INVALID_RATE: f64 = 0.05;
+
+/// The fraction of constant pool cases whose pool starts with `CONSTANT_POOL_PADDING` empty strings, so that the
+/// strings after them are pushed with 16 bit indices
+const CONSTANT_POOL_WIDE_RATE: f64 = 0.3;
+
+/// Enough empty strings for every index after them to need 16 bits
+const CONSTANT_POOL_PADDING: u16 = 256;
+
+/// The most distinct strings kept in a case's constant pool, later strings are pushed inline
+const CONSTANT_POOL_MAX_STRINGS: usize = 200;
+
+/// Indices that constant pool references past the end of the pool use, those below the longest pool a case can have
+/// are left out. 255 is the last 8 bit index and 256 the first 16 bit one
+const INVALID_POOL_INDICES: &[u16] = &[255, 256, 1000, u16::MAX];
+
+/// The chance of each argument of an opcode case being passed through a register
+const REGISTER_ARG_RATE: f64 = 0.2;
+
+/// The chance of an argument passed through a register being read from a register that was never written instead
+const UNSET_REGISTER_RATE: f64 = 0.25;
+
+/// The registers outside of a DefineFunction2, the main actions have these 4
+const GLOBAL_REGISTERS: u8 = 4;
+
+/// Registers past the global ones, which opcode cases sometimes read without writing
+const OUT_OF_RANGE_REGISTERS: &[u8] = &[4, 10, 255];
+
+/// The chance of a string opcode case taking its operands from `MULTIBYTE_STRINGS` and `MULTIBYTE_CHAR_CODES`
+const MULTIBYTE_CORPUS_RATE: f64 = 0.75;
+
+/// Strings for the string opcodes, by what makes them interesting. A push ends a string at its first null, so the
+/// embedded null cuts it short. SWF5 reads strings as WINDOWS-1252 and SWF6 on as UTF-8, so the same bytes are valid
+/// in one and not the other
+const MULTIBYTE_STRINGS: &[(&str, &[u8])] = &[
+    ("emoji", "a😀b😀".as_bytes()),
+    ("cjk", "日本語のテキスト".as_bytes()),
+    ("mixed", "aé日😀z".as_bytes()),
+    ("invalid utf-8", b"ab\xff\xfe\x80cd"),
+    ("truncated utf-8", b"abc\xe6\x97"),
+    ("windows-1252", b"caf\xe9 \x80\x99\x9f"),
+    ("embedded null", b"ab\x00cd"),
+    ("empty", b""),
+];
+
+/// A string over 65535 bytes is too long for a push, so it is built from this by doubling it
+/// `MULTIBYTE_LONG_DOUBLINGS` times
+const MULTIBYTE_LONG_CHUNK: &str = "日本語😀é";
+const MULTIBYTE_LONG_DOUBLINGS: u32 = 13;
+
+/// Character codes for MBAsciiToChar, either side of the ends of one to four byte UTF-8 and of the BMP, surrogates,
+/// and codes that aren't characters at all
+const MULTIBYTE_CHAR_CODES: &[i32] = &[
+    0, 0x41, 0x7F, 0x80, 0xE9, 0x7FF, 0x800, 0x3042, 0xD800, 0xDFFF, 0xFFFF, 0x10000, 0x1F600,
+    0x10FFFF, 0x110000, -1,
+];
+
+/// Indices and counts for MBStringExtract and StringExtract
+const MULTIBYTE_EXTRACT_BOUNDS: &[i32] = &[-1, 0, 1, 2, 3, 5, 100, 70000];
+
+/// The first version with DefineFunction2
+const DEFINE_FUNCTION2_MIN_VERSION: u8 = 7;
+
+/// The variable function cases keep the function in
+const FUZZ_FUNCTION_NAME: &str = "__fn";
+
+/// The parameters a function case can declare, the first few are used
+const FUZZ_FUNCTION_PARAMS: &[&str] = &["a", "b", "c"];
+
+/// The most registers a DefineFunction2 of a function case asks for
+const FUZZ_FUNCTION_MAX_REGISTERS: u8 = 8;
+
+/// How many more args than it declares a function case can be called with
+const FUZZ_FUNCTION_EXTRA_ARGS: usize = 2;
+
+/// The flags a DefineFunction2 of a function case picks from, each is set half the time
+const FUZZ_FUNCTION_FLAGS: &[FunctionFlags] = &[
+    FunctionFlags::PRELOAD_THIS,
+    FunctionFlags::SUPPRESS_THIS,
+    FunctionFlags::PRELOAD_ARGUMENTS,
+    FunctionFlags::SUPPRESS_ARGUMENTS,
+    FunctionFlags::PRELOAD_SUPER,
+    FunctionFlags::SUPPRESS_SUPER,
+    FunctionFlags::PRELOAD_ROOT,
+    FunctionFlags::PRELOAD_PARENT,
+    FunctionFlags::PRELOAD_GLOBAL,
+];
+
+/// Name of the value printing helper that is defined at the start of every swf
+const DUMP_FUNCTION_NAME: &str = "__dump";
+
+/// How deep `__dump` will recurse into objects and arrays before falling back to a plain trace
+const DUMP_MAX_DEPTH: i32 = 4;
+
+/// The first version with functions, so the first that defines `__dump`. Older cases trace results directly
+const DUMP_MIN_VERSION: u8 = 5;
+
+/// The first SWF version with `action`, older players don't know it and skip it, leaving the stack unbalanced
+pub fn action_min_version(action: &Action) -> u8 {
+    match action {
+        Action::GotoFrame(_)
+        | Action::GetUrl(_)
+        | Action::NextFrame
+        | Action::PreviousFrame
+        | Action::Play
+        | Action::Stop
+        | Action::ToggleQuality
+        | Action::StopSounds
+        | Action::WaitForFrame(_)
+        | Action::SetTarget(_)
+        | Action::GotoLabel(_) => 3,
+        Action::CallFunction
+        | Action::CallMethod
+        | Action::ConstantPool(_)
+        | Action::DefineFunction(_)
+        | Action::DefineLocal
+        | Action::DefineLocal2
+        | Action::Delete
+        | Action::Delete2
+        | Action::Enumerate
+        | Action::Equals2
+        | Action::GetMember
+        | Action::InitArray
+        | Action::InitObject
+        | Action::NewMethod
+        | Action::NewObject
+        | Action::SetMember
+        | Action::TargetPath
+        | Action::With(_)
+        | Action::ToNumber
+        | Action::ToString
+        | Action::TypeOf
+        | Action::Add2
+        | Action::Less2
+        | Action::Modulo
+        | Action::BitAnd
+        | Action::BitLShift
+        | Action::BitOr
+        | Action::BitRShift
+        | Action::BitURShift
+        | Action::BitXor
+        | Action::Decrement
+        | Action::Increment
+        | Action::PushDuplicate
+        | Action::Return
+        | Action::StackSwap
+        | Action::StoreRegister(_) => 5,
+        Action::InstanceOf
+        | Action::Enumerate2
+        | Action::StrictEquals
+        | Action::Greater
+        | Action::StringGreater => 6,
+        Action::DefineFunction2(_)
+        | Action::Extends
+        | Action::CastOp
+        | Action::ImplementsOp
+        | Action::Try(_)
+        | Action::Throw => 7,
+        _ => 4,
+    }
+}
+
+/// The arguments of opcode cases before SWF5, which could only push strings and floats. SWF4 strings double as
+/// numbers, so some of them look like numbers
+const OLD_VM_VALUES: &[SimpleValue<'static>] = &[
+    SimpleValue::String(Cow::Borrowed("this is a test")),
+    SimpleValue::String(Cow::Borrowed("10")),
+    SimpleValue::String(Cow::Borrowed("2.5")),
+    SimpleValue::String(Cow::Borrowed("")),
+    SimpleValue::Float(10.0),
+    SimpleValue::Float(-0.5),
+];
+
+/// Variable that caught errors are stored in
+const CAUGHT_ERROR_NAME: &str = "__e";
+
+/// The first version with ActionTry, older players skip the action but still run the catch block inline
+const TRY_MIN_VERSION: u8 = 7;
+
+/// How deep try/catch cases nest Try blocks
+const TRY_MAX_DEPTH: u8 = 2;
+
+/// The chance of a Try block of a try/catch case having another inside it, while under `TRY_MAX_DEPTH`
+const NESTED_TRY_RATE: f64 = 0.5;
+
+/// The chance of a nested catch block of a try/catch case throwing what it caught on to the block around it
+const RETHROW_RATE: f64 = 0.3;
+
+/// The classes that try/catch cases cast a random value to with CastOp before throwing the result
+const TRY_CAST_CLASSES: &[&str] = &["Object", "Array", "String", "Number", "MovieClip", "Error"];
+
+/// The most values the argument count fuzz will pop looking for `#PREFIX#`, after a call that consumed it
+const ARG_COUNT_MAX_DEPTH: i32 = 16;
+
+/// Size of an encoded `If` or `Jump` action, branch offsets are relative to the end of the action
+const BRANCH_ACTION_LEN: usize = 5;
+
+/// Assemble a list of actions into a standalone buffer, so that its length can be used for branch offsets
+fn assemble(version: u8, actions: &[Action]) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut w = Writer::new(&mut out, version);
+    for action in actions {
+        w.write_action(action)?;
+    }
+    Ok(out)
+}
+
+/// Assemble `if (cond) { then_block } else { else_block }`, `cond` must leave a single value on the stack
+fn assemble_if_else(
+    version: u8,
+    cond: &[u8],
+    then_block: &[u8],
+    else_block: &[u8],
+) -> std::io::Result<Vec<u8>> {
+    let mut out = cond.to_vec();
+    out.extend(assemble(
+        version,
+        &[
+            Action::Not,
+            Action::If(If {
+                offset: (then_block.len() + BRANCH_ACTION_LEN) as i16,
+            }),
+        ],
+    )?);
+    out.extend_from_slice(then_block);
+    out.extend(assemble(
+        version,
+        &[Action::Jump(Jump {
+            offset: else_block.len() as i16,
+        })],
+    )?);
+    out.extend_from_slice(else_block);
+    Ok(out)
+}
+
+/// Assemble `while (!head) { body }`, `head` must leave a single value on the stack
+fn assemble_loop_until(version: u8, head: &[u8], body: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut out = head.to_vec();
+    out.extend(assemble(
+        version,
+        &[Action::If(If {
+            offset: (body.len() + BRANCH_ACTION_LEN) as i16,
+        })],
+    )?);
+    out.extend_from_slice(body);
+    let loop_len = out.len() + BRANCH_ACTION_LEN;
+    out.extend(assemble(
+        version,
+        &[Action::Jump(Jump {
+            offset: -(loop_len as i16),
+        })],
+    )?);
+    Ok(out)
+}
+
+/// Ordinary cases start a new DoAction tag once the current one is over this, so that huge tags are only
+/// generated by the tag size mode, where the difference in how players handle them is what's being tested
+const DO_ACTION_SPLIT_LEN: usize = 0x10000;
+
+/// Sizes either side of the limits of the encodings, the short tag header (under 63 bytes), the u16 lengths of
+/// function, try and push bodies (65535 bytes), and a tag of a few MiB
+const TAG_SIZE_TARGETS: &[usize] = &[
+    62,
+    63,
+    64,
+    65534,
+    65535,
+    65536,
+    2 * 1024 * 1024 - 1,
+    2 * 1024 * 1024 + 1,
+];
+
+/// The longest string a single push can hold, its action length includes the type and null terminator
+const PAD_STRING_MAX_LEN: usize = u16::MAX as usize - 2;
+
+/// Size of a pad chunk aside from its string, the push header, type, terminator and the pop
+const PAD_CHUNK_OVERHEAD: usize = 6;
+
+/// Assemble exactly `len` bytes of actions that leave the stack as it was, as pushes and pops of long strings.
+/// `len` must be at least `PAD_CHUNK_OVERHEAD`
+fn assemble_pad(version: u8, len: usize) -> std::io::Result<Vec<u8>> {
+    let max_chunk_len = PAD_STRING_MAX_LEN + PAD_CHUNK_OVERHEAD;
+    let padding = vec![b'a'; PAD_STRING_MAX_LEN];
+    let mut out = Vec::with_capacity(len);
+    let mut remaining = len;
+    while remaining > 0 {
+        // Never leave less than a whole chunk for the end
+        let chunk_len = if remaining > max_chunk_len {
+            max_chunk_len.min(remaining - PAD_CHUNK_OVERHEAD)
+        } else {
+            remaining
+        };
+        let string_len = chunk_len.checked_sub(PAD_CHUNK_OVERHEAD).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Pad is too short")
+        })?;
+        out.extend(assemble(
+            version,
+            &[
+                Action::Push(Push {
+                    values: vec![Value::Str(SwfStr::from_bytes(&padding[..string_len]))],
+                }),
+                Action::Pop,
+            ],
+        )?);
+        remaining -= chunk_len;
+    }
+    Ok(out)
+}
+
+/// Assemble `trace(<marker>)` for the `index`th operation of a case
+fn assemble_op_marker(version: u8, index: usize, description: &str) -> std::io::Result<Vec<u8>> {
+    let marker = op_marker(index, description);
+    assemble(
+        version,
+        &[
+            Action::Push(Push {
+                values: vec![Value::Str(SwfStr::from_utf8_str(&marker))],
+            }),
+            Action::Trace,
+        ],
+    )
+}
+
+/// Urls used for navigation fuzzing, none of these should ever resolve to anything
+const URL_CORPUS: &[&str] = &[
+    "",
+    "http://localhost:1/does_not_exist.swf",
+    "https://invalid.invalid/vars.txt",
+    "file:///does/not/exist.swf",
+    "file:///etc/passwd",
+    "javascript:void(0)",
+    "javascript:trace('x')",
+    "does_not_exist.swf",
+    "../does_not_exist.txt",
+    "this is a test",
+];
+
+/// Targets used for navigation fuzzing, avoids `_level0` as that would replace the running movie
+const NAVIGATION_TARGET_CORPUS: &[&str] = &[
+    "_level1",
+    "_level5",
+    "_root.nav_target",
+    "nav_target",
+    "_root",
+    "",
+];
+
+/// The most members in a member chain fuzz case, e.g. `a.b.c.d.e`
+const MEMBER_CHAIN_MAX_LEN: usize = 5;
+
+/// Members accessed by member chain fuzz cases, numbers are pushed as ints so that they index like `a[0]`
+const MEMBER_CHAIN_MEMBERS: &[&str] = &[
+    "b",
+    "c",
+    "x",
+    "length",
+    "__proto__",
+    "constructor",
+    "0",
+    "-1",
+];
+
+/// The most operations in a member access case
+const MEMBER_ACCESS_MAX_OPS: usize = 6;
+
+/// The names that member access cases get, set and delete, the object starts with some of them. Always pushed as
+/// strings, including the numeric ones
+const MEMBER_ACCESS_NAMES: &[&str] = &["a", "b", "x", "0", "1", "-1", "", "length", "toString"];
+
+/// What member access cases get, set and delete the members of
+const MEMBER_ACCESS_RECEIVERS: &[&str] = &["Object", "null", "undefined"];
+
+/// The chance of a prototype chain case making the chain a cycle, by setting the prototype's `__proto__` to the
+/// instance
+const PROTO_CYCLE_RATE: f64 = 0.25;
+
+/// The chance of a prototype chain case writing to a property that is only on the prototype, through the instance
+const PROTO_WRITE_RATE: f64 = 0.5;
+
+/// The properties that prototype chain cases set on the instance and the prototype, each with the value it is set to
+const PROTO_INSTANCE_MEMBERS: &[(&str, &str)] =
+    &[("own", "instance own"), ("shared", "instance shared")];
+const PROTO_PROTOTYPE_MEMBERS: &[(&str, &str)] = &[
+    ("shared", "prototype shared"),
+    ("inherited", "prototype inherited"),
+];
+
+/// The properties that prototype chain cases read through the instance: only on the instance, on both, only on the
+/// prototype and on neither
+const PROTO_LOOKUPS: &[&str] = &["own", "shared", "inherited", "missing"];
+
+/// The chance of each class passed to Extends in a class hierarchy case being a random value instead
+const CLASS_NOT_FUNCTION_RATE: f64 = 0.1;
+
+/// The chance of a class hierarchy case declaring that the derived class implements an interface
+const CLASS_INTERFACE_RATE: f64 = 0.5;
+
+/// The chance of a class hierarchy case checking a primitive with InstanceOf, rather than the instance
+const CLASS_PRIMITIVE_RATE: f64 = 0.2;
+
+/// The primitives that class hierarchy cases check with InstanceOf
+const CLASS_PRIMITIVES: &[SimpleValue<'static>] = &[
+    SimpleValue::Int(1),
+    SimpleValue::String(Cow::Borrowed("str")),
+    SimpleValue::Bool(true),
+    SimpleValue::Undefined,
+    SimpleValue::Null,
+];
+
+/// The global functions that global function cases call, with the most args each takes
+const GLOBAL_FUNCTIONS: &[(&str, usize)] = &[
+    ("parseInt", 2),
+    ("parseFloat", 1),
+    ("isNaN", 1),
+    ("isFinite", 1),
+    ("escape", 1),
+    ("unescape", 1),
+    ("String", 1),
+    ("Number", 1),
+    ("Boolean", 1),
+    ("Object", 1),
+    ("Array", 2),
+];
+
+/// The strings that global function cases pass, with leading whitespace, hex and octal prefixes, exponents, trailing
+/// garbage, long digit strings, escapes and characters above 0x7F
+const GLOBAL_ARG_STRINGS: &[&str] = &[
+    "42",
+    "  42",
+    "\t\n42",
+    "0x1F",
+    "-0x1f",
+    "0X",
+    "010",
+    "+5",
+    "-0",
+    ".5",
+    "5.",
+    "1e5",
+    "1.5E-3",
+    "1e400",
+    "12abc",
+    "abc",
+    "",
+    " ",
+    "Infinity",
+    "-Infinity",
+    "NaN",
+    "123456789012345678901234567890",
+    "0.1234567890123456789012345",
+    "%41%zz%",
+    "%u00e9",
+    "a b+c/d?e=f&g",
+    "é",
+    "日本語",
+];
+
+/// The radixes that global function cases pass to parseInt, including ones out of range
+const GLOBAL_RADIXES: &[i32] = &[2, 8, 10, 16, 36, 0, 1, 37, -1];
+
+/// The chance of a global function case argument being random bytes instead, when random strings are generated (see
+/// `SwfGenerator::with_random_strings`)
+const GLOBAL_RANDOM_ARG_RATE: f64 = 0.2;
+
+/// The doubles that number formatting cases convert to strings, near the boundaries where the conversion switches to
+/// exponents or loses precision. A fixed table, so a mismatch regenerates the same
+#[allow(clippy::excessive_precision)]
+const NUMBER_FORMAT_VALUES: &[f64] = &[
+    1e15,
+    1e15 + 1.0,
+    999_999_999_999_999.9,
+    1e16,
+    1e20,
+    1e21,
+    123_456_789_012_345_680_000.0,
+    1e-5,
+    1e-6,
+    1e-7,
+    0.1 + 0.2,
+    1.0 / 3.0,
+    -0.0,
+    f64::MAX,
+    // Number.MIN_VALUE, the smallest denormal
+    5e-324,
+    2.2250738585072014e-308,
+    1e-310,
+    2_147_483_647.0,
+    2_147_483_648.0,
+    2_147_483_649.0,
+    4_294_967_296.0,
+    -2_147_483_649.0,
+    9_007_199_254_740_992.0,
+    9_007_199_254_740_994.0,
+    18_014_398_509_481_984.0,
+];
+
+/// What a member chain starts from
+#[derive(Debug, Clone, Copy)]
+enum ChainBase {
+    /// A variable that is never set
+    Undeclared,
+    /// A variable declared with `var` but not given a value
+    DeclaredUndefined,
+    /// A variable set to null
+    DeclaredNull,
+    Undefined,
+    Null,
+    /// A variable holding an object, with some of the chain's members set to objects
+    Object,
+    /// A variable holding an array, with some of the chain's members set to arrays
+    Array,
+    Number,
+    String,
+}
+
+const CHAIN_BASES: &[ChainBase] = &[
+    ChainBase::Undeclared,
+    ChainBase::DeclaredUndefined,
+    ChainBase::DeclaredNull,
+    ChainBase::Undefined,
+    ChainBase::Null,
+    ChainBase::Object,
+    ChainBase::Array,
+    ChainBase::Number,
+    ChainBase::String,
+];
+
+/// The strings of a case that are pushed by reference to a constant pool, which is written at the start of each of
+/// the main actions' DoAction tags once the case is generated, see `CONSTANT_POOL_PUSH`
+#[derive(Debug, Default)]
+pub struct PooledStrings {
+    /// Are strings pushed from the pool in this case
+    enabled: bool,
+    /// How many empty strings the pool starts with, see `CONSTANT_POOL_PADDING`
+    padding: u16,
+    /// The pooled strings as indices into the generator's strings, each is at `padding` plus its position
+    entries: Vec<usize>,
+}
+
+impl PooledStrings {
+    fn new(enabled: bool, padding: u16) -> Self {
+        Self {
+            enabled,
+            padding,
+            entries: Vec::new(),
+        }
+    }
+
+    /// The index to push `strings[string]` with instead of pushing it inline, None if it is pushed inline. Sometimes
+    /// an index past the end of the pool
+    fn reference(&mut self, rng: &mut StdRng, strings: &[Vec<u8>], string: usize) -> Option<u16> {
+        if !self.enabled || !rng.gen_bool(CONSTANT_POOL_REF_RATE) {
+            return None;
+        }
+        if rng.gen_bool(CONSTANT_POOL_INVALID_RATE) {
+            // Past the longest the pool can grow to, as strings can still be added after this
+            let max_len = self.padding as usize + CONSTANT_POOL_MAX_STRINGS;
+            let invalid: Vec<u16> = INVALID_POOL_INDICES
+                .iter()
+                .copied()
+                .filter(|&index| index as usize >= max_len)
+                .collect();
+            return Some(invalid[rng.gen_range(0..invalid.len())]);
+        }
+        let position = match self
+            .entries
+            .iter()
+            .position(|&entry| strings[entry] == strings[string])
+        {
+            Some(position) => position,
+            None if self.entries.len() < CONSTANT_POOL_MAX_STRINGS => {
+                self.entries.push(string);
+                self.entries.len() - 1
+            }
+            None => return None,
+        };
+        Some(self.padding + position as u16)
+    }
+
+    /// The ConstantPool action that the pooled strings are pushed from, empty if the case doesn't push from one
+    fn assemble(&self, strings: &[Vec<u8>], version: u8) -> std::io::Result<Vec<u8>> {
+        if !self.enabled {
+            return Ok(Vec::new());
+        }
+        let pool = (0..self.padding)
+            .map(|_| SwfStr::from_utf8_str(""))
+            .chain(
+                self.entries
+                    .iter()
+                    .map(|&entry| SwfStr::from_bytes(&strings[entry])),
+            )
+            .collect();
+        assemble(
+            version,
+            &[Action::ConstantPool(ConstantPool { strings: pool })],
+        )
+    }
 }
 
 pub struct DoActionGenerator<'c> {
     strings: &'c mut Vec<Vec<u8>>,
+    /// The strings pushed by constant pool reference, see `CONSTANT_POOL_PUSH`
+    constant_pool: &'c mut PooledStrings,
     rng: &'c mut StdRng,
     w: Writer<&'c mut Vec<u8>>,
+    version: u8,
+    /// The offsets in the output that each DoAction tag after the first starts at
+    splits: &'c mut Vec<usize>,
+    /// The static methods called so far, see `static_methods::StaticCoverage`
+    static_calls: &'c mut Vec<StaticCall>,
+    /// What the generator may pick, see `generator_filter`
+    filter: &'c GeneratorFilter,
+    /// The global registers written so far in the case, see `push_through_register`
+    written_registers: [bool; GLOBAL_REGISTERS as usize],
+    /// The clips placed on the stage for the case, see `movie_clip_fuzz`
+    stage: &'c mut StageClips,
+    /// The objects and arrays constructed so far, see `SwfGenerator::constructions`
+    constructions: &'c mut Vec<String>,
+    /// Push random byte strings, see `SwfGenerator::with_random_strings`
+    random_strings: bool,
 }
 
 impl<'c> DoActionGenerator<'c> {
@@ -51,15 +792,27 @@ impl<'c> DoActionGenerator<'c> {
     }
 
     pub fn random_value_simple<'v>(&mut self, recursion_depth: u8) -> SimpleValue<'v> {
-        match self.rng.gen_range(6..=8) {
-            0 => SimpleValue::Undefined,
-            1 => SimpleValue::Null,
-            2 => SimpleValue::Int(10),
-            3 => SimpleValue::Double(10.0),
-            4 => SimpleValue::Bool(self.rng.gen()),
-            5 => SimpleValue::Float(10.0),
-            6 => SimpleValue::String(Cow::Borrowed("this is a test")),
-            7 => {
+        // The other categories can be pushed, but only these are picked
+        let category = self.filter.select_value(
+            self.rng,
+            &[
+                ValueCategory::String,
+                ValueCategory::Object,
+                ValueCategory::Array,
+            ],
+            |filter, category| filter.allows_value(*category),
+        );
+        match category {
+            ValueCategory::Undefined => SimpleValue::Undefined,
+            ValueCategory::Null => SimpleValue::Null,
+            ValueCategory::Int => SimpleValue::Int(10),
+            ValueCategory::Double | ValueCategory::NanDouble => SimpleValue::Double(10.0),
+            ValueCategory::Bool => SimpleValue::Bool(self.rng.gen()),
+            ValueCategory::Float => SimpleValue::Float(10.0),
+            ValueCategory::String | ValueCategory::IntString => {
+                SimpleValue::String(Cow::Borrowed("this is a test"))
+            }
+            ValueCategory::Object => {
                 if recursion_depth > 4 {
                     SimpleValue::Null
                 } else {
@@ -72,7 +825,7 @@ impl<'c> DoActionGenerator<'c> {
                     SimpleValue::Object(SimpleObject { members })
                 }
             }
-            8 => {
+            ValueCategory::Array => {
                 if recursion_depth > 4 {
                     SimpleValue::Null
                 } else {
@@ -84,7 +837,6 @@ impl<'c> DoActionGenerator<'c> {
                     SimpleValue::Array(SimpleArray { members })
                 }
             }
-            _ => unreachable!(),
         }
     }
 
@@ -137,6 +889,7 @@ impl<'c> DoActionGenerator<'c> {
                     values: vec![Value::Int(so.members.len() as _)],
                 }))?;
                 self.w.write_action(&Action::InitObject)?;
+                self.constructions.push(format!("{:?}", sv));
             }
             SimpleValue::Array(sa) => {
                 for v in sa.members.iter() {
@@ -148,69 +901,467 @@ impl<'c> DoActionGenerator<'c> {
                     values: vec![Value::Int(sa.members.len() as _)],
                 }))?;
                 self.w.write_action(&Action::InitArray)?;
+                self.constructions.push(format!("{:?}", sv));
+            }
+            SimpleValue::Register(register) => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Register(*register)],
+                }))?;
             }
         }
         Ok(())
     }
 
-    pub fn static_function_fuzz(&mut self) -> Result<(), Box<dyn Error>> {
-        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+    /// A generator writing to `output` instead of the case, for a function body. The body has registers of its own
+    fn body_generator<'b>(
+        &'b mut self,
+        output: &'b mut Vec<u8>,
+        splits: &'b mut Vec<usize>,
+    ) -> DoActionGenerator<'b> {
+        DoActionGenerator {
+            strings: self.strings,
+            constant_pool: self.constant_pool,
+            rng: self.rng,
+            w: Writer::new(output, self.version),
+            version: self.version,
+            splits,
+            static_calls: self.static_calls,
+            filter: self.filter,
+            written_registers: [false; GLOBAL_REGISTERS as usize],
+            stage: self.stage,
+            constructions: self.constructions,
+            random_strings: self.random_strings,
+        }
+    }
 
-        let static_methods = &[
-            ("Accessibility", "isActive", 0..=0),
-            ("BitmapData", "loadBitmap", 1..=1),
-            //("Camera", "get", 0..=1),
-            ("CustomActions", "get", 1..=1),
-            ("CustomActions", "install", 2..=2),
-            ("CustomActions", "list", 0..=0),
-            ("CustomActions", "uninstall", 1..=1),
-            ("Date", "UTC", 2..=7),
-            ("ExternalInterface", "addCallback", 3..=3),
-            ("ExternalInterface", "call", 1..=2),
-            //IME
-            ("Key", "getAscii", 0..=0),
-            ("Key", "getCode", 0..=0),
-            //("Key", "isAccessible", 0..=0),
-            ("Key", "isDown", 1..=1),
-            //("Key", "isToggled", 1..=1),
-            ("Key", "removeListener", 1..=1),
-            ("Locale", "checkXMLStatus", 0..=0),
-            ("Locale", "getDefaultLang", 0..=0),
-            ("Locale", "loadString", 1..=1),
-            ("Locale", "loadStringEx", 2..=2),
-            ("String", "fromCharCode", 1..=1),
-            //Math
-            //("Microphone", "get", 1..=1),
-            //("Mouse", "hide", 0..=0),
-            ("Mouse", "removeListener", 1..=1),
-            //("Mouse", "show", 0..=0),
-            ("Object", "registerClass", 2..=2),
-            ("Point", "distance", 2..=2),
-            ("Point", "interpolate", 3..=3),
-            ("Point", "polar", 2..=2),
-            ("Selection", "getBeginIndex", 0..=0),
-            ("Selection", "getCaretIndex", 0..=0),
-            ("Selection", "getEndIndex", 0..=0),
-            ("Selection", "getFocus", 0..=0),
-            ("Selection", "removeListener", 1..=1),
-            ("Selection", "setFocus", 1..=1),
-            ("SharedObject", "getLocal", 1..=3),
-            ("Stage", "removeListener", 1..=1),
-            ("TextField", "getFontList", 0..=0),
-            ("XMLUI", "get", 1..=1),
+    /// Push `value` by storing it in a global register and pushing the register. Returns the register
+    pub fn push_through_register(&mut self, value: &SimpleValue<'_>) -> Result<u8, Box<dyn Error>> {
+        let register = self.rng.gen_range(0..GLOBAL_REGISTERS);
+        self.push(value)?;
+        self.w
+            .write_action(&Action::StoreRegister(StoreRegister { register }))?;
+        self.w.write_action(&Action::Pop)?;
+        self.push(&SimpleValue::Register(register))?;
+        self.written_registers[register as usize] = true;
+        Ok(register)
+    }
+
+    /// Push a register that was never written, one of the global registers not written so far in the case or one
+    /// past them. Returns the register
+    pub fn push_unset_register(&mut self) -> Result<u8, Box<dyn Error>> {
+        let mut registers: Vec<u8> = (0..GLOBAL_REGISTERS)
+            .filter(|&register| !self.written_registers[register as usize])
+            .collect();
+        registers.extend_from_slice(OUT_OF_RANGE_REGISTERS);
+        let register = self.select(&registers);
+        self.push(&SimpleValue::Register(register))?;
+        Ok(register)
+    }
+
+    /// Define `__dump(v, d)`, which traces the depth and type of a value, then recursively dumps the
+    /// members of arrays and objects. All result tracing goes through this so that the output has
+    /// enough detail to distinguish e.g. `null` from `"null"`
+    pub fn define_dump_function(&mut self) -> Result<(), Box<dyn Error>> {
+        let version = self.version;
+        let push_str = |s: &'static str| {
+            Action::Push(Push {
+                values: vec![Value::Str(s.into())],
+            })
+        };
+        let get = |s: &'static str| [push_str(s), Action::GetVariable];
+        // Emits `__dump(<member>, d + 1)`, where member is the `v[<key_var>]`
+        let dump_member = |key_var: &'static str| {
+            let mut actions = get("d").to_vec();
+            actions.push(Action::Increment);
+            actions.extend(get("v"));
+            actions.extend(get(key_var));
+            actions.extend([
+                Action::GetMember,
+                Action::Push(Push {
+                    values: vec![Value::Int(2), Value::Str(DUMP_FUNCTION_NAME.into())],
+                }),
+                Action::CallFunction,
+                Action::Pop,
+            ]);
+            actions
+        };
+
+        // Arrays: trace(d + ":array:" + v.length), then dump each element
+        let mut array_block = assemble(
+            version,
+            &[
+                push_str("d"),
+                Action::GetVariable,
+                push_str(":array:"),
+                Action::StringAdd,
+                push_str("v"),
+                Action::GetVariable,
+                push_str("length"),
+                Action::GetMember,
+                Action::StringAdd,
+                Action::Trace,
+                push_str("i"),
+                Action::Push(Push {
+                    values: vec![Value::Int(0)],
+                }),
+                Action::DefineLocal,
+            ],
+        )?;
+        let mut array_head = get("i").to_vec();
+        array_head.extend(get("v"));
+        array_head.extend([
+            push_str("length"),
+            Action::GetMember,
+            Action::Less2,
+            Action::Not,
+        ]);
+        let mut array_body = dump_member("i");
+        array_body.push(push_str("i"));
+        array_body.extend(get("i"));
+        array_body.extend([Action::Increment, Action::SetVariable]);
+        array_block.extend(assemble_loop_until(
+            version,
+            &assemble(version, &array_head)?,
+            &assemble(version, &array_body)?,
+        )?);
+
+        // Objects: trace(d + ":object"), then dump each enumerable member as `d:key:<name>` followed by its value
+        let mut object_block = assemble(
+            version,
+            &[
+                push_str("d"),
+                Action::GetVariable,
+                push_str(":object"),
+                Action::StringAdd,
+                Action::Trace,
+            ],
+        )?;
+        // Enumerate2 is SWF6, before it Enumerate takes the variable's name
+        object_block.extend(assemble(
+            version,
+            &if version >= action_min_version(&Action::Enumerate2) {
+                vec![push_str("v"), Action::GetVariable, Action::Enumerate2]
+            } else {
+                vec![push_str("v"), Action::Enumerate]
+            },
+        )?);
+        let object_head = [
+            Action::PushDuplicate,
+            Action::Push(Push {
+                values: vec![Value::Null],
+            }),
+            Action::Equals2,
+        ];
+        let mut object_body = vec![push_str("k"), Action::StackSwap, Action::DefineLocal];
+        object_body.extend(get("d"));
+        object_body.extend([push_str(":key:"), Action::StringAdd]);
+        object_body.extend(get("k"));
+        object_body.extend([Action::StringAdd, Action::Trace]);
+        object_body.extend(dump_member("k"));
+        object_block.extend(assemble_loop_until(
+            version,
+            &assemble(version, &object_head)?,
+            &assemble(version, &object_body)?,
+        )?);
+        // Remove the enumeration terminator
+        object_block.extend(assemble(version, &[Action::Pop])?);
+
+        // InstanceOf is SWF6, before it v.__proto__ == Array.prototype
+        let mut is_array = get("v").to_vec();
+        if version >= action_min_version(&Action::InstanceOf) {
+            is_array.extend(get("Array"));
+            is_array.push(Action::InstanceOf);
+        } else {
+            is_array.push(push_str("__proto__"));
+            is_array.push(Action::GetMember);
+            is_array.extend(get("Array"));
+            is_array.extend([push_str("prototype"), Action::GetMember, Action::Equals2]);
+        }
+        let container_block = assemble_if_else(
+            version,
+            &assemble(version, &is_array)?,
+            &array_block,
+            &object_block,
+        )?;
+
+        // Everything else: trace(d + ":" + t + ":" + v)
+        let mut value_block = get("d").to_vec();
+        value_block.extend([push_str(":"), Action::StringAdd]);
+        value_block.extend(get("t"));
+        value_block.extend([Action::StringAdd, push_str(":"), Action::StringAdd]);
+        value_block.extend(get("v"));
+        value_block.extend([Action::StringAdd, Action::Trace]);
+
+        // var t = typeof v; if (t === "object" && d < DUMP_MAX_DEPTH) { ... } else { ... }
+        let mut body = vec![push_str("t")];
+        body.extend(get("v"));
+        body.extend([Action::TypeOf, Action::DefineLocal]);
+        let mut body = assemble(version, &body)?;
+        let mut is_container = get("t").to_vec();
+        // StrictEquals is SWF6, but both sides are strings
+        is_container.push(push_str("object"));
+        is_container.push(if version >= action_min_version(&Action::StrictEquals) {
+            Action::StrictEquals
+        } else {
+            Action::Equals2
+        });
+        is_container.extend(get("d"));
+        is_container.extend([
+            Action::Push(Push {
+                values: vec![Value::Int(DUMP_MAX_DEPTH)],
+            }),
+            Action::Less2,
+            Action::And,
+        ]);
+        body.extend(assemble_if_else(
+            version,
+            &assemble(version, &is_container)?,
+            &container_block,
+            &assemble(version, &value_block)?,
+        )?);
+
+        self.w
+            .write_action(&Action::DefineFunction(DefineFunction {
+                name: DUMP_FUNCTION_NAME.into(),
+                params: vec!["v".into(), "d".into()],
+                actions: &body,
+            }))?;
+
+        Ok(())
+    }
+
+    /// Insert the marker for the operation written since `start`, so it is traced before the operation runs.
+    /// Returns the length of the marker
+    pub fn insert_op_marker(
+        &mut self,
+        start: usize,
+        index: usize,
+        description: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        let marker = assemble_op_marker(self.version, index, description)?;
+        let marker_len = marker.len();
+        self.w.output.splice(start..start, marker);
+        Ok(marker_len)
+    }
+
+    /// Start a new DoAction tag at `offset` in the output, unless one already starts there
+    pub fn split_at(&mut self, offset: usize) {
+        if offset > 0 && self.splits.last() != Some(&offset) {
+            self.splits.push(offset);
+        }
+    }
+
+    /// Start a new DoAction tag after what has been written so far if the current one is over `DO_ACTION_SPLIT_LEN`
+    pub fn split_if_full(&mut self) {
+        let tag_start = self.splits.last().copied().unwrap_or(0);
+        if self.w.output.len() - tag_start > DO_ACTION_SPLIT_LEN {
+            self.split_at(self.w.output.len());
+        }
+    }
+
+    /// Wrap everything written since `start` in a try block, whose catch traces
+    /// `ERROR:` followed by the caught value and its `name` and `message` members.
+    /// Returns false, leaving the actions unwrapped, if they are too long for a try body
+    pub fn catch_errors(&mut self, start: usize) -> Result<bool, Box<dyn Error>> {
+        if self.w.output.len() - start > u16::MAX as usize {
+            return Ok(false);
+        }
+        let try_body = self.w.output.split_off(start);
+
+        // trace("ERROR:" + __e.toString() + " name=" + __e.name + " message=" + __e.message)
+        let catch_body = assemble(
+            self.version,
+            &[
+                Action::Push(Push {
+                    values: vec![
+                        Value::Str("ERROR:".into()),
+                        Value::Int(0),
+                        Value::Str(CAUGHT_ERROR_NAME.into()),
+                    ],
+                }),
+                Action::GetVariable,
+                Action::Push(Push {
+                    values: vec![Value::Str("toString".into())],
+                }),
+                Action::CallMethod,
+                Action::Add2,
+                Action::Push(Push {
+                    values: vec![
+                        Value::Str(" name=".into()),
+                        Value::Str(CAUGHT_ERROR_NAME.into()),
+                    ],
+                }),
+                Action::GetVariable,
+                Action::Push(Push {
+                    values: vec![Value::Str("name".into())],
+                }),
+                Action::GetMember,
+                Action::Add2,
+                Action::Add2,
+                Action::Push(Push {
+                    values: vec![
+                        Value::Str(" message=".into()),
+                        Value::Str(CAUGHT_ERROR_NAME.into()),
+                    ],
+                }),
+                Action::GetVariable,
+                Action::Push(Push {
+                    values: vec![Value::Str("message".into())],
+                }),
+                Action::GetMember,
+                Action::Add2,
+                Action::Add2,
+                Action::Trace,
+            ],
+        )?;
+
+        self.w.write_action(&Action::Try(Try {
+            try_body: &try_body,
+            catch_body: Some((CatchVar::Var(CAUGHT_ERROR_NAME.into()), &catch_body)),
+            finally_body: None,
+        }))?;
+
+        Ok(true)
+    }
+
+    /// Replace `Math.random` with a generator seeded from the case's rng, so that both players produce the same
+    /// numbers. Returns the seed
+    pub fn define_seeded_random(&mut self) -> Result<i32, Box<dyn Error>> {
+        let seed = self.rng.gen_range(1..RANDOM_MODULUS);
+        let get_math = [
+            Action::Push(Push {
+                values: vec![Value::Str("Math".into())],
+            }),
+            Action::GetVariable,
+        ];
+        let get_seed = [
+            Action::Push(Push {
+                values: vec![Value::Str("Math".into())],
+            }),
+            Action::GetVariable,
+            Action::Push(Push {
+                values: vec![Value::Str(RANDOM_SEED_MEMBER.into())],
+            }),
+            Action::GetMember,
         ];
 
-        let (obj_name, func_name, arg_count_range) = self.select(static_methods);
+        // Math.__random_seed = Math.__random_seed * RANDOM_MULTIPLIER % RANDOM_MODULUS;
+        // return (Math.__random_seed - 1) / (RANDOM_MODULUS - 1);
+        let mut body = get_math.to_vec();
+        body.push(Action::Push(Push {
+            values: vec![Value::Str(RANDOM_SEED_MEMBER.into())],
+        }));
+        body.extend_from_slice(&get_seed);
+        body.extend([
+            Action::Push(Push {
+                values: vec![Value::Int(RANDOM_MULTIPLIER)],
+            }),
+            Action::Multiply,
+            Action::Push(Push {
+                values: vec![Value::Int(RANDOM_MODULUS)],
+            }),
+            Action::Modulo,
+            Action::SetMember,
+        ]);
+        body.extend_from_slice(&get_seed);
+        body.extend([
+            Action::Push(Push {
+                values: vec![Value::Int(1)],
+            }),
+            Action::Subtract,
+            Action::Push(Push {
+                values: vec![Value::Int(RANDOM_MODULUS - 1)],
+            }),
+            Action::Divide,
+            Action::Return,
+        ]);
+        let body = assemble(self.version, &body)?;
+
+        // Math.__random_seed = seed
+        for action in &get_math {
+            self.w.write_action(action)?;
+        }
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(RANDOM_SEED_MEMBER.into()), Value::Int(seed)],
+        }))?;
+        self.w.write_action(&Action::SetMember)?;
+
+        // Math.random = function() { ... }
+        for action in &get_math {
+            self.w.write_action(action)?;
+        }
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("random".into())],
+        }))?;
+        self.w
+            .write_action(&Action::DefineFunction(DefineFunction {
+                name: "".into(),
+                params: vec![],
+                actions: &body,
+            }))?;
+        self.w.write_action(&Action::SetMember)?;
+
+        Ok(seed)
+    }
+
+    /// Returns a description of the operation, for its marker
+    pub fn static_function_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+
+        let static_methods = static_methods::enabled_methods();
+
+        let (obj_name, func_name, arg_count_range) = if SEEDED_RANDOM
+            && self.filter.allows_static("Math", "random")
+            && self.rng.gen_bool(SEEDED_RANDOM_CALL_RATE)
+        {
+            ("Math", "random", 0..=0)
+        } else {
+            let (obj_name, method) =
+                self.filter
+                    .select(self.rng, &static_methods, |filter, (class, method)| {
+                        filter.allows_static(class, method.name)
+                    });
+            (obj_name, method.name, method.args.clone())
+        };
         // Some functions take a variable argument counts, pick a random number of args to get good coverage
         // We ignore the lower bound here as we also want to test how missing args are handled in avm1
         // In avm2 we will want to make use of that, as missing args will cause exceptions
-        let arg_count = self.rng.gen_range(0..=*arg_count_range.end());
+        let max_args = *arg_count_range.end();
+        let arg_count = if self.rng.gen_bool(STATIC_EXTRA_ARGS_RATE) {
+            self.rng
+                .gen_range(max_args + 1..=max_args + STATIC_MAX_EXTRA_ARGS)
+        } else {
+            self.rng.gen_range(0..=max_args)
+        };
+        let arg_types = static_methods::enabled_methods()
+            .into_iter()
+            .find(|(class, method)| *class == obj_name && method.name == func_name)
+            .map_or(&[][..], |(_, method)| method.arg_types);
 
-        for _ in 0..arg_count {
-            self.w.write_action(&Action::Push(Push {
-                values: vec![Self::random_value(self.rng, &mut self.strings)],
-            }))?;
+        // The last arg is pushed first. Extra args are always random, as the method has no type for them
+        let mut mistyped = Vec::new();
+        for slot in (0..arg_count as usize).rev() {
+            match arg_types.get(slot) {
+                Some(arg_type) if !self.rng.gen_bool(STATIC_ARG_MISTYPE_RATE) => {
+                    self.push_typed_arg(arg_type)?
+                }
+                arg_type => {
+                    if arg_type.is_some() {
+                        mistyped.push(slot);
+                    }
+                    self.w.write_action(&Action::Push(Push {
+                        values: vec![Self::random_value(
+                            self.rng,
+                            self.strings,
+                            self.constant_pool,
+                            self.filter,
+                            self.random_strings,
+                        )],
+                    }))?;
+                }
+            }
         }
+        mistyped.reverse();
 
         self.w.write_action(&Action::Push(Push {
             values: vec![Value::Int(arg_count), Value::Str(obj_name.into())],
@@ -222,31 +1373,155 @@ impl<'c> DoActionGenerator<'c> {
         self.w.write_action(&Action::CallMethod)?;
 
         SwfGenerator::dump_stack(&mut self.w)?;
+        self.static_calls.push((obj_name, func_name, arg_count));
 
-        Ok(())
+        // Inside the parentheses, so they aren't part of the signature
+        let mut args = format!("{} args", arg_count);
+        if arg_count > max_args {
+            args.push_str(&format!(", {} extra", arg_count - max_args));
+        }
+        if !mistyped.is_empty() {
+            args.push_str(&format!(", mistyped {:?}", mistyped));
+        }
+        Ok(format!("{}.{}({})", obj_name, func_name, args))
+    }
+
+    /// Push a plausible argument of the given type, one of the `StaticMethod::arg_types`. Values of a kind that the
+    /// generator filter denies, and types without values of their own, are random instead
+    fn push_typed_arg(&mut self, arg_type: &str) -> Result<(), Box<dyn Error>> {
+        if arg_type == "Function" {
+            self.w
+                .write_action(&Action::DefineFunction(DefineFunction {
+                    name: "".into(),
+                    params: vec![],
+                    actions: &[],
+                }))?;
+            return Ok(());
+        }
+        let value = self.value_of_type(arg_type);
+        self.push_or_random(value)
+    }
+
+    /// A value of the given type for an argument of a dynamic function call, None `DYNAMIC_ARG_MISTYPE_RATE` of the
+    /// time, and for types without values of their own, when a random value should be passed instead
+    pub fn random_value_of_type(&mut self, arg_type: &str) -> Option<SimpleValue<'static>> {
+        if self.rng.gen_bool(DYNAMIC_ARG_MISTYPE_RATE) {
+            return None;
+        }
+        self.value_of_type(arg_type)
+    }
+
+    /// A plausible value of the given type, None for types without values of their own
+    fn value_of_type(&mut self, arg_type: &str) -> Option<SimpleValue<'static>> {
+        Some(match arg_type {
+            "Number" => SimpleValue::Int(self.select(TYPED_ARG_NUMBERS)),
+            "MathNumber" => SimpleValue::Double(self.select(MATH_ARG_NUMBERS)),
+            "Boolean" => SimpleValue::Bool(self.rng.gen()),
+            "String" => SimpleValue::String(Cow::Borrowed(self.select(TYPED_ARG_STRINGS))),
+            "LinkageName" => SimpleValue::String(Cow::Borrowed(self.select(STATIC_LINKAGE_NAMES))),
+            "Object" => SimpleValue::Object(SimpleObject {
+                members: Vec::new(),
+            }),
+            "Array" => SimpleValue::Array(SimpleArray {
+                members: (0..self.rng.gen_range(0..=3))
+                    .map(|_| SimpleValue::Int(self.select(TYPED_ARG_NUMBERS)))
+                    .collect(),
+            }),
+            // An object with `x` and `y`, which is all the Point methods read
+            "Point" => SimpleValue::Object(SimpleObject {
+                members: vec![
+                    (
+                        SimpleValue::String(Cow::Borrowed("x")),
+                        SimpleValue::Int(self.select(TYPED_ARG_NUMBERS)),
+                    ),
+                    (
+                        SimpleValue::String(Cow::Borrowed("y")),
+                        SimpleValue::Int(self.select(TYPED_ARG_NUMBERS)),
+                    ),
+                ],
+            }),
+            _ => return None,
+        })
+    }
+
+    /// Push `value`, or a random value if it is None or of a kind that the generator filter denies
+    fn push_or_random(&mut self, value: Option<SimpleValue<'_>>) -> Result<(), Box<dyn Error>> {
+        let category = match value {
+            None => None,
+            Some(SimpleValue::Int(_)) => Some(ValueCategory::Int),
+            Some(SimpleValue::Double(d)) if d.is_nan() => Some(ValueCategory::NanDouble),
+            Some(SimpleValue::Double(_)) => Some(ValueCategory::Double),
+            Some(SimpleValue::Bool(_)) => Some(ValueCategory::Bool),
+            Some(SimpleValue::String(_)) => Some(ValueCategory::String),
+            Some(SimpleValue::Object(_)) => Some(ValueCategory::Object),
+            Some(SimpleValue::Array(_)) => Some(ValueCategory::Array),
+            Some(_) => None,
+        };
+        match (value, category) {
+            (Some(value), Some(category)) if self.filter.allows_value(category) => {
+                self.push(&value)
+            }
+            _ => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Self::random_value(
+                        self.rng,
+                        self.strings,
+                        self.constant_pool,
+                        self.filter,
+                        self.random_strings,
+                    )],
+                }))?;
+                Ok(())
+            }
+        }
     }
 
     fn random_value<'val, 'strings: 'val>(
         rng: &mut StdRng,
         strings: &'strings mut Vec<Vec<u8>>,
+        constant_pool: &mut PooledStrings,
+        filter: &GeneratorFilter,
+        random_strings: bool,
     ) -> Value<'val> {
-        match rng.gen_range(0..=6) {
-            0 => Value::Undefined,
-            1 => Value::Null,
-            2 => Value::Int(if FUZZ_RANDOM_INT { rng.gen() } else { 10 }),
-            3 => Value::Bool(rng.gen()),
+        let category = filter.select_value(
+            rng,
+            &[
+                ValueCategory::Undefined,
+                ValueCategory::Null,
+                ValueCategory::Int,
+                ValueCategory::Bool,
+                ValueCategory::Double,
+                ValueCategory::Float,
+                ValueCategory::String,
+            ],
+            |filter, category| match category {
+                // These are split in two below, so can be picked if either half is allowed
+                ValueCategory::Double if FUZZ_DOUBLE_NAN => {
+                    filter.allows_value(ValueCategory::Double)
+                        || filter.allows_value(ValueCategory::NanDouble)
+                }
+                ValueCategory::String if FUZZ_INT_STRING => {
+                    filter.allows_value(ValueCategory::String)
+                        || filter.allows_value(ValueCategory::IntString)
+                }
+                category => filter.allows_value(*category),
+            },
+        );
+        match category {
+            ValueCategory::Undefined => Value::Undefined,
+            ValueCategory::Null => Value::Null,
+            ValueCategory::Int => Value::Int(if FUZZ_RANDOM_INT { rng.gen() } else { 10 }),
+            ValueCategory::Bool => Value::Bool(rng.gen()),
             //TODO: double are also known to not match
-            4 => {
-                if FUZZ_DOUBLE_NAN {
-                    match rng.gen_range(0..=1) {
-                        0 => Value::Double(if FUZZ_RANDOM_INT {
-                            rng.gen::<i64>() as f64
-                        } else {
-                            10.
-                        }),
-                        1 => Value::Double(f64::NAN /*rng.gen()*/),
-                        _ => unreachable!(),
-                    }
+            ValueCategory::Double => {
+                let nan = FUZZ_DOUBLE_NAN
+                    && filter.select_value(
+                        rng,
+                        &[ValueCategory::Double, ValueCategory::NanDouble],
+                        |filter, category| filter.allows_value(*category),
+                    ) == ValueCategory::NanDouble;
+                if nan {
+                    Value::Double(f64::NAN /*rng.gen()*/)
                 } else {
                     Value::Double(if FUZZ_RANDOM_INT {
                         rng.gen::<i64>() as f64
@@ -256,46 +1531,51 @@ impl<'c> DoActionGenerator<'c> {
                 }
             }
             //TODO: floats are known to not match in ruffle
-            5 => Value::Float(f32::NAN /*rng.gen()*/),
-            6 => {
-                if FUZZ_INT_STRING {
-                    // Decide if we should make a text, or numerical string
-                    match rng.gen_range(0..=1) {
-                        0 => {
-                            if FUZZ_RANDOM_STRING {
-                                // Completely random bytes for strings
-                                let max_string_len = 256;
-                                let mut buf = Vec::<u8>::with_capacity(max_string_len);
-                                buf.resize(rng.gen_range(1..max_string_len), 0);
-                                rng.fill(buf.as_mut_slice());
-                                strings.push(buf);
-                            } else {
-                                strings.push("this is a test".as_bytes().to_vec())
-                            }
-                        }
-                        // Generate a integer numerical string
-                        1 => {
-                            let v = if FUZZ_RANDOM_INT {
-                                rng.gen::<i32>()
-                            } else {
-                                10
-                            };
-                            strings.push(v.to_string().into_bytes());
-                        }
-                        //TODO: numerical strings?
-                        _ => unreachable!(),
-                    }
+            ValueCategory::Float => Value::Float(f32::NAN /*rng.gen()*/),
+            ValueCategory::String => {
+                // Decide if we should make a text, or numerical string
+                let string_category = if FUZZ_INT_STRING {
+                    filter.select_value(
+                        rng,
+                        &[ValueCategory::String, ValueCategory::IntString],
+                        |filter, category| filter.allows_value(*category),
+                    )
                 } else {
-                    strings.push("this is a test".as_bytes().to_vec())
+                    ValueCategory::String
+                };
+                match string_category {
+                    ValueCategory::String if FUZZ_INT_STRING && random_strings => {
+                        // Completely random bytes for strings
+                        let max_string_len = 256;
+                        let mut buf = Vec::<u8>::with_capacity(max_string_len);
+                        buf.resize(rng.gen_range(1..max_string_len), 0);
+                        rng.fill(buf.as_mut_slice());
+                        strings.push(buf);
+                    }
+                    // Generate a integer numerical string
+                    ValueCategory::IntString => {
+                        let v = if FUZZ_RANDOM_INT {
+                            rng.gen::<i32>()
+                        } else {
+                            10
+                        };
+                        strings.push(v.to_string().into_bytes());
+                    }
+                    //TODO: numerical strings?
+                    _ => strings.push("this is a test".as_bytes().to_vec()),
                 }
 
-                Value::Str(SwfStr::from_bytes(strings.last().unwrap().as_slice()))
+                match constant_pool.reference(rng, strings, strings.len() - 1) {
+                    Some(index) => Value::ConstantPool(index),
+                    None => Value::Str(SwfStr::from_bytes(strings.last().unwrap().as_slice())),
+                }
             }
             _ => unreachable!(),
         }
     }
 
-    pub fn dynamic_function_fuzz(&mut self) -> Result<(), Box<dyn Error>> {
+    /// Returns a description of the operation, for its marker
+    pub fn dynamic_function_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
         self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
 
         //TODO: support for flash.foo.bar.Thing
@@ -312,200 +1592,4077 @@ impl<'c> DoActionGenerator<'c> {
                 &["length"],
                 &[
                     ("concat", &["Array"]),
-                    ("join", &["Array"]),
+                    ("join", &["String"]),
                     ("pop", &[]),
                     ("push", &["Any"]),
                     ("reverse", &[]),
                     ("shift", &[]),
                     ("slice", &["Number", "Number"]),
                     ("sort", &["Number", "Number"]),
-                    ("sortOn", &["Number", "Number"]),
+                    ("sortOn", &["String", "Number"]),
                     ("splice", &["Number", "Number", "Number"]),
                     ("toString", &[]),
                     ("unshift", &["Number"]),
                 ],
             ),
+            // Always constructed with one of `DATE_EPOCH_MILLIS`
+            (
+                "Date",
+                1..=1,
+                &[],
+                &[
+                    ("getTime", &[]),
+                    ("getFullYear", &[]),
+                    ("setMonth", &["Number", "Number"]),
+                    ("toString", &[]),
+                    ("valueOf", &[]),
+                ],
+            ),
+            (
+                "Number",
+                0..=1,
+                &[],
+                &[
+                    ("toString", &["Number"]),
+                    ("valueOf", &[]),
+                    ("toFixed", &["Number"]),
+                ],
+            ),
+            (
+                "Boolean",
+                0..=1,
+                &[],
+                &[("toString", &[]), ("valueOf", &[])],
+            ),
+            // The font, size and color, getTextExtent depends on the installed fonts
+            ("TextFormat", 0..=3, &["font", "size", "color"], &[]),
         ];
 
         //TODO: should we fuzz the case of args/classes to
-        let (class_name, constructor_arg_range, _properties, functions) = self.select(classes);
+        let (class_name, constructor_arg_range, properties, functions) = self.select(classes);
+        if !functions.is_empty() && self.rng.gen_bool(MISMATCHED_THIS_RATE) {
+            return self.mismatched_this_call(class_name, functions);
+        }
         //Ignore this, for same reason as in static
-        let arg_count = self.rng.gen_range(0..=*constructor_arg_range.end());
+        let arg_count = if class_name == "Date" {
+            1
+        } else {
+            self.rng.gen_range(0..=*constructor_arg_range.end())
+        };
 
         // The name of the object
         self.push(&SimpleValue::String(Cow::Borrowed("foo")))?;
 
         // Push the args
+        for _ in 0..arg_count {
+            if class_name == "Date" {
+                let millis = self.select(DATE_EPOCH_MILLIS);
+                self.push(&SimpleValue::Double(millis))?;
+                continue;
+            }
+            self.w.write_action(&Action::Push(Push {
+                values: vec![Self::random_value(
+                    self.rng,
+                    self.strings,
+                    self.constant_pool,
+                    self.filter,
+                    self.random_strings,
+                )],
+            }))?;
+        }
+
+        // The arg count, then the name or the object and method name
+        self.push(&SimpleValue::Int(arg_count))?;
+        let new_method = self
+            .rng
+            .gen_bool(NEW_METHOD_RATE)
+            .then(|| self.select(NEW_METHOD_KINDS));
+        let constructed = match new_method {
+            None => {
+                self.push(&SimpleValue::String(Cow::Borrowed(class_name)))?;
+                self.w.write_action(&Action::NewObject)?;
+                class_name.to_string()
+            }
+            Some("member") => {
+                self.push(&SimpleValue::String(Cow::Borrowed("_global")))?;
+                self.w.write_action(&Action::GetVariable)?;
+                self.push(&SimpleValue::String(Cow::Borrowed(class_name)))?;
+                self.w.write_action(&Action::NewMethod)?;
+                format!("_global.{}", class_name)
+            }
+            Some("empty name") => {
+                self.push(&SimpleValue::String(Cow::Borrowed("_global")))?;
+                self.w.write_action(&Action::GetVariable)?;
+                self.push(&SimpleValue::String(Cow::Borrowed(class_name)))?;
+                self.w.write_action(&Action::GetMember)?;
+                self.push(&SimpleValue::String(Cow::Borrowed("")))?;
+                self.w.write_action(&Action::NewMethod)?;
+                format!("_global.{}[\"\"]", class_name)
+            }
+            Some(_) => {
+                let value = self.random_value_simple(1);
+                self.push(&value)?;
+                self.push(&SimpleValue::String(Cow::Borrowed("")))?;
+                self.w.write_action(&Action::NewMethod)?;
+                "non-function[\"\"]".to_string()
+            }
+        };
+        self.w.write_action(&Action::DefineLocal)?;
+
+        if new_method.is_some() {
+            // typeof foo, foo.toString()
+            self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+            self.push(&SimpleValue::String(Cow::Borrowed("foo")))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.w.write_action(&Action::TypeOf)?;
+            self.push(&SimpleValue::Int(0))?;
+            self.push(&SimpleValue::String(Cow::Borrowed("foo")))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.push(&SimpleValue::String(Cow::Borrowed("toString")))?;
+            self.w.write_action(&Action::CallMethod)?;
+            SwfGenerator::dump_stack(&mut self.w)?;
+        }
+
+        if !properties.is_empty()
+            && (functions.is_empty() || self.rng.gen_bool(DYNAMIC_PROPERTY_READ_RATE))
+        {
+            // foo.<property>
+            let property = self.select(properties);
+            self.push(&SimpleValue::String(Cow::Borrowed("foo")))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.push(&SimpleValue::String(Cow::Borrowed(property)))?;
+            self.w.write_action(&Action::GetMember)?;
+            SwfGenerator::dump_stack(&mut self.w)?;
+            return Ok(format!(
+                "new {}({} args).{}",
+                constructed, arg_count, property
+            ));
+        }
+
+        // Call random functions in turn, dumping each return value after the index of the call
+        let mut calls = Vec::new();
+        for call in 0..self.rng.gen_range(1..=DYNAMIC_MAX_CALLS) {
+            // The first return value goes on the `#PREFIX#` pushed above
+            if call > 0 {
+                self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+            }
+            self.push(&SimpleValue::String(Cow::Owned(format!("call {}", call))))?;
+
+            let (function_name, args) = self.select(functions);
+            let function_arg_count = self.rng.gen_range(0..=args.len() as i32);
+
+            // Push function args and arg count
+            self.push_dynamic_args(args, function_arg_count)?;
+            self.w.write_action(&Action::Push(Push {
+                values: vec![Value::Int(function_arg_count)],
+            }))?;
+
+            // Get foo, again for each call
+            self.push(&SimpleValue::String(Cow::Borrowed("foo")))?;
+            self.w.write_action(&Action::GetVariable)?;
+
+            // Call foo.<function_name>()
+            self.push(&SimpleValue::String(Cow::Borrowed(function_name)))?;
+            self.w.write_action(&Action::CallMethod)?;
+
+            SwfGenerator::dump_stack(&mut self.w)?;
+            calls.push(format!(".{}({} args)", function_name, function_arg_count));
+        }
+
+        // Then what the methods left in foo, e.g. after splice or sort. The players enumerate in different orders
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(SORTED_BLOCK_BEGIN.into())],
+        }))?;
+        self.w.write_action(&Action::Trace)?;
+        self.trace_members("foo", PROPERTY_PREFIX)?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(SORTED_BLOCK_END.into())],
+        }))?;
+        self.w.write_action(&Action::Trace)?;
+
+        Ok(format!(
+            "new {}({} args){}",
+            constructed,
+            arg_count,
+            calls.concat()
+        ))
+    }
+
+    /// Push `arg_count` args for a dynamic function call to a method taking `arg_types`, the last first. Each is of
+    /// the type the method takes, or random, see `random_value_of_type`
+    fn push_dynamic_args(
+        &mut self,
+        arg_types: &[&str],
+        arg_count: i32,
+    ) -> Result<(), Box<dyn Error>> {
+        for slot in (0..arg_count as usize).rev() {
+            let value = arg_types
+                .get(slot)
+                .and_then(|arg_type| self.random_value_of_type(arg_type));
+            self.push_or_random(value)?;
+        }
+        Ok(())
+    }
+
+    /// Call one of `functions` from `class_name.prototype` with `call`, on a receiver of another type from
+    /// `MISMATCHED_THIS_RECEIVERS`, then dump the return value and the receiver. Returns a description of the
+    /// operation, for its marker
+    fn mismatched_this_call(
+        &mut self,
+        class_name: &str,
+        functions: &[(&str, &[&str])],
+    ) -> Result<String, Box<dyn Error>> {
+        let receivers: Vec<&str> = MISMATCHED_THIS_RECEIVERS
+            .iter()
+            .copied()
+            .filter(|receiver| *receiver != class_name)
+            .collect();
+        let receiver = self.select(&receivers);
+
+        // var foo = <receiver>
+        self.push(&SimpleValue::String(Cow::Borrowed("foo")))?;
+        match receiver {
+            "String" => self.push(&SimpleValue::String(Cow::Borrowed("this is a test")))?,
+            "Number" => self.push(&SimpleValue::Int(10))?,
+            "Array" => {
+                let members = (0..self.rng.gen_range(0..5))
+                    .map(|_| self.random_value_simple(1))
+                    .collect();
+                self.push(&SimpleValue::Array(SimpleArray { members }))?;
+            }
+            "Object" => {
+                let members = (0..self.rng.gen_range(0..5))
+                    .map(|_| (self.random_value_simple(1), self.random_value_simple(1)))
+                    .collect();
+                self.push(&SimpleValue::Object(SimpleObject { members }))?;
+            }
+            _ => self.push(&SimpleValue::Undefined)?,
+        }
+        self.w.write_action(&Action::DefineLocal)?;
+
+        let (function_name, args) = self.select(functions);
+        let function_arg_count = self.rng.gen_range(0..=args.len() as i32);
+        self.push_dynamic_args(args, function_arg_count)?;
+
+        // <class_name>.prototype.<function_name>.call(foo, <args>)
+        self.push(&SimpleValue::String(Cow::Borrowed("foo")))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.push(&SimpleValue::Int(function_arg_count + 1))?;
+        self.push(&SimpleValue::String(Cow::Borrowed(class_name)))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.push(&SimpleValue::String(Cow::Borrowed("prototype")))?;
+        self.w.write_action(&Action::GetMember)?;
+        self.push(&SimpleValue::String(Cow::Borrowed(function_name)))?;
+        self.w.write_action(&Action::GetMember)?;
+        self.push(&SimpleValue::String(Cow::Borrowed("call")))?;
+        self.w.write_action(&Action::CallMethod)?;
+
+        // The method may have changed the receiver, e.g. pushed onto it
+        self.push(&SimpleValue::String(Cow::Borrowed("foo")))?;
+        self.w.write_action(&Action::GetVariable)?;
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        Ok(format!(
+            "{}.prototype.{}({} args) on {}",
+            class_name, function_name, function_arg_count, receiver
+        ))
+    }
+
+    /// Returns a description of the operation, for its marker
+    pub fn navigation_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+
+        // _root.onData = function() { trace("#ONDATA#"); }, this should never fire before the case completes
+        let mut on_data_body = Vec::new();
+        let mut body_writer = Writer::new(&mut on_data_body, self.version);
+        body_writer.write_action(&Action::Push(Push {
+            values: vec![Value::Str("#ONDATA#".into())],
+        }))?;
+        body_writer.write_action(&Action::Trace)?;
+
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("_root".into())],
+        }))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("onData".into())],
+        }))?;
+        self.w
+            .write_action(&Action::DefineFunction(DefineFunction {
+                name: "".into(),
+                params: vec![],
+                actions: &on_data_body,
+            }))?;
+        self.w.write_action(&Action::SetMember)?;
+
+        let method = self.select(&[
+            SendVarsMethod::None,
+            SendVarsMethod::Get,
+            SendVarsMethod::Post,
+        ]);
+        let (flags, kind) = match self.rng.gen_range(0..=2) {
+            0 => (GetUrl2::for_load_movie(method), "loadMovie"),
+            1 => (GetUrl2::for_load_vars(method), "loadVariables"),
+            // getURL into a level/target behaves like loadMovie, but takes a different path in both players
+            2 => (GetUrl2::for_get_url(method), "getURL"),
+            _ => unreachable!(),
+        };
+        let url = self.select(URL_CORPUS);
+        let target = self.select(NAVIGATION_TARGET_CORPUS);
+
+        // GetURL2 pops the target, then the url
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(url.into()), Value::Str(target.into())],
+        }))?;
+        self.w.write_action(&Action::GetUrl2(flags))?;
+
+        // Trace the synchronous observables, the load itself should never complete
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("_level1".into())],
+        }))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::TypeOf)?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(target.into())],
+        }))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::TypeOf)?;
+
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        Ok(format!("{}({}, {})", kind, url, target))
+    }
+
+    /// Returns a description of the operation, for its marker
+    pub fn arg_count_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+
+        let arg_count = self.rng.gen_range(0..=3);
         for _ in 0..arg_count {
             self.w.write_action(&Action::Push(Push {
-                values: vec![Self::random_value(self.rng, self.strings)],
+                values: vec![Self::random_value(
+                    self.rng,
+                    self.strings,
+                    self.constant_pool,
+                    self.filter,
+                    self.random_strings,
+                )],
+            }))?;
+        }
+
+        // The declared count, which the players are free to trust
+        let declared_count = self.select(&[
+            Value::Int(arg_count + 1),
+            Value::Int(arg_count - 1),
+            Value::Int(0),
+            Value::Int(-1),
+            Value::Double(2.5),
+            Value::Double(1e9),
+            Value::Double(f64::NAN),
+            Value::Str("2".into()),
+        ]);
+        self.w.write_action(&Action::Push(Push {
+            values: vec![declared_count.clone()],
+        }))?;
+
+        // Only builtins without side effects, so the count handling is all that is being tested
+        let callee = match self.rng.gen_range(0..=2) {
+            0 => {
+                let function_name = self.select(&["isNaN", "escape", "parseInt"]);
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str(function_name.into())],
+                }))?;
+                self.w.write_action(&Action::CallFunction)?;
+                function_name.to_string()
+            }
+            1 => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str("Math".into())],
+                }))?;
+                self.w.write_action(&Action::GetVariable)?;
+                let method_name = self.select(&["max", "min"]);
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str(method_name.into())],
+                }))?;
+                self.w.write_action(&Action::CallMethod)?;
+                format!("Math.{}", method_name)
+            }
+            2 => {
+                let class_name = self.select(&["Array", "Object"]);
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str(class_name.into())],
+                }))?;
+                self.w.write_action(&Action::NewObject)?;
+                format!("new {}", class_name)
+            }
+            _ => unreachable!(),
+        };
+
+        // __dump(result, 0)
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Int(0)],
+        }))?;
+        self.w.write_action(&Action::StackSwap)?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Int(2), Value::Str(DUMP_FUNCTION_NAME.into())],
+        }))?;
+        self.w.write_action(&Action::CallFunction)?;
+        self.w.write_action(&Action::Pop)?;
+
+        // Count what is left above `#PREFIX#`, a call that took more than was pushed will have consumed it
+        // so stop after `ARG_COUNT_MAX_DEPTH` rather than popping forever
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("__n".into()), Value::Int(0)],
+        }))?;
+        self.w.write_action(&Action::SetVariable)?;
+        let head = assemble(
+            self.version,
+            &[
+                Action::Push(Push {
+                    values: vec![Value::Str("#PREFIX#".into())],
+                }),
+                Action::StrictEquals,
+                Action::Push(Push {
+                    values: vec![Value::Str("__n".into())],
+                }),
+                Action::GetVariable,
+                Action::Push(Push {
+                    values: vec![Value::Int(ARG_COUNT_MAX_DEPTH)],
+                }),
+                Action::Less2,
+                Action::Not,
+                Action::Or,
+            ],
+        )?;
+        let body = assemble(
+            self.version,
+            &[
+                Action::Push(Push {
+                    values: vec![Value::Str("__n".into()), Value::Str("__n".into())],
+                }),
+                Action::GetVariable,
+                Action::Increment,
+                Action::SetVariable,
+            ],
+        )?;
+        self.w
+            .output
+            .extend(assemble_loop_until(self.version, &head, &body)?);
+
+        // trace("depth=" + __n)
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("depth=".into()), Value::Str("__n".into())],
+        }))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::Add2)?;
+        self.w.write_action(&Action::Trace)?;
+
+        Ok(format!(
+            "{}({} args, count {:?})",
+            callee, arg_count, declared_count
+        ))
+    }
+
+    /// Returns a description of the operation, for its marker
+    pub fn version_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+
+        let description = match self.rng.gen_range(0..=2) {
+            0 => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Int(0), Value::Str("getVersion".into())],
+                }))?;
+                self.w.write_action(&Action::CallFunction)?;
+                "getVersion()"
+            }
+            1 => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str("System".into())],
+                }))?;
+                self.w.write_action(&Action::GetVariable)?;
+                for member in ["capabilities", "version"] {
+                    self.w.write_action(&Action::Push(Push {
+                        values: vec![Value::Str(member.into())],
+                    }))?;
+                    self.w.write_action(&Action::GetMember)?;
+                }
+                "System.capabilities.version"
+            }
+            2 => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str("$version".into())],
+                }))?;
+                self.w.write_action(&Action::GetVariable)?;
+                "$version"
+            }
+            _ => unreachable!(),
+        };
+
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        Ok(description.to_string())
+    }
+
+    /// Trace every capability as `#CAP#<key>=<typeof value>:<value>`, in a sorted block as enumeration order isn't meaningful
+    /// Returns a description of the operation, for its marker
+    pub fn capabilities_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(SORTED_BLOCK_BEGIN.into())],
+        }))?;
+        self.w.write_action(&Action::Trace)?;
+
+        // __caps = System.capabilities
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("__caps".into()), Value::Str("System".into())],
+        }))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("capabilities".into())],
+        }))?;
+        self.w.write_action(&Action::GetMember)?;
+        self.w.write_action(&Action::SetVariable)?;
+
+        self.trace_members("__caps", CAPABILITY_PREFIX)?;
+
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(SORTED_BLOCK_END.into())],
+        }))?;
+        self.w.write_action(&Action::Trace)?;
+
+        Ok("System.capabilities".to_string())
+    }
+
+    /// Trace every member of the object in the variable `object_var` as `<prefix><key>=<typeof value>:<value>`, in
+    /// enumeration order
+    fn trace_members(&mut self, object_var: &str, prefix: &str) -> Result<(), Box<dyn Error>> {
+        // Enumerate2 pushes a null terminator followed by the keys
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(SwfStr::from_utf8_str(object_var))],
+        }))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::Enumerate2)?;
+
+        let head = assemble(
+            self.version,
+            &[
+                Action::PushDuplicate,
+                Action::Push(Push {
+                    values: vec![Value::Null],
+                }),
+                Action::Equals2,
+            ],
+        )?;
+        // __k = key; trace(prefix + __k + "=" + typeof object[__k] + ":" + object[__k])
+        let body = assemble(
+            self.version,
+            &[
+                Action::Push(Push {
+                    values: vec![Value::Str("__k".into())],
+                }),
+                Action::StackSwap,
+                Action::SetVariable,
+                Action::Push(Push {
+                    values: vec![
+                        Value::Str(SwfStr::from_utf8_str(prefix)),
+                        Value::Str("__k".into()),
+                    ],
+                }),
+                Action::GetVariable,
+                Action::Add2,
+                Action::Push(Push {
+                    values: vec![
+                        Value::Str("=".into()),
+                        Value::Str(SwfStr::from_utf8_str(object_var)),
+                    ],
+                }),
+                Action::GetVariable,
+                Action::Push(Push {
+                    values: vec![Value::Str("__k".into())],
+                }),
+                Action::GetVariable,
+                Action::GetMember,
+                Action::TypeOf,
+                Action::Add2,
+                Action::Add2,
+                Action::Push(Push {
+                    values: vec![
+                        Value::Str(":".into()),
+                        Value::Str(SwfStr::from_utf8_str(object_var)),
+                    ],
+                }),
+                Action::GetVariable,
+                Action::Push(Push {
+                    values: vec![Value::Str("__k".into())],
+                }),
+                Action::GetVariable,
+                Action::GetMember,
+                Action::Add2,
+                Action::Add2,
+                Action::Trace,
+            ],
+        )?;
+        self.w
+            .output
+            .extend(assemble_loop_until(self.version, &head, &body)?);
+        // Drop the terminator
+        self.w.write_action(&Action::Pop)?;
+        Ok(())
+    }
+
+    /// Push a member name from `MEMBER_CHAIN_MEMBERS`, as an int if it is one
+    fn push_member(&mut self, member: &str) -> Result<(), Box<dyn Error>> {
+        let value = match member.parse() {
+            Ok(index) => Value::Int(index),
+            Err(_) => Value::Str(SwfStr::from_utf8_str(member)),
+        };
+        self.w.write_action(&Action::Push(Push {
+            values: vec![value],
+        }))?;
+        Ok(())
+    }
+
+    /// Access a chain of members on an undefined, null, primitive or partly defined base, e.g. `undefined.x.y` or
+    /// `a.b.c` where only `a.b` is set, and dump the result.
+    /// Returns a description of the operation, for its marker
+    pub fn member_chain_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let base = self.select(CHAIN_BASES);
+        let members = (0..self.rng.gen_range(1..=MEMBER_CHAIN_MAX_LEN))
+            .map(|_| self.select(MEMBER_CHAIN_MEMBERS))
+            .collect::<Vec<_>>();
+        // Each case has its own variable, so that it isn't declared by an earlier one
+        let variable = format!("__chain{}", self.rng.gen::<u16>());
+        let variable_str = Value::Str(SwfStr::from_utf8_str(&variable));
+
+        let mut defined = 0;
+        match base {
+            ChainBase::DeclaredUndefined => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![variable_str.clone()],
+                }))?;
+                self.w.write_action(&Action::DefineLocal2)?;
+            }
+            ChainBase::DeclaredNull => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![variable_str.clone(), Value::Null],
+                }))?;
+                self.w.write_action(&Action::SetVariable)?;
+            }
+            ChainBase::Object | ChainBase::Array => {
+                let constructor = if matches!(base, ChainBase::Object) {
+                    "Object"
+                } else {
+                    "Array"
+                };
+                defined = self.rng.gen_range(0..=members.len());
+                // <variable> = new <constructor>(), then <variable>.<members[..level]> = new <constructor>() for each level
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![
+                        variable_str.clone(),
+                        Value::Int(0),
+                        Value::Str(constructor.into()),
+                    ],
+                }))?;
+                self.w.write_action(&Action::NewObject)?;
+                self.w.write_action(&Action::SetVariable)?;
+                for level in 1..=defined {
+                    self.w.write_action(&Action::Push(Push {
+                        values: vec![variable_str.clone()],
+                    }))?;
+                    self.w.write_action(&Action::GetVariable)?;
+                    for member in &members[..level - 1] {
+                        self.push_member(member)?;
+                        self.w.write_action(&Action::GetMember)?;
+                    }
+                    self.push_member(members[level - 1])?;
+                    self.w.write_action(&Action::Push(Push {
+                        values: vec![Value::Int(0), Value::Str(constructor.into())],
+                    }))?;
+                    self.w.write_action(&Action::NewObject)?;
+                    self.w.write_action(&Action::SetMember)?;
+                }
+            }
+            _ => {}
+        }
+
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        let base_value = match base {
+            ChainBase::Undefined => Value::Undefined,
+            ChainBase::Null => Value::Null,
+            ChainBase::Number => Value::Int(1),
+            ChainBase::String => Value::Str("str".into()),
+            _ => variable_str,
+        };
+        self.w.write_action(&Action::Push(Push {
+            values: vec![base_value],
+        }))?;
+        if !matches!(
+            base,
+            ChainBase::Undefined | ChainBase::Null | ChainBase::Number | ChainBase::String
+        ) {
+            self.w.write_action(&Action::GetVariable)?;
+        }
+        for member in &members {
+            self.push_member(member)?;
+            self.w.write_action(&Action::GetMember)?;
+        }
+
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        let base_expr = match base {
+            ChainBase::Undefined => "undefined".to_string(),
+            ChainBase::Null => "null".to_string(),
+            ChainBase::Number => "1".to_string(),
+            ChainBase::String => "\"str\"".to_string(),
+            _ => variable,
+        };
+        let chain = members
+            .iter()
+            .map(|member| match member.parse::<i32>() {
+                Ok(index) => format!("[{}]", index),
+                Err(_) => format!(".{}", member),
+            })
+            .collect::<String>();
+        let mut description = format!("{}{} on {:?}", base_expr, chain, base);
+        if matches!(base, ChainBase::Object | ChainBase::Array) {
+            description.push_str(&format!(" with {} of {} defined", defined, members.len()));
+        }
+        Ok(description)
+    }
+
+    /// Put an object with some of `MEMBER_ACCESS_NAMES`, or null or undefined, in a variable, then get, set and delete
+    /// its members in a random order, dumping the result of each get and delete. Delete2 deletes the variable itself,
+    /// or a variable named like a member. Returns a description of the operation, for its marker
+    pub fn member_access_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let receiver = self.select(MEMBER_ACCESS_RECEIVERS);
+        // Each case has its own variable, so that it isn't set by an earlier one
+        let variable = format!("__obj{}", self.rng.gen::<u16>());
+
+        // <variable> = <receiver>
+        self.push(&SimpleValue::String(Cow::Borrowed(&variable)))?;
+        match receiver {
+            "Object" => {
+                let mut members = Vec::new();
+                for name in MEMBER_ACCESS_NAMES {
+                    if self.rng.gen() {
+                        let value = self.random_value_simple(1);
+                        members.push((SimpleValue::String(Cow::Borrowed(*name)), value));
+                    }
+                }
+                self.push(&SimpleValue::Object(SimpleObject { members }))?;
+            }
+            "null" => self.push(&SimpleValue::Null)?,
+            _ => self.push(&SimpleValue::Undefined)?,
+        }
+        self.w.write_action(&Action::SetVariable)?;
+
+        let mut ops = Vec::new();
+        for _ in 0..self.rng.gen_range(1..=MEMBER_ACCESS_MAX_OPS) {
+            let name = self.select(MEMBER_ACCESS_NAMES);
+            let op = self.select(&[
+                Action::GetMember,
+                Action::SetMember,
+                Action::Delete,
+                Action::Delete2,
+            ]);
+            if !self.filter.allows_op(&format!("{:?}", op)) {
+                continue;
+            }
+            if !matches!(op, Action::SetMember) {
+                self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+            }
+            if matches!(op, Action::Delete2) {
+                // delete <variable>, or delete <name>
+                let target = if self.rng.gen() {
+                    variable.as_str()
+                } else {
+                    name
+                };
+                self.push(&SimpleValue::String(Cow::Borrowed(target)))?;
+                self.w.write_action(&op)?;
+                SwfGenerator::dump_stack(&mut self.w)?;
+                ops.push(format!("{:?}({:?})", op, target));
+                continue;
+            }
+
+            self.push(&SimpleValue::String(Cow::Borrowed(&variable)))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.push(&SimpleValue::String(Cow::Borrowed(name)))?;
+            if matches!(op, Action::SetMember) {
+                let value = self.random_value_simple(1);
+                self.push(&value)?;
+                self.w.write_action(&op)?;
+            } else {
+                self.w.write_action(&op)?;
+                SwfGenerator::dump_stack(&mut self.w)?;
+            }
+            ops.push(format!("{:?}({:?})", op, name));
+        }
+
+        Ok(format!("{} {}", receiver, ops.join(", ")))
+    }
+
+    /// Give an object literal another as its `__proto__` and dump the properties in `PROTO_LOOKUPS` read through it,
+    /// in a random order. Sometimes the prototype's `__proto__` is set to the instance, so reading a missing property
+    /// goes round a cycle, and sometimes a property only on the prototype is written through the instance first,
+    /// which should only change the instance. Returns a description of the operation, for its marker
+    pub fn proto_chain_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        // Each case has its own variables, so that they aren't set by an earlier one
+        let id = self.rng.gen::<u16>();
+        let instance = format!("__inst{}", id);
+        let prototype = format!("__proto{}", id);
+
+        // <variable> = { <members> }
+        for (variable, members) in [
+            (&instance, PROTO_INSTANCE_MEMBERS),
+            (&prototype, PROTO_PROTOTYPE_MEMBERS),
+        ] {
+            self.push(&SimpleValue::String(Cow::Borrowed(variable)))?;
+            self.push(&SimpleValue::Object(SimpleObject {
+                members: members
+                    .iter()
+                    .map(|(name, value)| {
+                        (
+                            SimpleValue::String(Cow::Borrowed(*name)),
+                            SimpleValue::String(Cow::Borrowed(*value)),
+                        )
+                    })
+                    .collect(),
             }))?;
+            self.w.write_action(&Action::SetVariable)?;
+        }
+
+        // <object>.__proto__ = <proto>
+        let cyclic = self.rng.gen_bool(PROTO_CYCLE_RATE);
+        let mut links = vec![(&instance, &prototype)];
+        if cyclic {
+            links.push((&prototype, &instance));
+        }
+        for (object, proto) in links {
+            self.push(&SimpleValue::String(Cow::Borrowed(object)))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.push(&SimpleValue::String(Cow::Borrowed("__proto__")))?;
+            self.push(&SimpleValue::String(Cow::Borrowed(proto)))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.w.write_action(&Action::SetMember)?;
+        }
+
+        // <instance>.inherited = "written", then dump the instance's and the prototype's
+        let write_through = self.rng.gen_bool(PROTO_WRITE_RATE);
+        if write_through {
+            self.push(&SimpleValue::String(Cow::Borrowed(&instance)))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.push(&SimpleValue::String(Cow::Borrowed("inherited")))?;
+            self.push(&SimpleValue::String(Cow::Borrowed("written")))?;
+            self.w.write_action(&Action::SetMember)?;
+            self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+            for variable in [&instance, &prototype] {
+                self.push(&SimpleValue::String(Cow::Borrowed(variable)))?;
+                self.w.write_action(&Action::GetVariable)?;
+                self.push(&SimpleValue::String(Cow::Borrowed("inherited")))?;
+                self.w.write_action(&Action::GetMember)?;
+            }
+            SwfGenerator::dump_stack(&mut self.w)?;
+        }
+
+        let mut lookups = PROTO_LOOKUPS.to_vec();
+        lookups.shuffle(self.rng);
+        for name in &lookups {
+            self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+            self.push(&SimpleValue::String(Cow::Borrowed(&instance)))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.push(&SimpleValue::String(Cow::Borrowed(name)))?;
+            self.w.write_action(&Action::GetMember)?;
+            SwfGenerator::dump_stack(&mut self.w)?;
+        }
+
+        let mut description = format!(
+            "{}__proto__ chain ({})",
+            if cyclic { "cyclic " } else { "" },
+            lookups.join(", ")
+        );
+        if write_through {
+            description.push_str(" written through");
+        }
+        Ok(description)
+    }
+
+    /// Define a base and a derived class as DefineFunction2 constructors, wire them with Extends and maybe declare that
+    /// the derived one implements an interface with ImplementsOp, then construct an instance of it with NewObject or
+    /// NewMethod and dump whether it is an InstanceOf the base, the derived class, the interface and Array. Sometimes
+    /// a class passed to Extends is a random value instead, and sometimes a primitive is checked rather than the
+    /// instance. Returns a description of the operation, for its marker
+    pub fn class_hierarchy_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        // Each case has its own classes, so that they aren't wired up by an earlier one
+        let id = self.rng.gen::<u16>();
+        let base = format!("__Base{}", id);
+        let derived = format!("__Derived{}", id);
+        let interface = format!("__Iface{}", id);
+        let instance = format!("__inst{}", id);
+
+        // <class> = function () {}
+        for class in [&base, &derived, &interface] {
+            self.push(&SimpleValue::String(Cow::Borrowed(class)))?;
+            self.w
+                .write_action(&Action::DefineFunction2(DefineFunction2 {
+                    name: "".into(),
+                    register_count: 0,
+                    params: vec![],
+                    flags: FunctionFlags::empty(),
+                    actions: &[],
+                }))?;
+            self.w.write_action(&Action::SetVariable)?;
+        }
+
+        // <derived> extends <base>, the subclass is popped last
+        let mut not_functions = Vec::new();
+        for (role, class) in [("subclass", &derived), ("superclass", &base)] {
+            if self.rng.gen_bool(CLASS_NOT_FUNCTION_RATE) {
+                let value = self.random_value_simple(1);
+                self.push(&value)?;
+                not_functions.push(role);
+            } else {
+                self.push(&SimpleValue::String(Cow::Borrowed(class)))?;
+                self.w.write_action(&Action::GetVariable)?;
+            }
+        }
+        self.w.write_action(&Action::Extends)?;
+
+        // <derived> implements <interface>, the interfaces and their count are popped after the class
+        let implements = self.rng.gen_bool(CLASS_INTERFACE_RATE);
+        if implements {
+            self.push(&SimpleValue::String(Cow::Borrowed(&interface)))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.push(&SimpleValue::Int(1))?;
+            self.push(&SimpleValue::String(Cow::Borrowed(&derived)))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.w.write_action(&Action::ImplementsOp)?;
         }
 
-        // The name, the arg count
-        self.push(&SimpleValue::Int(arg_count))?;
-        self.push(&SimpleValue::String(Cow::Borrowed(class_name)))?;
-        self.w.write_action(&Action::NewObject)?;
-        self.w.write_action(&Action::DefineLocal)?;
+        // <instance> = new <derived>(), or new this[<derived>]()
+        let new_method = self.rng.gen();
+        self.push(&SimpleValue::String(Cow::Borrowed(&instance)))?;
+        self.push(&SimpleValue::Int(0))?;
+        if new_method {
+            self.push(&SimpleValue::String(Cow::Borrowed("this")))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.push(&SimpleValue::String(Cow::Borrowed(&derived)))?;
+            self.w.write_action(&Action::NewMethod)?;
+        } else {
+            self.push(&SimpleValue::String(Cow::Borrowed(&derived)))?;
+            self.w.write_action(&Action::NewObject)?;
+        }
+        self.w.write_action(&Action::SetVariable)?;
+
+        let primitive = self
+            .rng
+            .gen_bool(CLASS_PRIMITIVE_RATE)
+            .then(|| self.select(CLASS_PRIMITIVES));
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        for class in [&base, &derived, &interface, "Array"] {
+            match &primitive {
+                Some(primitive) => self.push(primitive)?,
+                None => {
+                    self.push(&SimpleValue::String(Cow::Borrowed(&instance)))?;
+                    self.w.write_action(&Action::GetVariable)?;
+                }
+            }
+            self.push(&SimpleValue::String(Cow::Borrowed(class)))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.w.write_action(&Action::InstanceOf)?;
+        }
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        let mut description = format!(
+            "{} of derived class{}",
+            match &primitive {
+                Some(_) => "InstanceOf primitive",
+                None => "InstanceOf instance",
+            },
+            if implements {
+                " implementing interface"
+            } else {
+                ""
+            },
+        );
+        if !not_functions.is_empty() {
+            description.push_str(&format!(
+                " extending a non-function ({})",
+                not_functions.join(", ")
+            ));
+        }
+        let mut details = vec![if new_method { "NewMethod" } else { "NewObject" }.to_string()];
+        if let Some(primitive) = &primitive {
+            details.push(format!("{:?}", primitive));
+        }
+        description.push_str(&format!(" ({})", details.join(", ")));
+        Ok(description)
+    }
+
+    /// Call one of `GLOBAL_FUNCTIONS` with strings from `GLOBAL_ARG_STRINGS`, or random bytes when `random_strings`
+    /// is set, and a radix from `GLOBAL_RADIXES` for parseInt, then dump the result. Returns a description of the
+    /// operation, for its marker
+    pub fn global_function_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let (name, max_args) = self.select(GLOBAL_FUNCTIONS);
+        let arg_count = self.rng.gen_range(0..=max_args);
+
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        // The last arg is pushed first
+        let mut args = Vec::new();
+        for slot in (0..arg_count).rev() {
+            if name == "parseInt" && slot == 1 {
+                let radix = self.select(GLOBAL_RADIXES);
+                self.push(&SimpleValue::Int(radix))?;
+                args.push(radix.to_string());
+            } else if self.random_strings && self.rng.gen_bool(GLOBAL_RANDOM_ARG_RATE) {
+                let mut buf = vec![0; self.rng.gen_range(1..64)];
+                self.rng.fill(buf.as_mut_slice());
+                args.push(format!("{} random bytes", buf.len()));
+                self.strings.push(buf);
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str(SwfStr::from_bytes(self.strings.last().unwrap()))],
+                }))?;
+            } else {
+                let arg = self.select(GLOBAL_ARG_STRINGS);
+                self.push(&SimpleValue::String(Cow::Borrowed(arg)))?;
+                args.push(format!("{:?}", arg));
+            }
+        }
+        args.reverse();
+
+        self.push(&SimpleValue::Int(arg_count as i32))?;
+        self.push(&SimpleValue::String(Cow::Borrowed(name)))?;
+        self.w.write_action(&Action::CallFunction)?;
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        Ok(format!("{}({})", name, args.join(", ")))
+    }
+
+    /// Convert one of `NUMBER_FORMAT_VALUES` to a string with ToString and StringAdd, trace it directly and parse the
+    /// ToString result back with ToNumber, then dump the results. Returns a description of the operation, for its
+    /// marker
+    pub fn number_format_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let value = self.select(NUMBER_FORMAT_VALUES);
+
+        self.push(&SimpleValue::Double(value))?;
+        self.w.write_action(&Action::Trace)?;
+
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        // String(value)
+        self.push(&SimpleValue::Double(value))?;
+        self.w.write_action(&Action::ToString)?;
+        // value add ""
+        self.push(&SimpleValue::Double(value))?;
+        self.push(&SimpleValue::String(Cow::Borrowed("")))?;
+        self.w.write_action(&Action::StringAdd)?;
+        // Number(String(value))
+        self.push(&SimpleValue::Double(value))?;
+        self.w.write_action(&Action::ToString)?;
+        self.w.write_action(&Action::ToNumber)?;
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        Ok(format!("number format of {:?}", value))
+    }
+
+    /// Place a sprite on the stage and do a random sequence of MovieClip operations to it or its duplicate, reading
+    /// and writing `_x` and `_y`, calling `getDepth`, `getBounds`, `gotoAndStop`, `duplicateMovieClip` and
+    /// `removeMovieClip`, and dumping each result. Each operation references the clip through one of
+    /// `MOVIE_CLIP_REFERENCES`, and operations after `removeMovieClip` find out what is left. Returns a description of
+    /// the operation, for its marker
+    pub fn movie_clip_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let num_frames = self.rng.gen_range(1..=MOVIE_CLIP_MAX_FRAMES);
+        let clip = self.stage.place_clip(num_frames);
+        let duplicate = format!("{}_copy", clip);
+        // Depths passed to duplicateMovieClip are seen by scripts, so they don't collide with timeline depths
+        let duplicate_depth = self.stage.allocate_depth();
+
+        let mut ops = Vec::new();
+        for _ in 0..self.rng.gen_range(1..=MOVIE_CLIP_MAX_OPS) {
+            let op = self.select(MOVIE_CLIP_OPS);
+            let name = if self.rng.gen_bool(MOVIE_CLIP_DUPLICATE_RATE) {
+                &duplicate
+            } else {
+                &clip
+            };
+            let reference = format!("{}{}", self.select(MOVIE_CLIP_REFERENCES), name);
+            let get_clip = |dag: &mut Self| -> Result<(), Box<dyn Error>> {
+                dag.push(&SimpleValue::String(Cow::Borrowed(&reference)))?;
+                dag.w.write_action(&Action::GetVariable)?;
+                Ok(())
+            };
+            // <clip>.<method>(<args>), `args` are pushed in reverse
+            let call = |dag: &mut Self,
+                        method: &str,
+                        args: &[SimpleValue<'_>]|
+             -> Result<(), Box<dyn Error>> {
+                for arg in args {
+                    dag.push(arg)?;
+                }
+                dag.push(&SimpleValue::Int(args.len() as i32))?;
+                get_clip(dag)?;
+                dag.push(&SimpleValue::String(Cow::Borrowed(method)))?;
+                dag.w.write_action(&Action::CallMethod)?;
+                Ok(())
+            };
+            let read = |dag: &mut Self, property: &str| -> Result<(), Box<dyn Error>> {
+                dag.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+                get_clip(dag)?;
+                dag.push(&SimpleValue::String(Cow::Borrowed(property)))?;
+                dag.w.write_action(&Action::GetMember)?;
+                SwfGenerator::dump_stack(&mut dag.w)?;
+                Ok(())
+            };
+
+            let args = match op {
+                "getDepth" => {
+                    self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+                    call(self, "getDepth", &[])?;
+                    SwfGenerator::dump_stack(&mut self.w)?;
+                    String::new()
+                }
+                "getBounds" => {
+                    self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+                    // The bounds in the clip's own space, or in the root's
+                    let args = if self.rng.gen() {
+                        vec![SimpleValue::String(Cow::Borrowed("_root"))]
+                    } else {
+                        Vec::new()
+                    };
+                    call(self, "getBounds", &args)?;
+                    SwfGenerator::dump_stack(&mut self.w)?;
+                    if args.is_empty() {
+                        String::new()
+                    } else {
+                        ", _root".to_string()
+                    }
+                }
+                "read _x" | "read _y" => {
+                    read(self, &op[5..])?;
+                    String::new()
+                }
+                "write _x" | "write _y" => {
+                    let (value, description) = match self.rng.gen_range(0..4) {
+                        0 => {
+                            let value = self.rng.gen_range(-100..=100);
+                            (SimpleValue::Int(value), value.to_string())
+                        }
+                        1 => (SimpleValue::Double(12.75), "12.75".to_string()),
+                        2 => (SimpleValue::Double(f64::NAN), "NaN".to_string()),
+                        _ => (SimpleValue::String(Cow::Borrowed("7")), "\"7\"".to_string()),
+                    };
+                    get_clip(self)?;
+                    self.push(&SimpleValue::String(Cow::Borrowed(&op[6..])))?;
+                    self.push(&value)?;
+                    self.w.write_action(&Action::SetMember)?;
+                    read(self, &op[6..])?;
+                    format!(", {}", description)
+                }
+                "gotoAndStop" => {
+                    // Frame 0 and the frame after the last don't exist
+                    let frame = self.rng.gen_range(0..=i32::from(num_frames) + 1);
+                    call(self, "gotoAndStop", &[SimpleValue::Int(frame)])?;
+                    self.w.write_action(&Action::Pop)?;
+                    read(self, "_currentframe")?;
+                    format!(", {}", frame)
+                }
+                "duplicateMovieClip" => {
+                    self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+                    call(
+                        self,
+                        "duplicateMovieClip",
+                        &[
+                            SimpleValue::Int(i32::from(duplicate_depth)),
+                            SimpleValue::String(Cow::Borrowed(&duplicate)),
+                        ],
+                    )?;
+                    SwfGenerator::dump_stack(&mut self.w)?;
+                    format!(", {}", duplicate_depth)
+                }
+                _ => {
+                    // A clip placed by the timeline has a negative depth, which removeMovieClip should refuse
+                    call(self, "removeMovieClip", &[])?;
+                    self.w.write_action(&Action::Pop)?;
+                    self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+                    get_clip(self)?;
+                    SwfGenerator::dump_stack(&mut self.w)?;
+                    String::new()
+                }
+            };
+            ops.push(format!("{}({}{})", op, reference, args));
+        }
+
+        Ok(format!(
+            "MovieClip ({} frames) {}",
+            num_frames,
+            ops.join(", ")
+        ))
+    }
+
+    /// Push a reference to the clip `name` for CloneSprite, RemoveSprite or SetTarget2, as a string in one of
+    /// `MOVIE_CLIP_REFERENCES`, the clip itself or a value that isn't a clip. Returns a description of what it pushed
+    fn push_sprite_target(&mut self, name: &str) -> Result<String, Box<dyn Error>> {
+        Ok(match self.rng.gen_range(0..3) {
+            0 => {
+                let reference = format!("{}{}", self.select(MOVIE_CLIP_REFERENCES), name);
+                self.push(&SimpleValue::String(Cow::Borrowed(&reference)))?;
+                format!("{:?}", reference)
+            }
+            1 => {
+                self.push(&SimpleValue::String(Cow::Borrowed(name)))?;
+                self.w.write_action(&Action::GetVariable)?;
+                name.to_string()
+            }
+            _ => {
+                let (value, description) = match self.rng.gen_range(0..5) {
+                    0 => (SimpleValue::Undefined, "undefined"),
+                    1 => (SimpleValue::Null, "null"),
+                    2 => (SimpleValue::Int(42), "42"),
+                    3 => (SimpleValue::String(Cow::Borrowed("missing")), "\"missing\""),
+                    _ => (SimpleValue::Object(SimpleObject { members: vec![] }), "{}"),
+                };
+                self.push(&value)?;
+                description.to_string()
+            }
+        })
+    }
+
+    /// Dump the target path and type of whatever `name` resolves to
+    fn dump_sprite(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        for action in [Action::TargetPath, Action::TypeOf] {
+            self.push(&SimpleValue::String(Cow::Borrowed(name)))?;
+            self.w.write_action(&Action::GetVariable)?;
+            self.w.write_action(&action)?;
+        }
+        SwfGenerator::dump_stack(&mut self.w)?;
+        Ok(())
+    }
+
+    /// Place a sprite on the stage and do a random sequence of CloneSprite and RemoveSprite to it and its clones, with
+    /// depths from `CLONE_SPRITE_DEPTHS` and sources and targets that may not be clips, dumping the target path and
+    /// type of the clips each affects. SetTarget and SetTarget2 change the target before tracing, both directly and
+    /// the target's `_target`. Returns a description of the operation, for its marker
+    pub fn clone_sprite_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let clip = self.stage.place_clip(1);
+        let mut clips = vec![clip.clone()];
+
+        let mut ops = Vec::new();
+        for _ in 0..self.rng.gen_range(1..=CLONE_SPRITE_MAX_OPS) {
+            let op = self.select(&[
+                Action::CloneSprite,
+                Action::RemoveSprite,
+                Action::SetTarget(SetTarget { target: "".into() }),
+                Action::SetTarget2,
+            ]);
+            let op = match op {
+                Action::CloneSprite => {
+                    // CloneSprite pops the depth, then the target, then the source
+                    let name = format!("{}_clone{}", clip, clips.len());
+                    let source = self.select(&clips);
+                    let source = self.push_sprite_target(&source)?;
+                    self.push(&SimpleValue::String(Cow::Borrowed(&name)))?;
+                    let depth = self.select(CLONE_SPRITE_DEPTHS);
+                    self.push(&SimpleValue::Int(depth))?;
+                    self.w.write_action(&Action::CloneSprite)?;
+                    self.dump_sprite(&name)?;
+                    clips.push(name.clone());
+                    format!("CloneSprite({}, {:?}, {})", source, name, depth)
+                }
+                Action::RemoveSprite => {
+                    let name = self.select(&clips);
+                    let target = self.push_sprite_target(&name)?;
+                    self.w.write_action(&Action::RemoveSprite)?;
+                    self.dump_sprite(&name)?;
+                    format!("RemoveSprite({})", target)
+                }
+                _ => {
+                    let target = if matches!(op, Action::SetTarget2) {
+                        let name = self.select(&clips);
+                        let target = self.push_sprite_target(&name)?;
+                        self.w.write_action(&Action::SetTarget2)?;
+                        format!("SetTarget2({})", target)
+                    } else {
+                        let target = if self.rng.gen() {
+                            self.select(&clips)
+                        } else {
+                            self.select(SET_TARGET_NAMES).to_string()
+                        };
+                        self.w.write_action(&Action::SetTarget(SetTarget {
+                            target: SwfStr::from_utf8_str(&target),
+                        }))?;
+                        format!("SetTarget({:?})", target)
+                    };
+                    // trace("in target"); trace(getProperty("", _target)), `__dump` may not be found from the target
+                    self.push(&SimpleValue::String(Cow::Borrowed("in target")))?;
+                    self.w.write_action(&Action::Trace)?;
+                    self.w.write_action(&Action::Push(Push {
+                        values: vec![Value::Str("".into()), Value::Int(TARGET_PROPERTY)],
+                    }))?;
+                    self.w.write_action(&Action::GetProperty)?;
+                    self.w.write_action(&Action::Trace)?;
+                    self.w
+                        .write_action(&Action::SetTarget(SetTarget { target: "".into() }))?;
+                    target
+                }
+            };
+            ops.push(op);
+        }
+
+        Ok(ops.join(", "))
+    }
+
+    /// Get or set a property by index with GetProperty or SetProperty, on the root, the current target or a placed
+    /// clip, then dump the property. Indices past the last property and that aren't integers are used too, and
+    /// properties in `UNSTABLE_PROPERTIES` are traced after `UNSTABLE_PREFIX` instead of being dumped. Expects
+    /// `#PREFIX#` to have been pushed already, returns a description of the operation, for its marker
+    fn property_opcode_fuzz(&mut self, action: &Action) -> Result<String, Box<dyn Error>> {
+        let target = if self.rng.gen() {
+            self.select(PROPERTY_TARGETS).to_string()
+        } else {
+            self.stage.place_clip(1)
+        };
+        let (index, description, unstable) = match self.rng.gen_range(0..PROPERTY_NAMES.len() + 5) {
+            i if i < PROPERTY_NAMES.len() => (
+                SimpleValue::Int(i as i32),
+                PROPERTY_NAMES[i].to_string(),
+                UNSTABLE_PROPERTIES.contains(&(i as i32)),
+            ),
+            i => {
+                let (index, description) = match i - PROPERTY_NAMES.len() {
+                    0 => (SimpleValue::Int(22), "22"),
+                    1 => (SimpleValue::Int(100), "100"),
+                    2 => (SimpleValue::Int(-1), "-1"),
+                    3 => (SimpleValue::Double(3.5), "3.5"),
+                    _ => (SimpleValue::String(Cow::Borrowed("1")), "\"1\""),
+                };
+                (index, description.to_string(), false)
+            }
+        };
+        let get_property = |dag: &mut Self| -> Result<(), Box<dyn Error>> {
+            dag.push(&SimpleValue::String(Cow::Borrowed(&target)))?;
+            dag.push(&index)?;
+            dag.w.write_action(&Action::GetProperty)?;
+            Ok(())
+        };
+
+        if matches!(action, Action::SetProperty) {
+            let value = if self.rng.gen() {
+                SimpleValue::Int(self.rng.gen_range(-100..=100))
+            } else {
+                self.random_value_simple(0)
+            };
+            self.push(&SimpleValue::String(Cow::Borrowed(&target)))?;
+            self.push(&index)?;
+            self.push(&value)?;
+            self.w.write_action(&Action::SetProperty)?;
+        }
+        if unstable {
+            // trace("#UNSTABLE#<name>=" + typeof <property> + ":" + <property>)
+            self.push(&SimpleValue::String(Cow::Owned(format!(
+                "{}{}=",
+                UNSTABLE_PREFIX, description
+            ))))?;
+            get_property(self)?;
+            self.w.write_action(&Action::TypeOf)?;
+            self.w.write_action(&Action::StringAdd)?;
+            self.push(&SimpleValue::String(Cow::Borrowed(":")))?;
+            self.w.write_action(&Action::StringAdd)?;
+            get_property(self)?;
+            self.w.write_action(&Action::StringAdd)?;
+            self.w.write_action(&Action::Trace)?;
+        } else {
+            get_property(self)?;
+        }
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        Ok(format!("{:?}({:?}, {})", action, target, description))
+    }
+
+    /// Run one of the string opcodes on a string from `MULTIBYTE_STRINGS` or a long string built at runtime, or
+    /// MBAsciiToChar on one of `MULTIBYTE_CHAR_CODES`, then dump the result. Expects `#PREFIX#` to have been pushed
+    /// already, returns a description of the operation that names the encoding the version reads strings as, for its
+    /// marker
+    fn multibyte_opcode_fuzz(&mut self, action: &Action) -> Result<String, Box<dyn Error>> {
+        let mut operands = Vec::new();
+        if matches!(action, Action::MBAsciiToChar) {
+            let code = self.select(MULTIBYTE_CHAR_CODES);
+            self.push(&SimpleValue::Int(code))?;
+            operands.push(format!("{:#x}", code));
+        } else if self.rng.gen_range(0..=MULTIBYTE_STRINGS.len()) == 0 {
+            self.push(&SimpleValue::String(Cow::Borrowed(MULTIBYTE_LONG_CHUNK)))?;
+            for _ in 0..MULTIBYTE_LONG_DOUBLINGS {
+                self.w.write_action(&Action::PushDuplicate)?;
+                self.w.write_action(&Action::StringAdd)?;
+            }
+            operands.push(format!(
+                "{} bytes",
+                MULTIBYTE_LONG_CHUNK.len() << MULTIBYTE_LONG_DOUBLINGS
+            ));
+        } else {
+            let (kind, bytes) = self.select(MULTIBYTE_STRINGS);
+            self.strings.push(bytes.to_vec());
+            self.w.write_action(&Action::Push(Push {
+                values: vec![Value::Str(SwfStr::from_bytes(self.strings.last().unwrap()))],
+            }))?;
+            operands.push(kind.to_string());
+        }
+        if matches!(action, Action::MBStringExtract | Action::StringExtract) {
+            for _ in 0..2 {
+                let bound = self.select(MULTIBYTE_EXTRACT_BOUNDS);
+                self.push(&SimpleValue::Int(bound))?;
+                operands.push(bound.to_string());
+            }
+        }
+        self.w.write_action(action)?;
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        let encoding = if self.version >= 6 {
+            "utf-8"
+        } else {
+            "windows-1252"
+        };
+        Ok(format!(
+            "{:?}({}) as {}",
+            action,
+            operands.join(", "),
+            encoding
+        ))
+    }
+
+    /// Navigate the labeled sprite to a label with ActionGoToLabel, gotoAndStop/gotoAndPlay or call, then dump its
+    /// `_currentframe`. The sprite's frames trace their number when they run, so the frame a call ran is traced too.
+    /// Returns a description of the operation, for its marker
+    pub fn frame_label_fuzz(
+        &mut self,
+        layout: &FrameLabelLayout,
+    ) -> Result<String, Box<dyn Error>> {
+        let label = if self.rng.gen_bool(0.2) {
+            self.select(UNLABELED_FRAME_TARGETS)
+        } else {
+            self.select(FRAME_LABEL_NAMES)
+        };
+        let label_str = SwfStr::from_utf8_str(label);
+
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        self.w.write_action(&Action::SetTarget(SetTarget {
+            target: SwfStr::from_utf8_str(LABEL_SPRITE_NAME),
+        }))?;
+        let kind = match self.rng.gen_range(0..=2) {
+            0 => {
+                self.w
+                    .write_action(&Action::GotoLabel(GotoLabel { label: label_str }))?;
+                "ActionGoToLabel"
+            }
+            1 => {
+                let set_playing = self.rng.gen();
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str(label_str)],
+                }))?;
+                self.w.write_action(&Action::GotoFrame2(GotoFrame2 {
+                    set_playing,
+                    scene_offset: 0,
+                }))?;
+                if set_playing {
+                    "gotoAndPlay"
+                } else {
+                    "gotoAndStop"
+                }
+            }
+            2 => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str(label_str)],
+                }))?;
+                self.w.write_action(&Action::Call)?;
+                "call"
+            }
+            _ => unreachable!(),
+        };
+        self.w
+            .write_action(&Action::SetTarget(SetTarget { target: "".into() }))?;
+
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(SwfStr::from_utf8_str(LABEL_SPRITE_NAME))],
+        }))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("_currentframe".into())],
+        }))?;
+        self.w.write_action(&Action::GetMember)?;
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        Ok(format!(
+            "{}({:?}) on {}, labeled frames {}",
+            kind,
+            label,
+            LABEL_SPRITE_NAME,
+            layout.frames_with(label)
+        ))
+    }
+
+    /// Turn a reference to one of the nested path clips, written in dot or slash syntax, into a path string with
+    /// ActionTargetPath, then resolve that string again with GetVariable, GetProperty and SetTarget2 and dump the path
+    /// and what each resolved to. The `_target` from GetProperty is in slash syntax, so it is resolved again too.
+    /// Returns a description of the operation, for its marker
+    pub fn target_path_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let reference = self.select(TARGET_PATH_REFERENCES);
+        let path_variable = || {
+            Action::Push(Push {
+                values: vec![Value::Str(TARGET_PATH_VARIABLE.into())],
+            })
+        };
+
+        // __tpath = targetPath(eval(<reference>))
+        self.w.write_action(&path_variable())?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(SwfStr::from_utf8_str(reference))],
+        }))?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::TargetPath)?;
+        self.w.write_action(&Action::SetVariable)?;
+
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        // The path itself
+        self.w.write_action(&path_variable())?;
+        self.w.write_action(&Action::GetVariable)?;
+        // targetPath(eval(__tpath))
+        self.w.write_action(&path_variable())?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::TargetPath)?;
+        // getProperty(__tpath, _target)
+        self.w.write_action(&path_variable())?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Int(TARGET_PROPERTY)],
+        }))?;
+        self.w.write_action(&Action::GetProperty)?;
+        // targetPath(eval(getProperty(__tpath, _target))), back from the slash syntax of `_target`
+        self.w.write_action(&path_variable())?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Int(TARGET_PROPERTY)],
+        }))?;
+        self.w.write_action(&Action::GetProperty)?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::TargetPath)?;
+        // tellTarget(__tpath) { getProperty("", _target) }
+        self.w.write_action(&path_variable())?;
+        self.w.write_action(&Action::GetVariable)?;
+        self.w.write_action(&Action::SetTarget2)?;
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str("".into()), Value::Int(TARGET_PROPERTY)],
+        }))?;
+        self.w.write_action(&Action::GetProperty)?;
+        self.w
+            .write_action(&Action::SetTarget(SetTarget { target: "".into() }))?;
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        Ok(format!(
+            "targetPath({}) resolved with GetVariable, GetProperty and SetTarget2",
+            reference
+        ))
+    }
+
+    /// Compare one of the curated string pairs with each of the comparison opcodes, left to right and right to left,
+    /// and dump the results. Returns a description of the operation, naming the pair, for its marker
+    pub fn string_compare_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let pair = self.select(STRING_COMPARE_PAIRS);
+        // StringGreater and Greater were added in SWF6
+        let opcodes: Vec<(&str, Action)> = STRING_COMPARE_OPCODES
+            .iter()
+            .filter(|(name, _)| self.filter.allows_op(name))
+            .filter(|(_, action)| {
+                self.version >= 6 || !matches!(action, Action::StringGreater | Action::Greater)
+            })
+            .cloned()
+            .collect();
+
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        for (_, action) in &opcodes {
+            for (a, b) in [(pair.left, pair.right), (pair.right, pair.left)] {
+                self.push_compare_operand(a)?;
+                self.push_compare_operand(b)?;
+                self.w.write_action(action)?;
+            }
+        }
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        let names: Vec<&str> = opcodes.iter().map(|(name, _)| *name).collect();
+        Ok(format!(
+            "string compare of {} ({}, {}) with {}",
+            pair.kind,
+            pair.left,
+            pair.right,
+            names.join(", ")
+        ))
+    }
+
+    /// Push an operand of a string comparison, see `CompareOperand`
+    fn push_compare_operand(&mut self, operand: CompareOperand) -> Result<(), Box<dyn Error>> {
+        match operand {
+            CompareOperand::Str(s) => self.push(&SimpleValue::String(Cow::Borrowed(s))),
+            CompareOperand::Int(i) => self.push(&SimpleValue::Int(i)),
+            CompareOperand::Double(d) => self.push(&SimpleValue::Double(d)),
+            CompareOperand::NullTerminated(s) => {
+                // s + String.fromCharCode(0)
+                self.push(&SimpleValue::String(Cow::Borrowed(s)))?;
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Int(0), Value::Int(1), Value::Str("String".into())],
+                }))?;
+                self.w.write_action(&Action::GetVariable)?;
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str("fromCharCode".into())],
+                }))?;
+                self.w.write_action(&Action::CallMethod)?;
+                self.w.write_action(&Action::StringAdd)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Run `action` the way the SWF4 VM can, on strings and floats pushed directly as it has no registers or other
+    /// types of push, then trace its result directly as it has no `__dump`. Returns a description of the operation,
+    /// for its marker
+    fn old_vm_opcode_fuzz(
+        &mut self,
+        action: &Action,
+        arg_count: usize,
+    ) -> Result<String, Box<dyn Error>> {
+        for _ in 0..arg_count {
+            let value = self.select(OLD_VM_VALUES);
+            self.push(&value)?;
+        }
+        self.w.write_action(action)?;
+        // Every other SWF4 opcode in the table leaves a single result
+        if !matches!(action, Action::Pop | Action::Trace | Action::SetProperty) {
+            self.w.write_action(&Action::Trace)?;
+        }
+        Ok(format!("{:?}", action))
+    }
+
+    /// Returns a description of the operation, for its marker
+    pub fn opcode_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        // Before functions there is no `__dump`, results are traced directly
+        let old_vm = self.version < DUMP_MIN_VERSION;
+        if !old_vm {
+            self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        }
+
+        //TODO: ActionAdd produces errors in some cases
+        // todo: so does less
+        let (action, arg_count) = self.filter.select(
+            self.rng,
+            &[
+                (Action::Add, 2),
+                (Action::Add2, 2),
+                (Action::And, 2),
+                (Action::AsciiToChar, 1),
+                (Action::BitAnd, 2),
+                (Action::BitLShift, 2),
+                (Action::BitOr, 2),
+                (Action::BitRShift, 2),
+                (Action::BitURShift, 2),
+                (Action::BitXor, 2),
+                //_
+                (Action::CastOp, 2),
+                (Action::CharToAscii, 1),
+                //_
+                // TODO: constant pool
+                (Action::Decrement, 1),
+                //_
+                // TODO: divide
+                // (Action::Enumerate, 1),
+                /*(Action::Enumerate2, 1),*/
+                (Action::Equals, 2),
+                (Action::Equals2, 2),
+                (Action::GetProperty, 2),
+                (Action::Greater, 2),
+                // (Action::ImplementsOp, <this is handled by class_hierarchy_fuzz>),
+                (Action::Increment, 1),
+                // (Action::InitArray, <this is handled by array generation>),
+                // (Action::InitObject, <this is handled by object generation>),
+                (Action::InstanceOf, 2),
+                (Action::Less, 2),
+                (Action::Less2, 2),
+                (Action::MBAsciiToChar, 1),
+                (Action::MBCharToAscii, 1),
+                (Action::MBStringExtract, 3),
+                (Action::MBStringLength, 1),
+                (Action::Modulo, 2),   //TODO: doubles dont match
+                (Action::Multiply, 2), //TODO: doubles dont match
+                //_
+                (Action::Not, 1),
+                (Action::Or, 2),
+                //_
+                (Action::Pop, 1),
+                //_
+                (Action::PushDuplicate, 1),
+                //_
+                (Action::SetProperty, 3),
+                (Action::StackSwap, 2),
+                //_
+                (Action::StrictEquals, 2),
+                (Action::StringAdd, 2),
+                (Action::StringEquals, 2),
+                (Action::StringExtract, 3),
+                (Action::StringGreater, 2),
+                (Action::StringLength, 1),
+                (Action::StringLess, 2),
+                (Action::Subtract, 2), //TODO: doubles dont match
+                (Action::TargetPath, 1),
+                //_
+                (Action::ToInteger, 1),
+                (Action::ToNumber, 1),
+                (Action::ToString, 1),
+                // (Action::ToggleQuality, 0),
+                (Action::Trace, 1),
+                (Action::TypeOf, 1),
+                //_
+            ],
+            |filter, (action, _)| {
+                filter.allows_op(&format!("{:?}", action))
+                    && action_min_version(action) <= self.version
+            },
+        );
+
+        //TODO: rest of non-frame actions
+        //TODO: dump entire stack, not just top so we can check multi value actions like enumerate
+
+        if old_vm {
+            return self.old_vm_opcode_fuzz(&action, arg_count);
+        }
+        // The property opcodes need a target and an index rather than random values
+        if matches!(action, Action::GetProperty | Action::SetProperty) {
+            return self.property_opcode_fuzz(&action);
+        }
+        if matches!(
+            action,
+            Action::MBAsciiToChar
+                | Action::MBCharToAscii
+                | Action::MBStringExtract
+                | Action::MBStringLength
+                | Action::StringExtract
+                | Action::StringLength
+        ) && self.rng.gen_bool(MULTIBYTE_CORPUS_RATE)
+        {
+            return self.multibyte_opcode_fuzz(&action);
+        }
+
+        // Some arguments go through a register, or are read from one that was never written
+        let mut registers = Vec::new();
+        for _ in 0..arg_count {
+            let v = self.random_value_simple(0);
+            if !self.rng.gen_bool(REGISTER_ARG_RATE) {
+                self.push(&v)?;
+            } else if self.rng.gen_bool(UNSET_REGISTER_RATE) {
+                let register = self.push_unset_register()?;
+                registers.push(format!("unset r{}", register));
+            } else {
+                let register = self.push_through_register(&v)?;
+                registers.push(format!("r{}", register));
+            }
+        }
+        // Testing arithmetic ops
+        self.w.write_action(&action)?;
+
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        if registers.is_empty() {
+            Ok(format!("{:?}", action))
+        } else {
+            Ok(format!("{:?}({})", action, registers.join(", ")))
+        }
+    }
+
+    /// Define a function whose body dumps its params, `arguments.length` and the type of each of its registers, then
+    /// runs an opcode case and returns a random value. From SWF7 it is a DefineFunction2 half the time, with random
+    /// preload and suppress flags and register count, and params in registers half the time. The function is stored
+    /// in `FUZZ_FUNCTION_NAME` and called with up to `FUZZ_FUNCTION_EXTRA_ARGS` more args than it declares, and the
+    /// return value dumped. Returns a description of the operation, for its marker
+    pub fn function_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let param_count = self.rng.gen_range(0..=FUZZ_FUNCTION_PARAMS.len());
+        let params = &FUZZ_FUNCTION_PARAMS[..param_count];
+        let function2 = self.version >= DEFINE_FUNCTION2_MIN_VERSION && self.rng.gen();
+        let (register_count, flags, param_registers) = if function2 {
+            let register_count = self.rng.gen_range(0..=FUZZ_FUNCTION_MAX_REGISTERS);
+            let mut flags = FunctionFlags::empty();
+            for &flag in FUZZ_FUNCTION_FLAGS {
+                if self.rng.gen() {
+                    flags |= flag;
+                }
+            }
+            // These can land on a preloaded register, or past the register count
+            let param_registers: Vec<Option<NonZeroU8>> = params
+                .iter()
+                .map(|_| {
+                    if self.rng.gen() {
+                        NonZeroU8::new(self.rng.gen_range(1..=register_count.max(1)))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            (register_count, flags, param_registers)
+        } else {
+            (0, FunctionFlags::empty(), vec![None; params.len()])
+        };
+
+        let mut body = Vec::new();
+        let mut body_splits = Vec::new();
+        let opcode_description = {
+            let mut bg = self.body_generator(&mut body, &mut body_splits);
+            bg.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+            for (param, register) in params.iter().zip(&param_registers) {
+                match register {
+                    Some(register) => bg.push(&SimpleValue::Register(register.get()))?,
+                    None => {
+                        bg.push(&SimpleValue::String(Cow::Borrowed(param)))?;
+                        bg.w.write_action(&Action::GetVariable)?;
+                    }
+                }
+            }
+            bg.push(&SimpleValue::String(Cow::Borrowed("arguments")))?;
+            bg.w.write_action(&Action::GetVariable)?;
+            bg.push(&SimpleValue::String(Cow::Borrowed("length")))?;
+            bg.w.write_action(&Action::GetMember)?;
+            // Only the type, as the preloaded objects are too big to dump
+            for register in 1..=register_count {
+                bg.push(&SimpleValue::Register(register))?;
+                bg.w.write_action(&Action::TypeOf)?;
+            }
+            SwfGenerator::dump_stack(&mut bg.w)?;
+
+            let opcode_description = bg.opcode_fuzz()?;
+            let return_value = bg.random_value_simple(0);
+            bg.push(&return_value)?;
+            bg.w.write_action(&Action::Return)?;
+            opcode_description
+        };
+
+        // var __fn = function (<params>) { <body> };
+        self.push(&SimpleValue::String(Cow::Borrowed(FUZZ_FUNCTION_NAME)))?;
+        if function2 {
+            self.w
+                .write_action(&Action::DefineFunction2(DefineFunction2 {
+                    name: "".into(),
+                    register_count,
+                    params: params
+                        .iter()
+                        .zip(&param_registers)
+                        .map(|(param, register)| FunctionParam {
+                            name: SwfStr::from_utf8_str(param),
+                            register_index: *register,
+                        })
+                        .collect(),
+                    flags,
+                    actions: &body,
+                }))?;
+        } else {
+            self.w
+                .write_action(&Action::DefineFunction(DefineFunction {
+                    name: "".into(),
+                    params: params
+                        .iter()
+                        .map(|param| SwfStr::from_utf8_str(param))
+                        .collect(),
+                    actions: &body,
+                }))?;
+        }
+        self.w.write_action(&Action::DefineLocal)?;
+
+        // __fn(<args>), with the return value dumped
+        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+        let arg_count = self
+            .rng
+            .gen_range(0..=param_count + FUZZ_FUNCTION_EXTRA_ARGS);
+        for _ in 0..arg_count {
+            let arg = self.random_value_simple(0);
+            self.push(&arg)?;
+        }
+        self.w.write_action(&Action::Push(Push {
+            values: vec![
+                Value::Int(arg_count as i32),
+                Value::Str(FUZZ_FUNCTION_NAME.into()),
+            ],
+        }))?;
+        self.w.write_action(&Action::CallFunction)?;
+        SwfGenerator::dump_stack(&mut self.w)?;
+
+        Ok(if function2 {
+            format!(
+                "DefineFunction2({} params, {} args, {} registers, {:?}) running {}",
+                param_count, arg_count, register_count, flags, opcode_description
+            )
+        } else {
+            format!(
+                "DefineFunction({} params, {} args) running {}",
+                param_count, arg_count, opcode_description
+            )
+        })
+    }
+
+    /// Write a Try block whose body throws and whose catch block dumps what it caught, then trace the order the blocks
+    /// ran in. Returns a description of the operation, for its marker
+    pub fn try_catch_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let mut blocks = 0;
+        self.write_try(0, &mut blocks)
+    }
+
+    /// Trace `label` followed by the number of a block of a try/catch case
+    fn trace_block(&mut self, label: &str, block: u32) -> Result<(), Box<dyn Error>> {
+        self.w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(SwfStr::from_utf8_str(&format!(
+                "{} {}",
+                label, block
+            )))],
+        }))?;
+        self.w.write_action(&Action::Trace)?;
+        Ok(())
+    }
+
+    /// Write a Try block of a try/catch case at `depth`, numbering it and any nested in it from `blocks`. The body
+    /// throws a random value, the result of a CastOp, whatever is on the stack (nothing, as everything before it was
+    /// dumped) or nothing at all, after a nested Try block half the time. The catch, which the outermost block always
+    /// has, catches into a variable or a global register and dumps the value, and in a nested block may throw it on.
+    /// Returns a description of the block
+    fn write_try(&mut self, depth: u8, blocks: &mut u32) -> Result<String, Box<dyn Error>> {
+        let block = *blocks;
+        *blocks += 1;
+        let start = self.w.output.len();
+
+        // try { trace("try N"); <nested try>; throw <value>; trace("not thrown N"); }
+        self.trace_block("try", block)?;
+        let nested = if depth + 1 < TRY_MAX_DEPTH && self.rng.gen_bool(NESTED_TRY_RATE) {
+            Some(self.write_try(depth + 1, blocks)?)
+        } else {
+            None
+        };
+        let thrown = match self.rng.gen_range(0..4) {
+            0 => {
+                let value = self.random_value_simple(0);
+                self.push(&value)?;
+                self.w.write_action(&Action::Throw)?;
+                "value"
+            }
+            1 => {
+                // throw <value> cast to <class>
+                let class = self.select(TRY_CAST_CLASSES);
+                self.push(&SimpleValue::String(Cow::Borrowed(class)))?;
+                self.w.write_action(&Action::GetVariable)?;
+                let value = self.random_value_simple(0);
+                self.push(&value)?;
+                self.w.write_action(&Action::CastOp)?;
+                self.w.write_action(&Action::Throw)?;
+                "CastOp result"
+            }
+            2 => {
+                self.w.write_action(&Action::Throw)?;
+                "empty stack"
+            }
+            _ => "nothing",
+        };
+        self.trace_block("not thrown", block)?;
+        let try_body = self.w.output.split_off(start);
+
+        // Without a catch an outermost throw would end the case's actions
+        let (catch, finally) = match self.rng.gen_range(0..3) {
+            0 => (true, false),
+            1 if depth > 0 => (false, true),
+            _ => (true, true),
+        };
+
+        // catch (<var>) { __dump("#PREFIX#", "caught N", <var>); throw <var>; }
+        let catch_var = if self.rng.gen() {
+            CatchVar::Var(CAUGHT_ERROR_NAME.into())
+        } else {
+            let register = self.rng.gen_range(0..GLOBAL_REGISTERS);
+            CatchVar::Register(register)
+        };
+        let mut rethrow = false;
+        if catch {
+            self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+            self.push(&SimpleValue::String(Cow::Owned(format!(
+                "caught {}",
+                block
+            ))))?;
+            self.push_caught(&catch_var)?;
+            SwfGenerator::dump_stack(&mut self.w)?;
+            if depth > 0 && self.rng.gen_bool(RETHROW_RATE) {
+                self.push_caught(&catch_var)?;
+                self.w.write_action(&Action::Throw)?;
+                rethrow = true;
+            }
+        }
+        let catch_body = self.w.output.split_off(start);
+
+        // finally { trace("finally N"); }
+        if finally {
+            self.trace_block("finally", block)?;
+        }
+        let finally_body = self.w.output.split_off(start);
+
+        self.w.write_action(&Action::Try(Try {
+            try_body: &try_body,
+            catch_body: catch.then(|| (catch_var.clone(), catch_body.as_slice())),
+            finally_body: finally.then_some(finally_body.as_slice()),
+        }))?;
+        self.trace_block("after try", block)?;
+
+        let mut parts = Vec::new();
+        if catch {
+            parts.push(match catch_var {
+                CatchVar::Var(_) => format!("catch in {}", CAUGHT_ERROR_NAME),
+                CatchVar::Register(register) => {
+                    // It is only written if something is thrown, but may be read later as if it was
+                    self.written_registers[register as usize] = true;
+                    format!("catch in r{}", register)
+                }
+            });
+        }
+        if rethrow {
+            parts.push("rethrow".to_string());
+        }
+        if finally {
+            parts.push("finally".to_string());
+        }
+        Ok(match nested {
+            Some(nested) => format!(
+                "Try({}) throwing {} after {}",
+                parts.join(", "),
+                thrown,
+                nested
+            ),
+            None => format!("Try({}) throwing {}", parts.join(", "), thrown),
+        })
+    }
+
+    /// Push the value a catch block caught into `catch_var`
+    fn push_caught(&mut self, catch_var: &CatchVar) -> Result<(), Box<dyn Error>> {
+        match catch_var {
+            CatchVar::Var(name) => {
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str(name)],
+                }))?;
+                self.w.write_action(&Action::GetVariable)?;
+            }
+            CatchVar::Register(register) => self.push(&SimpleValue::Register(*register))?,
+        }
+        Ok(())
+    }
+
+    /// Write actions whose length, or the length of a function or try body in them, is one of `TAG_SIZE_TARGETS`.
+    /// The caller gives these their own DoAction tag, so a target is also the length of a tag
+    /// Returns a description of the operation, for its marker
+    pub fn tag_size_fuzz(&mut self) -> Result<String, Box<dyn Error>> {
+        let done = assemble(
+            self.version,
+            &[
+                Action::Push(Push {
+                    values: vec![Value::Str("tag size done".into())],
+                }),
+                Action::Trace,
+            ],
+        )?;
+
+        // Function and try bodies have u16 lengths, so only the sizes up to that make sense for them
+        let body_targets = TAG_SIZE_TARGETS
+            .iter()
+            .copied()
+            .filter(|target| *target <= u16::MAX as usize)
+            .collect::<Vec<_>>();
+        let shape = if self.version >= TRY_MIN_VERSION {
+            self.rng.gen_range(0..=2)
+        } else {
+            self.rng.gen_range(0..=1)
+        };
+        let description = match shape {
+            0 => {
+                let target = self.select(TAG_SIZE_TARGETS);
+                let actions = assemble_pad(self.version, target - done.len())?;
+                self.w.output.extend(actions);
+                self.w.output.extend_from_slice(&done);
+                format!("DoAction of {} bytes", target)
+            }
+            1 => {
+                // function __big() { return "tag size done"; }; trace(__big())
+                let target = self.select(&body_targets);
+                let ret = assemble(
+                    self.version,
+                    &[
+                        Action::Push(Push {
+                            values: vec![Value::Str("tag size done".into())],
+                        }),
+                        Action::Return,
+                    ],
+                )?;
+                let mut body = assemble_pad(self.version, target - ret.len())?;
+                body.extend(ret);
+                self.w
+                    .write_action(&Action::DefineFunction(DefineFunction {
+                        name: "__big".into(),
+                        params: vec![],
+                        actions: &body,
+                    }))?;
+                self.w.write_action(&Action::Push(Push {
+                    values: vec![Value::Int(0), Value::Str("__big".into())],
+                }))?;
+                self.w.write_action(&Action::CallFunction)?;
+                self.w.write_action(&Action::Trace)?;
+                format!("DefineFunction body of {} bytes", target)
+            }
+            2 => {
+                let target = self.select(&body_targets);
+                let mut try_body = assemble_pad(self.version, target - done.len())?;
+                try_body.extend_from_slice(&done);
+                self.w.write_action(&Action::Try(Try {
+                    try_body: &try_body,
+                    catch_body: None,
+                    finally_body: None,
+                }))?;
+                format!("Try body of {} bytes", target)
+            }
+            _ => unreachable!(),
+        };
+
+        Ok(description)
+    }
+}
+
+/// Depths used by placement fuzz cases, the depth seen by scripts is offset by -16384
+const PLACEMENT_DEPTHS: &[Depth] = &[1, 2, 3];
+
+/// Sprites defined for placement fuzz cases
+const PLACEMENT_CHARACTERS: &[CharacterId] = &[1, 2];
+
+/// A character id that is never defined, used to generate invalid placements
+const UNDEFINED_CHARACTER: CharacterId = 3;
+
+/// How many frames of display list changes each placement fuzz case has
+const PLACEMENT_FRAME_COUNT: usize = 4;
+
+/// Instance names given to placed clips
+const INSTANCE_NAME_CORPUS: &[&str] = &["a", "b", "clip", "a.b", ""];
+
+/// Properties of each placed clip that are traced after every frame
+const PLACEMENT_TRACE_PROPERTIES: &[&str] = &["_x", "_y", "_alpha", "_name", "blendMode"];
+
+const BLEND_MODES: &[BlendMode] = &[
+    BlendMode::Normal,
+    BlendMode::Layer,
+    BlendMode::Multiply,
+    BlendMode::Screen,
+    BlendMode::Lighten,
+    BlendMode::Darken,
+    BlendMode::Difference,
+    BlendMode::Add,
+    BlendMode::Subtract,
+    BlendMode::Invert,
+    BlendMode::Alpha,
+    BlendMode::Erase,
+    BlendMode::Overlay,
+    BlendMode::HardLight,
+];
+
+/// The display list changes of a placement fuzz case, along with the DoAction of each frame that traces the result
+pub struct PlacementFrames {
+    pub tags: Vec<Vec<Tag<'static>>>,
+    pub actions: Vec<Vec<u8>>,
+}
+
+pub struct PlacementGenerator<'c> {
+    rng: &'c mut StdRng,
+    version: u8,
+}
+
+impl<'c> PlacementGenerator<'c> {
+    /// Select a random value from a slice
+    fn select<T: Clone>(&mut self, options: &[T]) -> T {
+        let index = self.rng.gen_range(0..options.len());
+        options[index].clone()
+    }
+
+    /// Pick a character to place, this will sometimes be a character that doesn't exist
+    fn random_character(&mut self) -> CharacterId {
+        if self.rng.gen_bool(0.2) {
+            UNDEFINED_CHARACTER
+        } else {
+            self.select(PLACEMENT_CHARACTERS)
+        }
+    }
+
+    fn random_matrix(&mut self) -> Matrix {
+        Matrix {
+            a: Fixed16::from_f64(self.rng.gen_range(-2.0..2.0)),
+            b: Fixed16::from_f64(self.rng.gen_range(-1.0..1.0)),
+            c: Fixed16::from_f64(self.rng.gen_range(-1.0..1.0)),
+            d: Fixed16::from_f64(self.rng.gen_range(-2.0..2.0)),
+            tx: Twips::new(self.rng.gen_range(-4000..4000)),
+            ty: Twips::new(self.rng.gen_range(-4000..4000)),
+        }
+    }
+
+    fn random_color_transform(&mut self) -> ColorTransform {
+        ColorTransform {
+            r_multiply: Fixed8::from_f32(self.rng.gen_range(-1.0..2.0)),
+            g_multiply: Fixed8::from_f32(self.rng.gen_range(-1.0..2.0)),
+            b_multiply: Fixed8::from_f32(self.rng.gen_range(-1.0..2.0)),
+            a_multiply: Fixed8::from_f32(self.rng.gen_range(-1.0..2.0)),
+            r_add: self.rng.gen_range(-255..=255),
+            g_add: self.rng.gen_range(-255..=255),
+            b_add: self.rng.gen_range(-255..=255),
+            a_add: self.rng.gen_range(-255..=255),
+        }
+    }
+
+    fn random_color(&mut self) -> Color {
+        Color {
+            r: self.rng.gen(),
+            g: self.rng.gen(),
+            b: self.rng.gen(),
+            a: self.rng.gen(),
+        }
+    }
+
+    fn random_filter(&mut self) -> Filter {
+        let blur_x = Fixed16::from_f64(self.rng.gen_range(0.0..32.0));
+        let blur_y = Fixed16::from_f64(self.rng.gen_range(0.0..32.0));
+        let num_passes = self.rng.gen_range(0..=3);
+        match self.rng.gen_range(0..=2) {
+            0 => Filter::BlurFilter(Box::new(BlurFilter {
+                blur_x,
+                blur_y,
+                num_passes,
+            })),
+            1 => Filter::DropShadowFilter(Box::new(DropShadowFilter {
+                color: self.random_color(),
+                blur_x,
+                blur_y,
+                angle: Fixed16::from_f64(self.rng.gen_range(-7.0..7.0)),
+                distance: Fixed16::from_f64(self.rng.gen_range(-10.0..10.0)),
+                strength: Fixed8::from_f32(self.rng.gen_range(0.0..2.0)),
+                is_inner: self.rng.gen(),
+                is_knockout: self.rng.gen(),
+                num_passes,
+            })),
+            2 => Filter::GlowFilter(Box::new(GlowFilter {
+                color: self.random_color(),
+                blur_x,
+                blur_y,
+                strength: Fixed8::from_f32(self.rng.gen_range(0.0..2.0)),
+                is_inner: self.rng.gen(),
+                is_knockout: self.rng.gen(),
+                num_passes,
+            })),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Generate a random PlaceObject2, or PlaceObject3 for SWFv8+
+    /// Place/Replace/Modify are picked without regard for what is at the depth, so placing over an
+    /// existing clip or modifying an empty depth will happen regularly
+    fn random_place_object(&mut self) -> PlaceObject<'static> {
+        let version = if self.version >= 8 && self.rng.gen_bool(0.5) {
+            3
+        } else {
+            2
+        };
+        let action = match self.rng.gen_range(0..=2) {
+            0 => PlaceObjectAction::Place(self.random_character()),
+            1 => PlaceObjectAction::Modify,
+            2 => PlaceObjectAction::Replace(self.random_character()),
+            _ => unreachable!(),
+        };
+        let depth = self.select(PLACEMENT_DEPTHS);
+        let matrix = self.rng.gen_bool(0.5).then(|| self.random_matrix());
+        let color_transform = self
+            .rng
+            .gen_bool(0.5)
+            .then(|| self.random_color_transform());
+        let ratio = self.rng.gen_bool(0.2).then(|| self.rng.gen());
+        let name = self
+            .rng
+            .gen_bool(0.5)
+            .then(|| SwfStr::from_utf8_str(self.select(INSTANCE_NAME_CORPUS)));
+        let clip_depth = self
+            .rng
+            .gen_bool(0.2)
+            .then(|| self.rng.gen_range(0..=PLACEMENT_DEPTHS.len() as Depth + 1));
+
+        let (filters, blend_mode, is_bitmap_cached) = if version == 3 {
+            let filters = self.rng.gen_bool(0.3).then(|| {
+                (0..self.rng.gen_range(0..=2))
+                    .map(|_| self.random_filter())
+                    .collect()
+            });
+            let blend_mode = self.rng.gen_bool(0.5).then(|| self.select(BLEND_MODES));
+            let is_bitmap_cached = self.rng.gen_bool(0.2).then(|| self.rng.gen());
+            (filters, blend_mode, is_bitmap_cached)
+        } else {
+            (None, None, None)
+        };
+
+        PlaceObject {
+            version,
+            action,
+            depth,
+            matrix,
+            color_transform,
+            ratio,
+            name,
+            clip_depth,
+            class_name: None,
+            filters,
+            background_color: None,
+            blend_mode,
+            clip_actions: None,
+            has_image: false,
+            is_bitmap_cached,
+            is_visible: None,
+            amf_data: None,
+        }
+    }
+
+    /// Generate a random RemoveObject, RemoveObject (v1) names a character that may not be the one at the depth
+    fn random_remove_object(&mut self) -> RemoveObject {
+        let character_id = self.rng.gen_bool(0.3).then(|| self.random_character());
+        RemoveObject {
+            depth: self.select(PLACEMENT_DEPTHS),
+            character_id,
+        }
+    }
+
+    /// The sprites that placement fuzz cases place, each is an empty single frame clip
+    pub fn define_sprites(&self) -> Vec<Tag<'static>> {
+        PLACEMENT_CHARACTERS
+            .iter()
+            .map(|&id| {
+                Tag::DefineSprite(Sprite {
+                    id,
+                    num_frames: 1,
+                    tags: vec![Tag::ShowFrame],
+                })
+            })
+            .collect()
+    }
+
+    /// Generate several frames of random display list changes, each traced by a DoAction in the same frame
+    pub fn frames(&mut self) -> Result<PlacementFrames, Box<dyn Error>> {
+        let mut frames = PlacementFrames {
+            tags: Vec::with_capacity(PLACEMENT_FRAME_COUNT),
+            actions: Vec::with_capacity(PLACEMENT_FRAME_COUNT),
+        };
+
+        for _ in 0..PLACEMENT_FRAME_COUNT {
+            let tags = (0..self.rng.gen_range(1..=3))
+                .map(|_| {
+                    if self.rng.gen_bool(0.8) {
+                        Tag::PlaceObject(Box::new(self.random_place_object()))
+                    } else {
+                        Tag::RemoveObject(self.random_remove_object())
+                    }
+                })
+                .collect();
+
+            let mut actions = Vec::new();
+            SwfGenerator::trace_placement(&mut Writer::new(&mut actions, self.version))?;
+
+            frames.tags.push(tags);
+            frames.actions.push(actions);
+        }
+
+        Ok(frames)
+    }
+}
+
+/// How many frames input events are delivered over in an input fuzz case
+const INPUT_FRAME_COUNT: usize = 4;
+
+/// Frame rate of input fuzz cases, slow enough that input sent to the projector window lands in the intended frame
+pub const INPUT_FRAME_RATE: f32 = 4.0;
+
+/// The most input events delivered before each frame
+const INPUT_EVENTS_PER_FRAME: usize = 3;
+
+/// The most listener objects registered in an input fuzz case
+const INPUT_LISTENER_COUNT: usize = 3;
+
+/// Mouse positions given to input events, slightly larger than ruffle's viewport so some land outside the stage
+const INPUT_MOUSE_RANGE: RangeInclusive<i32> = -4..=36;
+
+/// Handlers that can be defined on a listener, along with the broadcaster that calls them
+const INPUT_HANDLERS: &[(&str, &str)] = &[
+    ("Key", "onKeyDown"),
+    ("Key", "onKeyUp"),
+    ("Mouse", "onMouseDown"),
+    ("Mouse", "onMouseUp"),
+    ("Mouse", "onMouseMove"),
+];
+
+/// The listener setup and per-frame actions of an input fuzz case, along with the input to deliver while it runs
+pub struct InputCase {
+    /// Creates and registers the listeners, runs at the start of the main actions
+    pub setup: Vec<u8>,
+    /// The DoAction of each frame that traces the key state
+    pub actions: Vec<Vec<u8>>,
+    pub script: InputScript,
+}
+
+pub struct InputGenerator<'c> {
+    rng: &'c mut StdRng,
+    version: u8,
+}
+
+impl<'c> InputGenerator<'c> {
+    fn random_key(&mut self) -> u8 {
+        INPUT_KEYS[self.rng.gen_range(0..INPUT_KEYS.len())].0
+    }
+
+    /// Generate the input for a case, keys are usually released after being pressed but can be held
+    /// across frames, released without being pressed or pressed again while down
+    fn script(&mut self) -> InputScript {
+        let mut script = InputScript::default();
+        let mut held_keys = Vec::new();
+        for frame in 1..=INPUT_FRAME_COUNT {
+            for _ in 0..self.rng.gen_range(0..=INPUT_EVENTS_PER_FRAME) {
+                let x = self.rng.gen_range(INPUT_MOUSE_RANGE);
+                let y = self.rng.gen_range(INPUT_MOUSE_RANGE);
+                let event = match self.rng.gen_range(0..=4) {
+                    0 => {
+                        let key = self.random_key();
+                        held_keys.push(key);
+                        InputEvent::KeyDown(key)
+                    }
+                    1 if !held_keys.is_empty() && self.rng.gen_bool(0.8) => {
+                        InputEvent::KeyUp(held_keys.remove(self.rng.gen_range(0..held_keys.len())))
+                    }
+                    1 => InputEvent::KeyUp(self.random_key()),
+                    2 => InputEvent::MouseMove { x, y },
+                    3 => InputEvent::MouseDown { x, y },
+                    4 => InputEvent::MouseUp { x, y },
+                    _ => unreachable!(),
+                };
+                script.push(frame, event);
+            }
+        }
+        script
+    }
+
+    /// Assemble a handler that traces its listener, its name and whether `this` is the listener, followed
+    /// by the current key for key handlers. Some handlers remove their listener while the event is being broadcast
+    fn handler(
+        &mut self,
+        listener: &str,
+        broadcaster: &str,
+        name: &str,
+    ) -> std::io::Result<Vec<u8>> {
+        fn push_str(s: &str) -> Action<'_> {
+            Action::Push(Push {
+                values: vec![Value::Str(SwfStr::from_utf8_str(s))],
+            })
+        }
+        // Key.<method>()
+        fn call_key(method: &str) -> [Action<'_>; 4] {
+            [
+                Action::Push(Push {
+                    values: vec![Value::Int(0), Value::Str("Key".into())],
+                }),
+                Action::GetVariable,
+                push_str(method),
+                Action::CallMethod,
+            ]
+        }
+
+        let label = format!("{}.{} this=", listener, name);
+        let mut actions = vec![
+            push_str(&label),
+            push_str("this"),
+            Action::GetVariable,
+            push_str(listener),
+            Action::GetVariable,
+            Action::StrictEquals,
+            Action::Add2,
+        ];
+        if broadcaster == "Key" {
+            actions.push(push_str(" code="));
+            actions.push(Action::Add2);
+            actions.extend(call_key("getCode"));
+            actions.push(Action::Add2);
+            actions.push(push_str(" ascii="));
+            actions.push(Action::Add2);
+            actions.extend(call_key("getAscii"));
+            actions.push(Action::Add2);
+            // Key.isDown(Key.getCode())
+            actions.push(push_str(" down="));
+            actions.push(Action::Add2);
+            actions.extend(call_key("getCode"));
+            actions.extend([
+                Action::Push(Push {
+                    values: vec![Value::Int(1), Value::Str("Key".into())],
+                }),
+                Action::GetVariable,
+                push_str("isDown"),
+                Action::CallMethod,
+                Action::Add2,
+            ]);
+        }
+        actions.push(Action::Trace);
+
+        if self.rng.gen_bool(0.2) {
+            // <broadcaster>.removeListener(this)
+            actions.extend([
+                push_str("this"),
+                Action::GetVariable,
+                Action::Push(Push {
+                    values: vec![
+                        Value::Int(1),
+                        Value::Str(SwfStr::from_utf8_str(broadcaster)),
+                    ],
+                }),
+                Action::GetVariable,
+                push_str("removeListener"),
+                Action::CallMethod,
+                Action::Pop,
+            ]);
+        }
+
+        assemble(self.version, &actions)
+    }
+
+    /// Assemble the creation of the listeners, each gets a random subset of handlers and is registered with
+    /// Key and Mouse at random, sometimes twice
+    fn listeners(&mut self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut out = Vec::new();
+        let mut w = Writer::new(&mut out, self.version);
+        for index in 0..self.rng.gen_range(1..=INPUT_LISTENER_COUNT) {
+            let listener = format!("__l{}", index);
+            let listener_str = Value::Str(SwfStr::from_utf8_str(&listener));
+
+            // __lN = new Object()
+            w.write_action(&Action::Push(Push {
+                values: vec![
+                    listener_str.clone(),
+                    Value::Int(0),
+                    Value::Str("Object".into()),
+                ],
+            }))?;
+            w.write_action(&Action::NewObject)?;
+            w.write_action(&Action::SetVariable)?;
+
+            for &(broadcaster, name) in INPUT_HANDLERS {
+                if !self.rng.gen_bool(0.8) {
+                    continue;
+                }
+                let body = self.handler(&listener, broadcaster, name)?;
+                // __lN.<name> = function() { ... }
+                w.write_action(&Action::Push(Push {
+                    values: vec![listener_str.clone()],
+                }))?;
+                w.write_action(&Action::GetVariable)?;
+                w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str(SwfStr::from_utf8_str(name))],
+                }))?;
+                w.write_action(&Action::DefineFunction(DefineFunction {
+                    name: "".into(),
+                    params: vec![],
+                    actions: &body,
+                }))?;
+                w.write_action(&Action::SetMember)?;
+            }
+
+            for broadcaster in ["Key", "Mouse"] {
+                let registrations = match self.rng.gen_range(0..10) {
+                    0..=1 => 0,
+                    2..=8 => 1,
+                    _ => 2,
+                };
+                for _ in 0..registrations {
+                    // <broadcaster>.addListener(__lN)
+                    w.write_action(&Action::Push(Push {
+                        values: vec![listener_str.clone()],
+                    }))?;
+                    w.write_action(&Action::GetVariable)?;
+                    w.write_action(&Action::Push(Push {
+                        values: vec![Value::Int(1), Value::Str(broadcaster.into())],
+                    }))?;
+                    w.write_action(&Action::GetVariable)?;
+                    w.write_action(&Action::Push(Push {
+                        values: vec![Value::Str("addListener".into())],
+                    }))?;
+                    w.write_action(&Action::CallMethod)?;
+                    w.write_action(&Action::Pop)?;
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Assemble `trace("keys=" + Key.isDown(k) + ... + " focus=" + Selection.getFocus())` for every key in `INPUT_KEYS`
+    fn trace_key_state(&self) -> std::io::Result<Vec<u8>> {
+        let mut actions = vec![Action::Push(Push {
+            values: vec![Value::Str("keys=".into())],
+        })];
+        for &(key_code, _, _) in INPUT_KEYS {
+            actions.extend([
+                Action::Push(Push {
+                    values: vec![
+                        Value::Int(key_code.into()),
+                        Value::Int(1),
+                        Value::Str("Key".into()),
+                    ],
+                }),
+                Action::GetVariable,
+                Action::Push(Push {
+                    values: vec![Value::Str("isDown".into())],
+                }),
+                Action::CallMethod,
+                Action::Push(Push {
+                    values: vec![Value::Str(",".into())],
+                }),
+                Action::Add2,
+                Action::Add2,
+            ]);
+        }
+        actions.extend([
+            Action::Push(Push {
+                values: vec![
+                    Value::Str(" focus=".into()),
+                    Value::Int(0),
+                    Value::Str("Selection".into()),
+                ],
+            }),
+            Action::GetVariable,
+            Action::Push(Push {
+                values: vec![Value::Str("getFocus".into())],
+            }),
+            Action::CallMethod,
+            Action::Add2,
+            Action::Add2,
+            Action::Trace,
+        ]);
+        assemble(self.version, &actions)
+    }
+
+    pub fn case(&mut self) -> Result<InputCase, Box<dyn Error>> {
+        let setup = self.listeners()?;
+        let actions = (0..INPUT_FRAME_COUNT)
+            .map(|_| self.trace_key_state())
+            .collect::<std::io::Result<_>>()?;
+        Ok(InputCase {
+            setup,
+            actions,
+            script: self.script(),
+        })
+    }
+}
+
+/// The most data in the filler tag that a tag declaring too much data swallows
+const MALFORMED_FILLER_MAX_LEN: usize = 8;
+
+/// A tag code that no player knows, for the filler tag
+const MALFORMED_FILLER_TAG_CODE: u16 = 1000;
+
+/// Sound formats (the top 4 bits of a sound format byte) that don't exist
+const INVALID_SOUND_FORMATS: &[u8] = &[7, 8, 9, 10, 12, 13, 14];
+
+/// How deep sprites are nested in a malformed container case, the authoring tools never nest definitions at all
+const SPRITE_NESTING_DEPTHS: &[u16] = &[2, 8, 32, 128];
+
+/// The id of the innermost nested sprite, each sprite around it takes the next id
+const NESTED_SPRITE_ID: CharacterId = 100;
+
+/// The ways a malformed container case can break the format
+#[derive(Debug, Clone, Copy)]
+enum Malformation {
+    /// A tag declares more data than it has, swallowing a filler tag after it
+    LengthOver,
+    /// A tag declares less data than it has, the rest of its data is read as the tags that follow
+    LengthUnder,
+    /// A tag's data is cut short, and its length declares only what is left
+    Truncated,
+    /// A SoundStreamHead2 with a sound format that doesn't exist
+    InvalidSoundFormat,
+    /// Sprites defined inside sprites, see `SPRITE_NESTING_DEPTHS`
+    NestedSprites,
+}
+
+const MALFORMATIONS: &[Malformation] = &[
+    Malformation::LengthOver,
+    Malformation::LengthUnder,
+    Malformation::Truncated,
+    Malformation::InvalidSoundFormat,
+    Malformation::NestedSprites,
+];
+
+/// A malformed tag, written after the main actions of a case
+pub struct MalformedCase {
+    pub description: String,
+    /// The malformed tag along with any tags it relies on, such as the filler tag
+    pub tag_data: Vec<u8>,
+}
+
+pub struct MalformedGenerator<'c> {
+    rng: &'c mut StdRng,
+    version: u8,
+}
+
+impl<'c> MalformedGenerator<'c> {
+    fn select<T: Clone>(&mut self, options: &[T]) -> T {
+        options[self.rng.gen_range(0..options.len())].clone()
+    }
+
+    /// Actions that trace that they ran, for the DoAction tags inside malformed tags
+    fn trace_ran(&self) -> std::io::Result<Vec<u8>> {
+        assemble(
+            self.version,
+            &[
+                Action::Push(Push {
+                    values: vec![Value::Str("malformed tag actions ran".into())],
+                }),
+                Action::Trace,
+            ],
+        )
+    }
+
+    /// A well-formed tag with a few bytes of data for a malformation to be applied to, returns its code and data
+    fn random_tag(&mut self) -> Result<(TagCode, Vec<u8>), Box<dyn Error>> {
+        let trace = self.trace_ran()?;
+        let (tag_code, tag) = match self.rng.gen_range(0..4) {
+            0 => (
+                TagCode::SetBackgroundColor,
+                Tag::SetBackgroundColor(Color {
+                    r: self.rng.gen(),
+                    g: self.rng.gen(),
+                    b: self.rng.gen(),
+                    a: 255,
+                }),
+            ),
+            1 => (
+                TagCode::FrameLabel,
+                Tag::FrameLabel(FrameLabel {
+                    label: SwfStr::from_utf8_str(self.select(FRAME_LABEL_CORPUS)),
+                    is_anchor: self.rng.gen(),
+                }),
+            ),
+            2 => (
+                TagCode::RemoveObject2,
+                Tag::RemoveObject(RemoveObject {
+                    depth: self.select(PLACEMENT_DEPTHS),
+                    character_id: None,
+                }),
+            ),
+            _ => (TagCode::DoAction, Tag::DoAction(&trace)),
+        };
+        let mut tag_data = Vec::new();
+        raw::write_tag(&mut tag_data, &tag, self.version)?;
+        let mut reader = swf::read::Reader::new(&tag_data, self.version);
+        reader.read_tag_code_and_length()?;
+        Ok((tag_code, reader.get_ref().to_vec()))
+    }
+
+    /// A DefineSprite holding a DefineSprite, `depth` deep, the innermost one traces when its actions run
+    fn nested_sprites(&mut self, depth: u16) -> Result<Vec<u8>, Box<dyn Error>> {
+        let trace = self.trace_ran()?;
+        let mut sprite_tags = Vec::new();
+        raw::write_tag(&mut sprite_tags, &Tag::DoAction(&trace), self.version)?;
+        for level in 0..depth {
+            let mut body = Vec::new();
+            body.extend_from_slice(&(NESTED_SPRITE_ID + level).to_le_bytes());
+            body.extend_from_slice(&1u16.to_le_bytes());
+            body.extend_from_slice(&sprite_tags);
+            raw::write_tag(&mut body, &Tag::ShowFrame, self.version)?;
+            raw::write_tag(&mut body, &Tag::End, self.version)?;
+            sprite_tags.clear();
+            raw::write_tag_raw(&mut sprite_tags, TagCode::DefineSprite as u16, &body, None)?;
+        }
+        Ok(sprite_tags)
+    }
+
+    pub fn case(&mut self) -> Result<MalformedCase, Box<dyn Error>> {
+        let mut tag_data = Vec::new();
+        let description = match self.select(MALFORMATIONS) {
+            Malformation::LengthOver => {
+                let (tag_code, body) = self.random_tag()?;
+                let filler = vec![0; self.rng.gen_range(0..=MALFORMED_FILLER_MAX_LEN)];
+                let mut filler_tag = Vec::new();
+                raw::write_tag_raw(&mut filler_tag, MALFORMED_FILLER_TAG_CODE, &filler, None)?;
+                let declared_len = body.len() + filler_tag.len();
+                raw::write_tag_raw(
+                    &mut tag_data,
+                    tag_code as u16,
+                    &body,
+                    Some(declared_len as u32),
+                )?;
+                tag_data.extend_from_slice(&filler_tag);
+                format!(
+                    "{:?} declares {} bytes, {} more than it has",
+                    tag_code,
+                    declared_len,
+                    filler_tag.len()
+                )
+            }
+            Malformation::LengthUnder => {
+                let (tag_code, body) = self.random_tag()?;
+                let declared_len = self.rng.gen_range(0..body.len());
+                raw::write_tag_raw(
+                    &mut tag_data,
+                    tag_code as u16,
+                    &body,
+                    Some(declared_len as u32),
+                )?;
+                format!(
+                    "{:?} declares {} of its {} bytes",
+                    tag_code,
+                    declared_len,
+                    body.len()
+                )
+            }
+            Malformation::Truncated => {
+                let (tag_code, body) = self.random_tag()?;
+                let len = self.rng.gen_range(0..body.len());
+                raw::write_tag_raw(&mut tag_data, tag_code as u16, &body[..len], None)?;
+                format!("{:?} cut to {} of its {} bytes", tag_code, len, body.len())
+            }
+            Malformation::InvalidSoundFormat => {
+                let format = self.select(INVALID_SOUND_FORMATS);
+                // 44kHz 16 bit stereo, 576 samples per frame
+                let body = [0b0000_1111, format << 4 | 0b1111, 0x40, 0x02];
+                raw::write_tag_raw(&mut tag_data, TagCode::SoundStreamHead2 as u16, &body, None)?;
+                format!("SoundStreamHead2 with sound format {}", format)
+            }
+            Malformation::NestedSprites => {
+                let depth = self.select(SPRITE_NESTING_DEPTHS);
+                tag_data = self.nested_sprites(depth)?;
+                format!("DefineSprite nested {} deep", depth)
+            }
+        };
+        Ok(MalformedCase {
+            description: format!("malformed container: {}", description),
+            tag_data,
+        })
+    }
+}
+
+/// Sizes of the DefineBinaryData tag in a binary data case: empty, either side of the 63 byte short tag header limit
+/// and of 64KiB, and several MiB
+const BINARY_DATA_SIZES: &[usize] = &[0, 1, 62, 63, 64, 65535, 65536, 3 * 1024 * 1024];
+
+/// The id of the DefineBinaryData tag in a binary data case
+const BINARY_DATA_ID: CharacterId = 200;
+
+/// Linkage names that binary data is exported and attached as, the attach can use another name than the export
+const BINARY_DATA_LINKAGE_NAMES: &[&str] = &["binaryData", "BinaryData", "", "日本語"];
+
+/// The depth binary data is attached at
+const BINARY_DATA_DEPTH: i32 = 1000;
+
+/// The embedded data of a binary data case, along with the actions that look at it
+pub struct BinaryDataCase {
+    pub description: String,
+    pub data: Vec<u8>,
+    /// The linkage name of the data, it isn't exported if None
+    pub export_name: Option<&'static str>,
+    /// Attaches the data and traces what the root and attached clips report about their size, runs after the main actions
+    pub actions: Vec<u8>,
+}
+
+pub struct BinaryDataGenerator<'c> {
+    rng: &'c mut StdRng,
+    version: u8,
+}
+
+impl<'c> BinaryDataGenerator<'c> {
+    fn select<T: Clone>(&mut self, options: &[T]) -> T {
+        options[self.rng.gen_range(0..options.len())].clone()
+    }
+
+    /// Data of `len` bytes, returns it along with a description of what it holds
+    fn data(&mut self, len: usize) -> (Vec<u8>, &'static str) {
+        match self.rng.gen_range(0..3) {
+            0 => (vec![0; len], "zeroed"),
+            1 => {
+                // Looks like an uncompressed swf of the same length, in case either player tries to load it
+                let mut data = vec![0; len];
+                self.rng.fill(&mut data[..]);
+                let mut header = b"FWS".to_vec();
+                header.push(self.version);
+                header.extend_from_slice(&(len as u32).to_le_bytes());
+                let header_len = header.len().min(len);
+                data[..header_len].copy_from_slice(&header[..header_len]);
+                (data, "swf header")
+            }
+            _ => {
+                let mut data = vec![0; len];
+                self.rng.fill(&mut data[..]);
+                (data, "random")
+            }
+        }
+    }
+
+    /// Assemble `__b = _root.attachMovie(attach_name, "__binary", depth)`, then trace the bytes loaded and total of
+    /// the root clip, of `__b` and of the clip at the depth it was attached at
+    fn attach_and_trace(&self, attach_name: &str) -> std::io::Result<Vec<u8>> {
+        fn push_str(s: &str) -> Action<'_> {
+            Action::Push(Push {
+                values: vec![Value::Str(SwfStr::from_utf8_str(s))],
+            })
+        }
+        // <label> + typeof <clip> + " loaded=" + <clip>.getBytesLoaded() + " total=" + <clip>.getBytesTotal(),
+        // `get_clip` leaves the clip on the stack
+        fn trace_clip<'a>(label: &'a str, get_clip: &[Action<'a>]) -> Vec<Action<'a>> {
+            let mut actions = vec![push_str(label)];
+            actions.extend_from_slice(get_clip);
+            actions.extend([Action::TypeOf, Action::Add2]);
+            for (name, method) in [(" loaded=", "getBytesLoaded"), (" total=", "getBytesTotal")] {
+                actions.extend([push_str(name), Action::Add2]);
+                actions.push(Action::Push(Push {
+                    values: vec![Value::Int(0)],
+                }));
+                actions.extend_from_slice(get_clip);
+                actions.extend([push_str(method), Action::CallMethod, Action::Add2]);
+            }
+            actions.push(Action::Trace);
+            actions
+        }
+
+        let get_root = [push_str("_root"), Action::GetVariable];
+        let get_attached = [push_str("__b"), Action::GetVariable];
+        // _root.getInstanceAtDepth(depth - 16384)
+        let get_at_depth = [
+            Action::Push(Push {
+                values: vec![
+                    Value::Int(BINARY_DATA_DEPTH - 16384),
+                    Value::Int(1),
+                    Value::Str("_root".into()),
+                ],
+            }),
+            Action::GetVariable,
+            push_str("getInstanceAtDepth"),
+            Action::CallMethod,
+        ];
+
+        let mut actions = trace_clip("root=", &get_root);
+        actions.extend([
+            Action::Push(Push {
+                values: vec![
+                    Value::Str("__b".into()),
+                    Value::Int(BINARY_DATA_DEPTH),
+                    Value::Str("__binary".into()),
+                    Value::Str(SwfStr::from_utf8_str(attach_name)),
+                    Value::Int(3),
+                    Value::Str("_root".into()),
+                ],
+            }),
+            Action::GetVariable,
+            push_str("attachMovie"),
+            Action::CallMethod,
+            Action::SetVariable,
+        ]);
+        actions.extend(trace_clip("attached=", &get_attached));
+        actions.extend(trace_clip("at depth=", &get_at_depth));
+        assemble(self.version, &actions)
+    }
+
+    pub fn case(&mut self) -> Result<BinaryDataCase, Box<dyn Error>> {
+        let len = self.select(BINARY_DATA_SIZES);
+        let (data, contents) = self.data(len);
+        let export_name = self
+            .rng
+            .gen_bool(0.8)
+            .then(|| self.select(BINARY_DATA_LINKAGE_NAMES));
+        let attach_name = match export_name {
+            Some(export_name) if self.rng.gen_bool(0.7) => export_name,
+            _ => self.select(BINARY_DATA_LINKAGE_NAMES),
+        };
+        let exported = match export_name {
+            Some(export_name) => format!("exported as {:?}", export_name),
+            None => "not exported".to_string(),
+        };
+        Ok(BinaryDataCase {
+            description: format!(
+                "binary data: {} bytes {}, {}, attached as {:?}",
+                len, contents, exported, attach_name
+            ),
+            data,
+            export_name,
+            actions: self.attach_and_trace(attach_name)?,
+        })
+    }
+}
+
+/// The id of the sprite whose frames are labeled in frame label cases
+const LABEL_SPRITE_ID: CharacterId = 300;
+
+/// The depth and instance name the labeled sprite is placed at
+const LABEL_SPRITE_DEPTH: Depth = 2000;
+const LABEL_SPRITE_NAME: &str = "__labels";
+
+/// How many frames the labeled sprite has
+const LABEL_SPRITE_FRAMES: usize = 5;
+
+/// Labels given to the labeled sprite's frames and navigated to. Each frame picks up to 2, so they are often
+/// duplicated, and some look like frame numbers, only differ in case or are empty
+const FRAME_LABEL_NAMES: &[&str] = &["label", "Label", "1", "3", "10", "", "frame 2", "日本語"];
+
+/// Navigated to but never given to a frame, a missing label and a label qualified with the sprite's path
+const UNLABELED_FRAME_TARGETS: &[&str] = &["missing", "/__labels:label"];
+
+/// The labels of each frame of the labeled sprite, both its FrameLabel tags and the descriptions of the frame label
+/// sub-cases come from this
+pub struct FrameLabelLayout {
+    /// The labels of each frame, and whether each is an anchor
+    pub frames: Vec<Vec<(&'static str, bool)>>,
+    /// Traces the frame's number, the first frame also stops the sprite
+    frame_actions: Vec<Vec<u8>>,
+}
+
+impl FrameLabelLayout {
+    /// The frames that have `label`, e.g. `[1, 3 (anchor)]`
+    pub fn frames_with(&self, label: &str) -> String {
+        let frames = self
+            .frames
+            .iter()
+            .enumerate()
+            .flat_map(|(frame, labels)| {
+                labels
+                    .iter()
+                    .filter(move |(frame_label, _)| *frame_label == label)
+                    .map(move |(_, is_anchor)| {
+                        if *is_anchor {
+                            format!("{} (anchor)", frame + 1)
+                        } else {
+                            (frame + 1).to_string()
+                        }
+                    })
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", frames.join(", "))
+    }
+
+    pub fn define_sprite(&self) -> Tag<'_> {
+        let mut tags = Vec::new();
+        for (labels, actions) in self.frames.iter().zip(&self.frame_actions) {
+            for &(label, is_anchor) in labels {
+                tags.push(Tag::FrameLabel(FrameLabel {
+                    label: SwfStr::from_utf8_str(label),
+                    is_anchor,
+                }));
+            }
+            tags.push(Tag::DoAction(actions));
+            tags.push(Tag::ShowFrame);
+        }
+        Tag::DefineSprite(Sprite {
+            id: LABEL_SPRITE_ID,
+            num_frames: self.frames.len() as u16,
+            tags,
+        })
+    }
+
+    /// Place the sprite on the root's first frame, named so the main actions can target it
+    pub fn place_sprite(&self) -> Tag<'static> {
+        Tag::PlaceObject(Box::new(
+            PlaceObject::new_place(LABEL_SPRITE_ID, LABEL_SPRITE_DEPTH)
+                .with_name(SwfStr::from_utf8_str(LABEL_SPRITE_NAME)),
+        ))
+    }
+}
+
+/// How many frames multi-frame cases have after the first, not counting the frame that wraps back round
+const MULTI_FRAME_MAX_FRAMES: usize = 5;
+
+/// How many times the frames of a multi-frame case navigate in all, so frames that jump to each other can't loop
+/// forever within a frame
+const MULTI_FRAME_MAX_JUMPS: i32 = 8;
+
+/// The variables multi-frame cases count their navigations and entered frames in
+const MULTI_FRAME_JUMPS_VARIABLE: &str = "__mf_jumps";
+const MULTI_FRAME_TICKS_VARIABLE: &str = "__mf_ticks";
+
+/// The frames a multi-frame case runs for before its `onEnterFrame` completes it, whichever frame it is on, stopped
+/// or not
+const MULTI_FRAME_TICKS: i32 = 12;
+
+/// The frames of a multi-frame case, the root frames after the one the main actions are on. Each traces its number,
+/// navigates with one of the frame actions and traces that it finished, the last one only wraps back round to the
+/// second frame so that the main actions only run again when a frame navigates to them
+pub struct MultiFrameCase {
+    pub description: String,
+    /// The labels of each frame, and whether each is an anchor
+    frames: Vec<Vec<(&'static str, bool)>>,
+    frame_actions: Vec<Vec<u8>>,
+}
+
+impl MultiFrameCase {
+    /// The tags of the frames after the first, each ending with a ShowFrame
+    pub fn frame_tags(&self) -> Vec<Tag<'_>> {
+        let mut tags = Vec::new();
+        for (labels, actions) in self.frames.iter().zip(&self.frame_actions) {
+            for &(label, is_anchor) in labels {
+                tags.push(Tag::FrameLabel(FrameLabel {
+                    label: SwfStr::from_utf8_str(label),
+                    is_anchor,
+                }));
+            }
+            tags.push(Tag::DoAction(actions));
+            tags.push(Tag::ShowFrame);
+        }
+        tags
+    }
+
+    /// How many frames the case's swf has, counting the first
+    pub fn num_frames(&self) -> u16 {
+        self.frames.len() as u16 + 1
+    }
+}
+
+/// Assemble the setup of a multi-frame case, which runs after the main actions. It starts the counters and sets
+/// `_root.onEnterFrame` to run `complete_actions` once `MULTI_FRAME_TICKS` frames have been entered, so the case
+/// completes however its frames navigate. The main actions run again if a frame navigates back to the first, so the
+/// setup only runs once
+fn assemble_multi_frame_setup(version: u8, complete_actions: &[u8]) -> std::io::Result<Vec<u8>> {
+    fn push_str(s: &str) -> Action<'_> {
+        Action::Push(Push {
+            values: vec![Value::Str(SwfStr::from_utf8_str(s))],
+        })
+    }
+    let get_ticks = [push_str(MULTI_FRAME_TICKS_VARIABLE), Action::GetVariable];
+    let mut tick = vec![push_str(MULTI_FRAME_TICKS_VARIABLE)];
+    tick.extend(get_ticks.clone());
+    tick.extend([Action::Increment, Action::SetVariable]);
+    let mut is_last_tick = get_ticks.to_vec();
+    is_last_tick.extend([
+        Action::Push(Push {
+            values: vec![Value::Int(MULTI_FRAME_TICKS)],
+        }),
+        Action::Equals2,
+    ]);
+    let mut on_enter_frame = assemble(version, &tick)?;
+    on_enter_frame.extend(assemble_if_else(
+        version,
+        &assemble(version, &is_last_tick)?,
+        complete_actions,
+        &[],
+    )?);
+
+    let mut is_first_run = get_ticks.to_vec();
+    is_first_run.extend([
+        Action::Push(Push {
+            values: vec![Value::Undefined],
+        }),
+        Action::StrictEquals,
+    ]);
+    let setup = [
+        Action::Push(Push {
+            values: vec![
+                Value::Str(MULTI_FRAME_TICKS_VARIABLE.into()),
+                Value::Int(0),
+                Value::Str(MULTI_FRAME_JUMPS_VARIABLE.into()),
+                Value::Int(0),
+            ],
+        }),
+        Action::SetVariable,
+        Action::SetVariable,
+        Action::Push(Push {
+            values: vec![Value::Str("_root".into())],
+        }),
+        Action::GetVariable,
+        push_str("onEnterFrame"),
+        Action::DefineFunction(DefineFunction {
+            name: "".into(),
+            params: vec![],
+            actions: &on_enter_frame,
+        }),
+        Action::SetMember,
+    ];
+    assemble_if_else(
+        version,
+        &assemble(version, &is_first_run)?,
+        &assemble(version, &setup)?,
+        &[],
+    )
+}
+
+/// The ids of the sprites placed in target path cases, the outer one places the inner one on its first frame
+const PATH_SPRITE_ID: CharacterId = 310;
+const PATH_INNER_SPRITE_ID: CharacterId = 311;
+
+/// The depth and instance names the path sprites are placed at, the inner one inside the outer one
+const PATH_SPRITE_DEPTH: Depth = 2010;
+const PATH_SPRITE_NAME: &str = "__path";
+const PATH_INNER_SPRITE_NAME: &str = "inner";
+
+/// The variable target path cases keep the path string in between resolving it
+const TARGET_PATH_VARIABLE: &str = "__tpath";
+
+/// The property index of `_target`
+const TARGET_PROPERTY: i32 = 11;
+
+/// References to the path clips and the root passed to GetVariable, in dot, slash and mixed syntax. Some don't name
+/// a clip, such as a variable path or a clip that doesn't exist
+const TARGET_PATH_REFERENCES: &[&str] = &[
+    "__path",
+    "__path.inner",
+    "_root.__path",
+    "_root.__path.inner",
+    "_level0.__path.inner",
+    "/__path",
+    "/__path/inner",
+    "__path/inner",
+    "/",
+    "_root",
+    "_level0",
+    "/__path/inner/..",
+    "/__path:inner",
+    "__path.missing",
+    "/missing",
+];
+
+/// An operand of a string comparison case
+#[derive(Debug, Clone, Copy)]
+enum CompareOperand {
+    Str(&'static str),
+    /// The string followed by a null character, which is added with `String.fromCharCode(0)` as a pushed string
+    /// ends at its first null
+    NullTerminated(&'static str),
+    Int(i32),
+    Double(f64),
+}
+
+impl fmt::Display for CompareOperand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompareOperand::Str(s) => write!(f, "{:?}", s),
+            CompareOperand::NullTerminated(s) => write!(f, "{:?} + chr(0)", s),
+            CompareOperand::Int(i) => write!(f, "{}", i),
+            CompareOperand::Double(d) => write!(f, "{}", d),
+        }
+    }
+}
+
+/// A pair of operands compared by string comparison cases, `kind` is what makes it interesting
+#[derive(Debug, Clone, Copy)]
+struct ComparePair {
+    kind: &'static str,
+    left: CompareOperand,
+    right: CompareOperand,
+}
+
+const fn compare_pair(
+    kind: &'static str,
+    left: CompareOperand,
+    right: CompareOperand,
+) -> ComparePair {
+    ComparePair { kind, left, right }
+}
+
+/// The pairs that string comparison cases compare. Characters above 0x7F sort differently in WINDOWS-1252, which
+/// SWF5 strings are read as, than in UTF-16
+const STRING_COMPARE_PAIRS: &[ComparePair] = {
+    use CompareOperand::*;
+    &[
+        compare_pair("case", Str("a"), Str("A")),
+        compare_pair("case", Str("abc"), Str("ABD")),
+        compare_pair("case", Str("Zebra"), Str("apple")),
+        compare_pair("trailing null", NullTerminated("abc"), Str("abc")),
+        compare_pair("trailing null", NullTerminated(""), Str("")),
+        compare_pair("numeric", Str("10"), Int(9)),
+        compare_pair("numeric", Str("10"), Str("9")),
+        compare_pair("numeric", Str("1e1"), Int(10)),
+        compare_pair("numeric", Str("0x10"), Int(16)),
+        compare_pair("numeric", Str(" 5"), Double(5.0)),
+        compare_pair("numeric", Str("-0"), Double(-0.0)),
+        compare_pair("numeric", Str("NaN"), Double(f64::NAN)),
+        compare_pair("numeric", Str(""), Int(0)),
+        compare_pair("high characters", Str("é"), Str("z")),
+        compare_pair("high characters", Str("€"), Str("ÿ")),
+        compare_pair("high characters", Str("Ä"), Str("a")),
+        compare_pair("high characters", Str("日本"), Str("z")),
+        compare_pair("empty and space", Str(""), Str(" ")),
+        compare_pair("empty and space", Str(" "), Str("\t")),
+    ]
+};
+
+/// The opcodes string comparison cases compare each pair with, by their name in the generator filter
+const STRING_COMPARE_OPCODES: &[(&str, Action<'static>)] = &[
+    ("StringEquals", Action::StringEquals),
+    ("StringLess", Action::StringLess),
+    ("StringGreater", Action::StringGreater),
+    ("Equals", Action::Equals),
+    ("Equals2", Action::Equals2),
+    ("Less", Action::Less),
+    ("Less2", Action::Less2),
+    ("Greater", Action::Greater),
+];
+
+/// The tags of target path cases, the nested path sprites placed on the root so that the main actions can reach them
+pub fn target_path_tags() -> Vec<Tag<'static>> {
+    vec![
+        Tag::DefineSprite(Sprite {
+            id: PATH_INNER_SPRITE_ID,
+            num_frames: 1,
+            tags: vec![Tag::ShowFrame],
+        }),
+        Tag::DefineSprite(Sprite {
+            id: PATH_SPRITE_ID,
+            num_frames: 1,
+            tags: vec![
+                Tag::PlaceObject(Box::new(
+                    PlaceObject::new_place(PATH_INNER_SPRITE_ID, 1)
+                        .with_name(SwfStr::from_utf8_str(PATH_INNER_SPRITE_NAME)),
+                )),
+                Tag::ShowFrame,
+            ],
+        }),
+        Tag::PlaceObject(Box::new(
+            PlaceObject::new_place(PATH_SPRITE_ID, PATH_SPRITE_DEPTH)
+                .with_name(SwfStr::from_utf8_str(PATH_SPRITE_NAME)),
+        )),
+    ]
+}
+
+/// The first character id and depth given out by `StageClips`, above the fixed ones of the other modes
+const STAGE_FIRST_ID: CharacterId = 400;
+const STAGE_FIRST_DEPTH: Depth = 3000;
+
+/// The clips that sub-cases place on the root's first frame, each a sprite of its own. Character ids and depths are
+/// handed out in order, so clips placed by different sub-cases of a case never collide
+#[derive(Default)]
+pub struct StageClips {
+    ids: CharacterId,
+    depths: Depth,
+    /// The id, frame count, depth and instance name of each clip
+    clips: Vec<(CharacterId, u16, Depth, String)>,
+}
+
+impl StageClips {
+    pub fn allocate_id(&mut self) -> CharacterId {
+        self.ids += 1;
+        STAGE_FIRST_ID + self.ids - 1
+    }
+
+    pub fn allocate_depth(&mut self) -> Depth {
+        self.depths += 1;
+        STAGE_FIRST_DEPTH + self.depths - 1
+    }
+
+    /// Place a new sprite of `num_frames` empty frames, returns its instance name
+    pub fn place_clip(&mut self, num_frames: u16) -> String {
+        let id = self.allocate_id();
+        let depth = self.allocate_depth();
+        let name = format!("__mc{}", id - STAGE_FIRST_ID);
+        self.clips.push((id, num_frames, depth, name.clone()));
+        name
+    }
+
+    /// The definitions and placements of the clips, they go before the main actions so that those can find them
+    pub fn tags(&self) -> Vec<Tag<'_>> {
+        let mut tags = Vec::new();
+        for (id, num_frames, depth, name) in &self.clips {
+            tags.push(Tag::DefineSprite(Sprite {
+                id: *id,
+                num_frames: *num_frames,
+                tags: (0..*num_frames).map(|_| Tag::ShowFrame).collect(),
+            }));
+            tags.push(Tag::PlaceObject(Box::new(
+                PlaceObject::new_place(*id, *depth).with_name(SwfStr::from_utf8_str(name)),
+            )));
+        }
+        tags
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// The most frames the sprite of a movie clip case has
+const MOVIE_CLIP_MAX_FRAMES: u16 = 3;
+
+/// The most operations a movie clip case does to its clip
+const MOVIE_CLIP_MAX_OPS: usize = 6;
+
+/// The operations movie clip cases do to their clip or its duplicate
+const MOVIE_CLIP_OPS: &[&str] = &[
+    "getDepth",
+    "getBounds",
+    "read _x",
+    "read _y",
+    "write _x",
+    "write _y",
+    "gotoAndStop",
+    "duplicateMovieClip",
+    "removeMovieClip",
+];
+
+/// What movie clip cases put before a clip's name to reference it with GetVariable, as a plain name, in dot syntax
+/// and as a slash syntax target path
+const MOVIE_CLIP_REFERENCES: &[&str] = &["", "_root.", "_level0.", "/", "_root/"];
+
+/// How often a movie clip case operation is done to the duplicate of the clip instead, which only exists once
+/// `duplicateMovieClip` has been called
+const MOVIE_CLIP_DUPLICATE_RATE: f64 = 0.25;
+
+/// The most operations a clone sprite case does
+const CLONE_SPRITE_MAX_OPS: usize = 6;
+
+/// Depths passed to CloneSprite. Scripts see timeline depths offset by -16384, which compilers add back for
+/// duplicateMovieClip, so 16384 and above are the valid ones. Around them are the timeline's own range, negative
+/// depths, the edge of 1 << 20 and the top of the range reserved for clips that can't be removed
+const CLONE_SPRITE_DEPTHS: &[i32] = &[
+    16384,
+    16385,
+    16384 + 1000,
+    0,
+    1,
+    -1,
+    -16384,
+    1 << 20,
+    (1 << 20) + 1,
+    2130706427,
+    2130706428,
+    i32::MAX,
+];
+
+/// Targets of SetTarget, relative to the root as that is where the main actions run
+const SET_TARGET_NAMES: &[&str] = &["", "/", "_root", "_level0", "..", "missing", "/missing"];
+
+/// The properties of GetProperty and SetProperty, by index
+const PROPERTY_NAMES: &[&str] = &[
+    "_x",
+    "_y",
+    "_xscale",
+    "_yscale",
+    "_currentframe",
+    "_totalframes",
+    "_alpha",
+    "_visible",
+    "_width",
+    "_height",
+    "_rotation",
+    "_target",
+    "_framesloaded",
+    "_name",
+    "_droptarget",
+    "_url",
+    "_highquality",
+    "_focusrect",
+    "_soundbuftime",
+    "_quality",
+    "_xmouse",
+    "_ymouse",
+];
+
+/// Indices of the properties that depend on the player's state rather than the case, such as how much has loaded,
+/// where the swf was loaded from, the quality settings and the mouse position. They are traced after
+/// `UNSTABLE_PREFIX` so only their type is compared
+const UNSTABLE_PROPERTIES: &[i32] = &[5, 12, 14, 15, 16, 18, 19, 20, 21];
+
+/// Targets of GetProperty and SetProperty cases, besides a placed clip
+const PROPERTY_TARGETS: &[&str] = &["_root", ""];
+
+/// Metadata in the form exported by the Flash IDE
+const METADATA_XML: &str = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1"><dc:format>application/x-shockwave-flash</dc:format><dc:title>flash-fuzz</dc:title></rdf:Description></rdf:RDF>"#;
+
+/// Metadata recording the seed a case was generated from, see `SwfGenerator::reseed`
+fn case_seed_metadata(case_seed: u64) -> String {
+    format!(
+        r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1"><dc:format>application/x-shockwave-flash</dc:format><dc:title>flash-fuzz</dc:title><dc:description>case seed {}</dc:description></rdf:Description></rdf:RDF>"#,
+        case_seed
+    )
+}
 
-        // Pick a random function
-        let (function_name, args) = self.select(functions);
-        let function_arg_count = self.rng.gen_range(0..=args.len() as i32);
+/// The line traced after the nonce by a case generated right after `SwfGenerator::reseed`
+pub fn case_seed_line(case_seed: u64) -> String {
+    format!("case seed: {}", case_seed)
+}
 
-        // Push function args and arg count
-        for _ in 0..function_arg_count {
-            self.w.write_action(&Action::Push(Push {
-                values: vec![Self::random_value(self.rng, self.strings)],
-            }))?;
-        }
-        self.w.write_action(&Action::Push(Push {
-            values: vec![Value::Int(function_arg_count)],
-        }))?;
+/// Scene and frame labels, including duplicates and names that look like frame numbers
+const FRAME_LABEL_CORPUS: &[&str] = &["Scene 1", "Scene 2", "label", "label", "1", "", "日本語"];
 
-        // Get foo
-        self.push(&SimpleValue::String(Cow::Borrowed("foo")))?;
+/// The kinds of test that a case can be made up of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorMode {
+    DynamicFunction,
+    StaticFunction,
+    Opcode,
+    Navigation,
+    Placement,
+    ArgCount,
+    Version,
+    Capabilities,
+    Input,
+    TagSize,
+    MalformedContainer,
+    BinaryData,
+    MemberChain,
+    FrameLabel,
+    TargetPath,
+    StringCompare,
+    Function,
+    TryCatch,
+    MemberAccess,
+    ProtoChain,
+    ClassHierarchy,
+    GlobalFunction,
+    NumberFormat,
+    MultiFrame,
+    MovieClip,
+    CloneSprite,
+}
 
-        self.w.write_action(&Action::GetVariable)?;
+impl GeneratorMode {
+    /// Every mode, in the order their tests are emitted in a case
+    pub const ALL: &'static [GeneratorMode] = &[
+        GeneratorMode::DynamicFunction,
+        GeneratorMode::StaticFunction,
+        GeneratorMode::Opcode,
+        GeneratorMode::Navigation,
+        GeneratorMode::Placement,
+        GeneratorMode::ArgCount,
+        GeneratorMode::Version,
+        GeneratorMode::Capabilities,
+        GeneratorMode::Input,
+        GeneratorMode::TagSize,
+        GeneratorMode::MalformedContainer,
+        GeneratorMode::BinaryData,
+        GeneratorMode::MemberChain,
+        GeneratorMode::FrameLabel,
+        GeneratorMode::TargetPath,
+        GeneratorMode::StringCompare,
+        GeneratorMode::Function,
+        GeneratorMode::TryCatch,
+        GeneratorMode::MemberAccess,
+        GeneratorMode::ProtoChain,
+        GeneratorMode::ClassHierarchy,
+        GeneratorMode::GlobalFunction,
+        GeneratorMode::NumberFormat,
+        GeneratorMode::MultiFrame,
+        GeneratorMode::MovieClip,
+        GeneratorMode::CloneSprite,
+    ];
 
-        // Call foo.<function_name>()
-        self.push(&SimpleValue::String(Cow::Borrowed(function_name)))?;
-        self.w.write_action(&Action::CallMethod)?;
+    pub fn name(self) -> &'static str {
+        match self {
+            GeneratorMode::DynamicFunction => "dynamic_function",
+            GeneratorMode::StaticFunction => "static_function",
+            GeneratorMode::Opcode => "opcode",
+            GeneratorMode::Navigation => "navigation",
+            GeneratorMode::Placement => "placement",
+            GeneratorMode::ArgCount => "arg_count",
+            GeneratorMode::Version => "version",
+            GeneratorMode::Capabilities => "capabilities",
+            GeneratorMode::Input => "input",
+            GeneratorMode::TagSize => "tag_size",
+            GeneratorMode::MalformedContainer => "malformed_container",
+            GeneratorMode::BinaryData => "binary_data",
+            GeneratorMode::MemberChain => "member_chain",
+            GeneratorMode::FrameLabel => "frame_label",
+            GeneratorMode::TargetPath => "target_path",
+            GeneratorMode::StringCompare => "string_compare",
+            GeneratorMode::Function => "function",
+            GeneratorMode::TryCatch => "try_catch",
+            GeneratorMode::MemberAccess => "member_access",
+            GeneratorMode::ProtoChain => "proto_chain",
+            GeneratorMode::ClassHierarchy => "class_hierarchy",
+            GeneratorMode::GlobalFunction => "global_function",
+            GeneratorMode::NumberFormat => "number_format",
+            GeneratorMode::MultiFrame => "multi_frame",
+            GeneratorMode::MovieClip => "movie_clip",
+            GeneratorMode::CloneSprite => "clone_sprite",
+        }
+    }
 
-        SwfGenerator::dump_stack(&mut self.w)?;
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|mode| mode.name() == name)
+    }
 
-        Ok(())
+    /// The modes enabled in the config, in the order of `ALL`
+    pub fn enabled() -> Vec<GeneratorMode> {
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(|mode| mode.is_enabled())
+            .collect()
+    }
 
-        //TODO: dump return val + all properties
-        //TODO: run multiple functions on each object
-        //TODO: pay attention to types of args
+    /// Is this mode enabled in the config
+    pub fn is_enabled(self) -> bool {
+        match self {
+            GeneratorMode::DynamicFunction => DYNAMIC_FUNCTION_FUZZ,
+            GeneratorMode::StaticFunction => STATIC_FUNCTION_FUZZ,
+            GeneratorMode::Opcode => OPCODE_FUZZ,
+            GeneratorMode::Navigation => NAVIGATION_FUZZ,
+            GeneratorMode::Placement => PLACEMENT_FUZZ,
+            GeneratorMode::ArgCount => ARG_COUNT_FUZZ,
+            GeneratorMode::Version => VERSION_FUZZ,
+            GeneratorMode::Capabilities => CAPABILITIES_FUZZ,
+            GeneratorMode::Input => INPUT_FUZZ,
+            GeneratorMode::TagSize => TAG_SIZE_FUZZ,
+            GeneratorMode::MalformedContainer => MALFORMED_CONTAINER_FUZZ,
+            GeneratorMode::BinaryData => BINARY_DATA_FUZZ,
+            GeneratorMode::MemberChain => MEMBER_CHAIN_FUZZ,
+            GeneratorMode::FrameLabel => FRAME_LABEL_FUZZ,
+            GeneratorMode::TargetPath => TARGET_PATH_FUZZ,
+            GeneratorMode::StringCompare => STRING_COMPARE_FUZZ,
+            GeneratorMode::Function => FUNCTION_FUZZ,
+            GeneratorMode::TryCatch => TRY_CATCH_FUZZ,
+            GeneratorMode::MemberAccess => MEMBER_ACCESS_FUZZ,
+            GeneratorMode::ProtoChain => PROTO_CHAIN_FUZZ,
+            GeneratorMode::ClassHierarchy => CLASS_HIERARCHY_FUZZ,
+            GeneratorMode::GlobalFunction => GLOBAL_FUNCTION_FUZZ,
+            GeneratorMode::NumberFormat => NUMBER_FORMAT_FUZZ,
+            GeneratorMode::MultiFrame => MULTI_FRAME_FUZZ,
+            GeneratorMode::MovieClip => MOVIE_CLIP_FUZZ,
+            GeneratorMode::CloneSprite => CLONE_SPRITE_FUZZ,
+        }
     }
 
-    pub fn opcode_fuzz(&mut self) -> Result<(), Box<dyn Error>> {
-        self.push(&SimpleValue::String(Cow::Borrowed("#PREFIX#")))?;
+    /// Should this mode's sub-cases be wrapped in a try block that traces errors, placement, input, multi-frame,
+    /// malformed containers and binary data have no sub-cases to wrap, and wrapping tag size sub-cases would change
+    /// their size
+    pub fn catches_errors(self) -> bool {
+        CATCH_ERROR_MODES.contains(&self) && self.has_sub_cases() && self != GeneratorMode::TagSize
+    }
 
-        //TODO: ActionAdd produces errors in some cases
-        // todo: so does less
-        let (action, arg_count) = self.select(&[
-            (Action::Add, 2),
-            (Action::Add2, 2),
-            (Action::And, 2),
-            (Action::AsciiToChar, 1),
-            (Action::BitAnd, 2),
-            (Action::BitLShift, 2),
-            (Action::BitOr, 2),
-            (Action::BitRShift, 2),
-            (Action::BitURShift, 2),
-            (Action::BitXor, 2),
-            //_
-            (Action::CastOp, 2),
-            (Action::CharToAscii, 1),
-            //_
-            // TODO: constant pool
-            (Action::Decrement, 1),
-            //_
-            // TODO: divide
-            // (Action::Enumerate, 1),
-            /*(Action::Enumerate2, 1),*/
-            (Action::Equals, 2),
-            (Action::Equals2, 2),
-            //_
-            (Action::Greater, 2),
-            // (Action::ImplementsOp, ?), //TODO: needs special handling
-            (Action::Increment, 1),
-            // (Action::InitArray, <this is handled by array generation>),
-            // (Action::InitObject, <this is handled by object generation>),
-            (Action::InstanceOf, 2),
-            (Action::Less, 2),
-            (Action::Less2, 2),
-            (Action::MBAsciiToChar, 1),
-            (Action::MBCharToAscii, 1),
-            (Action::MBStringExtract, 3),
-            (Action::MBStringLength, 1),
-            (Action::Modulo, 2),   //TODO: doubles dont match
-            (Action::Multiply, 2), //TODO: doubles dont match
-            //_
-            (Action::Not, 1),
-            (Action::Or, 2),
-            //_
-            (Action::Pop, 1),
-            //_
-            (Action::PushDuplicate, 1),
-            //_
-            (Action::StackSwap, 2),
-            //_
-            (Action::StrictEquals, 2),
-            (Action::StringAdd, 2),
-            (Action::StringEquals, 2),
-            (Action::StringExtract, 3),
-            (Action::StringGreater, 2),
-            (Action::StringLength, 1),
-            (Action::StringLess, 2),
-            (Action::Subtract, 2), //TODO: doubles dont match
-            (Action::TargetPath, 1),
-            //_
-            (Action::ToInteger, 1),
-            (Action::ToNumber, 1),
-            (Action::ToString, 1),
-            // (Action::ToggleQuality, 0),
-            (Action::Trace, 1),
-            (Action::TypeOf, 1),
-            //_
-        ]);
+    /// Does this mode generate sub-cases of the main actions, placement, input, multi-frame, malformed containers and
+    /// binary data happen in frames of their own or after the main actions
+    pub fn has_sub_cases(self) -> bool {
+        !matches!(
+            self,
+            GeneratorMode::Placement
+                | GeneratorMode::Input
+                | GeneratorMode::MultiFrame
+                | GeneratorMode::MalformedContainer
+                | GeneratorMode::BinaryData
+        )
+    }
 
-        //TODO: rest of non-frame actions
-        //TODO: dump entire stack, not just top so we can check multi value actions like enumerate
+    /// The position of this mode in `ALL`
+    pub fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|mode| *mode == self)
+            .expect("Mode missing from GeneratorMode::ALL")
+    }
 
-        for _ in 0..arg_count {
-            let v = self.random_value_simple(0);
-            self.push(&v)?;
-        }
-        // Testing arithmetic ops
-        self.w.write_action(&action)?;
+    /// The weight of each of `ALL` in `MODE_WEIGHTS`
+    pub fn configured_weights() -> Vec<u32> {
+        Self::ALL.iter().map(|mode| mode.weight()).collect()
+    }
 
-        SwfGenerator::dump_stack(&mut self.w)?;
+    /// How often this mode is picked for a sub-case, see `MODE_WEIGHTS`
+    pub fn weight(self) -> u32 {
+        MODE_WEIGHTS
+            .iter()
+            .find(|(mode, _)| *mode == self)
+            .map_or(DEFAULT_MODE_WEIGHT, |(_, weight)| *weight)
+    }
+}
 
-        Ok(())
+/// Pick the mode of a sub-case from `modes` by `weights`, which has the weight of each of `GeneratorMode::ALL`. A lone
+/// mode is picked whatever its weight without drawing from `rng`, so cases of a single mode don't depend on the weights
+fn pick_mode(rng: &mut StdRng, modes: &[GeneratorMode], weights: &[u32]) -> Option<GeneratorMode> {
+    if let [mode] = modes {
+        return Some(*mode);
+    }
+    let total: u32 = modes.iter().map(|mode| weights[mode.index()]).sum();
+    if total == 0 {
+        return None;
     }
+    let mut pick = rng.gen_range(0..total);
+    modes.iter().copied().find(|mode| {
+        let weight = weights[mode.index()];
+        if pick < weight {
+            true
+        } else {
+            pick -= weight;
+            false
+        }
+    })
 }
 
 pub(crate) struct SwfGenerator {
     rng: StdRng,
     strings: Vec<Vec<u8>>,
     do_action_bytes: Vec<u8>,
+    /// The strings that the main actions push from a constant pool
+    constant_pool: PooledStrings,
+    /// The offsets in `do_action_bytes` that each DoAction tag after the first starts at
+    do_action_splits: Vec<usize>,
+    /// The static methods called by the last generated case
+    static_calls: Vec<StaticCall>,
+    /// What the generator may pick, see `generator_filter`
+    filter: GeneratorFilter,
+    /// Index into `SWF_VERSIONS` of the version to use for the next case
+    next_version: usize,
+    /// The input to deliver while the last generated case runs, empty unless it is an input fuzz case
+    input_script: InputScript,
+    /// Traced first by the last generated case, see `sentinel::check_nonce`
+    nonce: u64,
+    /// The seed of the last generated case's `Math.random`, if it was replaced, see `SEEDED_RANDOM`
+    random_seed: Option<i32>,
+    /// The seed passed to `reseed`, until the case generated from it embeds it
+    case_seed: Option<u64>,
+    /// The frames ruffle should run the last generated case for
+    frame_budget: u32,
+    /// The clips the last generated case placed on the stage
+    stage: StageClips,
+    /// The debug form of each object and array the last generated case constructs, in the order of their
+    /// InitObject and InitArray actions
+    constructions: Vec<String>,
+    /// The sub-cases of the main actions to keep, by index, None keeps all of them. See `keep_sub_cases`
+    sub_case_filter: Option<Vec<usize>>,
+    /// How many sub-cases the main actions of the last generated case were made up of, kept or not
+    sub_case_count: usize,
+    /// The mode of each sub-case of the last generated case, followed by the modes that run in frames of their own
+    /// or after the main actions
+    case_modes: Vec<GeneratorMode>,
+    /// The weight each of `GeneratorMode::ALL` is picked for a sub-case with, see `set_mode_weights`
+    mode_weights: Vec<u32>,
+    /// Generate random byte strings rather than fixed ones, see `Config::random_strings`
+    random_strings: bool,
+}
+
+/// The versions that cases made up of `modes` pick from, None if they cycle through `SWF_VERSIONS`. The default path
+/// syntax and string comparisons changed between SWF5 and 7, so target path and string comparison cases on their own
+/// pick from those. Try/catch and class hierarchy cases on their own need SWF7, and opcode cases on their own keep to
+/// the opcodes of the version so they go back to SWF4
+pub fn mode_swf_versions(modes: &[GeneratorMode]) -> Option<&'static [u8]> {
+    match modes {
+        [GeneratorMode::Opcode] => Some(OPCODE_SWF_VERSIONS),
+        [GeneratorMode::TargetPath] => Some(TARGET_PATH_SWF_VERSIONS),
+        [GeneratorMode::StringCompare] => Some(STRING_COMPARE_SWF_VERSIONS),
+        [GeneratorMode::TryCatch] => Some(TRY_CATCH_SWF_VERSIONS),
+        [GeneratorMode::ClassHierarchy] => Some(CLASS_HIERARCHY_SWF_VERSIONS),
+        _ => None,
+    }
 }
 
 impl SwfGenerator {
     pub fn new() -> Self {
-        let rng = StdRng::from_entropy();
+        Self::from_rng(StdRng::from_entropy())
+    }
+
+    /// Create a generator that always produces the same cases, for the generator self-test
+    pub fn with_seed(seed: u64) -> Self {
+        Self::from_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng(mut rng: StdRng) -> Self {
+        // Start each generator at a different version so short runs still cover all of them
+        let next_version = rng.gen_range(0..SWF_VERSIONS.len());
 
         Self {
             rng,
             strings: Vec::new(),
             do_action_bytes: Vec::with_capacity(1024),
+            constant_pool: PooledStrings::default(),
+            do_action_splits: Vec::new(),
+            static_calls: Vec::new(),
+            filter: GeneratorFilter::default(),
+            next_version,
+            input_script: InputScript::default(),
+            nonce: 0,
+            random_seed: None,
+            case_seed: None,
+            frame_budget: RUFFLE_FRAME_BUDGET,
+            stage: StageClips::default(),
+            constructions: Vec::new(),
+            sub_case_filter: None,
+            sub_case_count: 0,
+            case_modes: Vec::new(),
+            mode_weights: GeneratorMode::configured_weights(),
+            random_strings: FUZZ_RANDOM_STRING,
         }
     }
 
+    /// Generate the following cases from `seed`, a case generated right after this can be generated again by
+    /// reseeding with the same seed and passing its version to `generate_version`. That case traces the seed after
+    /// its nonce and records it in its metadata, see `case_seed_line`
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.case_seed = Some(seed);
+    }
+
+    /// Leave all but the given sub-cases out of the main actions of the following cases, None keeps all of them. The
+    /// others are still generated, so the kept ones come out the same as with all of them, see `minimizer`
+    pub fn keep_sub_cases(&mut self, sub_cases: Option<Vec<usize>>) {
+        self.sub_case_filter = sub_cases;
+    }
+
+    /// Pick the modes of the following cases' sub-cases by `weights`, the weight of each of `GeneratorMode::ALL`, rather
+    /// than by `MODE_WEIGHTS`, see `mode_scheduler`
+    pub fn set_mode_weights(&mut self, weights: Vec<u32>) {
+        self.mode_weights = weights;
+    }
+
+    /// The weight each of `GeneratorMode::ALL` is picked for a sub-case with
+    pub fn mode_weights(&self) -> &[u32] {
+        &self.mode_weights
+    }
+
+    /// Generate random byte strings rather than fixed ones when `random_strings` is set
+    pub fn with_random_strings(mut self, random_strings: bool) -> Self {
+        self.random_strings = random_strings;
+        self
+    }
+
+    /// Whether random byte strings are generated, see `with_random_strings`
+    pub fn random_strings(&self) -> bool {
+        self.random_strings
+    }
+
+    /// Only generate what `filter` allows
+    pub fn with_filter(mut self, filter: GeneratorFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
     pub fn do_action_generator<'c, 'd: 'c>(&'d mut self, version: u8) -> DoActionGenerator<'c> {
         DoActionGenerator {
             w: Writer::new(&mut self.do_action_bytes, version),
             strings: &mut self.strings,
+            constant_pool: &mut self.constant_pool,
+            rng: &mut self.rng,
+            version,
+            splits: &mut self.do_action_splits,
+            static_calls: &mut self.static_calls,
+            filter: &self.filter,
+            written_registers: [false; GLOBAL_REGISTERS as usize],
+            stage: &mut self.stage,
+            constructions: &mut self.constructions,
+            random_strings: self.random_strings,
+        }
+    }
+
+    pub fn placement_generator(&mut self, version: u8) -> PlacementGenerator<'_> {
+        PlacementGenerator {
+            rng: &mut self.rng,
+            version,
+        }
+    }
+
+    pub fn input_generator(&mut self, version: u8) -> InputGenerator<'_> {
+        InputGenerator {
+            rng: &mut self.rng,
+            version,
+        }
+    }
+
+    pub fn malformed_generator(&mut self, version: u8) -> MalformedGenerator<'_> {
+        MalformedGenerator {
+            rng: &mut self.rng,
+            version,
+        }
+    }
+
+    pub fn binary_data_generator(&mut self, version: u8) -> BinaryDataGenerator<'_> {
+        BinaryDataGenerator {
             rng: &mut self.rng,
+            version,
+        }
+    }
+
+    /// Label the frames of the labeled sprite at random, anchors are only used from SWF6, where they were added
+    pub fn frame_label_layout(&mut self, version: u8) -> Result<FrameLabelLayout, Box<dyn Error>> {
+        let mut frames = Vec::with_capacity(LABEL_SPRITE_FRAMES);
+        let mut frame_actions = Vec::with_capacity(LABEL_SPRITE_FRAMES);
+        for frame in 1..=LABEL_SPRITE_FRAMES {
+            let labels = (0..self.rng.gen_range(0..=2))
+                .map(|_| {
+                    let label = FRAME_LABEL_NAMES[self.rng.gen_range(0..FRAME_LABEL_NAMES.len())];
+                    (label, version >= 6 && self.rng.gen_bool(0.3))
+                })
+                .collect();
+            let trace = format!("{} frame {}", LABEL_SPRITE_NAME, frame);
+            let mut actions = vec![
+                Action::Push(Push {
+                    values: vec![Value::Str(SwfStr::from_utf8_str(&trace))],
+                }),
+                Action::Trace,
+            ];
+            if frame == 1 {
+                actions.push(Action::Stop);
+            }
+            frames.push(labels);
+            frame_actions.push(assemble(version, &actions)?);
+        }
+        Ok(FrameLabelLayout {
+            frames,
+            frame_actions,
+        })
+    }
+
+    pub fn multi_frame_case(&mut self, version: u8) -> Result<MultiFrameCase, Box<dyn Error>> {
+        fn push(value: Value<'_>) -> Action<'_> {
+            Action::Push(Push {
+                values: vec![value],
+            })
+        }
+        fn trace(line: &str) -> [Action<'_>; 2] {
+            [push(Value::Str(SwfStr::from_utf8_str(line))), Action::Trace]
+        }
+
+        let count = self.rng.gen_range(2..=MULTI_FRAME_MAX_FRAMES);
+        // Root frame numbers, the case's frames are 2 to count + 1 and the wrapping frame is after them. Navigating
+        // to frame 1 would run the main actions again, but one past the wrapping frame is out of range
+        let targets = 2..=count + 3;
+        let label_targets = [FRAME_LABEL_NAMES, UNLABELED_FRAME_TARGETS].concat();
+        let mut frames = Vec::with_capacity(count + 1);
+        let mut frame_actions = Vec::with_capacity(count + 1);
+        let mut navigations = Vec::with_capacity(count);
+        for frame in 2..=count + 1 {
+            let labels = (0..self.rng.gen_range(0..=2))
+                .map(|_| {
+                    let label = FRAME_LABEL_NAMES[self.rng.gen_range(0..FRAME_LABEL_NAMES.len())];
+                    (label, version >= 6 && self.rng.gen_bool(0.3))
+                })
+                .collect();
+
+            let trace_frame = format!("root frame {}", frame);
+            let trace_waited = format!("{} waited", trace_frame);
+            let trace_done = format!("{} done", trace_frame);
+            let target = self.rng.gen_range(targets.clone());
+            let label = label_targets[self.rng.gen_range(0..label_targets.len())];
+            let (navigation, actions) = match self.rng.gen_range(0..7) {
+                0 => (
+                    format!("gotoFrame({})", target),
+                    vec![Action::GotoFrame(GotoFrame {
+                        frame: target as u16 - 1,
+                    })],
+                ),
+                1 => (
+                    format!("gotoLabel({:?})", label),
+                    vec![Action::GotoLabel(GotoLabel {
+                        label: SwfStr::from_utf8_str(label),
+                    })],
+                ),
+                2 => {
+                    let set_playing = self.rng.gen_bool(0.5);
+                    let (description, value) = if self.rng.gen_bool(0.5) {
+                        (target.to_string(), Value::Int(target as i32))
+                    } else {
+                        (
+                            format!("{:?}", label),
+                            Value::Str(SwfStr::from_utf8_str(label)),
+                        )
+                    };
+                    (
+                        format!("gotoFrame2({}, playing={})", description, set_playing),
+                        vec![
+                            push(value),
+                            Action::GotoFrame2(GotoFrame2 {
+                                set_playing,
+                                scene_offset: 0,
+                            }),
+                        ],
+                    )
+                }
+                3 => ("play".to_string(), vec![Action::Play]),
+                4 => ("stop".to_string(), vec![Action::Stop]),
+                5 => {
+                    let mut actions = vec![Action::WaitForFrame(WaitForFrame {
+                        frame: target as u16 - 1,
+                        num_actions_to_skip: 2,
+                    })];
+                    actions.extend(trace(&trace_waited));
+                    (format!("waitForFrame({})", target), actions)
+                }
+                _ => ("none".to_string(), Vec::new()),
+            };
+            navigations.push(navigation);
+
+            // if (__mf_jumps < MULTI_FRAME_MAX_JUMPS) { __mf_jumps++; <navigation> }
+            let get_jumps = [
+                push(Value::Str(MULTI_FRAME_JUMPS_VARIABLE.into())),
+                Action::GetVariable,
+            ];
+            let mut can_jump = get_jumps.to_vec();
+            can_jump.extend([push(Value::Int(MULTI_FRAME_MAX_JUMPS)), Action::Less2]);
+            let mut jump = vec![push(Value::Str(MULTI_FRAME_JUMPS_VARIABLE.into()))];
+            jump.extend(get_jumps);
+            jump.extend([Action::Increment, Action::SetVariable]);
+            jump.extend(actions);
+
+            let mut actions = assemble(version, &trace(&trace_frame))?;
+            actions.extend(assemble_if_else(
+                version,
+                &assemble(version, &can_jump)?,
+                &assemble(version, &jump)?,
+                &[],
+            )?);
+            actions.extend(assemble(version, &trace(&trace_done))?);
+            frames.push(labels);
+            frame_actions.push(actions);
         }
+        // The wrapping frame, gotoAndPlay(2)
+        let trace_wrap = format!("root frame {} wrap", count + 2);
+        let mut wrap = trace(&trace_wrap).to_vec();
+        wrap.extend([Action::GotoFrame(GotoFrame { frame: 1 }), Action::Play]);
+        frames.push(Vec::new());
+        frame_actions.push(assemble(version, &wrap)?);
+
+        Ok(MultiFrameCase {
+            description: format!("multi-frame ({} frames) {}", count, navigations.join(", ")),
+            frames,
+            frame_actions,
+        })
     }
 
     pub fn reset(&mut self) {
         self.strings.clear();
         self.do_action_bytes.clear();
+        self.constant_pool = PooledStrings::default();
+        self.do_action_splits.clear();
+        self.static_calls.clear();
+        self.input_script.clear();
+        self.stage.clear();
+        self.constructions.clear();
+    }
+
+    /// The input to deliver while the last generated case runs
+    pub fn input_script(&self) -> &InputScript {
+        &self.input_script
+    }
+
+    /// The static methods called by the last generated case
+    pub fn static_calls(&self) -> &[StaticCall] {
+        &self.static_calls
+    }
+
+    /// The nonce traced first by the last generated case
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// How many sub-cases the main actions of the last generated case were made up of, see `keep_sub_cases`
+    pub fn sub_case_count(&self) -> usize {
+        self.sub_case_count
+    }
+
+    /// The modes that generated the last case, once for each of its sub-cases, see `case_modes`
+    pub fn case_modes(&self) -> &[GeneratorMode] {
+        &self.case_modes
+    }
+
+    /// The objects and arrays constructed by the last generated case, see `selftest::annotated_listing`
+    pub fn constructions(&self) -> &[String] {
+        &self.constructions
     }
 
-    /// Generate the version for the swf
+    /// The seed of the last generated case's `Math.random`, None unless it was replaced
+    pub fn random_seed(&self) -> Option<i32> {
+        self.random_seed
+    }
+
+    /// The frames ruffle should run the last generated case for, `RUFFLE_FRAME_BUDGET` plus the frames it adds
+    /// after the main actions
+    pub fn frame_budget(&self) -> u32 {
+        self.frame_budget
+    }
+
+    /// Generate the version for the swf, cycling through `SWF_VERSIONS` so each gets the same share of cases
     pub fn swf_version(&mut self) -> u8 {
-        //TODO: versions < 6 seem to hang the official player? maybe some opcodes aren't implemented? We could just add a timeout?
-        let swf_version: u8 = if RANDOM_SWF_VERSION {
-            self.rng.gen_range(6..=32)
-        } else {
-            32
-        };
+        let swf_version = SWF_VERSIONS[self.next_version];
+        self.next_version = (self.next_version + 1) % SWF_VERSIONS.len();
         swf_version
     }
 
@@ -526,11 +5683,21 @@ impl SwfGenerator {
         swf_header
     }
 
-    /// Emit opcodes to trace entire stack
+    /// Emit opcodes to trace entire stack, each value is printed with `__dump(v, 0)`
+    /// The `#PREFIX#` it stops at is always pushed inline, never from the constant pool, so a pool reference that
+    /// is out of range pushes a value to dump rather than ending the dump early
     fn dump_stack(w: &mut Writer<&mut Vec<u8>>) -> Result<(), Box<dyn Error>> {
         let pos = w.output.len();
         w.write_action(&Action::PushDuplicate)?;
-        w.write_action(&Action::Trace)?;
+        w.write_action(&Action::Push(Push {
+            values: vec![Value::Int(0)],
+        }))?;
+        w.write_action(&Action::StackSwap)?;
+        w.write_action(&Action::Push(Push {
+            values: vec![Value::Int(2), Value::Str(DUMP_FUNCTION_NAME.into())],
+        }))?;
+        w.write_action(&Action::CallFunction)?;
+        w.write_action(&Action::Pop)?;
         w.write_action(&Action::Push(Push {
             values: vec![Value::Str("#PREFIX#".into())],
         }))?;
@@ -544,43 +5711,648 @@ impl SwfGenerator {
         Ok(())
     }
 
-    /// Create a new random test case, will return Ok(()) on success or Err(_) on error
-    pub fn next_swf(&mut self, output_data: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
-        // common swf stuff
-        let swf_version = self.swf_version();
-        let swf_header = self.swf_header(swf_version);
-        let mut dag = self.do_action_generator(swf_version);
+    /// Emit opcodes to trace the script-visible state of the clip at each placement depth
+    fn trace_placement(w: &mut Writer<&mut Vec<u8>>) -> Result<(), Box<dyn Error>> {
+        for &depth in PLACEMENT_DEPTHS {
+            // __c = _root.getInstanceAtDepth(depth - 16384)
+            w.write_action(&Action::Push(Push {
+                values: vec![
+                    Value::Str("__c".into()),
+                    Value::Int(i32::from(depth) - 16384),
+                    Value::Int(1),
+                    Value::Str("_root".into()),
+                ],
+            }))?;
+            w.write_action(&Action::GetVariable)?;
+            w.write_action(&Action::Push(Push {
+                values: vec![Value::Str("getInstanceAtDepth".into())],
+            }))?;
+            w.write_action(&Action::CallMethod)?;
+            w.write_action(&Action::SetVariable)?;
 
-        for _ in 0..TESTS_PER_FUZZ_CASE {
-            if DYNAMIC_FUNCTION_FUZZ {
-                dag.dynamic_function_fuzz()?;
-            }
-            if STATIC_FUNCTION_FUZZ {
-                dag.static_function_fuzz()?;
-            }
+            w.write_action(&Action::Push(Push {
+                values: vec![
+                    Value::Str("#PREFIX#".into()),
+                    Value::Str("depth".into()),
+                    Value::Int(depth.into()),
+                    Value::Str("__c".into()),
+                ],
+            }))?;
+            w.write_action(&Action::GetVariable)?;
+            w.write_action(&Action::TypeOf)?;
 
-            //TODO: we need a way to generate objects, e.g point
-            if OPCODE_FUZZ {
-                dag.opcode_fuzz()?;
+            for &property in PLACEMENT_TRACE_PROPERTIES {
+                w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str("__c".into())],
+                }))?;
+                w.write_action(&Action::GetVariable)?;
+                w.write_action(&Action::Push(Push {
+                    values: vec![Value::Str(property.into())],
+                }))?;
+                w.write_action(&Action::GetMember)?;
             }
+
+            // __c.getDepth()
+            w.write_action(&Action::Push(Push {
+                values: vec![Value::Int(0), Value::Str("__c".into())],
+            }))?;
+            w.write_action(&Action::GetVariable)?;
+            w.write_action(&Action::Push(Push {
+                values: vec![Value::Str("getDepth".into())],
+            }))?;
+            w.write_action(&Action::CallMethod)?;
+
+            SwfGenerator::dump_stack(w)?;
+        }
+
+        Ok(())
+    }
+
+    /// Emit opcodes to trace a `display_list::summary_line` for each clip on the root timeline, in a sorted block as
+    /// enumeration order isn't meaningful
+    fn write_display_list_summary(
+        w: &mut Writer<&mut Vec<u8>>,
+        version: u8,
+    ) -> Result<(), Box<dyn Error>> {
+        let push_str = |s: &'static str| {
+            Action::Push(Push {
+                values: vec![Value::Str(SwfStr::from_utf8_str(s))],
+            })
+        };
+        // __c[property]
+        let get_property = |property| {
+            [
+                push_str("__c"),
+                Action::GetVariable,
+                push_str(property),
+                Action::GetMember,
+            ]
+        };
+
+        w.write_action(&push_str(SORTED_BLOCK_BEGIN))?;
+        w.write_action(&Action::Trace)?;
+        // Enumerate2 pushes a null terminator followed by the keys
+        w.write_action(&push_str("_root"))?;
+        w.write_action(&Action::GetVariable)?;
+        w.write_action(&Action::Enumerate2)?;
+
+        let head = assemble(
+            version,
+            &[
+                Action::PushDuplicate,
+                Action::Push(Push {
+                    values: vec![Value::Null],
+                }),
+                Action::Equals2,
+            ],
+        )?;
+        // __k = key; __c = _root[__k]
+        let mut body = assemble(
+            version,
+            &[
+                push_str("__k"),
+                Action::StackSwap,
+                Action::SetVariable,
+                Action::Push(Push {
+                    values: vec![Value::Str("__c".into()), Value::Str("_root".into())],
+                }),
+                Action::GetVariable,
+                push_str("__k"),
+                Action::GetVariable,
+                Action::GetMember,
+                Action::SetVariable,
+            ],
+        )?;
+        // Only the root's own clips, not variables that refer to clips
+        // typeof __c == "movieclip" && __c._parent == _root && __c._name == __k
+        let mut cond = vec![push_str("__c"), Action::GetVariable, Action::TypeOf];
+        cond.extend([push_str("movieclip"), Action::Equals2]);
+        cond.extend(get_property("_parent"));
+        cond.extend([
+            push_str("_root"),
+            Action::GetVariable,
+            Action::Equals2,
+            Action::And,
+        ]);
+        cond.extend(get_property("_name"));
+        cond.extend([
+            push_str("__k"),
+            Action::GetVariable,
+            Action::Equals2,
+            Action::And,
+        ]);
+        // trace("#DL#" + __k + ":" + __c.getDepth() + ":" + __c._x + ":" + __c._y + ":" + __c._visible)
+        let mut trace = vec![
+            Action::Push(Push {
+                values: vec![
+                    Value::Str(DISPLAY_LIST_PREFIX.into()),
+                    Value::Str("__k".into()),
+                ],
+            }),
+            Action::GetVariable,
+            Action::Add2,
+            push_str(":"),
+            Action::Add2,
+            Action::Push(Push {
+                values: vec![Value::Int(0), Value::Str("__c".into())],
+            }),
+            Action::GetVariable,
+            push_str("getDepth"),
+            Action::CallMethod,
+            Action::Add2,
+        ];
+        for property in ["_x", "_y", "_visible"] {
+            trace.extend([push_str(":"), Action::Add2]);
+            trace.extend(get_property(property));
+            trace.push(Action::Add2);
         }
+        trace.push(Action::Trace);
+        body.extend(assemble_if_else(
+            version,
+            &assemble(version, &cond)?,
+            &assemble(version, &trace)?,
+            &[],
+        )?);
+        w.output.extend(assemble_loop_until(version, &head, &body)?);
+        // Drop the terminator
+        w.write_action(&Action::Pop)?;
+
+        w.write_action(&push_str(SORTED_BLOCK_END))?;
+        w.write_action(&Action::Trace)?;
+        Ok(())
+    }
 
+    /// Emit opcodes to log the end of the case and quit the player
+    fn write_case_complete(w: &mut Writer<&mut Vec<u8>>) -> Result<(), Box<dyn Error>> {
         // Log a sentinal so we know that its done
-        dag.w.write_action(&Action::Push(Push {
-            values: vec![Value::Str("#CASE_COMPLETE#".into())],
+        w.write_action(&Action::Push(Push {
+            values: vec![Value::Str(CASE_COMPLETE_SENTINEL.into())],
         }))?;
-        dag.w.write_action(&Action::Trace)?;
+        w.write_action(&Action::Trace)?;
 
-        dag.w.write_action(&Action::GetUrl(GetUrl {
+        w.write_action(&Action::GetUrl(GetUrl {
             target: "_root".into(),
             url: "fscommand:quit".into(),
         }))?;
 
+        Ok(())
+    }
+
+    /// Create a new random test case from the modes enabled in the config, will return Ok(swf_version) on success or Err(_) on error
+    pub fn next_swf(&mut self, output_data: &mut Vec<u8>) -> Result<u8, Box<dyn Error>> {
+        self.generate(&GeneratorMode::enabled(), output_data)
+    }
+
+    /// Create a new random test case made up of the given modes, will return Ok(swf_version) on success or Err(_) on error
+    pub fn generate(
+        &mut self,
+        modes: &[GeneratorMode],
+        output_data: &mut Vec<u8>,
+    ) -> Result<u8, Box<dyn Error>> {
+        let swf_version = match mode_swf_versions(modes) {
+            Some(versions) => versions[self.rng.gen_range(0..versions.len())],
+            None => self.swf_version(),
+        };
+        self.generate_version(swf_version, modes, output_data)?;
+        Ok(swf_version)
+    }
+
+    /// Create a new random test case of the given version made up of the given modes
+    pub fn generate_version(
+        &mut self,
+        swf_version: u8,
+        modes: &[GeneratorMode],
+        output_data: &mut Vec<u8>,
+    ) -> Result<(), Box<dyn Error>> {
+        // common swf stuff
+        let mut swf_header = self.swf_header(swf_version);
+
+        let (sprites, placement_frames) = if modes.contains(&GeneratorMode::Placement) {
+            let mut pg = self.placement_generator(swf_version);
+            (pg.define_sprites(), Some(pg.frames()?))
+        } else {
+            (Vec::new(), None)
+        };
+        let input_case = if modes.contains(&GeneratorMode::Input) {
+            Some(self.input_generator(swf_version).case()?)
+        } else {
+            None
+        };
+        let malformed_case = if modes.contains(&GeneratorMode::MalformedContainer) {
+            Some(self.malformed_generator(swf_version).case()?)
+        } else {
+            None
+        };
+        let binary_data_case = if modes.contains(&GeneratorMode::BinaryData) {
+            Some(self.binary_data_generator(swf_version).case()?)
+        } else {
+            None
+        };
+        let frame_label_layout = if modes.contains(&GeneratorMode::FrameLabel) {
+            Some(self.frame_label_layout(swf_version)?)
+        } else {
+            None
+        };
+        // Multi-frame cases navigate the root's frames, which placement and input cases need for themselves
+        let multi_frame_case = if modes.contains(&GeneratorMode::MultiFrame)
+            && placement_frames.is_none()
+            && input_case.is_none()
+        {
+            Some(self.multi_frame_case(swf_version)?)
+        } else {
+            None
+        };
+        // A pool at the start of tag size cases' tags would change their size
+        self.constant_pool = if CONSTANT_POOL_PUSH
+            && !modes.contains(&GeneratorMode::TagSize)
+            && swf_version >= DUMP_MIN_VERSION
+        {
+            let wide = self.rng.gen_bool(CONSTANT_POOL_WIDE_RATE);
+            PooledStrings::new(true, if wide { CONSTANT_POOL_PADDING } else { 0 })
+        } else {
+            PooledStrings::default()
+        };
+
+        let sub_case_filter = self.sub_case_filter.clone();
+        let mode_weights = self.mode_weights.clone();
+        let mut dag = self.do_action_generator(swf_version);
+        // Before SWF5 there are no functions, so only opcode cases, which trace their results directly, are old enough
+        if swf_version >= DUMP_MIN_VERSION {
+            dag.define_dump_function()?;
+        }
+        let random_seed = if SEEDED_RANDOM && swf_version >= DUMP_MIN_VERSION {
+            Some(dag.define_seeded_random()?)
+        } else {
+            None
+        };
+        if let Some(input_case) = &input_case {
+            dag.w.output.extend_from_slice(&input_case.setup);
+        }
+
+        // Each sub-case picks one of the modes with sub-cases, so a case can mix them
+        let mut sub_case_modes: Vec<GeneratorMode> = modes
+            .iter()
+            .copied()
+            .filter(|mode| mode.has_sub_cases())
+            .collect();
+        let mut case_modes = Vec::with_capacity(TESTS_PER_FUZZ_CASE);
+        let mut op_index = 0;
+        for _ in 0..TESTS_PER_FUZZ_CASE {
+            let mode = match pick_mode(dag.rng, &sub_case_modes, &mode_weights) {
+                Some(mode) => mode,
+                None => break,
+            };
+            let start = dag.w.output.len();
+            let constructions_start = dag.constructions.len();
+            let mut description = match mode {
+                GeneratorMode::DynamicFunction => dag.dynamic_function_fuzz()?,
+                GeneratorMode::StaticFunction => dag.static_function_fuzz()?,
+                //TODO: we need a way to generate objects, e.g point
+                GeneratorMode::Opcode => dag.opcode_fuzz()?,
+                GeneratorMode::Navigation => dag.navigation_fuzz()?,
+                // Placement, input and multi-frame cases happen in their own frames, and the malformed tag and
+                // binary data after the main actions
+                GeneratorMode::Placement
+                | GeneratorMode::Input
+                | GeneratorMode::MultiFrame
+                | GeneratorMode::MalformedContainer
+                | GeneratorMode::BinaryData => unreachable!("{} has no sub-cases", mode.name()),
+                GeneratorMode::ArgCount => dag.arg_count_fuzz()?,
+                GeneratorMode::Version => dag.version_fuzz()?,
+                GeneratorMode::Capabilities => dag.capabilities_fuzz()?,
+                GeneratorMode::MemberChain => dag.member_chain_fuzz()?,
+                GeneratorMode::TargetPath => dag.target_path_fuzz()?,
+                GeneratorMode::StringCompare => dag.string_compare_fuzz()?,
+                GeneratorMode::Function => dag.function_fuzz()?,
+                GeneratorMode::TryCatch => dag.try_catch_fuzz()?,
+                GeneratorMode::MemberAccess => dag.member_access_fuzz()?,
+                GeneratorMode::ProtoChain => dag.proto_chain_fuzz()?,
+                GeneratorMode::ClassHierarchy => dag.class_hierarchy_fuzz()?,
+                GeneratorMode::GlobalFunction => dag.global_function_fuzz()?,
+                GeneratorMode::NumberFormat => dag.number_format_fuzz()?,
+                GeneratorMode::MovieClip => dag.movie_clip_fuzz()?,
+                GeneratorMode::CloneSprite => dag.clone_sprite_fuzz()?,
+                GeneratorMode::FrameLabel => match &frame_label_layout {
+                    Some(layout) => dag.frame_label_fuzz(layout)?,
+                    None => continue,
+                },
+                GeneratorMode::TagSize => {
+                    // A case of huge tags is slow enough to run with just the one
+                    sub_case_modes.retain(|mode| *mode != GeneratorMode::TagSize);
+                    dag.split_at(start);
+                    dag.tag_size_fuzz()?
+                }
+            };
+            if mode.catches_errors()
+                && swf_version >= TRY_MIN_VERSION
+                && !dag.catch_errors(start)?
+            {
+                description.push_str(" (too long to catch errors)");
+            }
+            let marker_len =
+                dag.insert_op_marker(start, op_index, &mode_description(mode, &description))?;
+            if mode == GeneratorMode::TagSize {
+                // Give the sub-case a tag of its own, without the marker
+                dag.split_at(start + marker_len);
+                dag.split_at(dag.w.output.len());
+            } else {
+                dag.split_if_full();
+            }
+            // Left out sub-cases keep their index, so the markers of the rest match the full case
+            if let Some(sub_cases) = &sub_case_filter {
+                if !sub_cases.contains(&op_index) {
+                    dag.w.output.truncate(start);
+                    dag.splits.retain(|&split| split <= start);
+                    dag.constructions.truncate(constructions_start);
+                }
+            }
+            case_modes.push(mode);
+            op_index += 1;
+        }
+        let sub_case_count = op_index;
+        case_modes.extend(
+            [
+                (GeneratorMode::Placement, placement_frames.is_some()),
+                (GeneratorMode::Input, input_case.is_some()),
+                (GeneratorMode::MultiFrame, multi_frame_case.is_some()),
+                (GeneratorMode::MalformedContainer, malformed_case.is_some()),
+                (GeneratorMode::BinaryData, binary_data_case.is_some()),
+            ]
+            .into_iter()
+            .filter(|(_, in_case)| *in_case)
+            .map(|(mode, _)| mode),
+        );
+
+        // Multi-frame cases complete from the setup's enter frame handler, however their frames navigate
+        if let Some(multi_frame_case) = &multi_frame_case {
+            let mut complete_actions = Vec::new();
+            let w = &mut Writer::new(&mut complete_actions, swf_version);
+            if DISPLAY_LIST_SUMMARY {
+                SwfGenerator::write_display_list_summary(w, swf_version)?;
+            }
+            SwfGenerator::write_case_complete(w)?;
+            dag.w.output.extend_from_slice(&assemble_op_marker(
+                swf_version,
+                op_index,
+                &mode_description(GeneratorMode::MultiFrame, &multi_frame_case.description),
+            )?);
+            dag.w
+                .output
+                .extend(assemble_multi_frame_setup(swf_version, &complete_actions)?);
+            op_index += 1;
+            dag.split_if_full();
+        }
+
+        // The malformed tag's marker is the last of the main actions, so output after it shows how the players coped
+        if let Some(malformed_case) = &malformed_case {
+            dag.w.output.extend_from_slice(&assemble_op_marker(
+                swf_version,
+                op_index,
+                &mode_description(
+                    GeneratorMode::MalformedContainer,
+                    &malformed_case.description,
+                ),
+            )?);
+            op_index += 1;
+        }
+        if let Some(binary_data_case) = &binary_data_case {
+            dag.w.output.extend_from_slice(&assemble_op_marker(
+                swf_version,
+                op_index,
+                &mode_description(GeneratorMode::BinaryData, &binary_data_case.description),
+            )?);
+            dag.w.output.extend_from_slice(&binary_data_case.actions);
+            op_index += 1;
+            dag.split_if_full();
+        }
+
+        // Placement and input frames run after the main actions, a case with both shares the frames
+        let (mut placement_tags, placement_actions) = match placement_frames {
+            Some(PlacementFrames { tags, actions }) => (tags, actions),
+            None => (Vec::new(), Vec::new()),
+        };
+        let input_actions = input_case
+            .as_ref()
+            .map(|input_case| input_case.actions.as_slice())
+            .unwrap_or_default();
+        let frame_count = placement_actions.len().max(input_actions.len());
+        let mut frame_actions = Vec::with_capacity(frame_count);
+        for frame in 0..frame_count {
+            // These run after the main actions, so their markers follow on
+            let mut actions =
+                assemble_op_marker(swf_version, op_index, &format!("frame {}", frame + 1))?;
+            op_index += 1;
+            actions.extend(placement_actions.get(frame).into_iter().flatten());
+            actions.extend(input_actions.get(frame).into_iter().flatten());
+            frame_actions.push(actions);
+        }
+
+        // With extra frames the case only completes once the last frame has been traced, and with a malformed tag
+        // once the tags after it have run
+        let mut complete_actions = Vec::new();
+        if multi_frame_case.is_none() {
+            let mut complete_writer = Writer::new(&mut complete_actions, swf_version);
+            let w = if frame_count > 0 || malformed_case.is_some() {
+                &mut complete_writer
+            } else {
+                &mut dag.w
+            };
+            if DISPLAY_LIST_SUMMARY {
+                SwfGenerator::write_display_list_summary(w, swf_version)?;
+            }
+            SwfGenerator::write_case_complete(w)?;
+        }
+
+        let (mut tags, scaling_grid) = self.extra_tags();
+        // A movie has at most one Metadata tag, the seed takes the place of the one from the IDE
+        let case_seed = self.case_seed.take();
+        let case_seed_metadata = case_seed.map(case_seed_metadata);
+        if let Some(metadata) = &case_seed_metadata {
+            tags.retain(|tag| !matches!(tag, Tag::Metadata(_)));
+            tags.insert(0, Tag::Metadata(SwfStr::from_utf8_str(metadata)));
+        }
+        tags.extend(sprites);
+        tags.extend(scaling_grid);
+        // Defined before any actions run, so that the main actions can attach it
+        if let Some(binary_data_case) = &binary_data_case {
+            tags.push(Tag::DefineBinaryData(DefineBinaryData {
+                id: BINARY_DATA_ID,
+                data: &binary_data_case.data,
+            }));
+            if let Some(export_name) = binary_data_case.export_name {
+                tags.push(Tag::ExportAssets(vec![ExportedAsset {
+                    id: BINARY_DATA_ID,
+                    name: SwfStr::from_utf8_str(export_name),
+                }]));
+            }
+        }
+        // Placed before any actions run, so that the main actions can navigate it
+        if let Some(layout) = &frame_label_layout {
+            tags.push(layout.define_sprite());
+            tags.push(layout.place_sprite());
+        }
+        if modes.contains(&GeneratorMode::TargetPath) {
+            tags.extend(target_path_tags());
+        }
+        tags.extend(self.stage.tags());
+        // Picked last so that it doesn't change the rest of the case, its tag runs before any other actions
+        self.nonce = self.rng.gen();
+        let nonce_line = nonce_line(self.nonce);
+        let case_seed_line = case_seed.map(case_seed_line);
+        let nonce_actions = assemble(
+            swf_version,
+            &[&nonce_line]
+                .into_iter()
+                .chain(&case_seed_line)
+                .flat_map(|line| {
+                    [
+                        Action::Push(Push {
+                            values: vec![Value::Str(SwfStr::from_utf8_str(line))],
+                        }),
+                        Action::Trace,
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        )?;
+        tags.push(Tag::DoAction(&nonce_actions));
+        // Each tag starts with the constant pool, so none of them relies on the pool carrying over from the one before
+        let constant_pool = self.constant_pool.assemble(&self.strings, swf_version)?;
+        let mut main_actions = Vec::new();
+        let mut tag_start = 0;
+        for &tag_end in self
+            .do_action_splits
+            .iter()
+            .chain([&self.do_action_bytes.len()])
+        {
+            if tag_end > tag_start {
+                let actions = &self.do_action_bytes[tag_start..tag_end];
+                main_actions.push(if constant_pool.is_empty() {
+                    Cow::Borrowed(actions)
+                } else {
+                    Cow::Owned([&constant_pool, actions].concat())
+                });
+            }
+            tag_start = tag_end;
+        }
+        tags.extend(main_actions.iter().map(|actions| Tag::DoAction(actions)));
+        let malformed_at = tags.len();
+        tags.push(Tag::EnableDebugger(SwfStr::from_utf8_str(
+            "$1$5C$2dKTbwjNlJlNSvp9qvD651",
+        )));
+        if frame_count > 0 {
+            for (frame, actions) in frame_actions.iter().enumerate() {
+                if let Some(placements) = placement_tags.get_mut(frame) {
+                    tags.append(placements);
+                }
+                tags.push(Tag::DoAction(actions));
+                tags.push(Tag::ShowFrame);
+            }
+            tags.push(Tag::DoAction(&complete_actions));
+            tags.push(Tag::ShowFrame);
+            swf_header.num_frames = (frame_count + 1) as u16;
+        } else if let Some(multi_frame_case) = &multi_frame_case {
+            tags.push(Tag::ShowFrame);
+            tags.extend(multi_frame_case.frame_tags());
+            swf_header.num_frames = multi_frame_case.num_frames();
+        } else if malformed_case.is_some() {
+            tags.push(Tag::DoAction(&complete_actions));
+        }
+        self.frame_budget = RUFFLE_FRAME_BUDGET + frame_count as u32;
+        if multi_frame_case.is_some() {
+            self.frame_budget += MULTI_FRAME_TICKS as u32;
+        }
+        self.random_seed = random_seed;
+        self.sub_case_count = sub_case_count;
+        self.case_modes = case_modes;
+        if input_case.is_some() {
+            swf_header.frame_rate = Fixed8::from_f32(INPUT_FRAME_RATE);
+        }
+
         // Create the swf
+        match &malformed_case {
+            Some(malformed_case) => {
+                let (before, after) = tags.split_at(malformed_at);
+                let mut tag_data = Vec::new();
+                for tag in before {
+                    raw::write_tag(&mut tag_data, tag, swf_version)?;
+                }
+                tag_data.extend_from_slice(&malformed_case.tag_data);
+                for tag in after.iter().chain([&Tag::End]) {
+                    raw::write_tag(&mut tag_data, tag, swf_version)?;
+                }
+                raw::write_swf_raw(&swf_header, &tag_data, output_data)?;
+            }
+            // Catches a case mixing AVM1 and AVM2, which the players would handle differently
+            None => swf::write::write_swf_with_options(
+                &swf_header,
+                &tags,
+                output_data,
+                &WriteOptions { validate: true },
+            )?,
+        }
+
+        if let Some(input_case) = input_case {
+            self.input_script = input_case.script;
+        }
+
+        Ok(())
+    }
+
+    /// Tags that scripts can't observe, added to some cases to check that both players handle them.
+    /// Returns the tags that belong at the start of the movie, and a DefineScalingGrid that belongs after the sprites
+    fn extra_tags(&mut self) -> (Vec<Tag<'static>>, Option<Tag<'static>>) {
+        let mut tags = Vec::new();
+        if self.rng.gen_bool(EXTRA_TAG_RATE) {
+            tags.push(Tag::Metadata(SwfStr::from_utf8_str(METADATA_XML)));
+        }
+        if self.rng.gen_bool(EXTRA_TAG_RATE) {
+            let random_labels = |rng: &mut StdRng| {
+                (0..rng.gen_range(0..=3))
+                    .map(|_| FrameLabelData {
+                        frame_num: rng.gen_range(0..=PLACEMENT_FRAME_COUNT as u32 + 1),
+                        label: SwfStr::from_utf8_str(
+                            FRAME_LABEL_CORPUS[rng.gen_range(0..FRAME_LABEL_CORPUS.len())],
+                        ),
+                    })
+                    .collect()
+            };
+            tags.push(Tag::DefineSceneAndFrameLabelData(
+                DefineSceneAndFrameLabelData {
+                    scenes: random_labels(&mut self.rng),
+                    frame_labels: random_labels(&mut self.rng),
+                },
+            ));
+        }
+
+        // The grid may name a sprite that doesn't exist
+        let scaling_grid = self.rng.gen_bool(EXTRA_TAG_RATE).then(|| {
+            let x_min = self.rng.gen_range(-200..200);
+            let y_min = self.rng.gen_range(-200..200);
+            Tag::DefineScalingGrid {
+                id: self.rng.gen_range(1..=UNDEFINED_CHARACTER),
+                splitter_rect: Rectangle {
+                    x_min: Twips::new(x_min),
+                    x_max: Twips::new(x_min + self.rng.gen_range(0..400)),
+                    y_min: Twips::new(y_min),
+                    y_max: Twips::new(y_min + self.rng.gen_range(0..400)),
+                },
+            }
+        });
+
+        (tags, scaling_grid)
+    }
+
+    /// Create a case that does nothing but complete, used to check that the player logs traces at all
+    pub fn smoke_test_swf(&mut self, output_data: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        let swf_version = self.swf_version();
+        let swf_header = self.swf_header(swf_version);
+        let mut actions = Vec::new();
+        SwfGenerator::write_case_complete(&mut Writer::new(&mut actions, swf_version))?;
+
         swf::write_swf(
             &swf_header,
             &[
-                Tag::DoAction(self.do_action_bytes.as_slice()),
+                Tag::DoAction(&actions),
                 Tag::EnableDebugger(SwfStr::from_utf8_str("$1$5C$2dKTbwjNlJlNSvp9qvD651")),
             ],
             output_data,
@@ -0,0 +1,147 @@
+//! Turning the bytes a player traced into text. Flash can trace bytes that aren't valid UTF-8, e.g. Windows-1252
+//! strings in SWF5 or the fuzzer's random byte strings, so its runner captures the output as bytes and converts it
+//! with `escape_trace` once the case has run. Each byte of an invalid sequence becomes `\xNN` and a backslash becomes
+//! `\\`, so two flash outputs only escape to the same text if they were the same bytes.
+//!
+//! Ruffle's side isn't bytes: ruffle decodes a SWF's strings itself, as Windows-1252 for SWF5 and lossily as UTF-8
+//! after, and logs the decoded `&str`. Its output never has an invalid sequence to escape, only backslashes, which
+//! `push_text` escapes as each trace is logged. So comparing the outputs compares flash's raw bytes with ruffle's
+//! decoded text, and bytes flash traced that aren't valid UTF-8 always differ from ruffle, even where ruffle decoded
+//! them correctly, e.g. flash's `\x93` against ruffle's `\u{201c}`
+use std::fmt::Write;
+
+/// Append valid UTF-8 to `escaped`, escaping backslashes
+pub(crate) fn push_text(escaped: &mut String, text: &str) {
+    for (i, part) in text.split('\\').enumerate() {
+        if i > 0 {
+            escaped.push_str("\\\\");
+        }
+        escaped.push_str(part);
+    }
+}
+
+/// Flash's traced bytes as text, see the module docs for the escaping
+pub fn escape_trace(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(text) => {
+                push_text(&mut escaped, text);
+                return escaped;
+            }
+            Err(e) => {
+                let (valid, after) = rest.split_at(e.valid_up_to());
+                push_text(&mut escaped, std::str::from_utf8(valid).unwrap());
+                // A sequence cut short by the end of the output has no error length
+                let invalid_len = e.error_len().unwrap_or(after.len());
+                for byte in &after[..invalid_len] {
+                    let _ = write!(escaped, "\\x{:02x}", byte);
+                }
+                rest = &after[invalid_len..];
+            }
+        }
+    }
+}
+
+/// Does the traced output contain `text`, without converting it
+pub fn contains_text(bytes: &[u8], text: &str) -> bool {
+    bytes
+        .windows(text.len())
+        .any(|window| window == text.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff_class::{classify, DiffClass};
+    use crate::flash_projector_runner::flash_log_content;
+    use crate::normalize::normalize;
+    use crate::ruffle_runner::StringLogger;
+    use crate::sentinel::{nonce_line, RunUntil, CASE_COMPLETE_SENTINEL};
+    use crate::NUMERIC_NOISE_EPSILON;
+    use ruffle_core::backend::log::LogBackend;
+
+    #[test]
+    fn escape_invalid_utf8() {
+        assert_eq!(escape_trace(b"plain"), "plain");
+        assert_eq!(
+            escape_trace("\u{201c}quoted\u{201d}".as_bytes()),
+            "\u{201c}quoted\u{201d}"
+        );
+        // A Windows-1252 left double quote
+        assert_eq!(escape_trace(b"\x93quoted\x94"), "\\x93quoted\\x94");
+        // A lead byte followed by a byte that can't continue it, and a sequence cut short by the end
+        assert_eq!(
+            escape_trace(b"a\xe2(\xa1b\xe2\x80"),
+            "a\\xe2(\\xa1b\\xe2\\x80"
+        );
+        // The escape of a byte and its escape as text don't collide
+        assert_eq!(escape_trace(b"\\x93"), "\\\\x93");
+        assert_ne!(escape_trace(b"\\x93"), escape_trace(b"\x93"));
+    }
+
+    #[test]
+    fn ruffle_escapes_like_flash() {
+        let ruffle = StringLogger::default();
+        ruffle.avm_trace("a\\x93");
+        ruffle.avm_trace("\u{201c}");
+        assert_eq!(
+            ruffle.__fuzz__get_log_string(),
+            escape_trace("a\\x93\n\u{201c}\n".as_bytes())
+        );
+    }
+
+    #[test]
+    fn compare_non_utf8_traces() {
+        let nonce = 0x1234;
+        let traces = |traced: &[u8]| {
+            let mut output = format!("{}\n", nonce_line(nonce)).into_bytes();
+            output.extend_from_slice(traced);
+            output
+                .extend_from_slice(format!("\n{}\nleft over\n", CASE_COMPLETE_SENTINEL).as_bytes());
+            output
+        };
+
+        // Flash traces the SWF5 string's bytes as they are, an invalid sequence included
+        let flash = flash_log_content(
+            &traces(b"\x93hi\x94 \xc3("),
+            RunUntil::Sentinel,
+            Some(nonce),
+        )
+        .unwrap();
+        assert_eq!(
+            flash,
+            format!(
+                "{}\n\\x93hi\\x94 \\xc3(\n{}\n",
+                nonce_line(nonce),
+                CASE_COMPLETE_SENTINEL
+            )
+        );
+
+        // Ruffle decodes it as Windows-1252, so the difference is kept rather than both sides being replaced
+        let ruffle = StringLogger::default();
+        ruffle.avm_trace(&nonce_line(nonce));
+        ruffle.avm_trace("\u{201c}hi\u{201d} \u{c3}(");
+        ruffle.avm_trace(CASE_COMPLETE_SENTINEL);
+        let ruffle = ruffle.__fuzz__get_log_string();
+        assert_ne!(normalize(&ruffle), normalize(&flash));
+        assert_eq!(
+            classify(
+                &normalize(&ruffle),
+                &normalize(&flash),
+                NUMERIC_NOISE_EPSILON
+            ),
+            DiffClass::Textual
+        );
+
+        // The same bytes on both sides compare equal
+        let agreeing = flash_log_content(
+            &traces("\u{201c}hi\u{201d} \u{c3}(".as_bytes()),
+            RunUntil::Sentinel,
+            Some(nonce),
+        )
+        .unwrap();
+        assert_eq!(normalize(&ruffle), normalize(&agreeing));
+    }
+}
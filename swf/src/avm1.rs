@@ -1,4 +1,5 @@
 pub(crate) mod opcode;
 pub mod read;
 pub mod types;
+#[cfg(feature = "std")]
 pub mod write;
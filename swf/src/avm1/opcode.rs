@@ -1,3 +1,5 @@
+use crate::prelude::*;
+
 #[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
 pub enum OpCode {
     End = 0x00,
@@ -1,7 +1,8 @@
 use crate::avm1::{opcode::OpCode, types::*};
 use crate::error::{Error, Result};
 use crate::extensions::ReadSwfExt;
-use std::num::NonZeroU8;
+use crate::prelude::*;
+use core::num::NonZeroU8;
 
 pub struct Reader<'a> {
     input: &'a [u8],
@@ -42,13 +43,6 @@ impl<'a> Reader<'a> {
         &mut self.input
     }
 
-    #[inline]
-    fn read_f64_me(&mut self) -> Result<f64> {
-        // Flash weirdly stores (some?) f64 as two LE 32-bit chunks.
-        // First word is the hi-word, second word is the lo-word.
-        Ok(f64::from_bits(self.read_u64()?.rotate_left(32)))
-    }
-
     #[inline]
     pub fn read_action(&mut self) -> Result<Action<'a>> {
         let (opcode, mut length) = self.read_opcode_and_length()?;
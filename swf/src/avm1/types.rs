@@ -1,6 +1,7 @@
+use crate::prelude::*;
 use crate::string::SwfStr;
 use bitflags::bitflags;
-use std::num::NonZeroU8;
+use core::num::NonZeroU8;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Action<'a> {
@@ -292,16 +293,32 @@ pub struct Push<'a> {
     pub values: Vec<Value<'a>>,
 }
 
+/// A value pushed by `ActionPush`, encoded as a type byte followed by the payload.
+///
+/// Floats are kept bit-for-bit, including NaN payloads and -0.0, so compare their bits rather than
+/// using `==`. Reading and writing a value always gives back the same value, and the same bytes for
+/// every encoding that `Writer` produces. Two encodings aren't kept byte-for-byte: a `Bool` byte
+/// other than 0 or 1 is read as `true` and written back as 1, and a type 9 `ConstantPool` index
+/// under 256 is written back as type 8.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value<'a> {
+    /// Type 3, no payload.
     Undefined,
+    /// Type 2, no payload.
     Null,
+    /// Type 5, a u8 that is true when non-zero.
     Bool(bool),
+    /// Type 7, a little endian i32.
     Int(i32),
+    /// Type 1, a little endian f32.
     Float(f32),
+    /// Type 6, an f64 stored as two little endian u32s, high word first.
     Double(f64),
+    /// Type 0, a null terminated string.
     Str(&'a SwfStr),
+    /// Type 4, a u8 register number.
     Register(u8),
+    /// Type 8 with a u8 index, or type 9 with a little endian u16 index for indices over 255.
     ConstantPool(u16),
 }
 
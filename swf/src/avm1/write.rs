@@ -1,9 +1,12 @@
 use crate::avm1::opcode::OpCode;
 use crate::avm1::types::*;
-use crate::string::SwfStr;
-use crate::write::SwfWriteExt;
-use byteorder::{LittleEndian, WriteBytesExt};
-use std::io::{Result, Write};
+use crate::extensions::WriteSwfExt;
+use std::io::{Error, ErrorKind, Result, Write};
+
+/// Convert a length or count for a u16 field, erroring rather than silently truncating it
+fn u16_len(len: usize, error: &'static str) -> Result<u16> {
+    u16::try_from(len).map_err(|_| Error::new(ErrorKind::InvalidData, error))
+}
 
 pub struct Writer<W: Write> {
     pub output: W,
@@ -11,56 +14,12 @@ pub struct Writer<W: Write> {
     version: u8,
 }
 
-impl<W: Write> SwfWriteExt for Writer<W> {
-    #[inline]
-    fn write_u8(&mut self, n: u8) -> Result<()> {
-        self.output.write_u8(n)
-    }
-
-    #[inline]
-    fn write_u16(&mut self, n: u16) -> Result<()> {
-        self.output.write_u16::<LittleEndian>(n)
-    }
+impl<W: Write> WriteSwfExt for Writer<W> {
+    type Output = W;
 
     #[inline]
-    fn write_u32(&mut self, n: u32) -> Result<()> {
-        self.output.write_u32::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_u64(&mut self, n: u64) -> Result<()> {
-        self.output.write_u64::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_i8(&mut self, n: i8) -> Result<()> {
-        self.output.write_i8(n)
-    }
-
-    #[inline]
-    fn write_i16(&mut self, n: i16) -> Result<()> {
-        self.output.write_i16::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_i32(&mut self, n: i32) -> Result<()> {
-        self.output.write_i32::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_f32(&mut self, n: f32) -> Result<()> {
-        self.output.write_f32::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_f64(&mut self, n: f64) -> Result<()> {
-        self.output.write_f64::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_string(&mut self, s: &'_ SwfStr) -> Result<()> {
-        self.output.write_all(s.as_bytes())?;
-        self.write_u8(0)
+    fn as_mut_writer(&mut self) -> &mut W {
+        &mut self.output
     }
 }
 
@@ -69,13 +28,6 @@ impl<W: Write> Writer<W> {
         Self { output, version }
     }
 
-    #[inline]
-    fn write_f64_me(&mut self, n: f64) -> Result<()> {
-        // Flash weirdly stores f64 as two LE 32-bit chunks.
-        // First word is the hi-word, second word is the lo-word.
-        self.write_u64(n.to_bits().rotate_left(32))
-    }
-
     pub fn write_action(&mut self, action: &Action) -> Result<()> {
         match action {
             Action::Add => self.write_small_action(OpCode::Add),
@@ -193,7 +145,8 @@ impl<W: Write> Writer<W> {
             "Opcodes less than 0x80 must have length 0"
         );
         if opcode >= 0x80 {
-            self.write_u16(length as u16)?;
+            // A truncated length would make the player read the rest of the action as new actions
+            self.write_u16(u16_len(length, "Action is too long")?)?;
         }
         Ok(())
     }
@@ -206,9 +159,9 @@ impl<W: Write> Writer<W> {
     fn write_constant_pool(&mut self, action: &ConstantPool) -> Result<()> {
         let len = 2 + action.strings.iter().map(|c| c.len() + 1).sum::<usize>();
         self.write_action_header(OpCode::ConstantPool, len)?;
-        self.write_u16(action.strings.len() as u16)?;
+        self.write_u16(u16_len(action.strings.len(), "Too many constants")?)?;
         for string in &action.strings {
-            self.write_string(string)?;
+            self.write_str(string)?;
         }
         Ok(())
     }
@@ -222,12 +175,12 @@ impl<W: Write> Writer<W> {
             + action.params.iter().map(|p| p.len() + 1).sum::<usize>()
             + 2;
         self.write_action_header(OpCode::DefineFunction, len)?;
-        self.write_string(action.name)?;
-        self.write_u16(action.params.len() as u16)?;
+        self.write_str(action.name)?;
+        self.write_u16(u16_len(action.params.len(), "Too many parameters")?)?;
         for param in &action.params {
-            self.write_string(param)?;
+            self.write_str(param)?;
         }
-        self.write_u16(action.actions.len() as u16)?;
+        self.write_u16(u16_len(action.actions.len(), "Function body is too long")?)?;
         self.output.write_all(action.actions)?;
         Ok(())
     }
@@ -243,23 +196,23 @@ impl<W: Write> Writer<W> {
                 .sum::<usize>()
             + 4;
         self.write_action_header(OpCode::DefineFunction2, len)?;
-        self.write_string(action.name)?;
-        self.write_u16(action.params.len() as u16)?;
+        self.write_str(action.name)?;
+        self.write_u16(u16_len(action.params.len(), "Too many parameters")?)?;
         self.write_u8(action.register_count)?;
         self.write_u16(action.flags.bits())?;
         for param in &action.params {
             self.write_u8(param.register_index.map(|n| n.get()).unwrap_or_default())?;
-            self.write_string(param.name)?;
+            self.write_str(param.name)?;
         }
-        self.write_u16(action.actions.len() as u16)?;
+        self.write_u16(u16_len(action.actions.len(), "Function body is too long")?)?;
         self.output.write_all(action.actions)?;
         Ok(())
     }
 
     fn write_get_url(&mut self, action: &GetUrl) -> Result<()> {
         self.write_action_header(OpCode::GetUrl, action.url.len() + action.target.len() + 2)?;
-        self.write_string(action.url)?;
-        self.write_string(action.target)?;
+        self.write_str(action.url)?;
+        self.write_str(action.target)?;
         Ok(())
     }
 
@@ -289,7 +242,7 @@ impl<W: Write> Writer<W> {
 
     fn write_goto_label(&mut self, action: &GotoLabel) -> Result<()> {
         self.write_action_header(OpCode::GotoLabel, action.label.len() + 1)?;
-        self.write_string(action.label)?;
+        self.write_str(action.label)?;
         Ok(())
     }
 
@@ -335,7 +288,7 @@ impl<W: Write> Writer<W> {
         match *value {
             Value::Str(string) => {
                 self.write_u8(0)?;
-                self.write_string(string)?;
+                self.write_str(string)?;
             }
             Value::Float(v) => {
                 self.write_u8(1)?;
@@ -378,7 +331,7 @@ impl<W: Write> Writer<W> {
 
     fn write_set_target(&mut self, action: &SetTarget) -> Result<()> {
         self.write_action_header(OpCode::SetTarget, action.target.len() + 1)?;
-        self.write_string(action.target)?;
+        self.write_str(action.target)?;
         Ok(())
     }
 
@@ -406,21 +359,21 @@ impl<W: Write> Writer<W> {
         self.write_u8(flags.bits())?;
 
         let try_size = action.try_body.len();
-        self.write_u16(try_size as u16)?;
+        self.write_u16(u16_len(try_size, "Try body is too long")?)?;
 
         let catch_size = action
             .catch_body
             .as_ref()
             .map_or(0, |(_, catch_body)| catch_body.len());
-        self.write_u16(catch_size as u16)?;
+        self.write_u16(u16_len(catch_size, "Catch body is too long")?)?;
 
         let finally_size = action
             .finally_body
             .map_or(0, |finally_body| finally_body.len());
-        self.write_u16(finally_size as u16)?;
+        self.write_u16(u16_len(finally_size, "Finally body is too long")?)?;
 
         match action.catch_body {
-            Some((CatchVar::Var(name), _)) => self.write_string(name)?,
+            Some((CatchVar::Var(name), _)) => self.write_str(name)?,
             Some((CatchVar::Register(i), _)) => self.write_u8(i)?,
             None => self.write_u8(0)?,
         }
@@ -452,7 +405,7 @@ impl<W: Write> Writer<W> {
 
     fn write_with(&mut self, action: &With) -> Result<()> {
         self.write_action_header(OpCode::With, 2)?;
-        self.write_u16(action.actions.len() as u16)?;
+        self.write_u16(u16_len(action.actions.len(), "With body is too long")?)?;
         self.output.write_all(action.actions)?;
         Ok(())
     }
@@ -467,6 +420,7 @@ impl<W: Write> Writer<W> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::avm1::read::Reader;
     use crate::test_data;
 
     #[test]
@@ -483,4 +437,146 @@ mod tests {
             );
         }
     }
+
+    /// Floats compare by bits, so NaN payloads and -0.0 have to survive the round trip too
+    fn same_value(a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            (Value::Double(a), Value::Double(b)) => a.to_bits() == b.to_bits(),
+            _ => a == b,
+        }
+    }
+
+    fn write_push(values: Vec<Value>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        Writer::new(&mut bytes, 5)
+            .write_action(&Action::Push(Push { values }))
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn push_value_round_trip() {
+        let floats = [
+            0.0,
+            -0.0,
+            1.5,
+            f32::MIN_POSITIVE,
+            // Denormals
+            f32::from_bits(1),
+            f32::from_bits(0x807f_ffff),
+            f32::MAX,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NAN,
+            // Signalling and negative NaNs with payloads
+            f32::from_bits(0x7fa0_0001),
+            f32::from_bits(0xffc1_2345),
+        ];
+        let doubles = [
+            0.0,
+            -0.0,
+            1.5,
+            f64::MIN_POSITIVE,
+            f64::from_bits(1),
+            f64::from_bits(0x800f_ffff_ffff_ffff),
+            f64::MAX,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+            f64::from_bits(0x7ff0_0000_0000_0001),
+            f64::from_bits(0xfff8_dead_beef_0001),
+        ];
+        let mut values = vec![
+            Value::Undefined,
+            Value::Null,
+            Value::Bool(false),
+            Value::Bool(true),
+            Value::Int(0),
+            Value::Int(-1),
+            Value::Int(i32::MIN),
+            Value::Int(i32::MAX),
+            Value::Str("".into()),
+            Value::Str("test".into()),
+            Value::Register(0),
+            Value::Register(255),
+            Value::ConstantPool(0),
+            Value::ConstantPool(255),
+            Value::ConstantPool(256),
+            Value::ConstantPool(u16::MAX),
+        ];
+        values.extend(floats.iter().map(|f| Value::Float(*f)));
+        values.extend(doubles.iter().map(|d| Value::Double(*d)));
+
+        // Each value on its own, then all of them in a single push
+        let pushes = values
+            .iter()
+            .map(|value| vec![value.clone()])
+            .chain(std::iter::once(values.clone()));
+        for push in pushes {
+            let bytes = write_push(push.clone());
+            let read = match Reader::new(&bytes, 5).read_action().unwrap() {
+                Action::Push(read) => read,
+                action => panic!("Expected Push, got {:?}", action),
+            };
+            assert_eq!(read.values.len(), push.len());
+            for (read, written) in read.values.iter().zip(&push) {
+                assert!(
+                    same_value(read, written),
+                    "Read {:?}, wrote {:?}",
+                    read,
+                    written
+                );
+            }
+            assert_eq!(write_push(read.values), bytes);
+        }
+    }
+
+    #[test]
+    fn push_value_encoding() {
+        assert_eq!(
+            write_push(vec![Value::Float(-0.0)]),
+            [0x96, 5, 0, 1, 0, 0, 0, 0x80]
+        );
+        assert_eq!(
+            write_push(vec![Value::Float(f32::from_bits(0x7fa0_0001))]),
+            [0x96, 5, 0, 1, 0x01, 0, 0xa0, 0x7f]
+        );
+        // High word first
+        assert_eq!(
+            write_push(vec![Value::Double(-0.0)]),
+            [0x96, 9, 0, 6, 0, 0, 0, 0x80, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            write_push(vec![Value::Double(f64::from_bits(0x7ff0_0000_0000_0001))]),
+            [0x96, 9, 0, 6, 0, 0, 0xf0, 0x7f, 1, 0, 0, 0]
+        );
+        assert_eq!(write_push(vec![Value::Register(255)]), [0x96, 2, 0, 4, 255]);
+        assert_eq!(
+            write_push(vec![Value::ConstantPool(255), Value::ConstantPool(256)]),
+            [0x96, 5, 0, 8, 255, 9, 0, 1]
+        );
+    }
+
+    #[test]
+    fn push_value_canonical_encoding() {
+        // A bool byte other than 1 and a type 9 index under 256 are both written back in the usual form
+        let bytes = [0x96, 5, 0, 5, 2, 9, 5, 0];
+        let read = match Reader::new(&bytes, 5).read_action().unwrap() {
+            Action::Push(read) => read,
+            action => panic!("Expected Push, got {:?}", action),
+        };
+        assert_eq!(read.values, [Value::Bool(true), Value::ConstantPool(5)]);
+        assert_eq!(write_push(read.values), [0x96, 4, 0, 5, 1, 8, 5]);
+    }
+
+    #[test]
+    fn write_action_too_long() {
+        let long = "a".repeat(usize::from(u16::MAX));
+        let mut bytes = Vec::new();
+        let result = Writer::new(&mut bytes, 5).write_action(&Action::Push(Push {
+            values: vec![Value::Str(long.as_str().into())],
+        }));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
 }
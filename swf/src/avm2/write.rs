@@ -1,64 +1,18 @@
 use crate::avm2::opcode::OpCode;
 use crate::avm2::types::*;
-use crate::string::SwfStr;
-use crate::write::SwfWriteExt;
-use byteorder::{LittleEndian, WriteBytesExt};
-use std::io::{self, Result, Write};
+use crate::extensions::WriteSwfExt;
+use std::io::{Result, Write};
 
 pub struct Writer<W: Write> {
     output: W,
 }
 
-impl<W: Write> SwfWriteExt for Writer<W> {
-    #[inline]
-    fn write_u8(&mut self, n: u8) -> io::Result<()> {
-        self.output.write_u8(n)
-    }
-
-    #[inline]
-    fn write_u16(&mut self, n: u16) -> io::Result<()> {
-        self.output.write_u16::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_u32(&mut self, n: u32) -> io::Result<()> {
-        self.output.write_u32::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_u64(&mut self, n: u64) -> io::Result<()> {
-        self.output.write_u64::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_i8(&mut self, n: i8) -> io::Result<()> {
-        self.output.write_i8(n)
-    }
+impl<W: Write> WriteSwfExt for Writer<W> {
+    type Output = W;
 
     #[inline]
-    fn write_i16(&mut self, n: i16) -> io::Result<()> {
-        self.output.write_i16::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_i32(&mut self, n: i32) -> io::Result<()> {
-        self.output.write_i32::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_f32(&mut self, n: f32) -> io::Result<()> {
-        self.output.write_f32::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_f64(&mut self, n: f64) -> io::Result<()> {
-        self.output.write_f64::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_string(&mut self, s: &'_ SwfStr) -> io::Result<()> {
-        self.output.write_all(s.as_bytes())?;
-        self.write_u8(0)
+    fn as_mut_writer(&mut self) -> &mut W {
+        &mut self.output
     }
 }
 
@@ -139,7 +93,7 @@ impl<W: Write> Writer<W> {
         self.write_u30(i.0)
     }
 
-    fn write_string(&mut self, s: &str) -> Result<()> {
+    fn write_str(&mut self, s: &str) -> Result<()> {
         self.write_u30(s.len() as u32)?;
         self.output.write_all(s.as_bytes())?;
         Ok(())
@@ -176,7 +130,7 @@ impl<W: Write> Writer<W> {
         if !constant_pool.strings.is_empty() {
             self.write_u30(constant_pool.strings.len() as u32 + 1)?;
             for s in &constant_pool.strings {
-                self.write_string(s)?;
+                self.write_str(s)?;
             }
         } else {
             self.write_u32(0)?;
@@ -1,9 +1,25 @@
 use crate::avm1::opcode::OpCode;
+use crate::prelude::*;
 use crate::tag_code::TagCode;
-use std::{borrow, error, fmt, io};
+use alloc::borrow;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+/// What an `Error` can have as its source. `core::error::Error` needs a newer compiler than the crate supports, so
+/// this is `std::error::Error` with the `std` feature and anything that can be printed without it.
+#[cfg(feature = "std")]
+pub use std::error::Error as ErrorSource;
+
+/// What an `Error` can have as its source without the `std` feature: anything that can be printed.
+#[cfg(not(feature = "std"))]
+pub trait ErrorSource: fmt::Debug + fmt::Display {}
+
+#[cfg(not(feature = "std"))]
+impl<T: fmt::Debug + fmt::Display + ?Sized> ErrorSource for T {}
 
 /// A `Result` from reading SWF data.
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
@@ -11,7 +27,7 @@ pub enum Error {
     /// This can contain sub-errors with further information (`Error::source`)
     Avm1ParseError {
         opcode: u8,
-        source: Option<Box<dyn error::Error + 'static>>,
+        source: Option<Box<dyn ErrorSource + 'static>>,
     },
 
     /// Invalid or unknown data was encountered.
@@ -21,10 +37,15 @@ pub enum Error {
     /// This can contain sub-errors with further information (`Error::source`)
     SwfParseError {
         tag_code: u16,
-        source: Box<dyn error::Error + 'static>,
+        source: Box<dyn ErrorSource + 'static>,
     },
 
-    /// An IO error occurred (probably unexpected EOF).
+    /// The data ended before the value being read. This is what reading past the end of a slice returns, with or
+    /// without the `std` feature.
+    UnexpectedEof(&'static str),
+
+    /// An IO error occurred while reading or decompressing a stream.
+    #[cfg(feature = "std")]
     IoError(io::Error),
 
     /// This SWF requires unsupported features.
@@ -43,7 +64,7 @@ impl Error {
 
     /// Helper method to create `Error::Avm1ParseError`.
     #[inline]
-    pub fn avm1_parse_error_with_source(opcode: u8, source: impl error::Error + 'static) -> Self {
+    pub fn avm1_parse_error_with_source(opcode: u8, source: impl ErrorSource + 'static) -> Self {
         Self::Avm1ParseError {
             opcode,
             source: Some(Box::new(source)),
@@ -58,13 +79,19 @@ impl Error {
 
     /// Helper method to create `Error::SwfParseError`.
     #[inline]
-    pub fn swf_parse_error(tag_code: u16, source: impl error::Error + 'static) -> Self {
+    pub fn swf_parse_error(tag_code: u16, source: impl ErrorSource + 'static) -> Self {
         Self::SwfParseError {
             tag_code,
             source: Box::new(source),
         }
     }
 
+    /// Helper method to create `Error::UnexpectedEof`.
+    #[inline]
+    pub fn unexpected_eof(message: &'static str) -> Self {
+        Self::UnexpectedEof(message)
+    }
+
     /// Helper method to create `Error::Unsupported`.
     #[inline]
     pub fn unsupported(message: impl Into<borrow::Cow<'static, str>>) -> Self {
@@ -90,6 +117,8 @@ impl fmt::Display for Error {
                     source
                 )
             }
+            Self::UnexpectedEof(message) => write!(f, "Unexpected end of data: {}", message),
+            #[cfg(feature = "std")]
             Self::IoError(e) => e.fmt(f),
             Self::InvalidData(message) => write!(f, "Invalid data: {}", message),
             Self::Unsupported(message) => write!(f, "Unsupported data: {}", message),
@@ -97,11 +126,14 @@ impl fmt::Display for Error {
     }
 }
 
-impl error::Error for Error {
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        use std::ops::Deref;
+        use core::ops::Deref;
         match self {
             Self::Avm1ParseError { source, .. } => source.as_ref().map(|s| s.deref()),
+            Self::UnexpectedEof(_) => None,
+            #[cfg(feature = "std")]
             Self::IoError(e) => e.source(),
             Self::InvalidData(_) => None,
             Self::SwfParseError { source, .. } => Some(source.as_ref()),
@@ -110,6 +142,7 @@ impl error::Error for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for Error {
     fn from(error: io::Error) -> Self {
         Self::IoError(error)
@@ -1,18 +1,54 @@
-use crate::error::Result;
+//! Low-level primitives for reading and writing SWF data.
+//!
+//! `ReadSwfExt` reads the little-endian integers, floats, fixed point numbers and strings that SWF files are built
+//! from out of a byte slice, and `WriteSwfExt` writes the same values to an `io::Write`. The two are symmetric: each
+//! `read_*` method has a `write_*` counterpart that writes what it reads. They are implemented by the readers and
+//! writers in this crate, by `&[u8]` and by any `io::Write`, so tools can use them to take apart or build data that
+//! the higher-level readers and writers don't cover.
+//!
+//! # Example
+#![cfg_attr(feature = "std", doc = "```")]
+#![cfg_attr(not(feature = "std"), doc = "```ignore")]
+//! use swf::extensions::{ReadSwfExt, WriteSwfExt};
+//! use swf::{Fixed8, SwfStr};
+//!
+//! let mut data = Vec::new();
+//! data.write_u16(1234).unwrap();
+//! data.write_fixed8(Fixed8::from_f32(1.5)).unwrap();
+//! data.write_encoded_u32(300).unwrap();
+//! data.write_str(SwfStr::from_utf8_str("name")).unwrap();
+//!
+//! let mut input = &data[..];
+//! assert_eq!(input.read_u16().unwrap(), 1234);
+//! assert_eq!(input.read_fixed8().unwrap(), Fixed8::from_f32(1.5));
+//! assert_eq!(input.read_encoded_u32().unwrap(), 300);
+//! assert_eq!(input.read_str().unwrap(), "name");
+//! assert!(input.is_empty());
+//! ```
+
+use crate::error::{Error, Result};
 use crate::string::SwfStr;
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io;
+use crate::types::{Fixed16, Fixed8};
+#[cfg(feature = "std")]
+use std::io::{self, Write};
 
+/// Reads SWF primitives from a byte slice, advancing past what was read.
+///
+/// Every multi-byte value is little-endian. Reading past the end of the slice returns `Error::UnexpectedEof`.
 pub trait ReadSwfExt<'a> {
+    /// The remaining data, which reading advances.
     fn as_mut_slice(&mut self) -> &mut &'a [u8];
 
+    /// The remaining data.
     fn as_slice(&self) -> &'a [u8];
 
+    /// The position of the remaining data in `data`, which it must be a part of.
     fn pos(&self, data: &[u8]) -> usize {
         self.as_slice().as_ptr() as usize - data.as_ptr() as usize
     }
 
     // TODO: Make this fallible?
+    /// Moves to `relative_offset` bytes from the current position in `data`, clamped to the end of `data`.
     fn seek(&mut self, data: &'a [u8], relative_offset: isize) {
         let pos = self.pos(data);
         let pos = (pos as isize + relative_offset) as usize;
@@ -20,56 +56,110 @@ pub trait ReadSwfExt<'a> {
         *self.as_mut_slice() = &data[pos..];
     }
 
+    /// Moves to `pos` in `data`, clamped to the end of `data`.
     fn seek_absolute(&mut self, data: &'a [u8], pos: usize) {
         let pos = pos.min(data.len());
         *self.as_mut_slice() = &data[pos..];
     }
 
+    /// Reads the next `N` bytes as an array, for decoding fixed-size values.
+    #[inline]
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let slice = self.as_mut_slice();
+        if slice.len() < N {
+            return Err(Error::unexpected_eof("Not enough data for value"));
+        }
+        let (bytes, rest) = slice.split_at(N);
+        *slice = rest;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    /// Reads a `UI8`.
     #[inline]
     fn read_u8(&mut self) -> Result<u8> {
-        Ok(ReadBytesExt::read_u8(self.as_mut_slice())?)
+        Ok(u8::from_le_bytes(self.read_array()?))
     }
 
+    /// Reads a `UI16`.
     #[inline]
     fn read_u16(&mut self) -> Result<u16> {
-        Ok(ReadBytesExt::read_u16::<LittleEndian>(self.as_mut_slice())?)
+        Ok(u16::from_le_bytes(self.read_array()?))
     }
 
+    /// Reads a `UI32`.
     #[inline]
     fn read_u32(&mut self) -> Result<u32> {
-        Ok(ReadBytesExt::read_u32::<LittleEndian>(self.as_mut_slice())?)
+        Ok(u32::from_le_bytes(self.read_array()?))
     }
 
+    /// Reads a `UI64`.
     #[inline]
     fn read_u64(&mut self) -> Result<u64> {
-        Ok(ReadBytesExt::read_u64::<LittleEndian>(self.as_mut_slice())?)
+        Ok(u64::from_le_bytes(self.read_array()?))
     }
 
+    /// Reads an `SI8`.
     #[inline]
     fn read_i8(&mut self) -> Result<i8> {
-        Ok(ReadBytesExt::read_i8(self.as_mut_slice())?)
+        Ok(i8::from_le_bytes(self.read_array()?))
     }
 
+    /// Reads an `SI16`.
     #[inline]
     fn read_i16(&mut self) -> Result<i16> {
-        Ok(ReadBytesExt::read_i16::<LittleEndian>(self.as_mut_slice())?)
+        Ok(i16::from_le_bytes(self.read_array()?))
     }
 
+    /// Reads an `SI32`.
     #[inline]
     fn read_i32(&mut self) -> Result<i32> {
-        Ok(ReadBytesExt::read_i32::<LittleEndian>(self.as_mut_slice())?)
+        Ok(i32::from_le_bytes(self.read_array()?))
     }
 
+    /// Reads a `FLOAT`.
     #[inline]
     fn read_f32(&mut self) -> Result<f32> {
-        Ok(ReadBytesExt::read_f32::<LittleEndian>(self.as_mut_slice())?)
+        Ok(f32::from_le_bytes(self.read_array()?))
     }
 
+    /// Reads a `DOUBLE`.
     #[inline]
     fn read_f64(&mut self) -> Result<f64> {
-        Ok(ReadBytesExt::read_f64::<LittleEndian>(self.as_mut_slice())?)
+        Ok(f64::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads a `DOUBLE` in the word-swapped format of AVM1 push actions: the high 32 bits come first, then the low
+    /// 32 bits, each little-endian.
+    ///
+    /// ```
+    /// use swf::extensions::ReadSwfExt;
+    /// let mut input = &[0x00, 0x00, 0xf0, 0x3f, 0x00, 0x00, 0x00, 0x00][..];
+    /// assert_eq!(input.read_f64_me().unwrap(), 1.0);
+    /// ```
+    #[inline]
+    fn read_f64_me(&mut self) -> Result<f64> {
+        Ok(f64::from_bits(self.read_u64()?.rotate_left(32)))
+    }
+
+    /// Reads an 8.8 fixed point `FIXED8`.
+    #[inline]
+    fn read_fixed8(&mut self) -> Result<Fixed8> {
+        Ok(Fixed8::from_bits(self.read_i16()?))
     }
 
+    /// Reads a 16.16 fixed point `FIXED`.
+    #[inline]
+    fn read_fixed16(&mut self) -> Result<Fixed16> {
+        Ok(Fixed16::from_bits(self.read_i32()?))
+    }
+
+    /// Reads an `EncodedU32`, 7 bits per byte with the high bit set on every byte but the last, up to 5 bytes.
+    ///
+    /// ```
+    /// use swf::extensions::ReadSwfExt;
+    /// let mut input = &[0xac, 0x02][..];
+    /// assert_eq!(input.read_encoded_u32().unwrap(), 300);
+    /// ```
     #[inline]
     fn read_encoded_u32(&mut self) -> Result<u32> {
         let mut val: u32 = 0;
@@ -83,6 +173,7 @@ pub trait ReadSwfExt<'a> {
         Ok(val)
     }
 
+    /// Reads the next `len` bytes.
     fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
         let slice = self.as_mut_slice();
         if slice.len() >= len {
@@ -90,10 +181,11 @@ pub trait ReadSwfExt<'a> {
             *slice = &slice[len..];
             Ok(new_slice)
         } else {
-            Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Not enough data for slice").into())
+            Err(Error::unexpected_eof("Not enough data for slice"))
         }
     }
 
+    /// Reads all of the remaining data.
     fn read_slice_to_end(&mut self) -> &'a [u8] {
         let slice = self.as_mut_slice();
         let res = &slice[..];
@@ -101,16 +193,24 @@ pub trait ReadSwfExt<'a> {
         res
     }
 
+    /// Reads a null-terminated `STRING`, without the null byte.
     #[inline]
     fn read_str(&mut self) -> Result<&'a SwfStr> {
         let slice = self.as_mut_slice();
-        let s = SwfStr::from_bytes_null_terminated(slice).ok_or_else(|| {
-            io::Error::new(io::ErrorKind::UnexpectedEof, "Not enough data for string")
-        })?;
+        let s = SwfStr::from_bytes_null_terminated(slice)
+            .ok_or_else(|| Error::unexpected_eof("Not enough data for string"))?;
         *slice = &slice[s.len() + 1..];
         Ok(s)
     }
 
+    /// Reads a string with a `UI8` length in front, as in font names. A trailing null byte is left out.
+    ///
+    /// ```
+    /// use swf::extensions::ReadSwfExt;
+    /// let mut input = &[3, b'a', b'b', 0, 1][..];
+    /// assert_eq!(input.read_str_with_len().unwrap(), "ab");
+    /// assert_eq!(input, [1]);
+    /// ```
     #[inline]
     fn read_str_with_len(&mut self) -> Result<&'a SwfStr> {
         let len = self.read_u8()?;
@@ -119,3 +219,289 @@ pub trait ReadSwfExt<'a> {
         Ok(SwfStr::from_bytes_null_terminated(bytes).unwrap_or_else(|| SwfStr::from_bytes(bytes)))
     }
 }
+
+impl<'a> ReadSwfExt<'a> for &'a [u8] {
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut &'a [u8] {
+        self
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &'a [u8] {
+        self
+    }
+}
+
+/// Writes SWF primitives, the counterpart of `ReadSwfExt`.
+///
+/// Every multi-byte value is little-endian. Each method writes what the `ReadSwfExt` method of the same name reads.
+#[cfg(feature = "std")]
+pub trait WriteSwfExt {
+    /// What the values are written to.
+    type Output: Write;
+
+    /// The output, which writing appends to.
+    fn as_mut_writer(&mut self) -> &mut Self::Output;
+
+    /// Writes `bytes` as they are.
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.as_mut_writer().write_all(bytes)
+    }
+
+    /// Writes a `UI8`.
+    #[inline]
+    fn write_u8(&mut self, n: u8) -> io::Result<()> {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// Writes a `UI16`.
+    #[inline]
+    fn write_u16(&mut self, n: u16) -> io::Result<()> {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// Writes a `UI32`.
+    #[inline]
+    fn write_u32(&mut self, n: u32) -> io::Result<()> {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// Writes a `UI64`.
+    #[inline]
+    fn write_u64(&mut self, n: u64) -> io::Result<()> {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// Writes an `SI8`.
+    #[inline]
+    fn write_i8(&mut self, n: i8) -> io::Result<()> {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// Writes an `SI16`.
+    #[inline]
+    fn write_i16(&mut self, n: i16) -> io::Result<()> {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// Writes an `SI32`.
+    #[inline]
+    fn write_i32(&mut self, n: i32) -> io::Result<()> {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// Writes a `FLOAT`.
+    #[inline]
+    fn write_f32(&mut self, n: f32) -> io::Result<()> {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// Writes a `DOUBLE`.
+    #[inline]
+    fn write_f64(&mut self, n: f64) -> io::Result<()> {
+        self.write_bytes(&n.to_le_bytes())
+    }
+
+    /// Writes a `DOUBLE` in the word-swapped format of AVM1 push actions, see `ReadSwfExt::read_f64_me`.
+    ///
+    /// ```
+    /// use swf::extensions::WriteSwfExt;
+    /// let mut output = Vec::new();
+    /// output.write_f64_me(1.0).unwrap();
+    /// assert_eq!(output, [0x00, 0x00, 0xf0, 0x3f, 0x00, 0x00, 0x00, 0x00]);
+    /// ```
+    #[inline]
+    fn write_f64_me(&mut self, n: f64) -> io::Result<()> {
+        self.write_u64(n.to_bits().rotate_left(32))
+    }
+
+    /// Writes an 8.8 fixed point `FIXED8`.
+    #[inline]
+    fn write_fixed8(&mut self, n: Fixed8) -> io::Result<()> {
+        self.write_i16(n.get())
+    }
+
+    /// Writes a 16.16 fixed point `FIXED`.
+    #[inline]
+    fn write_fixed16(&mut self, n: Fixed16) -> io::Result<()> {
+        self.write_i32(n.get())
+    }
+
+    /// Writes an `EncodedU32` in as few bytes as it fits in.
+    ///
+    /// ```
+    /// use swf::extensions::WriteSwfExt;
+    /// let mut output = Vec::new();
+    /// output.write_encoded_u32(300).unwrap();
+    /// assert_eq!(output, [0xac, 0x02]);
+    /// ```
+    fn write_encoded_u32(&mut self, mut n: u32) -> io::Result<()> {
+        loop {
+            let mut byte = (n & 0b01111111) as u8;
+            n >>= 7;
+            if n != 0 {
+                byte |= 0b10000000;
+            }
+            self.write_u8(byte)?;
+            if n == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a null-terminated `STRING`.
+    #[inline]
+    fn write_str(&mut self, s: &SwfStr) -> io::Result<()> {
+        self.write_bytes(s.as_bytes())?;
+        self.write_u8(0)
+    }
+
+    /// Writes a string with a `UI8` length in front and no null byte, as in font names. Fails if the string is
+    /// longer than 255 bytes.
+    ///
+    /// ```
+    /// use swf::extensions::WriteSwfExt;
+    /// use swf::SwfStr;
+    /// let mut output = Vec::new();
+    /// output.write_str_with_len(SwfStr::from_utf8_str("ab")).unwrap();
+    /// assert_eq!(output, [2, b'a', b'b']);
+    /// ```
+    #[inline]
+    fn write_str_with_len(&mut self, s: &SwfStr) -> io::Result<()> {
+        let len = u8::try_from(s.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "String is too long for a UI8 length",
+            )
+        })?;
+        self.write_u8(len)?;
+        self.write_bytes(s.as_bytes())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> WriteSwfExt for W {
+    type Output = W;
+
+    #[inline]
+    fn as_mut_writer(&mut self) -> &mut W {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_primitives() {
+        let data = [
+            0x12, // u8
+            0x34, 0x12, // u16
+            0x78, 0x56, 0x34, 0x12, // u32
+            0xef, 0xcd, 0xab, 0x89, 0x67, 0x45, 0x23, 0x01, // u64
+            0xfe, // i8
+            0xfe, 0xff, // i16
+            0xfe, 0xff, 0xff, 0xff, // i32
+            0x00, 0x00, 0xc0, 0x3f, // f32
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x3f, // f64
+            0x00, 0x00, 0xf8, 0x3f, 0x00, 0x00, 0x00, 0x00, // f64_me
+            0x80, 0xff, // fixed8
+            0x00, 0x80, 0xff, 0xff, // fixed16
+            0xff, 0xff, 0xff, 0xff, 0x0f, // encoded_u32
+            b'h', b'i', 0, // str
+            2, b'o', b'k', // str_with_len
+        ];
+        let mut input = &data[..];
+        assert_eq!(input.read_u8().unwrap(), 0x12);
+        assert_eq!(input.read_u16().unwrap(), 0x1234);
+        assert_eq!(input.read_u32().unwrap(), 0x12345678);
+        assert_eq!(input.read_u64().unwrap(), 0x0123456789abcdef);
+        assert_eq!(input.read_i8().unwrap(), -2);
+        assert_eq!(input.read_i16().unwrap(), -2);
+        assert_eq!(input.read_i32().unwrap(), -2);
+        assert_eq!(input.read_f32().unwrap(), 1.5);
+        assert_eq!(input.read_f64().unwrap(), 1.5);
+        assert_eq!(input.read_f64_me().unwrap(), 1.5);
+        assert_eq!(input.read_fixed8().unwrap(), Fixed8::from_f32(-0.5));
+        assert_eq!(input.read_fixed16().unwrap(), Fixed16::from_f32(-0.5));
+        assert_eq!(input.read_encoded_u32().unwrap(), u32::MAX);
+        assert_eq!(input.read_str().unwrap(), "hi");
+        assert_eq!(input.read_str_with_len().unwrap(), "ok");
+        assert!(input.is_empty());
+        assert!(matches!(input.read_u8(), Err(Error::UnexpectedEof(_))));
+        assert!(matches!(
+            (&[0x01, 0x02][..]).read_u32(),
+            Err(Error::UnexpectedEof(_))
+        ));
+        assert!(matches!(
+            (&b"no null"[..]).read_str(),
+            Err(Error::UnexpectedEof(_))
+        ));
+    }
+
+    #[test]
+    fn read_slices_and_seek() {
+        let data = [1, 2, 3, 4, 5];
+        let mut input = &data[..];
+        assert_eq!(input.read_slice(2).unwrap(), [1, 2]);
+        assert_eq!(input.pos(&data), 2);
+        input.seek(&data, -1);
+        assert_eq!(input.read_u8().unwrap(), 2);
+        input.seek_absolute(&data, 10);
+        assert!(input.is_empty());
+        input.seek_absolute(&data, 3);
+        assert!(input.read_slice(3).is_err());
+        assert_eq!(input.read_slice_to_end(), [4, 5]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_primitives_round_trip() {
+        let mut output = Vec::new();
+        output.write_u8(0x12).unwrap();
+        output.write_u16(0x1234).unwrap();
+        output.write_u32(0x12345678).unwrap();
+        output.write_u64(0x0123456789abcdef).unwrap();
+        output.write_i8(-2).unwrap();
+        output.write_i16(-2).unwrap();
+        output.write_i32(-2).unwrap();
+        output.write_f32(1.5).unwrap();
+        output.write_f64(1.5).unwrap();
+        output.write_f64_me(1.5).unwrap();
+        output.write_fixed8(Fixed8::from_f32(-0.5)).unwrap();
+        output.write_fixed16(Fixed16::from_f32(-0.5)).unwrap();
+        output.write_encoded_u32(u32::MAX).unwrap();
+        output.write_str(SwfStr::from_utf8_str("hi")).unwrap();
+        output
+            .write_str_with_len(SwfStr::from_utf8_str("ok"))
+            .unwrap();
+        assert_eq!(
+            output,
+            [
+                0x12, 0x34, 0x12, 0x78, 0x56, 0x34, 0x12, 0xef, 0xcd, 0xab, 0x89, 0x67, 0x45, 0x23,
+                0x01, 0xfe, 0xfe, 0xff, 0xfe, 0xff, 0xff, 0xff, 0x00, 0x00, 0xc0, 0x3f, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0xf8, 0x3f, 0x00, 0x00, 0xf8, 0x3f, 0x00, 0x00, 0x00, 0x00,
+                0x80, 0xff, 0x00, 0x80, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f, b'h', b'i', 0, 2,
+                b'o', b'k',
+            ]
+        );
+
+        for n in [0, 1, 127, 128, 300, 16383, 16384, u32::MAX] {
+            let mut output = Vec::new();
+            output.write_encoded_u32(n).unwrap();
+            assert_eq!((&output[..]).read_encoded_u32().unwrap(), n);
+        }
+        for n in [0.0, -0.0, 0.1, -1e300, f64::INFINITY] {
+            let mut output = Vec::new();
+            output.write_f64_me(n).unwrap();
+            assert_eq!((&output[..]).read_f64_me().unwrap().to_bits(), n.to_bits());
+        }
+        let long = "x".repeat(256);
+        assert!(Vec::new()
+            .write_str_with_len(SwfStr::from_utf8_str(&long))
+            .is_err());
+    }
+}
@@ -6,6 +6,19 @@
 //!
 //! This library consists of a `read` module for decoding SWF data, and a `write` library for
 //! writing SWF data.
+//!
+//! # Features
+//!
+//! The `std` feature is enabled by default. Without it the crate is `no_std`, and with the `alloc` feature it can
+//! still read tags and AVM1 actions from a slice of decompressed SWF data. Decompressing SWFs, the `io::Read` entry
+//! points, the writer and AVM2 need `std`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(not(feature = "alloc"))]
+compile_error!("The swf crate needs either the `std` or the `alloc` feature.");
+
+extern crate alloc;
 
 #[cfg(feature = "flate2")]
 extern crate flate2;
@@ -16,22 +29,34 @@ extern crate num_derive;
 extern crate num_traits;
 
 pub mod avm1;
+#[cfg(feature = "std")]
 pub mod avm2;
 pub mod error;
-// TODO: Make this private?
 pub mod extensions;
 pub mod read;
 mod string;
 mod tag_code;
 mod types;
+#[cfg(feature = "std")]
 pub mod write;
 
+/// The `alloc` types that `std` has in its prelude, for `no_std` builds.
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec::Vec;
+    pub use alloc::{format, vec};
+}
+
 #[cfg(test)]
 mod test_data;
 
 /// Re-exports
-pub use read::{decompress_swf, parse_swf};
+#[cfg(feature = "std")]
+pub use read::decompress_swf;
+pub use read::parse_swf;
 pub use string::*;
 pub use tag_code::TagCode;
 pub use types::*;
+#[cfg(feature = "std")]
 pub use write::write_swf;
@@ -1,18 +1,21 @@
 use crate::extensions::ReadSwfExt;
+use crate::prelude::*;
 use crate::{
     error::{Error, Result},
     string::{Encoding, SwfStr},
     tag_code::TagCode,
     types::*,
 };
-use bitstream_io::BitRead;
+#[cfg(feature = "std")]
 use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::{self, Read};
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, Read};
 
 /// Parse a decompressed SWF.
 ///
 /// # Example
-/// ```
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
 /// # std::env::set_current_dir(env!("CARGO_MANIFEST_DIR"));
 /// let data = std::fs::read("tests/swfs/DefineSprite.swf").unwrap();
 /// let stream = swf::decompress_swf(&data[..]).unwrap();
@@ -44,15 +47,29 @@ pub fn parse_swf(swf_buf: &SwfBuf) -> Result<Swf<'_>> {
 /// let swf_stream = swf::decompress_swf(&data[..]).unwrap();
 /// println!("FPS: {}", swf_stream.header.frame_rate());
 /// ```
+#[cfg(feature = "std")]
 pub fn decompress_swf<'a, R: Read + 'a>(mut input: R) -> Result<SwfBuf> {
     // Read SWF header.
     let compression = read_compression_type(&mut input)?;
     let version = input.read_u8()?;
     let uncompressed_len = input.read_u32::<LittleEndian>()?;
 
+    // Buffer the rest of the input, so that we know how many bytes the decompressor consumed.
+    let mut input_data = Vec::new();
+    input.read_to_end(&mut input_data)?;
+    let mut input = &input_data[..];
+
     // Now the SWF switches to a compressed stream.
-    let mut decompress_stream: Box<dyn Read> = match compression {
-        Compression::None => Box::new(input),
+    let mut data = Vec::with_capacity(uncompressed_len as usize);
+    let (compressed_len, trailing_bytes) = match compression {
+        Compression::None => {
+            data.extend_from_slice(input);
+            // There is no compressed stream, anything past the length given in the header is trailing data.
+            let trailing_bytes = input
+                .len()
+                .saturating_sub((uncompressed_len as usize).saturating_sub(8));
+            (None, trailing_bytes)
+        }
         Compression::Zlib => {
             if version < 6 {
                 log::warn!(
@@ -60,7 +77,14 @@ pub fn decompress_swf<'a, R: Read + 'a>(mut input: R) -> Result<SwfBuf> {
                     version
                 );
             }
-            make_zlib_reader(input)?
+            let mut decompress_stream = make_zlib_reader(&mut input)?;
+            if let Err(e) = decompress_stream.read_to_end(&mut data) {
+                log::error!("Error decompressing SWF: {}", e);
+            }
+            drop(decompress_stream);
+            // The zlib stream stops at its end marker, whatever is left over is trailing data.
+            let compressed_len = input_data.len() - input.len();
+            (Some(compressed_len as u64), input.len())
         }
         Compression::Lzma => {
             if version < 13 {
@@ -69,18 +93,19 @@ pub fn decompress_swf<'a, R: Read + 'a>(mut input: R) -> Result<SwfBuf> {
                     version
                 );
             }
+            // LZMA SWFs store the compressed length in the header, excluding the 5 bytes of LZMA properties.
+            let compressed_len = ReadSwfExt::read_u32(&mut input)?;
+            let trailing_bytes = input.len().saturating_sub(compressed_len as usize + 5);
             // Uncompressed length includes the 4-byte header and 4-byte uncompressed length itself,
             // subtract it here.
-            make_lzma_reader(input, uncompressed_len - 8)?
+            let mut decompress_stream = make_lzma_reader(input, uncompressed_len - 8)?;
+            if let Err(e) = decompress_stream.read_to_end(&mut data) {
+                log::error!("Error decompressing SWF: {}", e);
+            }
+            (Some(compressed_len.into()), trailing_bytes)
         }
     };
 
-    // Decompress the entire SWF.
-    let mut data = Vec::with_capacity(uncompressed_len as usize);
-    if let Err(e) = decompress_stream.read_to_end(&mut data) {
-        log::error!("Error decompressing SWF: {}", e);
-    }
-
     // Some SWF streams may not be compressed correctly,
     // (e.g. incorrect data length in the stream), so decompressing
     // may throw an error even though the data otherwise comes
@@ -90,6 +115,9 @@ pub fn decompress_swf<'a, R: Read + 'a>(mut input: R) -> Result<SwfBuf> {
     if data.len() as u64 + 8 != uncompressed_len as u64 {
         log::warn!("SWF length doesn't match header, may be corrupt");
     }
+    if trailing_bytes > 0 {
+        log::warn!("SWF has {} trailing bytes after the body", trailing_bytes);
+    }
 
     let mut reader = Reader::new(&data, version);
     let stage_size = reader.read_rectangle()?;
@@ -134,34 +162,71 @@ pub fn decompress_swf<'a, R: Read + 'a>(mut input: R) -> Result<SwfBuf> {
             file_attributes,
             background_color,
             uncompressed_len,
+            compressed_len,
+            trailing_bytes,
         },
         data,
     })
 }
 
 #[cfg(feature = "flate2")]
-fn make_zlib_reader<'a, R: Read + 'a>(input: R) -> Result<Box<dyn Read + 'a>> {
-    use flate2::read::ZlibDecoder;
+fn make_zlib_reader<'a, R: BufRead + 'a>(input: R) -> Result<Box<dyn Read + 'a>> {
+    // Use the `bufread` decoder, as it only consumes the bytes that are part of the zlib stream.
+    use flate2::bufread::ZlibDecoder;
     Ok(Box::new(ZlibDecoder::new(input)))
 }
 
 #[cfg(all(feature = "libflate", not(feature = "flate2")))]
-fn make_zlib_reader<'a, R: Read + 'a>(input: R) -> Result<Box<dyn Read + 'a>> {
+fn make_zlib_reader<'a, R: BufRead + 'a>(input: R) -> Result<Box<dyn Read + 'a>> {
     use libflate::zlib::Decoder;
     let decoder = Decoder::new(input)?;
     Ok(Box::new(decoder))
 }
 
-#[cfg(not(any(feature = "flate2", feature = "libflate")))]
-fn make_zlib_reader<'a, R: Read + 'a>(_input: R) -> Result<Box<dyn Read + 'a>> {
+#[cfg(all(feature = "std", not(any(feature = "flate2", feature = "libflate"))))]
+fn make_zlib_reader<'a, R: BufRead + 'a>(_input: R) -> Result<Box<dyn Read + 'a>> {
     Err(Error::unsupported(
         "Support for Zlib compressed SWFs is not enabled.",
     ))
 }
 
+/// The length of the zlib compressed `data` once decompressed, reading at most `limit` bytes of it. A stream that
+/// is cut short or corrupt counts the bytes before the problem. Returns `None` if zlib support isn't enabled.
+#[cfg(feature = "std")]
+fn decompressed_len(data: &[u8], limit: usize) -> Option<usize> {
+    let mut decompressed = make_zlib_reader(data).ok()?.take(limit as u64);
+    let mut buffer = [0; 4096];
+    let mut len = 0;
+    while let Ok(read @ 1..) = decompressed.read(&mut buffer) {
+        len += read;
+    }
+    Some(len)
+}
+
+#[cfg(not(feature = "std"))]
+fn decompressed_len(_data: &[u8], _limit: usize) -> Option<usize> {
+    None
+}
+
+/// The length of the decompressed data of a DefineBitsLossless, including the color table of a `ColorMap8`.
+/// Color table entries are RGB in DefineBitsLossless and RGBA in DefineBitsLossless2, and each row of pixels is
+/// padded to a multiple of 4 bytes except in `Rgb32`, where every pixel is 4 bytes anyway.
+fn bitmap_data_len(version: u8, format: BitmapFormat, width: u16, height: u16) -> usize {
+    let padded = |row_len: usize| (row_len + 3) & !3;
+    let (width, height) = (usize::from(width), usize::from(height));
+    match format {
+        BitmapFormat::ColorMap8 { num_colors } => {
+            let entry_len = if version == 1 { 3 } else { 4 };
+            (usize::from(num_colors) + 1) * entry_len + padded(width) * height
+        }
+        BitmapFormat::Rgb15 => padded(width * 2) * height,
+        BitmapFormat::Rgb32 => width * 4 * height,
+    }
+}
+
 #[cfg(feature = "lzma")]
 fn make_lzma_reader<'a, R: Read + 'a>(
-    mut input: R,
+    input: R,
     uncompressed_length: u32,
 ) -> Result<Box<dyn Read + 'a>> {
     use lzma_rs::{
@@ -182,9 +247,7 @@ fn make_lzma_reader<'a, R: Read + 'a>(
     // Bytes 5..13: Uncompressed length
     //
     // To deal with the mangled header, use lzma_rs options to anually provide uncompressed length.
-
-    // Read compressed length (ignored)
-    let _ = input.read_u32::<LittleEndian>()?;
+    // The compressed length has already been read by the caller.
 
     // TODO: Switch to lzma-rs streaming API when stable.
     let mut output = Vec::with_capacity(uncompressed_length as usize);
@@ -202,7 +265,7 @@ fn make_lzma_reader<'a, R: Read + 'a>(
     Ok(Box::new(io::Cursor::new(output)))
 }
 
-#[cfg(not(feature = "lzma"))]
+#[cfg(all(feature = "std", not(feature = "lzma")))]
 fn make_lzma_reader<'a, R: Read + 'a>(
     _input: R,
     _uncompressed_length: u32,
@@ -212,67 +275,142 @@ fn make_lzma_reader<'a, R: Read + 'a>(
     ))
 }
 
+/// Reads big-endian bit fields from a slice, taking whole bytes from it as they are needed.
 struct BitReader<'a, 'b> {
-    bits: bitstream_io::BitReader<&'b mut &'a [u8], bitstream_io::BigEndian>,
+    input: &'b mut &'a [u8],
+    byte: u8,
+    bits_left: u32,
 }
 
 impl<'a, 'b> BitReader<'a, 'b> {
     #[inline]
-    fn new(data: &'b mut &'a [u8]) -> Self {
+    fn new(input: &'b mut &'a [u8]) -> Self {
         Self {
-            bits: bitstream_io::BitReader::new(data),
+            input,
+            byte: 0,
+            bits_left: 0,
         }
     }
 
     #[inline]
-    fn read_bit(&mut self) -> io::Result<bool> {
-        self.bits.read_bit()
+    fn read_bit(&mut self) -> Result<bool> {
+        self.read_ubits(1).map(|bit| bit != 0)
     }
 
     #[inline]
-    fn read_ubits(&mut self, num_bits: u32) -> io::Result<u32> {
-        if num_bits > 0 {
-            self.bits.read(num_bits)
-        } else {
-            Ok(0)
+    fn read_ubits(&mut self, mut num_bits: u32) -> Result<u32> {
+        if num_bits > 32 {
+            return Err(Error::invalid_data("Bit field is wider than 32 bits"));
         }
+        let mut value = 0u32;
+        while num_bits > 0 {
+            if self.bits_left == 0 {
+                let (&byte, rest) = self
+                    .input
+                    .split_first()
+                    .ok_or_else(|| Error::unexpected_eof("Not enough data for bits"))?;
+                *self.input = rest;
+                self.byte = byte;
+                self.bits_left = 8;
+            }
+            let n = num_bits.min(self.bits_left);
+            let bits = (u32::from(self.byte) >> (self.bits_left - n)) & ((1 << n) - 1);
+            value = (value << n) | bits;
+            self.bits_left -= n;
+            num_bits -= n;
+        }
+        Ok(value)
     }
 
     #[inline]
-    fn read_sbits(&mut self, num_bits: u32) -> io::Result<i32> {
+    fn read_sbits(&mut self, num_bits: u32) -> Result<i32> {
         if num_bits > 0 {
-            self.bits.read_signed(num_bits)
+            let shift = 32 - num_bits.min(32);
+            Ok((self.read_ubits(num_bits)? << shift) as i32 >> shift)
         } else {
             Ok(0)
         }
     }
 
     #[inline]
-    fn read_sbits_fixed8(&mut self, num_bits: u32) -> io::Result<Fixed8> {
+    fn read_sbits_fixed8(&mut self, num_bits: u32) -> Result<Fixed8> {
         self.read_sbits(num_bits)
             .map(|n| Fixed8::from_bits(n as i16))
     }
 
     #[inline]
-    fn read_sbits_twips(&mut self, num_bits: u32) -> io::Result<Twips> {
+    fn read_sbits_twips(&mut self, num_bits: u32) -> Result<Twips> {
         self.read_sbits(num_bits).map(Twips::new)
     }
 
     #[inline]
-    fn read_fbits(&mut self, num_bits: u32) -> io::Result<Fixed16> {
+    fn read_fbits(&mut self, num_bits: u32) -> Result<Fixed16> {
         self.read_sbits(num_bits).map(Fixed16::from_bits)
     }
 
     #[inline]
     fn reader(&mut self) -> &mut &'a [u8] {
-        self.bits.byte_align();
-        self.bits.reader().unwrap()
+        self.bits_left = 0;
+        self.input
+    }
+}
+
+/// Lazily decodes the shape records of a shape, see `Reader::shape_records`.
+///
+/// Each item is decoded as the iterator is advanced, so a shape's records can be walked without collecting them
+/// into a `Vec`. Iteration ends after the end record, or after the first error.
+pub struct ShapeRecordIter<'a, 'b> {
+    bits: BitReader<'a, 'b>,
+    context: ShapeContext,
+    done: bool,
+}
+
+impl<'a, 'b> ShapeRecordIter<'a, 'b> {
+    /// The context the next record is decoded with, which reflects any new styles read so far.
+    pub fn context(&self) -> &ShapeContext {
+        &self.context
+    }
+
+    /// Counts the remaining edge records, without keeping any of them.
+    pub fn count_edges(self) -> Result<usize> {
+        let mut edges = 0;
+        for record in self {
+            if matches!(
+                record?,
+                ShapeRecord::StraightEdge { .. } | ShapeRecord::CurvedEdge { .. }
+            ) {
+                edges += 1;
+            }
+        }
+        Ok(edges)
+    }
+}
+
+impl<'a, 'b> Iterator for ShapeRecordIter<'a, 'b> {
+    type Item = Result<ShapeRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match Reader::read_shape_record(&mut self.bits, &mut self.context) {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
     }
 }
 
 pub struct Reader<'a> {
     input: &'a [u8],
     version: u8,
+    strict: bool,
 }
 
 impl<'a> ReadSwfExt<'a> for Reader<'a> {
@@ -290,7 +428,30 @@ impl<'a> ReadSwfExt<'a> for Reader<'a> {
 impl<'a> Reader<'a> {
     #[inline]
     pub const fn new(input: &'a [u8], version: u8) -> Self {
-        Self { input, version }
+        Self {
+            input,
+            version,
+            strict: false,
+        }
+    }
+
+    /// Returns a `Reader` in strict mode.
+    ///
+    /// In strict mode, inconsistencies that Flash Player tolerates (such as offsets that
+    /// don't match the data they point to) are returned as errors instead of being worked around.
+    #[inline]
+    pub const fn new_strict(input: &'a [u8], version: u8) -> Self {
+        Self {
+            input,
+            version,
+            strict: true,
+        }
+    }
+
+    /// Whether this reader is in strict mode.
+    #[inline]
+    pub const fn is_strict(&self) -> bool {
+        self.strict
     }
 
     /// Returns the suggested string encoding for this SWF.
@@ -325,19 +486,10 @@ impl<'a> Reader<'a> {
         BitReader::new(&mut self.input)
     }
 
-    #[inline]
-    fn read_fixed8(&mut self) -> Result<Fixed8> {
-        Ok(Fixed8::from_bits(self.read_i16()?))
-    }
-
-    #[inline]
-    fn read_fixed16(&mut self) -> Result<Fixed16> {
-        Ok(Fixed16::from_bits(self.read_i32()?))
-    }
-
     /// Reads the next SWF tag from the stream.
     /// # Example
-    /// ```
+    #[cfg_attr(feature = "std", doc = "```")]
+    #[cfg_attr(not(feature = "std"), doc = "```ignore")]
     /// # std::env::set_current_dir(env!("CARGO_MANIFEST_DIR"));
     /// let data = std::fs::read("tests/swfs/DefineSprite.swf").unwrap();
     /// let mut swf_buf = swf::decompress_swf(&data[..]).unwrap();
@@ -359,10 +511,20 @@ impl<'a> Reader<'a> {
     }
 
     fn read_tag_with_code(&mut self, tag_code: TagCode, length: usize) -> Result<Tag<'a>> {
-        let mut tag_reader = Reader::new(self.read_slice(length)?, self.version);
+        let mut tag_reader = Reader {
+            input: self.read_slice(length)?,
+            version: self.version,
+            strict: self.strict,
+        };
         let tag = match tag_code {
             TagCode::End => Tag::End,
             TagCode::ShowFrame => Tag::ShowFrame,
+            // Not documented in SWF19 reference, these are from SWF1-2 and are ignored by the official Flash Player.
+            // See https://www.m2osw.com/swf_alexref
+            TagCode::FreeAll => Tag::FreeAll,
+            TagCode::FreeCharacter => Tag::FreeCharacter(tag_reader.read_character_id()?),
+            TagCode::PathsArePostScript => Tag::PathsArePostScript,
+            TagCode::SyncFrame => Tag::SyncFrame,
             TagCode::CsmTextSettings => Tag::CsmTextSettings(tag_reader.read_csm_text_settings()?),
             TagCode::DefineBinaryData => {
                 Tag::DefineBinaryData(tag_reader.read_define_binary_data()?)
@@ -428,10 +590,25 @@ impl<'a> Reader<'a> {
             }
             TagCode::EnableTelemetry => {
                 tag_reader.read_u16()?; // Reserved
-                let password_hash = if length > 2 {
-                    tag_reader.read_slice(32)?
-                } else {
-                    &[]
+                let hash = tag_reader.read_slice_to_end();
+                let password_hash = match TelemetryPasswordHash::from_bytes(hash) {
+                    Some(password_hash) => password_hash,
+                    None if tag_reader.strict => {
+                        return Err(Error::invalid_data(format!(
+                            "EnableTelemetry password hash is {} bytes, expected 0 or 32",
+                            hash.len()
+                        )));
+                    }
+                    None => {
+                        // A tag too short for a hash has no password, and bytes past the hash are ignored.
+                        log::warn!(
+                            "EnableTelemetry password hash is {} bytes, expected 0 or 32",
+                            hash.len()
+                        );
+                        hash.get(..32)
+                            .and_then(TelemetryPasswordHash::from_bytes)
+                            .unwrap_or(TelemetryPasswordHash::NoPassword)
+                    }
                 };
                 Tag::EnableTelemetry { password_hash }
             }
@@ -517,6 +694,9 @@ impl<'a> Reader<'a> {
                 })
             }
 
+            // Not documented in SWF19 reference, written by Flash Player 9 prerelease tools.
+            TagCode::DoAbcDefine => Tag::DoAbcDefine(tag_reader.read_slice_to_end()),
+
             TagCode::DoAction => {
                 let action_data = tag_reader.read_slice_to_end();
                 Tag::DoAction(action_data)
@@ -595,7 +775,12 @@ impl<'a> Reader<'a> {
             TagCode::RemoveObject2 => Tag::RemoveObject(tag_reader.read_remove_object_2()?),
 
             TagCode::VideoFrame => Tag::VideoFrame(tag_reader.read_video_frame()?),
-            TagCode::ProductInfo => Tag::ProductInfo(tag_reader.read_product_info()?),
+            TagCode::ProductInfo if length == ProductInfo::LEN => {
+                Tag::ProductInfo(tag_reader.read_product_info()?)
+            }
+            // Not documented in SWF19 reference.
+            // Older tools write tag 41 as a serial number string, any length but ProductInfo's is taken to be one.
+            TagCode::ProductInfo => Tag::SerialNumber(tag_reader.read_str()?),
             TagCode::NameCharacter => Tag::NameCharacter(tag_reader.read_name_character()?),
         };
 
@@ -773,6 +958,8 @@ impl<'a> Reader<'a> {
         let id = self.read_u16()?;
         let flags = self.read_u8()?;
         let is_track_as_menu = (flags & 0b1) != 0;
+        // The action offset is relative to the start of the offset field itself.
+        let action_offset_start = self.input;
         let action_offset = self.read_u16()?;
 
         let mut records = Vec::new();
@@ -782,6 +969,27 @@ impl<'a> Reader<'a> {
 
         let mut actions = Vec::new();
         if action_offset != 0 {
+            let records_end = action_offset_start.len() - self.input.len();
+            if records_end != usize::from(action_offset) {
+                if self.strict {
+                    return Err(Error::invalid_data(format!(
+                        "DefineButton2 action offset is {} but button records end at {}",
+                        action_offset, records_end
+                    )));
+                }
+                // Flash Player always uses the offset, so seek to it.
+                log::warn!(
+                    "DefineButton2 action offset is {} but button records end at {}, seeking to offset",
+                    action_offset,
+                    records_end
+                );
+                self.input = action_offset_start
+                    .get(usize::from(action_offset)..)
+                    .ok_or_else(|| {
+                        Error::invalid_data("DefineButton2 action offset out of bounds")
+                    })?;
+            }
+
             loop {
                 let (button_action, has_more_actions) = self.read_button_action()?;
                 actions.push(button_action);
@@ -924,15 +1132,24 @@ impl<'a> Reader<'a> {
         let flags = self.read_u8()?;
         let thickness = self.read_f32()?;
         let sharpness = self.read_f32()?;
-        self.read_u8()?; // Reserved (0).
-        Ok(CsmTextSettings {
+        let reserved = self.read_u8()?; // Reserved (0).
+        let mut settings = CsmTextSettings {
             id,
             use_advanced_rendering: flags & 0b01000000 != 0,
             grid_fit: TextGridFit::from_u8((flags >> 3) & 0b11)
                 .ok_or_else(|| Error::invalid_data("Invalid text grid fitting"))?,
             thickness,
             sharpness,
-        })
+            raw_flags: None,
+            raw_reserved: None,
+        };
+        if flags != settings.flags() {
+            settings.raw_flags = Some(flags);
+        }
+        if reserved != 0 {
+            settings.raw_reserved = Some(reserved);
+        }
+        Ok(settings)
     }
 
     pub fn read_frame_label(&mut self, length: usize) -> Result<FrameLabel<'a>> {
@@ -980,18 +1197,14 @@ impl<'a> Reader<'a> {
 
         let mut glyphs = Vec::with_capacity(num_glyphs as usize);
         for _ in 0..num_glyphs {
-            let mut glyph = vec![];
             let num_bits = self.read_u8()?;
-            let mut shape_context = ShapeContext {
+            let shape_context = ShapeContext {
                 swf_version: self.version,
                 shape_version: 1,
                 num_fill_bits: num_bits >> 4,
                 num_line_bits: num_bits & 0b1111,
             };
-            let mut bits = self.bits();
-            while let Some(record) = Self::read_shape_record(&mut bits, &mut shape_context)? {
-                glyph.push(record);
-            }
+            let glyph = self.shape_records(shape_context).collect::<Result<_>>()?;
             glyphs.push(glyph);
         }
 
@@ -1004,6 +1217,10 @@ impl<'a> Reader<'a> {
         let language = self.read_language()?;
         // SWF19 states that the font name should not have a terminating null byte,
         // but it often does (depends on Flash IDE version?)
+        let name_has_null = match self.get_ref() {
+            [len, name @ ..] if *len > 0 => name.get(usize::from(*len) - 1) == Some(&0),
+            _ => false,
+        };
         let name = self.read_str_with_len()?;
 
         let num_glyphs = self.read_u16()? as usize;
@@ -1020,13 +1237,18 @@ impl<'a> Reader<'a> {
         // SWF19 p. 164 doesn't make it super clear: If there are no glyphs,
         // then the following tables are omitted. But the table offset values
         // may or may not be written... (depending on Flash IDE version that was used?)
-        if num_glyphs == 0 {
+        let offsets = if num_glyphs == 0 {
             // Try to read the CodeTableOffset. It may or may not be present,
             // so just dump any error.
-            if flags.contains(FontFlag::HAS_WIDE_OFFSETS) {
-                let _ = self.read_u32();
+            let code_table_offset = if flags.contains(FontFlag::HAS_WIDE_OFFSETS) {
+                self.read_u32().ok()
             } else {
-                let _ = self.read_u16();
+                self.read_u16().ok().map(u32::from)
+            };
+            FontOffsets {
+                glyph_offsets: vec![],
+                code_table_offset,
+                name_has_null,
             }
         } else {
             let offsets_ref = self.get_ref();
@@ -1070,7 +1292,7 @@ impl<'a> Reader<'a> {
                 }
 
                 let num_bits = self.read_u8()?;
-                let mut shape_context = ShapeContext {
+                let shape_context = ShapeContext {
                     swf_version: self.version,
                     shape_version: 1,
                     num_fill_bits: num_bits >> 4,
@@ -1082,10 +1304,7 @@ impl<'a> Reader<'a> {
                 }
 
                 // TODO: Avoid reading more than `available_bytes - 1`?
-                let mut bits = self.bits();
-                while let Some(record) = Self::read_shape_record(&mut bits, &mut shape_context)? {
-                    glyph.shape_records.push(record);
-                }
+                glyph.shape_records = self.shape_records(shape_context).collect::<Result<_>>()?;
             }
 
             // The code table is assumed to be positioned right after the glyph shapes.
@@ -1101,7 +1320,13 @@ impl<'a> Reader<'a> {
                     self.read_u8()?.into()
                 };
             }
-        }
+
+            FontOffsets {
+                glyph_offsets: offsets,
+                code_table_offset: Some(code_table_offset),
+                name_has_null,
+            }
+        };
 
         // TODO: Is it possible to have a layout when there are no glyphs?
         let layout = if flags.contains(FontFlag::HAS_LAYOUT) {
@@ -1151,6 +1376,7 @@ impl<'a> Reader<'a> {
             layout,
             glyphs,
             flags,
+            offsets: Some(offsets),
         })
     }
 
@@ -1194,8 +1420,18 @@ impl<'a> Reader<'a> {
         let thickness = FontThickness::from_u8(self.read_u8()? >> 6)
             .ok_or_else(|| Error::invalid_data("Invalid font thickness type."))?;
         let mut zones = vec![];
-        while let Ok(zone) = self.read_font_align_zone() {
-            zones.push(zone);
+        while !self.input.is_empty() {
+            if self.input.len() < FontAlignZone::ENCODED_LEN {
+                if self.strict {
+                    return Err(Error::invalid_data("Truncated font align zone"));
+                }
+                log::warn!(
+                    "Ignoring {} trailing bytes in DefineFontAlignZones",
+                    self.input.len()
+                );
+                break;
+            }
+            zones.push(self.read_font_align_zone()?);
         }
         Ok(Tag::DefineFontAlignZones {
             id,
@@ -1204,16 +1440,32 @@ impl<'a> Reader<'a> {
         })
     }
 
+    /// In strict mode, a zone data count other than 2 or a mask with reserved bits set is an error.
     fn read_font_align_zone(&mut self) -> Result<FontAlignZone> {
-        self.read_u8()?; // Always 2.
-        let zone = FontAlignZone {
-            left: self.read_i16()?,
-            width: self.read_i16()?,
-            bottom: self.read_i16()?,
-            height: self.read_i16()?,
-        };
-        self.read_u8()?; // Always 0b000000_11 (2 dimensions).
-        Ok(zone)
+        let num_zone_data = self.read_u8()?; // Always 2.
+        if self.strict && num_zone_data != 2 {
+            return Err(Error::invalid_data(
+                "Font align zone must have 2 zone data entries",
+            ));
+        }
+        let left = self.read_i16()?;
+        let width = self.read_i16()?;
+        let bottom = self.read_i16()?;
+        let height = self.read_i16()?;
+        let mask_bits = self.read_u8()?;
+        let mask = FontAlignZoneMask::from_bits_truncate(mask_bits);
+        if self.strict && mask.bits() != mask_bits {
+            return Err(Error::invalid_data("Reserved font align zone mask bits"));
+        }
+        Ok(FontAlignZone {
+            left,
+            width,
+            bottom,
+            height,
+            mask,
+            raw_num_zone_data: (num_zone_data != 2).then_some(num_zone_data),
+            raw_mask: (mask.bits() != mask_bits).then_some(mask_bits),
+        })
     }
 
     fn read_define_font_info(&mut self, version: u8) -> Result<FontInfo<'a>> {
@@ -1282,7 +1534,9 @@ impl<'a> Reader<'a> {
                 )
             };
 
-        self.read_u32()?; // Offset to EndEdges.
+        // The offset to the end edges is relative to the end of the offset field itself.
+        let end_edges_offset = self.read_u32()?;
+        let start_edges_start = self.input;
 
         let num_fill_styles = match self.read_u8()? {
             0xff => self.read_u16()? as usize,
@@ -1309,31 +1563,41 @@ impl<'a> Reader<'a> {
         }
 
         // TODO(Herschel): Add read_shape
-        let swf_version = self.version;
-        let mut bits = self.bits();
-        let mut shape_context = ShapeContext {
-            swf_version,
+        let num_bits = self.read_u8()?;
+        let shape_context = ShapeContext {
+            swf_version: self.version,
             shape_version,
-            num_fill_bits: bits.read_ubits(4)? as u8,
-            num_line_bits: bits.read_ubits(4)? as u8,
+            num_fill_bits: num_bits >> 4,
+            num_line_bits: num_bits & 0b1111,
+        };
+        let start_shape = self.shape_records(shape_context).collect::<Result<_>>()?;
+
+        let start_edges_len = start_edges_start.len() - self.input.len();
+        let end_edges_offset = if start_edges_len == end_edges_offset as usize {
+            None
+        } else if self.strict {
+            return Err(Error::invalid_data(format!(
+                "DefineMorphShape end edges offset is {} but the start shape ends at {}",
+                end_edges_offset, start_edges_len
+            )));
+        } else {
+            // The end shape is still read from where the start shape ends, the offset is only kept for writing.
+            log::warn!(
+                "DefineMorphShape end edges offset is {} but the start shape ends at {}, ignoring offset",
+                end_edges_offset,
+                start_edges_len
+            );
+            Some(end_edges_offset)
         };
-        let mut start_shape = Vec::new();
-        while let Some(record) = Self::read_shape_record(&mut bits, &mut shape_context)? {
-            start_shape.push(record);
-        }
 
-        let mut end_shape = Vec::new();
         self.read_u8()?; // NumFillBits and NumLineBits are written as 0 for the end shape.
-        let mut shape_context = ShapeContext {
+        let shape_context = ShapeContext {
             swf_version: self.version,
             shape_version,
             num_fill_bits: 0,
             num_line_bits: 0,
         };
-        let mut bits = self.bits();
-        while let Some(record) = Self::read_shape_record(&mut bits, &mut shape_context)? {
-            end_shape.push(record);
-        }
+        let end_shape = self.shape_records(shape_context).collect::<Result<_>>()?;
         Ok(DefineMorphShape {
             id,
             version: shape_version,
@@ -1353,6 +1617,7 @@ impl<'a> Reader<'a> {
                 fill_styles: end_fill_styles,
                 line_styles: end_line_styles,
             },
+            end_edges_offset,
         })
     }
 
@@ -1486,7 +1751,7 @@ impl<'a> Reader<'a> {
     fn read_morph_gradient(&mut self) -> Result<(Gradient, Gradient)> {
         let start_matrix = self.read_matrix()?;
         let end_matrix = self.read_matrix()?;
-        let (num_records, spread, interpolation) = self.read_gradient_flags()?;
+        let (num_records, spread, interpolation, raw_flags) = self.read_gradient_flags()?;
         let mut start_records = Vec::with_capacity(num_records);
         let mut end_records = Vec::with_capacity(num_records);
         for _ in 0..num_records {
@@ -1499,23 +1764,37 @@ impl<'a> Reader<'a> {
                 color: self.read_rgba()?,
             });
         }
+        self.validate_gradient_records(&start_records)?;
+        self.validate_gradient_records(&end_records)?;
         Ok((
             Gradient {
                 matrix: start_matrix,
                 spread,
                 interpolation,
                 records: start_records,
+                raw_flags,
             },
             Gradient {
                 matrix: end_matrix,
                 spread,
                 interpolation,
                 records: end_records,
+                raw_flags,
             },
         ))
     }
 
     pub fn read_define_shape(&mut self, version: u8) -> Result<Shape> {
+        let (mut shape, shape_context) = self.read_define_shape_header(version)?;
+        shape.shape = self.shape_records(shape_context).collect::<Result<_>>()?;
+        Ok(shape)
+    }
+
+    /// Reads a DefineShape tag up to its shape records, which can then be decoded lazily with
+    /// `shape_records` and the returned context.
+    ///
+    /// The returned shape has no records.
+    pub fn read_define_shape_header(&mut self, version: u8) -> Result<(Shape, ShapeContext)> {
         let id = self.read_u16()?;
         let shape_bounds = self.read_rectangle()?;
         let (edge_bounds, has_fill_winding_rule, has_non_scaling_strokes, has_scaling_strokes) =
@@ -1532,18 +1811,13 @@ impl<'a> Reader<'a> {
                 (shape_bounds.clone(), false, true, false)
             };
         let (styles, num_fill_bits, num_line_bits) = self.read_shape_styles(version)?;
-        let mut records = Vec::new();
-        let mut shape_context = ShapeContext {
+        let shape_context = ShapeContext {
             swf_version: self.version,
             shape_version: version,
             num_fill_bits,
             num_line_bits,
         };
-        let mut bits = self.bits();
-        while let Some(record) = Self::read_shape_record(&mut bits, &mut shape_context)? {
-            records.push(record);
-        }
-        Ok(Shape {
+        let shape = Shape {
             version,
             id,
             shape_bounds,
@@ -1552,13 +1826,27 @@ impl<'a> Reader<'a> {
             has_non_scaling_strokes,
             has_scaling_strokes,
             styles,
-            shape: records,
-        })
+            shape: Vec::new(),
+        };
+        Ok((shape, shape_context))
+    }
+
+    /// Returns an iterator that decodes shape records from the current position as it is advanced.
+    ///
+    /// The reader is left after the last record read, so once the iterator has returned `None` it is positioned
+    /// after the end record.
+    pub fn shape_records<'b>(&'b mut self, context: ShapeContext) -> ShapeRecordIter<'a, 'b> {
+        ShapeRecordIter {
+            bits: self.bits(),
+            context,
+            done: false,
+        }
     }
 
     pub fn read_define_sound(&mut self) -> Result<Sound<'a>> {
         let id = self.read_u16()?;
         let format = self.read_sound_format()?;
+        self.validate_sound_format(&format)?;
         let num_samples = self.read_u32()?;
         let data = self.read_slice_to_end();
         Ok(Sound {
@@ -1573,13 +1861,20 @@ impl<'a> Reader<'a> {
         // TODO: Verify version requirements.
         let playback_format = self.read_sound_format()?;
         let stream_format = self.read_sound_format()?;
+        self.validate_sound_format(&stream_format)?;
         let num_samples_per_block = self.read_u16()?;
+        let mut latency_seek_missing = false;
         let latency_seek = if stream_format.compression == AudioCompression::Mp3 {
             // SWF19 says latency seek is i16, not u16. Is this wrong> How are negative values used?
             // Some software creates SWF files that incorrectly omit this value.
-            // Fail silently if it's missing.
+            // Fail silently if it's missing, and remember so that it is omitted when written.
             // TODO: What is Flash's behavior in this case? Does it read the value from the following bytes?
-            self.read_i16().unwrap_or(0)
+            if self.input.len() < 2 && !self.strict {
+                latency_seek_missing = true;
+                0
+            } else {
+                self.read_i16()?
+            }
         } else {
             0
         };
@@ -1588,6 +1883,7 @@ impl<'a> Reader<'a> {
             playback_format,
             num_samples_per_block,
             latency_seek,
+            latency_seek_missing,
         })
     }
 
@@ -1706,7 +2002,7 @@ impl<'a> Reader<'a> {
 
     fn read_gradient(&mut self, shape_version: u8) -> Result<Gradient> {
         let matrix = self.read_matrix()?;
-        let (num_records, spread, interpolation) = self.read_gradient_flags()?;
+        let (num_records, spread, interpolation, raw_flags) = self.read_gradient_flags()?;
         let mut records = Vec::with_capacity(num_records);
         for _ in 0..num_records {
             records.push(GradientRecord {
@@ -1718,22 +2014,64 @@ impl<'a> Reader<'a> {
                 },
             });
         }
+        self.validate_gradient_records(&records)?;
         Ok(Gradient {
             matrix,
             spread,
             interpolation,
             records,
+            raw_flags,
         })
     }
 
-    fn read_gradient_flags(&mut self) -> Result<(usize, GradientSpread, GradientInterpolation)> {
+    /// Reads the gradient flags byte, returning the record count, spread mode and interpolation
+    /// mode, along with the raw byte if it uses reserved modes that won't be written back.
+    ///
+    /// In strict mode, reserved spread and interpolation modes are an error.
+    fn read_gradient_flags(
+        &mut self,
+    ) -> Result<(usize, GradientSpread, GradientInterpolation, Option<u8>)> {
         let flags = self.read_u8()?;
-        let spread = GradientSpread::from_u8((flags >> 6) & 0b11)
+        let spread_bits = (flags >> 6) & 0b11;
+        let interpolation_bits = (flags >> 4) & 0b11;
+        if self.strict && GradientSpread::from_u8(spread_bits).map(|s| s as u8) != Some(spread_bits)
+        {
+            return Err(Error::invalid_data("Reserved gradient spread mode"));
+        }
+        if self.strict
+            && GradientInterpolation::from_u8(interpolation_bits).map(|i| i as u8)
+                != Some(interpolation_bits)
+        {
+            return Err(Error::invalid_data("Reserved gradient interpolation mode"));
+        }
+        let spread = GradientSpread::from_u8(spread_bits)
             .ok_or_else(|| Error::invalid_data("Invalid gradient spread mode"))?;
-        let interpolation = GradientInterpolation::from_u8((flags >> 4) & 0b11)
+        let interpolation = GradientInterpolation::from_u8(interpolation_bits)
             .ok_or_else(|| Error::invalid_data("Invalid gradient interpolation mode"))?;
         let num_records: usize = (flags & 0b1111).into();
-        Ok((num_records, spread, interpolation))
+        let canonical_flags =
+            ((spread as u8) << 6) | ((interpolation as u8) << 4) | (flags & 0b1111);
+        let raw_flags = if flags != canonical_flags {
+            Some(flags)
+        } else {
+            None
+        };
+        Ok((num_records, spread, interpolation, raw_flags))
+    }
+
+    /// In strict mode, checks that a gradient has records and that their ratios don't decrease.
+    /// Flash Player renders these gradients anyway, so they are allowed otherwise.
+    fn validate_gradient_records(&self, records: &[GradientRecord]) -> Result<()> {
+        if !self.strict {
+            return Ok(());
+        }
+        if records.is_empty() {
+            return Err(Error::invalid_data("Gradient has no records"));
+        }
+        if records.windows(2).any(|pair| pair[1].ratio < pair[0].ratio) {
+            return Err(Error::invalid_data("Gradient ratios are not in order"));
+        }
+        Ok(())
     }
 
     fn read_shape_record(
@@ -1847,9 +2185,8 @@ impl<'a> Reader<'a> {
         // TODO: What's a best way to know if the tag has a color transform?
         // You only know if there is still data remaining after the matrix.
         // This sucks.
-        let mut vector = [0; 128];
-        self.get_mut().read_exact(&mut vector[..tag_length])?;
-        let mut reader = Reader::new(&vector[..], self.version);
+        let data = self.read_slice(tag_length)?;
+        let mut reader = Reader::new(data, self.version);
         Ok(PlaceObject {
             version: 1,
             action: PlaceObjectAction::Place(reader.read_u16()?),
@@ -2029,7 +2366,7 @@ impl<'a> Reader<'a> {
         BlendMode::from_u8(self.read_u8()?).ok_or_else(|| Error::invalid_data("Invalid blend mode"))
     }
 
-    fn read_clip_actions(&mut self) -> Result<Vec<ClipAction<'a>>> {
+    pub(crate) fn read_clip_actions(&mut self) -> Result<Vec<ClipAction<'a>>> {
         self.read_u16()?; // Must be 0
         self.read_clip_event_flags(); // All event flags
         let mut clip_actions = vec![];
@@ -2182,11 +2519,27 @@ impl<'a> Reader<'a> {
                 let num_matrix_rows = self.read_u8()?;
                 let divisor = self.read_fixed16()?;
                 let bias = self.read_fixed16()?;
-                let num_entries = num_matrix_cols * num_matrix_rows;
-                let mut matrix = Vec::with_capacity(num_entries as usize);
-                for _ in 0..num_entries {
+                let num_entries = usize::from(num_matrix_cols) * usize::from(num_matrix_rows);
+                // Each entry is 4 bytes, followed by the 4 byte default color and the flags byte.
+                let available_entries = self.input.len().saturating_sub(5) / 4;
+                if num_entries > available_entries {
+                    if self.strict {
+                        return Err(Error::invalid_data(
+                            "Convolution filter matrix is larger than the filter",
+                        ));
+                    }
+                    log::warn!(
+                        "Convolution filter declares {} matrix entries, but only {} are present",
+                        num_entries,
+                        available_entries
+                    );
+                }
+                let mut matrix = Vec::with_capacity(num_entries);
+                for _ in 0..num_entries.min(available_entries) {
                     matrix.push(self.read_fixed16()?);
                 }
+                // Missing entries are zero, so the matrix always has `cols * rows` entries.
+                matrix.resize(num_entries, Fixed16::ZERO);
                 let default_color = self.read_rgba()?;
                 let flags = self.read_u8()?;
                 Filter::ConvolutionFilter(Box::new(ConvolutionFilter {
@@ -2265,6 +2618,34 @@ impl<'a> Reader<'a> {
         })
     }
 
+    /// In strict mode, checks the constraints that SWF19 and the FLV spec place on each compression format.
+    fn validate_sound_format(&self, format: &SoundFormat) -> Result<()> {
+        if !self.strict {
+            return Ok(());
+        }
+        match format.compression {
+            AudioCompression::Mp3 if format.sample_rate == 5512 => Err(Error::invalid_data(
+                "MP3 sound can't use a 5.5kHz sample rate",
+            )),
+            AudioCompression::Nellymoser16Khz
+            | AudioCompression::Nellymoser8Khz
+            | AudioCompression::Nellymoser
+                if format.is_stereo =>
+            {
+                Err(Error::invalid_data("Nellymoser sound must be mono"))
+            }
+            // Speex is always 16kHz mono, with the rate field set to 0.
+            AudioCompression::Speex
+                if format.is_stereo || format.sample_rate != 5512 || !format.is_16_bit =>
+            {
+                Err(Error::invalid_data(
+                    "Speex sound must be 16-bit mono with a sample rate of 0",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn read_sound_info(&mut self) -> Result<SoundInfo> {
         let flags = self.read_u8()?;
         let event = SoundEvent::from_u8((flags >> 4) & 0b11).unwrap();
@@ -2536,10 +2917,38 @@ impl<'a> Reader<'a> {
                 num_colors: self.read_u8()?,
             },
             4 if version == 1 => BitmapFormat::Rgb15,
+            4 if self.strict => {
+                return Err(Error::invalid_data(
+                    "DefineBitsLossless2 can't use the 15-bit RGB format.",
+                ))
+            }
+            // SWF19 only allows 15-bit RGB in DefineBitsLossless, but some tools write it in DefineBitsLossless2 too.
+            // The format has no alpha channel, so the bitmap is opaque.
+            4 => {
+                log::warn!(
+                    "DefineBitsLossless2 uses the 15-bit RGB format, reading it without alpha"
+                );
+                BitmapFormat::Rgb15
+            }
             5 => BitmapFormat::Rgb32,
             _ => return Err(Error::invalid_data("Invalid bitmap format.")),
         };
         let data = self.read_slice_to_end();
+
+        let expected_len = bitmap_data_len(version, format, width, height);
+        if let Some(decompressed_len) = decompressed_len(data, expected_len) {
+            if decompressed_len < expected_len {
+                return Err(Error::invalid_data(format!(
+                    "DefineBitsLossless{} of {}x{} {:?} needs {} bytes of bitmap data, but only has {}",
+                    if version == 1 { "" } else { "2" },
+                    width,
+                    height,
+                    format,
+                    expected_len,
+                    decompressed_len
+                )));
+            }
+        }
         Ok(DefineBitsLossless {
             version,
             id,
@@ -2566,9 +2975,7 @@ impl<'a> Reader<'a> {
     pub fn read_debug_id(&mut self) -> Result<DebugId> {
         // Not documented in SWF19 reference.
         // See http://wahlers.com.br/claus/blog/undocumented-swf-tags-written-by-mxmlc/
-        let mut debug_id = [0u8; 16];
-        self.get_mut().read_exact(&mut debug_id)?;
-        Ok(debug_id)
+        self.read_array()
     }
 
     pub fn read_name_character(&mut self) -> Result<NameCharacter<'a>> {
@@ -2582,6 +2989,7 @@ impl<'a> Reader<'a> {
     }
 }
 
+#[cfg(feature = "std")]
 pub fn read_compression_type<R: Read>(mut input: R) -> Result<Compression> {
     let mut signature = [0u8; 3];
     input.read_exact(&mut signature)?;
@@ -2599,6 +3007,7 @@ pub fn read_compression_type<R: Read>(mut input: R) -> Result<Compression> {
 pub mod tests {
     use super::*;
     use crate::tag_code::TagCode;
+    #[cfg(feature = "std")]
     use crate::test_data;
     use std::vec::Vec;
 
@@ -2607,11 +3016,13 @@ pub mod tests {
         Reader::new(data, default_version)
     }
 
+    #[cfg(feature = "std")]
     fn read_from_file(path: &str) -> SwfBuf {
         let data = std::fs::read(path).unwrap();
         decompress_swf(&data[..]).unwrap()
     }
 
+    #[cfg(feature = "std")]
     pub fn read_tag_bytes_from_file_with_index(
         path: &str,
         tag_code: TagCode,
@@ -2661,10 +3072,12 @@ pub mod tests {
         }
     }
 
+    #[cfg(feature = "std")]
     pub fn read_tag_bytes_from_file(path: &str, tag_code: TagCode) -> Vec<u8> {
         read_tag_bytes_from_file_with_index(path, tag_code, 0)
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn read_swfs() {
         assert_eq!(
@@ -2685,6 +3098,147 @@ pub mod tests {
         }
     }
 
+    /// Every tag in the test swfs with a code in `TagCode` is read as a typed tag, not `Tag::Unknown`
+    #[cfg(feature = "std")]
+    #[test]
+    fn read_swfs_no_unknown_known_tags() {
+        fn check_tags(path: &std::path::Path, tags: &[Tag]) {
+            for tag in tags {
+                match tag {
+                    Tag::Unknown { tag_code, .. } => assert!(
+                        TagCode::from_u16(*tag_code).is_none(),
+                        "{}: tag {} read as Unknown",
+                        path.display(),
+                        tag_code
+                    ),
+                    Tag::DefineSprite(sprite) => check_tags(path, &sprite.tags),
+                    _ => {}
+                }
+            }
+        }
+
+        for entry in std::fs::read_dir("tests/swfs").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("swf") {
+                continue;
+            }
+            let data = std::fs::read(&path).unwrap();
+            // LZMA swfs can't be read without the feature
+            let swf_buf = match decompress_swf(&data[..]) {
+                Ok(swf_buf) => swf_buf,
+                Err(_) => continue,
+            };
+            let swf = parse_swf(&swf_buf).unwrap();
+            check_tags(&path, &swf.tags);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn read_swf_trailing_bytes() {
+        let data = std::fs::read("tests/swfs/zlib.swf").unwrap();
+        let swf_buf = decompress_swf(&data[..]).unwrap();
+        assert_eq!(swf_buf.header.trailing_bytes(), 0);
+        assert_eq!(swf_buf.header.compressed_len(), Some(data.len() as u64 - 8));
+
+        let mut data_with_junk = data.clone();
+        data_with_junk.extend_from_slice(b"garbage");
+        let swf_buf_with_junk = decompress_swf(&data_with_junk[..]).unwrap();
+        assert_eq!(swf_buf_with_junk.header.trailing_bytes(), 7);
+        assert_eq!(
+            swf_buf_with_junk.header.compressed_len(),
+            swf_buf.header.compressed_len()
+        );
+        assert_eq!(swf_buf_with_junk.data, swf_buf.data);
+
+        let data = std::fs::read("tests/swfs/uncompressed.swf").unwrap();
+        let mut data_with_junk = data.clone();
+        data_with_junk.extend_from_slice(b"garbage");
+        let swf_buf = decompress_swf(&data_with_junk[..]).unwrap();
+        assert_eq!(swf_buf.header.compressed_len(), None);
+        assert_eq!(swf_buf.header.trailing_bytes(), 7);
+    }
+
+    /// Returns the body of a DefineButton2 tag, with `padding` junk bytes inserted between the
+    /// button records and the button actions.
+    #[cfg(feature = "std")]
+    fn define_button_2_with_padding(padding: usize) -> Vec<u8> {
+        let tag_bytes =
+            read_tag_bytes_from_file("tests/swfs/DefineButton2-CS6.swf", TagCode::DefineButton2);
+        let mut tag_reader = reader(&tag_bytes);
+        let (_, tag_len) = tag_reader.read_tag_code_and_length().unwrap();
+        let mut body = tag_reader.get_ref()[..tag_len].to_vec();
+
+        // The offset is 3 bytes into the tag, after the character id and flags.
+        let action_offset = u16::from_le_bytes([body[3], body[4]]);
+        let records_end = 3 + usize::from(action_offset);
+        body.splice(records_end..records_end, vec![0xff; padding]);
+        body[3..5].copy_from_slice(&(action_offset + padding as u16).to_le_bytes());
+        body
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn read_define_button_2_action_offset() {
+        let body = define_button_2_with_padding(0);
+        let expected = reader(&body).read_define_button_2().unwrap();
+        let strict = Reader::new_strict(&body, 13)
+            .read_define_button_2()
+            .unwrap();
+        assert_eq!(strict, expected);
+        assert!(!expected.actions.is_empty());
+
+        // Lenient mode seeks to the action offset, like Flash Player.
+        let mangled = define_button_2_with_padding(2);
+        let lenient = reader(&mangled).read_define_button_2().unwrap();
+        assert_eq!(lenient, expected);
+
+        Reader::new_strict(&mangled, 13)
+            .read_define_button_2()
+            .unwrap_err();
+    }
+
+    #[test]
+    fn read_enable_telemetry_hash_length() {
+        let hash: Vec<u8> = (0..34).collect();
+        let tag_bytes = |hash_len: usize| {
+            let header = ((TagCode::EnableTelemetry as u16) << 6) | (2 + hash_len as u16);
+            let mut bytes = header.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&[0, 0]);
+            bytes.extend_from_slice(&hash[..hash_len]);
+            bytes
+        };
+        fn password_hash(tag: Tag) -> TelemetryPasswordHash {
+            match tag {
+                Tag::EnableTelemetry { password_hash } => password_hash,
+                tag => panic!("Expected EnableTelemetry, got {:?}", tag),
+            }
+        }
+
+        for (hash_len, expected) in [
+            (0, TelemetryPasswordHash::NoPassword),
+            (32, TelemetryPasswordHash::from_bytes(&hash[..32]).unwrap()),
+        ] {
+            let bytes = tag_bytes(hash_len);
+            let strict = Reader::new_strict(&bytes, 10).read_tag().unwrap();
+            assert_eq!(password_hash(strict), expected);
+            assert_eq!(expected.as_bytes(), &hash[..hash_len]);
+        }
+
+        // Lenient mode keeps a hash if there are enough bytes for one.
+        for (hash_len, expected) in [
+            (10, TelemetryPasswordHash::NoPassword),
+            (34, TelemetryPasswordHash::from_bytes(&hash[..32]).unwrap()),
+        ] {
+            let bytes = tag_bytes(hash_len);
+            Reader::new_strict(&bytes, 10).read_tag().unwrap_err();
+            let lenient = Reader::new(&bytes, 10).read_tag().unwrap();
+            assert_eq!(password_hash(lenient), expected);
+        }
+        assert_eq!(TelemetryPasswordHash::from_bytes(&hash[..31]), None);
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn read_invalid_swf() {
         let junk = [0u8; 128];
@@ -2693,6 +3247,7 @@ pub mod tests {
         assert!(result.is_err());
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn read_compression_type() {
         assert_eq!(
@@ -2739,6 +3294,21 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn read_past_end() {
+        let buf: &[u8] = &[0b1011_0101, 0xff];
+        let mut input = Reader::new(buf, 1);
+        let mut bits = input.bits();
+        assert_eq!(bits.read_ubits(3).unwrap(), 0b101);
+        assert_eq!(bits.read_sbits(9).unwrap(), 0b1_0101_1111 - 0x200);
+        assert!(matches!(bits.read_ubits(5), Err(Error::UnexpectedEof(_))));
+        assert!(bits.read_ubits(33).is_err());
+        assert!(matches!(
+            reader(&[1, 2, 3]).read_u32(),
+            Err(Error::UnexpectedEof(_))
+        ));
+    }
+
     #[test]
     fn read_sbits() {
         let buf: &[u8] = &[0b01010101, 0b00100101];
@@ -2986,9 +3556,180 @@ pub mod tests {
         // TODO: Read LineStyle2 from DefineShape4.
     }
 
+    /// An identity matrix, the given gradient flags byte, and a DefineShape3+ record for each ratio.
+    fn gradient_bytes(flags: u8, ratios: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0, flags];
+        for &ratio in ratios {
+            bytes.extend_from_slice(&[ratio, 255, 0, 0, 255]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn read_convolution_filter_size() {
+        // A 255x255 matrix with only two entries present.
+        let mut bytes = vec![5, 255, 255];
+        bytes.extend_from_slice(&[0, 0, 1, 0]); // Divisor
+        bytes.extend_from_slice(&[0, 0, 0, 0]); // Bias
+        bytes.extend_from_slice(&[0, 0, 1, 0, 0, 0, 2, 0]); // Matrix
+        bytes.extend_from_slice(&[255, 0, 0, 255, 0b11]); // Default color, flags
+        let filter = reader(&bytes).read_filter().unwrap();
+        match filter {
+            Filter::ConvolutionFilter(convolve) => {
+                assert_eq!(convolve.matrix.len(), 255 * 255);
+                assert_eq!(convolve.matrix[0], Fixed16::ONE);
+                assert_eq!(convolve.matrix[1], Fixed16::from_f64(2.0));
+                assert_eq!(convolve.matrix[2], Fixed16::ZERO);
+                assert_eq!(convolve.default_color, Color::from_rgba(0xffff0000));
+                assert!(convolve.is_clamped && convolve.is_preserve_alpha);
+            }
+            _ => panic!("Expected ConvolutionFilter, got {:?}", filter),
+        }
+        assert!(Reader::new_strict(&bytes, 13).read_filter().is_err());
+    }
+
+    #[test]
+    fn read_sound_stream_head_missing_latency_seek() {
+        // MP3 at 44kHz, 16-bit stereo, without the latency seek field.
+        let mut tag_bytes = ((TagCode::SoundStreamHead2 as u16) << 6 | 4)
+            .to_le_bytes()
+            .to_vec();
+        tag_bytes.extend_from_slice(&[0b0010_1111, 0b0010_1111, 0x40, 0x02]);
+
+        let tag = reader(&tag_bytes).read_tag().unwrap();
+        match &tag {
+            Tag::SoundStreamHead2(head) => {
+                assert_eq!(head.stream_format.compression, AudioCompression::Mp3);
+                assert_eq!(head.num_samples_per_block, 576);
+                assert_eq!(head.latency_seek, 0);
+                assert!(head.latency_seek_missing);
+            }
+            _ => panic!("Expected SoundStreamHead2, got {:?}", tag),
+        }
+        assert!(Reader::new_strict(&tag_bytes, 13).read_tag().is_err());
+    }
+
+    #[test]
+    fn read_define_sound_format_constraints() {
+        let sound_bytes = |format: u8| {
+            let mut bytes = vec![1, 0, format];
+            bytes.extend_from_slice(&[4, 0, 0, 0]); // Sample count
+            bytes.extend_from_slice(&[1, 2, 3, 4]);
+            bytes
+        };
+
+        // Speex, 16-bit mono with a rate of 0.
+        let bytes = sound_bytes(0b1011_00_1_0);
+        let sound = Reader::new_strict(&bytes, 13).read_define_sound().unwrap();
+        assert_eq!(sound.format.compression, AudioCompression::Speex);
+        assert_eq!(sound.format.sample_rate, 5512);
+        assert_eq!(sound.data, [1, 2, 3, 4]);
+
+        for format in [
+            0b1011_00_1_1, // Stereo Speex
+            0b1011_11_1_0, // Speex with a rate
+            0b0110_10_1_1, // Stereo Nellymoser
+            0b0010_00_1_0, // 5.5kHz MP3
+        ] {
+            let bytes = sound_bytes(format);
+            assert!(reader(&bytes).read_define_sound().is_ok());
+            assert!(
+                Reader::new_strict(&bytes, 13).read_define_sound().is_err(),
+                "{:#010b}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn read_font_align_zones() {
+        let zone = |num_zone_data: u8, mask: u8| {
+            let mut bytes = vec![num_zone_data];
+            bytes.extend_from_slice(&[0x2a, 0x33, 0, 0, 0, 0, 0xce, 0x42]);
+            bytes.push(mask);
+            bytes
+        };
+        let header = [1, 0, 0b01_000000];
+
+        // Zones with an empty mask are written by the Flash IDE.
+        let mut bytes = header.to_vec();
+        bytes.extend(zone(2, 0b11));
+        bytes.extend(zone(2, 0));
+        for mut reader in [reader(&bytes), Reader::new_strict(&bytes, 13)] {
+            match reader.read_define_font_align_zones().unwrap() {
+                Tag::DefineFontAlignZones { zones, .. } => {
+                    assert_eq!(zones.len(), 2);
+                    assert_eq!(zones[0].mask, FontAlignZoneMask::X | FontAlignZoneMask::Y);
+                    assert_eq!(zones[1].mask, FontAlignZoneMask::empty());
+                    assert_eq!(zones[1].raw_mask, None);
+                }
+                tag => panic!("Expected DefineFontAlignZones, got {:?}", tag),
+            }
+        }
+
+        // A truncated zone is dropped.
+        let mut bytes = header.to_vec();
+        bytes.extend(zone(2, 0b11));
+        bytes.extend_from_slice(&[2, 0, 0]);
+        match reader(&bytes).read_define_font_align_zones().unwrap() {
+            Tag::DefineFontAlignZones { zones, .. } => assert_eq!(zones.len(), 1),
+            tag => panic!("Expected DefineFontAlignZones, got {:?}", tag),
+        }
+        assert!(Reader::new_strict(&bytes, 13)
+            .read_define_font_align_zones()
+            .is_err());
+
+        // Unusual constant bytes are kept.
+        let mut bytes = header.to_vec();
+        bytes.extend(zone(3, 0b1000_0001));
+        match reader(&bytes).read_define_font_align_zones().unwrap() {
+            Tag::DefineFontAlignZones { zones, .. } => {
+                assert_eq!(zones[0].mask, FontAlignZoneMask::X);
+                assert_eq!(zones[0].raw_num_zone_data, Some(3));
+                assert_eq!(zones[0].raw_mask, Some(0b1000_0001));
+            }
+            tag => panic!("Expected DefineFontAlignZones, got {:?}", tag),
+        }
+        assert!(Reader::new_strict(&bytes, 13)
+            .read_define_font_align_zones()
+            .is_err());
+    }
+
     #[test]
     fn read_gradient() {
-        // TODO
+        let ratios: Vec<u8> = (0..15).map(|i| i * 17).collect();
+        let bytes = gradient_bytes(0b00_00_1111, &ratios);
+        let gradient = reader(&bytes).read_gradient(3).unwrap();
+        assert_eq!(gradient.records.len(), 15);
+        assert_eq!(gradient.records[14].ratio, 238);
+        assert_eq!(gradient.raw_flags, None);
+        assert_eq!(
+            Reader::new_strict(&bytes, 13).read_gradient(3).unwrap(),
+            gradient
+        );
+    }
+
+    #[test]
+    fn read_gradient_strict() {
+        // Ratios out of order.
+        let bytes = gradient_bytes(0b00_00_0010, &[200, 100]);
+        let gradient = reader(&bytes).read_gradient(3).unwrap();
+        assert_eq!(gradient.records[0].ratio, 200);
+        assert_eq!(gradient.records[1].ratio, 100);
+        assert!(Reader::new_strict(&bytes, 13).read_gradient(3).is_err());
+
+        // No records.
+        let bytes = gradient_bytes(0b00_00_0000, &[]);
+        assert!(reader(&bytes).read_gradient(3).unwrap().records.is_empty());
+        assert!(Reader::new_strict(&bytes, 13).read_gradient(3).is_err());
+
+        // Reserved spread and interpolation modes are read as pad and RGB.
+        let bytes = gradient_bytes(0b11_10_0001, &[0]);
+        let gradient = reader(&bytes).read_gradient(3).unwrap();
+        assert_eq!(gradient.spread, GradientSpread::Pad);
+        assert_eq!(gradient.interpolation, GradientInterpolation::Rgb);
+        assert_eq!(gradient.raw_flags, Some(0b11_10_0001));
+        assert!(Reader::new_strict(&bytes, 13).read_gradient(3).is_err());
     }
 
     #[test]
@@ -3029,6 +3770,7 @@ pub mod tests {
         assert_eq!(read(&[0b11_0100_0_0, 0b100010_00]), shape_record);
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn read_tags() {
         for (swf_version, expected_tag, tag_bytes) in test_data::tag_tests() {
@@ -3111,4 +3853,71 @@ pub mod tests {
             place_object
         );
     }
+
+    /// A zlib stream of `data` in a single uncompressed block.
+    fn zlib_stored(data: &[u8]) -> Vec<u8> {
+        let len = data.len() as u16;
+        let mut stream = vec![0x78, 0x01, 0x01];
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(data);
+        let (mut a, mut b) = (1u32, 0u32);
+        for byte in data {
+            a = (a + u32::from(*byte)) % 65521;
+            b = (b + a) % 65521;
+        }
+        stream.extend_from_slice(&((b << 16) | a).to_be_bytes());
+        stream
+    }
+
+    #[test]
+    fn read_define_bits_lossless_rgb15_in_version_2() {
+        // A 2x2 15-bit RGB bitmap, with rows padded to 4 bytes.
+        let mut body = vec![1, 0, 4, 2, 0, 2, 0];
+        body.extend(zlib_stored(&[0; 8]));
+
+        let lenient = reader(&body).read_define_bits_lossless(2).unwrap();
+        assert_eq!(lenient.version, 2);
+        assert_eq!(lenient.format, BitmapFormat::Rgb15);
+        assert!(Reader::new_strict(&body, 8)
+            .read_define_bits_lossless(2)
+            .is_err());
+        assert_eq!(
+            Reader::new_strict(&body, 8)
+                .read_define_bits_lossless(1)
+                .unwrap()
+                .format,
+            BitmapFormat::Rgb15
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn read_define_bits_lossless_checks_data_len() {
+        // A 3x2 bitmap with a 2 color RGB table, rows are padded to 4 bytes: 2 * 3 + 4 * 2 = 14 bytes.
+        let body = |data_len| {
+            let mut body = vec![1, 0, 3, 3, 0, 2, 0, 1];
+            body.extend(zlib_stored(&vec![0; data_len]));
+            body
+        };
+        assert_eq!(
+            reader(&body(14))
+                .read_define_bits_lossless(1)
+                .unwrap()
+                .format,
+            BitmapFormat::ColorMap8 { num_colors: 1 }
+        );
+        let error = reader(&body(13))
+            .read_define_bits_lossless(1)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("needs 14 bytes"), "{}", error);
+        // DefineBitsLossless2 color tables are RGBA, 2 * 4 + 4 * 2 = 16 bytes.
+        assert!(reader(&body(14)).read_define_bits_lossless(2).is_err());
+        assert!(reader(&body(16)).read_define_bits_lossless(2).is_ok());
+        // Cut short partway through the stream.
+        let mut cut = body(14);
+        cut.truncate(cut.len() - 8);
+        assert!(reader(&cut).read_define_bits_lossless(1).is_err());
+    }
 }
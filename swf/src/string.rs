@@ -1,7 +1,9 @@
 //! String type used by SWF files.
 
+use crate::prelude::*;
+use alloc::borrow::Cow;
+use core::fmt;
 pub use encoding_rs::{Encoding, SHIFT_JIS, UTF_8, WINDOWS_1252};
-use std::{borrow::Cow, fmt};
 
 /// A bstr-like string type analogous to [`str`] that's returned by SWF parsing functions:
 ///
@@ -94,6 +96,34 @@ impl SwfStr {
         Self::from_bytes_null_terminated(string.as_bytes())
     }
 
+    /// Creates a new UTF-8 `SwfStr` from a Rust [`str`], removing a trailing NULL byte (`0`) if present.
+    ///
+    /// Unlike [`SwfStr::from_utf8_str_null_terminated`], a missing NULL byte is not an error,
+    /// and only the final NULL byte is removed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf::SwfStr;
+    ///
+    /// let s = SwfStr::from_utf8_str_with_nul("Verdana\0");
+    /// assert_eq!(s, "Verdana");
+    ///
+    /// let s = SwfStr::from_utf8_str_with_nul("Verdana");
+    /// assert_eq!(s, "Verdana");
+    ///
+    /// let s = SwfStr::from_utf8_str_with_nul("a\0b\0");
+    /// assert_eq!(s.as_bytes(), b"a\0b");
+    /// ```
+    #[inline]
+    pub fn from_utf8_str_with_nul(string: &str) -> &Self {
+        let bytes = string.as_bytes();
+        match bytes.split_last() {
+            Some((0, rest)) => Self::from_bytes(rest),
+            _ => Self::from_bytes(bytes),
+        }
+    }
+
     /// Creates a new `SwfStr` with the given encoding from a Rust [`str`].
     /// Returns `None` if the encoding is not lossless.
     ///
@@ -199,6 +229,51 @@ impl SwfStr {
         self.string.len()
     }
 
+    /// Returns `true` if the string starts with the given bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf::SwfStr;
+    ///
+    /// let s = SwfStr::from_utf8_str("_level0.foo");
+    /// assert!(s.starts_with("_level"));
+    /// assert!(s.starts_with(SwfStr::from_utf8_str("_level0")));
+    /// assert!(!s.starts_with("_root"));
+    ///
+    /// // Works with any encoding, or invalid data.
+    /// let s = SwfStr::from_bytes(&[0xE9, 0xFF, 0x41]);
+    /// assert!(s.starts_with([0xE9, 0xFF]));
+    /// ```
+    #[inline]
+    pub fn starts_with(&self, prefix: impl AsRef<[u8]>) -> bool {
+        self.string.starts_with(prefix.as_ref())
+    }
+
+    /// Compares two strings, ignoring ASCII case.
+    ///
+    /// This matches how AVM1 compares identifiers in SWF6 and lower, where only
+    /// the ASCII letters `A-Z` are case-folded. Other bytes, including non-ASCII
+    /// WINDOWS-1252 characters and invalid UTF-8, must match exactly.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf::SwfStr;
+    ///
+    /// let s = SwfStr::from_utf8_str("onEnterFrame");
+    /// assert!(s.eq_ignore_case("ONENTERFRAME"));
+    /// assert!(!s.eq_ignore_case("onEnterFrame2"));
+    ///
+    /// // WINDOWS-1252 "É" and "é" are not folded.
+    /// let s = SwfStr::from_bytes(&[0xC9]);
+    /// assert!(!s.eq_ignore_case(SwfStr::from_bytes(&[0xE9])));
+    /// ```
+    #[inline]
+    pub fn eq_ignore_case(&self, other: impl AsRef<[u8]>) -> bool {
+        self.string.eq_ignore_ascii_case(other.as_ref())
+    }
+
     /// Decodes the string into a Rust UTF-8 [`str`].
     ///
     /// The UTF-8 replacement character will be used for any invalid data.
@@ -248,6 +323,13 @@ impl<'a> From<&'a str> for &'a SwfStr {
     }
 }
 
+impl AsRef<[u8]> for SwfStr {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.string
+    }
+}
+
 impl<T: ?Sized + AsRef<str>> PartialEq<T> for SwfStr {
     fn eq(&self, other: &T) -> bool {
         &self.string == other.as_ref().as_bytes()
@@ -264,10 +346,79 @@ impl fmt::Debug for SwfStr {
         for chr in self
             .string
             .iter()
-            .flat_map(|&c| std::ascii::escape_default(c))
+            .flat_map(|&c| core::ascii::escape_default(c))
         {
             fmt::Write::write_char(f, char::from(chr))?;
         }
         fmt::Write::write_char(f, '"')
     }
 }
+
+impl fmt::Display for SwfStr {
+    /// Formats the `SwfStr` as UTF-8.
+    ///
+    /// The UTF-8 replacement character will be used for any invalid data,
+    /// use [`SwfStr::to_str_lossy`] for other encodings.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&String::from_utf8_lossy(&self.string), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            SwfStr::from_utf8_str("Hello, 🌏!").to_string(),
+            "Hello, 🌏!"
+        );
+        // Invalid UTF-8 is replaced.
+        assert_eq!(SwfStr::from_bytes(b"ab\xFFcd").to_string(), "ab\u{FFFD}cd");
+        // WINDOWS-1252 data is displayed as if it were UTF-8.
+        assert_eq!(
+            SwfStr::from_bytes(&[0x63, 0x61, 0x66, 0xE9]).to_string(),
+            "caf\u{FFFD}"
+        );
+    }
+
+    #[test]
+    fn to_string_lossy() {
+        let s = SwfStr::from_bytes(&[0x63, 0x61, 0x66, 0xE9]);
+        assert_eq!(s.to_string_lossy(WINDOWS_1252), "café");
+        assert_eq!(s.to_string_lossy(UTF_8), "caf\u{FFFD}");
+
+        let s = SwfStr::from_bytes(&[0xE2, 0x82, 0xAC, 0x80]);
+        assert_eq!(s.to_string_lossy(UTF_8), "€\u{FFFD}");
+        assert_eq!(s.to_string_lossy(WINDOWS_1252), "â‚¬€");
+    }
+
+    #[test]
+    fn eq_ignore_case() {
+        assert!(SwfStr::from_utf8_str("_LEVEL0").eq_ignore_case("_level0"));
+        assert!(SwfStr::from_utf8_str("").eq_ignore_case(""));
+        assert!(!SwfStr::from_utf8_str("abc").eq_ignore_case("abcd"));
+
+        // Non-ASCII WINDOWS-1252 bytes and invalid UTF-8 compare exactly.
+        let s = SwfStr::from_bytes(&[0x41, 0xE9, 0xFF]);
+        assert!(s.eq_ignore_case([0x61, 0xE9, 0xFF]));
+        assert!(!s.eq_ignore_case([0x61, 0xC9, 0xFF]));
+    }
+
+    #[test]
+    fn starts_with() {
+        let s = SwfStr::from_bytes(&[0xFF, 0xFE, 0x41]);
+        assert!(s.starts_with([0xFF]));
+        assert!(s.starts_with(s));
+        assert!(!s.starts_with([0xFE]));
+        assert!(s.starts_with(""));
+    }
+
+    #[test]
+    fn from_utf8_str_with_nul() {
+        assert!(SwfStr::from_utf8_str_with_nul("").is_empty());
+        assert!(SwfStr::from_utf8_str_with_nul("\0").is_empty());
+        assert_eq!(SwfStr::from_utf8_str_with_nul("é\0"), "é");
+    }
+}
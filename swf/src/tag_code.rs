@@ -1,9 +1,11 @@
+use crate::prelude::*;
+
 #[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
 pub enum TagCode {
     End = 0,
     ShowFrame = 1,
     DefineShape = 2,
-
+    FreeCharacter = 3,
     PlaceObject = 4,
     RemoveObject = 5,
     DefineBits = 6,
@@ -25,10 +27,13 @@ pub enum TagCode {
     DefineShape2 = 22,
     DefineButtonCxform = 23,
     Protect = 24,
-
+    PathsArePostScript = 25,
     PlaceObject2 = 26,
 
     RemoveObject2 = 28,
+    SyncFrame = 29,
+
+    FreeAll = 31,
 
     DefineShape3 = 32,
     DefineText2 = 33,
@@ -65,6 +70,7 @@ pub enum TagCode {
 
     PlaceObject3 = 70,
     ImportAssets2 = 71,
+    DoAbcDefine = 72,
 
     DefineFontAlignZones = 73,
     CsmTextSettings = 74,
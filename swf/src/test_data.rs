@@ -1,17 +1,29 @@
 #![allow(clippy::unusual_byte_groupings)]
 
 use crate::avm1::types::*;
+#[cfg(feature = "std")]
 use crate::avm2::read::tests::read_abc_from_file;
+#[cfg(feature = "std")]
 use crate::avm2::types::*;
+#[cfg(feature = "std")]
 use crate::read::tests::{read_tag_bytes_from_file, read_tag_bytes_from_file_with_index};
+#[cfg(feature = "std")]
 use crate::read::{decompress_swf, parse_swf};
 use crate::string::{SwfStr, WINDOWS_1252};
+#[cfg(feature = "std")]
 use crate::tag_code::TagCode;
+#[cfg(feature = "std")]
 use crate::types::*;
+#[cfg(feature = "std")]
 use crate::write::write_swf;
+#[cfg(feature = "std")]
 use std::fs::File;
 use std::vec::Vec;
 
+// The tag and AVM2 tests read their expected bytes from the SWFs in `tests/swfs`, which needs `std`. The AVM1 tests
+// are only slices, and run without it too.
+
+#[cfg(feature = "std")]
 #[allow(dead_code)]
 pub fn echo_swf(filename: &str) {
     let in_data = std::fs::read(filename).unwrap();
@@ -22,10 +34,13 @@ pub fn echo_swf(filename: &str) {
 }
 
 pub type TestData<T> = (u8, T, Vec<u8>);
+#[cfg(feature = "std")]
 pub type TagTestData = TestData<Tag<'static>>;
 pub type Avm1TestData = TestData<Action<'static>>;
+#[cfg(feature = "std")]
 pub type Avm2TestData = TestData<AbcFile>;
 
+#[cfg(feature = "std")]
 pub fn tag_tests() -> Vec<TagTestData> {
     vec![
         (
@@ -36,6 +51,8 @@ pub fn tag_tests() -> Vec<TagTestData> {
                 grid_fit: TextGridFit::SubPixel,
                 thickness: 1.0,
                 sharpness: 2.0,
+                raw_flags: None,
+                raw_reserved: None,
             }),
             read_tag_bytes_from_file("tests/swfs/DefineFont3-CS6.swf", TagCode::CsmTextSettings),
         ),
@@ -329,25 +346,12 @@ pub fn tag_tests() -> Vec<TagTestData> {
             2,
             Tag::DefineButtonSound(Box::new(ButtonSounds {
                 id: 3,
-                up_to_over_sound: Some((
-                    2,
-                    SoundInfo {
-                        event: SoundEvent::Event,
-                        in_sample: None,
-                        out_sample: None,
-                        num_loops: 1,
-                        envelope: None,
-                    },
-                )),
+                up_to_over_sound: Some((2, SoundInfo::new())),
                 over_to_down_sound: Some((
                     2,
-                    SoundInfo {
-                        event: SoundEvent::Start,
-                        in_sample: None,
-                        out_sample: None,
-                        num_loops: 2,
-                        envelope: None,
-                    },
+                    SoundInfo::new()
+                        .with_event(SoundEvent::Start)
+                        .with_num_loops(2),
                 )),
                 down_to_over_sound: None,
                 over_to_up_sound: None,
@@ -359,44 +363,37 @@ pub fn tag_tests() -> Vec<TagTestData> {
         ),
         (
             4,
-            Tag::DefineEditText(Box::new(EditText {
-                id: 2,
-                bounds: Rectangle {
-                    x_min: Twips::from_pixels(-2.0),
-                    x_max: Twips::from_pixels(77.9),
-                    y_min: Twips::from_pixels(-2.0),
-                    y_max: Twips::from_pixels(23.9),
-                },
-                font_id: Some(1),
-                font_class_name: None,
-                height: Some(Twips::from_pixels(18.0)),
-                color: Some(Color {
+            Tag::DefineEditText(Box::new(
+                EditText::new(
+                    2,
+                    Rectangle {
+                        x_min: Twips::from_pixels(-2.0),
+                        x_max: Twips::from_pixels(77.9),
+                        y_min: Twips::from_pixels(-2.0),
+                        y_max: Twips::from_pixels(23.9),
+                    },
+                )
+                .with_font_id(1)
+                .with_height(Twips::from_pixels(18.0))
+                .with_color(Color {
                     r: 0,
                     g: 255,
                     b: 0,
                     a: 255,
-                }),
-                max_length: None,
-                layout: Some(TextLayout {
+                })
+                .with_layout(TextLayout {
                     align: TextAlign::Justify,
                     left_margin: Twips::from_pixels(3.0),
                     right_margin: Twips::from_pixels(4.0),
                     indent: Twips::from_pixels(1.0),
                     leading: Twips::from_pixels(2.0),
-                }),
-                variable_name: SwfStr::from_str_with_encoding("foo", WINDOWS_1252).unwrap(),
-                initial_text: Some(SwfStr::from_str_with_encoding("-_-", WINDOWS_1252).unwrap()),
-                is_word_wrap: false,
-                is_multiline: true,
-                is_password: false,
-                is_read_only: true,
-                is_auto_size: false,
-                is_selectable: true,
-                has_border: true,
-                was_static: false,
-                is_html: false,
-                is_device_font: true,
-            })),
+                })
+                .with_variable_name(SwfStr::from_str_with_encoding("foo", WINDOWS_1252).unwrap())
+                .with_initial_text(SwfStr::from_str_with_encoding("-_-", WINDOWS_1252).unwrap())
+                .with_is_multiline(true)
+                .with_is_read_only(true)
+                .with_has_border(true),
+            )),
             read_tag_bytes_from_file("tests/swfs/DefineEditText-MX.swf", TagCode::DefineEditText),
         ),
         (
@@ -624,12 +621,18 @@ pub fn tag_tests() -> Vec<TagTestData> {
                         width: 0,
                         bottom: 0,
                         height: 17102,
+                        mask: FontAlignZoneMask::X | FontAlignZoneMask::Y,
+                        raw_num_zone_data: None,
+                        raw_mask: None,
                     },
                     FontAlignZone {
                         left: 15333,
                         width: 0,
                         bottom: 0,
                         height: 17102,
+                        mask: FontAlignZoneMask::X | FontAlignZoneMask::Y,
+                        raw_num_zone_data: None,
+                        raw_mask: None,
                     },
                 ],
             },
@@ -638,6 +641,18 @@ pub fn tag_tests() -> Vec<TagTestData> {
                 TagCode::DefineFontAlignZones,
             ),
         ),
+        (
+            8,
+            Tag::DefineFontAlignZones {
+                id: 1,
+                thickness: FontThickness::Medium,
+                zones: vec![],
+            },
+            read_tag_bytes_from_file(
+                "tests/swfs/DefineFont3-DeviceText.swf",
+                TagCode::DefineFontAlignZones,
+            ),
+        ),
         (
             10,
             Tag::DefineFont4(Font4 {
@@ -736,6 +751,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                                 },
                             },
                         ],
+                        raw_flags: None,
                     })],
                     line_styles: vec![LineStyle::new()
                         .with_width(Twips::from_pixels(10.0))
@@ -833,6 +849,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                                 },
                             },
                         ],
+                        raw_flags: None,
                     })],
                     line_styles: vec![LineStyle::new()
                         .with_width(Twips::from_pixels(2.0))
@@ -887,6 +904,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                         },
                     ],
                 },
+                end_edges_offset: None,
             })),
             read_tag_bytes_from_file(
                 "tests/swfs/DefineMorphShape-MX.swf",
@@ -954,6 +972,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                                     },
                                 },
                             ],
+                            raw_flags: None,
                         },
                         focal_point: Fixed8::from_f64(0.97265625),
                     }],
@@ -1063,6 +1082,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                                     },
                                 },
                             ],
+                            raw_flags: None,
                         },
                         focal_point: Fixed8::from_f64(-0.9921875),
                     }],
@@ -1119,6 +1139,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                         },
                     ],
                 },
+                end_edges_offset: None,
             })),
             read_tag_bytes_from_file(
                 "tests/swfs/DefineMorphShape2.swf",
@@ -1176,6 +1197,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                                 },
                             },
                         ],
+                        raw_flags: None,
                     })],
                     line_styles: vec![LineStyle::new()
                         .with_width(Twips::ZERO)
@@ -1250,6 +1272,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                                 },
                             },
                         ],
+                        raw_flags: None,
                     })],
                     line_styles: vec![LineStyle::new()
                         .with_width(Twips::from_pixels(0.0))
@@ -1273,6 +1296,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                         },
                     ],
                 },
+                end_edges_offset: None,
             })),
             read_tag_bytes_from_file(
                 "tests/swfs/DefineMorphShape2-GradientFlags.swf",
@@ -1445,6 +1469,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                                 },
                             },
                         ],
+                        raw_flags: None,
                     })],
                     line_styles: vec![],
                 },
@@ -1568,6 +1593,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                                         },
                                     },
                                 ],
+                                raw_flags: None,
                             },
                             focal_point: Fixed8::from_f64(0.56640625),
                         },
@@ -1620,6 +1646,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                                         },
                                     },
                                 ],
+                                raw_flags: None,
                             }))
                             .with_allow_scale_y(false)
                             .with_is_pixel_hinted(true),
@@ -1840,16 +1867,18 @@ pub fn tag_tests() -> Vec<TagTestData> {
         ),
         (
             10,
-            Tag::EnableTelemetry { password_hash: &[] },
+            Tag::EnableTelemetry {
+                password_hash: TelemetryPasswordHash::NoPassword,
+            },
             read_tag_bytes_from_file("tests/swfs/EnableTelemetry.swf", TagCode::EnableTelemetry),
         ),
         (
             10,
             Tag::EnableTelemetry {
-                password_hash: &[
+                password_hash: TelemetryPasswordHash::Sha256(&[
                     207, 128, 205, 138, 237, 72, 45, 93, 21, 39, 215, 220, 114, 252, 239, 248, 78,
                     99, 38, 89, 40, 72, 68, 125, 45, 192, 176, 232, 125, 252, 154, 144,
-                ],
+                ]),
             },
             read_tag_bytes_from_file(
                 "tests/swfs/EnableTelemetry-password.swf",
@@ -1959,52 +1988,22 @@ pub fn tag_tests() -> Vec<TagTestData> {
         ),
         (
             4,
-            Tag::PlaceObject(Box::new(PlaceObject {
-                version: 2,
-                action: PlaceObjectAction::Place(1),
-                depth: 1,
-                matrix: Some(Matrix::IDENTITY),
-                color_transform: None,
-                ratio: None,
-                name: None,
-                clip_depth: None,
-                class_name: None,
-                filters: None,
-                background_color: None,
-                blend_mode: None,
-                clip_actions: None,
-                has_image: false,
-                is_bitmap_cached: None,
-                is_visible: None,
-                amf_data: None,
-            })),
+            Tag::PlaceObject(Box::new(
+                PlaceObject::new_place(1, 1).with_matrix(Matrix::IDENTITY),
+            )),
             read_tag_bytes_from_file("tests/swfs/DefineShape.swf", TagCode::PlaceObject2),
         ),
         (
             5, // Specifically test for SWFv5 ClipActions.
-            Tag::PlaceObject(Box::new(PlaceObject {
-                version: 2,
-                action: PlaceObjectAction::Place(2),
-                depth: 1,
-                matrix: Some(Matrix::IDENTITY),
-                color_transform: None,
-                ratio: None,
-                name: None,
-                clip_depth: None,
-                class_name: None,
-                filters: None,
-                background_color: None,
-                blend_mode: None,
-                clip_actions: Some(vec![ClipAction {
-                    events: ClipEventFlag::ENTER_FRAME,
-                    key_code: None,
-                    action_data: &[150, 6, 0, 0, 99, 108, 105, 112, 0, 38, 0],
-                }]),
-                has_image: false,
-                is_bitmap_cached: None,
-                is_visible: None,
-                amf_data: None,
-            })),
+            Tag::PlaceObject(Box::new(
+                PlaceObject::new_place(2, 1)
+                    .with_matrix(Matrix::IDENTITY)
+                    .with_clip_actions(vec![ClipAction {
+                        events: ClipEventFlag::ENTER_FRAME,
+                        key_code: None,
+                        action_data: &[150, 6, 0, 0, 99, 108, 105, 112, 0, 38, 0],
+                    }]),
+            )),
             read_tag_bytes_from_file(
                 "tests/swfs/PlaceObject2-ClipActionsV5-CS6.swf",
                 TagCode::PlaceObject2,
@@ -2012,41 +2011,55 @@ pub fn tag_tests() -> Vec<TagTestData> {
         ),
         (
             6, // ClipActions added in SWF version 5-6.
-            Tag::PlaceObject(Box::new(PlaceObject {
-                version: 2,
-                action: PlaceObjectAction::Place(2),
-                depth: 1,
-                matrix: Some(Matrix::IDENTITY),
-                color_transform: None,
-                ratio: None,
-                name: None,
-                clip_depth: None,
-                class_name: None,
-                filters: None,
-                background_color: None,
-                blend_mode: None,
-                clip_actions: Some(vec![
-                    ClipAction {
-                        events: ClipEventFlag::PRESS | ClipEventFlag::RELEASE,
-                        key_code: None,
-                        action_data: &[150, 3, 0, 0, 65, 0, 38, 0],
-                    },
-                    ClipAction {
-                        events: ClipEventFlag::KEY_PRESS,
-                        key_code: Some(99),
-                        action_data: &[150, 3, 0, 0, 66, 0, 38, 0],
-                    },
-                    ClipAction {
-                        events: ClipEventFlag::ENTER_FRAME,
-                        key_code: None,
-                        action_data: &[150, 3, 0, 0, 67, 0, 38, 0],
-                    },
-                ]),
-                has_image: false,
-                is_bitmap_cached: None,
-                is_visible: None,
-                amf_data: None,
-            })),
+            Tag::PlaceObject(Box::new(
+                PlaceObject::new_place(2, 1)
+                    .with_matrix(Matrix::IDENTITY)
+                    .with_clip_actions(vec![
+                        ClipAction {
+                            events: ClipEventFlag::PRESS | ClipEventFlag::RELEASE,
+                            key_code: None,
+                            action_data: &[150, 3, 0, 0, 65, 0, 38, 0],
+                        },
+                        ClipAction {
+                            events: ClipEventFlag::KEY_PRESS,
+                            key_code: Some(99),
+                            action_data: &[150, 3, 0, 0, 66, 0, 38, 0],
+                        },
+                        ClipAction {
+                            events: ClipEventFlag::ENTER_FRAME,
+                            key_code: None,
+                            action_data: &[150, 3, 0, 0, 67, 0, 38, 0],
+                        },
+                    ]),
+            )),
+            read_tag_bytes_from_file(
+                "tests/swfs/PlaceObject2-ClipActions-CS6.swf",
+                TagCode::PlaceObject2,
+            ),
+        ),
+        (
+            7, // SWFv7 uses the same 4-byte ClipEventFlags as SWFv6.
+            Tag::PlaceObject(Box::new(
+                PlaceObject::new_place(2, 1)
+                    .with_matrix(Matrix::IDENTITY)
+                    .with_clip_actions(vec![
+                        ClipAction {
+                            events: ClipEventFlag::PRESS | ClipEventFlag::RELEASE,
+                            key_code: None,
+                            action_data: &[150, 3, 0, 0, 65, 0, 38, 0],
+                        },
+                        ClipAction {
+                            events: ClipEventFlag::KEY_PRESS,
+                            key_code: Some(99),
+                            action_data: &[150, 3, 0, 0, 66, 0, 38, 0],
+                        },
+                        ClipAction {
+                            events: ClipEventFlag::ENTER_FRAME,
+                            key_code: None,
+                            action_data: &[150, 3, 0, 0, 67, 0, 38, 0],
+                        },
+                    ]),
+            )),
             read_tag_bytes_from_file(
                 "tests/swfs/PlaceObject2-ClipActions-CS6.swf",
                 TagCode::PlaceObject2,
@@ -2308,6 +2321,7 @@ pub fn tag_tests() -> Vec<TagTestData> {
                 },
                 num_samples_per_block: 229,
                 latency_seek: 0,
+                latency_seek_missing: false,
             })),
             read_tag_bytes_from_file("tests/swfs/SoundStreamHead2.swf", TagCode::SoundStreamHead2),
         ),
@@ -2329,13 +2343,11 @@ pub fn tag_tests() -> Vec<TagTestData> {
             4,
             Tag::StartSound(StartSound {
                 id: 1,
-                sound_info: Box::new(SoundInfo {
-                    event: SoundEvent::Start,
-                    in_sample: None,
-                    out_sample: None,
-                    num_loops: 3,
-                    envelope: None,
-                }),
+                sound_info: Box::new(
+                    SoundInfo::new()
+                        .with_event(SoundEvent::Start)
+                        .with_num_loops(3),
+                ),
             }),
             read_tag_bytes_from_file("tests/swfs/DefineSound.swf", TagCode::StartSound),
         ),
@@ -2343,17 +2355,11 @@ pub fn tag_tests() -> Vec<TagTestData> {
             9,
             Tag::StartSound2 {
                 class_name: "TestSound".into(),
-                sound_info: Box::new(SoundInfo {
-                    event: SoundEvent::Event,
-                    in_sample: None,
-                    out_sample: None,
-                    num_loops: 1,
-                    envelope: Some(vec![SoundEnvelopePoint {
-                        sample: 0,
-                        left_volume: 0.0,
-                        right_volume: 1.0,
-                    }]),
-                }),
+                sound_info: Box::new(SoundInfo::new().with_envelope(vec![SoundEnvelopePoint {
+                    sample: 0,
+                    left_volume: 0.0,
+                    right_volume: 1.0,
+                }])),
             },
             read_tag_bytes_from_file("tests/swfs/StartSound2.swf", TagCode::StartSound2),
         ),
@@ -2366,6 +2372,20 @@ pub fn tag_tests() -> Vec<TagTestData> {
             }),
             read_tag_bytes_from_file("tests/swfs/DefineVideoStream.swf", TagCode::VideoFrame),
         ),
+        (1, Tag::FreeAll, vec![0b11_000000, 0b111]),
+        (1, Tag::FreeCharacter(2), vec![0b11_000010, 0, 2, 0]),
+        (1, Tag::PathsArePostScript, vec![0b01_000000, 0b110]),
+        (2, Tag::SyncFrame, vec![0b01_000000, 0b111]),
+        (
+            5,
+            Tag::SerialNumber(SwfStr::from_str_with_encoding("1-2", WINDOWS_1252).unwrap()),
+            vec![0b01_000100, 0b1010, b'1', b'-', b'2', 0],
+        ),
+        (
+            9,
+            Tag::DoAbcDefine(&[0x10, 0, 0x2e, 0]),
+            vec![0b00_000100, 0b10010, 0x10, 0, 0x2e, 0],
+        ),
         (
             1,
             Tag::Unknown {
@@ -2764,6 +2784,7 @@ pub fn avm1_tests() -> Vec<Avm1TestData> {
     ]
 }
 
+#[cfg(feature = "std")]
 pub fn avm2_tests() -> Vec<Avm2TestData> {
     vec![(
         10,
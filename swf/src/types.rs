@@ -3,11 +3,12 @@
 //! These structures are documented in the Adobe SWF File Format Specification
 //! version 19 (henceforth SWF19):
 //! <https://www.adobe.com/content/dam/acom/en/devnet/pdf/swf-file-format-spec.pdf>
+use crate::prelude::*;
 use crate::string::SwfStr;
 use bitflags::bitflags;
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
 use enum_map::Enum;
-use std::fmt::{self, Display, Formatter};
-use std::str::FromStr;
 
 mod color;
 mod fixed;
@@ -77,6 +78,8 @@ pub struct HeaderExt {
     pub(crate) file_attributes: FileAttributes,
     pub(crate) background_color: Option<SetBackgroundColor>,
     pub(crate) uncompressed_len: u32,
+    pub(crate) compressed_len: Option<u64>,
+    pub(crate) trailing_bytes: usize,
 }
 
 impl HeaderExt {
@@ -88,6 +91,8 @@ impl HeaderExt {
             file_attributes: Default::default(),
             background_color: None,
             uncompressed_len: 0,
+            compressed_len: None,
+            trailing_bytes: 0,
         }
     }
 
@@ -114,7 +119,7 @@ impl HeaderExt {
     /// Whether this SWF contains XMP metadata in a Metadata tag.
     #[inline]
     pub fn has_metdata(&self) -> bool {
-        self.file_attributes.contains(FileAttributes::HAS_METADATA)
+        self.file_attributes.has_metadata()
     }
 
     /// Returns the basic SWF header.
@@ -126,8 +131,7 @@ impl HeaderExt {
     /// Whether this SWF uses ActionScript 3.0 (AVM2).
     #[inline]
     pub fn is_action_script_3(&self) -> bool {
-        self.file_attributes
-            .contains(FileAttributes::IS_ACTION_SCRIPT_3)
+        self.file_attributes.is_action_script_3()
     }
 
     /// The number of frames on the root timeline.
@@ -154,17 +158,45 @@ impl HeaderExt {
         self.uncompressed_len
     }
 
+    /// The number of compressed bytes that were consumed when decompressing the SWF body.
+    ///
+    /// `None` will be returned if the SWF is uncompressed.
+    #[inline]
+    pub fn compressed_len(&self) -> Option<u64> {
+        self.compressed_len
+    }
+
+    /// The ratio of the compressed body length to the uncompressed body length.
+    ///
+    /// `None` will be returned if the SWF is uncompressed.
+    #[inline]
+    pub fn compression_ratio(&self) -> Option<f64> {
+        let body_len = self.uncompressed_len.saturating_sub(8);
+        match self.compressed_len {
+            Some(compressed_len) if body_len > 0 => Some(compressed_len as f64 / body_len as f64),
+            _ => None,
+        }
+    }
+
+    /// The number of bytes that were left in the input after the end of the SWF body.
+    ///
+    /// Non-zero values usually indicate junk appended to the file, while truncated files will show up
+    /// as a mismatch between `uncompressed_len` and the decompressed data.
+    #[inline]
+    pub fn trailing_bytes(&self) -> usize {
+        self.trailing_bytes
+    }
+
     /// Whether this SWF requests hardware acceleration to blit to the screen.
     #[inline]
     pub fn use_direct_blit(&self) -> bool {
-        self.file_attributes
-            .contains(FileAttributes::USE_DIRECT_BLIT)
+        self.file_attributes.use_direct_blit()
     }
 
     /// Whether this SWF requests hardware acceleration for compositing.
     #[inline]
     pub fn use_gpu(&self) -> bool {
-        self.file_attributes.contains(FileAttributes::USE_GPU)
+        self.file_attributes.use_gpu()
     }
 
     /// Whether this SWF should be placed in the network sandbox when run locally.
@@ -173,8 +205,7 @@ impl HeaderExt {
     /// SWFs in the local sandbox can only access local resources, not network resources.
     #[inline]
     pub fn use_network_sandbox(&self) -> bool {
-        self.file_attributes
-            .contains(FileAttributes::USE_NETWORK_SANDBOX)
+        self.file_attributes.use_network_sandbox()
     }
 }
 
@@ -288,6 +319,52 @@ impl Default for FileAttributes {
     }
 }
 
+impl FileAttributes {
+    /// The attributes of an ActionScript 1.0/2.0 (AVM1) SWF.
+    #[inline]
+    pub fn avm1(use_network_sandbox: bool) -> Self {
+        let mut attributes = Self::empty();
+        attributes.set(Self::USE_NETWORK_SANDBOX, use_network_sandbox);
+        attributes
+    }
+
+    /// The attributes of an ActionScript 3.0 (AVM2) SWF, which must be SWF9 or later.
+    #[inline]
+    pub fn avm2(use_network_sandbox: bool) -> Self {
+        Self::avm1(use_network_sandbox) | Self::IS_ACTION_SCRIPT_3
+    }
+
+    /// Whether this SWF requests hardware acceleration to blit to the screen.
+    #[inline]
+    pub fn use_direct_blit(self) -> bool {
+        self.contains(Self::USE_DIRECT_BLIT)
+    }
+
+    /// Whether this SWF requests hardware acceleration for compositing.
+    #[inline]
+    pub fn use_gpu(self) -> bool {
+        self.contains(Self::USE_GPU)
+    }
+
+    /// Whether this SWF contains XMP metadata in a Metadata tag.
+    #[inline]
+    pub fn has_metadata(self) -> bool {
+        self.contains(Self::HAS_METADATA)
+    }
+
+    /// Whether this SWF uses ActionScript 3.0 (AVM2).
+    #[inline]
+    pub fn is_action_script_3(self) -> bool {
+        self.contains(Self::IS_ACTION_SCRIPT_3)
+    }
+
+    /// Whether this SWF should be placed in the network sandbox when run locally.
+    #[inline]
+    pub fn use_network_sandbox(self) -> bool {
+        self.contains(Self::USE_NETWORK_SANDBOX)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct FrameLabel<'a> {
     pub label: &'a SwfStr,
@@ -330,6 +407,151 @@ pub struct PlaceObject<'a> {
     pub amf_data: Option<&'a [u8]>,
 }
 
+impl<'a> PlaceObject<'a> {
+    /// A PlaceObject2 with `action` at `depth`, and nothing else set.
+    #[inline]
+    fn new(action: PlaceObjectAction, depth: Depth) -> Self {
+        Self {
+            version: 2,
+            action,
+            depth,
+            matrix: None,
+            color_transform: None,
+            ratio: None,
+            name: None,
+            clip_depth: None,
+            class_name: None,
+            filters: None,
+            background_color: None,
+            blend_mode: None,
+            clip_actions: None,
+            has_image: false,
+            is_bitmap_cached: None,
+            is_visible: None,
+            amf_data: None,
+        }
+    }
+
+    /// A PlaceObject2 that places character `id` at `depth`.
+    #[inline]
+    pub fn new_place(id: CharacterId, depth: Depth) -> Self {
+        Self::new(PlaceObjectAction::Place(id), depth)
+    }
+
+    /// A PlaceObject2 that modifies the character already at `depth`.
+    #[inline]
+    pub fn new_modify(depth: Depth) -> Self {
+        Self::new(PlaceObjectAction::Modify, depth)
+    }
+
+    /// A PlaceObject2 that replaces the character at `depth` with character `id`.
+    #[inline]
+    pub fn new_replace(id: CharacterId, depth: Depth) -> Self {
+        Self::new(PlaceObjectAction::Replace(id), depth)
+    }
+
+    /// Sets the PlaceObject tag version, the setters for fields added in later versions raise it as needed.
+    #[inline]
+    pub fn with_version(mut self, val: u8) -> Self {
+        self.version = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_matrix(mut self, val: Matrix) -> Self {
+        self.matrix = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_color_transform(mut self, val: ColorTransform) -> Self {
+        self.color_transform = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_ratio(mut self, val: u16) -> Self {
+        self.ratio = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_name(mut self, val: &'a SwfStr) -> Self {
+        self.name = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_clip_depth(mut self, val: Depth) -> Self {
+        self.clip_depth = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_clip_actions(mut self, val: Vec<ClipAction<'a>>) -> Self {
+        self.clip_actions = Some(val);
+        self
+    }
+
+    // The fields below were added in PlaceObject3, and raise the version to at least 3.
+    #[inline]
+    pub fn with_class_name(mut self, val: &'a SwfStr) -> Self {
+        self.version = self.version.max(3);
+        self.class_name = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_filters(mut self, val: Vec<Filter>) -> Self {
+        self.version = self.version.max(3);
+        self.filters = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_background_color(mut self, val: Color) -> Self {
+        self.version = self.version.max(3);
+        self.background_color = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_blend_mode(mut self, val: BlendMode) -> Self {
+        self.version = self.version.max(3);
+        self.blend_mode = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_has_image(mut self, val: bool) -> Self {
+        self.version = self.version.max(3);
+        self.has_image = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_is_bitmap_cached(mut self, val: bool) -> Self {
+        self.version = self.version.max(3);
+        self.is_bitmap_cached = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_is_visible(mut self, val: bool) -> Self {
+        self.version = self.version.max(3);
+        self.is_visible = Some(val);
+        self
+    }
+
+    /// Sets the AMF data, which was added in PlaceObject4 and raises the version to 4.
+    #[inline]
+    pub fn with_amf_data(mut self, val: &'a [u8]) -> Self {
+        self.version = self.version.max(4);
+        self.amf_data = Some(val);
+        self
+    }
+}
+
 bitflags! {
     pub struct PlaceFlag: u16 {
         const MOVE = 1 << 0;
@@ -643,6 +865,8 @@ pub enum Tag<'a> {
     DefineText(Box<Text>),
     DefineVideoStream(DefineVideoStream),
     DoAbc(DoAbc<'a>),
+    /// The DoAbc tag of Flash Player 9 prereleases, without the flags and name
+    DoAbcDefine(&'a [u8]),
     DoAction(DoAction<'a>),
     DoInitAction {
         id: CharacterId,
@@ -650,7 +874,7 @@ pub enum Tag<'a> {
     },
     EnableDebugger(&'a SwfStr),
     EnableTelemetry {
-        password_hash: &'a [u8],
+        password_hash: TelemetryPasswordHash<'a>,
     },
     End,
     Metadata(&'a SwfStr),
@@ -678,11 +902,17 @@ pub enum Tag<'a> {
     RemoveObject(RemoveObject),
     VideoFrame(VideoFrame<'a>),
     FileAttributes(FileAttributes),
+    FreeAll,
+    FreeCharacter(CharacterId),
+    PathsArePostScript,
+    SyncFrame,
 
     FrameLabel(FrameLabel<'a>),
     DefineSceneAndFrameLabelData(DefineSceneAndFrameLabelData<'a>),
 
     ProductInfo(ProductInfo),
+    /// Tag 41 as written by older tools, a serial number string rather than a `ProductInfo`
+    SerialNumber(&'a SwfStr),
 
     Unknown {
         tag_code: u16,
@@ -750,6 +980,56 @@ pub struct SoundInfo {
     pub envelope: Option<SoundEnvelope>,
 }
 
+impl Default for SoundInfo {
+    /// Plays the sound once from start to end, like a SoundInfo with no flags set.
+    fn default() -> Self {
+        Self {
+            event: SoundEvent::Event,
+            in_sample: None,
+            out_sample: None,
+            num_loops: 1,
+            envelope: None,
+        }
+    }
+}
+
+impl SoundInfo {
+    #[inline]
+    pub fn new() -> SoundInfo {
+        Default::default()
+    }
+
+    #[inline]
+    pub fn with_event(mut self, val: SoundEvent) -> Self {
+        self.event = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_in_sample(mut self, val: u32) -> Self {
+        self.in_sample = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_out_sample(mut self, val: u32) -> Self {
+        self.out_sample = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_num_loops(mut self, val: u16) -> Self {
+        self.num_loops = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_envelope(mut self, val: SoundEnvelope) -> Self {
+        self.envelope = Some(val);
+        self
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
 pub enum SoundEvent {
     Event = 0,
@@ -841,6 +1121,22 @@ pub struct Gradient {
     pub spread: GradientSpread,
     pub interpolation: GradientInterpolation,
     pub records: Vec<GradientRecord>,
+
+    /// The gradient flags byte as it was read from the SWF.
+    ///
+    /// This is only `Some` when the byte can't be reproduced from the other fields, such as when
+    /// it uses a reserved spread or interpolation mode. The writer uses it as long as it still
+    /// matches the other fields.
+    pub raw_flags: Option<u8>,
+}
+
+impl Gradient {
+    /// Encodes the spread mode, interpolation mode and record count into a gradient flags byte.
+    pub fn flags(&self) -> u8 {
+        ((self.spread as u8) << 6)
+            | ((self.interpolation as u8) << 4)
+            | ((self.records.len() as u8) & 0b1111)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
@@ -1120,6 +1416,11 @@ pub struct SoundStreamHead {
     pub playback_format: SoundFormat,
     pub num_samples_per_block: u16,
     pub latency_seek: i16,
+
+    /// Whether an MP3 stream head left out the latency seek field, which some encoders do.
+    ///
+    /// `latency_seek` is 0 in this case, and the writer leaves the field out as well.
+    pub latency_seek_missing: bool,
 }
 
 pub type SoundStreamBlock<'a> = &'a [u8];
@@ -1199,6 +1500,12 @@ pub struct DefineMorphShape {
     pub has_scaling_strokes: bool,
     pub start: MorphShape,
     pub end: MorphShape,
+
+    /// The offset to the end edges as it was read from the SWF, kept only when it doesn't match the length of the
+    /// start shape so that such a tag can be written back byte-for-byte.
+    ///
+    /// When this is `None`, the writer calculates the offset from the start shape.
+    pub end_edges_offset: Option<u32>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -1225,6 +1532,62 @@ pub struct Font<'a> {
     pub layout: Option<FontLayout>,
     pub glyphs: Vec<Glyph>,
     pub flags: FontFlag,
+
+    /// The offset table as it was read from the SWF.
+    ///
+    /// When this is `None`, or the offsets no longer match the glyphs (e.g. after editing
+    /// `glyphs`), the writer calculates the offsets from the glyph shapes.
+    pub offsets: Option<FontOffsets>,
+}
+
+/// The password of an EnableTelemetry tag, which a telemetry client such as Adobe Scout must give before it can
+/// gather advanced telemetry.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TelemetryPasswordHash<'a> {
+    /// No password is needed, the tag has no hash.
+    NoPassword,
+
+    /// The SHA-256 hash of the password.
+    Sha256(&'a [u8; 32]),
+}
+
+impl<'a> TelemetryPasswordHash<'a> {
+    /// Returns the password hash stored as `bytes`, which must be empty or a 32 byte SHA-256 hash.
+    #[inline]
+    pub fn from_bytes(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.is_empty() {
+            Some(Self::NoPassword)
+        } else {
+            bytes.try_into().ok().map(Self::Sha256)
+        }
+    }
+
+    /// Returns the bytes of the hash as it is stored in the tag, empty if there is no password.
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match self {
+            Self::NoPassword => &[],
+            Self::Sha256(hash) => &hash[..],
+        }
+    }
+}
+
+/// The offset table of a DefineFont2 or DefineFont3 tag, along with the other encoding
+/// details that are needed to write the tag back out byte-for-byte.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FontOffsets {
+    /// The offset of each glyph shape, relative to the start of the offset table.
+    pub glyph_offsets: Vec<u32>,
+
+    /// The offset of the code table, relative to the start of the offset table.
+    ///
+    /// This may be `None` for fonts with no glyphs, as the field is optional in that case.
+    pub code_table_offset: Option<u32>,
+
+    /// Whether the font name has a trailing null byte.
+    ///
+    /// SWF19 states that it shouldn't, but the Flash IDE writes one.
+    pub name_has_null: bool,
 }
 
 bitflags! {
@@ -1347,6 +1710,143 @@ pub struct EditText<'a> {
     pub is_device_font: bool,
 }
 
+impl<'a> EditText<'a> {
+    /// An EditText with no flags set, so it is selectable and uses a device font, and no other fields set.
+    #[inline]
+    pub fn new(id: CharacterId, bounds: Rectangle) -> Self {
+        Self {
+            id,
+            bounds,
+            font_id: None,
+            font_class_name: None,
+            height: None,
+            color: None,
+            max_length: None,
+            layout: None,
+            variable_name: SwfStr::from_bytes(b""),
+            initial_text: None,
+            is_word_wrap: false,
+            is_multiline: false,
+            is_password: false,
+            is_read_only: false,
+            is_auto_size: false,
+            is_selectable: true,
+            has_border: false,
+            was_static: false,
+            is_html: false,
+            is_device_font: true,
+        }
+    }
+
+    #[inline]
+    pub fn with_font_id(mut self, val: CharacterId) -> Self {
+        self.font_id = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_font_class_name(mut self, val: &'a SwfStr) -> Self {
+        self.font_class_name = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_height(mut self, val: Twips) -> Self {
+        self.height = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_color(mut self, val: Color) -> Self {
+        self.color = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_max_length(mut self, val: u16) -> Self {
+        self.max_length = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_layout(mut self, val: TextLayout) -> Self {
+        self.layout = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_variable_name(mut self, val: &'a SwfStr) -> Self {
+        self.variable_name = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_initial_text(mut self, val: &'a SwfStr) -> Self {
+        self.initial_text = Some(val);
+        self
+    }
+
+    #[inline]
+    pub fn with_is_word_wrap(mut self, val: bool) -> Self {
+        self.is_word_wrap = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_is_multiline(mut self, val: bool) -> Self {
+        self.is_multiline = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_is_password(mut self, val: bool) -> Self {
+        self.is_password = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_is_read_only(mut self, val: bool) -> Self {
+        self.is_read_only = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_is_auto_size(mut self, val: bool) -> Self {
+        self.is_auto_size = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_is_selectable(mut self, val: bool) -> Self {
+        self.is_selectable = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_has_border(mut self, val: bool) -> Self {
+        self.has_border = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_was_static(mut self, val: bool) -> Self {
+        self.was_static = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_is_html(mut self, val: bool) -> Self {
+        self.is_html = val;
+        self
+    }
+
+    #[inline]
+    pub fn with_is_device_font(mut self, val: bool) -> Self {
+        self.is_device_font = val;
+        self
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct TextLayout {
     pub align: TextAlign,
@@ -1377,6 +1877,32 @@ pub struct FontAlignZone {
     pub width: i16,
     pub bottom: i16,
     pub height: i16,
+    pub mask: FontAlignZoneMask,
+
+    /// The `NumZoneData` byte as it was read from the SWF.
+    ///
+    /// This is only `Some` when it isn't the usual 2. Two zone data entries are always read and
+    /// written regardless.
+    pub raw_num_zone_data: Option<u8>,
+
+    /// The zone mask byte as it was read from the SWF.
+    ///
+    /// This is only `Some` when its reserved bits are set. The writer uses it as long as it still
+    /// matches `mask`.
+    pub raw_mask: Option<u8>,
+}
+
+impl FontAlignZone {
+    /// The size of a zone in a DefineFontAlignZones tag.
+    pub const ENCODED_LEN: usize = 10;
+}
+
+bitflags! {
+    /// The dimensions that a font align zone applies to.
+    pub struct FontAlignZoneMask: u8 {
+        const X = 1 << 0;
+        const Y = 1 << 1;
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
@@ -1399,6 +1925,26 @@ pub struct CsmTextSettings {
     pub grid_fit: TextGridFit,
     pub thickness: f32, // TODO(Herschel): 0.0 is default. Should be Option?
     pub sharpness: f32,
+
+    /// The flags byte as it was read from the SWF.
+    ///
+    /// This is only `Some` when its reserved bits are set. The writer uses it as long as it still
+    /// matches the other fields.
+    pub raw_flags: Option<u8>,
+
+    /// The reserved byte at the end of the tag, only `Some` when it isn't 0.
+    pub raw_reserved: Option<u8>,
+}
+
+impl CsmTextSettings {
+    /// Encodes the rendering and grid fit settings into a flags byte.
+    pub fn flags(&self) -> u8 {
+        (if self.use_advanced_rendering {
+            0b01_000_000
+        } else {
+            0
+        }) | ((self.grid_fit as u8) << 3)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
@@ -1426,7 +1972,11 @@ pub struct DefineBitsLossless<'a> {
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum BitmapFormat {
-    ColorMap8 { num_colors: u8 },
+    ColorMap8 {
+        num_colors: u8,
+    },
+    /// 15-bit RGB, which SWF19 only allows in DefineBitsLossless. Non-strict readers also accept it in
+    /// DefineBitsLossless2, where it still has no alpha channel.
     Rgb15,
     Rgb32,
 }
@@ -1513,6 +2063,11 @@ pub struct ProductInfo {
     pub compilation_date: u64,
 }
 
+impl ProductInfo {
+    /// The length of a `ProductInfo` tag, tag 41 of any other length is a `Tag::SerialNumber`
+    pub(crate) const LEN: usize = 26;
+}
+
 /// `DebugId` is a UUID written to debug SWFs and used by the Flash Debugger.
 pub type DebugId = [u8; 16];
 
@@ -1524,3 +2079,23 @@ pub struct NameCharacter<'a> {
     pub id: CharacterId,
     pub name: &'a SwfStr,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn place_object_setters_raise_version() {
+        let place_object = PlaceObject::new_place(1, 1).with_ratio(2);
+        assert_eq!(place_object.version, 2);
+        let place_object = place_object.with_blend_mode(BlendMode::Add);
+        assert_eq!(place_object.version, 3);
+        assert_eq!(place_object.with_amf_data(&[]).version, 4);
+
+        // An explicit version isn't lowered.
+        let place_object = PlaceObject::new_modify(1)
+            .with_version(4)
+            .with_is_visible(true);
+        assert_eq!(place_object.version, 4);
+    }
+}
@@ -10,7 +10,7 @@
 //! Use `from_f32`/`from_f64` methods to convert from float to fixed-point.
 //! Extra precision will be truncated, and out-of-range values are saturated.
 
-use std::ops::*;
+use core::ops::*;
 
 macro_rules! define_fixed {
     (
@@ -26,7 +26,7 @@ macro_rules! define_fixed {
         impl $type_name {
             /// The number of integer bits.
             pub const INTEGER_BITS: u8 =
-                (std::mem::size_of::<$underlying_type>() as u8) * 8 - $frac_bits;
+                (core::mem::size_of::<$underlying_type>() as u8) * 8 - $frac_bits;
 
             /// The number of fractional bits.
             pub const FRACTIONAL_BITS: u8 = $frac_bits;
@@ -183,8 +183,8 @@ macro_rules! define_fixed {
             }
         }
 
-        impl std::fmt::Display for $type_name {
-            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        impl core::fmt::Display for $type_name {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
                 write!(f, "{}", self.to_f64())
             }
         }
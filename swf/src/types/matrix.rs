@@ -1,5 +1,5 @@
 use crate::{Fixed16, Twips};
-use std::ops;
+use core::ops;
 
 /// The transformation matrix used by Flash display objects.
 ///
@@ -60,6 +60,9 @@ impl Matrix {
     }
 
     /// Returns a rotation matrix that rotates by `angle` radians.
+    ///
+    /// This needs the `std` feature, as `core` has no `sin` or `cos`.
+    #[cfg(feature = "std")]
     #[inline]
     pub fn rotate(angle: f32) -> Self {
         Self {
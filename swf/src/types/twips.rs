@@ -107,60 +107,60 @@ impl Twips {
     }
 }
 
-impl std::ops::Add for Twips {
+impl core::ops::Add for Twips {
     type Output = Self;
     fn add(self, other: Self) -> Self {
         Self(self.0 + other.0)
     }
 }
 
-impl std::ops::AddAssign for Twips {
+impl core::ops::AddAssign for Twips {
     fn add_assign(&mut self, other: Self) {
         self.0 += other.0
     }
 }
 
-impl std::ops::Sub for Twips {
+impl core::ops::Sub for Twips {
     type Output = Self;
     fn sub(self, other: Self) -> Self {
         Self(self.0 - other.0)
     }
 }
 
-impl std::ops::SubAssign for Twips {
+impl core::ops::SubAssign for Twips {
     fn sub_assign(&mut self, other: Self) {
         self.0 -= other.0
     }
 }
 
-impl std::ops::Mul<i32> for Twips {
+impl core::ops::Mul<i32> for Twips {
     type Output = Self;
     fn mul(self, other: i32) -> Self {
         Self(self.0 * other)
     }
 }
 
-impl std::ops::MulAssign<i32> for Twips {
+impl core::ops::MulAssign<i32> for Twips {
     fn mul_assign(&mut self, other: i32) {
         self.0 *= other
     }
 }
 
-impl std::ops::Div<i32> for Twips {
+impl core::ops::Div<i32> for Twips {
     type Output = Self;
     fn div(self, other: i32) -> Self {
         Self(self.0 / other)
     }
 }
 
-impl std::ops::DivAssign<i32> for Twips {
+impl core::ops::DivAssign<i32> for Twips {
     fn div_assign(&mut self, other: i32) {
         self.0 /= other
     }
 }
 
-impl std::fmt::Display for Twips {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for Twips {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{}", self.to_pixels())
     }
 }
@@ -1,14 +1,15 @@
 use crate::{
     error::{Error, Result},
-    string::SwfStr,
+    extensions::WriteSwfExt,
     tag_code::TagCode,
     types::*,
 };
 use bitstream_io::BitWrite;
-use byteorder::{LittleEndian, WriteBytesExt};
 use std::cmp::max;
 use std::io::{self, Write};
 
+pub mod raw;
+
 /// Writes an SWF file to an output stream.
 /// # Example
 /// ```
@@ -28,7 +29,83 @@ use std::io::{self, Write};
 /// let output = Vec::new();
 /// swf::write_swf(&header, &tags, output).unwrap();
 /// ```
-pub fn write_swf<W: Write>(header: &Header, tags: &[Tag<'_>], mut output: W) -> Result<()> {
+pub fn write_swf<W: Write>(header: &Header, tags: &[Tag<'_>], output: W) -> Result<()> {
+    write_swf_with(header, output, |writer| writer.write_tag_list(tags))
+}
+
+/// Options for `write_swf_with_options`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Whether to check that the SWF version, the `FileAttributes` tag and the action tags agree on which
+    /// ActionScript version the SWF uses before writing it. See `validate_script_version`.
+    pub validate: bool,
+}
+
+/// Writes an SWF file to an output stream, checking it first as set in `options`.
+pub fn write_swf_with_options<W: Write>(
+    header: &Header,
+    tags: &[Tag<'_>],
+    output: W,
+    options: &WriteOptions,
+) -> Result<()> {
+    if options.validate {
+        validate_script_version(header.version, tags)?;
+    }
+    write_swf(header, tags, output)
+}
+
+/// Checks that the SWF version, the `FileAttributes` tag and the action tags of an SWF agree on which ActionScript
+/// version it uses. Players treat SWFs where they don't differently, so this returns an error if:
+/// * `FileAttributes::IS_ACTION_SCRIPT_3` is set below SWF9, where AVM2 was introduced.
+/// * There are `DoAbc` tags without `FileAttributes::IS_ACTION_SCRIPT_3` set, so they won't be run.
+/// * There are `DoAction` or `DoInitAction` tags with `FileAttributes::IS_ACTION_SCRIPT_3` set, so they won't be run.
+///
+/// Only the `FileAttributes` tag at the start of the SWF is used, as players ignore any others.
+pub fn validate_script_version(swf_version: u8, tags: &[Tag<'_>]) -> Result<()> {
+    fn find_action_tags(tags: &[Tag<'_>], has_abc: &mut bool, has_avm1_actions: &mut bool) {
+        for tag in tags {
+            match tag {
+                Tag::DoAbc(_) | Tag::DoAbcDefine(_) => *has_abc = true,
+                Tag::DoAction(_) | Tag::DoInitAction { .. } => *has_avm1_actions = true,
+                Tag::DefineSprite(sprite) => {
+                    find_action_tags(&sprite.tags, has_abc, has_avm1_actions)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let is_action_script_3 = match tags.first() {
+        Some(Tag::FileAttributes(attributes)) => attributes.is_action_script_3(),
+        _ => false,
+    };
+    if is_action_script_3 && swf_version < 9 {
+        return Err(Error::invalid_data(format!(
+            "FileAttributes sets IS_ACTION_SCRIPT_3 in a SWF{}, it requires SWF9 or later",
+            swf_version
+        )));
+    }
+    let (mut has_abc, mut has_avm1_actions) = (false, false);
+    find_action_tags(tags, &mut has_abc, &mut has_avm1_actions);
+    if has_abc && !is_action_script_3 {
+        return Err(Error::invalid_data(
+            "DoAbc tags in a SWF without FileAttributes IS_ACTION_SCRIPT_3 set",
+        ));
+    }
+    if has_avm1_actions && is_action_script_3 {
+        return Err(Error::invalid_data(
+            "DoAction or DoInitAction tags in a SWF with FileAttributes IS_ACTION_SCRIPT_3 set",
+        ));
+    }
+    Ok(())
+}
+
+/// Writes an SWF whose tags are written by `write_tags`, after the header fields of the SWF body.
+fn write_swf_with<W: Write>(
+    header: &Header,
+    mut output: W,
+    write_tags: impl FnOnce(&mut Writer<&mut Vec<u8>>) -> Result<()>,
+) -> Result<()> {
     let signature = match header.compression {
         Compression::None => b"FWS",
         Compression::Zlib => b"CWS",
@@ -47,12 +124,12 @@ pub fn write_swf<W: Write>(header: &Header, tags: &[Tag<'_>], mut output: W) ->
         writer.write_u16(header.num_frames)?;
 
         // Write main timeline tag list.
-        writer.write_tag_list(tags)?;
+        write_tags(&mut writer)?;
     }
 
     // Write SWF header.
     // Uncompressed SWF length.
-    output.write_u32::<LittleEndian>(swf_body.len() as u32 + 8)?;
+    output.write_u32(swf_body.len() as u32 + 8)?;
 
     // Compress SWF body.
     match header.compression {
@@ -104,7 +181,7 @@ fn write_lzma_swf<W: Write>(mut output: W, mut swf_body: &[u8]) -> Result<()> {
 
     // Flash uses a mangled LZMA header, so we have to massage it into the SWF format.
     // https://helpx.adobe.com/flash-player/kb/exception-thrown-you-decompress-lzma-compressed.html
-    output.write_u32::<LittleEndian>(data.len() as u32 - 13)?; // Compressed length (- 13 to not include lzma header)
+    output.write_u32(data.len() as u32 - 13)?; // Compressed length (- 13 to not include lzma header)
     output.write_all(&data[0..5])?; // LZMA properties
     output.write_all(&data[13..])?; // Data
     Ok(())
@@ -117,19 +194,6 @@ fn write_lzma_swf<W: Write>(_output: W, _swf_body: &[u8]) -> Result<()> {
     ))
 }
 
-pub trait SwfWriteExt {
-    fn write_u8(&mut self, n: u8) -> io::Result<()>;
-    fn write_u16(&mut self, n: u16) -> io::Result<()>;
-    fn write_u32(&mut self, n: u32) -> io::Result<()>;
-    fn write_u64(&mut self, n: u64) -> io::Result<()>;
-    fn write_i8(&mut self, n: i8) -> io::Result<()>;
-    fn write_i16(&mut self, n: i16) -> io::Result<()>;
-    fn write_i32(&mut self, n: i32) -> io::Result<()>;
-    fn write_f32(&mut self, n: f32) -> io::Result<()>;
-    fn write_f64(&mut self, n: f64) -> io::Result<()>;
-    fn write_string(&mut self, s: &'_ SwfStr) -> io::Result<()>;
-}
-
 pub struct BitWriter<W: Write> {
     bits: bitstream_io::BitWriter<W, bitstream_io::BigEndian>,
 }
@@ -198,56 +262,12 @@ struct Writer<W: Write> {
     pub version: u8,
 }
 
-impl<W: Write> SwfWriteExt for Writer<W> {
-    #[inline]
-    fn write_u8(&mut self, n: u8) -> io::Result<()> {
-        self.output.write_u8(n)
-    }
-
-    #[inline]
-    fn write_u16(&mut self, n: u16) -> io::Result<()> {
-        self.output.write_u16::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_u32(&mut self, n: u32) -> io::Result<()> {
-        self.output.write_u32::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_u64(&mut self, n: u64) -> io::Result<()> {
-        self.output.write_u64::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_i8(&mut self, n: i8) -> io::Result<()> {
-        self.output.write_i8(n)
-    }
+impl<W: Write> WriteSwfExt for Writer<W> {
+    type Output = W;
 
     #[inline]
-    fn write_i16(&mut self, n: i16) -> io::Result<()> {
-        self.output.write_i16::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_i32(&mut self, n: i32) -> io::Result<()> {
-        self.output.write_i32::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_f32(&mut self, n: f32) -> io::Result<()> {
-        self.output.write_f32::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_f64(&mut self, n: f64) -> io::Result<()> {
-        self.output.write_f64::<LittleEndian>(n)
-    }
-
-    #[inline]
-    fn write_string(&mut self, s: &'_ SwfStr) -> io::Result<()> {
-        self.output.write_all(s.as_bytes())?;
-        self.write_u8(0)
+    fn as_mut_writer(&mut self) -> &mut W {
+        &mut self.output
     }
 }
 
@@ -263,32 +283,15 @@ impl<W: Write> Writer<W> {
         }
     }
 
-    #[inline]
-    fn write_fixed8(&mut self, n: Fixed8) -> io::Result<()> {
-        self.write_i16(n.get())
-    }
-
-    #[inline]
-    fn write_fixed16(&mut self, n: Fixed16) -> io::Result<()> {
-        self.write_i32(n.get())
-    }
-
-    fn write_encoded_u32(&mut self, mut n: u32) -> Result<()> {
-        loop {
-            let mut byte = (n & 0b01111111) as u8;
-            n >>= 7;
-            if n != 0 {
-                byte |= 0b10000000;
-            }
-            self.write_u8(byte)?;
-            if n == 0 {
-                break;
-            }
-        }
-        Ok(())
+    fn write_rectangle(&mut self, rectangle: &Rectangle) -> Result<()> {
+        self.write_rectangle_with_min_bits(rectangle, 0)
     }
 
-    fn write_rectangle(&mut self, rectangle: &Rectangle) -> Result<()> {
+    fn write_rectangle_with_min_bits(
+        &mut self,
+        rectangle: &Rectangle,
+        min_bits: u32,
+    ) -> Result<()> {
         let num_bits = [
             rectangle.x_min,
             rectangle.x_max,
@@ -298,7 +301,8 @@ impl<W: Write> Writer<W> {
         .iter()
         .map(|x| count_sbits_twips(*x))
         .max()
-        .unwrap();
+        .unwrap()
+        .max(min_bits);
         let mut bits = self.bits();
         bits.write_ubits(5, num_bits)?;
         bits.write_sbits_twips(num_bits, rectangle.x_min)?;
@@ -476,26 +480,30 @@ impl<W: Write> Writer<W> {
                 if let Some(password_md5) = password {
                     self.write_tag_header(TagCode::Protect, password_md5.len() as u32 + 3)?;
                     self.write_u16(0)?; // Two null bytes? Not specified in SWF19.
-                    self.write_string(password_md5)?;
+                    self.write_str(password_md5)?;
                 } else {
                     self.write_tag_header(TagCode::Protect, 0)?;
                 }
             }
 
-            #[allow(clippy::unusual_byte_groupings)]
             Tag::CsmTextSettings(ref settings) => {
                 self.write_tag_header(TagCode::CsmTextSettings, 12)?;
                 self.write_character_id(settings.id)?;
-                self.write_u8(
-                    if settings.use_advanced_rendering {
-                        0b01_000000
-                    } else {
-                        0
-                    } | ((settings.grid_fit as u8) << 3),
-                )?;
+                let flags = match settings.raw_flags {
+                    // Preserve reserved bits, as long as the raw byte still decodes to the same settings.
+                    Some(raw_flags)
+                        if (raw_flags & 0b01000000 != 0) == settings.use_advanced_rendering
+                            && TextGridFit::from_u8((raw_flags >> 3) & 0b11)
+                                == Some(settings.grid_fit) =>
+                    {
+                        raw_flags
+                    }
+                    _ => settings.flags(),
+                };
+                self.write_u8(flags)?;
                 self.write_f32(settings.thickness)?;
                 self.write_f32(settings.sharpness)?;
-                self.write_u8(0)?; // Reserved (0).
+                self.write_u8(settings.raw_reserved.unwrap_or(0))?; // Reserved (0).
             }
 
             Tag::DefineBinaryData(ref binary_data) => self.write_define_binary_data(binary_data)?,
@@ -653,16 +661,28 @@ impl<W: Write> Writer<W> {
                 thickness,
                 ref zones,
             } => {
-                self.write_tag_header(TagCode::DefineFontAlignZones, 3 + 10 * zones.len() as u32)?;
+                self.write_tag_header(
+                    TagCode::DefineFontAlignZones,
+                    3 + (FontAlignZone::ENCODED_LEN * zones.len()) as u32,
+                )?;
                 self.write_character_id(id)?;
                 self.write_u8((thickness as u8) << 6)?;
                 for zone in zones {
-                    self.write_u8(2)?; // Always 2 dimensions.
+                    self.write_u8(zone.raw_num_zone_data.unwrap_or(2))?; // Always 2 dimensions.
                     self.write_i16(zone.left)?;
                     self.write_i16(zone.width)?;
                     self.write_i16(zone.bottom)?;
                     self.write_i16(zone.height)?;
-                    self.write_u8(0b000000_11)?; // Always 2 dimensions.
+                    let mask = match zone.raw_mask {
+                        // Preserve reserved bits, as long as the raw byte still has the same mask.
+                        Some(raw_mask)
+                            if FontAlignZoneMask::from_bits_truncate(raw_mask) == zone.mask =>
+                        {
+                            raw_mask
+                        }
+                        _ => zone.mask.bits(),
+                    };
+                    self.write_u8(mask)?;
                 }
             }
 
@@ -676,8 +696,8 @@ impl<W: Write> Writer<W> {
                 let len = name.len() + copyright_info.len() + 4;
                 self.write_tag_header(TagCode::DefineFontName, len as u32)?;
                 self.write_character_id(id)?;
-                self.write_string(name)?;
-                self.write_string(copyright_info)?;
+                self.write_str(name)?;
+                self.write_str(copyright_info)?;
             }
 
             Tag::DefineMorphShape(ref define_morph_shape) => {
@@ -707,9 +727,13 @@ impl<W: Write> Writer<W> {
                 let len = do_abc.data.len() + do_abc.name.len() + 5;
                 self.write_tag_header(TagCode::DoAbc, len as u32)?;
                 self.write_u32(if do_abc.is_lazy_initialize { 1 } else { 0 })?;
-                self.write_string(do_abc.name)?;
+                self.write_str(do_abc.name)?;
                 self.output.write_all(do_abc.data)?;
             }
+            Tag::DoAbcDefine(abc_data) => {
+                self.write_tag_header(TagCode::DoAbcDefine, abc_data.len() as u32)?;
+                self.output.write_all(abc_data)?;
+            }
             Tag::DoAction(action_data) => {
                 self.write_tag_header(TagCode::DoAction, action_data.len() as u32)?;
                 self.output.write_all(action_data)?;
@@ -730,21 +754,24 @@ impl<W: Write> Writer<W> {
                     self.write_tag_header(TagCode::EnableDebugger, len)?;
                 }
 
-                self.write_string(password_md5)?;
+                self.write_str(password_md5)?;
             }
 
             Tag::EnableTelemetry { password_hash } => {
-                if !password_hash.is_empty() {
-                    self.write_tag_header(TagCode::EnableTelemetry, 34)?;
-                    self.write_u16(0)?;
-                    self.output.write_all(&password_hash[0..32])?;
-                } else {
-                    self.write_tag_header(TagCode::EnableTelemetry, 2)?;
-                    self.write_u16(0)?;
-                }
+                let hash = password_hash.as_bytes();
+                self.write_tag_header(TagCode::EnableTelemetry, 2 + hash.len() as u32)?;
+                self.write_u16(0)?; // Reserved
+                self.output.write_all(hash)?;
             }
 
             Tag::End => self.write_tag_header(TagCode::End, 0)?,
+            Tag::FreeAll => self.write_tag_header(TagCode::FreeAll, 0)?,
+            Tag::FreeCharacter(id) => {
+                self.write_tag_header(TagCode::FreeCharacter, 2)?;
+                self.write_character_id(id)?;
+            }
+            Tag::PathsArePostScript => self.write_tag_header(TagCode::PathsArePostScript, 0)?,
+            Tag::SyncFrame => self.write_tag_header(TagCode::SyncFrame, 0)?,
 
             Tag::ImportAssets { url, ref imports } => {
                 let len = imports.iter().map(|e| e.name.len() as u32 + 3).sum::<u32>()
@@ -754,17 +781,17 @@ impl<W: Write> Writer<W> {
                 // SWF v8 and later use ImportAssets2 tag.
                 if self.version >= 8 {
                     self.write_tag_header(TagCode::ImportAssets2, len + 2)?;
-                    self.write_string(url)?;
+                    self.write_str(url)?;
                     self.write_u8(1)?;
                     self.write_u8(0)?;
                 } else {
                     self.write_tag_header(TagCode::ImportAssets, len)?;
-                    self.write_string(url)?;
+                    self.write_str(url)?;
                 }
                 self.write_u16(imports.len() as u16)?;
                 for &ExportedAsset { id, name } in imports {
                     self.write_u16(id)?;
-                    self.write_string(name)?;
+                    self.write_str(name)?;
                 }
             }
 
@@ -775,7 +802,7 @@ impl<W: Write> Writer<W> {
 
             Tag::Metadata(metadata) => {
                 self.write_tag_header(TagCode::Metadata, metadata.len() as u32 + 1)?;
-                self.write_string(metadata)?;
+                self.write_str(metadata)?;
             }
 
             // TODO: Allow clone of color.
@@ -869,7 +896,7 @@ impl<W: Write> Writer<W> {
                         0
                     };
                 self.write_tag_header(TagCode::StartSound2, length)?;
-                self.write_string(class_name)?;
+                self.write_str(class_name)?;
                 self.write_sound_info(sound_info)?;
             }
 
@@ -883,7 +910,7 @@ impl<W: Write> Writer<W> {
                 self.write_u16(symbols.len() as u16)?;
                 for &SymbolClassLink { id, class_name } in symbols {
                     self.write_u16(id)?;
-                    self.write_string(class_name)?;
+                    self.write_str(class_name)?;
                 }
             }
 
@@ -904,7 +931,7 @@ impl<W: Write> Writer<W> {
                 let is_anchor = is_anchor && self.version >= 6;
                 let length = label.len() as u32 + if is_anchor { 2 } else { 1 };
                 self.write_tag_header(TagCode::FrameLabel, length)?;
-                self.write_string(label)?;
+                self.write_str(label)?;
                 if is_anchor {
                     self.write_u8(1)?;
                 }
@@ -914,6 +941,10 @@ impl<W: Write> Writer<W> {
                 self.write_define_scene_and_frame_label_data(data)?
             }
             Tag::ProductInfo(ref product_info) => self.write_product_info(product_info)?,
+            Tag::SerialNumber(serial_number) => {
+                self.write_tag_header(TagCode::ProductInfo, serial_number.len() as u32 + 1)?;
+                self.write_str(serial_number)?;
+            }
             Tag::DebugId(ref debug_id) => self.write_debug_id(debug_id)?,
             Tag::NameCharacter(ref name_character) => self.write_name_character(name_character)?,
             Tag::Unknown { tag_code, data } => {
@@ -1070,7 +1101,7 @@ impl<W: Write> Writer<W> {
             }
 
             // Offset to EndEdges.
-            writer.write_u32(start_buf.len() as u32)?;
+            writer.write_u32(data.end_edges_offset.unwrap_or(start_buf.len() as u32))?;
 
             writer.output.write_all(&start_buf)?;
 
@@ -1259,12 +1290,12 @@ impl<W: Write> Writer<W> {
             writer.write_encoded_u32(data.scenes.len() as u32)?;
             for scene in &data.scenes {
                 writer.write_encoded_u32(scene.frame_num)?;
-                writer.write_string(scene.label)?;
+                writer.write_str(scene.label)?;
             }
             writer.write_encoded_u32(data.frame_labels.len() as u32)?;
             for frame_label in &data.frame_labels {
                 writer.write_encoded_u32(frame_label.frame_num)?;
-                writer.write_string(frame_label.label)?;
+                writer.write_str(frame_label.label)?;
             }
         }
         self.write_tag_header(TagCode::DefineSceneAndFrameLabelData, buf.len() as u32)?;
@@ -1351,7 +1382,7 @@ impl<W: Write> Writer<W> {
         self.write_u16(exports.len() as u16)?;
         for &ExportedAsset { id, name } in exports {
             self.write_u16(id)?;
-            self.write_string(name)?;
+            self.write_str(name)?;
         }
         Ok(())
     }
@@ -1610,9 +1641,21 @@ impl<W: Write> Writer<W> {
     }
 
     fn write_gradient_flags(&mut self, gradient: &Gradient) -> Result<()> {
-        let flags = ((gradient.spread as u8) << 6)
-            | ((gradient.interpolation as u8) << 4)
-            | ((gradient.records.len() as u8) & 0b1111);
+        if gradient.records.len() > 0b1111 {
+            return Err(Error::invalid_data("Gradient has more than 15 records."));
+        }
+        let flags = match gradient.raw_flags {
+            // Preserve reserved modes, as long as the raw byte still decodes to the same gradient.
+            Some(raw_flags)
+                if GradientSpread::from_u8((raw_flags >> 6) & 0b11) == Some(gradient.spread)
+                    && GradientInterpolation::from_u8((raw_flags >> 4) & 0b11)
+                        == Some(gradient.interpolation)
+                    && usize::from(raw_flags & 0b1111) == gradient.records.len() =>
+            {
+                raw_flags
+            }
+            _ => gradient.flags(),
+        };
         self.write_u8(flags)?;
         Ok(())
     }
@@ -1705,7 +1748,7 @@ impl<W: Write> Writer<W> {
 
             if place_object_version >= 3 {
                 if let Some(class_name) = place_object.class_name {
-                    writer.write_string(class_name)?;
+                    writer.write_str(class_name)?;
                 }
             }
 
@@ -1724,7 +1767,7 @@ impl<W: Write> Writer<W> {
                 writer.write_u16(ratio)?;
             }
             if let Some(name) = place_object.name {
-                writer.write_string(name)?;
+                writer.write_str(name)?;
             };
             if let Some(clip_depth) = place_object.clip_depth {
                 writer.write_u16(clip_depth)?;
@@ -1854,6 +1897,13 @@ impl<W: Write> Writer<W> {
             }
 
             Filter::ConvolutionFilter(ref convolve) => {
+                if convolve.matrix.len()
+                    != usize::from(convolve.num_matrix_cols) * usize::from(convolve.num_matrix_rows)
+                {
+                    return Err(Error::invalid_data(
+                        "Convolution filter matrix size doesn't match its rows and columns.",
+                    ));
+                }
                 self.write_u8(5)?;
                 self.write_u8(convolve.num_matrix_cols)?;
                 self.write_u8(convolve.num_matrix_rows)?;
@@ -1911,11 +1961,27 @@ impl<W: Write> Writer<W> {
             self.write_clip_event_flags(all_events)?;
         }
         for action in clip_actions {
-            self.write_clip_event_flags(action.events)?;
+            let events = self.encodable_clip_events(action.events);
+            if events.is_empty() {
+                // An empty flag set would be read back as the end of the list.
+                log::warn!(
+                    "Dropping clip action with no events encodable in SWF{}",
+                    self.version
+                );
+                continue;
+            }
+            self.write_clip_event_flags(events)?;
+            // ActionData length includes the 1 byte key code, which is only present
+            // when the KEY_PRESS event is encoded.
+            let key_code = if events.contains(ClipEventFlag::KEY_PRESS) {
+                Some(action.key_code.unwrap_or_default())
+            } else {
+                None
+            };
             let action_length =
-                action.action_data.len() as u32 + if action.key_code.is_some() { 1 } else { 0 };
+                action.action_data.len() as u32 + if key_code.is_some() { 1 } else { 0 };
             self.write_u32(action_length)?;
-            if let Some(k) = action.key_code {
+            if let Some(k) = key_code {
                 self.write_u8(k)?;
             }
             self.output.write_all(action.action_data)?;
@@ -1928,13 +1994,24 @@ impl<W: Write> Writer<W> {
         Ok(())
     }
 
+    /// Returns the subset of `clip_events` that can be encoded at the writer's SWF version.
+    /// SWFv5 only has the 8 events stored in the first byte of the flags.
+    fn encodable_clip_events(&self, clip_events: ClipEventFlag) -> ClipEventFlag {
+        if self.version >= 6 {
+            clip_events
+        } else {
+            ClipEventFlag::from_bits_truncate(clip_events.bits() & 0xff)
+        }
+    }
+
     fn write_clip_event_flags(&mut self, clip_events: ClipEventFlag) -> Result<()> {
-        // TODO: Assert proper version.
-        let bits = clip_events.bits();
+        // SWF19 pp. 48-50: SWFv5 ClipEventFlags are 2 bytes with the 2nd byte reserved,
+        // expanded to 4 bytes in SWFv6.
+        let bits = self.encodable_clip_events(clip_events).bits();
         if self.version >= 6 {
             self.write_u32(bits)?;
         } else {
-            self.write_u16((bits as u8).into())?;
+            self.write_u16(bits as u16)?;
         }
         Ok(())
     }
@@ -1949,17 +2026,15 @@ impl<W: Write> Writer<W> {
         } else {
             TagCode::SoundStreamHead
         };
-        // MP3 compression has added latency seek field.
-        let length = if stream_head.stream_format.compression == AudioCompression::Mp3 {
-            6
-        } else {
-            4
-        };
+        // MP3 compression has added latency seek field, unless it was missing when read.
+        let has_latency_seek = stream_head.stream_format.compression == AudioCompression::Mp3
+            && !stream_head.latency_seek_missing;
+        let length = if has_latency_seek { 6 } else { 4 };
         self.write_tag_header(tag_code, length)?;
         self.write_sound_format(&stream_head.playback_format)?;
         self.write_sound_format(&stream_head.stream_format)?;
         self.write_u16(stream_head.num_samples_per_block)?;
-        if stream_head.stream_format.compression == AudioCompression::Mp3 {
+        if has_latency_seek {
             self.write_i16(stream_head.latency_seek)?;
         }
         Ok(())
@@ -2022,6 +2097,36 @@ impl<W: Write> Writer<W> {
         Ok(())
     }
 
+    /// Writes the glyph shapes of a DefineFont2 or DefineFont3 tag into a buffer,
+    /// returning the buffer and the position of each glyph shape within it.
+    fn write_glyph_shape_table(glyphs: &[Glyph], swf_version: u8) -> Result<(Vec<u8>, Vec<usize>)> {
+        let mut positions = Vec::with_capacity(glyphs.len());
+        let mut shape_buf = Vec::new();
+        {
+            let mut shape_writer = Writer::new(&mut shape_buf, swf_version);
+
+            // ShapeTable
+            let mut shape_context = ShapeContext {
+                swf_version,
+                shape_version: 1,
+                num_fill_bits: 1,
+                num_line_bits: 0,
+            };
+            for glyph in glyphs {
+                positions.push(shape_writer.output.len());
+
+                shape_writer.write_u8(0b0001_0000)?;
+                let mut bits = shape_writer.bits();
+                for shape_record in &glyph.shape_records {
+                    Self::write_shape_record(shape_record, &mut bits, &mut shape_context)?;
+                }
+                // End shape record.
+                bits.write_ubits(6, 0)?;
+            }
+        }
+        Ok((shape_buf, positions))
+    }
+
     fn write_define_font_2(&mut self, font: &Font) -> Result<()> {
         let mut buf = Vec::new();
         {
@@ -2029,66 +2134,60 @@ impl<W: Write> Writer<W> {
 
             // We must write the glyph shapes into a temporary buffer
             // so that we can calculate their offsets.
-            let mut offsets = Vec::with_capacity(num_glyphs);
-            let mut has_wide_offsets = false;
-            let has_wide_codes = !font.flags.contains(FontFlag::IS_ANSI);
-            let mut shape_buf = Vec::new();
-            {
-                let mut shape_writer = Writer::new(&mut shape_buf, self.version);
-
-                // ShapeTable
-                let mut shape_context = ShapeContext {
-                    swf_version: self.version,
-                    shape_version: 1,
-                    num_fill_bits: 1,
-                    num_line_bits: 0,
-                };
-                for glyph in &font.glyphs {
-                    // Store offset for later.
-                    let offset = num_glyphs * 4 + shape_writer.output.len();
-                    offsets.push(offset);
-                    if offset > 0xFFFF {
-                        has_wide_offsets = true;
-                    }
-
-                    shape_writer.write_u8(0b0001_0000)?;
-                    let mut bits = shape_writer.bits();
-                    for shape_record in &glyph.shape_records {
-                        Self::write_shape_record(shape_record, &mut bits, &mut shape_context)?;
-                    }
-                    // End shape record.
-                    bits.write_ubits(6, 0)?;
+            let (shape_buf, shape_positions) =
+                Self::write_glyph_shape_table(&font.glyphs, self.version)?;
+            let preserved = font.offsets.as_ref().and_then(|offsets| {
+                preserved_shape_table(font, offsets, &shape_buf, &shape_positions)
+                    .map(|shape_table| (offsets.clone(), font.flags, shape_table))
+            });
+            let (offsets, flags, shape_buf) = match preserved {
+                Some(preserved) => preserved,
+                // Missing or stale offsets are recalculated, only keeping how the name was written.
+                None => {
+                    let (mut offsets, flags) =
+                        font_offsets_for_shapes(font, shape_buf.len(), &shape_positions);
+                    offsets.name_has_null =
+                        matches!(&font.offsets, Some(offsets) if offsets.name_has_null);
+                    (offsets, flags, shape_buf)
                 }
-            }
+            };
+            // The reader decides between 8 and 16-bit codes by this flag, not by `IS_ANSI`.
+            let has_wide_offsets = flags.contains(FontFlag::HAS_WIDE_OFFSETS);
+            let has_wide_codes = flags.contains(FontFlag::HAS_WIDE_CODES);
 
             let mut writer = Writer::new(&mut buf, self.version);
             writer.write_character_id(font.id)?;
-            writer.write_u8(font.flags.bits())?;
+            writer.write_u8(flags.bits())?;
             writer.write_language(font.language)?;
-            writer.write_u8(font.name.len() as u8)?;
-            writer.output.write_all(font.name.as_bytes())?;
+            if offsets.name_has_null {
+                writer.write_u8(font.name.len() as u8 + 1)?;
+                writer.write_str(font.name)?;
+            } else {
+                writer.write_str_with_len(font.name)?;
+            }
             writer.write_u16(num_glyphs as u16)?;
 
+            // OffsetTable
             // If there are no glyphs, then the following tables are omitted.
-            if num_glyphs > 0 {
-                // OffsetTable
-                for offset in offsets {
-                    if has_wide_offsets {
-                        writer.write_u32(offset as u32)?;
-                    } else {
-                        writer.write_u16(offset as u16)?;
-                    }
+            for &offset in &offsets.glyph_offsets {
+                if has_wide_offsets {
+                    writer.write_u32(offset)?;
+                } else {
+                    writer.write_u16(offset as u16)?;
                 }
+            }
 
-                // CodeTableOffset
-                let code_table_offset =
-                    (num_glyphs + 1) * if has_wide_offsets { 4 } else { 2 } + shape_buf.len();
+            // CodeTableOffset
+            // This is optional when there are no glyphs.
+            if let Some(code_table_offset) = offsets.code_table_offset {
                 if has_wide_offsets {
-                    writer.write_u32(code_table_offset as u32)?;
+                    writer.write_u32(code_table_offset)?;
                 } else {
                     writer.write_u16(code_table_offset as u16)?;
                 }
+            }
 
+            if num_glyphs > 0 {
                 writer.output.write_all(&shape_buf)?;
 
                 // CodeTable
@@ -2109,11 +2208,13 @@ impl<W: Write> Writer<W> {
                     writer.write_i16(glyph.advance)?;
                 }
                 for glyph in &font.glyphs {
-                    writer.write_rectangle(
+                    // Flash writes zero bounds with 1-bit rectangles, match it so that fonts round-trip.
+                    writer.write_rectangle_with_min_bits(
                         glyph
                             .bounds
                             .as_ref()
                             .ok_or_else(|| Error::invalid_data("glyph.bounds cannot be None"))?,
+                        1,
                     )?;
                 }
                 writer.write_u16(layout.kerning.len() as u16)?;
@@ -2145,7 +2246,7 @@ impl<W: Write> Writer<W> {
                 | if font.is_italic { 0b10 } else { 0 }
                 | if font.is_bold { 0b1 } else { 0 },
         )?;
-        self.write_string(font.name)?;
+        self.write_str(font.name)?;
         if let Some(data) = font.data {
             self.output.write_all(data)?;
         }
@@ -2169,8 +2270,7 @@ impl<W: Write> Writer<W> {
         self.write_u16(font_info.id)?;
 
         // SWF19 has ANSI and Shift-JIS backwards?
-        self.write_u8(font_info.name.len() as u8)?;
-        self.output.write_all(font_info.name.as_bytes())?;
+        self.write_str_with_len(font_info.name)?;
 
         let mut flags = font_info.flags;
         flags.set(FontInfoFlag::HAS_WIDE_CODES, use_wide_codes);
@@ -2318,7 +2418,7 @@ impl<W: Write> Writer<W> {
 
             // TODO(Herschel): Check SWF version.
             if let Some(class) = edit_text.font_class_name {
-                writer.write_string(class)?;
+                writer.write_str(class)?;
             }
 
             // TODO(Herschel): Height only exists iff HasFontId, maybe for HasFontClass too?
@@ -2342,9 +2442,9 @@ impl<W: Write> Writer<W> {
                 writer.write_i16(layout.leading.get() as i16)?;
             }
 
-            writer.write_string(edit_text.variable_name)?;
+            writer.write_str(edit_text.variable_name)?;
             if let Some(text) = edit_text.initial_text {
-                writer.write_string(text)?;
+                writer.write_str(text)?;
             }
         }
 
@@ -2365,7 +2465,7 @@ impl<W: Write> Writer<W> {
     }
 
     fn write_product_info(&mut self, product_info: &ProductInfo) -> Result<()> {
-        self.write_tag_header(TagCode::ProductInfo, 26)?;
+        self.write_tag_header(TagCode::ProductInfo, ProductInfo::LEN as u32)?;
         self.write_u32(product_info.product_id)?;
         self.write_u32(product_info.edition)?;
         self.write_u8(product_info.major_version)?;
@@ -2384,7 +2484,7 @@ impl<W: Write> Writer<W> {
     fn write_name_character(&mut self, name_character: &NameCharacter) -> Result<()> {
         self.write_tag_header(TagCode::NameCharacter, 3 + name_character.name.len() as u32)?;
         self.write_character_id(name_character.id)?;
-        self.write_string(name_character.name)?;
+        self.write_str(name_character.name)?;
         Ok(())
     }
 
@@ -2395,7 +2495,7 @@ impl<W: Write> Writer<W> {
     fn write_tag_code_and_length(&mut self, tag_code: u16, length: u32) -> Result<()> {
         // TODO: Test for tag code/length overflow.
         let mut tag_code_and_length = tag_code << 6;
-        if length < 0b111111 {
+        if !needs_long_header(length as usize) {
             tag_code_and_length |= length as u16;
             self.write_u16(tag_code_and_length)?;
         } else {
@@ -2417,6 +2517,170 @@ impl<W: Write> Writer<W> {
     }
 }
 
+/// Returns the number of bytes `tag` will take up when written at the given SWF version,
+/// including its tag header.
+///
+/// The tag is serialized into a sink that only counts bytes, so no output buffer is allocated for
+/// the tag itself.
+pub fn tag_size(tag: &Tag, swf_version: u8) -> Result<usize> {
+    let mut counter = ByteCounter::default();
+    Writer::new(&mut counter, swf_version).write_tag(tag)?;
+    Ok(counter.len)
+}
+
+/// Returns whether `tag` will be written with a long (6 byte) tag header at the given SWF version.
+pub fn tag_has_long_header(tag: &Tag, swf_version: u8) -> Result<bool> {
+    // A short header holds at most 62 bytes of data, and a long header at least 63.
+    Ok(tag_size(tag, swf_version)? > header_size(0) + 0b111110)
+}
+
+/// Returns the size of the tag header that will be written for a tag with `len` bytes of data.
+pub fn header_size(len: usize) -> usize {
+    if needs_long_header(len) {
+        6
+    } else {
+        2
+    }
+}
+
+/// Returns whether a tag with `len` bytes of data needs a long tag header.
+/// Lengths of 0x3F or more don't fit in the 6 length bits of a short tag header.
+pub fn needs_long_header(len: usize) -> bool {
+    len >= 0b111111
+}
+
+/// A `Write` sink that discards its input and counts the bytes written to it.
+#[derive(Default)]
+struct ByteCounter {
+    len: usize,
+}
+
+impl Write for ByteCounter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Calculates the offset table of a DefineFont2 or DefineFont3 tag from its glyphs, ignoring any
+/// offsets preserved in `font.offsets`.
+///
+/// Returns the offsets along with the font flags that they must be written with: wide offsets are
+/// only used when the glyph shapes don't fit in 16-bit offsets, and wide codes are used when the
+/// font requests them or a glyph code doesn't fit in a byte. This allows building a font from just its
+/// glyph shapes, codes and advances.
+pub fn calculate_font_offsets(font: &Font, swf_version: u8) -> Result<(FontOffsets, FontFlag)> {
+    let (shape_buf, shape_positions) =
+        Writer::<Vec<u8>>::write_glyph_shape_table(&font.glyphs, swf_version)?;
+    Ok(font_offsets_for_shapes(
+        font,
+        shape_buf.len(),
+        &shape_positions,
+    ))
+}
+
+/// The glyph shape table to write with the offsets preserved in `offsets`, or `None` if they no
+/// longer describe the glyphs of `font`, whose shapes are at `shape_positions` in `shape_table`.
+///
+/// Offsets that were read from a SWF go stale when the glyphs are edited, in which case they must
+/// be recalculated rather than written back. Otherwise they are kept even if they aren't the ones
+/// `calculate_font_offsets` would give: a glyph without shape records may have been read from a
+/// slot of zero or one bytes rather than from an empty shape, and is written back the same way.
+fn preserved_shape_table(
+    font: &Font,
+    offsets: &FontOffsets,
+    shape_table: &[u8],
+    shape_positions: &[usize],
+) -> Option<Vec<u8>> {
+    let num_glyphs = font.glyphs.len();
+    if offsets.glyph_offsets.len() != num_glyphs {
+        return None;
+    }
+    if !font.flags.contains(FontFlag::HAS_WIDE_CODES)
+        && font.glyphs.iter().any(|glyph| glyph.code > 0xFF)
+    {
+        return None;
+    }
+    // The CodeTableOffset of a font without glyphs is written back as it was read.
+    if num_glyphs == 0 {
+        return Some(Vec::new());
+    }
+
+    let has_wide_offsets = font.flags.contains(FontFlag::HAS_WIDE_OFFSETS);
+    let offset_table_len = (num_glyphs + 1) * if has_wide_offsets { 4 } else { 2 };
+    let max_offset = if has_wide_offsets {
+        u32::MAX as usize
+    } else {
+        0xFFFF
+    };
+    let code_table_offset = offsets.code_table_offset? as usize;
+    if code_table_offset > max_offset {
+        return None;
+    }
+
+    // The reader expects each glyph shape to start where the previous one ends.
+    let mut preserved = Vec::with_capacity(code_table_offset.saturating_sub(offset_table_len));
+    for (i, glyph) in font.glyphs.iter().enumerate() {
+        let start = offsets.glyph_offsets[i] as usize;
+        if start != offset_table_len + preserved.len() {
+            return None;
+        }
+        let end = offsets
+            .glyph_offsets
+            .get(i + 1)
+            .map_or(code_table_offset, |&offset| offset as usize);
+        let slot_len = end.checked_sub(start)?;
+        let shape_end = shape_positions
+            .get(i + 1)
+            .copied()
+            .unwrap_or(shape_table.len());
+        let shape = &shape_table[shape_positions[i]..shape_end];
+        if slot_len != shape.len() && !(glyph.shape_records.is_empty() && slot_len <= 1) {
+            return None;
+        }
+        preserved.extend_from_slice(&shape[..slot_len]);
+    }
+    Some(preserved)
+}
+
+fn font_offsets_for_shapes(
+    font: &Font,
+    shape_table_len: usize,
+    shape_positions: &[usize],
+) -> (FontOffsets, FontFlag) {
+    let num_glyphs = font.glyphs.len();
+    // The offsets are relative to the start of the offset table, which includes the CodeTableOffset.
+    let has_wide_offsets = (num_glyphs + 1) * 2 + shape_table_len > 0xFFFF;
+    let offset_table_len = (num_glyphs + 1) * if has_wide_offsets { 4 } else { 2 };
+    let has_wide_codes = font.flags.contains(FontFlag::HAS_WIDE_CODES)
+        || font.glyphs.iter().any(|glyph| glyph.code > 0xFF);
+
+    let mut flags = font.flags;
+    flags.set(FontFlag::HAS_WIDE_OFFSETS, has_wide_offsets);
+    flags.set(FontFlag::HAS_WIDE_CODES, has_wide_codes);
+
+    let offsets = FontOffsets {
+        glyph_offsets: shape_positions
+            .iter()
+            .map(|position| (offset_table_len + position) as u32)
+            .collect(),
+        // If there are no glyphs, then the following tables are omitted.
+        code_table_offset: if num_glyphs > 0 {
+            Some((offset_table_len + shape_table_len) as u32)
+        } else {
+            None
+        },
+        name_has_null: false,
+    };
+    (offsets, flags)
+}
+
 fn count_ubits(mut n: u32) -> u32 {
     let mut num_bits = 0;
     while n > 0 {
@@ -2497,6 +2761,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn validate_script_versions() {
+        let avm1 = || Tag::FileAttributes(FileAttributes::avm1(true));
+        let avm2 = || Tag::FileAttributes(FileAttributes::avm2(false));
+        let abc = || {
+            Tag::DoAbc(DoAbc {
+                name: "".into(),
+                is_lazy_initialize: false,
+                data: &[],
+            })
+        };
+        let sprite = || {
+            Tag::DefineSprite(Sprite {
+                id: 1,
+                num_frames: 1,
+                tags: vec![Tag::DoAction(&[]), Tag::ShowFrame],
+            })
+        };
+
+        assert!(validate_script_version(8, &[avm1(), Tag::DoAction(&[])]).is_ok());
+        assert!(validate_script_version(6, &[Tag::DoAction(&[])]).is_ok());
+        assert!(validate_script_version(9, &[avm2(), abc()]).is_ok());
+        assert!(validate_script_version(8, &[avm2(), abc()]).is_err());
+        assert!(validate_script_version(9, &[abc()]).is_err());
+        assert!(validate_script_version(9, &[avm2(), abc(), sprite()]).is_err());
+        // Only the first tag is used as the attributes
+        assert!(validate_script_version(9, &[Tag::ShowFrame, avm2()]).is_ok());
+
+        let attributes = FileAttributes::avm2(true);
+        assert!(attributes.is_action_script_3());
+        assert!(attributes.use_network_sandbox());
+        assert!(!attributes.use_gpu());
+        assert!(!FileAttributes::avm1(false).is_action_script_3());
+    }
+
     #[test]
     fn write_fixed8() {
         let mut buf = Vec::new();
@@ -2644,7 +2943,7 @@ mod tests {
             {
                 // TODO: What if I use a cursor instead of buf ?
                 let mut writer = Writer::new(&mut buf, 1);
-                writer.write_string("Hello!".into()).unwrap();
+                writer.write_str("Hello!".into()).unwrap();
             }
             assert_eq!(buf, "Hello!\0".bytes().collect::<Vec<_>>());
         }
@@ -2654,7 +2953,7 @@ mod tests {
             {
                 // TODO: What if I use a cursor instead of buf ?
                 let mut writer = Writer::new(&mut buf, 1);
-                writer.write_string("😀😂!🐼".into()).unwrap();
+                writer.write_str("😀😂!🐼".into()).unwrap();
             }
             assert_eq!(buf, "😀😂!🐼\0".bytes().collect::<Vec<_>>());
         }
@@ -2749,6 +3048,632 @@ mod tests {
         }
     }
 
+    #[test]
+    fn morph_shape_end_edges_offset() {
+        use crate::read::tests::read_tag_bytes_from_file;
+        use crate::read::Reader;
+
+        for (path, tag_code) in [
+            (
+                "tests/swfs/DefineMorphShape-MX.swf",
+                TagCode::DefineMorphShape,
+            ),
+            (
+                "tests/swfs/DefineMorphShape2.swf",
+                TagCode::DefineMorphShape2,
+            ),
+            (
+                "tests/swfs/DefineMorphShape2-GradientFlags.swf",
+                TagCode::DefineMorphShape2,
+            ),
+        ] {
+            // The offsets written by the Flash IDE are right, so strict mode accepts them and none is kept.
+            let tag_bytes = read_tag_bytes_from_file(path, tag_code);
+            let mut morph_shape = match Reader::new_strict(&tag_bytes, 10).read_tag().unwrap() {
+                Tag::DefineMorphShape(morph_shape) => morph_shape,
+                tag => panic!("Expected DefineMorphShape, got {:?}", tag),
+            };
+            assert_eq!(morph_shape.end_edges_offset, None, "{}", path);
+            let mut written = Vec::new();
+            Writer::new(&mut written, 10)
+                .write_tag(&Tag::DefineMorphShape(morph_shape.clone()))
+                .unwrap();
+            assert_eq!(written, tag_bytes, "{}", path);
+
+            // A wrong offset is an error in strict mode, and kept in lenient mode so it is written back as it was.
+            morph_shape.end_edges_offset = Some(1);
+            let mut crafted = Vec::new();
+            Writer::new(&mut crafted, 10)
+                .write_tag(&Tag::DefineMorphShape(morph_shape.clone()))
+                .unwrap();
+            assert_eq!(crafted.len(), tag_bytes.len(), "{}", path);
+            assert!(
+                Reader::new_strict(&crafted, 10).read_tag().is_err(),
+                "{}",
+                path
+            );
+            let lenient = Reader::new(&crafted, 10).read_tag().unwrap();
+            assert_eq!(lenient, Tag::DefineMorphShape(morph_shape), "{}", path);
+            let mut rewritten = Vec::new();
+            Writer::new(&mut rewritten, 10).write_tag(&lenient).unwrap();
+            assert_eq!(rewritten, crafted, "{}", path);
+        }
+    }
+
+    #[test]
+    fn tag_sizes() {
+        for (swf_version, tag, expected_tag_bytes) in test_data::tag_tests() {
+            assert_eq!(
+                tag_size(&tag, swf_version).unwrap(),
+                expected_tag_bytes.len(),
+                "Wrong size for tag:\n{:?}",
+                tag
+            );
+            let long_header = expected_tag_bytes[0] & 0b111111 == 0b111111;
+            assert_eq!(
+                tag_has_long_header(&tag, swf_version).unwrap(),
+                long_header,
+                "Wrong header size for tag:\n{:?}",
+                tag
+            );
+        }
+        assert_eq!(header_size(0), 2);
+        assert_eq!(header_size(62), 2);
+        assert_eq!(header_size(63), 6);
+    }
+
+    /// Write `tag`, read it back and check that the structures match.
+    fn assert_tag_round_trips(tag: &Tag, swf_version: u8) {
+        let mut written_tag_bytes = Vec::new();
+        Writer::new(&mut written_tag_bytes, swf_version)
+            .write_tag(tag)
+            .unwrap();
+        let read_tag = crate::read::Reader::new(&written_tag_bytes, swf_version)
+            .read_tag()
+            .unwrap();
+        assert_eq!(&read_tag, tag);
+    }
+
+    #[test]
+    fn write_sound_stream_head_missing_latency_seek() {
+        // An MP3 stream head without the latency seek field keeps it missing.
+        let mut tag_bytes = ((TagCode::SoundStreamHead2 as u16) << 6 | 4)
+            .to_le_bytes()
+            .to_vec();
+        tag_bytes.extend_from_slice(&[0b0010_1111, 0b0010_1111, 0x40, 0x02]);
+        let tag = crate::read::Reader::new(&tag_bytes, 10).read_tag().unwrap();
+        let mut written_tag_bytes = Vec::new();
+        Writer::new(&mut written_tag_bytes, 10)
+            .write_tag(&tag)
+            .unwrap();
+        assert_eq!(written_tag_bytes, tag_bytes);
+
+        // The field is written when present.
+        let mut tag_bytes = ((TagCode::SoundStreamHead2 as u16) << 6 | 6)
+            .to_le_bytes()
+            .to_vec();
+        tag_bytes.extend_from_slice(&[0b0010_1111, 0b0010_1111, 0x40, 0x02, 0xff, 0xff]);
+        let tag = crate::read::Reader::new(&tag_bytes, 10).read_tag().unwrap();
+        let mut written_tag_bytes = Vec::new();
+        Writer::new(&mut written_tag_bytes, 10)
+            .write_tag(&tag)
+            .unwrap();
+        assert_eq!(written_tag_bytes, tag_bytes);
+    }
+
+    #[test]
+    fn write_font_tags_preserve_raw_bytes() {
+        use crate::read::tests::read_tag_bytes_from_file;
+        let assert_bytes_round_trip = |tag_bytes: &[u8]| {
+            let tag = crate::read::Reader::new(tag_bytes, 10).read_tag().unwrap();
+            let mut written_tag_bytes = Vec::new();
+            Writer::new(&mut written_tag_bytes, 10)
+                .write_tag(&tag)
+                .unwrap();
+            assert_eq!(written_tag_bytes, tag_bytes, "{:?}", tag);
+        };
+
+        // Includes zones with an empty mask.
+        assert_bytes_round_trip(&read_tag_bytes_from_file(
+            "tests/swfs/uncompressed.swf",
+            TagCode::DefineFontAlignZones,
+        ));
+        assert_bytes_round_trip(&read_tag_bytes_from_file(
+            "tests/swfs/DefineFont4.swf",
+            TagCode::DefineFontAlignZones,
+        ));
+
+        // Unusual constant bytes in a zone.
+        let mut tag_bytes = ((TagCode::DefineFontAlignZones as u16) << 6 | 13)
+            .to_le_bytes()
+            .to_vec();
+        tag_bytes.extend_from_slice(&[1, 0, 0b10_000000]);
+        tag_bytes.extend_from_slice(&[3, 0x2a, 0x33, 0, 0, 0, 0, 0xce, 0x42, 0b1000_0010]);
+        assert_bytes_round_trip(&tag_bytes);
+
+        // Reserved CSMTextSettings flag bits and trailing byte.
+        let mut tag_bytes = ((TagCode::CsmTextSettings as u16) << 6 | 12)
+            .to_le_bytes()
+            .to_vec();
+        tag_bytes.extend_from_slice(&[2, 0, 0b0101_0111]);
+        tag_bytes.extend_from_slice(&1.0f32.to_le_bytes());
+        tag_bytes.extend_from_slice(&2.0f32.to_le_bytes());
+        tag_bytes.push(7);
+        assert_bytes_round_trip(&tag_bytes);
+    }
+
+    #[test]
+    fn write_scaling_grid_and_labels_round_trip() {
+        use crate::read::tests::read_tag_bytes_from_file;
+        for (path, tag_code) in [
+            (
+                "tests/swfs/DefineScalingGrid.swf",
+                TagCode::DefineScalingGrid,
+            ),
+            (
+                "tests/swfs/DefineSceneAndFrameLabelData.swf",
+                TagCode::DefineSceneAndFrameLabelData,
+            ),
+        ] {
+            let tag_bytes = read_tag_bytes_from_file(path, tag_code);
+            let tag = crate::read::Reader::new(&tag_bytes, 10).read_tag().unwrap();
+            assert_tag_round_trips(&tag, 10);
+        }
+    }
+
+    #[test]
+    fn write_scene_and_frame_label_data_large_counts() {
+        // Counts and frame numbers above 127 take multiple bytes when encoded.
+        let labels: Vec<String> = (0..300).map(|i| format!("label{}", i)).collect();
+        let scenes = (0..200)
+            .map(|i| FrameLabelData {
+                frame_num: i * 1000,
+                label: "scene".into(),
+            })
+            .collect();
+        let frame_labels = labels
+            .iter()
+            .enumerate()
+            .map(|(i, label)| FrameLabelData {
+                frame_num: u32::MAX - i as u32,
+                label: label.as_str().into(),
+            })
+            .collect();
+        let tag = Tag::DefineSceneAndFrameLabelData(DefineSceneAndFrameLabelData {
+            scenes,
+            frame_labels,
+        });
+        assert_tag_round_trips(&tag, 10);
+    }
+
+    #[test]
+    fn write_metadata_round_trip() {
+        // Metadata exported by the Flash IDE is multi-kilobyte XMP.
+        let mut xml = String::from(
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">"#,
+        );
+        for i in 0..100 {
+            xml.push_str(&format!(
+                r#"<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/"><xmp:CreatorTool>Adobe Flash Professional CS6 - build {}</xmp:CreatorTool></rdf:Description>"#,
+                i
+            ));
+        }
+        xml.push_str("</rdf:RDF></x:xmpmeta>");
+        assert!(xml.len() > 4096);
+
+        let tag = Tag::Metadata(xml.as_str().into());
+        assert_tag_round_trips(&tag, 10);
+        let mut written_tag_bytes = Vec::new();
+        Writer::new(&mut written_tag_bytes, 10)
+            .write_tag(&tag)
+            .unwrap();
+        assert_eq!(written_tag_bytes.len(), 6 + xml.len() + 1);
+    }
+
+    #[test]
+    fn write_define_font_2_round_trip() {
+        use crate::read::tests::read_tag_bytes_from_file;
+        for (path, tag_code) in [
+            ("tests/swfs/DefineFont2-CS6.swf", TagCode::DefineFont2),
+            ("tests/swfs/DefineFont3-CS6.swf", TagCode::DefineFont3),
+            (
+                "tests/swfs/DefineFont3-DeviceText.swf",
+                TagCode::DefineFont3,
+            ),
+        ] {
+            let tag_bytes = read_tag_bytes_from_file(path, tag_code);
+            let tag = crate::read::Reader::new(&tag_bytes, 10).read_tag().unwrap();
+            let mut written_tag_bytes = Vec::new();
+            Writer::new(&mut written_tag_bytes, 10)
+                .write_tag(&tag)
+                .unwrap();
+            assert_eq!(
+                written_tag_bytes, tag_bytes,
+                "Error round-tripping {}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn write_define_font_2_calculated_offsets() {
+        use crate::read::tests::read_tag_bytes_from_file;
+        let tag_bytes =
+            read_tag_bytes_from_file("tests/swfs/DefineFont3-CS6.swf", TagCode::DefineFont3);
+        let font = match crate::read::Reader::new(&tag_bytes, 10).read_tag() {
+            Ok(Tag::DefineFont2(font)) => *font,
+            result => panic!("Expected DefineFont3, got {:?}", result),
+        };
+        let preserved_offsets = font.offsets.clone().unwrap();
+        let (offsets, flags) = calculate_font_offsets(&font, 10).unwrap();
+        assert_eq!(offsets.glyph_offsets, preserved_offsets.glyph_offsets);
+        assert_eq!(
+            offsets.code_table_offset,
+            preserved_offsets.code_table_offset
+        );
+        assert_eq!(flags, font.flags);
+
+        // Without preserved offsets the writer calculates them, the result should read back the same.
+        let font = Font {
+            offsets: None,
+            ..font
+        };
+        let mut written_tag_bytes = Vec::new();
+        Writer::new(&mut written_tag_bytes, 10)
+            .write_tag(&Tag::DefineFont2(Box::new(font.clone())))
+            .unwrap();
+        let read_font = match crate::read::Reader::new(&written_tag_bytes, 10).read_tag() {
+            Ok(Tag::DefineFont2(font)) => *font,
+            result => panic!("Expected DefineFont3, got {:?}", result),
+        };
+        assert_eq!(read_font.glyphs, font.glyphs);
+        assert_eq!(
+            read_font.offsets.unwrap().glyph_offsets,
+            offsets.glyph_offsets
+        );
+    }
+
+    #[test]
+    fn write_define_font_2_stale_offsets() {
+        use crate::read::tests::read_tag_bytes_from_file;
+        let tag_bytes =
+            read_tag_bytes_from_file("tests/swfs/DefineFont3-CS6.swf", TagCode::DefineFont3);
+        let font = match crate::read::Reader::new(&tag_bytes, 10).read_tag() {
+            Ok(Tag::DefineFont2(font)) => *font,
+            result => panic!("Expected DefineFont3, got {:?}", result),
+        };
+        assert!(font.glyphs.len() > 1);
+
+        let mut removed_glyph = font.clone();
+        removed_glyph.glyphs.pop();
+        let mut edited_shape = font.clone();
+        edited_shape.glyphs[0].shape_records.clear();
+
+        // The preserved offsets no longer match the glyphs, so they must be recalculated.
+        for edited_font in [removed_glyph, edited_shape] {
+            let (offsets, flags) = calculate_font_offsets(&edited_font, 10).unwrap();
+            let mut written_tag_bytes = Vec::new();
+            Writer::new(&mut written_tag_bytes, 10)
+                .write_tag(&Tag::DefineFont2(Box::new(edited_font.clone())))
+                .unwrap();
+            let read_font = match crate::read::Reader::new(&written_tag_bytes, 10).read_tag() {
+                Ok(Tag::DefineFont2(font)) => *font,
+                result => panic!("Expected DefineFont3, got {:?}", result),
+            };
+            assert_eq!(read_font.glyphs, edited_font.glyphs);
+            assert_eq!(read_font.flags, flags);
+            let read_offsets = read_font.offsets.unwrap();
+            assert_eq!(read_offsets.glyph_offsets, offsets.glyph_offsets);
+            assert_eq!(read_offsets.code_table_offset, offsets.code_table_offset);
+        }
+    }
+
+    #[test]
+    fn write_define_font_2_non_standard_offsets() {
+        let empty_glyph = |code| Glyph {
+            shape_records: vec![],
+            code,
+            advance: 0,
+            bounds: None,
+        };
+        let font = Font {
+            version: 3,
+            id: 1,
+            name: crate::string::SwfStr::from_bytes(b"Test"),
+            language: Language::Unknown,
+            layout: None,
+            glyphs: vec![empty_glyph(65), empty_glyph(66), empty_glyph(67)],
+            flags: FontFlag::empty(),
+            // The offset table is 8 bytes. The first glyph has an empty slot and the second only
+            // has its NumBits, rather than the 2 bytes of an empty shape the third glyph has.
+            offsets: Some(FontOffsets {
+                glyph_offsets: vec![8, 8, 9],
+                code_table_offset: Some(11),
+                name_has_null: false,
+            }),
+        };
+        let mut written_tag_bytes = Vec::new();
+        Writer::new(&mut written_tag_bytes, 10)
+            .write_tag(&Tag::DefineFont2(Box::new(font.clone())))
+            .unwrap();
+        let read_font = match crate::read::Reader::new(&written_tag_bytes, 10).read_tag() {
+            Ok(Tag::DefineFont2(font)) => *font,
+            result => panic!("Expected DefineFont3, got {:?}", result),
+        };
+        assert_eq!(read_font, font);
+
+        let mut rewritten_tag_bytes = Vec::new();
+        Writer::new(&mut rewritten_tag_bytes, 10)
+            .write_tag(&Tag::DefineFont2(Box::new(read_font)))
+            .unwrap();
+        assert_eq!(rewritten_tag_bytes, written_tag_bytes);
+    }
+
+    #[test]
+    fn write_define_font_2_codes_follow_wide_codes_flag() {
+        // Neither `IS_ANSI` nor `HAS_WIDE_CODES`, the reader expects 8-bit codes.
+        let font = Font {
+            version: 3,
+            id: 1,
+            name: crate::string::SwfStr::from_bytes(b"Test"),
+            language: Language::Unknown,
+            layout: None,
+            glyphs: vec![Glyph {
+                shape_records: vec![],
+                code: 65,
+                advance: 0,
+                bounds: None,
+            }],
+            flags: FontFlag::empty(),
+            offsets: None,
+        };
+        let mut written_tag_bytes = Vec::new();
+        Writer::new(&mut written_tag_bytes, 10)
+            .write_tag(&Tag::DefineFont2(Box::new(font.clone())))
+            .unwrap();
+        let read_font = match crate::read::Reader::new(&written_tag_bytes, 10).read_tag() {
+            Ok(Tag::DefineFont2(font)) => *font,
+            result => panic!("Expected DefineFont3, got {:?}", result),
+        };
+        assert!(!read_font.flags.contains(FontFlag::HAS_WIDE_CODES));
+        assert_eq!(read_font.glyphs, font.glyphs);
+    }
+
+    #[test]
+    fn write_zero_glyph_bounds_with_one_bit() {
+        // Flash writes zero glyph bounds as 1-bit rectangles (5 + 4 bits), rather than 0-bit (5 bits).
+        let mut buf = Vec::new();
+        Writer::new(&mut buf, 10)
+            .write_rectangle_with_min_bits(&Rectangle::default(), 1)
+            .unwrap();
+        assert_eq!(buf, [0b0000_1000, 0]);
+
+        let mut buf = Vec::new();
+        Writer::new(&mut buf, 10)
+            .write_rectangle(&Rectangle::default())
+            .unwrap();
+        assert_eq!(buf, [0]);
+    }
+
+    #[test]
+    fn write_clip_actions_versions() {
+        let clip_actions = [
+            ClipAction {
+                events: ClipEventFlag::PRESS | ClipEventFlag::ENTER_FRAME,
+                key_code: None,
+                action_data: &[0],
+            },
+            ClipAction {
+                events: ClipEventFlag::KEY_PRESS,
+                key_code: Some(99),
+                action_data: &[0],
+            },
+        ];
+        for version in [5, 6, 7] {
+            let mut buf = Vec::new();
+            Writer::new(&mut buf, version)
+                .write_clip_actions(&clip_actions)
+                .unwrap();
+            let read_actions = crate::read::Reader::new(&buf, version)
+                .read_clip_actions()
+                .unwrap();
+            if version >= 6 {
+                assert_eq!(read_actions, clip_actions, "SWF{}", version);
+            } else {
+                // SWFv5 can't encode PRESS or KEY_PRESS, so the key press action is dropped.
+                assert_eq!(
+                    read_actions,
+                    [ClipAction {
+                        events: ClipEventFlag::ENTER_FRAME,
+                        key_code: None,
+                        action_data: &[0],
+                    }]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn write_gradient_flags() {
+        let write = |gradient: &Gradient| {
+            let mut buf = Vec::new();
+            Writer::new(&mut buf, 10)
+                .write_gradient_flags(gradient)
+                .map(|_| buf)
+        };
+        let mut gradient = Gradient {
+            matrix: Matrix::IDENTITY,
+            spread: GradientSpread::Pad,
+            interpolation: GradientInterpolation::Rgb,
+            records: vec![
+                GradientRecord {
+                    ratio: 0,
+                    color: Color::from_rgba(0xffff0000),
+                };
+                2
+            ],
+            raw_flags: Some(0b11_10_0010),
+        };
+        // Reserved modes are preserved.
+        assert_eq!(write(&gradient).unwrap(), [0b11_10_0010]);
+
+        // Stale raw flags are ignored.
+        gradient.spread = GradientSpread::Reflect;
+        assert_eq!(write(&gradient).unwrap(), [0b01_00_0010]);
+
+        gradient.records = vec![gradient.records[0].clone(); 16];
+        assert!(write(&gradient).is_err());
+    }
+
+    #[test]
+    fn write_filters_round_trip() {
+        const RED: Color = Color::from_rgba(0xffff0000);
+        const BLUE: Color = Color::from_rgba(0x800000ff);
+        let colors = vec![
+            GradientRecord {
+                ratio: 0,
+                color: RED,
+            },
+            GradientRecord {
+                ratio: 255,
+                color: BLUE,
+            },
+        ];
+        let filters = [
+            Filter::DropShadowFilter(Box::new(DropShadowFilter {
+                color: RED,
+                blur_x: Fixed16::from_f64(4.0),
+                blur_y: Fixed16::from_f64(8.5),
+                angle: Fixed16::from_f64(0.75),
+                distance: Fixed16::from_f64(-3.0),
+                strength: Fixed8::from_f64(1.5),
+                is_inner: true,
+                is_knockout: false,
+                num_passes: 3,
+            })),
+            Filter::BlurFilter(Box::new(BlurFilter {
+                blur_x: Fixed16::from_f64(2.0),
+                blur_y: Fixed16::from_f64(255.0),
+                num_passes: 15,
+            })),
+            Filter::GlowFilter(Box::new(GlowFilter {
+                color: BLUE,
+                blur_x: Fixed16::from_f64(6.0),
+                blur_y: Fixed16::from_f64(6.0),
+                strength: Fixed8::from_f64(2.0),
+                is_inner: false,
+                is_knockout: true,
+                num_passes: 1,
+            })),
+            Filter::BevelFilter(Box::new(BevelFilter {
+                shadow_color: RED,
+                highlight_color: BLUE,
+                blur_x: Fixed16::from_f64(5.0),
+                blur_y: Fixed16::from_f64(5.0),
+                angle: Fixed16::from_f64(0.5),
+                distance: Fixed16::from_f64(4.0),
+                strength: Fixed8::from_f64(1.0),
+                is_inner: true,
+                is_knockout: true,
+                is_on_top: true,
+                num_passes: 2,
+            })),
+            Filter::GradientGlowFilter(Box::new(GradientGlowFilter {
+                colors: colors.clone(),
+                blur_x: Fixed16::from_f64(3.0),
+                blur_y: Fixed16::from_f64(3.0),
+                angle: Fixed16::from_f64(1.0),
+                distance: Fixed16::from_f64(2.0),
+                strength: Fixed8::from_f64(0.5),
+                is_inner: false,
+                is_knockout: false,
+                is_on_top: true,
+                num_passes: 1,
+            })),
+            Filter::ConvolutionFilter(Box::new(ConvolutionFilter {
+                num_matrix_rows: 2,
+                num_matrix_cols: 3,
+                matrix: (0..6).map(|i| Fixed16::from_f64(i as f64 - 2.5)).collect(),
+                divisor: Fixed16::from_f64(6.0),
+                bias: Fixed16::from_f64(-1.0),
+                default_color: BLUE,
+                is_clamped: true,
+                is_preserve_alpha: false,
+            })),
+            // Largest possible convolution matrix.
+            Filter::ConvolutionFilter(Box::new(ConvolutionFilter {
+                num_matrix_rows: 255,
+                num_matrix_cols: 255,
+                matrix: (0..255 * 255)
+                    .map(|i| Fixed16::from_f64(f64::from(i % 7)))
+                    .collect(),
+                divisor: Fixed16::ONE,
+                bias: Fixed16::ZERO,
+                default_color: RED,
+                is_clamped: false,
+                is_preserve_alpha: true,
+            })),
+            Filter::ColorMatrixFilter(Box::new(ColorMatrixFilter {
+                matrix: [
+                    Fixed16::ONE,
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::from_f64(16.0),
+                    Fixed16::ZERO,
+                    Fixed16::from_f64(0.5),
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::from_f64(-1.0),
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::ZERO,
+                    Fixed16::ONE,
+                    Fixed16::ZERO,
+                ],
+            })),
+            Filter::GradientBevelFilter(Box::new(GradientBevelFilter {
+                colors,
+                blur_x: Fixed16::from_f64(1.0),
+                blur_y: Fixed16::from_f64(2.0),
+                angle: Fixed16::from_f64(-0.5),
+                distance: Fixed16::from_f64(1.0),
+                strength: Fixed8::from_f64(3.0),
+                is_inner: true,
+                is_knockout: false,
+                is_on_top: false,
+                num_passes: 4,
+            })),
+        ];
+        for filter in &filters {
+            let mut buf = Vec::new();
+            Writer::new(&mut buf, 10).write_filter(filter).unwrap();
+            let read_filter = crate::read::Reader::new_strict(&buf, 10)
+                .read_filter()
+                .unwrap();
+            assert_eq!(&read_filter, filter);
+        }
+
+        // The matrix must match the declared size.
+        let mut buf = Vec::new();
+        let filter = Filter::ConvolutionFilter(Box::new(ConvolutionFilter {
+            num_matrix_rows: 2,
+            num_matrix_cols: 2,
+            matrix: vec![Fixed16::ONE],
+            divisor: Fixed16::ONE,
+            bias: Fixed16::ZERO,
+            default_color: RED,
+            is_clamped: false,
+            is_preserve_alpha: false,
+        }));
+        assert!(Writer::new(&mut buf, 10).write_filter(&filter).is_err());
+    }
+
     #[test]
     fn write_tag_to_buf_list() {
         {
@@ -2787,4 +3712,21 @@ mod tests {
             assert_eq!(buf, expected);
         }
     }
+
+    #[test]
+    fn write_do_action_header_boundary() {
+        // Tags of 63 bytes and over need the long header, which has a u32 length
+        for len in [0, 62, 63, 64, 0xffff, 0x10000, 2 * 1024 * 1024] {
+            let actions = vec![0; len];
+            let mut buf = Vec::new();
+            Writer::new(&mut buf, 1)
+                .write_tag(&Tag::DoAction(&actions))
+                .unwrap();
+            let header_len = if len < 63 { 2 } else { 6 };
+            assert_eq!(buf.len(), header_len + len, "len = {}", len);
+
+            let mut reader = crate::read::Reader::new(&buf, 1);
+            assert_eq!(reader.read_tag_code_and_length().unwrap(), (12, len));
+        }
+    }
 }
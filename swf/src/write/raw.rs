@@ -0,0 +1,149 @@
+//! Writing tags that break the SWF format on purpose.
+//!
+//! This is for testing how readers (including this crate's) handle malformed files: tags whose
+//! declared length doesn't match their body, truncated bodies, invalid enum values and so on.
+//! Nothing here checks that what is written is valid, use `write_swf` for anything else.
+//!
+//! # Example
+//! ```
+//! use swf::write::raw;
+//! use swf::*;
+//!
+//! // A SetBackgroundColor tag that declares one byte more than it has, swallowing the first
+//! // byte of the End tag after it.
+//! let mut tag_data = Vec::new();
+//! raw::write_tag_raw(&mut tag_data, TagCode::SetBackgroundColor as u16, &[255, 0, 0], Some(4)).unwrap();
+//! raw::write_tag(&mut tag_data, &Tag::End, 8).unwrap();
+//!
+//! let header = Header {
+//!     compression: Compression::None,
+//!     version: 8,
+//!     stage_size: Rectangle {
+//!         x_min: Twips::from_pixels(0.0),
+//!         x_max: Twips::from_pixels(400.0),
+//!         y_min: Twips::from_pixels(0.0),
+//!         y_max: Twips::from_pixels(400.0),
+//!     },
+//!     frame_rate: Fixed8::from_f32(60.0),
+//!     num_frames: 1,
+//! };
+//! let mut output = Vec::new();
+//! raw::write_swf_raw(&header, &tag_data, &mut output).unwrap();
+//! ```
+use super::{write_swf_with, Writer};
+use crate::error::Result;
+use crate::types::{Header, Tag};
+use std::io::Write;
+
+/// Writes a tag header declaring `declared_len` bytes of data, whatever actually follows it.
+///
+/// A long header is written when `declared_len` doesn't fit in a short one. Only the low 10 bits
+/// of `tag_code` are written.
+pub fn write_tag_header<W: Write>(output: W, tag_code: u16, declared_len: u32) -> Result<()> {
+    // The version only matters for tag bodies.
+    Writer::new(output, 0).write_tag_code_and_length(tag_code & 0x3ff, declared_len)
+}
+
+/// Writes a tag with `body` as its data, declaring `declared_len_override` bytes if it is given
+/// rather than the length of `body`.
+///
+/// The body is written as is, so it can be truncated or hold invalid values.
+pub fn write_tag_raw<W: Write>(
+    mut output: W,
+    tag_code: u16,
+    body: &[u8],
+    declared_len_override: Option<u32>,
+) -> Result<()> {
+    let declared_len = declared_len_override.unwrap_or(body.len() as u32);
+    write_tag_header(&mut output, tag_code, declared_len)?;
+    output.write_all(body)?;
+    Ok(())
+}
+
+/// Writes a well-formed tag, so that malformed tags can be written in between valid ones.
+pub fn write_tag<W: Write>(output: W, tag: &Tag, swf_version: u8) -> Result<()> {
+    Writer::new(output, swf_version).write_tag(tag)
+}
+
+/// Writes an SWF file with `tag_data` as its tags.
+///
+/// Unlike `write_swf`, no End tag is added after the tags.
+pub fn write_swf_raw<W: Write>(header: &Header, tag_data: &[u8], output: W) -> Result<()> {
+    write_swf_with(header, output, |writer| {
+        writer.output.write_all(tag_data)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::Reader;
+    use crate::{decompress_swf, Compression, Fixed8, Rectangle, TagCode, Twips};
+
+    fn header() -> Header {
+        Header {
+            compression: Compression::None,
+            version: 8,
+            stage_size: Rectangle {
+                x_min: Twips::from_pixels(0.0),
+                x_max: Twips::from_pixels(10.0),
+                y_min: Twips::from_pixels(0.0),
+                y_max: Twips::from_pixels(10.0),
+            },
+            frame_rate: Fixed8::from_f32(60.0),
+            num_frames: 1,
+        }
+    }
+
+    #[test]
+    fn write_declared_len() {
+        for (body_len, declared_len, long_header) in [
+            (3, None, false),
+            (3, Some(1), false),
+            (3, Some(100), true),
+            (100, Some(3), false),
+        ] {
+            let body = vec![0xaa; body_len];
+            let mut buf = Vec::new();
+            write_tag_raw(
+                &mut buf,
+                TagCode::SetBackgroundColor as u16,
+                &body,
+                declared_len,
+            )
+            .unwrap();
+            assert_eq!(buf.len(), body_len + if long_header { 6 } else { 2 });
+
+            let mut reader = Reader::new(&buf, 8);
+            let (tag_code, len) = reader.read_tag_code_and_length().unwrap();
+            assert_eq!(tag_code, TagCode::SetBackgroundColor as u16);
+            assert_eq!(len, declared_len.map_or(body_len, |len| len as usize));
+            assert_eq!(reader.get_ref(), &body[..]);
+        }
+    }
+
+    #[test]
+    fn write_swf_raw_tags() {
+        // The SetBackgroundColor tag is cut short, but declares what it has.
+        let mut tag_data = Vec::new();
+        write_tag(&mut tag_data, &Tag::ShowFrame, 8).unwrap();
+        write_tag_raw(
+            &mut tag_data,
+            TagCode::SetBackgroundColor as u16,
+            &[255],
+            None,
+        )
+        .unwrap();
+        write_tag(&mut tag_data, &Tag::End, 8).unwrap();
+        let mut output = Vec::new();
+        write_swf_raw(&header(), &tag_data, &mut output).unwrap();
+
+        let swf_buf = decompress_swf(&output[..]).unwrap();
+        let mut reader = Reader::new(&swf_buf.data, 8);
+        assert_eq!(reader.read_tag().unwrap(), Tag::ShowFrame);
+        reader.read_tag().unwrap_err();
+        assert_eq!(reader.read_tag().unwrap(), Tag::End);
+        assert!(reader.get_ref().is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+//! Tests that count allocations. They have their own test binary, as the counting allocator is global and would
+//! count the allocations of every other test too.
+#![cfg(feature = "std")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use swf::read::Reader;
+use swf::*;
+
+/// Counts the allocations made on the current thread, so a test can check that a path doesn't allocate.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // The counter may already be destroyed while a thread exits.
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+#[test]
+fn shape_records_lazy() {
+    const NUM_EDGES: usize = 50_000;
+    let shape = Shape {
+        version: 1,
+        id: 1,
+        shape_bounds: Default::default(),
+        edge_bounds: Default::default(),
+        has_fill_winding_rule: false,
+        has_non_scaling_strokes: true,
+        has_scaling_strokes: false,
+        styles: ShapeStyles {
+            fill_styles: vec![],
+            line_styles: vec![],
+        },
+        shape: (0..NUM_EDGES)
+            .map(|i| ShapeRecord::StraightEdge {
+                delta_x: Twips::new(1 + i as i32 % 100),
+                delta_y: Twips::new(-(i as i32 % 7)),
+            })
+            .collect(),
+    };
+    let mut swf = Vec::new();
+    write_swf(
+        &Header::default_with_swf_version(8),
+        &[Tag::DefineShape(shape.clone())],
+        &mut swf,
+    )
+    .unwrap();
+    let swf_buf = decompress_swf(&swf[..]).unwrap();
+    let mut reader = Reader::new(&swf_buf.data, 8);
+    reader.read_tag_code_and_length().unwrap();
+    let tag_data = reader.get_ref();
+
+    let mut reader = Reader::new(tag_data, 8);
+    let (header, context) = reader.read_define_shape_header(1).unwrap();
+    assert!(header.shape.is_empty());
+    let records = reader.shape_records(context);
+    let before = ALLOCATIONS.with(|count| count.get());
+    let edges = records.count_edges().unwrap();
+    let allocations = ALLOCATIONS.with(|count| count.get()) - before;
+    assert_eq!(edges, NUM_EDGES);
+    assert_eq!(allocations, 0);
+
+    // The eager path decodes the same records, and does allocate.
+    let mut reader = Reader::new(tag_data, 8);
+    let before = ALLOCATIONS.with(|count| count.get());
+    let eager = reader.read_define_shape(1).unwrap();
+    assert!(ALLOCATIONS.with(|count| count.get()) > before);
+    assert_eq!(eager, shape);
+}